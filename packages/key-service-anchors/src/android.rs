@@ -0,0 +1,227 @@
+//! Android Keystore [`KeyProtector`]: an AES-256-GCM key generated inside the keystore (TEE,
+//! or StrongBox when requested) encrypts the data key. The key material never reaches the
+//! app process.
+//!
+//! Calls go through JNI, so the protector needs the process [`JavaVM`]. Get it from
+//! `JNI_OnLoad` or `ndk-context`.
+
+use jni::objects::{JByteArray, JObject, JValue};
+use jni::{JNIEnv, JavaVM};
+use zeroize::Zeroizing;
+
+use crate::{AnchorError, KeyProtector};
+
+const KEYSTORE_PROVIDER: &str = "AndroidKeyStore";
+const TRANSFORMATION: &str = "AES/GCM/NoPadding";
+const BUILDER_CLASS: &str = "android/security/keystore/KeyGenParameterSpec$Builder";
+const BUILDER_SIG: &str = "Landroid/security/keystore/KeyGenParameterSpec$Builder;";
+/// `KeyProperties.PURPOSE_ENCRYPT | KeyProperties.PURPOSE_DECRYPT`.
+const PURPOSE_ENCRYPT_DECRYPT: i32 = 3;
+const ENCRYPT_MODE: i32 = 1;
+const DECRYPT_MODE: i32 = 2;
+const GCM_IV_LEN: usize = 12;
+const GCM_TAG_BITS: i32 = 128;
+
+pub struct AndroidKeystoreProtector {
+    vm: JavaVM,
+    alias: String,
+    strongbox: bool,
+}
+
+impl AndroidKeystoreProtector {
+    /// Uses (or on first use, generates) the keystore key named `alias`.
+    pub fn new(vm: JavaVM, alias: impl Into<String>) -> Self {
+        Self {
+            vm,
+            alias: alias.into(),
+            strongbox: false,
+        }
+    }
+
+    /// Generates the key in StrongBox (API 28+). Only affects a key that does not exist yet.
+    pub fn strongbox(mut self, strongbox: bool) -> Self {
+        self.strongbox = strongbox;
+        self
+    }
+
+    fn with_env<R>(
+        &self,
+        f: impl FnOnce(&mut JNIEnv) -> jni::errors::Result<R>,
+    ) -> Result<R, AnchorError> {
+        let mut env = self
+            .vm
+            .attach_current_thread()
+            .map_err(|e| AnchorError::Platform(format!("JNI attach: {e}")))?;
+        f(&mut env).map_err(|e| {
+            // A pending Java exception would poison every later JNI call on this thread.
+            if env.exception_check().unwrap_or(false) {
+                let _ = env.exception_describe();
+                let _ = env.exception_clear();
+            }
+            AnchorError::Platform(format!("Android Keystore: {e}"))
+        })
+    }
+
+    fn key<'local>(&self, env: &mut JNIEnv<'local>) -> jni::errors::Result<JObject<'local>> {
+        let provider = env.new_string(KEYSTORE_PROVIDER)?;
+        let keystore = env
+            .call_static_method(
+                "java/security/KeyStore",
+                "getInstance",
+                "(Ljava/lang/String;)Ljava/security/KeyStore;",
+                &[JValue::Object(&provider)],
+            )?
+            .l()?;
+        env.call_method(
+            &keystore,
+            "load",
+            "(Ljava/security/KeyStore$LoadStoreParameter;)V",
+            &[JValue::Object(&JObject::null())],
+        )?;
+        let alias = env.new_string(&self.alias)?;
+        let key = env
+            .call_method(
+                &keystore,
+                "getKey",
+                "(Ljava/lang/String;[C)Ljava/security/Key;",
+                &[JValue::Object(&alias), JValue::Object(&JObject::null())],
+            )?
+            .l()?;
+        if !key.is_null() {
+            return Ok(key);
+        }
+
+        let algorithm = env.new_string("AES")?;
+        let generator = env
+            .call_static_method(
+                "javax/crypto/KeyGenerator",
+                "getInstance",
+                "(Ljava/lang/String;Ljava/lang/String;)Ljavax/crypto/KeyGenerator;",
+                &[JValue::Object(&algorithm), JValue::Object(&provider)],
+            )?
+            .l()?;
+        let builder = env.new_object(
+            BUILDER_CLASS,
+            "(Ljava/lang/String;I)V",
+            &[JValue::Object(&alias), JValue::Int(PURPOSE_ENCRYPT_DECRYPT)],
+        )?;
+        let gcm = env.new_string("GCM")?;
+        let modes = env.new_object_array(1, "java/lang/String", &gcm)?;
+        env.call_method(
+            &builder,
+            "setBlockModes",
+            format!("([Ljava/lang/String;){BUILDER_SIG}"),
+            &[JValue::Object(&modes)],
+        )?;
+        let no_padding = env.new_string("NoPadding")?;
+        let paddings = env.new_object_array(1, "java/lang/String", &no_padding)?;
+        env.call_method(
+            &builder,
+            "setEncryptionPaddings",
+            format!("([Ljava/lang/String;){BUILDER_SIG}"),
+            &[JValue::Object(&paddings)],
+        )?;
+        env.call_method(
+            &builder,
+            "setKeySize",
+            format!("(I){BUILDER_SIG}"),
+            &[JValue::Int(256)],
+        )?;
+        if self.strongbox {
+            env.call_method(
+                &builder,
+                "setIsStrongBoxBacked",
+                format!("(Z){BUILDER_SIG}"),
+                &[JValue::Bool(1)],
+            )?;
+        }
+        let spec = env
+            .call_method(
+                &builder,
+                "build",
+                "()Landroid/security/keystore/KeyGenParameterSpec;",
+                &[],
+            )?
+            .l()?;
+        env.call_method(
+            &generator,
+            "init",
+            "(Ljava/security/spec/AlgorithmParameterSpec;)V",
+            &[JValue::Object(&spec)],
+        )?;
+        env.call_method(&generator, "generateKey", "()Ljavax/crypto/SecretKey;", &[])?
+            .l()
+    }
+
+    fn cipher<'local>(env: &mut JNIEnv<'local>) -> jni::errors::Result<JObject<'local>> {
+        let transformation = env.new_string(TRANSFORMATION)?;
+        env.call_static_method(
+            "javax/crypto/Cipher",
+            "getInstance",
+            "(Ljava/lang/String;)Ljavax/crypto/Cipher;",
+            &[JValue::Object(&transformation)],
+        )?
+        .l()
+    }
+}
+
+impl KeyProtector for AndroidKeystoreProtector {
+    /// Blob: `iv (12) || AES-GCM ct`. The keystore picks the IV; caller IVs are rejected by
+    /// default for keystore keys.
+    fn protect(&self, data_key: &[u8; 32]) -> Result<Vec<u8>, AnchorError> {
+        let (iv, ct) = self.with_env(|env| {
+            let key = self.key(env)?;
+            let cipher = Self::cipher(env)?;
+            env.call_method(
+                &cipher,
+                "init",
+                "(ILjava/security/Key;)V",
+                &[JValue::Int(ENCRYPT_MODE), JValue::Object(&key)],
+            )?;
+            let input = env.byte_array_from_slice(data_key)?;
+            let ct = JByteArray::from(
+                env.call_method(&cipher, "doFinal", "([B)[B", &[JValue::Object(&input)])?
+                    .l()?,
+            );
+            let iv = JByteArray::from(env.call_method(&cipher, "getIV", "()[B", &[])?.l()?);
+            Ok((env.convert_byte_array(&iv)?, env.convert_byte_array(&ct)?))
+        })?;
+        if iv.len() != GCM_IV_LEN {
+            return Err(AnchorError::Format("keystore IV length"));
+        }
+        Ok([iv, ct].concat())
+    }
+
+    fn unprotect(&self, protected: &[u8]) -> Result<Zeroizing<Vec<u8>>, AnchorError> {
+        if protected.len() < GCM_IV_LEN {
+            return Err(AnchorError::Format("truncated keystore blob"));
+        }
+        let (iv, ct) = protected.split_at(GCM_IV_LEN);
+        self.with_env(|env| {
+            let key = self.key(env)?;
+            let cipher = Self::cipher(env)?;
+            let iv = env.byte_array_from_slice(iv)?;
+            let spec = env.new_object(
+                "javax/crypto/spec/GCMParameterSpec",
+                "(I[B)V",
+                &[JValue::Int(GCM_TAG_BITS), JValue::Object(&iv)],
+            )?;
+            env.call_method(
+                &cipher,
+                "init",
+                "(ILjava/security/Key;Ljava/security/spec/AlgorithmParameterSpec;)V",
+                &[
+                    JValue::Int(DECRYPT_MODE),
+                    JValue::Object(&key),
+                    JValue::Object(&spec),
+                ],
+            )?;
+            let input = env.byte_array_from_slice(ct)?;
+            let plain = JByteArray::from(
+                env.call_method(&cipher, "doFinal", "([B)[B", &[JValue::Object(&input)])?
+                    .l()?,
+            );
+            Ok(Zeroizing::new(env.convert_byte_array(&plain)?))
+        })
+    }
+}
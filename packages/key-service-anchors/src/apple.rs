@@ -0,0 +1,267 @@
+#![allow(unsafe_code)]
+//! Apple [`KeyProtector`]: a P-256 key in the Secure Enclave (or the data-protection keychain
+//! on Macs without one) encrypts the data key with ECIES
+//! (`ECIESEncryptionCofactorVariableIVX963SHA256AESGCM`).
+//!
+//! Encryption uses only the public key. Decryption runs inside the enclave and needs the
+//! device to be unlocked. The key is found by its application tag, so the tag must stay
+//! stable across app versions.
+
+use core::ffi::c_void;
+
+use core_foundation::base::{CFAllocatorRef, CFOptionFlags, CFType, CFTypeRef, OSStatus, TCFType};
+use core_foundation::boolean::CFBoolean;
+use core_foundation::data::{CFData, CFDataRef};
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::error::{CFError, CFErrorRef};
+use core_foundation::number::CFNumber;
+use core_foundation::string::{CFString, CFStringRef};
+use zeroize::Zeroizing;
+
+use crate::{AnchorError, KeyProtector};
+
+type SecKeyRef = *mut c_void;
+type SecAccessControlRef = *mut c_void;
+
+const ERR_SEC_SUCCESS: OSStatus = 0;
+const ERR_SEC_ITEM_NOT_FOUND: OSStatus = -25300;
+/// `kSecAccessControlPrivateKeyUsage`.
+const ACCESS_CONTROL_PRIVATE_KEY_USAGE: CFOptionFlags = 1 << 30;
+
+#[link(name = "Security", kind = "framework")]
+extern "C" {
+    static kSecClass: CFStringRef;
+    static kSecClassKey: CFStringRef;
+    static kSecAttrKeyType: CFStringRef;
+    static kSecAttrKeyTypeECSECPrimeRandom: CFStringRef;
+    static kSecAttrKeySizeInBits: CFStringRef;
+    static kSecAttrKeyClass: CFStringRef;
+    static kSecAttrKeyClassPrivate: CFStringRef;
+    static kSecAttrTokenID: CFStringRef;
+    static kSecAttrTokenIDSecureEnclave: CFStringRef;
+    static kSecAttrIsPermanent: CFStringRef;
+    static kSecAttrApplicationTag: CFStringRef;
+    static kSecAttrAccessControl: CFStringRef;
+    static kSecAttrAccessibleWhenUnlockedThisDeviceOnly: CFStringRef;
+    static kSecPrivateKeyAttrs: CFStringRef;
+    static kSecReturnRef: CFStringRef;
+    static kSecUseDataProtectionKeychain: CFStringRef;
+    static kSecKeyAlgorithmECIESEncryptionCofactorVariableIVX963SHA256AESGCM: CFStringRef;
+
+    fn SecItemCopyMatching(query: CFDictionaryRef, result: *mut CFTypeRef) -> OSStatus;
+    fn SecKeyCreateRandomKey(parameters: CFDictionaryRef, error: *mut CFErrorRef) -> SecKeyRef;
+    fn SecKeyCopyPublicKey(key: SecKeyRef) -> SecKeyRef;
+    fn SecAccessControlCreateWithFlags(
+        allocator: CFAllocatorRef,
+        protection: CFTypeRef,
+        flags: CFOptionFlags,
+        error: *mut CFErrorRef,
+    ) -> SecAccessControlRef;
+    fn SecKeyCreateEncryptedData(
+        key: SecKeyRef,
+        algorithm: CFStringRef,
+        plaintext: CFDataRef,
+        error: *mut CFErrorRef,
+    ) -> CFDataRef;
+    fn SecKeyCreateDecryptedData(
+        key: SecKeyRef,
+        algorithm: CFStringRef,
+        ciphertext: CFDataRef,
+        error: *mut CFErrorRef,
+    ) -> CFDataRef;
+}
+
+/// Where the private key lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppleKeyLocation {
+    SecureEnclave,
+    /// Data-protection keychain, for Macs without a Secure Enclave.
+    Keychain,
+}
+
+pub struct SecureEnclaveProtector {
+    tag: Vec<u8>,
+    location: AppleKeyLocation,
+}
+
+impl SecureEnclaveProtector {
+    /// Uses (or on first use, creates) the key with application tag `tag`.
+    pub fn new(tag: impl Into<Vec<u8>>) -> Self {
+        Self {
+            tag: tag.into(),
+            location: AppleKeyLocation::SecureEnclave,
+        }
+    }
+
+    pub fn location(mut self, location: AppleKeyLocation) -> Self {
+        self.location = location;
+        self
+    }
+
+    fn key(&self) -> Result<CFType, AnchorError> {
+        let tag = CFData::from_buffer(&self.tag);
+        // SAFETY: the `kSec*` statics are immutable CFStrings exported by Security.framework.
+        let query = unsafe {
+            CFDictionary::from_CFType_pairs(&[
+                (cf_str(kSecClass), cf_str(kSecClassKey).as_CFType()),
+                (
+                    cf_str(kSecAttrKeyClass),
+                    cf_str(kSecAttrKeyClassPrivate).as_CFType(),
+                ),
+                (cf_str(kSecAttrApplicationTag), tag.as_CFType()),
+                (
+                    cf_str(kSecUseDataProtectionKeychain),
+                    CFBoolean::true_value().as_CFType(),
+                ),
+                (cf_str(kSecReturnRef), CFBoolean::true_value().as_CFType()),
+            ])
+        };
+        let mut found: CFTypeRef = core::ptr::null();
+        // SAFETY: `query` is a valid dictionary; on success `found` holds a +1 reference.
+        let status = unsafe { SecItemCopyMatching(query.as_concrete_TypeRef(), &mut found) };
+        match status {
+            ERR_SEC_SUCCESS if !found.is_null() => {
+                // SAFETY: ownership of the +1 reference moves into the wrapper.
+                Ok(unsafe { CFType::wrap_under_create_rule(found) })
+            }
+            ERR_SEC_ITEM_NOT_FOUND => self.create_key(tag),
+            status => Err(AnchorError::Platform(format!(
+                "SecItemCopyMatching failed: {status}"
+            ))),
+        }
+    }
+
+    fn create_key(&self, tag: CFData) -> Result<CFType, AnchorError> {
+        let mut error: CFErrorRef = core::ptr::null_mut();
+        let flags = match self.location {
+            AppleKeyLocation::SecureEnclave => ACCESS_CONTROL_PRIVATE_KEY_USAGE,
+            AppleKeyLocation::Keychain => 0,
+        };
+        // SAFETY: the protection class is a Security.framework constant; a null result comes
+        // with an owned `error`.
+        let access = unsafe {
+            SecAccessControlCreateWithFlags(
+                core::ptr::null(),
+                kSecAttrAccessibleWhenUnlockedThisDeviceOnly as CFTypeRef,
+                flags,
+                &mut error,
+            )
+        };
+        if access.is_null() {
+            return Err(cf_error("SecAccessControlCreateWithFlags", error));
+        }
+        // SAFETY: `access` is a +1 reference owned from here on.
+        let access = unsafe { CFType::wrap_under_create_rule(access as CFTypeRef) };
+
+        // SAFETY: as in `key`.
+        let params = unsafe {
+            let private = CFDictionary::from_CFType_pairs(&[
+                (
+                    cf_str(kSecAttrIsPermanent),
+                    CFBoolean::true_value().as_CFType(),
+                ),
+                (cf_str(kSecAttrApplicationTag), tag.as_CFType()),
+                (cf_str(kSecAttrAccessControl), access),
+            ]);
+            let mut pairs = vec![
+                (
+                    cf_str(kSecAttrKeyType),
+                    cf_str(kSecAttrKeyTypeECSECPrimeRandom).as_CFType(),
+                ),
+                (
+                    cf_str(kSecAttrKeySizeInBits),
+                    CFNumber::from(256).as_CFType(),
+                ),
+                (
+                    cf_str(kSecUseDataProtectionKeychain),
+                    CFBoolean::true_value().as_CFType(),
+                ),
+                (cf_str(kSecPrivateKeyAttrs), private.as_CFType()),
+            ];
+            if self.location == AppleKeyLocation::SecureEnclave {
+                pairs.push((
+                    cf_str(kSecAttrTokenID),
+                    cf_str(kSecAttrTokenIDSecureEnclave).as_CFType(),
+                ));
+            }
+            CFDictionary::from_CFType_pairs(&pairs)
+        };
+        // SAFETY: `params` is a valid dictionary; a null result comes with an owned `error`.
+        let key = unsafe { SecKeyCreateRandomKey(params.as_concrete_TypeRef(), &mut error) };
+        if key.is_null() {
+            return Err(cf_error("SecKeyCreateRandomKey", error));
+        }
+        // SAFETY: `key` is a +1 reference.
+        Ok(unsafe { CFType::wrap_under_create_rule(key as CFTypeRef) })
+    }
+}
+
+impl KeyProtector for SecureEnclaveProtector {
+    fn protect(&self, data_key: &[u8; 32]) -> Result<Vec<u8>, AnchorError> {
+        let private = self.key()?;
+        // SAFETY: `private` is a live SecKey; the copied public key is a +1 reference.
+        let public = unsafe { SecKeyCopyPublicKey(private.as_CFTypeRef() as SecKeyRef) };
+        if public.is_null() {
+            return Err(AnchorError::Platform(
+                "SecKeyCopyPublicKey failed".to_string(),
+            ));
+        }
+        // SAFETY: as above.
+        let public = unsafe { CFType::wrap_under_create_rule(public as CFTypeRef) };
+        let plaintext = CFData::from_buffer(data_key);
+        let mut error: CFErrorRef = core::ptr::null_mut();
+        // SAFETY: all arguments are live CF objects; a null result comes with an owned `error`.
+        let ct = unsafe {
+            SecKeyCreateEncryptedData(
+                public.as_CFTypeRef() as SecKeyRef,
+                kSecKeyAlgorithmECIESEncryptionCofactorVariableIVX963SHA256AESGCM,
+                plaintext.as_concrete_TypeRef(),
+                &mut error,
+            )
+        };
+        if ct.is_null() {
+            return Err(cf_error("SecKeyCreateEncryptedData", error));
+        }
+        // SAFETY: `ct` is a +1 CFData.
+        let ct = unsafe { CFData::wrap_under_create_rule(ct) };
+        Ok(ct.bytes().to_vec())
+    }
+
+    fn unprotect(&self, protected: &[u8]) -> Result<Zeroizing<Vec<u8>>, AnchorError> {
+        let private = self.key()?;
+        let ciphertext = CFData::from_buffer(protected);
+        let mut error: CFErrorRef = core::ptr::null_mut();
+        // SAFETY: all arguments are live CF objects; a null result comes with an owned `error`.
+        let plain = unsafe {
+            SecKeyCreateDecryptedData(
+                private.as_CFTypeRef() as SecKeyRef,
+                kSecKeyAlgorithmECIESEncryptionCofactorVariableIVX963SHA256AESGCM,
+                ciphertext.as_concrete_TypeRef(),
+                &mut error,
+            )
+        };
+        if plain.is_null() {
+            return Err(cf_error("SecKeyCreateDecryptedData", error));
+        }
+        // SAFETY: `plain` is a +1 CFData.
+        let plain = unsafe { CFData::wrap_under_create_rule(plain) };
+        Ok(Zeroizing::new(plain.bytes().to_vec()))
+    }
+}
+
+/// Borrows a Security.framework string constant.
+///
+/// # Safety
+/// `s` must be a valid, immortal CFString.
+unsafe fn cf_str(s: CFStringRef) -> CFString {
+    CFString::wrap_under_get_rule(s)
+}
+
+fn cf_error(call: &str, error: CFErrorRef) -> AnchorError {
+    if error.is_null() {
+        return AnchorError::Platform(format!("{call} failed"));
+    }
+    // SAFETY: Security.framework hands out a +1 CFError with the failure.
+    let error = unsafe { CFError::wrap_under_create_rule(error) };
+    AnchorError::Platform(format!("{call} failed: {}", error.description()))
+}
@@ -0,0 +1,149 @@
+#![deny(unsafe_code)]
+//! Platform [`DeviceAnchorAdapter`] backends for `mo-key-service-core`.
+//!
+//! Every backend follows one scheme. [`HardwareAnchor`] seals each value under a fresh
+//! AES-256-GCM data key. It stores that key protected by a non-exportable platform key
+//! through a [`KeyProtector`]. The platform key never leaves its hardware, so a copied
+//! storage directory cannot be unsealed on another device.
+//!
+//! Backends sit behind features: `tpm2` (TPM 2.0 on Linux and Windows), `secure-enclave`
+//! (Apple targets) and `android-keystore` (JNI).
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use mo_key_service_core::adapters::{DeviceAnchorAdapter, MaybeSend};
+use zeroize::Zeroizing;
+
+#[cfg(feature = "android-keystore")]
+pub mod android;
+#[cfg(all(feature = "secure-enclave", target_vendor = "apple"))]
+pub mod apple;
+#[cfg(feature = "tpm2")]
+pub mod tpm2;
+
+/// Version byte of a sealed anchor blob.
+pub const ANCHOR_BLOB_V1: u8 = 0x01;
+/// Prefix of the AEAD associated data; the label and caller AAD follow.
+pub const ANCHOR_SEAL_AAD_V1: &[u8] = b"mo-anchor-seal-v1";
+
+const DATA_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnchorError {
+    #[error("platform keystore error: {0}")]
+    Platform(String),
+    #[error("malformed anchor blob: {0}")]
+    Format(&'static str),
+    #[error("anchor blob failed authentication")]
+    Crypto,
+}
+
+/// Protects a 32-byte data key with a key that cannot leave the platform keystore.
+pub trait KeyProtector: MaybeSend {
+    fn protect(&self, data_key: &[u8; 32]) -> Result<Vec<u8>, AnchorError>;
+    fn unprotect(&self, protected: &[u8]) -> Result<Zeroizing<Vec<u8>>, AnchorError>;
+}
+
+/// [`DeviceAnchorAdapter`] over any [`KeyProtector`].
+///
+/// Blob layout: `0x01 || u16 len || protected data key || nonce (12) || AES-256-GCM ct`.
+/// The AEAD binds [`ANCHOR_SEAL_AAD_V1`], the label, and the caller's AAD.
+pub struct HardwareAnchor<P: KeyProtector> {
+    protector: P,
+}
+
+impl<P: KeyProtector> HardwareAnchor<P> {
+    pub fn new(protector: P) -> Self {
+        Self { protector }
+    }
+
+    pub fn protector(&self) -> &P {
+        &self.protector
+    }
+
+    pub fn into_inner(self) -> P {
+        self.protector
+    }
+}
+
+fn seal_aad(label: &str, aad: &[u8]) -> Result<Vec<u8>, AnchorError> {
+    let label_len = u16::try_from(label.len()).map_err(|_| AnchorError::Format("label length"))?;
+    let mut out = Vec::with_capacity(ANCHOR_SEAL_AAD_V1.len() + 2 + label.len() + aad.len());
+    out.extend_from_slice(ANCHOR_SEAL_AAD_V1);
+    out.extend_from_slice(&label_len.to_be_bytes());
+    out.extend_from_slice(label.as_bytes());
+    out.extend_from_slice(aad);
+    Ok(out)
+}
+
+fn random<const N: usize>() -> Result<[u8; N], AnchorError> {
+    let mut out = [0u8; N];
+    getrandom::getrandom(&mut out).map_err(|e| AnchorError::Platform(e.to_string()))?;
+    Ok(out)
+}
+
+impl<P: KeyProtector> DeviceAnchorAdapter for HardwareAnchor<P> {
+    type Error = AnchorError;
+
+    fn seal(&self, label: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AnchorError> {
+        let data_key = Zeroizing::new(random::<DATA_KEY_LEN>()?);
+        let nonce = random::<NONCE_LEN>()?;
+        let protected = self.protector.protect(&data_key)?;
+        let protected_len = u16::try_from(protected.len())
+            .map_err(|_| AnchorError::Format("protected key length"))?;
+        let cipher =
+            Aes256Gcm::new_from_slice(data_key.as_ref()).map_err(|_| AnchorError::Crypto)?;
+        let ct = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &seal_aad(label, aad)?,
+                },
+            )
+            .map_err(|_| AnchorError::Crypto)?;
+
+        let mut out = Vec::with_capacity(3 + protected.len() + NONCE_LEN + ct.len());
+        out.push(ANCHOR_BLOB_V1);
+        out.extend_from_slice(&protected_len.to_be_bytes());
+        out.extend_from_slice(&protected);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ct);
+        Ok(out)
+    }
+
+    fn unseal(&self, label: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AnchorError> {
+        let (&version, rest) = ciphertext
+            .split_first()
+            .ok_or(AnchorError::Format("empty blob"))?;
+        if version != ANCHOR_BLOB_V1 {
+            return Err(AnchorError::Format("unsupported blob version"));
+        }
+        if rest.len() < 2 {
+            return Err(AnchorError::Format("truncated blob"));
+        }
+        let protected_len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+        let rest = &rest[2..];
+        if rest.len() < protected_len + NONCE_LEN {
+            return Err(AnchorError::Format("truncated blob"));
+        }
+        let (protected, rest) = rest.split_at(protected_len);
+        let (nonce, ct) = rest.split_at(NONCE_LEN);
+
+        let data_key = self.protector.unprotect(protected)?;
+        if data_key.len() != DATA_KEY_LEN {
+            return Err(AnchorError::Format("data key length"));
+        }
+        let cipher = Aes256Gcm::new_from_slice(&data_key).map_err(|_| AnchorError::Crypto)?;
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ct,
+                    aad: &seal_aad(label, aad)?,
+                },
+            )
+            .map_err(|_| AnchorError::Crypto)
+    }
+}
@@ -0,0 +1,414 @@
+//! TPM 2.0 [`KeyProtector`]: the data key is sealed as a keyed-hash object under the storage
+//! root key, and only the TPM that created it can load and unseal it.
+//!
+//! Commands are marshalled by hand (TPM 2.0 Library Part 3) and sent through a
+//! [`Tpm2Transport`]. That is `/dev/tpmrm0` on Linux ([`LinuxTpmDevice`]) or TBS on Windows
+//! ([`WindowsTbs`]). Every command authorises with the empty password session, so the
+//! owner hierarchy must have an empty auth value (the default on both platforms).
+
+use zeroize::Zeroizing;
+
+use crate::{AnchorError, KeyProtector};
+use mo_key_service_core::adapters::MaybeSend;
+
+const TPM_ST_NO_SESSIONS: u16 = 0x8001;
+const TPM_ST_SESSIONS: u16 = 0x8002;
+const TPM_CC_CREATE_PRIMARY: u32 = 0x0000_0131;
+const TPM_CC_CREATE: u32 = 0x0000_0153;
+const TPM_CC_LOAD: u32 = 0x0000_0157;
+const TPM_CC_UNSEAL: u32 = 0x0000_015E;
+const TPM_CC_FLUSH_CONTEXT: u32 = 0x0000_0165;
+const TPM_RH_OWNER: u32 = 0x4000_0001;
+const TPM_RS_PW: u32 = 0x4000_0009;
+const TPM_ALG_AES: u16 = 0x0006;
+const TPM_ALG_KEYEDHASH: u16 = 0x0008;
+const TPM_ALG_SHA256: u16 = 0x000B;
+const TPM_ALG_NULL: u16 = 0x0010;
+const TPM_ALG_ECC: u16 = 0x0023;
+const TPM_ALG_CFB: u16 = 0x0043;
+const TPM_ECC_NIST_P256: u16 = 0x0003;
+/// fixedTPM | fixedParent | sensitiveDataOrigin | userWithAuth | noDA | restricted | decrypt:
+/// the TCG "storage root key" template.
+const SRK_ATTRIBUTES: u32 = 0x0003_0472;
+/// fixedTPM | fixedParent | userWithAuth | noDA: a sealed data object.
+const SEALED_ATTRIBUTES: u32 = 0x0000_0452;
+const RESPONSE_HEADER_LEN: usize = 10;
+
+/// Persistent handle Windows and most Linux provisioning tools use for the SRK.
+pub const TPM2_DEFAULT_SRK_HANDLE: u32 = 0x8100_0001;
+
+/// Sends one marshalled TPM 2.0 command and returns the full response.
+pub trait Tpm2Transport: MaybeSend {
+    fn submit(&self, command: &[u8]) -> Result<Vec<u8>, AnchorError>;
+}
+
+/// Parent key the sealed objects are created under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tpm2Parent {
+    /// Re-derive the ECC P-256 SRK under the owner hierarchy for each operation. Works with
+    /// no provisioning; the primary is flushed afterwards.
+    CreatePrimary,
+    /// An already provisioned persistent storage key, e.g. [`TPM2_DEFAULT_SRK_HANDLE`].
+    Persistent(u32),
+}
+
+pub struct Tpm2KeyProtector<T: Tpm2Transport> {
+    transport: T,
+    parent: Tpm2Parent,
+}
+
+impl<T: Tpm2Transport> Tpm2KeyProtector<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            parent: Tpm2Parent::CreatePrimary,
+        }
+    }
+
+    pub fn with_parent(mut self, parent: Tpm2Parent) -> Self {
+        self.parent = parent;
+        self
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    fn call(&self, code: u32, handles: &[u32], params: &[u8]) -> Result<Vec<u8>, AnchorError> {
+        let mut body = Vec::with_capacity(4 * handles.len() + 13 + params.len());
+        for handle in handles {
+            body.extend_from_slice(&handle.to_be_bytes());
+        }
+        // One password session with an empty nonce and empty auth value.
+        body.extend_from_slice(&9u32.to_be_bytes());
+        body.extend_from_slice(&TPM_RS_PW.to_be_bytes());
+        put_tpm2b(&mut body, &[]);
+        body.push(0);
+        put_tpm2b(&mut body, &[]);
+        body.extend_from_slice(params);
+        self.submit(TPM_ST_SESSIONS, code, &body)
+    }
+
+    fn submit(&self, tag: u16, code: u32, body: &[u8]) -> Result<Vec<u8>, AnchorError> {
+        let size = u32::try_from(RESPONSE_HEADER_LEN + body.len())
+            .map_err(|_| AnchorError::Format("TPM command size"))?;
+        let mut command = Vec::with_capacity(size as usize);
+        command.extend_from_slice(&tag.to_be_bytes());
+        command.extend_from_slice(&size.to_be_bytes());
+        command.extend_from_slice(&code.to_be_bytes());
+        command.extend_from_slice(body);
+
+        let response = self.transport.submit(&command)?;
+        let mut reader = Reader::new(&response);
+        let _tag = reader.u16()?;
+        let size = reader.u32()? as usize;
+        let rc = reader.u32()?;
+        if rc != 0 {
+            return Err(AnchorError::Platform(format!(
+                "TPM2 command {code:#x} failed with rc {rc:#x}"
+            )));
+        }
+        if size != response.len() {
+            return Err(AnchorError::Format("TPM response size"));
+        }
+        Ok(response)
+    }
+
+    fn flush(&self, handle: u32) -> Result<(), AnchorError> {
+        self.submit(
+            TPM_ST_NO_SESSIONS,
+            TPM_CC_FLUSH_CONTEXT,
+            &handle.to_be_bytes(),
+        )
+        .map(|_| ())
+    }
+
+    /// Runs `f` with the parent handle, flushing a transient primary afterwards.
+    fn with_parent_handle<R>(
+        &self,
+        f: impl FnOnce(u32) -> Result<R, AnchorError>,
+    ) -> Result<R, AnchorError> {
+        match self.parent {
+            Tpm2Parent::Persistent(handle) => f(handle),
+            Tpm2Parent::CreatePrimary => {
+                let response = self.call(TPM_CC_CREATE_PRIMARY, &[TPM_RH_OWNER], &srk_params())?;
+                let handle = Reader::new(&response[RESPONSE_HEADER_LEN..]).u32()?;
+                let result = f(handle);
+                let flushed = self.flush(handle);
+                let value = result?;
+                flushed?;
+                Ok(value)
+            }
+        }
+    }
+}
+
+impl<T: Tpm2Transport> KeyProtector for Tpm2KeyProtector<T> {
+    fn protect(&self, data_key: &[u8; 32]) -> Result<Vec<u8>, AnchorError> {
+        self.with_parent_handle(|parent| {
+            let mut sensitive = Zeroizing::new(Vec::with_capacity(36));
+            put_tpm2b(&mut sensitive, &[]);
+            put_tpm2b(&mut sensitive, data_key);
+            let mut params = Zeroizing::new(Vec::with_capacity(96));
+            put_tpm2b(&mut params, &sensitive);
+            put_tpm2b(&mut params, &sealed_public());
+            put_tpm2b(&mut params, &[]);
+            params.extend_from_slice(&0u32.to_be_bytes());
+
+            let response = self.call(TPM_CC_CREATE, &[parent], &params)?;
+            let mut reader = Reader::new(&response[RESPONSE_HEADER_LEN..]);
+            let _parameter_size = reader.u32()?;
+            let private = reader.tpm2b()?;
+            let public = reader.tpm2b()?;
+
+            // Blob: TPM2B_PRIVATE || TPM2B_PUBLIC, exactly as `TPM2_Load` takes them.
+            let mut out = Vec::with_capacity(4 + private.len() + public.len());
+            put_tpm2b(&mut out, private);
+            put_tpm2b(&mut out, public);
+            Ok(out)
+        })
+    }
+
+    fn unprotect(&self, protected: &[u8]) -> Result<Zeroizing<Vec<u8>>, AnchorError> {
+        let mut reader = Reader::new(protected);
+        let private = reader.tpm2b()?;
+        let public = reader.tpm2b()?;
+        if !reader.is_empty() {
+            return Err(AnchorError::Format("trailing bytes after TPM object"));
+        }
+        self.with_parent_handle(|parent| {
+            let mut params = Vec::with_capacity(protected.len());
+            put_tpm2b(&mut params, private);
+            put_tpm2b(&mut params, public);
+            let response = self.call(TPM_CC_LOAD, &[parent], &params)?;
+            let object = Reader::new(&response[RESPONSE_HEADER_LEN..]).u32()?;
+
+            let unsealed = self
+                .call(TPM_CC_UNSEAL, &[object], &[])
+                .and_then(|response| {
+                    let response = Zeroizing::new(response);
+                    let mut reader = Reader::new(&response[RESPONSE_HEADER_LEN..]);
+                    let _parameter_size = reader.u32()?;
+                    Ok(Zeroizing::new(reader.tpm2b()?.to_vec()))
+                });
+            let flushed = self.flush(object);
+            let data = unsealed?;
+            flushed?;
+            Ok(data)
+        })
+    }
+}
+
+fn put_tpm2b(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+/// `TPMT_PUBLIC` of the ECC P-256 storage root key (TCG EK/SRK template, empty unique).
+fn srk_params() -> Vec<u8> {
+    let mut public = Vec::with_capacity(32);
+    public.extend_from_slice(&TPM_ALG_ECC.to_be_bytes());
+    public.extend_from_slice(&TPM_ALG_SHA256.to_be_bytes());
+    public.extend_from_slice(&SRK_ATTRIBUTES.to_be_bytes());
+    put_tpm2b(&mut public, &[]);
+    public.extend_from_slice(&TPM_ALG_AES.to_be_bytes());
+    public.extend_from_slice(&128u16.to_be_bytes());
+    public.extend_from_slice(&TPM_ALG_CFB.to_be_bytes());
+    public.extend_from_slice(&TPM_ALG_NULL.to_be_bytes());
+    public.extend_from_slice(&TPM_ECC_NIST_P256.to_be_bytes());
+    public.extend_from_slice(&TPM_ALG_NULL.to_be_bytes());
+    put_tpm2b(&mut public, &[]);
+    put_tpm2b(&mut public, &[]);
+
+    let mut params = Vec::with_capacity(48);
+    // Empty TPM2B_SENSITIVE_CREATE: no auth value, no data.
+    put_tpm2b(&mut params, &[0, 0, 0, 0]);
+    put_tpm2b(&mut params, &public);
+    put_tpm2b(&mut params, &[]);
+    params.extend_from_slice(&0u32.to_be_bytes());
+    params
+}
+
+/// `TPMT_PUBLIC` of a sealed data object.
+fn sealed_public() -> Vec<u8> {
+    let mut public = Vec::with_capacity(14);
+    public.extend_from_slice(&TPM_ALG_KEYEDHASH.to_be_bytes());
+    public.extend_from_slice(&TPM_ALG_SHA256.to_be_bytes());
+    public.extend_from_slice(&SEALED_ATTRIBUTES.to_be_bytes());
+    put_tpm2b(&mut public, &[]);
+    public.extend_from_slice(&TPM_ALG_NULL.to_be_bytes());
+    put_tpm2b(&mut public, &[]);
+    public
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], AnchorError> {
+        if self.bytes.len() < len {
+            return Err(AnchorError::Format("truncated TPM structure"));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, AnchorError> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, AnchorError> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn tpm2b(&mut self) -> Result<&'a [u8], AnchorError> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+/// The kernel TPM resource manager, `/dev/tpmrm0`. Access usually needs the `tss` group.
+#[cfg(target_os = "linux")]
+pub struct LinuxTpmDevice {
+    file: std::sync::Mutex<std::fs::File>,
+}
+
+#[cfg(target_os = "linux")]
+impl LinuxTpmDevice {
+    pub const DEFAULT_PATH: &'static str = "/dev/tpmrm0";
+
+    pub fn open() -> Result<Self, AnchorError> {
+        Self::open_path(Self::DEFAULT_PATH)
+    }
+
+    pub fn open_path(path: &str) -> Result<Self, AnchorError> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| AnchorError::Platform(format!("{path}: {e}")))?;
+        Ok(Self {
+            file: std::sync::Mutex::new(file),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Tpm2Transport for LinuxTpmDevice {
+    fn submit(&self, command: &[u8]) -> Result<Vec<u8>, AnchorError> {
+        use std::io::{Read, Write};
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| AnchorError::Platform("TPM device lock poisoned".to_string()))?;
+        file.write_all(command)
+            .map_err(|e| AnchorError::Platform(format!("TPM write: {e}")))?;
+        // The driver returns a whole response per read.
+        let mut response = vec![0u8; 4096];
+        let len = file
+            .read(&mut response)
+            .map_err(|e| AnchorError::Platform(format!("TPM read: {e}")))?;
+        response.truncate(len);
+        Ok(response)
+    }
+}
+
+/// The Windows TPM Base Services context (TPM 2.0 only).
+#[cfg(windows)]
+pub struct WindowsTbs {
+    context: *mut core::ffi::c_void,
+}
+
+#[cfg(windows)]
+#[allow(unsafe_code)]
+mod tbs {
+    use super::{AnchorError, Tpm2Transport, WindowsTbs};
+    use windows_sys::Win32::System::TpmBaseServices::{
+        Tbsi_Context_Create, Tbsip_Context_Close, Tbsip_Submit_Command, TBS_COMMAND_LOCALITY_ZERO,
+        TBS_COMMAND_PRIORITY_NORMAL, TBS_CONTEXT_PARAMS, TBS_CONTEXT_PARAMS2,
+        TBS_CONTEXT_PARAMS2_0, TBS_CONTEXT_VERSION_TWO, TBS_SUCCESS,
+    };
+
+    /// `includeTpm20` bit of `TBS_CONTEXT_PARAMS2`.
+    const INCLUDE_TPM20: u32 = 1 << 2;
+
+    // SAFETY: a TBS context handle may be used from any thread; TBS serialises submissions.
+    unsafe impl Send for WindowsTbs {}
+    unsafe impl Sync for WindowsTbs {}
+
+    impl WindowsTbs {
+        pub fn open() -> Result<Self, AnchorError> {
+            let params = TBS_CONTEXT_PARAMS2 {
+                version: TBS_CONTEXT_VERSION_TWO,
+                Anonymous: TBS_CONTEXT_PARAMS2_0 {
+                    asUINT32: INCLUDE_TPM20,
+                },
+            };
+            let mut context = core::ptr::null_mut();
+            // SAFETY: `params` outlives the call and TBS reads it by its `version` field.
+            let rc = unsafe {
+                Tbsi_Context_Create(
+                    &params as *const TBS_CONTEXT_PARAMS2 as *const TBS_CONTEXT_PARAMS,
+                    &mut context,
+                )
+            };
+            if rc != TBS_SUCCESS {
+                return Err(AnchorError::Platform(format!(
+                    "Tbsi_Context_Create failed: {rc:#x}"
+                )));
+            }
+            Ok(Self { context })
+        }
+    }
+
+    impl Tpm2Transport for WindowsTbs {
+        fn submit(&self, command: &[u8]) -> Result<Vec<u8>, AnchorError> {
+            let command_len = u32::try_from(command.len())
+                .map_err(|_| AnchorError::Format("TPM command size"))?;
+            let mut response = vec![0u8; 4096];
+            let mut response_len = response.len() as u32;
+            // SAFETY: both buffers are valid for the lengths passed, and TBS writes at most
+            // `response_len` bytes.
+            let rc = unsafe {
+                Tbsip_Submit_Command(
+                    self.context,
+                    TBS_COMMAND_LOCALITY_ZERO,
+                    TBS_COMMAND_PRIORITY_NORMAL,
+                    command.as_ptr(),
+                    command_len,
+                    response.as_mut_ptr(),
+                    &mut response_len,
+                )
+            };
+            if rc != TBS_SUCCESS {
+                return Err(AnchorError::Platform(format!(
+                    "Tbsip_Submit_Command failed: {rc:#x}"
+                )));
+            }
+            response.truncate(response_len as usize);
+            Ok(response)
+        }
+    }
+
+    impl Drop for WindowsTbs {
+        fn drop(&mut self) {
+            // SAFETY: the context came from `Tbsi_Context_Create` and is closed once.
+            unsafe {
+                Tbsip_Context_Close(self.context);
+            }
+        }
+    }
+}
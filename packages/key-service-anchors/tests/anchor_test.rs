@@ -0,0 +1,129 @@
+use std::cell::Cell;
+
+use mo_key_service_anchors::{AnchorError, HardwareAnchor, KeyProtector, ANCHOR_BLOB_V1};
+use mo_key_service_core::adapters::DeviceAnchorAdapter;
+use mo_key_service_core::key_service::KeyService;
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::UserId;
+use zeroize::Zeroizing;
+
+/// Stand-in for a platform keystore: XORs the data key with a device secret.
+struct XorProtector {
+    device_secret: [u8; 32],
+    calls: Cell<u32>,
+}
+
+impl XorProtector {
+    fn new(byte: u8) -> Self {
+        Self {
+            device_secret: [byte; 32],
+            calls: Cell::new(0),
+        }
+    }
+}
+
+impl KeyProtector for XorProtector {
+    fn protect(&self, data_key: &[u8; 32]) -> Result<Vec<u8>, AnchorError> {
+        self.calls.set(self.calls.get() + 1);
+        Ok(data_key
+            .iter()
+            .zip(self.device_secret)
+            .map(|(a, b)| a ^ b)
+            .collect())
+    }
+
+    fn unprotect(&self, protected: &[u8]) -> Result<Zeroizing<Vec<u8>>, AnchorError> {
+        self.calls.set(self.calls.get() + 1);
+        Ok(Zeroizing::new(
+            protected
+                .iter()
+                .zip(self.device_secret)
+                .map(|(a, b)| a ^ b)
+                .collect(),
+        ))
+    }
+}
+
+fn service(storage: &MemoryStorage) -> KeyService<MemoryStorage, VirtualClock, SeededEntropy> {
+    KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(1),
+        test_config(),
+    )
+}
+
+#[test]
+fn seal_round_trips_and_binds_label_and_aad() {
+    let anchor = HardwareAnchor::new(XorProtector::new(0x5a));
+    let sealed = anchor.seal("label", b"aad", b"secret").expect("seal");
+    assert_eq!(sealed[0], ANCHOR_BLOB_V1);
+    assert_eq!(
+        anchor.unseal("label", b"aad", &sealed).expect("unseal"),
+        b"secret"
+    );
+    assert_ne!(
+        anchor.seal("label", b"aad", b"secret").expect("seal"),
+        sealed
+    );
+
+    assert!(matches!(
+        anchor.unseal("other", b"aad", &sealed),
+        Err(AnchorError::Crypto)
+    ));
+    assert!(matches!(
+        anchor.unseal("label", b"other", &sealed),
+        Err(AnchorError::Crypto)
+    ));
+}
+
+#[test]
+fn tampered_or_foreign_blobs_are_rejected() {
+    let anchor = HardwareAnchor::new(XorProtector::new(0x5a));
+    let sealed = anchor.seal("label", b"aad", b"secret").expect("seal");
+
+    let mut tampered = sealed.clone();
+    *tampered.last_mut().expect("tag") ^= 1;
+    assert!(matches!(
+        anchor.unseal("label", b"aad", &tampered),
+        Err(AnchorError::Crypto)
+    ));
+
+    let mut versioned = sealed.clone();
+    versioned[0] = 0x02;
+    assert!(matches!(
+        anchor.unseal("label", b"aad", &versioned),
+        Err(AnchorError::Format(_))
+    ));
+    assert!(matches!(
+        anchor.unseal("label", b"aad", &sealed[..20]),
+        Err(AnchorError::Format(_))
+    ));
+
+    // Another device's keystore recovers a different data key.
+    let other_device = HardwareAnchor::new(XorProtector::new(0xa5));
+    assert!(matches!(
+        other_device.unseal("label", b"aad", &sealed),
+        Err(AnchorError::Crypto)
+    ));
+}
+
+#[test]
+fn key_service_unlocks_with_hardware_anchor() {
+    let storage = MemoryStorage::new();
+    let mut ks = service(&storage);
+    ks.set_device_anchor(HardwareAnchor::new(XorProtector::new(0x5a)));
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    ks.store_app_master_key(&session_id, &[1u8; 32])
+        .expect("store");
+    ks.lock(&session_id).expect("lock");
+    ks.unlock_passphrase(b"pass").expect("unlock with anchor");
+
+    let mut moved = service(&storage);
+    moved.set_device_anchor(HardwareAnchor::new(XorProtector::new(0xa5)));
+    assert!(moved.unlock_passphrase(b"pass").is_err());
+}
@@ -0,0 +1,177 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use mo_key_service_anchors::tpm2::{Tpm2KeyProtector, Tpm2Parent, Tpm2Transport};
+use mo_key_service_anchors::{AnchorError, HardwareAnchor, KeyProtector};
+use mo_key_service_core::adapters::DeviceAnchorAdapter;
+
+const PRIMARY: u32 = 0x8000_0000;
+const SRK: u32 = 0x8100_0001;
+
+/// Minimal TPM: checks command framing, "seals" by storing the sensitive data in the private
+/// blob, and tracks which transient handles are loaded.
+#[derive(Default)]
+struct FakeTpm {
+    loaded: RefCell<HashMap<u32, Vec<u8>>>,
+    next_handle: RefCell<u32>,
+    commands: RefCell<Vec<u32>>,
+}
+
+fn be16(b: &[u8], at: usize) -> usize {
+    u16::from_be_bytes([b[at], b[at + 1]]) as usize
+}
+
+fn be32(b: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+fn tpm2b(b: &[u8], at: usize) -> (&[u8], usize) {
+    let len = be16(b, at);
+    (&b[at + 2..at + 2 + len], at + 2 + len)
+}
+
+fn response(handle: Option<u32>, params: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    if let Some(handle) = handle {
+        body.extend_from_slice(&handle.to_be_bytes());
+    }
+    body.extend_from_slice(&(params.len() as u32).to_be_bytes());
+    body.extend_from_slice(params);
+    let mut out = vec![0x80, 0x02];
+    out.extend_from_slice(&(10 + body.len() as u32).to_be_bytes());
+    out.extend_from_slice(&0u32.to_be_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+fn tpm2b_bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = (data.len() as u16).to_be_bytes().to_vec();
+    out.extend_from_slice(data);
+    out
+}
+
+impl FakeTpm {
+    fn parent_ok(&self, handle: u32) -> bool {
+        handle == SRK || self.loaded.borrow().contains_key(&handle)
+    }
+
+    fn allocate(&self, data: Vec<u8>) -> u32 {
+        let mut next = self.next_handle.borrow_mut();
+        let handle = PRIMARY + *next;
+        *next += 1;
+        self.loaded.borrow_mut().insert(handle, data);
+        handle
+    }
+}
+
+impl Tpm2Transport for FakeTpm {
+    fn submit(&self, cmd: &[u8]) -> Result<Vec<u8>, AnchorError> {
+        assert_eq!(be32(cmd, 2) as usize, cmd.len(), "command size");
+        let code = be32(cmd, 6);
+        self.commands.borrow_mut().push(code);
+        if code == 0x165 {
+            assert_eq!(be16(cmd, 0), 0x8001);
+            let removed = self.loaded.borrow_mut().remove(&be32(cmd, 10));
+            assert!(removed.is_some(), "flushed an unknown handle");
+            let mut out = vec![0x80, 0x01, 0, 0, 0, 10];
+            out.extend_from_slice(&0u32.to_be_bytes());
+            return Ok(out);
+        }
+        assert_eq!(be16(cmd, 0), 0x8002);
+        let handle = be32(cmd, 10);
+        // Authorization area: one empty password session.
+        assert_eq!(be32(cmd, 14), 9);
+        assert_eq!(be32(cmd, 18), 0x4000_0009);
+        let params = 14 + 4 + 9;
+        let rc_error = |rc: u32| {
+            let mut out = vec![0x80, 0x01, 0, 0, 0, 10];
+            out.extend_from_slice(&rc.to_be_bytes());
+            Ok(out)
+        };
+        match code {
+            0x131 => {
+                assert_eq!(handle, 0x4000_0001);
+                let (_sensitive, at) = tpm2b(cmd, params);
+                let (public, _) = tpm2b(cmd, at);
+                assert_eq!(be16(public, 0), 0x0023, "ECC SRK template");
+                let primary = self.allocate(Vec::new());
+                Ok(response(Some(primary), &[]))
+            }
+            0x153 => {
+                if !self.parent_ok(handle) {
+                    return rc_error(0x18b);
+                }
+                let (sensitive, at) = tpm2b(cmd, params);
+                let (_auth, data_at) = tpm2b(sensitive, 0);
+                let (data, _) = tpm2b(sensitive, data_at);
+                let (public, _) = tpm2b(cmd, at);
+                assert_eq!(be16(public, 0), 0x0008, "keyed-hash object");
+                let mut out = tpm2b_bytes(data);
+                out.extend_from_slice(&tpm2b_bytes(public));
+                Ok(response(None, &out))
+            }
+            0x157 => {
+                if !self.parent_ok(handle) {
+                    return rc_error(0x18b);
+                }
+                let (private, _) = tpm2b(cmd, params);
+                let object = self.allocate(private.to_vec());
+                Ok(response(Some(object), &tpm2b_bytes(&[0u8; 34])))
+            }
+            0x15E => {
+                let data = self.loaded.borrow().get(&handle).cloned();
+                match data {
+                    Some(data) => Ok(response(None, &tpm2b_bytes(&data))),
+                    None => rc_error(0x18b),
+                }
+            }
+            other => panic!("unexpected command {other:#x}"),
+        }
+    }
+}
+
+#[test]
+fn seals_under_a_transient_primary_and_flushes_it() {
+    let protector = Tpm2KeyProtector::new(FakeTpm::default());
+    let protected = protector.protect(&[9u8; 32]).expect("protect");
+    assert_eq!(
+        *protector.unprotect(&protected).expect("unprotect"),
+        [9u8; 32]
+    );
+
+    let tpm = protector.into_inner();
+    assert!(tpm.loaded.borrow().is_empty(), "no transient handles leak");
+    assert_eq!(
+        *tpm.commands.borrow(),
+        [0x131, 0x153, 0x165, 0x131, 0x157, 0x15E, 0x165, 0x165]
+    );
+}
+
+#[test]
+fn persistent_parent_skips_create_primary() {
+    let protector =
+        Tpm2KeyProtector::new(FakeTpm::default()).with_parent(Tpm2Parent::Persistent(SRK));
+    let anchor = HardwareAnchor::new(protector);
+    let sealed = anchor.seal("label", b"aad", b"secret").expect("seal");
+    assert_eq!(
+        anchor.unseal("label", b"aad", &sealed).expect("unseal"),
+        b"secret"
+    );
+
+    let tpm = anchor.into_inner().into_inner();
+    assert!(!tpm.commands.borrow().contains(&0x131));
+}
+
+#[test]
+fn tpm_errors_and_malformed_blobs_surface() {
+    let protector =
+        Tpm2KeyProtector::new(FakeTpm::default()).with_parent(Tpm2Parent::Persistent(0x8100_0002));
+    assert!(matches!(
+        protector.protect(&[1u8; 32]),
+        Err(AnchorError::Platform(msg)) if msg.contains("0x18b")
+    ));
+    assert!(matches!(
+        protector.unprotect(&[0, 4, 1]),
+        Err(AnchorError::Format(_))
+    ));
+}
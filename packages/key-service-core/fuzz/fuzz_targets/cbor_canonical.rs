@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mo_key_service_core::cbor::{
+    decode_canonical_value, decode_cbor_value, encode_canonical_value, CborLimits, CborStrictness,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let limits = CborLimits::default();
+    // Anything the canonical decoder accepts is already in the form the encoder writes.
+    if let Ok(value) = decode_canonical_value(data, &limits) {
+        let encoded = encode_canonical_value(&value).expect("re-encode accepted value");
+        assert_eq!(encoded, data);
+    }
+    let _ = decode_cbor_value(data, &limits, CborStrictness::Lenient);
+});
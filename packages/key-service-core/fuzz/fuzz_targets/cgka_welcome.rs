@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mo_key_service_core::formats::decode_cgka_welcome_v1;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(welcome) = decode_cgka_welcome_v1(data) {
+        let _ = welcome.to_be_signed_bytes();
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mo_key_service_core::ciphersuite::{pack_hybrid_signature, unpack_hybrid_signature};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((ed, mldsa)) = unpack_hybrid_signature(data) {
+        assert_eq!(pack_hybrid_signature(&ed, &mldsa).expect("repack"), data);
+    }
+});
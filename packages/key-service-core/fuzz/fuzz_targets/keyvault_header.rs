@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mo_key_service_core::crypto::KdfMinimums;
+use mo_key_service_core::formats::decode_keyvault_header_v1;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_keyvault_header_v1(data, &KdfMinimums::NONE);
+});
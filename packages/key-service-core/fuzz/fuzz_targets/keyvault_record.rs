@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mo_key_service_core::formats::{
+    decode_keyvault_record_container_v1, decode_keyvault_record_plain_v1,
+};
+
+// Containers arrive from storage and sync; plain records only after decryption, but a
+// corrupted or hostile vault key still lets an attacker choose their bytes.
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_keyvault_record_container_v1(data);
+    let _ = decode_keyvault_record_plain_v1(data);
+});
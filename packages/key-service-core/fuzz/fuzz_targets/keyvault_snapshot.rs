@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mo_key_service_core::cbor::{decode_canonical_value, CborLimits};
+use mo_key_service_core::formats::decode_keyvault_snapshot;
+
+// Mirrors `KeyService::import_keyvault`, including v2 decompression.
+fuzz_target!(|data: &[u8]| {
+    let limits = CborLimits::default();
+    if let Ok(value) = decode_canonical_value(data, &limits) {
+        if let Ok(Some(manifest)) =
+            decode_keyvault_snapshot(value, &limits).map(|snapshot| snapshot.manifest)
+        {
+            let _ = manifest.to_be_signed_bytes();
+        }
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mo_key_service_core::formats::decode_resource_grant_v1;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(grant) = decode_resource_grant_v1(data) {
+        let _ = grant.to_be_signed_bytes();
+        let _ = grant.grant_ref_bytes();
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mo_key_service_core::formats::decode_scope_state_v1;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(scope_state) = decode_scope_state_v1(data) {
+        let _ = scope_state.to_be_signed_bytes();
+        let _ = scope_state.scope_state_ref_bytes();
+    }
+});
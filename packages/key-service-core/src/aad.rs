@@ -1,6 +1,16 @@
-use crate::cbor::{cbor_map, cbor_text, cbor_uint, encode_canonical_value, CborLimits};
+use crate::cbor::{
+    cbor_array, cbor_bytes, cbor_map, cbor_text, cbor_uint, encode_canonical_value, CborLimits,
+};
 use crate::crypto::KdfParams;
+use crate::domains::{
+    AAD_CAPABILITY_TOKEN_WRAP_V1, AAD_CGKA_PATH_SECRET_V1, AAD_CGKA_WELCOME_V1,
+    AAD_DEVICE_SHARE_SEAL_V1, AAD_DEVICE_SHARE_TRANSFER_V1, AAD_KEYVAULT_HEAD_MARKER_V1,
+    AAD_KEYVAULT_INDEX_V1, AAD_KEYVAULT_KEYWRAP_V1, AAD_KEYVAULT_KMS_WRAP_V1,
+    AAD_KEYVAULT_PASSPHRASE_CHECK_V1, AAD_KEYVAULT_RECORD_V1, AAD_KEY_ENVELOPE_PREKEY_V1,
+    AAD_KEY_ENVELOPE_V1, AAD_RESOURCE_GRANT_V1, AAD_USER_PRESENCE_WRAP_V1, INFO_RESOURCE_SUBKEY_V1,
+};
 use crate::error::CoreResult;
+use crate::formats::CapabilityTokenV1;
 use crate::types::{AeadId, KemCiphersuiteId};
 
 pub fn aad_keyvault_keywrap_v1(
@@ -9,7 +19,34 @@ pub fn aad_keyvault_keywrap_v1(
     kdf: &KdfParams,
     aead: AeadId,
 ) -> CoreResult<Vec<u8>> {
-    let kdf_map = cbor_map(vec![
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_KEYVAULT_KEYWRAP_V1)),
+        (1, cbor_text(vault_id)),
+        (2, cbor_text(user_id)),
+        (3, kdf_value(kdf)),
+        (4, cbor_text(aead.as_str())),
+    ]);
+    encode_canonical_value(&value)
+}
+
+/// AAD of the keyvault header's passphrase check, bound to the KDF parameters it was sealed
+/// under like the vault key wrap.
+pub fn aad_keyvault_passphrase_check_v1(
+    vault_id: &str,
+    user_id: &str,
+    kdf: &KdfParams,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_KEYVAULT_PASSPHRASE_CHECK_V1)),
+        (1, cbor_text(vault_id)),
+        (2, cbor_text(user_id)),
+        (3, kdf_value(kdf)),
+    ]);
+    encode_canonical_value(&value)
+}
+
+fn kdf_value(kdf: &KdfParams) -> ciborium::value::Value {
+    cbor_map(vec![
         (0, cbor_text(&kdf.id)),
         (1, ciborium::value::Value::Bytes(kdf.salt.clone())),
         (
@@ -20,29 +57,96 @@ pub fn aad_keyvault_keywrap_v1(
                 (2, cbor_uint(kdf.parallelism as u64)),
             ]),
         ),
-    ]);
+    ])
+}
+
+pub fn aad_keyvault_record_v1(
+    vault_id: &str,
+    user_id: &str,
+    aead: AeadId,
+    record_id: &str,
+) -> CoreResult<Vec<u8>> {
     let value = cbor_map(vec![
-        (0, cbor_text("mo-keyvault-keywrap-aad-v1")),
+        (0, cbor_text(AAD_KEYVAULT_RECORD_V1)),
         (1, cbor_text(vault_id)),
         (2, cbor_text(user_id)),
-        (3, kdf_map),
-        (4, cbor_text(aead.as_str())),
+        (3, cbor_text(aead.as_str())),
+        (4, cbor_text(record_id)),
     ]);
     encode_canonical_value(&value)
 }
 
-pub fn aad_keyvault_record_v1(
+pub fn aad_keyvault_head_marker_v1(
     vault_id: &str,
     user_id: &str,
     aead: AeadId,
-    record_id: &str,
 ) -> CoreResult<Vec<u8>> {
     let value = cbor_map(vec![
-        (0, cbor_text("mo-keyvault-record-aad-v1")),
+        (0, cbor_text(AAD_KEYVAULT_HEAD_MARKER_V1)),
         (1, cbor_text(vault_id)),
         (2, cbor_text(user_id)),
         (3, cbor_text(aead.as_str())),
-        (4, cbor_text(record_id)),
+    ]);
+    encode_canonical_value(&value)
+}
+
+pub fn aad_keyvault_index_v1(vault_id: &str, user_id: &str, aead: AeadId) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_KEYVAULT_INDEX_V1)),
+        (1, cbor_text(vault_id)),
+        (2, cbor_text(user_id)),
+        (3, cbor_text(aead.as_str())),
+    ]);
+    encode_canonical_value(&value)
+}
+
+/// AAD the KMS binds to its ciphertext of `K_vault`; passed to [`crate::adapters::KmsAdapter`].
+pub fn aad_keyvault_kms_wrap_v1(
+    vault_id: &str,
+    user_id: &str,
+    kms_key_id: &str,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_KEYVAULT_KMS_WRAP_V1)),
+        (1, cbor_text(vault_id)),
+        (2, cbor_text(user_id)),
+        (3, cbor_text(kms_key_id)),
+    ]);
+    encode_canonical_value(&value)
+}
+
+/// AAD a device anchor binds to this device's share of a device-share split.
+pub fn aad_device_share_seal_v1(
+    vault_id: &str,
+    split_id: &[u8],
+    device_id: &str,
+    share_index: u64,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_DEVICE_SHARE_SEAL_V1)),
+        (1, cbor_text(vault_id)),
+        (2, cbor_bytes(split_id)),
+        (3, cbor_text(device_id)),
+        (4, cbor_uint(share_index)),
+    ]);
+    encode_canonical_value(&value)
+}
+
+/// AAD of a device share sent to the device that asked for it in request `request_id`.
+pub fn aad_device_share_transfer_v1(
+    vault_id: &str,
+    split_id: &[u8],
+    request_id: &[u8],
+    device_id: &str,
+    share_index: u64,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_DEVICE_SHARE_TRANSFER_V1)),
+        (1, cbor_text(vault_id)),
+        (2, cbor_bytes(split_id)),
+        (3, cbor_bytes(request_id)),
+        (4, cbor_text(device_id)),
+        (5, cbor_uint(share_index)),
     ]);
     encode_canonical_value(&value)
 }
@@ -57,7 +161,7 @@ pub fn aad_key_envelope_wrap_v1(
     recipient_uk_pub_fingerprint: Option<&Vec<u8>>,
 ) -> CoreResult<Vec<u8>> {
     let mut entries = vec![
-        (0, cbor_text("mo-key-envelope-aad-v1")),
+        (0, cbor_text(AAD_KEY_ENVELOPE_V1)),
         (1, cbor_text(scope_id)),
         (2, cbor_uint(scope_epoch)),
         (3, cbor_text(recipient_user_id)),
@@ -72,6 +176,32 @@ pub fn aad_key_envelope_wrap_v1(
     encode_canonical_value(&value)
 }
 
+/// AAD of a key envelope sealed to a one-time prekey instead of the recipient's user key.
+#[allow(clippy::too_many_arguments)]
+pub fn aad_key_envelope_prekey_wrap_v1(
+    scope_id: &str,
+    scope_epoch: u64,
+    recipient_user_id: &str,
+    scope_state_ref: &[u8],
+    kem: KemCiphersuiteId,
+    aead: AeadId,
+    recipient_uk_pub_fingerprint: &[u8],
+    prekey_id: u64,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_KEY_ENVELOPE_PREKEY_V1)),
+        (1, cbor_text(scope_id)),
+        (2, cbor_uint(scope_epoch)),
+        (3, cbor_text(recipient_user_id)),
+        (4, cbor_bytes(scope_state_ref)),
+        (5, cbor_text(kem.as_str())),
+        (6, cbor_text(aead.as_str())),
+        (7, cbor_bytes(recipient_uk_pub_fingerprint)),
+        (8, cbor_uint(prekey_id)),
+    ]);
+    encode_canonical_value(&value)
+}
+
 pub fn aad_resource_grant_wrap_v1(
     scope_id: &str,
     resource_id: &str,
@@ -80,7 +210,7 @@ pub fn aad_resource_grant_wrap_v1(
     aead: AeadId,
 ) -> CoreResult<Vec<u8>> {
     let value = cbor_map(vec![
-        (0, cbor_text("mo-resource-grant-aad-v1")),
+        (0, cbor_text(AAD_RESOURCE_GRANT_V1)),
         (1, cbor_text(scope_id)),
         (2, cbor_text(resource_id)),
         (3, cbor_uint(scope_epoch)),
@@ -96,25 +226,97 @@ pub fn aad_user_presence_wrap_v1(
     kdf: &KdfParams,
     aead: AeadId,
 ) -> CoreResult<Vec<u8>> {
-    let kdf_map = cbor_map(vec![
-        (0, cbor_text(&kdf.id)),
-        (1, ciborium::value::Value::Bytes(kdf.salt.clone())),
-        (
-            2,
-            cbor_map(vec![
-                (0, cbor_uint(kdf.memory_kib as u64)),
-                (1, cbor_uint(kdf.iterations as u64)),
-                (2, cbor_uint(kdf.parallelism as u64)),
-            ]),
-        ),
-    ]);
     let value = cbor_map(vec![
-        (0, cbor_text("mo-user-presence-wrap-aad-v1")),
+        (0, cbor_text(AAD_USER_PRESENCE_WRAP_V1)),
         (1, cbor_text(vault_id)),
         (2, cbor_text(user_id)),
         (3, cbor_text("salt-v1")),
         (4, cbor_text(aead.as_str())),
-        (5, kdf_map),
+        (5, kdf_value(kdf)),
+    ]);
+    encode_canonical_value(&value)
+}
+
+/// Binds every field of `token` except the nonce and the wrapped key itself.
+pub fn aad_capability_token_wrap_v1(
+    user_id: &str,
+    token: &CapabilityTokenV1,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_CAPABILITY_TOKEN_WRAP_V1)),
+        (1, cbor_text(&token.vault_id)),
+        (2, cbor_text(user_id)),
+        (3, cbor_text(token.aead.as_str())),
+        (4, cbor_uint(token.v)),
+        (5, cbor_text(&token.resource_id.0)),
+        (6, cbor_text(&token.resource_key_id.0)),
+        (7, ciborium::value::Value::Bytes(token.aad_prefix.clone())),
+        (8, cbor_uint(token.ops.bits())),
+        (9, cbor_uint(token.issued_at_ms)),
+        (10, cbor_uint(token.expires_at_ms)),
+    ]);
+    encode_canonical_value(&value)
+}
+
+/// Binds a CGKA path secret to the commit epoch, the tree node it belongs to, and the node it
+/// is encrypted to.
+pub fn aad_cgka_path_secret_v1(
+    scope_id: &str,
+    scope_epoch: u64,
+    path_node: u64,
+    recipient_node: u64,
+    kem: KemCiphersuiteId,
+    aead: AeadId,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_CGKA_PATH_SECRET_V1)),
+        (1, cbor_text(scope_id)),
+        (2, cbor_uint(scope_epoch)),
+        (3, cbor_uint(path_node)),
+        (4, cbor_uint(recipient_node)),
+        (5, cbor_text(kem.as_str())),
+        (6, cbor_text(aead.as_str())),
+    ]);
+    encode_canonical_value(&value)
+}
+
+pub fn aad_cgka_welcome_v1(
+    scope_id: &str,
+    scope_epoch: u64,
+    recipient_user_id: &str,
+    tree_hash: &[u8],
+    kem: KemCiphersuiteId,
+    aead: AeadId,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(AAD_CGKA_WELCOME_V1)),
+        (1, cbor_text(scope_id)),
+        (2, cbor_uint(scope_epoch)),
+        (3, cbor_text(recipient_user_id)),
+        (4, cbor_bytes(tree_hash)),
+        (5, cbor_text(kem.as_str())),
+        (6, cbor_text(aead.as_str())),
+    ]);
+    encode_canonical_value(&value)
+}
+
+/// HKDF info for a sub-key of a resource key. Binds the output length so keys of different
+/// lengths for one path are not prefixes of each other.
+pub fn hkdf_info_resource_subkey_v1(
+    resource_id: &str,
+    resource_key_id: &str,
+    path: &[&[u8]],
+    len: usize,
+) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_text(INFO_RESOURCE_SUBKEY_V1)),
+        (1, cbor_text(resource_id)),
+        (2, cbor_text(resource_key_id)),
+        (
+            3,
+            cbor_array(path.iter().map(|segment| cbor_bytes(segment)).collect()),
+        ),
+        (4, cbor_uint(len as u64)),
     ]);
     encode_canonical_value(&value)
 }
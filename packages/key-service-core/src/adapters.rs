@@ -1,6 +1,10 @@
+use crate::logging::LogEvent;
+use crate::transparency::{InclusionProof, SignedTreeHead};
+use crate::types::{DeviceId, ScopeEpoch, ScopeId, SessionId};
 use std::fmt::Debug;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 
 pub type ListSinceResult = (Vec<(String, Vec<u8>)>, String);
 
@@ -24,9 +28,45 @@ pub trait EntropyAdapter {
     fn random_bytes(&self, len: usize) -> Vec<u8>;
 }
 
+impl<T: ClockAdapter + ?Sized> ClockAdapter for Arc<T> {
+    fn now_ms(&self) -> u64 {
+        (**self).now_ms()
+    }
+}
+
+impl<T: EntropyAdapter + ?Sized> EntropyAdapter for Arc<T> {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        (**self).random_bytes(len)
+    }
+}
+
+/// `Send` on native targets so services can run on multithreaded executors; a no-op on wasm32,
+/// where adapters wrap JS values and promises that are never `Send`.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + ?Sized> MaybeSend for T {}
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// `Sync` on native targets; see [`MaybeSend`].
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSync: Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSync for T {}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+#[cfg(target_arch = "wasm32")]
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
 
-pub trait AsyncStorageAdapter {
+pub trait AsyncStorageAdapter: MaybeSend + MaybeSync {
     type Error: Debug + Send + Sync + 'static;
     fn get<'a>(
         &'a self,
@@ -49,7 +89,7 @@ pub trait AsyncStorageAdapter {
 
 pub struct SyncStorageAdapter<S: StorageAdapter>(pub S);
 
-impl<S: StorageAdapter> AsyncStorageAdapter for SyncStorageAdapter<S> {
+impl<S: StorageAdapter + MaybeSend + MaybeSync> AsyncStorageAdapter for SyncStorageAdapter<S> {
     type Error = S::Error;
 
     fn get<'a>(
@@ -85,8 +125,192 @@ pub enum PlatformSignal {
     Blur,
 }
 
-pub trait DeviceAnchorAdapter {
+/// Raw FIDO HID transport for a roaming authenticator (e.g. a `hidapi` device). Reports are
+/// the 64-byte CTAPHID packets without a report id; framing and CTAP2 live in
+/// [`crate::fido2`] (`fido2` feature).
+pub trait HidAuthenticatorAdapter: MaybeSend {
+    type Error: Debug + Send + Sync + 'static;
+    fn write_report(&self, report: &[u8; 64]) -> Result<(), Self::Error>;
+    /// Waits up to `timeout_ms` for the next input report; `None` on timeout.
+    fn read_report(&self, timeout_ms: u32) -> Result<Option<[u8; 64]>, Self::Error>;
+}
+
+pub trait DeviceAnchorAdapter: MaybeSend {
     type Error: Debug + Send + Sync + 'static;
     fn seal(&self, label: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Self::Error>;
     fn unseal(&self, label: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Self::Error>;
 }
+
+/// Remote key management service (a cloud KMS or HSM) that wraps and unwraps small secrets
+/// under a key it never releases. In KMS mode every unlock calls `unwrap`, so the service's
+/// access policy and audit log see each one; see `KeyService::enable_kms_wrap`.
+pub trait KmsAdapter: MaybeSend {
+    type Error: Debug + Send + Sync + 'static;
+    fn wrap(&self, key_id: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Self::Error>;
+    fn unwrap(&self, key_id: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Self::Error>;
+}
+
+/// A counter kept outside vault storage (a TPM NV index, a platform keystore entry, or a
+/// server), so restoring old vault storage cannot lower it. It backs the head marker's
+/// rollback check; see `KeyService::set_monotonic_counter`.
+pub trait MonotonicCounterAdapter: MaybeSend {
+    type Error: Debug + Send + Sync + 'static;
+    /// Current value of `name`; 0 if it was never advanced.
+    fn read(&self, name: &str) -> Result<u64, Self::Error>;
+    /// Raises `name` to `value`. Must never lower it.
+    fn advance(&self, name: &str, value: u64) -> Result<(), Self::Error>;
+}
+
+/// Async [`DeviceAnchorAdapter`] for platform keystores (Android Keystore, WebAuthn-gated
+/// secrets); used by `AsyncKeyService::set_async_device_anchor`.
+pub trait AsyncDeviceAnchorAdapter: MaybeSend + MaybeSync {
+    type Error: Debug + Send + Sync + 'static;
+    fn seal<'a>(
+        &'a self,
+        label: &'a str,
+        aad: &'a [u8],
+        plaintext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, Self::Error>>;
+    fn unseal<'a>(
+        &'a self,
+        label: &'a str,
+        aad: &'a [u8],
+        ciphertext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, Self::Error>>;
+}
+
+pub struct SyncDeviceAnchorAdapter<A: DeviceAnchorAdapter>(pub A);
+
+impl<A: DeviceAnchorAdapter + MaybeSync> AsyncDeviceAnchorAdapter for SyncDeviceAnchorAdapter<A> {
+    type Error = A::Error;
+
+    fn seal<'a>(
+        &'a self,
+        label: &'a str,
+        aad: &'a [u8],
+        plaintext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, Self::Error>> {
+        Box::pin(async move { self.0.seal(label, aad, plaintext) })
+    }
+
+    fn unseal<'a>(
+        &'a self,
+        label: &'a str,
+        aad: &'a [u8],
+        ciphertext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, Self::Error>> {
+        Box::pin(async move { self.0.unseal(label, aad, ciphertext) })
+    }
+}
+
+/// Fetches tree heads and proofs from a key transparency log; the service verifies everything
+/// it gets back, so the adapter and the log need not be trusted. See [`crate::transparency`].
+pub trait TransparencyAdapter: MaybeSend {
+    type Error: Debug + Send + Sync + 'static;
+    /// The log's latest signed tree head.
+    fn tree_head(&self) -> Result<SignedTreeHead, Self::Error>;
+    /// Proof for the leaf with `leaf_hash` in the tree of `tree_size` leaves; `None` when the
+    /// log has no such leaf.
+    fn inclusion_proof(
+        &self,
+        leaf_hash: &[u8; 32],
+        tree_size: u64,
+    ) -> Result<Option<InclusionProof>, Self::Error>;
+    /// Proof that the tree of `first_size` leaves is a prefix of the tree of `second_size`.
+    fn consistency_proof(
+        &self,
+        first_size: u64,
+        second_size: u64,
+    ) -> Result<Vec<[u8; 32]>, Self::Error>;
+}
+
+/// Operations reported to a [`MetricsAdapter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MetricOp {
+    Unlock,
+    Kdf,
+    RecordApply,
+    Sign,
+    Verify,
+    Encrypt,
+    Decrypt,
+}
+
+impl MetricOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricOp::Unlock => "unlock",
+            MetricOp::Kdf => "kdf",
+            MetricOp::RecordApply => "record_apply",
+            MetricOp::Sign => "sign",
+            MetricOp::Verify => "verify",
+            MetricOp::Encrypt => "encrypt",
+            MetricOp::Decrypt => "decrypt",
+        }
+    }
+}
+
+/// Optional counters and timings; durations come from the service's `ClockAdapter`.
+pub trait MetricsAdapter: MaybeSend {
+    /// Counts one completed `op`; `ok` is false when it returned an error.
+    fn increment(&self, op: MetricOp, ok: bool);
+    fn observe(&self, op: MetricOp, duration_ms: u64);
+}
+
+/// Receives structured events for state transitions and failures; see [`LogEvent`] for redaction.
+pub trait LogAdapter: MaybeSend {
+    fn log(&self, event: &LogEvent);
+}
+
+/// KeyVault mutations, reported after they are persisted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VaultEvent {
+    RecordAppended {
+        seq: u64,
+        record_id: String,
+    },
+    /// The header was created, replaced by import, or rewrapped by a passphrase change.
+    HeaderUpdated,
+    ScopeKeyAdded {
+        scope_id: ScopeId,
+        scope_epoch: ScopeEpoch,
+    },
+    SignerTrusted {
+        scope_id: ScopeId,
+        device_id: DeviceId,
+    },
+}
+
+pub trait VaultEventsAdapter: MaybeSend {
+    fn on_vault_event(&self, event: &VaultEvent);
+}
+
+/// Session lifecycle notices; sent from [`KeyService::on_timer`](crate::key_service::KeyService::on_timer),
+/// whenever an expired session is dropped, and when a session is locked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionEvent {
+    /// Sent once per expiry time, `session_expiry_warning_ms` before it; renewing re-arms it.
+    ExpiringSoon {
+        session_id: SessionId,
+        expires_at_ms: u64,
+    },
+    /// The session passed its TTL and was locked.
+    Expired { session_id: SessionId },
+    /// The session was locked before its TTL, e.g. by [`KeyService::lock`](crate::key_service::KeyService::lock).
+    Locked { session_id: SessionId },
+}
+
+pub trait SessionEventsAdapter: MaybeSend {
+    fn on_session_event(&self, event: &SessionEvent);
+}
+
+/// Wakes the host at the service's next session deadline, so auto-lock and expiry warnings do
+/// not depend on the caller polling. At most one wake-up is pending; when it fires the host
+/// calls [`KeyService::on_timer`](crate::key_service::KeyService::on_timer).
+pub trait TimerAdapter: MaybeSend {
+    /// Replaces any pending wake-up with one `delay_ms` from now.
+    fn schedule(&self, delay_ms: u64);
+    fn cancel(&self);
+}
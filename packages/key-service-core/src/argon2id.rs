@@ -0,0 +1,327 @@
+//! Argon2id (v0x13) with a hook between passes.
+//!
+//! The `argon2` crate runs every pass inside one call, so unlock progress needs its own fill
+//! loop. This follows the crate (and RFC 9106) step for step and is checked against it in
+//! tests; parameter validation still goes through [`argon2::Params`].
+
+use argon2::Params;
+use blake2::digest::{self, Digest, VariableOutput};
+use blake2::{Blake2b512, Blake2bVar};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::{CoreError, CoreResult};
+
+const SYNC_POINTS: usize = 4;
+const BLOCK_WORDS: usize = 128;
+const BLOCK_BYTES: usize = BLOCK_WORDS * 8;
+const MIN_SALT_LEN: usize = 8;
+const VERSION: u32 = 0x13;
+const ARGON2ID: u32 = 2;
+
+type Block = [u64; BLOCK_WORDS];
+
+/// Hashes `pwd` into `out_len` bytes, calling `on_pass(done, total)` after each pass.
+pub(crate) fn hash(
+    pwd: &[u8],
+    salt: &[u8],
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    out_len: usize,
+    on_pass: &mut dyn FnMut(u32, u32),
+) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let params = Params::new(memory_kib, iterations, parallelism, Some(out_len))
+        .map_err(|e| CoreError::Crypto(e.to_string()))?;
+    if salt.len() < MIN_SALT_LEN {
+        return Err(CoreError::Crypto(argon2::Error::SaltTooShort.to_string()));
+    }
+    let lanes = parallelism as usize;
+    let block_count = params.block_count();
+    let lane_length = block_count / lanes;
+    let segment_length = lane_length / SYNC_POINTS;
+
+    let mut initial_hash = initial_hash(pwd, salt, &params, out_len);
+    let mut memory = Zeroizing::new(vec![[0u64; BLOCK_WORDS]; block_count]);
+    for lane in 0..lanes {
+        for i in 0..2u32 {
+            let mut bytes = [0u8; BLOCK_BYTES];
+            blake2b_long(
+                &[
+                    &initial_hash[..],
+                    &i.to_le_bytes(),
+                    &(lane as u32).to_le_bytes(),
+                ],
+                &mut bytes,
+            )?;
+            load(&mut memory[lane * lane_length + i as usize], &bytes);
+            bytes.zeroize();
+        }
+    }
+    initial_hash.zeroize();
+
+    for pass in 0..iterations as usize {
+        for slice in 0..SYNC_POINTS {
+            for lane in 0..lanes {
+                fill_segment(
+                    &mut memory,
+                    pass,
+                    slice,
+                    lane,
+                    lanes,
+                    lane_length,
+                    segment_length,
+                    iterations,
+                );
+            }
+        }
+        on_pass(pass as u32 + 1, iterations);
+    }
+
+    let mut last = memory[lane_length - 1];
+    for lane in 1..lanes {
+        xor_into(&mut last, &memory[lane * lane_length + lane_length - 1]);
+    }
+    let mut last_bytes = [0u8; BLOCK_BYTES];
+    for (chunk, word) in last_bytes.chunks_exact_mut(8).zip(last.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    let mut out = Zeroizing::new(vec![0u8; out_len]);
+    blake2b_long(&[&last_bytes], &mut out)?;
+    last.zeroize();
+    last_bytes.zeroize();
+    Ok(out)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fill_segment(
+    memory: &mut [Block],
+    pass: usize,
+    slice: usize,
+    lane: usize,
+    lanes: usize,
+    lane_length: usize,
+    segment_length: usize,
+    iterations: u32,
+) {
+    // Argon2id addresses data-independently for the first half of the first pass.
+    let independent = pass == 0 && slice < SYNC_POINTS / 2;
+    let zero = [0u64; BLOCK_WORDS];
+    let mut address = [0u64; BLOCK_WORDS];
+    let mut input = [0u64; BLOCK_WORDS];
+    if independent {
+        input[..6].copy_from_slice(&[
+            pass as u64,
+            lane as u64,
+            slice as u64,
+            memory.len() as u64,
+            u64::from(iterations),
+            u64::from(ARGON2ID),
+        ]);
+    }
+    let first_block = if pass == 0 && slice == 0 {
+        if independent {
+            next_addresses(&mut address, &mut input, &zero);
+        }
+        2
+    } else {
+        0
+    };
+
+    let segment_start = lane * lane_length + slice * segment_length;
+    for block in first_block..segment_length {
+        let cur = segment_start + block;
+        let prev = if slice == 0 && block == 0 {
+            cur + lane_length - 1
+        } else {
+            cur - 1
+        };
+        let rand = if independent {
+            if block % BLOCK_WORDS == 0 {
+                next_addresses(&mut address, &mut input, &zero);
+            }
+            address[block % BLOCK_WORDS]
+        } else {
+            memory[prev][0]
+        };
+        let ref_lane = if pass == 0 && slice == 0 {
+            lane
+        } else {
+            (rand >> 32) as usize % lanes
+        };
+        let area = if pass == 0 {
+            if slice == 0 {
+                block - 1
+            } else if ref_lane == lane {
+                slice * segment_length + block - 1
+            } else {
+                slice * segment_length - usize::from(block == 0)
+            }
+        } else if ref_lane == lane {
+            lane_length - segment_length + block - 1
+        } else {
+            lane_length - segment_length - usize::from(block == 0)
+        };
+        let mut map = rand & 0xFFFF_FFFF;
+        map = (map * map) >> 32;
+        let relative = area - 1 - ((area as u64 * map) >> 32) as usize;
+        let start = if pass != 0 && slice != SYNC_POINTS - 1 {
+            (slice + 1) * segment_length
+        } else {
+            0
+        };
+        let reference = ref_lane * lane_length + (start + relative) % lane_length;
+
+        let result = compress(&memory[prev], &memory[reference]);
+        if pass == 0 {
+            memory[cur] = result;
+        } else {
+            xor_into(&mut memory[cur], &result);
+        }
+    }
+    address.zeroize();
+    input.zeroize();
+}
+
+fn next_addresses(address: &mut Block, input: &mut Block, zero: &Block) {
+    input[6] += 1;
+    *address = compress(zero, input);
+    *address = compress(zero, address);
+}
+
+fn initial_hash(pwd: &[u8], salt: &[u8], params: &Params, out_len: usize) -> Zeroizing<[u8; 64]> {
+    let mut digest = Blake2b512::new();
+    digest.update(params.p_cost().to_le_bytes());
+    digest.update((out_len as u32).to_le_bytes());
+    digest.update(params.m_cost().to_le_bytes());
+    digest.update(params.t_cost().to_le_bytes());
+    digest.update(VERSION.to_le_bytes());
+    digest.update(ARGON2ID.to_le_bytes());
+    digest.update((pwd.len() as u32).to_le_bytes());
+    digest.update(pwd);
+    digest.update((salt.len() as u32).to_le_bytes());
+    digest.update(salt);
+    // No secret and no associated data.
+    digest.update(0u32.to_le_bytes());
+    digest.update(0u32.to_le_bytes());
+    Zeroizing::new(digest.finalize().into())
+}
+
+/// H' from RFC 9106 section 3.3.
+fn blake2b_long(inputs: &[&[u8]], out: &mut [u8]) -> CoreResult<()> {
+    let len_bytes = (out.len() as u32).to_le_bytes();
+    if out.len() <= 64 {
+        let mut digest =
+            Blake2bVar::new(out.len()).map_err(|e| CoreError::Crypto(e.to_string()))?;
+        digest::Update::update(&mut digest, &len_bytes);
+        for input in inputs {
+            digest::Update::update(&mut digest, input);
+        }
+        return digest
+            .finalize_variable(out)
+            .map_err(|e| CoreError::Crypto(e.to_string()));
+    }
+
+    let mut digest = Blake2b512::new();
+    digest.update(len_bytes);
+    for input in inputs {
+        digest.update(input);
+    }
+    let mut last = digest.finalize();
+    out[..32].copy_from_slice(&last[..32]);
+    let mut written = 32;
+    while out.len() - written > 64 {
+        last = Blake2b512::digest(last);
+        out[written..written + 32].copy_from_slice(&last[..32]);
+        written += 32;
+    }
+    let mut digest =
+        Blake2bVar::new(out.len() - written).map_err(|e| CoreError::Crypto(e.to_string()))?;
+    digest::Update::update(&mut digest, &last);
+    digest
+        .finalize_variable(&mut out[written..])
+        .map_err(|e| CoreError::Crypto(e.to_string()))?;
+    last.zeroize();
+    Ok(())
+}
+
+fn load(block: &mut Block, bytes: &[u8; BLOCK_BYTES]) {
+    for (word, chunk) in block.iter_mut().zip(bytes.chunks_exact(8)) {
+        *word = u64::from_le_bytes(chunk.try_into().expect("8-byte chunk"));
+    }
+}
+
+fn xor_into(dst: &mut Block, src: &Block) {
+    for (d, s) in dst.iter_mut().zip(src.iter()) {
+        *d ^= s;
+    }
+}
+
+/// The BlaMka-based compression function G.
+fn compress(x: &Block, y: &Block) -> Block {
+    let mut r = *x;
+    xor_into(&mut r, y);
+    let mut q = r;
+    for row in 0..8 {
+        let mut v: [usize; 16] = [0; 16];
+        for (i, idx) in v.iter_mut().enumerate() {
+            *idx = row * 16 + i;
+        }
+        permute(&mut q, &v);
+    }
+    for col in 0..8 {
+        let b = col * 2;
+        let v = [
+            b,
+            b + 1,
+            b + 16,
+            b + 17,
+            b + 32,
+            b + 33,
+            b + 48,
+            b + 49,
+            b + 64,
+            b + 65,
+            b + 80,
+            b + 81,
+            b + 96,
+            b + 97,
+            b + 112,
+            b + 113,
+        ];
+        permute(&mut q, &v);
+    }
+    xor_into(&mut q, &r);
+    r.zeroize();
+    q
+}
+
+fn permute(q: &mut Block, v: &[usize; 16]) {
+    for (a, b, c, d) in [
+        (0, 4, 8, 12),
+        (1, 5, 9, 13),
+        (2, 6, 10, 14),
+        (3, 7, 11, 15),
+        (0, 5, 10, 15),
+        (1, 6, 11, 12),
+        (2, 7, 8, 13),
+        (3, 4, 9, 14),
+    ] {
+        mix(q, v[a], v[b], v[c], v[d]);
+    }
+}
+
+fn mix(q: &mut Block, a: usize, b: usize, c: usize, d: usize) {
+    q[a] = blamka(q[a], q[b]);
+    q[d] = (q[d] ^ q[a]).rotate_right(32);
+    q[c] = blamka(q[c], q[d]);
+    q[b] = (q[b] ^ q[c]).rotate_right(24);
+    q[a] = blamka(q[a], q[b]);
+    q[d] = (q[d] ^ q[a]).rotate_right(16);
+    q[c] = blamka(q[c], q[d]);
+    q[b] = (q[b] ^ q[c]).rotate_right(63);
+}
+
+fn blamka(x: u64, y: u64) -> u64 {
+    let low = (x & 0xFFFF_FFFF).wrapping_mul(y & 0xFFFF_FFFF);
+    x.wrapping_add(y).wrapping_add(low.wrapping_mul(2))
+}
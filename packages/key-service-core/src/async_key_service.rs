@@ -1,14 +1,50 @@
-use crate::adapters::{AsyncStorageAdapter, ClockAdapter, EntropyAdapter, StorageAdapter};
+use crate::adapters::{
+    AsyncDeviceAnchorAdapter, AsyncStorageAdapter, BoxFuture, ClockAdapter, DeviceAnchorAdapter,
+    EntropyAdapter, KmsAdapter, LogAdapter, MaybeSend, MaybeSync, MetricsAdapter,
+    MonotonicCounterAdapter, SessionEventsAdapter, StorageAdapter, TimerAdapter,
+    TransparencyAdapter, VaultEventsAdapter,
+};
+use crate::audit::{AuditEntryV1, AuditVerifyReport, AUDIT_NAMESPACE};
+use crate::cancel::CancellationToken;
+use crate::ciphersuite::SignerKeys;
+use crate::diagnostics::DiagnosticsReport;
+use crate::formats::CgkaMemberV1;
+use crate::hash::sha256;
 use crate::key_service::{
-    DecryptResponse, EncryptResponse, GetUserPresenceUnlockInfoResponse, IngestKeyEnvelopeResponse,
-    IngestScopeStateResponse, KeyService, KeyServiceConfig, KeyServiceError, OpenResourceResponse,
-    OpenScopeResponse, RenewSessionResponse, StepUpResponse, UnlockResponse, VerifyResponse,
+    CapabilityRequest, CgkaCommitResponse, ChangePassphraseResponse, Contact, DecryptInitResponse,
+    DecryptResponse, DeviceAttestationResponse, DevicePublicKeysResponse, DeviceShareUnlock,
+    DueRotationsResponse, EncryptInitResponse, EncryptResponse, GetUserPresenceUnlockInfoResponse,
+    HealthCheckResponse, ImportKeyVaultInitResponse, IngestCgkaResponse, IngestDelegationResponse,
+    IngestKeyEnvelopeResponse, IngestScopeStateResponse, KeyService, KeyServiceConfig,
+    KeyServiceError, ListContactsResponse, ListScopesResponse, MintCapabilityResponse,
+    OpaqueUnlockResponse, OpenResourceResponse, OpenScopeResponse, PublishPrekeysResponse,
+    RedeemCapabilityResponse, ReissueGrantResponse, ReloadResponse, RenewSessionResponse,
+    SalvageUnlockResponse, SealKeyEnvelopeResponse, SessionSummary, StepUpResponse, UnlockResponse,
+    UserPublicKeyResponse, VaultInfoResponse, VerifyKeyVaultResponse, VerifyResponse,
+    HEAD_MARKER_ANCHOR_LABEL, HEALTH_NAMESPACE, HEALTH_PROBE_KEY,
+};
+use crate::keyvault::{
+    KeyVaultCheckpoint, KeyVaultRecordMetadata, KeyVaultSalvageReport, KeyVaultStats,
+};
+use crate::opaque::{OpaqueLogin, OpaqueRegistration};
+use crate::transparency::TransparencyLogKey;
+use crate::types::{
+    CapabilityOps, DeviceId, KeyHandle, ScopeEpoch, ScopeId, ScopeRotationPolicy, SessionId,
+    SnapshotCompression, StreamId, UserId,
 };
-use crate::types::{DeviceId, KeyHandle, ScopeEpoch, ScopeId, SessionId, UserId};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zeroize::Zeroizing;
 
 const DEFAULT_LIST_LIMIT: usize = 512;
+/// Attempts per entry before `flush_pending` gives up and keeps the rest queued.
+const DEFAULT_FLUSH_ATTEMPTS: u32 = 3;
+/// Namespaces mirrored into the buffered storage.
+const CACHED_NAMESPACES: [&str; 2] = ["keyvault", AUDIT_NAMESPACE];
+/// Prefix of the stand-in bytes the inner service stores until the async anchor seals them.
+const PENDING_SEAL_PREFIX: &[u8] = b"mo-pending-anchor-seal:";
 
 #[derive(Clone, Debug)]
 pub struct StorageEntry {
@@ -17,9 +53,16 @@ pub struct StorageEntry {
     pub value: Vec<u8>,
 }
 
+impl StorageEntry {
+    fn is_same_slot(&self, namespace: &str, key: &str) -> bool {
+        self.namespace == namespace && self.key == key
+    }
+}
+
 #[derive(Default, Debug)]
 struct StorageState {
     values: HashMap<String, HashMap<String, Vec<u8>>>,
+    /// At most one entry per (namespace, key), ordered by its latest write.
     pending: Vec<StorageEntry>,
 }
 
@@ -43,10 +86,60 @@ impl BufferedStorage {
         }
     }
 
+    /// Replaces cached values with a fresh durable read; unflushed writes stay on top.
+    fn reload_entries(&self, entries: Vec<StorageEntry>) {
+        let mut state = self.state.lock().expect("buffered storage lock");
+        for namespace in CACHED_NAMESPACES {
+            state.values.remove(namespace);
+        }
+        let pending = state.pending.clone();
+        for entry in entries.into_iter().chain(pending) {
+            let namespace = state.values.entry(entry.namespace).or_default();
+            namespace.insert(entry.key, entry.value);
+        }
+    }
+
     fn drain_pending(&self) -> Vec<StorageEntry> {
         let mut state = self.state.lock().expect("buffered storage lock");
         std::mem::take(&mut state.pending)
     }
+
+    fn has_pending_value(&self, value: &[u8]) -> bool {
+        let state = self.state.lock().expect("buffered storage lock");
+        state.pending.iter().any(|e| e.value == value)
+    }
+
+    /// Swaps `old` for `new` wherever it is cached or queued.
+    fn replace_value(&self, old: &[u8], new: &[u8]) {
+        let mut state = self.state.lock().expect("buffered storage lock");
+        for entry in state.pending.iter_mut().filter(|e| e.value == old) {
+            entry.value = new.to_vec();
+        }
+        for value in state.values.values_mut().flat_map(|ns| ns.values_mut()) {
+            if value == old {
+                *value = new.to_vec();
+            }
+        }
+    }
+
+    fn pending(&self) -> Vec<StorageEntry> {
+        let state = self.state.lock().expect("buffered storage lock");
+        state.pending.clone()
+    }
+
+    /// Puts unflushed entries back ahead of anything written since, keeping write order;
+    /// entries overwritten since are dropped.
+    fn requeue_front(&self, mut entries: Vec<StorageEntry>) {
+        let mut state = self.state.lock().expect("buffered storage lock");
+        entries.retain(|e| {
+            !state
+                .pending
+                .iter()
+                .any(|newer| newer.is_same_slot(&e.namespace, &e.key))
+        });
+        entries.append(&mut state.pending);
+        state.pending = entries;
+    }
 }
 
 impl StorageAdapter for BufferedStorage {
@@ -70,6 +163,7 @@ impl StorageAdapter for BufferedStorage {
             .map_err(|_| "storage lock poisoned".to_string())?;
         let ns = state.values.entry(namespace.to_string()).or_default();
         ns.insert(key.to_string(), value.to_vec());
+        state.pending.retain(|e| !e.is_same_slot(namespace, key));
         state.pending.push(StorageEntry {
             namespace: namespace.to_string(),
             key: key.to_string(),
@@ -115,10 +209,161 @@ impl StorageAdapter for BufferedStorage {
     }
 }
 
+/// Object-safe view of an [`AsyncDeviceAnchorAdapter`].
+trait ErasedAsyncDeviceAnchor: MaybeSend + MaybeSync {
+    fn seal<'a>(
+        &'a self,
+        label: &'a str,
+        aad: &'a [u8],
+        plaintext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, String>>;
+    fn unseal<'a>(
+        &'a self,
+        label: &'a str,
+        aad: &'a [u8],
+        ciphertext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, String>>;
+}
+
+impl<A: AsyncDeviceAnchorAdapter> ErasedAsyncDeviceAnchor for A {
+    fn seal<'a>(
+        &'a self,
+        label: &'a str,
+        aad: &'a [u8],
+        plaintext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, String>> {
+        Box::pin(async move {
+            AsyncDeviceAnchorAdapter::seal(self, label, aad, plaintext)
+                .await
+                .map_err(|e| format!("{e:?}"))
+        })
+    }
+
+    fn unseal<'a>(
+        &'a self,
+        label: &'a str,
+        aad: &'a [u8],
+        ciphertext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, String>> {
+        Box::pin(async move {
+            AsyncDeviceAnchorAdapter::unseal(self, label, aad, ciphertext)
+                .await
+                .map_err(|e| format!("{e:?}"))
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct AnchoredValue {
+    label: String,
+    aad: Vec<u8>,
+    plaintext: Vec<u8>,
+}
+
+#[derive(Default, Debug)]
+struct AnchorBridgeState {
+    /// Sealed (or stand-in) bytes to what they unseal to.
+    known: HashMap<Vec<u8>, AnchoredValue>,
+    /// Stand-ins still waiting for the async anchor, oldest first.
+    to_seal: Vec<Vec<u8>>,
+}
+
+/// Sync anchor handed to the inner service when an async one is set. Unseal answers from values
+/// the async anchor opened ahead of time; seal returns a stand-in that `flush_pending` replaces
+/// with the real sealed bytes before anything reaches durable storage.
+#[derive(Clone, Debug, Default)]
+struct AnchorBridge {
+    state: Arc<Mutex<AnchorBridgeState>>,
+}
+
+impl AnchorBridge {
+    fn knows(&self, sealed: &[u8]) -> bool {
+        let state = self.state.lock().expect("anchor bridge lock");
+        state.known.contains_key(sealed)
+    }
+
+    fn learn(&self, sealed: Vec<u8>, value: AnchoredValue) {
+        let mut state = self.state.lock().expect("anchor bridge lock");
+        state.known.insert(sealed, value);
+    }
+
+    fn take_requests(&self) -> Vec<(Vec<u8>, AnchoredValue)> {
+        let mut state = self.state.lock().expect("anchor bridge lock");
+        let to_seal = std::mem::take(&mut state.to_seal);
+        to_seal
+            .into_iter()
+            .filter_map(|stand_in| {
+                let value = state.known.get(&stand_in).cloned()?;
+                Some((stand_in, value))
+            })
+            .collect()
+    }
+
+    fn requeue_requests(&self, mut stand_ins: Vec<Vec<u8>>) {
+        let mut state = self.state.lock().expect("anchor bridge lock");
+        stand_ins.append(&mut state.to_seal);
+        state.to_seal = stand_ins;
+    }
+
+    /// Forgets a stand-in and, once it is sealed, every older sealed value.
+    fn settle(&self, stand_in: &[u8], sealed: Option<Vec<u8>>) {
+        let mut state = self.state.lock().expect("anchor bridge lock");
+        let value = state.known.remove(stand_in);
+        if let (Some(sealed), Some(value)) = (sealed, value) {
+            state
+                .known
+                .retain(|bytes, _| bytes.starts_with(PENDING_SEAL_PREFIX));
+            state.known.insert(sealed, value);
+        }
+    }
+}
+
+impl DeviceAnchorAdapter for AnchorBridge {
+    type Error = String;
+
+    fn seal(&self, label: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        let mut input = Vec::with_capacity(label.len() + aad.len() + plaintext.len() + 2);
+        input.extend_from_slice(label.as_bytes());
+        input.push(0);
+        input.extend_from_slice(aad);
+        input.push(0);
+        input.extend_from_slice(plaintext);
+        let stand_in = [PENDING_SEAL_PREFIX, &sha256(&input)].concat();
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| "anchor bridge lock poisoned")?;
+        state.known.insert(
+            stand_in.clone(),
+            AnchoredValue {
+                label: label.to_string(),
+                aad: aad.to_vec(),
+                plaintext: plaintext.to_vec(),
+            },
+        );
+        state.to_seal.push(stand_in.clone());
+        Ok(stand_in)
+    }
+
+    fn unseal(&self, label: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|_| "anchor bridge lock poisoned")?;
+        match state.known.get(ciphertext) {
+            Some(value) if value.label == label && value.aad == aad => Ok(value.plaintext.clone()),
+            _ => Err("value was not unsealed by the async device anchor; call invalidate".into()),
+        }
+    }
+}
+
 pub struct AsyncKeyService<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> {
     storage: S,
     buffered: BufferedStorage,
     inner: KeyService<BufferedStorage, C, E>,
+    flush_attempts: u32,
+    async_anchor: Option<Box<dyn ErasedAsyncDeviceAnchor>>,
+    anchor_bridge: AnchorBridge,
 }
 
 impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService<S, C, E> {
@@ -129,16 +374,117 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         config: KeyServiceConfig,
     ) -> Result<Self, KeyServiceError> {
         let buffered = BufferedStorage::new();
-        let entries = load_namespace_entries(&storage, "keyvault").await?;
-        buffered.load_entries(entries);
+        for namespace in CACHED_NAMESPACES {
+            let entries = load_namespace_entries(&storage, namespace).await?;
+            buffered.load_entries(entries);
+        }
         let inner = KeyService::new(buffered.clone(), clock, entropy, config);
         Ok(Self {
             storage,
             buffered,
             inner,
+            flush_attempts: DEFAULT_FLUSH_ATTEMPTS,
+            async_anchor: None,
+            anchor_bridge: AnchorBridge::default(),
         })
     }
 
+    /// Re-reads durable storage after another instance wrote it, keeping unflushed local writes
+    /// on top; loaded vault state reloads on the next session-checked call.
+    pub async fn invalidate(&mut self) -> Result<(), KeyServiceError> {
+        self.refresh_cache().await?;
+        self.inner.invalidate();
+        Ok(())
+    }
+
+    pub async fn reload_from_storage(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<ReloadResponse, KeyServiceError> {
+        self.refresh_cache().await?;
+        let response = self.inner.reload_from_storage(session_id)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    /// Attempts per buffered write before a flush fails; clamped to at least 1.
+    pub fn set_flush_attempts(&mut self, attempts: u32) {
+        self.flush_attempts = attempts.max(1);
+    }
+
+    /// Retries buffered writes left over from a failed flush, oldest first. Puts are
+    /// overwrites, so re-sending an entry that did land is harmless.
+    pub async fn flush(&mut self) -> Result<(), KeyServiceError> {
+        self.flush_pending().await
+    }
+
+    /// Writes applied in memory but not yet durable, in the order they will be flushed.
+    pub fn unflushed_writes(&self) -> Vec<StorageEntry> {
+        self.buffered.pending()
+    }
+
+    /// Removes the unflushed writes so the caller can persist them elsewhere; until it does,
+    /// durable storage stays behind this service.
+    pub fn take_unflushed_writes(&mut self) -> Vec<StorageEntry> {
+        self.buffered.drain_pending()
+    }
+
+    pub fn set_device_anchor<A: DeviceAnchorAdapter + 'static>(&mut self, anchor: A) {
+        self.async_anchor = None;
+        self.inner.set_device_anchor(anchor);
+    }
+
+    /// Seals the anti-rollback head marker with an async device anchor. The stored marker is
+    /// unsealed here and after every cache refresh, so the sync unlock calls can read it; new
+    /// markers are sealed when buffered writes flush.
+    pub async fn set_async_device_anchor<A: AsyncDeviceAnchorAdapter + 'static>(
+        &mut self,
+        anchor: A,
+    ) -> Result<(), KeyServiceError> {
+        self.async_anchor = Some(Box::new(anchor));
+        self.anchor_bridge = AnchorBridge::default();
+        self.inner.set_device_anchor(self.anchor_bridge.clone());
+        self.unseal_anchored().await
+    }
+
+    pub fn set_kms_adapter<K: KmsAdapter + 'static>(&mut self, kms: K) {
+        self.inner.set_kms_adapter(kms);
+    }
+
+    /// The counter advances only after the marker it guards has flushed.
+    pub fn set_monotonic_counter<M: MonotonicCounterAdapter + 'static>(&mut self, counter: M) {
+        self.inner.set_monotonic_counter(counter);
+        self.inner.defer_counter_advance();
+    }
+
+    pub fn set_metrics_adapter<M: MetricsAdapter + 'static>(&mut self, metrics: M) {
+        self.inner.set_metrics_adapter(metrics);
+    }
+
+    pub fn set_log_adapter<L: LogAdapter + 'static>(&mut self, logger: L) {
+        self.inner.set_log_adapter(logger);
+    }
+
+    pub fn set_vault_events_adapter<V: VaultEventsAdapter + 'static>(&mut self, events: V) {
+        self.inner.set_vault_events_adapter(events);
+    }
+
+    pub fn set_session_events_adapter<V: SessionEventsAdapter + 'static>(&mut self, events: V) {
+        self.inner.set_session_events_adapter(events);
+    }
+
+    pub fn set_timer_adapter<T: TimerAdapter + 'static>(&mut self, timer: T) {
+        self.inner.set_timer_adapter(timer);
+    }
+
+    pub fn set_transparency_adapter<T: TransparencyAdapter + 'static>(
+        &mut self,
+        key: TransparencyLogKey,
+        adapter: T,
+    ) {
+        self.inner.set_transparency_adapter(key, adapter);
+    }
+
     pub async fn create_vault(
         &mut self,
         user_id: UserId,
@@ -149,6 +495,42 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         self.flush_pending().await
     }
 
+    pub fn start_opaque_registration(
+        &self,
+        password_utf8: &[u8],
+    ) -> Result<(OpaqueRegistration, Vec<u8>), KeyServiceError> {
+        self.inner.start_opaque_registration(password_utf8)
+    }
+
+    pub async fn create_vault_opaque(
+        &mut self,
+        user_id: UserId,
+        registration: OpaqueRegistration,
+        response: &[u8],
+        kdf: crate::crypto::KdfParams,
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        let record = self
+            .inner
+            .create_new_vault_opaque(user_id, registration, response, kdf)?;
+        self.flush_pending().await?;
+        Ok(record)
+    }
+
+    pub fn start_opaque_login(
+        &self,
+        password_utf8: &[u8],
+    ) -> Result<(OpaqueLogin, Vec<u8>), KeyServiceError> {
+        self.inner.start_opaque_login(password_utf8)
+    }
+
+    pub fn unlock_opaque(
+        &mut self,
+        login: OpaqueLogin,
+        ke2: &[u8],
+    ) -> Result<OpaqueUnlockResponse, KeyServiceError> {
+        self.inner.unlock_opaque(login, ke2)
+    }
+
     pub fn unlock_passphrase(
         &mut self,
         passphrase_utf8: &[u8],
@@ -156,6 +538,24 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         self.inner.unlock_passphrase(passphrase_utf8)
     }
 
+    pub fn unlock_passphrase_with_progress(
+        &mut self,
+        passphrase_utf8: &[u8],
+        on_pass: &mut dyn FnMut(u32, u32),
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.inner
+            .unlock_passphrase_with_progress(passphrase_utf8, on_pass)
+    }
+
+    pub fn unlock_passphrase_cancellable(
+        &mut self,
+        passphrase_utf8: &[u8],
+        cancel: &CancellationToken,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.inner
+            .unlock_passphrase_cancellable(passphrase_utf8, cancel)
+    }
+
     pub fn unlock_user_presence(
         &mut self,
         user_presence_secret: &[u8],
@@ -163,21 +563,115 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         self.inner.unlock_user_presence(user_presence_secret)
     }
 
-    pub fn step_up(
+    pub fn unlock_multi_factor(
+        &mut self,
+        passphrase_utf8: &[u8],
+        user_presence_secret: &[u8],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.inner
+            .unlock_multi_factor(passphrase_utf8, user_presence_secret)
+    }
+
+    pub fn unlock_passphrase_read_only(
+        &mut self,
+        passphrase_utf8: &[u8],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.inner.unlock_passphrase_read_only(passphrase_utf8)
+    }
+
+    pub fn verify_passphrase(&self, passphrase_utf8: &[u8]) -> Result<bool, KeyServiceError> {
+        self.inner.verify_passphrase(passphrase_utf8)
+    }
+
+    pub fn unlock_user_presence_read_only(
+        &mut self,
+        user_presence_secret: &[u8],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.inner
+            .unlock_user_presence_read_only(user_presence_secret)
+    }
+
+    pub fn unlock_with_salvage(
+        &mut self,
+        passphrase_utf8: &[u8],
+    ) -> Result<SalvageUnlockResponse, KeyServiceError> {
+        self.inner.unlock_with_salvage(passphrase_utf8)
+    }
+
+    pub fn unlock_with_salvage_rechaining(
+        &mut self,
+        passphrase_utf8: &[u8],
+    ) -> Result<SalvageUnlockResponse, KeyServiceError> {
+        self.inner.unlock_with_salvage_rechaining(passphrase_utf8)
+    }
+
+    pub async fn repair_keyvault(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<KeyVaultSalvageReport, KeyServiceError> {
+        let report = self.inner.repair_keyvault(session_id)?;
+        self.flush_pending().await?;
+        Ok(report)
+    }
+
+    pub async fn step_up(
+        &mut self,
+        session_id: &SessionId,
+        passphrase_utf8: &[u8],
+    ) -> Result<StepUpResponse, KeyServiceError> {
+        let response = self.inner.step_up(session_id, passphrase_utf8)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn step_up_multi_factor(
         &mut self,
         session_id: &SessionId,
         passphrase_utf8: &[u8],
+        user_presence_secret: &[u8],
     ) -> Result<StepUpResponse, KeyServiceError> {
-        self.inner.step_up(session_id, passphrase_utf8)
+        let response =
+            self.inner
+                .step_up_multi_factor(session_id, passphrase_utf8, user_presence_secret)?;
+        self.flush_pending().await?;
+        Ok(response)
     }
 
     pub async fn change_passphrase(
         &mut self,
         session_id: &SessionId,
         new_passphrase_utf8: &[u8],
+    ) -> Result<ChangePassphraseResponse, KeyServiceError> {
+        let response = self
+            .inner
+            .change_passphrase(session_id, new_passphrase_utf8)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn change_passphrase_keeping_user_presence(
+        &mut self,
+        session_id: &SessionId,
+        new_passphrase_utf8: &[u8],
+        user_presence_secret: &[u8],
+    ) -> Result<ChangePassphraseResponse, KeyServiceError> {
+        let response = self.inner.change_passphrase_keeping_user_presence(
+            session_id,
+            new_passphrase_utf8,
+            user_presence_secret,
+        )?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn enable_kms_wrap(
+        &mut self,
+        session_id: &SessionId,
+        passphrase_utf8: &[u8],
+        kms_key_id: &str,
     ) -> Result<(), KeyServiceError> {
         self.inner
-            .change_passphrase(session_id, new_passphrase_utf8)?;
+            .enable_kms_wrap(session_id, passphrase_utf8, kms_key_id)?;
         self.flush_pending().await
     }
 
@@ -207,6 +701,50 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         self.flush_pending().await
     }
 
+    pub async fn enable_device_share_unlock(
+        &mut self,
+        session_id: &SessionId,
+        threshold: u64,
+        device_ids: Vec<DeviceId>,
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .enable_device_share_unlock(session_id, threshold, device_ids)?;
+        self.flush_pending().await
+    }
+
+    pub async fn disable_device_share_unlock(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(), KeyServiceError> {
+        self.inner.disable_device_share_unlock(session_id)?;
+        self.flush_pending().await
+    }
+
+    pub async fn enroll_device_share(
+        &mut self,
+        session_id: &SessionId,
+        device_id: &DeviceId,
+    ) -> Result<(), KeyServiceError> {
+        self.inner.enroll_device_share(session_id, device_id)?;
+        self.flush_pending().await
+    }
+
+    pub fn begin_device_share_unlock(&mut self) -> Result<DeviceShareUnlock, KeyServiceError> {
+        self.inner.begin_device_share_unlock()
+    }
+
+    pub fn release_device_share(&mut self, request: &[u8]) -> Result<Vec<u8>, KeyServiceError> {
+        self.inner.release_device_share(request)
+    }
+
+    pub fn unlock_device_shares(
+        &mut self,
+        unlock: DeviceShareUnlock,
+        responses: &[Vec<u8>],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.inner.unlock_device_shares(unlock, responses)
+    }
+
     pub async fn store_app_master_key(
         &mut self,
         session_id: &SessionId,
@@ -219,47 +757,271 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
     pub fn get_app_master_key(
         &mut self,
         session_id: &SessionId,
-    ) -> Result<Vec<u8>, KeyServiceError> {
+    ) -> Result<Zeroizing<Vec<u8>>, KeyServiceError> {
         self.inner.get_app_master_key(session_id)
     }
 
-    pub fn get_user_presence_unlock_info(
+    pub fn list_records_metadata(
         &mut self,
-    ) -> Result<GetUserPresenceUnlockInfoResponse, KeyServiceError> {
-        self.inner.get_user_presence_unlock_info()
+        session_id: &SessionId,
+    ) -> Result<Vec<KeyVaultRecordMetadata>, KeyServiceError> {
+        self.inner.list_records_metadata(session_id)
     }
 
-    pub fn ingest_scope_state(
+    pub fn vault_stats(
         &mut self,
         session_id: &SessionId,
-        scope_state_cbor: &[u8],
-        expected_owner_signer_fingerprint: Option<String>,
-    ) -> Result<IngestScopeStateResponse, KeyServiceError> {
-        self.inner.ingest_scope_state(
-            session_id,
-            scope_state_cbor,
-            expected_owner_signer_fingerprint,
-        )
+    ) -> Result<KeyVaultStats, KeyServiceError> {
+        self.inner.vault_stats(session_id)
     }
 
-    pub async fn ingest_key_envelope(
+    pub async fn write_checkpoint(
         &mut self,
         session_id: &SessionId,
-        key_envelope_cbor: &[u8],
-    ) -> Result<IngestKeyEnvelopeResponse, KeyServiceError> {
-        let response = self
-            .inner
-            .ingest_key_envelope(session_id, key_envelope_cbor)?;
+    ) -> Result<KeyVaultCheckpoint, KeyServiceError> {
+        let checkpoint = self.inner.write_checkpoint(session_id)?;
         self.flush_pending().await?;
-        Ok(response)
+        Ok(checkpoint)
     }
 
-    pub fn open_scope(
+    pub fn latest_checkpoint(
         &mut self,
         session_id: &SessionId,
-        scope_id: ScopeId,
-        scope_epoch: ScopeEpoch,
-    ) -> Result<OpenScopeResponse, KeyServiceError> {
+    ) -> Result<Option<KeyVaultCheckpoint>, KeyServiceError> {
+        self.inner.latest_checkpoint(session_id)
+    }
+
+    pub async fn put_vault_blob(
+        &mut self,
+        session_id: &SessionId,
+        label: &str,
+        bytes: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        self.inner.put_vault_blob(session_id, label, bytes)?;
+        self.flush_pending().await
+    }
+
+    pub fn get_vault_blob(
+        &mut self,
+        session_id: &SessionId,
+        label: &str,
+    ) -> Result<Option<Zeroizing<Vec<u8>>>, KeyServiceError> {
+        self.inner.get_vault_blob(session_id, label)
+    }
+
+    pub fn get_user_presence_unlock_info(
+        &mut self,
+    ) -> Result<GetUserPresenceUnlockInfoResponse, KeyServiceError> {
+        self.inner.get_user_presence_unlock_info()
+    }
+
+    pub fn get_vault_info(&mut self) -> Result<VaultInfoResponse, KeyServiceError> {
+        self.inner.get_vault_info()
+    }
+
+    pub fn export_diagnostics(&self) -> DiagnosticsReport {
+        self.inner.export_diagnostics()
+    }
+
+    /// Like [`KeyService::health_check`], but the storage probe round-trips through the async adapter.
+    pub async fn health_check(&mut self) -> HealthCheckResponse {
+        let mut report = self.inner.health_check();
+        let probe = self
+            .buffered
+            .get(HEALTH_NAMESPACE, HEALTH_PROBE_KEY)
+            .ok()
+            .flatten();
+        let durable = match self.flush_pending().await {
+            Ok(()) => self
+                .storage
+                .get(HEALTH_NAMESPACE, HEALTH_PROBE_KEY)
+                .await
+                .ok()
+                .flatten(),
+            Err(_) => None,
+        };
+        if report.storage_ok && (probe.is_none() || durable != probe) {
+            report.storage_ok = false;
+            report.ok = false;
+            report
+                .problems
+                .push("storage: async adapter round-trip failed".to_string());
+        }
+        report
+    }
+
+    pub async fn ingest_scope_state(
+        &mut self,
+        session_id: &SessionId,
+        scope_state_cbor: &[u8],
+        expected_owner_signer_fingerprint: Option<String>,
+    ) -> Result<IngestScopeStateResponse, KeyServiceError> {
+        let response = self.inner.ingest_scope_state(
+            session_id,
+            scope_state_cbor,
+            expected_owner_signer_fingerprint,
+        )?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn ingest_device_attestation(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        device_id: &DeviceId,
+        signer: &SignerKeys,
+        attestation_object: &[u8],
+    ) -> Result<DeviceAttestationResponse, KeyServiceError> {
+        let response = self.inner.ingest_device_attestation(
+            session_id,
+            scope_id,
+            device_id,
+            signer,
+            attestation_object,
+        )?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn ingest_delegation(
+        &mut self,
+        session_id: &SessionId,
+        delegation_cbor: &[u8],
+    ) -> Result<IngestDelegationResponse, KeyServiceError> {
+        let response = self.inner.ingest_delegation(session_id, delegation_cbor)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn ingest_key_envelope(
+        &mut self,
+        session_id: &SessionId,
+        key_envelope_cbor: &[u8],
+    ) -> Result<IngestKeyEnvelopeResponse, KeyServiceError> {
+        let response = self
+            .inner
+            .ingest_key_envelope(session_id, key_envelope_cbor)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn publish_prekeys(
+        &mut self,
+        session_id: &SessionId,
+        count: u64,
+    ) -> Result<PublishPrekeysResponse, KeyServiceError> {
+        let response = self.inner.publish_prekeys(session_id, count)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub fn seal_key_envelope(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+        recipient_user_id: &UserId,
+    ) -> Result<SealKeyEnvelopeResponse, KeyServiceError> {
+        self.inner
+            .seal_key_envelope(session_id, scope_id, scope_epoch, recipient_user_id)
+    }
+
+    pub fn seal_key_envelope_to_prekey(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+        prekey_bundle_cbor: &[u8],
+        prekey_id: u64,
+        expected_signer_fingerprint: &str,
+    ) -> Result<SealKeyEnvelopeResponse, KeyServiceError> {
+        self.inner.seal_key_envelope_to_prekey(
+            session_id,
+            scope_id,
+            scope_epoch,
+            prekey_bundle_cbor,
+            prekey_id,
+            expected_signer_fingerprint,
+        )
+    }
+
+    pub async fn create_cgka_group(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .create_cgka_group(session_id, scope_id, scope_epoch)?;
+        self.flush_pending().await
+    }
+
+    pub async fn commit_cgka(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        adds: &[CgkaMemberV1],
+        removes: &[UserId],
+    ) -> Result<CgkaCommitResponse, KeyServiceError> {
+        let response = self
+            .inner
+            .commit_cgka(session_id, scope_id, adds, removes)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn ingest_cgka_commit(
+        &mut self,
+        session_id: &SessionId,
+        commit_cbor: &[u8],
+    ) -> Result<IngestCgkaResponse, KeyServiceError> {
+        let response = self.inner.ingest_cgka_commit(session_id, commit_cbor)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub async fn ingest_cgka_welcome(
+        &mut self,
+        session_id: &SessionId,
+        welcome_cbor: &[u8],
+    ) -> Result<IngestCgkaResponse, KeyServiceError> {
+        let response = self.inner.ingest_cgka_welcome(session_id, welcome_cbor)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub fn list_scopes(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<ListScopesResponse, KeyServiceError> {
+        self.inner.list_scopes(session_id)
+    }
+
+    pub async fn set_rotation_policy(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        policy: Option<ScopeRotationPolicy>,
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .set_rotation_policy(session_id, scope_id, policy)?;
+        self.flush_pending().await
+    }
+
+    pub fn due_rotations(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<DueRotationsResponse, KeyServiceError> {
+        self.inner.due_rotations(session_id)
+    }
+
+    pub fn open_scope(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: ScopeId,
+        scope_epoch: ScopeEpoch,
+    ) -> Result<OpenScopeResponse, KeyServiceError> {
         self.inner.open_scope(session_id, scope_id, scope_epoch)
     }
 
@@ -276,6 +1038,41 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         Ok(response)
     }
 
+    pub async fn open_resource_with_ops(
+        &mut self,
+        session_id: &SessionId,
+        scope_key_handle: &KeyHandle,
+        grant_cbor: &[u8],
+        ops: CapabilityOps,
+    ) -> Result<OpenResourceResponse, KeyServiceError> {
+        let response =
+            self.inner
+                .open_resource_with_ops(session_id, scope_key_handle, grant_cbor, ops)?;
+        self.flush_pending().await?;
+        Ok(response)
+    }
+
+    pub fn derive_subkey(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        path: &[&[u8]],
+        len: usize,
+    ) -> Result<Zeroizing<Vec<u8>>, KeyServiceError> {
+        self.inner
+            .derive_subkey(session_id, resource_key_handle, path, len)
+    }
+
+    pub fn reissue_grant(
+        &mut self,
+        session_id: &SessionId,
+        grant_cbor: &[u8],
+        scope_key_handle: &KeyHandle,
+    ) -> Result<ReissueGrantResponse, KeyServiceError> {
+        self.inner
+            .reissue_grant(session_id, grant_cbor, scope_key_handle)
+    }
+
     pub fn close_handle(
         &mut self,
         session_id: &SessionId,
@@ -284,6 +1081,33 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         self.inner.close_handle(session_id, key_handle)
     }
 
+    pub fn mint_capability(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        request: &CapabilityRequest,
+    ) -> Result<MintCapabilityResponse, KeyServiceError> {
+        self.inner
+            .mint_capability(session_id, resource_key_handle, request)
+    }
+
+    pub fn redeem_capability(
+        &mut self,
+        session_id: &SessionId,
+        token: &[u8],
+    ) -> Result<RedeemCapabilityResponse, KeyServiceError> {
+        self.inner.redeem_capability(session_id, token)
+    }
+
+    pub fn enable_resource_ratchet(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .enable_resource_ratchet(session_id, resource_key_handle)
+    }
+
     pub fn encrypt(
         &mut self,
         session_id: &SessionId,
@@ -306,6 +1130,87 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
             .decrypt(session_id, resource_key_handle, aad, ciphertext)
     }
 
+    pub fn encrypt_into(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        plaintext: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .encrypt_into(session_id, resource_key_handle, aad, plaintext, out)
+    }
+
+    pub fn decrypt_into(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        ciphertext: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .decrypt_into(session_id, resource_key_handle, aad, ciphertext, out)
+    }
+
+    pub fn encrypt_init(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+    ) -> Result<EncryptInitResponse, KeyServiceError> {
+        self.inner
+            .encrypt_init(session_id, resource_key_handle, aad)
+    }
+
+    pub fn encrypt_push(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<EncryptResponse, KeyServiceError> {
+        self.inner.encrypt_push(session_id, stream_id, chunk)
+    }
+
+    pub fn encrypt_finish(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<EncryptResponse, KeyServiceError> {
+        self.inner.encrypt_finish(session_id, stream_id, chunk)
+    }
+
+    pub fn decrypt_init(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        header: &[u8],
+    ) -> Result<DecryptInitResponse, KeyServiceError> {
+        self.inner
+            .decrypt_init(session_id, resource_key_handle, aad, header)
+    }
+
+    pub fn decrypt_push(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<DecryptResponse, KeyServiceError> {
+        self.inner.decrypt_push(session_id, stream_id, chunk)
+    }
+
+    pub fn decrypt_finish(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<DecryptResponse, KeyServiceError> {
+        self.inner.decrypt_finish(session_id, stream_id, chunk)
+    }
+
     pub fn sign(
         &mut self,
         session_id: &SessionId,
@@ -314,6 +1219,111 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         self.inner.sign(session_id, data)
     }
 
+    pub fn sign_format(
+        &mut self,
+        session_id: &SessionId,
+        format: crate::key_service::SignedFormat,
+        to_be_signed: &[u8],
+    ) -> Result<crate::key_service::SignResponse, KeyServiceError> {
+        self.inner.sign_format(session_id, format, to_be_signed)
+    }
+
+    pub fn start_cosign_enrollment(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(crate::key_service::CosignEnrollment, Vec<u8>), KeyServiceError> {
+        self.inner.start_cosign_enrollment(session_id)
+    }
+
+    pub async fn finish_cosign_enrollment(
+        &mut self,
+        session_id: &SessionId,
+        enrollment: crate::key_service::CosignEnrollment,
+        response: &[u8],
+    ) -> Result<crate::key_service::CosignEnrollResponse, KeyServiceError> {
+        let out = self
+            .inner
+            .finish_cosign_enrollment(session_id, enrollment, response)?;
+        self.flush_pending().await?;
+        Ok(out)
+    }
+
+    pub fn start_cosign(
+        &mut self,
+        session_id: &SessionId,
+        data: &[u8],
+    ) -> Result<(crate::key_service::CosignSigning, Vec<u8>), KeyServiceError> {
+        self.inner.start_cosign(session_id, data)
+    }
+
+    pub fn start_cosign_format(
+        &mut self,
+        session_id: &SessionId,
+        format: crate::key_service::SignedFormat,
+        to_be_signed: &[u8],
+    ) -> Result<(crate::key_service::CosignSigning, Vec<u8>), KeyServiceError> {
+        self.inner
+            .start_cosign_format(session_id, format, to_be_signed)
+    }
+
+    pub fn finish_cosign(
+        &mut self,
+        session_id: &SessionId,
+        pending: crate::key_service::CosignSigning,
+        response: &[u8],
+    ) -> Result<crate::key_service::SignResponse, KeyServiceError> {
+        self.inner.finish_cosign(session_id, pending, response)
+    }
+
+    pub fn verify_user_public_key(
+        &mut self,
+        session_id: &SessionId,
+        user_id: &UserId,
+        public_bytes: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .verify_user_public_key(session_id, user_id, public_bytes)
+    }
+
+    pub async fn add_contact(
+        &mut self,
+        session_id: &SessionId,
+        user_id: &UserId,
+        public_bytes: &[u8],
+        expected_fingerprint: &str,
+    ) -> Result<Contact, KeyServiceError> {
+        let contact =
+            self.inner
+                .add_contact(session_id, user_id, public_bytes, expected_fingerprint)?;
+        self.flush_pending().await?;
+        Ok(contact)
+    }
+
+    pub async fn remove_contact(
+        &mut self,
+        session_id: &SessionId,
+        user_id: &UserId,
+    ) -> Result<bool, KeyServiceError> {
+        let removed = self.inner.remove_contact(session_id, user_id)?;
+        self.flush_pending().await?;
+        Ok(removed)
+    }
+
+    pub fn get_contact(
+        &mut self,
+        session_id: &SessionId,
+        user_id: &UserId,
+    ) -> Result<Contact, KeyServiceError> {
+        self.inner.get_contact(session_id, user_id)
+    }
+
+    pub fn list_contacts(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<ListContactsResponse, KeyServiceError> {
+        self.inner.list_contacts(session_id)
+    }
+
     pub fn verify(
         &mut self,
         scope_id: ScopeId,
@@ -326,8 +1336,46 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
             .verify(scope_id, signer_device_id, data, signature, ciphersuite)
     }
 
-    pub fn export_keyvault(&mut self, session_id: &SessionId) -> Result<Vec<u8>, KeyServiceError> {
-        self.inner.export_keyvault(session_id)
+    pub async fn export_keyvault(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        let blob = self.inner.export_keyvault(session_id)?;
+        self.flush_pending().await?;
+        Ok(blob)
+    }
+
+    pub async fn export_keyvault_compressed(
+        &mut self,
+        session_id: &SessionId,
+        compression: SnapshotCompression,
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        let blob = self
+            .inner
+            .export_keyvault_compressed(session_id, compression)?;
+        self.flush_pending().await?;
+        Ok(blob)
+    }
+
+    pub async fn export_keyvault_filtered(
+        &mut self,
+        session_id: &SessionId,
+        kinds: &[u64],
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        let blob = self.inner.export_keyvault_filtered(session_id, kinds)?;
+        self.flush_pending().await?;
+        Ok(blob)
+    }
+
+    pub async fn export_keyvault_chunked(
+        &mut self,
+        session_id: &SessionId,
+        chunk_len: usize,
+        sink: &mut dyn FnMut(&[u8]) -> Result<(), KeyServiceError>,
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .export_keyvault_chunked(session_id, chunk_len, sink)?;
+        self.flush_pending().await
     }
 
     pub async fn import_keyvault(
@@ -339,20 +1387,214 @@ impl<S: AsyncStorageAdapter, C: ClockAdapter, E: EntropyAdapter> AsyncKeyService
         self.flush_pending().await
     }
 
+    pub async fn import_keyvault_cancellable(
+        &mut self,
+        session_id: &SessionId,
+        blob: &[u8],
+        cancel: &CancellationToken,
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .import_keyvault_cancellable(session_id, blob, cancel)?;
+        self.flush_pending().await
+    }
+
+    pub fn import_keyvault_init(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<ImportKeyVaultInitResponse, KeyServiceError> {
+        self.inner.import_keyvault_init(session_id)
+    }
+
+    pub fn import_keyvault_push(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        self.inner
+            .import_keyvault_push(session_id, stream_id, chunk)
+    }
+
+    pub async fn import_keyvault_finish(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+    ) -> Result<(), KeyServiceError> {
+        self.inner.import_keyvault_finish(session_id, stream_id)?;
+        self.flush_pending().await
+    }
+
+    pub fn get_user_public_key(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<UserPublicKeyResponse, KeyServiceError> {
+        self.inner.get_user_public_key(session_id)
+    }
+
+    pub fn get_device_public_keys(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<DevicePublicKeysResponse, KeyServiceError> {
+        self.inner.get_device_public_keys(session_id)
+    }
+
+    pub fn read_audit_log(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<Vec<AuditEntryV1>, KeyServiceError> {
+        self.inner.read_audit_log(session_id)
+    }
+
+    pub fn verify_audit_log(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<AuditVerifyReport, KeyServiceError> {
+        self.inner.verify_audit_log(session_id)
+    }
+
+    pub fn verify_keyvault(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<VerifyKeyVaultResponse, KeyServiceError> {
+        self.inner.verify_keyvault(session_id)
+    }
+
     pub fn lock(&mut self, session_id: &SessionId) -> Result<(), KeyServiceError> {
         self.inner.lock(session_id)
     }
 
-    async fn flush_pending(&mut self) -> Result<(), KeyServiceError> {
-        let pending = self.buffered.drain_pending();
-        for entry in pending {
-            self.storage
-                .put(&entry.namespace, &entry.key, &entry.value)
+    pub fn sweep_expired_sessions(&mut self) -> usize {
+        self.inner.sweep_expired_sessions()
+    }
+
+    pub fn lock_all(&mut self) -> usize {
+        self.inner.lock_all()
+    }
+
+    pub fn list_sessions(&mut self) -> Vec<SessionSummary> {
+        self.inner.list_sessions()
+    }
+
+    pub fn on_timer(&mut self) {
+        self.inner.on_timer()
+    }
+
+    async fn refresh_cache(&mut self) -> Result<(), KeyServiceError> {
+        let mut entries = Vec::new();
+        for namespace in CACHED_NAMESPACES {
+            entries.extend(load_namespace_entries(&self.storage, namespace).await?);
+        }
+        self.buffered.reload_entries(entries);
+        self.unseal_anchored().await
+    }
+
+    /// Opens the stored head marker with the async anchor so the inner service can read it.
+    async fn unseal_anchored(&mut self) -> Result<(), KeyServiceError> {
+        let Some(anchor) = &self.async_anchor else {
+            return Ok(());
+        };
+        let Some((aad, sealed)) = self.inner.anchored_head_marker()? else {
+            return Ok(());
+        };
+        if self.anchor_bridge.knows(&sealed) {
+            return Ok(());
+        }
+        let plaintext = anchor
+            .unseal(HEAD_MARKER_ANCHOR_LABEL, &aad, &sealed)
+            .await
+            .map_err(KeyServiceError::CryptoError)?;
+        self.anchor_bridge.learn(
+            sealed,
+            AnchoredValue {
+                label: HEAD_MARKER_ANCHOR_LABEL.to_string(),
+                aad,
+                plaintext,
+            },
+        );
+        Ok(())
+    }
+
+    /// Replaces queued stand-ins with bytes sealed by the async anchor. Stand-ins already
+    /// overwritten are dropped; on failure the rest stay queued and nothing is flushed.
+    async fn seal_anchored(&mut self) -> Result<(), KeyServiceError> {
+        let Some(anchor) = &self.async_anchor else {
+            return Ok(());
+        };
+        let mut requests = self.anchor_bridge.take_requests().into_iter();
+        while let Some((stand_in, value)) = requests.next() {
+            if !self.buffered.has_pending_value(&stand_in) {
+                self.anchor_bridge.settle(&stand_in, None);
+                continue;
+            }
+            match anchor
+                .seal(&value.label, &value.aad, &value.plaintext)
                 .await
-                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+            {
+                Ok(sealed) => {
+                    self.buffered.replace_value(&stand_in, &sealed);
+                    self.anchor_bridge.settle(&stand_in, Some(sealed));
+                }
+                Err(e) => {
+                    let mut unsealed = vec![stand_in];
+                    unsealed.extend(requests.map(|(stand_in, _)| stand_in));
+                    self.anchor_bridge.requeue_requests(unsealed);
+                    return Err(KeyServiceError::CryptoError(e));
+                }
+            }
         }
         Ok(())
     }
+
+    /// Writes buffered entries in order of their latest write. On failure the failed entry and
+    /// everything after it stay queued, so durable storage only ever holds a prefix of that order.
+    async fn flush_pending(&mut self) -> Result<(), KeyServiceError> {
+        self.seal_anchored().await?;
+        let mut pending = self.buffered.drain_pending().into_iter();
+        while let Some(entry) = pending.next() {
+            if let Err(err) = put_with_retry(&self.storage, &entry, self.flush_attempts).await {
+                let mut unflushed = vec![entry];
+                unflushed.extend(pending);
+                self.buffered.requeue_front(unflushed);
+                return Err(err);
+            }
+        }
+        self.inner.commit_counter_advance()
+    }
+}
+
+/// Calls `sweep` every `interval` using the caller's timer (e.g. `tokio::time::sleep`) until it
+/// returns `false`, typically once the service it holds a `Weak` to is gone.
+pub async fn run_session_sweeper<Sleep, Fut>(
+    interval: Duration,
+    mut sweep: impl FnMut() -> bool,
+    sleep: Sleep,
+) where
+    Sleep: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    while sweep() {
+        sleep(interval).await;
+    }
+}
+
+async fn put_with_retry<S: AsyncStorageAdapter>(
+    storage: &S,
+    entry: &StorageEntry,
+    attempts: u32,
+) -> Result<(), KeyServiceError> {
+    let mut attempt = 1;
+    loop {
+        match storage
+            .put(&entry.namespace, &entry.key, &entry.value)
+            .await
+        {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= attempts => {
+                return Err(KeyServiceError::StorageError(format!("{e:?}")))
+            }
+            Err(_) => attempt += 1,
+        }
+    }
 }
 
 async fn load_namespace_entries<S: AsyncStorageAdapter>(
@@ -0,0 +1,710 @@
+//! Device attestation: WebAuthn `packed` and `tpm` statements vouching for the authenticator
+//! behind a device that joins a scope.
+//!
+//! A statement is bound to the device's hybrid signing key through its `clientDataHash`, which
+//! must be [`device_attestation_challenge`] for that device and key. Each scope names the
+//! formats, trust roots, and authenticator models it accepts in an [`AttestationPolicy`].
+//! Verifying statements needs the `attestation` feature.
+
+use crate::cbor::{cbor_bytes, cbor_map, cbor_text, encode_canonical_value};
+use crate::ciphersuite::SignerKeys;
+use crate::domains::DEVICE_ATTESTATION_CHALLENGE_V1;
+use crate::error::CoreResult;
+use crate::hash::sha256;
+use crate::types::DeviceId;
+
+#[cfg(feature = "attestation")]
+pub use verify::verify_device_attestation;
+
+/// WebAuthn attestation statement format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttestationFormat {
+    Packed,
+    Tpm,
+}
+
+impl AttestationFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Packed => "packed",
+            Self::Tpm => "tpm",
+        }
+    }
+}
+
+/// What a scope accepts from devices joining it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttestationPolicy {
+    /// Statement formats accepted for the scope.
+    pub formats: Vec<AttestationFormat>,
+    /// DER certificates an attestation chain must end at, or be issued by.
+    pub trust_roots: Vec<Vec<u8>>,
+    /// Accept `packed` statements signed by the credential key itself. These show the device
+    /// holds the key but say nothing about the authenticator.
+    pub allow_self_attestation: bool,
+    /// Authenticator models (AAGUIDs) accepted; empty accepts any model.
+    pub aaguids: Vec<[u8; 16]>,
+}
+
+impl Default for AttestationPolicy {
+    fn default() -> Self {
+        Self {
+            formats: vec![AttestationFormat::Packed, AttestationFormat::Tpm],
+            trust_roots: Vec::new(),
+            allow_self_attestation: false,
+            aaguids: Vec::new(),
+        }
+    }
+}
+
+/// An attestation statement that passed a scope's policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedAttestation {
+    pub format: AttestationFormat,
+    pub aaguid: [u8; 16],
+    pub self_attested: bool,
+}
+
+/// The `clientDataHash` a device's attestation statement must carry:
+/// `SHA-256({0: tag, 1: device id, 2: ed25519 key, 3: ML-DSA key})`.
+pub fn device_attestation_challenge(
+    device_id: &DeviceId,
+    signer: &SignerKeys,
+) -> CoreResult<[u8; 32]> {
+    let value = cbor_map(vec![
+        (0, cbor_text(DEVICE_ATTESTATION_CHALLENGE_V1)),
+        (1, cbor_text(&device_id.0)),
+        (2, cbor_bytes(&signer.ed25519_pub)),
+        (3, cbor_bytes(&signer.mldsa_pub)),
+    ]);
+    Ok(sha256(&encode_canonical_value(&value)?))
+}
+
+#[cfg(feature = "attestation")]
+mod verify {
+    use ciborium::value::Value;
+    use p256::ecdsa::{Signature as EcdsaSignature, VerifyingKey as P256VerifyingKey};
+    use rsa::pkcs1::DecodeRsaPublicKey;
+    use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+    use sha1::Sha1;
+    use sha2::{Digest, Sha256};
+    use signature::Verifier;
+    use x509_cert::certificate::Version;
+    use x509_cert::der::asn1::OctetString;
+    use x509_cert::der::{Decode, Encode};
+    use x509_cert::ext::pkix::{BasicConstraints, ExtendedKeyUsage};
+    use x509_cert::spki::ObjectIdentifier;
+    use x509_cert::Certificate;
+
+    use super::{AttestationFormat, AttestationPolicy, VerifiedAttestation};
+    use crate::cbor::{cbor_item_len, decode_canonical_value, CborLimits};
+
+    type Reason = &'static str;
+
+    const COSE_ALG_ES256: i64 = -7;
+    const COSE_ALG_EDDSA: i64 = -8;
+    const COSE_ALG_RS256: i64 = -257;
+    const COSE_ALG_RS1: i64 = -65535;
+
+    const AUTH_DATA_FLAG_AT: u8 = 0x40;
+    const MAX_CHAIN_LEN: usize = 5;
+
+    const OID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+    const OID_P256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+    const OID_RSA_ENCRYPTION: ObjectIdentifier =
+        ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+    const OID_ECDSA_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+    const OID_RSA_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+    const OID_BASIC_CONSTRAINTS: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.19");
+    const OID_EXT_KEY_USAGE: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.37");
+    const OID_FIDO_AAGUID: ObjectIdentifier =
+        ObjectIdentifier::new_unwrap("1.3.6.1.4.1.45724.1.1.4");
+    const OID_TCG_AIK_CERTIFICATE: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.23.133.8.3");
+
+    const TPM_GENERATED_VALUE: u32 = 0xff54_4347;
+    const TPM_ST_ATTEST_CERTIFY: u16 = 0x8017;
+    const TPM_ALG_RSA: u16 = 0x0001;
+    const TPM_ALG_SHA1: u16 = 0x0004;
+    const TPM_ALG_SHA256: u16 = 0x000b;
+    const TPM_ALG_NULL: u16 = 0x0010;
+    const TPM_ALG_ECC: u16 = 0x0023;
+    const TPM_ECC_NIST_P256: u16 = 0x0003;
+    const TPM_RSA_DEFAULT_EXPONENT: u32 = 65_537;
+
+    /// Checks `attestation_object` (the CBOR `{fmt, attStmt, authData}` from `makeCredential`)
+    /// against `policy`, with `client_data_hash` as the statement's `clientDataHash`.
+    pub fn verify_device_attestation(
+        attestation_object: &[u8],
+        client_data_hash: &[u8; 32],
+        policy: &AttestationPolicy,
+        now_ms: u64,
+        limits: &CborLimits,
+    ) -> Result<VerifiedAttestation, Reason> {
+        let value = decode_canonical_value(attestation_object, limits)
+            .map_err(|_| "attestation object malformed")?;
+        let format = match text_entry(&value, "fmt") {
+            Some(Value::Text(fmt)) if fmt == "packed" => AttestationFormat::Packed,
+            Some(Value::Text(fmt)) if fmt == "tpm" => AttestationFormat::Tpm,
+            Some(Value::Text(_)) => return Err("attestation format not supported"),
+            _ => return Err("attestation object malformed"),
+        };
+        let statement = text_entry(&value, "attStmt")
+            .filter(|statement| matches!(statement, Value::Map(_)))
+            .ok_or("attestation object malformed")?;
+        let Some(Value::Bytes(auth_data)) = text_entry(&value, "authData") else {
+            return Err("attestation object malformed");
+        };
+        if !policy.formats.contains(&format) {
+            return Err("attestation format not allowed for the scope");
+        }
+        let credential = parse_auth_data(auth_data, limits)?;
+        if !policy.aaguids.is_empty() && !policy.aaguids.contains(&credential.aaguid) {
+            return Err("authenticator model not allowed for the scope");
+        }
+        let signed = [auth_data.as_slice(), client_data_hash].concat();
+        let self_attested = match format {
+            AttestationFormat::Packed => {
+                verify_packed(statement, &credential, &signed, policy, now_ms)?
+            }
+            AttestationFormat::Tpm => {
+                verify_tpm(statement, &credential, &signed, policy, now_ms)?;
+                false
+            }
+        };
+        Ok(VerifiedAttestation {
+            format,
+            aaguid: credential.aaguid,
+            self_attested,
+        })
+    }
+
+    /// WebAuthn §8.2: a signature by the attestation certificate, or by the credential key
+    /// itself when there is no `x5c`. Returns whether the statement was self attestation.
+    fn verify_packed(
+        statement: &Value,
+        credential: &AttestedCredential,
+        signed: &[u8],
+        policy: &AttestationPolicy,
+        now_ms: u64,
+    ) -> Result<bool, Reason> {
+        let alg = int_entry(statement, "alg").ok_or("attestation statement malformed")?;
+        let signature = bytes_entry(statement, "sig").ok_or("attestation statement malformed")?;
+        let Some(x5c) = text_entry(statement, "x5c") else {
+            if !policy.allow_self_attestation {
+                return Err("self attestation not allowed for the scope");
+            }
+            if alg != credential.alg {
+                return Err("self attestation algorithm differs from the credential key");
+            }
+            if !credential.key.public_key()?.verify(alg, signed, signature) {
+                return Err("attestation signature invalid");
+            }
+            return Ok(true);
+        };
+        let chain = parse_chain(x5c)?;
+        let leaf = &chain[0];
+        if !certificate_key(leaf)?.verify(alg, signed, signature) {
+            return Err("attestation signature invalid");
+        }
+        check_attestation_certificate(leaf, &credential.aaguid)?;
+        verify_chain(&chain, policy, now_ms)?;
+        Ok(false)
+    }
+
+    /// WebAuthn §8.3: the TPM certifies a key matching the credential key, over the hash of
+    /// the signed data, with an attestation identity key (AIK) whose certificate chains to a
+    /// trust root.
+    fn verify_tpm(
+        statement: &Value,
+        credential: &AttestedCredential,
+        signed: &[u8],
+        policy: &AttestationPolicy,
+        now_ms: u64,
+    ) -> Result<(), Reason> {
+        if !matches!(text_entry(statement, "ver"), Some(Value::Text(ver)) if ver == "2.0") {
+            return Err("tpm version not supported");
+        }
+        let alg = int_entry(statement, "alg").ok_or("attestation statement malformed")?;
+        let signature = bytes_entry(statement, "sig").ok_or("attestation statement malformed")?;
+        let cert_info =
+            bytes_entry(statement, "certInfo").ok_or("attestation statement malformed")?;
+        let pub_area =
+            bytes_entry(statement, "pubArea").ok_or("attestation statement malformed")?;
+        let x5c = text_entry(statement, "x5c").ok_or("tpm attestation needs a certificate")?;
+
+        let public = parse_tpm_public(pub_area)?;
+        if public.key != credential.key {
+            return Err("tpm key differs from the credential key");
+        }
+        let attest = parse_tpm_attest(cert_info)?;
+        if attest.magic != TPM_GENERATED_VALUE || attest.kind != TPM_ST_ATTEST_CERTIFY {
+            return Err("tpm attestation is not a certification");
+        }
+        if digest_for_alg(alg, signed).as_deref() != Some(attest.extra_data) {
+            return Err("tpm attestation not bound to the device key");
+        }
+        let name = tpm_name(public.name_alg, pub_area).ok_or("tpm name algorithm not supported")?;
+        if attest.attested_name != name.as_slice() {
+            return Err("tpm attested name differs from the key");
+        }
+
+        let chain = parse_chain(x5c)?;
+        let leaf = &chain[0];
+        if !certificate_key(leaf)?.verify(alg, cert_info, signature) {
+            return Err("attestation signature invalid");
+        }
+        check_attestation_certificate(leaf, &credential.aaguid)?;
+        if !leaf.tbs_certificate.subject.0.is_empty() {
+            return Err("tpm attestation certificate subject must be empty");
+        }
+        let is_aik = extension(leaf, OID_EXT_KEY_USAGE)
+            .and_then(|value| ExtendedKeyUsage::from_der(value).ok())
+            .is_some_and(|usage| usage.0.contains(&OID_TCG_AIK_CERTIFICATE));
+        if !is_aik {
+            return Err("tpm attestation certificate is not an AIK certificate");
+        }
+        verify_chain(&chain, policy, now_ms)
+    }
+
+    struct AttestedCredential {
+        aaguid: [u8; 16],
+        alg: i64,
+        key: CoseKey,
+    }
+
+    #[derive(PartialEq, Eq)]
+    enum CoseKey {
+        P256 { x: Vec<u8>, y: Vec<u8> },
+        Ed25519(Vec<u8>),
+        Rsa { n: Vec<u8>, e: Vec<u8> },
+    }
+
+    impl CoseKey {
+        fn parse(value: &Value) -> Result<(i64, Self), Reason> {
+            const MALFORMED: Reason = "credential public key malformed";
+            let alg = int_label(value, 3).ok_or(MALFORMED)?;
+            let bytes = |label| bytes_label(value, label).ok_or(MALFORMED);
+            let key = match (int_label(value, 1), int_label(value, -1)) {
+                // EC2 on P-256
+                (Some(2), Some(1)) => Self::P256 {
+                    x: fixed(bytes(-2)?, 32).ok_or(MALFORMED)?,
+                    y: fixed(bytes(-3)?, 32).ok_or(MALFORMED)?,
+                },
+                // OKP on Ed25519
+                (Some(1), Some(6)) => Self::Ed25519(fixed(bytes(-2)?, 32).ok_or(MALFORMED)?),
+                (Some(3), _) => Self::Rsa {
+                    n: strip_leading_zeros(bytes(-1)?),
+                    e: strip_leading_zeros(bytes(-2)?),
+                },
+                _ => return Err("credential key type not supported"),
+            };
+            Ok((alg, key))
+        }
+
+        fn public_key(&self) -> Result<PublicKey, Reason> {
+            const MALFORMED: Reason = "credential public key malformed";
+            match self {
+                Self::P256 { x, y } => {
+                    let point = [&[0x04][..], x, y].concat();
+                    P256VerifyingKey::from_sec1_bytes(&point)
+                        .map(PublicKey::P256)
+                        .map_err(|_| MALFORMED)
+                }
+                Self::Ed25519(x) => {
+                    let bytes: [u8; 32] = x.as_slice().try_into().map_err(|_| MALFORMED)?;
+                    ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+                        .map(PublicKey::Ed25519)
+                        .map_err(|_| MALFORMED)
+                }
+                Self::Rsa { n, e } => {
+                    RsaPublicKey::new(BigUint::from_bytes_be(n), BigUint::from_bytes_be(e))
+                        .map(PublicKey::Rsa)
+                        .map_err(|_| MALFORMED)
+                }
+            }
+        }
+    }
+
+    enum PublicKey {
+        P256(P256VerifyingKey),
+        Ed25519(ed25519_dalek::VerifyingKey),
+        Rsa(RsaPublicKey),
+    }
+
+    impl PublicKey {
+        /// Whether `signature` is this key's signature over `message` under COSE `alg`.
+        fn verify(&self, alg: i64, message: &[u8], signature: &[u8]) -> bool {
+            match (self, alg) {
+                (Self::P256(key), COSE_ALG_ES256) => EcdsaSignature::from_der(signature)
+                    .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+                (Self::Ed25519(key), COSE_ALG_EDDSA) => {
+                    ed25519_dalek::Signature::from_slice(signature)
+                        .is_ok_and(|signature| key.verify_strict(message, &signature).is_ok())
+                }
+                (Self::Rsa(key), COSE_ALG_RS256) => key
+                    .verify(
+                        Pkcs1v15Sign::new::<Sha256>(),
+                        &Sha256::digest(message),
+                        signature,
+                    )
+                    .is_ok(),
+                (Self::Rsa(key), COSE_ALG_RS1) => key
+                    .verify(
+                        Pkcs1v15Sign::new::<Sha1>(),
+                        &Sha1::digest(message),
+                        signature,
+                    )
+                    .is_ok(),
+                _ => false,
+            }
+        }
+    }
+
+    /// `rpIdHash(32) || flags || signCount(4) || aaguid(16) || credentialIdLength(2) ||
+    /// credentialId || COSE public key || [extensions]`.
+    fn parse_auth_data(
+        auth_data: &[u8],
+        limits: &CborLimits,
+    ) -> Result<AttestedCredential, Reason> {
+        const HEADER_LEN: usize = 37;
+        if auth_data.len() < HEADER_LEN + 18 || auth_data[32] & AUTH_DATA_FLAG_AT == 0 {
+            return Err("authenticator data has no attested credential");
+        }
+        let rest = &auth_data[HEADER_LEN..];
+        let mut aaguid = [0u8; 16];
+        aaguid.copy_from_slice(&rest[..16]);
+        let key_start = 18 + u16::from_be_bytes([rest[16], rest[17]]) as usize;
+        let key_bytes = rest
+            .get(key_start..)
+            .ok_or("authenticator data malformed")?;
+        let key_len = cbor_item_len(key_bytes)
+            .ok()
+            .flatten()
+            .ok_or("credential public key malformed")?;
+        let cose = decode_canonical_value(&key_bytes[..key_len], limits)
+            .map_err(|_| "credential public key malformed")?;
+        let (alg, key) = CoseKey::parse(&cose)?;
+        Ok(AttestedCredential { aaguid, alg, key })
+    }
+
+    /// Requirements shared by packed and TPM attestation certificates.
+    fn check_attestation_certificate(leaf: &Certificate, aaguid: &[u8; 16]) -> Result<(), Reason> {
+        if leaf.tbs_certificate.version != Version::V3 {
+            return Err("attestation certificate is not X.509 v3");
+        }
+        if is_ca(leaf) {
+            return Err("attestation certificate is a CA");
+        }
+        if let Some(value) = extension(leaf, OID_FIDO_AAGUID) {
+            let certified =
+                OctetString::from_der(value).map_err(|_| "attestation certificate malformed")?;
+            if certified.as_bytes() != aaguid {
+                return Err("attestation certificate names another authenticator model");
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_chain(x5c: &Value) -> Result<Vec<Certificate>, Reason> {
+        const MALFORMED: Reason = "attestation certificate malformed";
+        let Value::Array(items) = x5c else {
+            return Err(MALFORMED);
+        };
+        if items.is_empty() || items.len() > MAX_CHAIN_LEN {
+            return Err("attestation certificate chain length");
+        }
+        items
+            .iter()
+            .map(|item| match item {
+                Value::Bytes(der) => Certificate::from_der(der).map_err(|_| MALFORMED),
+                _ => Err(MALFORMED),
+            })
+            .collect()
+    }
+
+    /// Each certificate is in its validity period and issued by the next, intermediates are
+    /// CAs, and the last is a trust root or issued by one.
+    fn verify_chain(
+        chain: &[Certificate],
+        policy: &AttestationPolicy,
+        now_ms: u64,
+    ) -> Result<(), Reason> {
+        let roots = policy
+            .trust_roots
+            .iter()
+            .map(|der| Certificate::from_der(der).map_err(|_| "trust root malformed"))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (index, certificate) in chain.iter().enumerate() {
+            if !is_valid_at(certificate, now_ms) {
+                return Err("attestation certificate outside its validity period");
+            }
+            if index > 0 && !is_ca(certificate) {
+                return Err("intermediate certificate is not a CA");
+            }
+            if let Some(issuer) = chain.get(index + 1) {
+                if !issued_by(certificate, issuer) {
+                    return Err("attestation certificate chain broken");
+                }
+            }
+        }
+        let last = &chain[chain.len() - 1];
+        let anchored = roots
+            .iter()
+            .any(|root| root == last || (is_valid_at(root, now_ms) && issued_by(last, root)));
+        if !anchored {
+            return Err("attestation certificate chain not anchored at a trust root");
+        }
+        Ok(())
+    }
+
+    fn issued_by(certificate: &Certificate, issuer: &Certificate) -> bool {
+        let alg = match certificate.signature_algorithm.oid {
+            OID_ECDSA_SHA256 => COSE_ALG_ES256,
+            OID_RSA_SHA256 => COSE_ALG_RS256,
+            _ => return false,
+        };
+        let Ok(tbs) = certificate.tbs_certificate.to_der() else {
+            return false;
+        };
+        certificate.tbs_certificate.issuer == issuer.tbs_certificate.subject
+            && certificate_key(issuer)
+                .is_ok_and(|key| key.verify(alg, &tbs, certificate.signature.raw_bytes()))
+    }
+
+    fn certificate_key(certificate: &Certificate) -> Result<PublicKey, Reason> {
+        const MALFORMED: Reason = "certificate public key malformed";
+        let spki = &certificate.tbs_certificate.subject_public_key_info;
+        let bits = spki.subject_public_key.as_bytes().ok_or(MALFORMED)?;
+        let curve = spki
+            .algorithm
+            .parameters
+            .as_ref()
+            .and_then(|parameters| parameters.decode_as::<ObjectIdentifier>().ok());
+        match spki.algorithm.oid {
+            OID_EC_PUBLIC_KEY if curve == Some(OID_P256) => P256VerifyingKey::from_sec1_bytes(bits)
+                .map(PublicKey::P256)
+                .map_err(|_| MALFORMED),
+            OID_RSA_ENCRYPTION => RsaPublicKey::from_pkcs1_der(bits)
+                .map(PublicKey::Rsa)
+                .map_err(|_| MALFORMED),
+            _ => Err("certificate key type not supported"),
+        }
+    }
+
+    fn is_valid_at(certificate: &Certificate, now_ms: u64) -> bool {
+        let validity = &certificate.tbs_certificate.validity;
+        let now = u128::from(now_ms);
+        validity.not_before.to_unix_duration().as_millis() <= now
+            && now <= validity.not_after.to_unix_duration().as_millis()
+    }
+
+    fn is_ca(certificate: &Certificate) -> bool {
+        extension(certificate, OID_BASIC_CONSTRAINTS)
+            .and_then(|value| BasicConstraints::from_der(value).ok())
+            .is_some_and(|constraints| constraints.ca)
+    }
+
+    fn extension(certificate: &Certificate, oid: ObjectIdentifier) -> Option<&[u8]> {
+        certificate
+            .tbs_certificate
+            .extensions
+            .as_ref()?
+            .iter()
+            .find(|extension| extension.extn_id == oid)
+            .map(|extension| extension.extn_value.as_bytes())
+    }
+
+    struct TpmPublic {
+        name_alg: u16,
+        key: CoseKey,
+    }
+
+    struct TpmAttest<'a> {
+        magic: u32,
+        kind: u16,
+        extra_data: &'a [u8],
+        attested_name: &'a [u8],
+    }
+
+    /// `TPMT_PUBLIC` for an RSA or NIST P-256 key without a symmetric scheme.
+    fn parse_tpm_public(pub_area: &[u8]) -> Result<TpmPublic, Reason> {
+        let mut reader = TpmReader(pub_area);
+        let kind = reader.u16()?;
+        let name_alg = reader.u16()?;
+        let _object_attributes = reader.u32()?;
+        let _auth_policy = reader.sized()?;
+        if reader.u16()? != TPM_ALG_NULL {
+            return Err("tpm key parameters not supported");
+        }
+        reader.scheme()?;
+        let key = match kind {
+            TPM_ALG_RSA => {
+                let _key_bits = reader.u16()?;
+                let exponent = match reader.u32()? {
+                    0 => TPM_RSA_DEFAULT_EXPONENT,
+                    exponent => exponent,
+                };
+                CoseKey::Rsa {
+                    n: strip_leading_zeros(reader.sized()?),
+                    e: strip_leading_zeros(&exponent.to_be_bytes()),
+                }
+            }
+            TPM_ALG_ECC => {
+                if reader.u16()? != TPM_ECC_NIST_P256 {
+                    return Err("tpm key parameters not supported");
+                }
+                reader.scheme()?;
+                CoseKey::P256 {
+                    x: reader.sized()?.to_vec(),
+                    y: reader.sized()?.to_vec(),
+                }
+            }
+            _ => return Err("tpm key type not supported"),
+        };
+        reader.finish()?;
+        Ok(TpmPublic { name_alg, key })
+    }
+
+    /// `TPMS_ATTEST` carrying `TPMS_CERTIFY_INFO`.
+    fn parse_tpm_attest(cert_info: &[u8]) -> Result<TpmAttest<'_>, Reason> {
+        let mut reader = TpmReader(cert_info);
+        let magic = reader.u32()?;
+        let kind = reader.u16()?;
+        let _qualified_signer = reader.sized()?;
+        let extra_data = reader.sized()?;
+        // clockInfo (17 bytes) and firmwareVersion (8 bytes)
+        reader.take(25)?;
+        let attested_name = reader.sized()?;
+        let _qualified_name = reader.sized()?;
+        reader.finish()?;
+        Ok(TpmAttest {
+            magic,
+            kind,
+            extra_data,
+            attested_name,
+        })
+    }
+
+    /// The TPM name of a key: its name algorithm followed by that algorithm's hash of the
+    /// public area.
+    fn tpm_name(name_alg: u16, pub_area: &[u8]) -> Option<Vec<u8>> {
+        let digest = match name_alg {
+            TPM_ALG_SHA256 => Sha256::digest(pub_area).to_vec(),
+            TPM_ALG_SHA1 => Sha1::digest(pub_area).to_vec(),
+            _ => return None,
+        };
+        Some([&name_alg.to_be_bytes()[..], &digest].concat())
+    }
+
+    fn digest_for_alg(alg: i64, data: &[u8]) -> Option<Vec<u8>> {
+        match alg {
+            COSE_ALG_ES256 | COSE_ALG_RS256 => Some(Sha256::digest(data).to_vec()),
+            COSE_ALG_RS1 => Some(Sha1::digest(data).to_vec()),
+            _ => None,
+        }
+    }
+
+    /// Big-endian reader over TPM 2.0 marshalled structures.
+    struct TpmReader<'a>(&'a [u8]);
+
+    impl<'a> TpmReader<'a> {
+        fn take(&mut self, len: usize) -> Result<&'a [u8], Reason> {
+            if self.0.len() < len {
+                return Err("tpm structure truncated");
+            }
+            let (head, rest) = self.0.split_at(len);
+            self.0 = rest;
+            Ok(head)
+        }
+
+        fn u16(&mut self) -> Result<u16, Reason> {
+            let bytes = self.take(2)?;
+            Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+        }
+
+        fn u32(&mut self) -> Result<u32, Reason> {
+            let bytes = self.take(4)?;
+            Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+
+        /// A `TPM2B_*`: a 16-bit length and that many bytes.
+        fn sized(&mut self) -> Result<&'a [u8], Reason> {
+            let len = self.u16()? as usize;
+            self.take(len)
+        }
+
+        /// A signing or KDF scheme: an algorithm, then its hash algorithm unless it is null.
+        fn scheme(&mut self) -> Result<(), Reason> {
+            if self.u16()? != TPM_ALG_NULL {
+                self.u16()?;
+            }
+            Ok(())
+        }
+
+        fn finish(&self) -> Result<(), Reason> {
+            if self.0.is_empty() {
+                Ok(())
+            } else {
+                Err("tpm structure has trailing bytes")
+            }
+        }
+    }
+
+    fn text_entry<'a>(map: &'a Value, key: &str) -> Option<&'a Value> {
+        match map {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, Value::Text(name) if name == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn int_entry(map: &Value, key: &str) -> Option<i64> {
+        as_i64(text_entry(map, key)?)
+    }
+
+    fn bytes_entry<'a>(map: &'a Value, key: &str) -> Option<&'a [u8]> {
+        match text_entry(map, key)? {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    fn int_label(map: &Value, label: i64) -> Option<i64> {
+        as_i64(label_entry(map, label)?)
+    }
+
+    fn bytes_label(map: &Value, label: i64) -> Option<&[u8]> {
+        match label_entry(map, label)? {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    fn label_entry(map: &Value, label: i64) -> Option<&Value> {
+        match map {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| as_i64(k) == Some(label))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_i64(value: &Value) -> Option<i64> {
+        match value {
+            Value::Integer(i) => i64::try_from(*i).ok(),
+            _ => None,
+        }
+    }
+
+    fn fixed(bytes: &[u8], len: usize) -> Option<Vec<u8>> {
+        (bytes.len() == len).then(|| bytes.to_vec())
+    }
+
+    fn strip_leading_zeros(bytes: &[u8]) -> Vec<u8> {
+        let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+        bytes[start..].to_vec()
+    }
+}
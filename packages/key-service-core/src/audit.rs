@@ -0,0 +1,207 @@
+//! Device-signed, hash-chained audit log of sensitive Key Service operations.
+
+use crate::cbor::{
+    as_map, cbor_bytes, cbor_map, cbor_text, cbor_uint, decode_canonical_value,
+    encode_canonical_value, opt_bytes, opt_text, req_bytes, req_text, req_uint, CborLimits,
+};
+use crate::ciphersuite::{hybrid_verify_or_unbound, SignerKeys};
+use crate::crypto::ct_eq;
+use crate::domains::SIG_AUDIT_ENTRY_V1;
+use crate::error::{CoreError, CoreResult};
+use crate::hash::sha256;
+use crate::types::DeviceId;
+
+/// Storage namespace holding `head` and `entry:{seq}` keys.
+pub const AUDIT_NAMESPACE: &str = "audit";
+pub const AUDIT_HEAD_KEY: &str = "head";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AuditEventKind {
+    Unlock,
+    StepUp,
+    ExportKeyVault,
+    ImportKeyVault,
+    ChangePassphrase,
+    IngestKeyEnvelope,
+    SignerChange,
+    RepairKeyVault,
+    CgkaCommit,
+    EnableKmsWrap,
+    EnableDeviceShares,
+    SetRotationPolicy,
+    ContactChange,
+}
+
+impl AuditEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventKind::Unlock => "unlock",
+            AuditEventKind::StepUp => "step-up",
+            AuditEventKind::ExportKeyVault => "export-keyvault",
+            AuditEventKind::ImportKeyVault => "import-keyvault",
+            AuditEventKind::ChangePassphrase => "change-passphrase",
+            AuditEventKind::IngestKeyEnvelope => "ingest-key-envelope",
+            AuditEventKind::SignerChange => "signer-change",
+            AuditEventKind::RepairKeyVault => "repair-keyvault",
+            AuditEventKind::CgkaCommit => "cgka-commit",
+            AuditEventKind::EnableKmsWrap => "enable-kms-wrap",
+            AuditEventKind::EnableDeviceShares => "enable-device-shares",
+            AuditEventKind::SetRotationPolicy => "set-rotation-policy",
+            AuditEventKind::ContactChange => "contact-change",
+        }
+    }
+}
+
+impl TryFrom<&str> for AuditEventKind {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "unlock" => Ok(AuditEventKind::Unlock),
+            "step-up" => Ok(AuditEventKind::StepUp),
+            "export-keyvault" => Ok(AuditEventKind::ExportKeyVault),
+            "import-keyvault" => Ok(AuditEventKind::ImportKeyVault),
+            "change-passphrase" => Ok(AuditEventKind::ChangePassphrase),
+            "ingest-key-envelope" => Ok(AuditEventKind::IngestKeyEnvelope),
+            "signer-change" => Ok(AuditEventKind::SignerChange),
+            "repair-keyvault" => Ok(AuditEventKind::RepairKeyVault),
+            "cgka-commit" => Ok(AuditEventKind::CgkaCommit),
+            "enable-kms-wrap" => Ok(AuditEventKind::EnableKmsWrap),
+            "enable-device-shares" => Ok(AuditEventKind::EnableDeviceShares),
+            "set-rotation-policy" => Ok(AuditEventKind::SetRotationPolicy),
+            "contact-change" => Ok(AuditEventKind::ContactChange),
+            _ => Err(format!("unknown audit event kind: {value}")),
+        }
+    }
+}
+
+/// One audit entry; `prev_hash` is the SHA-256 of the previous encoded entry (empty for seq 1).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntryV1 {
+    pub seq: u64,
+    pub prev_hash: Vec<u8>,
+    pub at_ms: u64,
+    pub kind: AuditEventKind,
+    pub detail: String,
+    pub signer_device_id: Option<DeviceId>,
+    pub signature: Option<Vec<u8>>,
+}
+
+impl AuditEntryV1 {
+    pub fn to_be_signed_bytes(&self) -> CoreResult<Vec<u8>> {
+        encode_canonical_value(&cbor_map(self.signed_entries()))
+    }
+
+    /// Hash the next entry chains to.
+    pub fn entry_hash(&self) -> CoreResult<Vec<u8>> {
+        Ok(sha256(&encode_audit_entry_v1(self)?).to_vec())
+    }
+
+    fn signed_entries(&self) -> Vec<(u64, ciborium::value::Value)> {
+        let mut entries = vec![
+            (0, cbor_uint(self.seq)),
+            (1, cbor_bytes(&self.prev_hash)),
+            (2, cbor_uint(self.at_ms)),
+            (3, cbor_text(self.kind.as_str())),
+            (4, cbor_text(&self.detail)),
+        ];
+        if let Some(device_id) = &self.signer_device_id {
+            entries.push((5, cbor_text(&device_id.0)));
+        }
+        entries
+    }
+}
+
+pub fn audit_entry_key(seq: u64) -> String {
+    format!("entry:{seq:020}")
+}
+
+pub fn encode_audit_entry_v1(entry: &AuditEntryV1) -> CoreResult<Vec<u8>> {
+    let mut entries = entry.signed_entries();
+    if let Some(signature) = &entry.signature {
+        entries.push((6, cbor_bytes(signature)));
+    }
+    encode_canonical_value(&cbor_map(entries))
+}
+
+pub fn decode_audit_entry_v1(bytes: &[u8]) -> CoreResult<AuditEntryV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())?;
+    let map = as_map(&value)?;
+    let kind = AuditEventKind::try_from(req_text(map, 3)?.as_str()).map_err(CoreError::Format)?;
+    let entry = AuditEntryV1 {
+        seq: req_uint(map, 0)?,
+        prev_hash: req_bytes(map, 1)?,
+        at_ms: req_uint(map, 2)?,
+        kind,
+        detail: req_text(map, 4)?,
+        signer_device_id: opt_text(map, 5)?.map(DeviceId),
+        signature: opt_bytes(map, 6)?,
+    };
+    if entry.signer_device_id.is_some() != entry.signature.is_some() {
+        return Err(CoreError::Format(
+            "audit signer and signature must be set together".to_string(),
+        ));
+    }
+    Ok(entry)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditVerifyReport {
+    pub ok: bool,
+    pub entry_count: u64,
+    pub unsigned_count: u64,
+    /// Seq of the first entry that failed chaining or signature checks.
+    pub first_invalid_seq: Option<u64>,
+}
+
+/// Checks seq continuity, hash chaining, and device signatures in order.
+///
+/// Unsigned entries (written before a device key existed) are counted, not rejected.
+/// `accept_unbound` also accepts signatures made before entries were bound to
+/// [`SIG_AUDIT_ENTRY_V1`].
+pub fn verify_audit_chain(
+    entries: &[AuditEntryV1],
+    signer_for: impl Fn(&DeviceId) -> Option<SignerKeys>,
+    accept_unbound: bool,
+) -> CoreResult<AuditVerifyReport> {
+    let mut prev_hash = Vec::new();
+    let mut unsigned_count = 0;
+    for (index, entry) in entries.iter().enumerate() {
+        let chained = entry.seq == index as u64 + 1 && ct_eq(&entry.prev_hash, &prev_hash);
+        let signed = match (&entry.signer_device_id, &entry.signature) {
+            (Some(device_id), Some(signature)) => signer_for(device_id)
+                .map(|signer| {
+                    entry.to_be_signed_bytes().map(|bytes| {
+                        hybrid_verify_or_unbound(
+                            SIG_AUDIT_ENTRY_V1,
+                            &bytes,
+                            signature,
+                            &signer,
+                            accept_unbound,
+                        )
+                    })
+                })
+                .transpose()?
+                .unwrap_or(false),
+            _ => {
+                unsigned_count += 1;
+                true
+            }
+        };
+        if !chained || !signed {
+            return Ok(AuditVerifyReport {
+                ok: false,
+                entry_count: entries.len() as u64,
+                unsigned_count,
+                first_invalid_seq: Some(entry.seq),
+            });
+        }
+        prev_hash = entry.entry_hash()?;
+    }
+    Ok(AuditVerifyReport {
+        ok: true,
+        entry_count: entries.len() as u64,
+        unsigned_count,
+        first_invalid_seq: None,
+    })
+}
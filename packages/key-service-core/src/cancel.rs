@@ -0,0 +1,61 @@
+//! Cooperative cancellation for long-running calls (passphrase unlock, vault import).
+
+use crate::adapters::{MaybeSend, MaybeSync};
+use crate::error::{CoreError, CoreResult};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Host-side cancellation check, e.g. a worker reading a `SharedArrayBuffer` flag.
+pub trait CancelProbe: Fn() -> bool + MaybeSend + MaybeSync {}
+
+impl<F: Fn() -> bool + MaybeSend + MaybeSync> CancelProbe for F {}
+
+/// Checked at fixed points of a call; once cancelled, the call stops with
+/// [`KeyServiceError::Cancelled`](crate::key_service::KeyServiceError::Cancelled) before
+/// writing anything.
+///
+/// Clones share state, so one clone can cancel a call running with another.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    probe: Option<Arc<dyn CancelProbe>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that is also cancelled whenever `probe` returns true.
+    pub fn from_probe<P: CancelProbe + 'static>(probe: P) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            probe: Some(Arc::new(probe)),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire) || self.probe.as_ref().is_some_and(|probe| probe())
+    }
+
+    pub(crate) fn check(&self) -> CoreResult<()> {
+        if self.is_cancelled() {
+            return Err(CoreError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.cancelled.load(Ordering::Acquire))
+            .field("probe", &self.probe.is_some())
+            .finish()
+    }
+}
@@ -2,6 +2,7 @@
 
 use ciborium::value::{Integer, Value};
 use std::collections::BTreeMap;
+use zeroize::Zeroize;
 
 use crate::error::{CoreError, CoreResult};
 
@@ -31,18 +32,347 @@ pub fn encode_canonical_value(value: &Value) -> CoreResult<Vec<u8>> {
     Ok(out)
 }
 
+/// How closely [`decode_cbor_value`] holds input to the canonical profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CborStrictness {
+    /// Exactly one canonical item, the form every wire format requires. Violations are
+    /// reported by [`check_canonical`].
+    #[default]
+    Canonical,
+    /// Any single well-formed item: indefinite lengths, unsorted or duplicate map keys, and
+    /// non-minimal heads are accepted. For diagnostic tooling that inspects damaged or foreign
+    /// data; never for input that is then trusted or re-signed.
+    Lenient,
+}
+
+/// Decodes one canonical item. Errors carry the byte offset of the violation, or for
+/// structural limits the key path (`[5][0]: cbor text too large`).
 pub fn decode_canonical_value(bytes: &[u8], limits: &CborLimits) -> CoreResult<Value> {
+    decode_cbor_value(bytes, limits, CborStrictness::Canonical)
+}
+
+/// Decodes one item under `strictness`; the limits apply at every level.
+pub fn decode_cbor_value(
+    bytes: &[u8],
+    limits: &CborLimits,
+    strictness: CborStrictness,
+) -> CoreResult<Value> {
     if bytes.len() > limits.max_bytes {
         return Err(CoreError::Cbor("cbor too large".to_string()));
     }
-    let value: Value =
-        ciborium::de::from_reader(bytes).map_err(|e| CoreError::Cbor(e.to_string()))?;
+    if strictness == CborStrictness::Canonical {
+        check_canonical(bytes, limits)?;
+    }
+    let mut rest = bytes;
+    let value: Value = ciborium::de::from_reader(&mut rest).map_err(|e| match e {
+        ciborium::de::Error::Io(_) => {
+            CoreError::Cbor(format!("truncated cbor at byte {}", bytes.len()))
+        }
+        ciborium::de::Error::Syntax(offset) => {
+            CoreError::Cbor(format!("invalid cbor at byte {offset}"))
+        }
+        ciborium::de::Error::Semantic(Some(offset), msg) => {
+            CoreError::Cbor(format!("{msg} at byte {offset}"))
+        }
+        other => CoreError::Cbor(other.to_string()),
+    })?;
+    if !rest.is_empty() {
+        let offset = bytes.len() - rest.len();
+        return Err(CoreError::Cbor(format!("trailing bytes at byte {offset}")));
+    }
     check_limits(&value, limits, 0)?;
-    let encoded = encode_canonical_value(&value)?;
-    if encoded != bytes {
+    Ok(value)
+}
+
+/// Checks in one pass, without building a value, that `bytes` is exactly one item in the form
+/// [`encode_canonical_value`] writes: minimal heads, definite lengths, map keys in strictly
+/// increasing bytewise order, shortest lossless floats, and no bignum that fits in 64 bits.
+/// The first violation is reported with its byte offset, e.g.
+/// `map key out of order at byte 3`.
+pub fn check_canonical(bytes: &[u8], limits: &CborLimits) -> CoreResult<()> {
+    let mut checker = CanonicalChecker {
+        bytes,
+        pos: 0,
+        max_depth: limits.max_depth,
+    };
+    checker.item(0)?;
+    if checker.pos != bytes.len() {
+        return Err(CoreError::Cbor(format!(
+            "trailing bytes at byte {}",
+            checker.pos
+        )));
+    }
+    Ok(())
+}
+
+struct CanonicalChecker<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    max_depth: usize,
+}
+
+impl CanonicalChecker<'_> {
+    fn violation(&self, problem: &str, at: usize) -> CoreError {
+        CoreError::Cbor(format!("{problem} at byte {at}"))
+    }
+
+    fn truncated(&self) -> CoreError {
+        self.violation("truncated cbor", self.bytes.len())
+    }
+
+    /// Reads the head at the cursor as `(major type, additional info, argument)`.
+    fn head(&mut self) -> CoreResult<(u8, u8, u64)> {
+        let start = self.pos;
+        let initial = *self.bytes.get(start).ok_or_else(|| self.truncated())?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let extra = match info {
+            0..=23 => 0,
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 if matches!(major, 2..=5) => {
+                return Err(self.violation("indefinite-length item", start));
+            }
+            _ => return Err(self.violation("invalid cbor", start)),
+        };
+        let end = start + 1 + extra;
+        let arg_bytes = self
+            .bytes
+            .get(start + 1..end)
+            .ok_or_else(|| self.truncated())?;
+        let arg = if extra == 0 {
+            u64::from(info)
+        } else {
+            arg_bytes
+                .iter()
+                .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte))
+        };
+        // Floats (major 7) carry their bits in the argument and are checked separately.
+        let minimal = match extra {
+            0 => true,
+            1 => arg >= 24,
+            2 => arg > 0xff,
+            4 => arg > 0xffff,
+            _ => arg > 0xffff_ffff,
+        };
+        if major != 7 && !minimal {
+            return Err(self.violation("non-minimal head", start));
+        }
+        self.pos = end;
+        Ok((major, info, arg))
+    }
+
+    fn item(&mut self, depth: usize) -> CoreResult<()> {
+        let start = self.pos;
+        if depth > self.max_depth {
+            return Err(self.violation("cbor depth exceeded", start));
+        }
+        let (major, info, arg) = self.head()?;
+        match major {
+            0 | 1 => {}
+            2 | 3 => {
+                let end = usize::try_from(arg)
+                    .ok()
+                    .and_then(|len| self.pos.checked_add(len))
+                    .filter(|end| *end <= self.bytes.len())
+                    .ok_or_else(|| self.truncated())?;
+                self.pos = end;
+            }
+            4 => {
+                for _ in 0..arg {
+                    self.item(depth + 1)?;
+                }
+            }
+            5 => {
+                let mut previous: Option<&[u8]> = None;
+                for _ in 0..arg {
+                    let key_start = self.pos;
+                    self.item(depth + 1)?;
+                    let key = &self.bytes[key_start..self.pos];
+                    match previous.map(|previous| previous.cmp(key)) {
+                        Some(std::cmp::Ordering::Equal) => {
+                            return Err(self.violation("duplicate map key", key_start));
+                        }
+                        Some(std::cmp::Ordering::Greater) => {
+                            return Err(self.violation("map key out of order", key_start));
+                        }
+                        _ => {}
+                    }
+                    previous = Some(key);
+                    self.item(depth + 1)?;
+                }
+            }
+            6 => {
+                let content = self.pos;
+                self.item(depth + 1)?;
+                if matches!(arg, 2 | 3) && !canonical_bignum(&self.bytes[content..self.pos]) {
+                    return Err(self.violation("non-canonical bignum", start));
+                }
+            }
+            _ => {
+                let canonical = match info {
+                    20..=22 => true,
+                    // The encoder writes every NaN as the half-precision quiet NaN.
+                    25 => arg == 0x7e00 || !is_nan_f16(arg as u16),
+                    26 => {
+                        let value = f32::from_bits(arg as u32);
+                        !value.is_nan() && !fits_f16(value)
+                    }
+                    27 => {
+                        let value = f64::from_bits(arg);
+                        !value.is_nan() && f64::from(value as f32) != value
+                    }
+                    _ => return Err(self.violation("unsupported simple value", start)),
+                };
+                if !canonical {
+                    return Err(self.violation("non-shortest float", start));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A bignum is canonical only if it does not fit a plain integer head and has no leading zero.
+fn canonical_bignum(content: &[u8]) -> bool {
+    match cbor_head(content) {
+        Ok(Some((2, len, head_len))) => len > 8 && content.get(head_len) != Some(&0),
+        _ => false,
+    }
+}
+
+fn is_nan_f16(bits: u16) -> bool {
+    bits & 0x7c00 == 0x7c00 && bits & 0x03ff != 0
+}
+
+/// True when `value` survives a round trip through half precision, so the encoder would
+/// write it in two bytes.
+fn fits_f16(value: f32) -> bool {
+    if value == 0.0 || value.is_infinite() {
+        return true;
+    }
+    let bits = value.to_bits();
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127;
+    let mantissa = (bits & 0x7f_ffff) | 0x80_0000;
+    match exponent {
+        // Normal halves keep 10 of the 23 mantissa bits.
+        -14..=15 => mantissa & 0x1fff == 0,
+        // Subnormal halves lose one more bit per step below the normal range.
+        -24..=-15 => mantissa & ((1u32 << (13 + (-14 - exponent))) - 1) == 0,
+        _ => false,
+    }
+}
+
+/// Prefixes a decode error with where it happened: a format name (`resource_grant`) or a key
+/// path segment (`[3]`). Field errors start with their key path, so the prefixes nest into
+/// `keyvault_header[5][0][4]: expected 12-byte nonce, got 8`.
+///
+/// ```
+/// use mo_key_service_core::cbor::{cbor_context, cbor_map, cbor_uint, req_bytes, as_map};
+///
+/// let value = cbor_map(vec![(11, cbor_uint(1))]);
+/// let err = req_bytes(as_map(&value).unwrap(), 11)
+///     .map_err(cbor_context("resource_grant"))
+///     .unwrap_err();
+/// assert_eq!(err.to_string(), "cbor error: resource_grant[11]: expected bytes");
+/// ```
+pub fn cbor_context(context: &str) -> impl FnOnce(CoreError) -> CoreError + '_ {
+    move |err| {
+        let join = |msg: String| {
+            if msg.starts_with('[') {
+                format!("{context}{msg}")
+            } else {
+                format!("{context}: {msg}")
+            }
+        };
+        match err {
+            CoreError::Cbor(msg) => CoreError::Cbor(join(msg)),
+            CoreError::Format(msg) => CoreError::Format(join(msg)),
+            other => other,
+        }
+    }
+}
+
+/// Length of the first complete data item in `bytes`, or `None` while it is still truncated.
+/// Only definite-length, minimally encoded heads are accepted, as canonical CBOR requires.
+pub fn cbor_item_len(bytes: &[u8]) -> CoreResult<Option<usize>> {
+    let mut pos = 0usize;
+    let mut pending = 1u64;
+    while pending > 0 {
+        let Some((major, arg, head_len)) = cbor_head(&bytes[pos..])? else {
+            return Ok(None);
+        };
+        pos += head_len;
+        pending -= 1;
+        let nested = match major {
+            2 | 3 => {
+                let len = usize::try_from(arg)
+                    .map_err(|_| CoreError::Cbor("cbor length overflow".to_string()))?;
+                if bytes.len() - pos < len {
+                    return Ok(None);
+                }
+                pos += len;
+                0
+            }
+            4 => arg,
+            5 => arg
+                .checked_mul(2)
+                .ok_or_else(|| CoreError::Cbor("cbor length overflow".to_string()))?,
+            6 => 1,
+            _ => 0,
+        };
+        pending = pending
+            .checked_add(nested)
+            .ok_or_else(|| CoreError::Cbor("cbor length overflow".to_string()))?;
+    }
+    Ok(Some(pos))
+}
+
+/// Decodes an item head as `(major type, argument, head length)`.
+pub fn cbor_head(bytes: &[u8]) -> CoreResult<Option<(u8, u64, usize)>> {
+    let Some(&initial) = bytes.first() else {
+        return Ok(None);
+    };
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+    let extra = match info {
+        0..=23 => return Ok(Some((major, u64::from(info), 1))),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err(CoreError::Cbor("non-canonical cbor".to_string())),
+    };
+    if bytes.len() < 1 + extra {
+        return Ok(None);
+    }
+    let arg = bytes[1..1 + extra]
+        .iter()
+        .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+    // Floats (major 7) carry their bits in the argument; everything else must be minimal.
+    let minimal = match extra {
+        1 => arg >= 24,
+        2 => arg > 0xff,
+        4 => arg > 0xffff,
+        _ => arg > 0xffff_ffff,
+    };
+    if major != 7 && !minimal {
         return Err(CoreError::Cbor("non-canonical cbor".to_string()));
     }
-    Ok(value)
+    Ok(Some((major, arg, 1 + extra)))
+}
+
+/// Minimal head for a non-float item, e.g. `encode_cbor_head(4, n)` for an array of `n` items.
+pub fn encode_cbor_head(major: u8, arg: u64) -> Vec<u8> {
+    let initial = major << 5;
+    match arg {
+        0..=23 => vec![initial | arg as u8],
+        24..=0xff => vec![initial | 24, arg as u8],
+        0x100..=0xffff => [&[initial | 25][..], &(arg as u16).to_be_bytes()].concat(),
+        0x1_0000..=0xffff_ffff => [&[initial | 26][..], &(arg as u32).to_be_bytes()].concat(),
+        _ => [&[initial | 27][..], &arg.to_be_bytes()].concat(),
+    }
 }
 
 pub fn cbor_map(entries: Vec<(u64, Value)>) -> Value {
@@ -69,6 +399,22 @@ pub fn cbor_array(items: Vec<Value>) -> Value {
     Value::Array(items)
 }
 
+/// Wipes every byte string nested in `value`; used for values that carried key material.
+pub fn zeroize_value(value: &mut Value) {
+    match value {
+        Value::Bytes(bytes) => bytes.zeroize(),
+        Value::Array(items) => items.iter_mut().for_each(zeroize_value),
+        Value::Map(entries) => {
+            for (key, val) in entries.iter_mut() {
+                zeroize_value(key);
+                zeroize_value(val);
+            }
+        }
+        Value::Tag(_, inner) => zeroize_value(inner),
+        _ => {}
+    }
+}
+
 pub fn as_map(value: &Value) -> CoreResult<&[(Value, Value)]> {
     match value {
         Value::Map(entries) => Ok(entries),
@@ -87,7 +433,7 @@ pub fn req_text(map: &[(Value, Value)], key: u64) -> CoreResult<String> {
     let value = map_get(map, key)?;
     match value {
         Value::Text(text) => Ok(text.clone()),
-        _ => Err(CoreError::Cbor(format!("expected text at key {key}"))),
+        _ => Err(CoreError::Cbor(format!("[{key}]: expected text"))),
     }
 }
 
@@ -95,7 +441,7 @@ pub fn req_bytes(map: &[(Value, Value)], key: u64) -> CoreResult<Vec<u8>> {
     let value = map_get(map, key)?;
     match value {
         Value::Bytes(bytes) => Ok(bytes.clone()),
-        _ => Err(CoreError::Cbor(format!("expected bytes at key {key}"))),
+        _ => Err(CoreError::Cbor(format!("[{key}]: expected bytes"))),
     }
 }
 
@@ -104,8 +450,15 @@ pub fn req_uint(map: &[(Value, Value)], key: u64) -> CoreResult<u64> {
     match value {
         Value::Integer(int) => (*int)
             .try_into()
-            .map_err(|_| CoreError::Cbor(format!("expected u64 at key {key}"))),
-        _ => Err(CoreError::Cbor(format!("expected u64 at key {key}"))),
+            .map_err(|_| CoreError::Cbor(format!("[{key}]: expected u64"))),
+        _ => Err(CoreError::Cbor(format!("[{key}]: expected u64"))),
+    }
+}
+
+pub fn req_array(map: &[(Value, Value)], key: u64) -> CoreResult<&[Value]> {
+    match map_get(map, key)? {
+        Value::Array(items) => Ok(items),
+        _ => Err(CoreError::Cbor(format!("[{key}]: expected array"))),
     }
 }
 
@@ -113,7 +466,7 @@ pub fn opt_bytes(map: &[(Value, Value)], key: u64) -> CoreResult<Option<Vec<u8>>
     match map_get_opt(map, key) {
         None => Ok(None),
         Some(Value::Bytes(bytes)) => Ok(Some(bytes.clone())),
-        Some(_) => Err(CoreError::Cbor(format!("expected bytes at key {key}"))),
+        Some(_) => Err(CoreError::Cbor(format!("[{key}]: expected bytes"))),
     }
 }
 
@@ -121,7 +474,7 @@ pub fn opt_text(map: &[(Value, Value)], key: u64) -> CoreResult<Option<String>>
     match map_get_opt(map, key) {
         None => Ok(None),
         Some(Value::Text(text)) => Ok(Some(text.clone())),
-        Some(_) => Err(CoreError::Cbor(format!("expected text at key {key}"))),
+        Some(_) => Err(CoreError::Cbor(format!("[{key}]: expected text"))),
     }
 }
 
@@ -131,13 +484,13 @@ pub fn opt_uint(map: &[(Value, Value)], key: u64) -> CoreResult<Option<u64>> {
         Some(Value::Integer(int)) => (*int)
             .try_into()
             .map(Some)
-            .map_err(|_| CoreError::Cbor(format!("expected u64 at key {key}"))),
-        Some(_) => Err(CoreError::Cbor(format!("expected u64 at key {key}"))),
+            .map_err(|_| CoreError::Cbor(format!("[{key}]: expected u64"))),
+        Some(_) => Err(CoreError::Cbor(format!("[{key}]: expected u64"))),
     }
 }
 
 fn map_get(map: &[(Value, Value)], key: u64) -> CoreResult<&Value> {
-    map_get_opt(map, key).ok_or_else(|| CoreError::Cbor(format!("missing key {key}")))
+    map_get_opt(map, key).ok_or_else(|| CoreError::Cbor(format!("[{key}]: missing")))
 }
 
 fn map_get_opt(map: &[(Value, Value)], key: u64) -> Option<&Value> {
@@ -192,23 +545,29 @@ fn check_limits(value: &Value, limits: &CborLimits, depth: usize) -> CoreResult<
             }
             for (k, v) in entries {
                 check_limits(k, limits, depth + 1)?;
-                check_limits(v, limits, depth + 1)?;
+                check_limits(v, limits, depth + 1).map_err(cbor_context(&key_segment(k)))?;
             }
         }
         Value::Array(items) => {
             if items.len() > limits.max_items {
                 return Err(CoreError::Cbor("cbor array too large".to_string()));
             }
-            for item in items {
-                check_limits(item, limits, depth + 1)?;
+            for (i, item) in items.iter().enumerate() {
+                check_limits(item, limits, depth + 1).map_err(cbor_context(&format!("[{i}]")))?;
             }
         }
-        Value::Text(text) => {
-            if text.len() > limits.max_text_bytes {
-                return Err(CoreError::Cbor("cbor text too large".to_string()));
-            }
+        Value::Text(text) if text.len() > limits.max_text_bytes => {
+            return Err(CoreError::Cbor("cbor text too large".to_string()));
         }
         _ => {}
     }
     Ok(())
 }
+
+fn key_segment(key: &Value) -> String {
+    match key {
+        Value::Integer(int) => format!("[{}]", i128::from(*int)),
+        Value::Text(text) => format!("[{text:?}]"),
+        _ => "[?]".to_string(),
+    }
+}
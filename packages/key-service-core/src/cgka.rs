@@ -0,0 +1,853 @@
+//! Continuous group key agreement: scope keys from an MLS-style ratchet tree (RFC 9420
+//! TreeKEM) instead of one key envelope per member.
+//!
+//! Leaves hold members' user KEM keys; each parent node holds a hybrid KEM key derived from
+//! a path secret that exactly the members below it know. A commit replaces the committer's
+//! direct path and encrypts each new path secret to the resolution of the copath node beside
+//! it, so a rotation costs `O(log n)` ciphertexts for `n` members. Adding or removing a member
+//! blanks that leaf's direct path, which keeps a removed member's secrets out of every later
+//! epoch. The epoch schedule follows MLS:
+//!
+//! ```text
+//! joiner_secret = HKDF(init_secret || commit_secret)
+//! epoch_secret  = HKDF(joiner_secret, group_context)
+//! scope_key     = HKDF(epoch_secret)        init_secret' = HKDF(epoch_secret)
+//! ```
+//!
+//! The group epoch is the scope epoch, so each commit yields the scope key of the next epoch.
+//! Members a commit adds receive the joiner secret in a [`CgkaWelcomeV1`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use aes_gcm::Aes256Gcm;
+use ciborium::value::Value;
+use zeroize::Zeroizing;
+
+use crate::aad::{aad_cgka_path_secret_v1, aad_cgka_welcome_v1};
+use crate::cbor::{
+    as_array, as_map, cbor_array, cbor_bytes, cbor_map, cbor_text, cbor_uint,
+    decode_canonical_value, encode_canonical_value, opt_text, req_array, req_bytes, req_text,
+    req_uint, CborLimits,
+};
+use crate::ciphersuite::{
+    decode_user_public_bytes, derive_hybrid_kem_wrap_key, derive_kem_keypair,
+    hybrid_kem_encapsulate, HybridKemRecipient, KEM_KEYPAIR_SEED_LEN,
+};
+use crate::crypto::{aead_decrypt, aead_encrypt, ct_eq, random_bytes};
+use crate::domains::{
+    CGKA_COMMIT_V1, CGKA_CONFIRM_V1, CGKA_EPOCH_V1, CGKA_GROUP_CONTEXT_V1, CGKA_INIT_V1,
+    CGKA_JOINER_V1, CGKA_NODE_KEY_V1, CGKA_PATH_V1, CGKA_SCOPE_KEY_V1,
+};
+use crate::error::{CoreError, CoreResult};
+use crate::formats::{
+    CgkaCiphertextV1, CgkaCommitV1, CgkaMemberV1, CgkaPathNodeV1, CgkaWelcomeSecretV1,
+    CgkaWelcomeV1,
+};
+use crate::hash::sha256;
+use crate::types::{
+    AeadId, DeviceId, KemCiphersuiteId, ScopeEpoch, ScopeId, SigCiphersuiteId, UserId,
+};
+
+/// Length of path, init, joiner, and epoch secrets.
+pub const CGKA_SECRET_LEN: usize = 32;
+
+const CGKA_KEM: KemCiphersuiteId = KemCiphersuiteId::HybridKem1;
+const CGKA_AEAD: AeadId = AeadId::Aead1;
+
+/// Why a commit or welcome was refused.
+pub type CgkaResult<T> = Result<T, &'static str>;
+
+/// A non-blank tree node. Leaves carry the member's user id; parents only a public key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CgkaNode {
+    pub public_bytes: Vec<u8>,
+    pub user_id: Option<UserId>,
+}
+
+/// The public ratchet tree in RFC 9420 array layout: leaf `i` is node `2i`, and the leaf
+/// count is always a power of two.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CgkaTree {
+    nodes: Vec<Option<CgkaNode>>,
+}
+
+impl CgkaTree {
+    /// A one-leaf tree holding `member`.
+    pub fn new(member: &CgkaMemberV1) -> Self {
+        Self {
+            nodes: vec![Some(leaf_node(member))],
+        }
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        (self.nodes.len() as u64).div_ceil(2)
+    }
+
+    /// Non-blank leaves as `(leaf index, user id)`.
+    pub fn members(&self) -> Vec<(u64, &UserId)> {
+        self.nodes
+            .iter()
+            .step_by(2)
+            .enumerate()
+            .filter_map(|(leaf, node)| Some((leaf as u64, node.as_ref()?.user_id.as_ref()?)))
+            .collect()
+    }
+
+    pub fn find_member(&self, user_id: &UserId) -> Option<u64> {
+        self.members()
+            .into_iter()
+            .find_map(|(leaf, id)| (id == user_id).then_some(leaf))
+    }
+
+    pub fn leaf(&self, leaf: u64) -> Option<&CgkaNode> {
+        self.node(leaf * 2)
+    }
+
+    fn node(&self, node: u64) -> Option<&CgkaNode> {
+        self.nodes.get(node as usize)?.as_ref()
+    }
+
+    fn set_public(&mut self, node: u64, public_bytes: Vec<u8>) {
+        self.nodes[node as usize] = Some(CgkaNode {
+            public_bytes,
+            user_id: None,
+        });
+    }
+
+    fn blank_direct_path(&mut self, node: u64) {
+        for parent in direct_path(node, self.leaf_count()) {
+            self.nodes[parent as usize] = None;
+        }
+    }
+
+    /// Puts `member` in the leftmost blank leaf, doubling the tree when it is full.
+    fn add(&mut self, member: &CgkaMemberV1) -> u64 {
+        let leaf = match (0..self.leaf_count()).find(|leaf| self.leaf(*leaf).is_none()) {
+            Some(leaf) => leaf,
+            None => {
+                let leaves = self.leaf_count();
+                self.nodes.resize(4 * leaves as usize - 1, None);
+                leaves
+            }
+        };
+        self.nodes[(leaf * 2) as usize] = Some(leaf_node(member));
+        self.blank_direct_path(leaf * 2);
+        leaf
+    }
+
+    fn remove(&mut self, leaf: u64) {
+        self.nodes[(leaf * 2) as usize] = None;
+        self.blank_direct_path(leaf * 2);
+    }
+
+    /// Halves the tree while its right half is blank.
+    fn truncate(&mut self) {
+        while self.nodes.len() > 1 {
+            let root = self.nodes.len() / 2;
+            if self.nodes[root + 1..].iter().any(Option::is_some) {
+                break;
+            }
+            self.nodes.truncate(root);
+        }
+    }
+
+    /// The non-blank nodes that together cover every leaf below `node`.
+    fn resolution(&self, node: u64) -> Vec<u64> {
+        if self.node(node).is_some() {
+            return vec![node];
+        }
+        if level(node) == 0 {
+            return Vec::new();
+        }
+        let mut out = self.resolution(left(node));
+        out.extend(self.resolution(right(node)));
+        out
+    }
+
+    pub fn to_bytes(&self) -> CoreResult<Vec<u8>> {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                None => Value::Null,
+                Some(node) => {
+                    let mut entries = vec![(0, cbor_bytes(&node.public_bytes))];
+                    if let Some(user_id) = &node.user_id {
+                        entries.push((1, cbor_text(&user_id.0)));
+                    }
+                    cbor_map(entries)
+                }
+            })
+            .collect();
+        encode_canonical_value(&cbor_array(nodes))
+    }
+
+    pub fn from_bytes(bytes: &[u8], limits: &CborLimits) -> CoreResult<Self> {
+        let invalid = |problem: &str| CoreError::Format(format!("cgka tree: {problem}"));
+        let value = decode_canonical_value(bytes, limits)?;
+        let items = as_array(&value)?;
+        let width = items.len();
+        if width == 0 || !(width + 1).is_power_of_two() {
+            return Err(invalid("node count is not 2^k - 1"));
+        }
+        let mut nodes = Vec::with_capacity(width);
+        for (index, item) in items.iter().enumerate() {
+            let node = match item {
+                Value::Null => None,
+                item => {
+                    let map = as_map(item)?;
+                    let public_bytes = req_bytes(map, 0)?;
+                    decode_user_public_bytes(&public_bytes)?;
+                    let user_id = opt_text(map, 1)?.map(UserId);
+                    if user_id.is_some() != (index % 2 == 0) {
+                        return Err(invalid("user ids belong on leaves only"));
+                    }
+                    Some(CgkaNode {
+                        public_bytes,
+                        user_id,
+                    })
+                }
+            };
+            nodes.push(node);
+        }
+        let tree = Self { nodes };
+        let members = tree.members();
+        for (i, (_, user_id)) in members.iter().enumerate() {
+            if members[..i].iter().any(|(_, other)| other == user_id) {
+                return Err(invalid("duplicate member"));
+            }
+        }
+        Ok(tree)
+    }
+
+    pub fn hash(&self) -> CoreResult<[u8; 32]> {
+        Ok(sha256(&self.to_bytes()?))
+    }
+}
+
+/// A member's private view of a group at one epoch.
+#[derive(Clone)]
+pub struct CgkaGroup {
+    pub scope_id: ScopeId,
+    pub epoch: u64,
+    pub own_leaf: u64,
+    pub tree: CgkaTree,
+    init_secret: Zeroizing<Vec<u8>>,
+    /// Path secrets of the parent nodes above `own_leaf` that this member knows.
+    path_secrets: BTreeMap<u64, Zeroizing<Vec<u8>>>,
+}
+
+impl fmt::Debug for CgkaGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CgkaGroup")
+            .field("scope_id", &self.scope_id)
+            .field("epoch", &self.epoch)
+            .field("own_leaf", &self.own_leaf)
+            .field("leaf_count", &self.tree.leaf_count())
+            .field("init_secret", &"<redacted>")
+            .field("path_secrets", &self.path_secrets.len())
+            .finish()
+    }
+}
+
+/// A commit built by [`CgkaGroup::commit`]. `commit` and `welcome` still need their
+/// signatures; `group` and `scope_key` take effect once the commit is published.
+#[derive(Debug)]
+pub struct CgkaCommitOutcome {
+    pub group: CgkaGroup,
+    pub scope_key: Zeroizing<Vec<u8>>,
+    pub commit: CgkaCommitV1,
+    pub welcome: Option<CgkaWelcomeV1>,
+}
+
+/// Who signs a commit and the scope state it is made under.
+#[derive(Clone, Debug)]
+pub struct CgkaCommitContext {
+    pub scope_state_ref: Vec<u8>,
+    pub signer_device_id: DeviceId,
+    pub sig_suite: SigCiphersuiteId,
+}
+
+struct EpochSecrets {
+    joiner_secret: Zeroizing<Vec<u8>>,
+    scope_key: Zeroizing<Vec<u8>>,
+    init_secret: Zeroizing<Vec<u8>>,
+    confirmation_tag: Zeroizing<Vec<u8>>,
+}
+
+impl CgkaGroup {
+    /// A group with `member` as its only leaf, at `epoch`. `epoch_secret` must be fresh
+    /// randomness; it seeds the scope key returned beside the group.
+    pub fn create(
+        scope_id: ScopeId,
+        epoch: u64,
+        member: &CgkaMemberV1,
+        epoch_secret: &[u8],
+    ) -> CoreResult<(Self, Zeroizing<Vec<u8>>)> {
+        let group = Self {
+            scope_id,
+            epoch,
+            own_leaf: 0,
+            tree: CgkaTree::new(member),
+            init_secret: CGKA_INIT_V1.derive(epoch_secret, CGKA_SECRET_LEN)?,
+            path_secrets: BTreeMap::new(),
+        };
+        let scope_key = CGKA_SCOPE_KEY_V1.derive(epoch_secret, CGKA_SECRET_LEN)?;
+        Ok((group, scope_key))
+    }
+
+    /// Builds the commit to the next epoch that removes `removes`, adds `adds`, and
+    /// replaces this member's direct path with secrets chained from `path_seed`.
+    pub fn commit(
+        &self,
+        removes: &[u64],
+        adds: &[CgkaMemberV1],
+        path_seed: &[u8],
+        context: CgkaCommitContext,
+    ) -> CgkaResult<CgkaCommitOutcome> {
+        let epoch = self.epoch.checked_add(1).ok_or("epoch overflow")?;
+        let mut tree = self.tree.clone();
+        let joiners =
+            apply_proposals(&mut tree, self.own_leaf, removes, adds).ok_or("invalid proposals")?;
+
+        let own_node = self.own_leaf * 2;
+        let path = direct_path(own_node, tree.leaf_count());
+        let secrets = chain_path_secrets(path_seed, path.len()).map_err(|_| "path secret")?;
+        for (node, secret) in path.iter().zip(&secrets) {
+            let keypair = node_keypair(secret).map_err(|_| "path key derivation")?;
+            tree.set_public(*node, keypair.public_bytes.clone());
+        }
+
+        let mut path_nodes = Vec::with_capacity(path.len());
+        let mut child = own_node;
+        for (node, secret) in path.iter().zip(&secrets) {
+            let mut ciphertexts = Vec::new();
+            for recipient in copath_resolution(&tree, child, &joiners) {
+                let public_bytes = &tree.node(recipient).ok_or("blank node")?.public_bytes;
+                let aad = aad_cgka_path_secret_v1(
+                    &self.scope_id.0,
+                    epoch,
+                    *node,
+                    recipient,
+                    CGKA_KEM,
+                    CGKA_AEAD,
+                )
+                .map_err(|_| "path secret aad")?;
+                ciphertexts
+                    .push(seal(public_bytes, &aad, secret).map_err(|_| "path secret encrypt")?);
+            }
+            let public_bytes = tree.node(*node).ok_or("blank node")?.public_bytes.clone();
+            path_nodes.push(CgkaPathNodeV1 {
+                public_bytes,
+                ciphertexts,
+            });
+            child = *node;
+        }
+
+        let commit_secret = commit_secret(path_seed, &secrets).map_err(|_| "commit secret")?;
+        let tree_hash = tree.hash().map_err(|_| "tree hash")?;
+        let schedule = self
+            .epoch_secrets(epoch, &commit_secret, &tree_hash)
+            .map_err(|_| "key schedule")?;
+
+        let commit = CgkaCommitV1 {
+            v: 1,
+            scope_id: self.scope_id.clone(),
+            scope_epoch: ScopeEpoch(epoch),
+            scope_state_ref: context.scope_state_ref.clone(),
+            committer_leaf: self.own_leaf,
+            removes: removes.to_vec(),
+            adds: adds.to_vec(),
+            kem: CGKA_KEM,
+            aead: CGKA_AEAD,
+            path: path_nodes,
+            confirmation_tag: schedule.confirmation_tag.to_vec(),
+            signer_device_id: context.signer_device_id.clone(),
+            sig_suite: context.sig_suite,
+            signature: Vec::new(),
+        };
+        let welcome = if joiners.is_empty() {
+            None
+        } else {
+            let tree_bytes = tree.to_bytes().map_err(|_| "tree encoding")?;
+            let mut welcome_secrets = Vec::with_capacity(joiners.len());
+            for joiner in &joiners {
+                let leaf = tree.leaf(*joiner).ok_or("blank joiner leaf")?;
+                let user_id = leaf.user_id.clone().ok_or("blank joiner leaf")?;
+                let ancestor = common_ancestor(&path, *joiner * 2).ok_or("no common ancestor")?;
+                let plaintext = Zeroizing::new(
+                    encode_canonical_value(&cbor_map(vec![
+                        (0, cbor_bytes(&schedule.joiner_secret)),
+                        (1, cbor_bytes(&secrets[ancestor])),
+                        (2, cbor_uint(path[ancestor])),
+                    ]))
+                    .map_err(|_| "welcome encoding")?,
+                );
+                let aad = aad_cgka_welcome_v1(
+                    &self.scope_id.0,
+                    epoch,
+                    &user_id.0,
+                    &tree_hash,
+                    CGKA_KEM,
+                    CGKA_AEAD,
+                )
+                .map_err(|_| "welcome aad")?;
+                let sealed =
+                    seal(&leaf.public_bytes, &aad, &plaintext).map_err(|_| "welcome encrypt")?;
+                welcome_secrets.push(CgkaWelcomeSecretV1 {
+                    user_id,
+                    enc: sealed.enc,
+                    nonce: sealed.nonce,
+                    ct: sealed.ct,
+                });
+            }
+            Some(CgkaWelcomeV1 {
+                v: 1,
+                scope_id: self.scope_id.clone(),
+                scope_epoch: ScopeEpoch(epoch),
+                scope_state_ref: context.scope_state_ref,
+                committer_leaf: self.own_leaf,
+                tree: tree_bytes,
+                kem: CGKA_KEM,
+                aead: CGKA_AEAD,
+                secrets: welcome_secrets,
+                signer_device_id: context.signer_device_id,
+                sig_suite: context.sig_suite,
+                signature: Vec::new(),
+            })
+        };
+
+        let group = Self {
+            scope_id: self.scope_id.clone(),
+            epoch,
+            own_leaf: self.own_leaf,
+            tree,
+            init_secret: schedule.init_secret,
+            path_secrets: path.iter().copied().zip(secrets).collect(),
+        };
+        Ok(CgkaCommitOutcome {
+            group,
+            scope_key: schedule.scope_key,
+            commit,
+            welcome,
+        })
+    }
+
+    /// Applies another member's commit to the next epoch; the caller has verified its
+    /// signature. `own_keys` is this member's user KEM key, held by its leaf.
+    pub fn process_commit(
+        &self,
+        commit: &CgkaCommitV1,
+        own_keys: &HybridKemRecipient,
+    ) -> CgkaResult<(Self, Zeroizing<Vec<u8>>)> {
+        if commit.scope_id != self.scope_id {
+            return Err("commit is for another scope");
+        }
+        if Some(commit.scope_epoch.0) != self.epoch.checked_add(1) {
+            return Err("commit does not follow the current epoch");
+        }
+        if commit.kem != CGKA_KEM || commit.aead != CGKA_AEAD {
+            return Err("unsupported ciphersuite");
+        }
+        if commit.committer_leaf == self.own_leaf || self.tree.leaf(commit.committer_leaf).is_none()
+        {
+            return Err("unknown committer leaf");
+        }
+        if commit.removes.contains(&self.own_leaf) {
+            return Err("removed from the group");
+        }
+        let mut tree = self.tree.clone();
+        let joiners = apply_proposals(
+            &mut tree,
+            commit.committer_leaf,
+            &commit.removes,
+            &commit.adds,
+        )
+        .ok_or("invalid proposals")?;
+
+        let committer_node = commit.committer_leaf * 2;
+        let own_node = self.own_leaf * 2;
+        let path = direct_path(committer_node, tree.leaf_count());
+        if commit.path.len() != path.len() {
+            return Err("path length does not match the tree");
+        }
+        let ancestor = common_ancestor(&path, own_node).ok_or("no common ancestor")?;
+        let mut path_secrets: BTreeMap<u64, Zeroizing<Vec<u8>>> = self
+            .path_secrets
+            .iter()
+            .filter(|(node, _)| tree.node(**node).is_some() && !path.contains(node))
+            .map(|(node, secret)| (*node, secret.clone()))
+            .collect();
+
+        let child = if ancestor == 0 {
+            committer_node
+        } else {
+            path[ancestor - 1]
+        };
+        let resolution = copath_resolution(&tree, child, &joiners);
+        let path_node = &commit.path[ancestor];
+        if path_node.ciphertexts.len() != resolution.len() {
+            return Err("ciphertext count does not match the resolution");
+        }
+        let position = resolution
+            .iter()
+            .position(|node| *node == own_node || path_secrets.contains_key(node))
+            .ok_or("no key for the commit path")?;
+        let derived = match path_secrets.get(&resolution[position]) {
+            Some(secret) => Some(node_keypair(secret).map_err(|_| "path key derivation")?),
+            None => None,
+        };
+        let recipient = derived.as_ref().unwrap_or(own_keys);
+        let aad = aad_cgka_path_secret_v1(
+            &self.scope_id.0,
+            commit.scope_epoch.0,
+            path[ancestor],
+            resolution[position],
+            commit.kem,
+            commit.aead,
+        )
+        .map_err(|_| "path secret aad")?;
+        let secret = open(recipient, &aad, &path_node.ciphertexts[position])
+            .map_err(|_| "path secret decrypt failed")?;
+
+        let secrets =
+            chain_path_secrets(&secret, path.len() - ancestor).map_err(|_| "path secret")?;
+        for (path_node, secret) in commit.path[ancestor..].iter().zip(&secrets) {
+            let keypair = node_keypair(secret).map_err(|_| "path key derivation")?;
+            if !ct_eq(&keypair.public_bytes, &path_node.public_bytes) {
+                return Err("path public key does not match its secret");
+            }
+        }
+        for (node, path_node) in path.iter().zip(&commit.path) {
+            decode_user_public_bytes(&path_node.public_bytes).map_err(|_| "path public key")?;
+            tree.set_public(*node, path_node.public_bytes.clone());
+        }
+
+        let commit_secret = commit_secret(&secret, &secrets).map_err(|_| "commit secret")?;
+        let tree_hash = tree.hash().map_err(|_| "tree hash")?;
+        let schedule = self
+            .epoch_secrets(commit.scope_epoch.0, &commit_secret, &tree_hash)
+            .map_err(|_| "key schedule")?;
+        if !ct_eq(&schedule.confirmation_tag, &commit.confirmation_tag) {
+            return Err("confirmation tag mismatch");
+        }
+
+        path_secrets.extend(path[ancestor..].iter().copied().zip(secrets));
+        let group = Self {
+            scope_id: self.scope_id.clone(),
+            epoch: commit.scope_epoch.0,
+            own_leaf: self.own_leaf,
+            tree,
+            init_secret: schedule.init_secret,
+            path_secrets,
+        };
+        Ok((group, schedule.scope_key))
+    }
+
+    /// Joins the epoch a welcome was made for, as `user_id` holding `own_keys`; the caller
+    /// has verified the welcome's signature.
+    pub fn join(
+        welcome: &CgkaWelcomeV1,
+        user_id: &UserId,
+        own_keys: &HybridKemRecipient,
+        limits: &CborLimits,
+    ) -> CgkaResult<(Self, Zeroizing<Vec<u8>>)> {
+        if welcome.kem != CGKA_KEM || welcome.aead != CGKA_AEAD {
+            return Err("unsupported ciphersuite");
+        }
+        let tree = CgkaTree::from_bytes(&welcome.tree, limits).map_err(|_| "invalid tree")?;
+        let own_leaf = tree
+            .find_member(user_id)
+            .ok_or("not a member of the tree")?;
+        let leaf = tree.leaf(own_leaf).ok_or("not a member of the tree")?;
+        if !ct_eq(&leaf.public_bytes, &own_keys.public_bytes) {
+            return Err("leaf key is not this user's key");
+        }
+        if tree.leaf(welcome.committer_leaf).is_none() || welcome.committer_leaf == own_leaf {
+            return Err("unknown committer leaf");
+        }
+        let sealed = welcome
+            .secrets
+            .iter()
+            .find(|secret| &secret.user_id == user_id)
+            .ok_or("no welcome secret for this user")?;
+        let tree_hash = tree.hash().map_err(|_| "tree hash")?;
+        let aad = aad_cgka_welcome_v1(
+            &welcome.scope_id.0,
+            welcome.scope_epoch.0,
+            &user_id.0,
+            &tree_hash,
+            welcome.kem,
+            welcome.aead,
+        )
+        .map_err(|_| "welcome aad")?;
+        let plaintext = open(
+            own_keys,
+            &aad,
+            &CgkaCiphertextV1 {
+                enc: sealed.enc.clone(),
+                nonce: sealed.nonce.clone(),
+                ct: sealed.ct.clone(),
+            },
+        )
+        .map_err(|_| "welcome decrypt failed")?;
+        let value = decode_canonical_value(&plaintext, limits).map_err(|_| "welcome secrets")?;
+        let map = as_map(&value).map_err(|_| "welcome secrets")?;
+        let joiner_secret = Zeroizing::new(req_bytes(map, 0).map_err(|_| "welcome secrets")?);
+        let path_secret = Zeroizing::new(req_bytes(map, 1).map_err(|_| "welcome secrets")?);
+        let path_node = req_uint(map, 2).map_err(|_| "welcome secrets")?;
+
+        let committer_path = direct_path(welcome.committer_leaf * 2, tree.leaf_count());
+        let ancestor =
+            common_ancestor(&committer_path, own_leaf * 2).ok_or("no common ancestor")?;
+        if committer_path[ancestor] != path_node {
+            return Err("path secret is not for the common ancestor");
+        }
+        let nodes = &committer_path[ancestor..];
+        let secrets = chain_path_secrets(&path_secret, nodes.len()).map_err(|_| "path secret")?;
+        for (node, secret) in nodes.iter().zip(&secrets) {
+            let keypair = node_keypair(secret).map_err(|_| "path key derivation")?;
+            let public = tree.node(*node).ok_or("blank path node")?;
+            if !ct_eq(&keypair.public_bytes, &public.public_bytes) {
+                return Err("path public key does not match its secret");
+            }
+        }
+
+        let context = group_context(&welcome.scope_id, welcome.scope_epoch.0, &tree_hash)
+            .map_err(|_| "group context")?;
+        let epoch_secret = CGKA_EPOCH_V1
+            .derive_with(&joiner_secret, &context, CGKA_SECRET_LEN)
+            .map_err(|_| "key schedule")?;
+        let group = Self {
+            scope_id: welcome.scope_id.clone(),
+            epoch: welcome.scope_epoch.0,
+            own_leaf,
+            tree,
+            init_secret: CGKA_INIT_V1
+                .derive(&epoch_secret, CGKA_SECRET_LEN)
+                .map_err(|_| "key schedule")?,
+            path_secrets: nodes.iter().copied().zip(secrets).collect(),
+        };
+        let scope_key = CGKA_SCOPE_KEY_V1
+            .derive(&epoch_secret, CGKA_SECRET_LEN)
+            .map_err(|_| "key schedule")?;
+        Ok((group, scope_key))
+    }
+
+    fn epoch_secrets(
+        &self,
+        epoch: u64,
+        commit_secret: &[u8],
+        tree_hash: &[u8; 32],
+    ) -> CoreResult<EpochSecrets> {
+        let context = group_context(&self.scope_id, epoch, tree_hash)?;
+        let ikm = Zeroizing::new([self.init_secret.as_slice(), commit_secret].concat());
+        let joiner_secret = CGKA_JOINER_V1.derive(&ikm, CGKA_SECRET_LEN)?;
+        let epoch_secret = CGKA_EPOCH_V1.derive_with(&joiner_secret, &context, CGKA_SECRET_LEN)?;
+        Ok(EpochSecrets {
+            scope_key: CGKA_SCOPE_KEY_V1.derive(&epoch_secret, CGKA_SECRET_LEN)?,
+            init_secret: CGKA_INIT_V1.derive(&epoch_secret, CGKA_SECRET_LEN)?,
+            confirmation_tag: CGKA_CONFIRM_V1.derive_with(&epoch_secret, &context, 32)?,
+            joiner_secret,
+        })
+    }
+
+    pub fn to_bytes(&self) -> CoreResult<Vec<u8>> {
+        let path_secrets = self
+            .path_secrets
+            .iter()
+            .map(|(node, secret)| cbor_array(vec![cbor_uint(*node), cbor_bytes(secret)]))
+            .collect();
+        encode_canonical_value(&cbor_map(vec![
+            (0, cbor_text(&self.scope_id.0)),
+            (1, cbor_uint(self.epoch)),
+            (2, cbor_uint(self.own_leaf)),
+            (3, cbor_bytes(&self.tree.to_bytes()?)),
+            (4, cbor_bytes(&self.init_secret)),
+            (5, cbor_array(path_secrets)),
+        ]))
+    }
+
+    pub fn from_bytes(bytes: &[u8], limits: &CborLimits) -> CoreResult<Self> {
+        let value = decode_canonical_value(bytes, limits)?;
+        let map = as_map(&value)?;
+        let tree = CgkaTree::from_bytes(&req_bytes(map, 3)?, limits)?;
+        let own_leaf = req_uint(map, 2)?;
+        if tree.leaf(own_leaf).is_none() {
+            return Err(CoreError::Format(
+                "cgka group: own leaf is blank".to_string(),
+            ));
+        }
+        let mut path_secrets = BTreeMap::new();
+        for entry in req_array(map, 5)? {
+            match as_array(entry)? {
+                [Value::Integer(node), Value::Bytes(secret)] => {
+                    let node = u64::try_from(*node)
+                        .map_err(|_| CoreError::Format("cgka group: node index".to_string()))?;
+                    path_secrets.insert(node, Zeroizing::new(secret.clone()));
+                }
+                _ => {
+                    return Err(CoreError::Format(
+                        "cgka group: expected [node, path_secret]".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(Self {
+            scope_id: ScopeId(req_text(map, 0)?),
+            epoch: req_uint(map, 1)?,
+            own_leaf,
+            tree,
+            init_secret: Zeroizing::new(req_bytes(map, 4)?),
+            path_secrets,
+        })
+    }
+}
+
+/// The CBOR map each epoch secret and confirmation tag is bound to.
+pub fn group_context(scope_id: &ScopeId, epoch: u64, tree_hash: &[u8; 32]) -> CoreResult<Vec<u8>> {
+    encode_canonical_value(&cbor_map(vec![
+        (0, cbor_text(CGKA_GROUP_CONTEXT_V1)),
+        (1, cbor_text(&scope_id.0)),
+        (2, cbor_uint(epoch)),
+        (3, cbor_bytes(tree_hash)),
+    ]))
+}
+
+fn leaf_node(member: &CgkaMemberV1) -> CgkaNode {
+    CgkaNode {
+        public_bytes: member.public_bytes.clone(),
+        user_id: Some(member.user_id.clone()),
+    }
+}
+
+/// Removes, then adds, as both committer and receivers must. Returns the new members' leaves,
+/// or `None` when a removal names a blank leaf or the committer, or an add names a member.
+fn apply_proposals(
+    tree: &mut CgkaTree,
+    committer_leaf: u64,
+    removes: &[u64],
+    adds: &[CgkaMemberV1],
+) -> Option<Vec<u64>> {
+    for (i, leaf) in removes.iter().enumerate() {
+        if *leaf == committer_leaf || tree.leaf(*leaf).is_none() || removes[..i].contains(leaf) {
+            return None;
+        }
+        tree.remove(*leaf);
+    }
+    if !removes.is_empty() {
+        tree.truncate();
+    }
+    let mut joiners = Vec::with_capacity(adds.len());
+    for member in adds {
+        decode_user_public_bytes(&member.public_bytes).ok()?;
+        if tree.find_member(&member.user_id).is_some() {
+            return None;
+        }
+        joiners.push(tree.add(member));
+    }
+    Some(joiners)
+}
+
+/// The resolution of `child`'s sibling, minus the leaves of members joining in this commit.
+fn copath_resolution(tree: &CgkaTree, child: u64, joiners: &[u64]) -> Vec<u64> {
+    tree.resolution(sibling(child))
+        .into_iter()
+        .filter(|node| !joiners.iter().any(|leaf| leaf * 2 == *node))
+        .collect()
+}
+
+/// Index in `path` of the lowest node that is also an ancestor of `node`.
+fn common_ancestor(path: &[u64], node: u64) -> Option<usize> {
+    path.iter().position(|parent| is_ancestor(*parent, node))
+}
+
+/// `count` path secrets, each derived from the one below it, starting at `first`.
+fn chain_path_secrets(first: &[u8], count: usize) -> CoreResult<Vec<Zeroizing<Vec<u8>>>> {
+    let mut secrets: Vec<Zeroizing<Vec<u8>>> = Vec::with_capacity(count);
+    for i in 0..count {
+        let secret = match i {
+            0 => Zeroizing::new(first.to_vec()),
+            _ => CGKA_PATH_V1.derive(&secrets[i - 1], CGKA_SECRET_LEN)?,
+        };
+        secrets.push(secret);
+    }
+    Ok(secrets)
+}
+
+/// From the root path secret, or from `seed` when the committer's leaf is the whole tree.
+fn commit_secret(seed: &[u8], secrets: &[Zeroizing<Vec<u8>>]) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let root = secrets.last().map_or(seed, |secret| secret.as_slice());
+    CGKA_COMMIT_V1.derive(root, CGKA_SECRET_LEN)
+}
+
+fn node_keypair(path_secret: &[u8]) -> CoreResult<HybridKemRecipient> {
+    derive_kem_keypair(&CGKA_NODE_KEY_V1.derive(path_secret, KEM_KEYPAIR_SEED_LEN)?)
+}
+
+fn seal(public_bytes: &[u8], aad: &[u8], plaintext: &[u8]) -> CoreResult<CgkaCiphertextV1> {
+    let recipient = decode_user_public_bytes(public_bytes)?;
+    let encap = hybrid_kem_encapsulate(&recipient, CGKA_KEM)?;
+    let nonce = random_bytes(12)?;
+    let ct = aead_encrypt::<Aes256Gcm>(&encap.wrap_key, aad, plaintext, &nonce)?;
+    Ok(CgkaCiphertextV1 {
+        enc: encap.enc,
+        nonce,
+        ct,
+    })
+}
+
+fn open(
+    recipient: &HybridKemRecipient,
+    aad: &[u8],
+    ciphertext: &CgkaCiphertextV1,
+) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let wrap_key = derive_hybrid_kem_wrap_key(&ciphertext.enc, recipient, CGKA_KEM)?;
+    aead_decrypt::<Aes256Gcm>(&wrap_key, aad, &ciphertext.nonce, &ciphertext.ct).map(Zeroizing::new)
+}
+
+// RFC 9420 appendix C array-tree arithmetic, for trees whose leaf count is a power of two.
+
+fn level(node: u64) -> u32 {
+    node.trailing_ones()
+}
+
+fn left(node: u64) -> u64 {
+    node ^ (1 << (level(node) - 1))
+}
+
+fn right(node: u64) -> u64 {
+    node ^ (3 << (level(node) - 1))
+}
+
+fn parent(node: u64) -> u64 {
+    let k = level(node);
+    let b = (node >> (k + 1)) & 1;
+    (node | (1 << k)) ^ (b << (k + 1))
+}
+
+fn sibling(node: u64) -> u64 {
+    let p = parent(node);
+    if node < p {
+        right(p)
+    } else {
+        left(p)
+    }
+}
+
+fn direct_path(node: u64, leaf_count: u64) -> Vec<u64> {
+    let root = leaf_count - 1;
+    let mut path = Vec::new();
+    let mut node = node;
+    while node != root {
+        node = parent(node);
+        path.push(node);
+    }
+    path
+}
+
+fn is_ancestor(ancestor: u64, node: u64) -> bool {
+    let span = (1u64 << level(ancestor)) - 1;
+    ancestor != node && ancestor - span <= node && node <= ancestor + span
+}
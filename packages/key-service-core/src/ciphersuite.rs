@@ -1,7 +1,7 @@
 //! Cryptographic primitives and hybrid signing/KEM wrappers.
 
-use crate::cbor::{cbor_array, cbor_bytes, encode_canonical_value};
-use crate::crypto::hkdf_sha256;
+use crate::cbor::{cbor_array, cbor_bytes, encode_canonical_value, zeroize_value};
+use crate::domains::{SigContext, KEY_ENVELOPE_HYBRID_KEM_1, PREKEY_SEED_V1, XWING_LABEL};
 use crate::error::{CoreError, CoreResult};
 use crate::types::{KemCiphersuiteId, SigCiphersuiteId};
 use ed25519_dalek::{
@@ -22,10 +22,11 @@ use ml_kem::{
     Ciphertext as MlKemCiphertext, EncapsulateDeterministic, Encoded, EncodedSizeUser, KemCore,
     MlKem768, MlKem768Params, Seed as MlKemSeed, B32 as MlKemB32,
 };
+use sha3::{Digest, Sha3_256};
 use signature::Signer as EdSigner;
 use std::fmt;
 use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret as X25519Secret};
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 fn random_bytes<const N: usize>() -> CoreResult<[u8; N]> {
     let mut bytes = [0u8; N];
@@ -34,9 +35,9 @@ fn random_bytes<const N: usize>() -> CoreResult<[u8; N]> {
 }
 
 pub struct HybridKemRecipient {
-    pub x25519_secret: [u8; 32],
+    pub x25519_secret: Zeroizing<[u8; 32]>,
     pub x25519_public: [u8; 32],
-    pub mlkem_decaps_bytes: Vec<u8>,
+    pub mlkem_decaps_bytes: Zeroizing<Vec<u8>>,
     pub mlkem_encaps_bytes: Vec<u8>,
     pub public_bytes: Vec<u8>,
 }
@@ -69,16 +70,35 @@ pub struct HybridKemRecipientPublic {
     pub mlkem_encaps_bytes: Vec<u8>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct HybridKemEncap {
     pub enc: Vec<u8>,
-    pub wrap_key: Vec<u8>,
+    pub wrap_key: Zeroizing<Vec<u8>>,
+}
+
+impl fmt::Debug for HybridKemEncap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HybridKemEncap")
+            .field("enc_len", &self.enc.len())
+            .field("wrap_key", &"<redacted>")
+            .finish()
+    }
 }
 
+pub const MLKEM768_ENCAPS_KEY_LEN: usize = 1184;
+pub const MLKEM768_CIPHERTEXT_LEN: usize = 1088;
+/// X-Wing public key: ML-KEM-768 encapsulation key followed by the X25519 public key.
+pub const XWING_PUBLIC_KEY_LEN: usize = MLKEM768_ENCAPS_KEY_LEN + 32;
+/// X-Wing ciphertext: ML-KEM-768 ciphertext followed by the ephemeral X25519 public key.
+pub const XWING_CIPHERTEXT_LEN: usize = MLKEM768_CIPHERTEXT_LEN + 32;
+
+pub const ED25519_PUBLIC_KEY_LEN: usize = 32;
+pub const MLDSA65_PUBLIC_KEY_LEN: usize = 1952;
+
 pub struct HybridSignatureKeypair {
-    pub ed25519_priv: Vec<u8>,
+    pub ed25519_priv: Zeroizing<Vec<u8>>,
     pub ed25519_pub: Vec<u8>,
-    pub mldsa_priv: Vec<u8>,
+    pub mldsa_priv: Zeroizing<Vec<u8>>,
     pub mldsa_pub: Vec<u8>,
 }
 
@@ -102,28 +122,66 @@ impl Zeroize for HybridSignatureKeypair {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SignerKeys {
     pub sig_suite: SigCiphersuiteId,
     pub ed25519_pub: Vec<u8>,
     pub mldsa_pub: Vec<u8>,
 }
 
-pub fn generate_user_keypair() -> CoreResult<(HybridKemRecipient, Vec<u8>)> {
-    let x25519_seed = random_bytes::<32>()?;
-    let x_secret = X25519Secret::from(x25519_seed);
+pub fn generate_user_keypair() -> CoreResult<(HybridKemRecipient, Zeroizing<Vec<u8>>)> {
+    let x25519_seed = Zeroizing::new(random_bytes::<32>()?);
+    let mlkem_seed_bytes = Zeroizing::new(random_bytes::<64>()?);
+    user_keypair_from_seeds(&x25519_seed, &mlkem_seed_bytes)
+}
+
+/// Length of the seed [`derive_kem_keypair`] expects: an X25519 secret and an ML-KEM seed.
+pub const KEM_KEYPAIR_SEED_LEN: usize = 32 + 64;
+
+/// Deterministic hybrid KEM keypair from `seed` (see [`KEM_KEYPAIR_SEED_LEN`]), for keys
+/// every holder of a shared secret must derive alike, such as CGKA tree nodes.
+pub fn derive_kem_keypair(seed: &[u8]) -> CoreResult<HybridKemRecipient> {
+    let invalid = || CoreError::Crypto("invalid kem keypair seed length".to_string());
+    if seed.len() != KEM_KEYPAIR_SEED_LEN {
+        return Err(invalid());
+    }
+    let (x25519_seed, mlkem_seed) = seed.split_at(32);
+    let x25519_seed = Zeroizing::new(<[u8; 32]>::try_from(x25519_seed).map_err(|_| invalid())?);
+    let mlkem_seed = Zeroizing::new(<[u8; 64]>::try_from(mlkem_seed).map_err(|_| invalid())?);
+    user_keypair_from_seeds(&x25519_seed, &mlkem_seed).map(|(recipient, _)| recipient)
+}
+
+/// One-time prekey `prekey_id` of `user`. Prekeys are derived from the user private key, so
+/// the vault stores only which ids were published and consumed.
+pub fn derive_prekey_keypair(
+    user: &HybridKemRecipient,
+    prekey_id: u64,
+) -> CoreResult<HybridKemRecipient> {
+    let mut ikm = Zeroizing::new(Vec::new());
+    ikm.extend_from_slice(user.x25519_secret.as_slice());
+    ikm.extend_from_slice(&user.mlkem_decaps_bytes);
+    let seed = PREKEY_SEED_V1.derive_with(&ikm, &prekey_id.to_be_bytes(), KEM_KEYPAIR_SEED_LEN)?;
+    derive_kem_keypair(&seed)
+}
+
+fn user_keypair_from_seeds(
+    x25519_seed: &[u8; 32],
+    mlkem_seed_bytes: &[u8; 64],
+) -> CoreResult<(HybridKemRecipient, Zeroizing<Vec<u8>>)> {
+    let x_secret = X25519Secret::from(*x25519_seed);
     let x_public = X25519PublicKey::from(&x_secret);
 
-    let mlkem_seed: MlKemSeed = random_bytes::<64>()?.into();
+    let mlkem_seed: MlKemSeed = (*mlkem_seed_bytes).into();
     let (dk, ek) = MlKem768::from_seed(mlkem_seed);
-    let dk_bytes = dk.as_bytes().to_vec();
+    let dk_bytes = Zeroizing::new(dk.as_bytes().to_vec());
     let ek_bytes = ek.as_bytes().to_vec();
 
+    let x_secret_bytes = Zeroizing::new(x_secret.to_bytes());
     let public_bytes = encode_user_public_bytes(&x_public.to_bytes(), &ek_bytes)?;
-    let private_bytes = encode_user_private_bytes(&x_secret.to_bytes(), &dk_bytes)?;
+    let private_bytes = encode_user_private_bytes(x_secret_bytes.as_slice(), &dk_bytes)?;
 
     let recipient = HybridKemRecipient {
-        x25519_secret: x_secret.to_bytes(),
+        x25519_secret: x_secret_bytes,
         x25519_public: x_public.to_bytes(),
         mlkem_decaps_bytes: dk_bytes,
         mlkem_encaps_bytes: ek_bytes,
@@ -141,63 +199,129 @@ pub fn user_keypair_public(recipient: &HybridKemRecipient) -> HybridKemRecipient
 }
 
 pub fn generate_device_signing_keypair() -> CoreResult<HybridSignatureKeypair> {
-    let ed_seed = random_bytes::<32>()?;
+    let ed_seed = Zeroizing::new(random_bytes::<32>()?);
     let ed_sign = Ed25519SigningKey::from_bytes(&ed_seed);
     let ed_pub = ed_sign.verifying_key();
 
-    let mldsa_seed: ml_dsa::Seed = random_bytes::<32>()?.into();
+    let mldsa_seed_bytes = Zeroizing::new(random_bytes::<32>()?);
+    let mldsa_seed: ml_dsa::Seed = (*mldsa_seed_bytes).into();
     let ml_sign = MlDsaSigningKey::<MlDsa65>::from_seed(&mldsa_seed);
     let ml_pub = ml_sign.verifying_key();
 
     Ok(HybridSignatureKeypair {
-        ed25519_priv: ed_sign.to_bytes().to_vec(),
+        ed25519_priv: Zeroizing::new(ed_sign.to_bytes().to_vec()),
         ed25519_pub: ed_pub.to_bytes().to_vec(),
-        mldsa_priv: ml_sign.encode().to_vec(),
+        mldsa_priv: Zeroizing::new(ml_sign.encode().to_vec()),
         mldsa_pub: ml_pub.encode().to_vec(),
     })
 }
 
+/// Shared secrets and ciphertexts of one X25519 + ML-KEM-768 encapsulation.
+struct KemParts {
+    x25519_ct: [u8; 32],
+    x25519_ss: Zeroizing<[u8; 32]>,
+    mlkem_ct: Vec<u8>,
+    mlkem_ss: Zeroizing<[u8; 32]>,
+}
+
 pub fn hybrid_kem_encapsulate(
     recipient: &HybridKemRecipientPublic,
     kem: KemCiphersuiteId,
 ) -> CoreResult<HybridKemEncap> {
-    if kem != KemCiphersuiteId::HybridKem1 {
-        return Err(CoreError::Crypto("unsupported kem".to_string()));
+    let parts = encapsulate_parts(recipient)?;
+    match kem {
+        KemCiphersuiteId::HybridKem1 => Ok(HybridKemEncap {
+            enc: pack_hybrid_kem_enc(&parts.x25519_ct, &parts.mlkem_ct)?,
+            wrap_key: hybrid_kem_1_combine(&parts)?,
+        }),
+        KemCiphersuiteId::XWing => Ok(HybridKemEncap {
+            enc: [parts.mlkem_ct.as_slice(), &parts.x25519_ct].concat(),
+            wrap_key: xwing_combine(&parts, &recipient.x25519_public),
+        }),
     }
+}
 
-    let x_seed = random_bytes::<32>()?;
-    let x25519_ephemeral = X25519Secret::from(x_seed);
+/// INSECURE: X-Wing encapsulation with caller-chosen randomness, the draft's
+/// `EncapsulateDerand`: `eseed` is the ML-KEM message followed by the X25519 ephemeral secret.
+/// Only for known-answer tests.
+#[cfg(feature = "test-utils")]
+pub fn xwing_encapsulate_derand(
+    recipient: &HybridKemRecipientPublic,
+    eseed: &[u8; 64],
+) -> CoreResult<HybridKemEncap> {
+    let invalid = |_| CoreError::Crypto("invalid x-wing eseed".to_string());
+    let (m, x_seed) = eseed.split_at(32);
+    let m = Zeroizing::new(<[u8; 32]>::try_from(m).map_err(invalid)?);
+    let x_seed = Zeroizing::new(<[u8; 32]>::try_from(x_seed).map_err(invalid)?);
+    let parts = encapsulate_parts_with(recipient, &x_seed, &m)?;
+    Ok(HybridKemEncap {
+        enc: [parts.mlkem_ct.as_slice(), &parts.x25519_ct].concat(),
+        wrap_key: xwing_combine(&parts, &recipient.x25519_public),
+    })
+}
+
+pub fn derive_hybrid_kem_wrap_key(
+    enc: &[u8],
+    recipient: &HybridKemRecipient,
+    kem: KemCiphersuiteId,
+) -> CoreResult<Zeroizing<Vec<u8>>> {
+    match kem {
+        KemCiphersuiteId::HybridKem1 => {
+            let (x25519_ct, mlkem_ct) = unpack_hybrid_kem_enc(enc)?;
+            hybrid_kem_1_combine(&decapsulate_parts(recipient, x25519_ct, mlkem_ct)?)
+        }
+        KemCiphersuiteId::XWing => {
+            if enc.len() != XWING_CIPHERTEXT_LEN {
+                return Err(CoreError::Format("x-wing ciphertext size".to_string()));
+            }
+            let (mlkem_ct, x25519_ct) = enc.split_at(MLKEM768_CIPHERTEXT_LEN);
+            let x25519_ct: [u8; 32] = x25519_ct
+                .try_into()
+                .map_err(|_| CoreError::Format("x-wing ciphertext size".to_string()))?;
+            let parts = decapsulate_parts(recipient, x25519_ct, mlkem_ct.to_vec())?;
+            Ok(xwing_combine(&parts, &recipient.x25519_public))
+        }
+    }
+}
+
+fn encapsulate_parts(recipient: &HybridKemRecipientPublic) -> CoreResult<KemParts> {
+    let x_seed = Zeroizing::new(random_bytes::<32>()?);
+    let m = Zeroizing::new(random_bytes::<32>()?);
+    encapsulate_parts_with(recipient, &x_seed, &m)
+}
+
+/// Encapsulation with the X25519 ephemeral secret and the ML-KEM message supplied.
+fn encapsulate_parts_with(
+    recipient: &HybridKemRecipientPublic,
+    x_seed: &[u8; 32],
+    m: &[u8; 32],
+) -> CoreResult<KemParts> {
+    let x25519_ephemeral = X25519Secret::from(*x_seed);
     let x25519_public = X25519PublicKey::from(&x25519_ephemeral);
     let x25519_shared =
         x25519_ephemeral.diffie_hellman(&X25519PublicKey::from(recipient.x25519_public));
 
     let ek = decode_mlkem_encapsulation_key(&recipient.mlkem_encaps_bytes)?;
-    let m: MlKemB32 = random_bytes::<32>()?.into();
+    let m: MlKemB32 = (*m).into();
     let (ct, ss_mlkem) = ek
         .encapsulate_deterministic(&m)
         .map_err(|_| CoreError::Crypto("ml-kem encapsulate failed".to_string()))?;
 
-    let mut ikm = Vec::new();
-    ikm.extend_from_slice(x25519_shared.as_bytes());
-    ikm.extend_from_slice(ss_mlkem.as_slice());
-    let wrap_key = hkdf_sha256(&ikm, b"mo-key-envelope|hybrid-kem-1", 32)?;
-
-    let enc = pack_hybrid_kem_enc(&x25519_public.to_bytes(), ct.as_slice())?;
-
-    Ok(HybridKemEncap { enc, wrap_key })
+    Ok(KemParts {
+        x25519_ct: x25519_public.to_bytes(),
+        x25519_ss: Zeroizing::new(x25519_shared.to_bytes()),
+        mlkem_ct: ct.to_vec(),
+        mlkem_ss: Zeroizing::new(ss_mlkem.into()),
+    })
 }
 
-pub fn derive_hybrid_kem_wrap_key(
-    enc: &[u8],
+fn decapsulate_parts(
     recipient: &HybridKemRecipient,
-    kem: KemCiphersuiteId,
-) -> CoreResult<Vec<u8>> {
-    if kem != KemCiphersuiteId::HybridKem1 {
-        return Err(CoreError::Crypto("unsupported kem".to_string()));
-    }
-    let (x25519_pub, mlkem_ct) = unpack_hybrid_kem_enc(enc)?;
-    let x_secret = X25519Secret::from(recipient.x25519_secret);
-    let x_shared = x_secret.diffie_hellman(&X25519PublicKey::from(x25519_pub));
+    x25519_ct: [u8; 32],
+    mlkem_ct: Vec<u8>,
+) -> CoreResult<KemParts> {
+    let x_secret = X25519Secret::from(*recipient.x25519_secret);
+    let x_shared = x_secret.diffie_hellman(&X25519PublicKey::from(x25519_ct));
 
     let dk = decode_mlkem_decapsulation_key(&recipient.mlkem_decaps_bytes)?;
     let ct_arr = decode_mlkem_ciphertext(&mlkem_ct)?;
@@ -205,33 +329,103 @@ pub fn derive_hybrid_kem_wrap_key(
         .decapsulate(&ct_arr)
         .map_err(|_| CoreError::Crypto("ml-kem decapsulate failed".to_string()))?;
 
-    let mut ikm = Vec::new();
-    ikm.extend_from_slice(x_shared.as_bytes());
-    ikm.extend_from_slice(ss_mlkem.as_slice());
-    hkdf_sha256(&ikm, b"mo-key-envelope|hybrid-kem-1", 32)
+    Ok(KemParts {
+        x25519_ct,
+        x25519_ss: Zeroizing::new(x_shared.to_bytes()),
+        mlkem_ct,
+        mlkem_ss: Zeroizing::new(ss_mlkem.into()),
+    })
+}
+
+/// `hybrid-kem-1`: HKDF-SHA256 over the X25519 and ML-KEM shared secrets.
+fn hybrid_kem_1_combine(parts: &KemParts) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let mut ikm = Zeroizing::new(Vec::new());
+    ikm.extend_from_slice(parts.x25519_ss.as_slice());
+    ikm.extend_from_slice(parts.mlkem_ss.as_slice());
+    KEY_ENVELOPE_HYBRID_KEM_1.derive(&ikm, 32)
 }
 
-pub fn hybrid_sign(data: &[u8], keypair: &HybridSignatureKeypair) -> CoreResult<Vec<u8>> {
-    let ed_seed: [u8; 32] = keypair
-        .ed25519_priv
-        .clone()
-        .try_into()
-        .map_err(|_| CoreError::Crypto("ed25519 priv size".to_string()))?;
-    let ed = Ed25519SigningKey::from_bytes(&ed_seed);
-    let ed_sig = ed.sign(data);
+/// X-Wing: `SHA3-256(ss_M || ss_X || ct_X || pk_X || label)`; the output is the wrap key.
+fn xwing_combine(parts: &KemParts, x25519_public: &[u8; 32]) -> Zeroizing<Vec<u8>> {
+    let digest = Sha3_256::new()
+        .chain_update(parts.mlkem_ss.as_slice())
+        .chain_update(parts.x25519_ss.as_slice())
+        .chain_update(parts.x25519_ct)
+        .chain_update(x25519_public)
+        .chain_update(XWING_LABEL)
+        .finalize();
+    Zeroizing::new(digest.to_vec())
+}
+
+/// Signs `data` bound to `context`; see [`SigContext::frame`].
+pub fn hybrid_sign(
+    context: SigContext,
+    data: &[u8],
+    keypair: &HybridSignatureKeypair,
+) -> CoreResult<Vec<u8>> {
+    sign_raw(&context.frame(data), keypair)
+}
+
+pub fn hybrid_verify(
+    context: SigContext,
+    data: &[u8],
+    signature: &[u8],
+    signer: &SignerKeys,
+) -> bool {
+    verify_raw(&context.frame(data), signature, signer)
+}
+
+/// Like [`hybrid_verify`], also accepting a legacy signature over the bare `data` when
+/// `accept_unbound` is set (the migration window of `accept_unbound_signatures`).
+pub fn hybrid_verify_or_unbound(
+    context: SigContext,
+    data: &[u8],
+    signature: &[u8],
+    signer: &SignerKeys,
+    accept_unbound: bool,
+) -> bool {
+    hybrid_verify(context, data, signature, signer)
+        || (accept_unbound && verify_raw(data, signature, signer))
+}
 
-    let ml_enc: MlDsaEncodedSigningKey<MlDsa65> = keypair
+/// Completes a hybrid signature whose Ed25519 half was produced elsewhere (co-signing):
+/// signs the already framed `data` with the local ML-DSA key and packs both halves.
+pub fn hybrid_sign_with_ed25519(
+    data: &[u8],
+    ed_sig: &[u8; 64],
+    keypair: &HybridSignatureKeypair,
+) -> CoreResult<Vec<u8>> {
+    let ml_sig = mldsa_sign(data, keypair)?;
+    pack_hybrid_signature(ed_sig, &ml_sig)
+}
+
+fn mldsa_sign(data: &[u8], keypair: &HybridSignatureKeypair) -> CoreResult<Vec<u8>> {
+    let mut ml_enc: MlDsaEncodedSigningKey<MlDsa65> = keypair
         .mldsa_priv
         .as_slice()
         .try_into()
         .map_err(|_| CoreError::Crypto("mldsa priv size".to_string()))?;
     let ml_sign = MlDsaSigningKey::<MlDsa65>::decode(&ml_enc);
-    let ml_sig = ml_sign.sign(data);
+    ml_enc.as_mut_slice().zeroize();
+    Ok(ml_sign.sign(data).encode().to_vec())
+}
+
+fn sign_raw(data: &[u8], keypair: &HybridSignatureKeypair) -> CoreResult<Vec<u8>> {
+    let ed_seed: Zeroizing<[u8; 32]> = Zeroizing::new(
+        keypair
+            .ed25519_priv
+            .as_slice()
+            .try_into()
+            .map_err(|_| CoreError::Crypto("ed25519 priv size".to_string()))?,
+    );
+    let ed = Ed25519SigningKey::from_bytes(&ed_seed);
+    let ed_sig = ed.sign(data);
+    let ml_sig = mldsa_sign(data, keypair)?;
 
-    pack_hybrid_signature(ed_sig.to_bytes().as_slice(), &ml_sig.encode())
+    pack_hybrid_signature(ed_sig.to_bytes().as_slice(), &ml_sig)
 }
 
-pub fn hybrid_verify(data: &[u8], signature: &[u8], signer: &SignerKeys) -> bool {
+fn verify_raw(data: &[u8], signature: &[u8], signer: &SignerKeys) -> bool {
     if signer.sig_suite != SigCiphersuiteId::HybridSig1 {
         return false;
     }
@@ -345,26 +539,61 @@ pub fn decode_user_public_bytes(bytes: &[u8]) -> CoreResult<HybridKemRecipientPu
     })
 }
 
+/// The recipient key in X-Wing encoding (`pk_M || pk_X`), for peers that speak X-Wing natively.
+pub fn encode_xwing_public_key(recipient: &HybridKemRecipientPublic) -> Vec<u8> {
+    [
+        recipient.mlkem_encaps_bytes.as_slice(),
+        &recipient.x25519_public,
+    ]
+    .concat()
+}
+
+pub fn decode_xwing_public_key(bytes: &[u8]) -> CoreResult<HybridKemRecipientPublic> {
+    if bytes.len() != XWING_PUBLIC_KEY_LEN {
+        return Err(CoreError::Format("x-wing public key size".to_string()));
+    }
+    let (mlkem, x25519) = bytes.split_at(MLKEM768_ENCAPS_KEY_LEN);
+    let x25519_public: [u8; 32] = x25519
+        .try_into()
+        .map_err(|_| CoreError::Format("x-wing public key size".to_string()))?;
+    decode_mlkem_encapsulation_key(mlkem)?;
+    Ok(HybridKemRecipientPublic {
+        x25519_public,
+        mlkem_encaps_bytes: mlkem.to_vec(),
+    })
+}
+
 pub fn decode_user_keypair(uk_priv: &[u8], uk_pub: &[u8]) -> CoreResult<HybridKemRecipient> {
     let pub_parts = decode_user_public_bytes(uk_pub)?;
-    let value = crate::cbor::decode_canonical_value(uk_priv, &crate::cbor::CborLimits::default())?;
-    let arr = crate::cbor::as_array(&value)?;
+    let mut value =
+        crate::cbor::decode_canonical_value(uk_priv, &crate::cbor::CborLimits::default())?;
+    let recipient = decode_user_private_value(&value, pub_parts, uk_pub);
+    zeroize_value(&mut value);
+    recipient
+}
+
+fn decode_user_private_value(
+    value: &ciborium::value::Value,
+    pub_parts: HybridKemRecipientPublic,
+    uk_pub: &[u8],
+) -> CoreResult<HybridKemRecipient> {
+    let arr = crate::cbor::as_array(value)?;
     if arr.len() != 2 {
         return Err(CoreError::Format("invalid user private array".to_string()));
     }
     let x = match &arr[0] {
-        ciborium::value::Value::Bytes(b) => b.clone(),
+        ciborium::value::Value::Bytes(b) => b.as_slice(),
         _ => return Err(CoreError::Format("invalid user private x25519".to_string())),
     };
     let ml = match &arr[1] {
-        ciborium::value::Value::Bytes(b) => b.clone(),
+        ciborium::value::Value::Bytes(b) => Zeroizing::new(b.clone()),
         _ => return Err(CoreError::Format("invalid user private mlkem".to_string())),
     };
     let x_bytes: [u8; 32] = x
         .try_into()
         .map_err(|_| CoreError::Format("invalid x25519 priv size".to_string()))?;
     Ok(HybridKemRecipient {
-        x25519_secret: x_bytes,
+        x25519_secret: Zeroizing::new(x_bytes),
         x25519_public: pub_parts.x25519_public,
         mlkem_decaps_bytes: ml,
         mlkem_encaps_bytes: pub_parts.mlkem_encaps_bytes,
@@ -372,9 +601,14 @@ pub fn decode_user_keypair(uk_priv: &[u8], uk_pub: &[u8]) -> CoreResult<HybridKe
     })
 }
 
-pub fn encode_user_private_bytes(x25519_priv: &[u8], mlkem_decaps: &[u8]) -> CoreResult<Vec<u8>> {
-    let value = cbor_array(vec![cbor_bytes(x25519_priv), cbor_bytes(mlkem_decaps)]);
-    encode_canonical_value(&value)
+pub fn encode_user_private_bytes(
+    x25519_priv: &[u8],
+    mlkem_decaps: &[u8],
+) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let mut value = cbor_array(vec![cbor_bytes(x25519_priv), cbor_bytes(mlkem_decaps)]);
+    let encoded = encode_canonical_value(&value).map(Zeroizing::new);
+    zeroize_value(&mut value);
+    encoded
 }
 
 fn decode_mlkem_encapsulation_key(
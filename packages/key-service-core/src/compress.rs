@@ -0,0 +1,46 @@
+//! Compression for v2 keyvault exports: deflate on every target, zstd behind the `zstd` feature.
+
+use crate::error::{CoreError, CoreResult};
+use crate::types::SnapshotCompression;
+
+const DEFLATE_LEVEL: u8 = 6;
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+pub fn compress(algorithm: SnapshotCompression, data: &[u8]) -> CoreResult<Vec<u8>> {
+    match algorithm {
+        SnapshotCompression::Deflate => {
+            Ok(miniz_oxide::deflate::compress_to_vec(data, DEFLATE_LEVEL))
+        }
+        #[cfg(feature = "zstd")]
+        SnapshotCompression::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL)
+            .map_err(|e| CoreError::Format(format!("zstd compress failed: {e}"))),
+        #[cfg(not(feature = "zstd"))]
+        SnapshotCompression::Zstd => Err(zstd_unavailable()),
+    }
+}
+
+/// Fails instead of producing more than `max_len` bytes, so a small export cannot expand
+/// into an unbounded allocation.
+pub fn decompress(
+    algorithm: SnapshotCompression,
+    data: &[u8],
+    max_len: usize,
+) -> CoreResult<Vec<u8>> {
+    match algorithm {
+        SnapshotCompression::Deflate => {
+            miniz_oxide::inflate::decompress_to_vec_with_limit(data, max_len)
+                .map_err(|e| CoreError::Format(format!("deflate decompress failed: {e}")))
+        }
+        #[cfg(feature = "zstd")]
+        SnapshotCompression::Zstd => zstd::bulk::decompress(data, max_len)
+            .map_err(|e| CoreError::Format(format!("zstd decompress failed: {e}"))),
+        #[cfg(not(feature = "zstd"))]
+        SnapshotCompression::Zstd => Err(zstd_unavailable()),
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_unavailable() -> CoreError {
+    CoreError::Format("zstd support is not built in".to_string())
+}
@@ -0,0 +1,354 @@
+//! Two-party (device + server) co-signing of the Ed25519 half of the hybrid signature.
+//!
+//! The Ed25519 signing key is split additively: `s = s_device + s_server`, and the group key
+//! `A = A_device + A_server` is what peers see as the device's `ed25519_pub`. Signing follows
+//! FROST (RFC 9591, Ed25519-SHA512) for two participants with Lagrange coefficients of 1.
+//! Each party commits to a hiding and a binding nonce, and the binding factors tie every
+//! nonce to the message and to both commitments. The result is a plain Ed25519 signature
+//! under `A`, so verifiers need no changes. The local vault alone holds only `s_device`.
+//!
+//! One round trip per signature: the device sends its commitment with the message
+//! ([`CosignSignRequestV1`]). The server picks its nonces after seeing it and answers with its
+//! commitment and signature share ([`CosignSignResponseV1`]). Enrollment exchanges the share
+//! public keys with Schnorr proofs of possession, so neither side can pick its key as a
+//! function of the other's.
+//!
+//! [`CosignSignRequestV1`]: crate::formats::CosignSignRequestV1
+//! [`CosignSignResponseV1`]: crate::formats::CosignSignResponseV1
+
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+use std::fmt;
+use zeroize::Zeroizing;
+
+use crate::adapters::EntropyAdapter;
+use crate::domains::COSIGN_ED25519_SHA512_CONTEXT;
+use crate::error::{CoreError, CoreResult};
+
+/// Compressed Edwards point or canonical scalar.
+pub const COSIGN_ELEMENT_LEN: usize = 32;
+/// Schnorr proof of possession: `R || z`.
+pub const COSIGN_PROOF_LEN: usize = 64;
+
+/// FROST participant identifier; fixed per role.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CosignParticipant {
+    Device = 1,
+    Server = 2,
+}
+
+impl CosignParticipant {
+    fn scalar(self) -> Scalar {
+        Scalar::from(self as u64)
+    }
+}
+
+/// One party's additive share of the Ed25519 key.
+pub struct CosignKeyShare {
+    pub secret: Zeroizing<[u8; 32]>,
+    pub public: [u8; 32],
+}
+
+impl fmt::Debug for CosignKeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CosignKeyShare")
+            .field("secret", &"<redacted>")
+            .field("public", &hex::encode(self.public))
+            .finish()
+    }
+}
+
+impl CosignKeyShare {
+    pub fn generate(entropy: &impl EntropyAdapter) -> CoreResult<Self> {
+        let secret = Zeroizing::new(random_scalar(entropy)?);
+        Ok(Self {
+            public: EdwardsPoint::mul_base(&secret).compress().to_bytes(),
+            secret: Zeroizing::new(secret.to_bytes()),
+        })
+    }
+
+    pub fn from_secret(secret: &[u8]) -> CoreResult<Self> {
+        let secret = Zeroizing::new(decode_scalar(secret)?);
+        Ok(Self {
+            public: EdwardsPoint::mul_base(&secret).compress().to_bytes(),
+            secret: Zeroizing::new(secret.to_bytes()),
+        })
+    }
+
+    fn scalar(&self) -> CoreResult<Zeroizing<Scalar>> {
+        decode_scalar(self.secret.as_slice()).map(Zeroizing::new)
+    }
+
+    /// Schnorr proof that the holder knows the secret of `public`, bound to `binding` (e.g.
+    /// the device id), for enrollment.
+    pub fn prove_possession(
+        &self,
+        binding: &[u8],
+        entropy: &impl EntropyAdapter,
+    ) -> CoreResult<[u8; COSIGN_PROOF_LEN]> {
+        let k = Zeroizing::new(random_scalar(entropy)?);
+        let r = EdwardsPoint::mul_base(&k).compress().to_bytes();
+        let c = pop_challenge(binding, &self.public, &r);
+        let z = *k + c * *self.scalar()?;
+        let mut proof = [0u8; COSIGN_PROOF_LEN];
+        proof[..32].copy_from_slice(&r);
+        proof[32..].copy_from_slice(z.as_bytes());
+        Ok(proof)
+    }
+}
+
+/// This device's enrolled co-signing state, stored in a keyvault record.
+pub struct CosignDeviceShare {
+    pub share: CosignKeyShare,
+    pub server_pub: [u8; 32],
+    /// Published as the device's `ed25519_pub` while co-signing is on.
+    pub group_pub: [u8; 32],
+}
+
+impl fmt::Debug for CosignDeviceShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CosignDeviceShare")
+            .field("share", &self.share)
+            .field("group_pub", &hex::encode(self.group_pub))
+            .finish()
+    }
+}
+
+/// Checks a proof from [`CosignKeyShare::prove_possession`].
+pub fn cosign_verify_possession(public: &[u8], binding: &[u8], proof: &[u8]) -> bool {
+    let Ok(point) = decode_point(public) else {
+        return false;
+    };
+    if proof.len() != COSIGN_PROOF_LEN {
+        return false;
+    }
+    let (Ok(r), Ok(z)) = (decode_point(&proof[..32]), decode_scalar(&proof[32..])) else {
+        return false;
+    };
+    let c = pop_challenge(binding, public, &proof[..32]);
+    EdwardsPoint::mul_base(&z) == r + c * point
+}
+
+/// `A_device + A_server`; the Ed25519 public key co-signed signatures verify under.
+pub fn cosign_group_key(device_public: &[u8], server_public: &[u8]) -> CoreResult<[u8; 32]> {
+    let group = decode_point(device_public)? + decode_point(server_public)?;
+    if group.is_small_order() {
+        return Err(CoreError::Crypto(
+            "cosign group key has small order".to_string(),
+        ));
+    }
+    Ok(group.compress().to_bytes())
+}
+
+/// Public nonce commitment `(D, E)` of one signing attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CosignCommitment {
+    pub hiding: [u8; 32],
+    pub binding: [u8; 32],
+}
+
+/// Secret nonces behind a [`CosignCommitment`]; use once.
+pub struct CosignNonces {
+    hiding: Zeroizing<Scalar>,
+    binding: Zeroizing<Scalar>,
+}
+
+impl fmt::Debug for CosignNonces {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CosignNonces(<redacted>)")
+    }
+}
+
+/// RFC 9591 `nonce_generate`: fresh randomness hashed with the share, so a weak RNG alone
+/// does not repeat nonces across shares.
+pub fn cosign_commit(
+    share: &CosignKeyShare,
+    entropy: &impl EntropyAdapter,
+) -> CoreResult<(CosignNonces, CosignCommitment)> {
+    let hiding = generate_nonce(share, entropy)?;
+    let binding = generate_nonce(share, entropy)?;
+    let commitment = CosignCommitment {
+        hiding: EdwardsPoint::mul_base(&hiding).compress().to_bytes(),
+        binding: EdwardsPoint::mul_base(&binding).compress().to_bytes(),
+    };
+    Ok((CosignNonces { hiding, binding }, commitment))
+}
+
+/// Binding factors, group commitment `R`, and challenge `c` of one signing attempt.
+pub struct CosignTranscript {
+    device_rho: Scalar,
+    server_rho: Scalar,
+    group_commitment: [u8; 32],
+    challenge: Scalar,
+}
+
+impl CosignTranscript {
+    pub fn new(
+        group_key: &[u8],
+        message: &[u8],
+        device: &CosignCommitment,
+        server: &CosignCommitment,
+    ) -> CoreResult<Self> {
+        let mut commitment_list = Vec::with_capacity(2 * 96);
+        for (participant, commitment) in [
+            (CosignParticipant::Device, device),
+            (CosignParticipant::Server, server),
+        ] {
+            commitment_list.extend_from_slice(participant.scalar().as_bytes());
+            commitment_list.extend_from_slice(&commitment.hiding);
+            commitment_list.extend_from_slice(&commitment.binding);
+        }
+        let msg_hash = hash_raw(b"msg", &[message]);
+        let com_hash = hash_raw(b"com", &[&commitment_list]);
+        let rho = |participant: CosignParticipant| {
+            hash_to_scalar(
+                b"rho",
+                &[
+                    group_key,
+                    &msg_hash,
+                    &com_hash,
+                    participant.scalar().as_bytes(),
+                ],
+            )
+        };
+        let device_rho = rho(CosignParticipant::Device);
+        let server_rho = rho(CosignParticipant::Server);
+
+        let r = decode_point(&device.hiding)?
+            + device_rho * decode_point(&device.binding)?
+            + decode_point(&server.hiding)?
+            + server_rho * decode_point(&server.binding)?;
+        let group_commitment = r.compress().to_bytes();
+        // The Ed25519 challenge, so the aggregate verifies as a standard signature.
+        let challenge = Scalar::from_hash(
+            Sha512::new()
+                .chain_update(group_commitment)
+                .chain_update(group_key)
+                .chain_update(message),
+        );
+        Ok(Self {
+            device_rho,
+            server_rho,
+            group_commitment,
+            challenge,
+        })
+    }
+
+    fn rho(&self, participant: CosignParticipant) -> Scalar {
+        match participant {
+            CosignParticipant::Device => self.device_rho,
+            CosignParticipant::Server => self.server_rho,
+        }
+    }
+
+    /// `z_i = d_i + e_i * rho_i + c * s_i`. Consumes the nonces.
+    pub fn sign_share(
+        &self,
+        participant: CosignParticipant,
+        nonces: CosignNonces,
+        share: &CosignKeyShare,
+    ) -> CoreResult<[u8; 32]> {
+        let z = *nonces.hiding
+            + *nonces.binding * self.rho(participant)
+            + self.challenge * *share.scalar()?;
+        Ok(z.to_bytes())
+    }
+
+    /// Checks `z_i * B == D_i + rho_i * E_i + c * A_i` for the other party's share.
+    pub fn verify_share(
+        &self,
+        participant: CosignParticipant,
+        public: &[u8],
+        commitment: &CosignCommitment,
+        z: &[u8],
+    ) -> bool {
+        let (Ok(public), Ok(hiding), Ok(binding), Ok(z)) = (
+            decode_point(public),
+            decode_point(&commitment.hiding),
+            decode_point(&commitment.binding),
+            decode_scalar(z),
+        ) else {
+            return false;
+        };
+        EdwardsPoint::mul_base(&z)
+            == hiding + self.rho(participant) * binding + self.challenge * public
+    }
+
+    /// The Ed25519 signature `R || z_device + z_server`.
+    pub fn aggregate(&self, device_z: &[u8], server_z: &[u8]) -> CoreResult<[u8; 64]> {
+        let z = decode_scalar(device_z)? + decode_scalar(server_z)?;
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&self.group_commitment);
+        signature[32..].copy_from_slice(z.as_bytes());
+        Ok(signature)
+    }
+}
+
+fn generate_nonce(
+    share: &CosignKeyShare,
+    entropy: &impl EntropyAdapter,
+) -> CoreResult<Zeroizing<Scalar>> {
+    let random = Zeroizing::new(entropy.random_bytes(32));
+    if random.len() != 32 {
+        return Err(CoreError::Crypto("entropy returned short read".to_string()));
+    }
+    Ok(Zeroizing::new(hash_to_scalar(
+        b"nonce",
+        &[random.as_slice(), share.secret.as_slice()],
+    )))
+}
+
+fn pop_challenge(binding: &[u8], public: &[u8], r: &[u8]) -> Scalar {
+    let binding_len = (binding.len() as u64).to_be_bytes();
+    hash_to_scalar(b"pop", &[&binding_len, binding, public, r])
+}
+
+/// `SHA-512(context || label || parts...)`, the RFC 9591 `H4`/`H5` shape.
+fn hash_raw(label: &[u8], parts: &[&[u8]]) -> [u8; 64] {
+    let mut hasher = Sha512::new()
+        .chain_update(COSIGN_ED25519_SHA512_CONTEXT)
+        .chain_update(label);
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// [`hash_raw`] reduced mod `L`, the RFC 9591 `H1`/`H3` shape.
+fn hash_to_scalar(label: &[u8], parts: &[&[u8]]) -> Scalar {
+    Scalar::from_bytes_mod_order_wide(&hash_raw(label, parts))
+}
+
+fn random_scalar(entropy: &impl EntropyAdapter) -> CoreResult<Scalar> {
+    let bytes = Zeroizing::new(entropy.random_bytes(64));
+    let wide: Zeroizing<[u8; 64]> = Zeroizing::new(
+        bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| CoreError::Crypto("entropy returned short read".to_string()))?,
+    );
+    Ok(Scalar::from_bytes_mod_order_wide(&wide))
+}
+
+fn decode_scalar(bytes: &[u8]) -> CoreResult<Scalar> {
+    let arr: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| CoreError::Crypto("cosign scalar size".to_string()))?;
+    Option::from(Scalar::from_canonical_bytes(arr))
+        .ok_or_else(|| CoreError::Crypto("non-canonical cosign scalar".to_string()))
+}
+
+fn decode_point(bytes: &[u8]) -> CoreResult<EdwardsPoint> {
+    let compressed = CompressedEdwardsY::from_slice(bytes)
+        .map_err(|_| CoreError::Crypto("cosign point size".to_string()))?;
+    let point = compressed
+        .decompress()
+        .ok_or_else(|| CoreError::Crypto("invalid cosign point".to_string()))?;
+    if point.is_small_order() {
+        return Err(CoreError::Crypto(
+            "cosign point has small order".to_string(),
+        ));
+    }
+    Ok(point)
+}
@@ -1,11 +1,16 @@
-use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::aead::{Aead, AeadInPlace, KeyInit};
 use aes_gcm::{Aes256Gcm, Key, Nonce};
+use aes_kw::KekAes256;
 use argon2::{Argon2, Params};
 use getrandom::getrandom;
 use hkdf::Hkdf;
-use sha2::Sha256;
+use sha2::{Sha256, Sha512};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
 
+use crate::domains::{KEY_COMMIT_ENC_V1, KEY_COMMIT_TAG_V1, KEY_WRAP_KWP_V1};
 use crate::error::{CoreError, CoreResult};
+use crate::types::KeyWrapAlg;
 
 #[derive(Clone, Debug)]
 pub struct KdfParams {
@@ -16,7 +21,24 @@ pub struct KdfParams {
     pub parallelism: u32,
 }
 
+/// KDF id of [`KdfParams::insecure_fast_for_tests`]. Vaults using it only open in builds with the
+/// `test-utils` feature and under a policy that allows it, so they fail closed elsewhere.
+pub const INSECURE_TEST_KDF_ID: &str = "kdf-1-INSECURE-test-only";
+
 impl KdfParams {
+    /// INSECURE: Argon2id with 8 KiB, one pass, and a fixed salt, so test suites can create
+    /// and unlock vaults quickly. Needs `KeyServicePolicy::allow_insecure_test_kdf`.
+    #[cfg(feature = "test-utils")]
+    pub fn insecure_fast_for_tests() -> Self {
+        Self {
+            id: INSECURE_TEST_KDF_ID.to_string(),
+            salt: vec![0x5a; 16],
+            memory_kib: 8,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
     pub fn new_random() -> CoreResult<Self> {
         Ok(Self {
             id: "kdf-1".to_string(),
@@ -28,10 +50,84 @@ impl KdfParams {
     }
 }
 
-pub fn derive_kek(passphrase_utf8: &[u8], params: &KdfParams) -> CoreResult<Vec<u8>> {
-    if params.id != "kdf-1" {
-        return Err(CoreError::Crypto("unsupported kdf".to_string()));
+/// Weakest `kdf-1` parameters a vault may use. A header is attacker-controlled once it leaves
+/// the device, so unlock refuses costs an attacker lowered to make the KEK cheap to brute-force.
+///
+/// The default is the OWASP Argon2id floor (19 MiB, two passes) with a 16-byte salt.
+/// [`INSECURE_TEST_KDF_ID`] is exempt; its own policy gate applies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfMinimums {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub salt_bytes: usize,
+}
+
+impl KdfMinimums {
+    /// Accepts any parameters Argon2id itself accepts.
+    pub const NONE: Self = Self {
+        memory_kib: 0,
+        iterations: 0,
+        parallelism: 0,
+        salt_bytes: 0,
+    };
+
+    pub fn check(&self, params: &KdfParams) -> CoreResult<()> {
+        if params.id == INSECURE_TEST_KDF_ID {
+            return Ok(());
+        }
+        let weak = |param, value: u64, minimum: u64| {
+            Err(CoreError::WeakKdf {
+                param,
+                value,
+                minimum,
+            })
+        };
+        if params.memory_kib < self.memory_kib {
+            return weak(
+                "memoryKib",
+                params.memory_kib.into(),
+                self.memory_kib.into(),
+            );
+        }
+        if params.iterations < self.iterations {
+            return weak(
+                "iterations",
+                params.iterations.into(),
+                self.iterations.into(),
+            );
+        }
+        if params.parallelism < self.parallelism {
+            return weak(
+                "parallelism",
+                params.parallelism.into(),
+                self.parallelism.into(),
+            );
+        }
+        if params.salt.len() < self.salt_bytes {
+            return weak(
+                "saltBytes",
+                params.salt.len() as u64,
+                self.salt_bytes as u64,
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Default for KdfMinimums {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+            salt_bytes: 16,
+        }
     }
+}
+
+pub fn derive_kek(passphrase_utf8: &[u8], params: &KdfParams) -> CoreResult<Zeroizing<Vec<u8>>> {
+    check_kdf_id(params)?;
     let argon = Argon2::new(
         argon2::Algorithm::Argon2id,
         argon2::Version::V0x13,
@@ -43,21 +139,69 @@ pub fn derive_kek(passphrase_utf8: &[u8], params: &KdfParams) -> CoreResult<Vec<
         )
         .map_err(|e| CoreError::Crypto(e.to_string()))?,
     );
-    let mut out = vec![0u8; 32];
+    let mut out = Zeroizing::new(vec![0u8; 32]);
     argon
         .hash_password_into(passphrase_utf8, &params.salt, &mut out)
         .map_err(|e| CoreError::Crypto(e.to_string()))?;
     Ok(out)
 }
 
-pub fn hkdf_sha256(ikm: &[u8], info: &[u8], len: usize) -> CoreResult<Vec<u8>> {
-    let hk = Hkdf::<Sha256>::new(None, ikm);
-    let mut okm = vec![0u8; len];
-    hk.expand(info, &mut okm)
-        .map_err(|_| CoreError::Crypto("hkdf expand failed".to_string()))?;
+/// Like [`derive_kek`], calling `on_pass(done, total)` after each of the `iterations` passes
+/// so hosts can show unlock progress. Produces the same key.
+pub fn derive_kek_with_progress(
+    passphrase_utf8: &[u8],
+    params: &KdfParams,
+    on_pass: &mut dyn FnMut(u32, u32),
+) -> CoreResult<Zeroizing<Vec<u8>>> {
+    check_kdf_id(params)?;
+    crate::argon2id::hash(
+        passphrase_utf8,
+        &params.salt,
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        32,
+        on_pass,
+    )
+}
+
+/// Hash underlying an HKDF derivation; see [`crate::domains`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HkdfHash {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+/// `kdf-1` is Argon2id; the insecure test id runs the same KDF, but only with `test-utils`.
+fn check_kdf_id(params: &KdfParams) -> CoreResult<()> {
+    match params.id.as_str() {
+        "kdf-1" => Ok(()),
+        INSECURE_TEST_KDF_ID if cfg!(feature = "test-utils") => Ok(()),
+        _ => Err(CoreError::Crypto("unsupported kdf".to_string())),
+    }
+}
+
+pub fn hkdf_sha256(ikm: &[u8], info: &[u8], len: usize) -> CoreResult<Zeroizing<Vec<u8>>> {
+    hkdf(HkdfHash::Sha256, ikm, info, len)
+}
+
+/// HKDF without salt; `len` is capped at 255 hash lengths.
+pub fn hkdf(hash: HkdfHash, ikm: &[u8], info: &[u8], len: usize) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let mut okm = Zeroizing::new(vec![0u8; len]);
+    let expanded = match hash {
+        HkdfHash::Sha256 => Hkdf::<Sha256>::new(None, ikm).expand(info, &mut okm),
+        HkdfHash::Sha512 => Hkdf::<Sha512>::new(None, ikm).expand(info, &mut okm),
+    };
+    expanded.map_err(|_| CoreError::Crypto("hkdf expand failed".to_string()))?;
     Ok(okm)
 }
 
+/// Constant-time equality for secrets, fingerprints, and hashes; only the length may leak.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
 pub fn sha256_bytes(input: &[u8]) -> Vec<u8> {
     crate::hash::sha256(input).to_vec()
 }
@@ -113,6 +257,130 @@ pub fn aead_decrypt<A: Aead + KeyInit>(
         .map_err(|_| CoreError::Crypto("decrypt failed".to_string()))
 }
 
+/// Wraps `plaintext` under an HKDF subkey of `key` and returns `(ct, commitment)`.
+///
+/// AES-GCM is not key-committing; the stored commitment binds the ciphertext to one `key`.
+pub fn committing_wrap(
+    key: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+    nonce: &[u8],
+) -> CoreResult<(Vec<u8>, Vec<u8>)> {
+    let enc_key = KEY_COMMIT_ENC_V1.derive(key, 32)?;
+    let commitment = KEY_COMMIT_TAG_V1.derive(key, 32)?;
+    let ct = aead_encrypt::<Aes256Gcm>(&enc_key, aad, plaintext, nonce)?;
+    Ok((ct, commitment.to_vec()))
+}
+
+/// Unwraps a key wrap; `None` commitment means a legacy plain AES-GCM wrap under `key`.
+pub fn unwrap_key(
+    key: &[u8],
+    aad: &[u8],
+    nonce: &[u8],
+    ct: &[u8],
+    commitment: Option<&[u8]>,
+) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let Some(commitment) = commitment else {
+        return aead_decrypt::<Aes256Gcm>(key, aad, nonce, ct).map(Zeroizing::new);
+    };
+    let expected = KEY_COMMIT_TAG_V1.derive(key, 32)?;
+    if !ct_eq(&expected, commitment) {
+        return Err(CoreError::Crypto("key commitment mismatch".to_string()));
+    }
+    let enc_key = KEY_COMMIT_ENC_V1.derive(key, 32)?;
+    aead_decrypt::<Aes256Gcm>(&enc_key, aad, nonce, ct).map(Zeroizing::new)
+}
+
+/// AES-256-KWP wrap of `plaintext` under an HKDF subkey of `key`. KWP takes no AAD, so `aad`
+/// is bound through the subkey's info; KWP's integrity check then fails for any other `aad`.
+pub fn kwp_wrap(key: &[u8], aad: &[u8], plaintext: &[u8]) -> CoreResult<Vec<u8>> {
+    kwp_kek(key, aad)?
+        .wrap_with_padding_vec(plaintext)
+        .map_err(|_| CoreError::Crypto("key wrap failed".to_string()))
+}
+
+pub fn kwp_unwrap(key: &[u8], aad: &[u8], wrapped: &[u8]) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let mut out = Zeroizing::new(vec![0u8; wrapped.len().saturating_sub(8)]);
+    let len = kwp_kek(key, aad)?
+        .unwrap_with_padding(wrapped, &mut out)
+        .map_err(|_| CoreError::Crypto("key unwrap failed".to_string()))?
+        .len();
+    out.truncate(len);
+    Ok(out)
+}
+
+fn kwp_kek(key: &[u8], aad: &[u8]) -> CoreResult<KekAes256> {
+    let subkey = KEY_WRAP_KWP_V1.derive_with(key, aad, 32)?;
+    KekAes256::try_from(subkey.as_slice())
+        .map_err(|_| CoreError::Crypto("invalid key length".to_string()))
+}
+
+/// Unwraps a key wrapped with `alg`; `nonce` and `commitment` only apply to AES-GCM wraps.
+pub fn unwrap_key_with(
+    alg: KeyWrapAlg,
+    key: &[u8],
+    aad: &[u8],
+    nonce: &[u8],
+    ct: &[u8],
+    commitment: Option<&[u8]>,
+) -> CoreResult<Zeroizing<Vec<u8>>> {
+    match alg {
+        KeyWrapAlg::AesGcm => unwrap_key(key, aad, nonce, ct, commitment),
+        KeyWrapAlg::AesKwp => kwp_unwrap(key, aad, ct),
+    }
+}
+
+/// Encrypts `buffer[offset..]` in place and appends the tag, leaving `buffer[..offset]` untouched.
+pub fn aead_encrypt_in_place<A: AeadInPlace + KeyInit>(
+    key_bytes: &[u8],
+    aad: &[u8],
+    nonce: &[u8],
+    buffer: &mut Vec<u8>,
+    offset: usize,
+) -> CoreResult<()> {
+    if key_bytes.len() != 32 {
+        return Err(CoreError::Crypto("invalid key length".to_string()));
+    }
+    if nonce.len() != 12 {
+        return Err(CoreError::Crypto("invalid nonce length".to_string()));
+    }
+    if offset > buffer.len() {
+        return Err(CoreError::Crypto("invalid buffer offset".to_string()));
+    }
+    let key = Key::<A>::from_slice(key_bytes);
+    let cipher = A::new(key);
+    let tag = cipher
+        .encrypt_in_place_detached(Nonce::from_slice(nonce), aad, &mut buffer[offset..])
+        .map_err(|_| CoreError::Crypto("encrypt failed".to_string()))?;
+    buffer.extend_from_slice(&tag);
+    Ok(())
+}
+
+/// Decrypts `buffer` (ciphertext || tag) in place; on failure the buffer is cleared.
+pub fn aead_decrypt_in_place<A: AeadInPlace + KeyInit>(
+    key_bytes: &[u8],
+    aad: &[u8],
+    nonce: &[u8],
+    buffer: &mut Vec<u8>,
+) -> CoreResult<()> {
+    if key_bytes.len() != 32 {
+        return Err(CoreError::Crypto("invalid key length".to_string()));
+    }
+    if nonce.len() != 12 {
+        return Err(CoreError::Crypto("invalid nonce length".to_string()));
+    }
+    let key = Key::<A>::from_slice(key_bytes);
+    let cipher = A::new(key);
+    if cipher
+        .decrypt_in_place(Nonce::from_slice(nonce), aad, buffer)
+        .is_err()
+    {
+        buffer.zeroize();
+        return Err(CoreError::Crypto("decrypt failed".to_string()));
+    }
+    Ok(())
+}
+
 pub fn random_bytes(len: usize) -> CoreResult<Vec<u8>> {
     let mut out = vec![0u8; len];
     getrandom(&mut out).map_err(|_| CoreError::Entropy("getrandom failed".to_string()))?;
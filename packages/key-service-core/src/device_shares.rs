@@ -0,0 +1,111 @@
+//! n-of-m split of `K_vault` across the user's own devices.
+//!
+//! Each byte of the secret is the constant term of a polynomial of degree `threshold - 1` over
+//! GF(2^8) (the AES field, `x^8 + x^4 + x^3 + x + 1`). The device at 1-based position `i` in a
+//! split's device list holds every polynomial evaluated at `x = i`. The other coefficients
+//! come from HKDF over the secret and the split id, so any device that has `K_vault` can
+//! recompute its own share, and the shares are never stored together. Any `threshold` shares
+//! give back the secret by Lagrange interpolation at zero; fewer reveal nothing about it
+//! beyond what HKDF leaks.
+
+use crate::domains::{DEVICE_SHARE_CHECK_V1, DEVICE_SHARE_COEFF_V1};
+use crate::error::{CoreError, CoreResult};
+use zeroize::Zeroizing;
+
+pub const DEVICE_SHARE_SPLIT_ID_LEN: usize = 16;
+/// Most devices one split may name; share indices are `1..=MAX_SHARE_DEVICES`.
+pub const MAX_SHARE_DEVICES: usize = 16;
+const KEY_CHECK_LEN: usize = 32;
+
+/// Share of `secret` at `index` (1-based) for a split with `threshold`.
+pub fn device_share(
+    secret: &[u8],
+    split_id: &[u8],
+    threshold: u8,
+    index: u8,
+) -> CoreResult<Zeroizing<Vec<u8>>> {
+    if threshold == 0 || usize::from(threshold) > MAX_SHARE_DEVICES {
+        return Err(CoreError::Format("device share threshold".to_string()));
+    }
+    if index == 0 || usize::from(index) > MAX_SHARE_DEVICES {
+        return Err(CoreError::Format("device share index".to_string()));
+    }
+    let degree = usize::from(threshold) - 1;
+    let coefficients =
+        DEVICE_SHARE_COEFF_V1.derive_with(secret, split_id, degree * secret.len())?;
+    let mut share = Zeroizing::new(Vec::with_capacity(secret.len()));
+    for (pos, byte) in secret.iter().enumerate() {
+        // Horner from the highest coefficient down to the secret byte.
+        let mut y = 0u8;
+        for k in (0..degree).rev() {
+            y = gf_mul(y, index) ^ coefficients[k * secret.len() + pos];
+        }
+        share.push(gf_mul(y, index) ^ byte);
+    }
+    Ok(share)
+}
+
+/// Recombines `(index, share)` pairs; pass exactly the split's threshold or more. Indices must
+/// be distinct and non-zero and the shares of equal length. Too few shares give a wrong
+/// secret rather than an error, so callers check the result with [`device_share_key_check`].
+pub fn combine_device_shares(shares: &[(u8, &[u8])]) -> CoreResult<Zeroizing<Vec<u8>>> {
+    let len = shares
+        .first()
+        .map(|(_, share)| share.len())
+        .ok_or_else(|| CoreError::Format("no device shares".to_string()))?;
+    for (i, (index, share)) in shares.iter().enumerate() {
+        if *index == 0 || shares[..i].iter().any(|(other, _)| other == index) {
+            return Err(CoreError::Format("device share index".to_string()));
+        }
+        if share.len() != len {
+            return Err(CoreError::Format("device share length".to_string()));
+        }
+    }
+    let mut secret = Zeroizing::new(vec![0u8; len]);
+    for (i, (x_i, share)) in shares.iter().enumerate() {
+        let mut basis = 1u8;
+        for (j, (x_j, _)) in shares.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_mul(*x_j, gf_inv(x_j ^ x_i)));
+            }
+        }
+        for (out, byte) in secret.iter_mut().zip(share.iter()) {
+            *out ^= gf_mul(basis, *byte);
+        }
+    }
+    Ok(secret)
+}
+
+/// Public value that confirms recombined shares give the secret of split `split_id`.
+pub fn device_share_key_check(secret: &[u8], split_id: &[u8]) -> CoreResult<Vec<u8>> {
+    Ok(DEVICE_SHARE_CHECK_V1
+        .derive_with(secret, split_id, KEY_CHECK_LEN)?
+        .to_vec())
+}
+
+/// Multiplication in GF(2^8) without table lookups or data-dependent branches.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= (b & 1).wrapping_neg() & a;
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+/// `a^254`, the inverse of a non-zero `a`.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
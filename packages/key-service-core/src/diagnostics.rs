@@ -0,0 +1,160 @@
+//! Redacted snapshot of vault structure and service state, safe to attach to bug reports.
+
+use crate::adapters::MetricOp;
+use crate::cbor::{cbor_array, cbor_map, cbor_text, cbor_uint, encode_canonical_value};
+use crate::error::CoreResult;
+use crate::hash::sha256;
+use crate::key_service::{KeyServicePolicy, NonceMode};
+use crate::types::AeadId;
+use ciborium::value::Value;
+
+pub const DIAGNOSTICS_VERSION: u64 = 1;
+/// Failed operations kept for [`DiagnosticsReport::recent_errors`]; older ones are dropped.
+pub const DIAGNOSTICS_ERROR_LIMIT: usize = 16;
+
+/// A failed public operation: when, which op, and its stable error code. No messages or inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecentError {
+    pub at_ms: u64,
+    pub op: MetricOp,
+    pub code: &'static str,
+}
+
+/// Header and record-chain facts read without the vault key.
+///
+/// Identifiers and hashes appear only as tags from [`redact_identifier`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VaultDiagnostics {
+    pub vault_id_tag: String,
+    pub user_id_tag: String,
+    pub kdf_memory_kib: u32,
+    pub kdf_iterations: u32,
+    pub kdf_parallelism: u32,
+    pub aead: AeadId,
+    pub header_bytes: u64,
+    pub header_record_count: u64,
+    pub record_count: u64,
+    pub record_bytes_total: u64,
+    pub record_bytes_max: u64,
+    pub head_seq: u64,
+    /// Empty when there are no records.
+    pub head_hash_tag: String,
+    /// Seqs are contiguous from 1 and every `prev_hash` matches; ciphertexts are not checked.
+    pub chain_linked: bool,
+    pub head_marker_present: bool,
+    pub user_presence_enabled: bool,
+    pub audit_head_seq: u64,
+}
+
+/// Output of [`KeyService::export_diagnostics`](crate::key_service::KeyService::export_diagnostics).
+#[derive(Clone, Debug)]
+pub struct DiagnosticsReport {
+    pub generated_at_ms: u64,
+    /// `None` when no header is stored or it could not be read; see `problems`.
+    pub vault: Option<VaultDiagnostics>,
+    pub unlocked: bool,
+    pub session_count: u64,
+    pub policy: KeyServicePolicy,
+    /// Oldest first, at most [`DIAGNOSTICS_ERROR_LIMIT`].
+    pub recent_errors: Vec<RecentError>,
+    /// One line per part of the vault that could not be read, as `<part>: <error code>`.
+    pub problems: Vec<String>,
+}
+
+impl DiagnosticsReport {
+    /// Canonical CBOR with integer keys, like the other wire formats.
+    pub fn to_cbor(&self) -> CoreResult<Vec<u8>> {
+        let mut entries = vec![
+            (0, cbor_uint(DIAGNOSTICS_VERSION)),
+            (1, cbor_uint(self.generated_at_ms)),
+            (3, Value::Bool(self.unlocked)),
+            (4, cbor_uint(self.session_count)),
+            (5, policy_value(&self.policy)),
+            (
+                6,
+                cbor_array(
+                    self.recent_errors
+                        .iter()
+                        .map(|e| {
+                            cbor_map(vec![
+                                (0, cbor_uint(e.at_ms)),
+                                (1, cbor_text(e.op.as_str())),
+                                (2, cbor_text(e.code)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                7,
+                cbor_array(self.problems.iter().map(|p| cbor_text(p)).collect()),
+            ),
+        ];
+        if let Some(vault) = &self.vault {
+            entries.push((2, vault_value(vault)));
+        }
+        encode_canonical_value(&cbor_map(entries))
+    }
+}
+
+/// 16 hex chars of SHA-256 over `label || 0x00 || value`; stable across reports, not reversible
+/// for high-entropy ids.
+pub fn redact_identifier(label: &str, value: &[u8]) -> String {
+    let mut input = Vec::with_capacity(label.len() + 1 + value.len());
+    input.extend_from_slice(label.as_bytes());
+    input.push(0);
+    input.extend_from_slice(value);
+    hex::encode(&sha256(&input)[..8])
+}
+
+fn vault_value(vault: &VaultDiagnostics) -> Value {
+    cbor_map(vec![
+        (0, cbor_text(&vault.vault_id_tag)),
+        (1, cbor_text(&vault.user_id_tag)),
+        (2, cbor_uint(vault.kdf_memory_kib.into())),
+        (3, cbor_uint(vault.kdf_iterations.into())),
+        (4, cbor_uint(vault.kdf_parallelism.into())),
+        (5, cbor_text(vault.aead.as_str())),
+        (6, cbor_uint(vault.header_bytes)),
+        (7, cbor_uint(vault.header_record_count)),
+        (8, cbor_uint(vault.record_count)),
+        (9, cbor_uint(vault.record_bytes_total)),
+        (10, cbor_uint(vault.record_bytes_max)),
+        (11, cbor_uint(vault.head_seq)),
+        (12, cbor_text(&vault.head_hash_tag)),
+        (13, Value::Bool(vault.chain_linked)),
+        (14, Value::Bool(vault.head_marker_present)),
+        (15, Value::Bool(vault.user_presence_enabled)),
+        (16, cbor_uint(vault.audit_head_seq)),
+    ])
+}
+
+fn policy_value(policy: &KeyServicePolicy) -> Value {
+    let mut entries = vec![
+        (0, cbor_uint(policy.normal_session_ttl_ms)),
+        (1, cbor_uint(policy.step_up_session_ttl_ms)),
+        (2, cbor_uint(policy.max_handles_per_session as u64)),
+        (3, cbor_uint(policy.max_cbor_bytes as u64)),
+        (4, cbor_uint(policy.max_cbor_depth as u64)),
+        (5, cbor_uint(policy.max_cbor_items as u64)),
+        (6, cbor_uint(policy.max_cbor_text_bytes as u64)),
+        (7, cbor_uint(policy.max_scope_state_refs_per_scope as u64)),
+        (9, cbor_uint(policy.max_plaintext_bytes as u64)),
+        (
+            10,
+            cbor_text(match policy.nonce_mode {
+                NonceMode::Random => "random",
+                NonceMode::Counter => "counter",
+            }),
+        ),
+        (11, cbor_uint(policy.session_expiry_warning_ms)),
+        (12, Value::Bool(policy.read_only_sessions)),
+        (13, Value::Bool(policy.lazy_resource_keys)),
+        (14, cbor_uint(policy.max_record_bytes as u64)),
+        (15, cbor_uint(policy.max_total_records)),
+    ];
+    if let Some(score) = policy.min_passphrase_score {
+        entries.push((8, cbor_uint(score.into())));
+    }
+    cbor_map(entries)
+}
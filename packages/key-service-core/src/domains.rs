@@ -0,0 +1,250 @@
+//! Domain-separation registry: every HKDF info string, AAD tag, signing context, and hash label
+//! the core uses.
+//!
+//! Each constant carries its version. Changing the bytes of an existing entry breaks stored
+//! vaults and envelopes, so a new scheme gets a new entry instead. New suites may pick
+//! SHA-512 HKDF through [`HkdfDomain::sha512`]; every existing entry uses SHA-256.
+
+use zeroize::Zeroizing;
+
+use crate::crypto::{hkdf, HkdfHash};
+use crate::error::CoreResult;
+
+/// An HKDF info string together with the hash it is expanded with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HkdfDomain {
+    pub info: &'static [u8],
+    pub hash: HkdfHash,
+}
+
+impl HkdfDomain {
+    pub const fn sha256(info: &'static [u8]) -> Self {
+        Self {
+            info,
+            hash: HkdfHash::Sha256,
+        }
+    }
+
+    pub const fn sha512(info: &'static [u8]) -> Self {
+        Self {
+            info,
+            hash: HkdfHash::Sha512,
+        }
+    }
+
+    /// HKDF (no salt) of `ikm` under this domain's info.
+    pub fn derive(&self, ikm: &[u8], len: usize) -> CoreResult<Zeroizing<Vec<u8>>> {
+        hkdf(self.hash, ikm, self.info, len)
+    }
+
+    /// Like [`Self::derive`], with `suffix` appended to the info (e.g. a per-stream salt).
+    pub fn derive_with(
+        &self,
+        ikm: &[u8],
+        suffix: &[u8],
+        len: usize,
+    ) -> CoreResult<Zeroizing<Vec<u8>>> {
+        hkdf(self.hash, ikm, &[self.info, suffix].concat(), len)
+    }
+}
+
+/// AES-GCM key of a committing key wrap.
+pub const KEY_COMMIT_ENC_V1: HkdfDomain = HkdfDomain::sha256(b"mo-key-commit|enc|v1");
+/// Commitment tag stored beside a committing key wrap.
+pub const KEY_COMMIT_TAG_V1: HkdfDomain = HkdfDomain::sha256(b"mo-key-commit|tag|v1");
+/// Wrap key from the X25519 + ML-KEM shared secrets of `hybrid-kem-1`.
+pub const KEY_ENVELOPE_HYBRID_KEM_1: HkdfDomain =
+    HkdfDomain::sha256(b"mo-key-envelope|hybrid-kem-1");
+/// Per-stream AEAD key; the stream salt is appended to the info.
+pub const STREAM_AEAD_KEY_V1: HkdfDomain = HkdfDomain::sha256(b"mo-stream-aead|aead-1|v1");
+/// AES-KWP key-encryption key; the wrap's AAD is appended to the info, as KWP has none.
+pub const KEY_WRAP_KWP_V1: HkdfDomain = HkdfDomain::sha256(b"mo-key-wrap|aes-kwp|v1");
+/// Key that unwraps `K_vault` from a WebAuthn PRF output.
+pub const USER_PRESENCE_UNWRAP_K_VAULT_V1: HkdfDomain =
+    HkdfDomain::sha256(b"mo-user-presence|unwrap-k-vault|v1");
+
+/// Next path secret up a CGKA tree from the one below it; see [`crate::cgka`].
+pub const CGKA_PATH_V1: HkdfDomain = HkdfDomain::sha256(b"mo-cgka|path|v1");
+/// Seed of a CGKA parent node's hybrid KEM keypair, from that node's path secret.
+pub const CGKA_NODE_KEY_V1: HkdfDomain = HkdfDomain::sha256(b"mo-cgka|node-key|v1");
+/// Commit secret from the root path secret of a commit.
+pub const CGKA_COMMIT_V1: HkdfDomain = HkdfDomain::sha256(b"mo-cgka|commit|v1");
+/// Joiner secret from `init_secret || commit_secret`; new members receive it in a welcome.
+pub const CGKA_JOINER_V1: HkdfDomain = HkdfDomain::sha256(b"mo-cgka|joiner|v1");
+/// Epoch secret from the joiner secret; the group context is appended to the info.
+pub const CGKA_EPOCH_V1: HkdfDomain = HkdfDomain::sha256(b"mo-cgka|epoch|v1");
+/// Scope key of a CGKA epoch.
+pub const CGKA_SCOPE_KEY_V1: HkdfDomain = HkdfDomain::sha256(b"mo-cgka|scope-key|v1");
+/// `init_secret` carried from one CGKA epoch into the next.
+pub const CGKA_INIT_V1: HkdfDomain = HkdfDomain::sha256(b"mo-cgka|init|v1");
+/// Commit confirmation tag; the group context is appended to the info.
+pub const CGKA_CONFIRM_V1: HkdfDomain = HkdfDomain::sha256(b"mo-cgka|confirm|v1");
+/// Seed of a one-time prekey's hybrid KEM keypair, from the user private key; the prekey id
+/// (u64, big-endian) is appended to the info.
+pub const PREKEY_SEED_V1: HkdfDomain = HkdfDomain::sha256(b"mo-prekey|seed|v1");
+/// First chain key of a resource ratchet, from the resource key; the chain id is appended to
+/// the info. See [`crate::ratchet`].
+pub const RESOURCE_RATCHET_CHAIN_V1: HkdfDomain =
+    HkdfDomain::sha256(b"mo-resource-ratchet|chain|v1");
+/// Next chain key of a resource ratchet, from the current one.
+pub const RESOURCE_RATCHET_NEXT_V1: HkdfDomain = HkdfDomain::sha256(b"mo-resource-ratchet|next|v1");
+/// Message key of a resource ratchet, from the chain key of that message.
+pub const RESOURCE_RATCHET_MESSAGE_V1: HkdfDomain =
+    HkdfDomain::sha256(b"mo-resource-ratchet|message|v1");
+
+/// Non-constant polynomial coefficients of a device-share split of `K_vault`; the split id is
+/// appended to the info. See [`crate::device_shares`].
+pub const DEVICE_SHARE_COEFF_V1: HkdfDomain = HkdfDomain::sha256(b"mo-device-share|coeff|v1");
+/// Key check stored with a device-share split; the split id is appended to the info.
+pub const DEVICE_SHARE_CHECK_V1: HkdfDomain = HkdfDomain::sha256(b"mo-device-share|check|v1");
+/// Check value sealed under the passphrase check key in the keyvault header, from the KEK.
+pub const KEYVAULT_PASSPHRASE_CHECK_V1: HkdfDomain =
+    HkdfDomain::sha256(b"mo-keyvault|passphrase-check|v1");
+/// AES-GCM key for the keyvault header's passphrase check, from the KEK. The KEK itself also
+/// wraps `K_vault` (AES-KWP in `aes-kwp` vaults), so the check never uses it directly.
+pub const KEYVAULT_PASSPHRASE_CHECK_KEY_V1: HkdfDomain =
+    HkdfDomain::sha256(b"mo-keyvault|passphrase-check-key|v1");
+
+pub const HKDF_DOMAINS: &[HkdfDomain] = &[
+    KEY_COMMIT_ENC_V1,
+    KEY_COMMIT_TAG_V1,
+    KEY_ENVELOPE_HYBRID_KEM_1,
+    STREAM_AEAD_KEY_V1,
+    KEY_WRAP_KWP_V1,
+    USER_PRESENCE_UNWRAP_K_VAULT_V1,
+    CGKA_PATH_V1,
+    CGKA_NODE_KEY_V1,
+    CGKA_COMMIT_V1,
+    CGKA_JOINER_V1,
+    CGKA_EPOCH_V1,
+    CGKA_SCOPE_KEY_V1,
+    CGKA_INIT_V1,
+    CGKA_CONFIRM_V1,
+    PREKEY_SEED_V1,
+    RESOURCE_RATCHET_CHAIN_V1,
+    RESOURCE_RATCHET_NEXT_V1,
+    RESOURCE_RATCHET_MESSAGE_V1,
+    DEVICE_SHARE_COEFF_V1,
+    DEVICE_SHARE_CHECK_V1,
+    KEYVAULT_PASSPHRASE_CHECK_V1,
+    KEYVAULT_PASSPHRASE_CHECK_KEY_V1,
+];
+
+/// Trailing label of the X-Wing SHA3-256 combiner, fixed by draft-connolly-cfrg-xwing-kem.
+pub const XWING_LABEL: &[u8] = b"\\.//^\\";
+
+/// RFC 9497 `contextString` for OPRF mode 0 over ristretto255-SHA512.
+pub const OPRF_RISTRETTO255_SHA512_CONTEXT: &[u8] = b"OPRFV1-\x00-ristretto255-SHA512";
+/// RFC 9807 info for `DeriveDiffieHellmanKeyPair`.
+pub const OPAQUE_DERIVE_DH_KEY_PAIR_INFO: &[u8] = b"OPAQUE-DeriveDiffieHellmanKeyPair";
+/// RFC 9807 info the server uses to derive per-credential OPRF keys.
+pub const OPAQUE_DERIVE_OPRF_KEY_PAIR_INFO: &[u8] = b"OPAQUE-DeriveKeyPair";
+
+/// Hash prefix of the two-party Ed25519 co-signing scheme in [`crate::cosign`]; each hash
+/// appends a label (`rho`, `nonce`, `msg`, `com`, `pop`) as in RFC 9591.
+pub const COSIGN_ED25519_SHA512_CONTEXT: &[u8] = b"mo-cosign-ed25519-sha512-v1";
+
+/// Message type a device signature is bound to. The signed bytes are
+/// `len(tag) as u8 || tag || data`, so a signature over one type never verifies as another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SigContext {
+    pub tag: &'static str,
+}
+
+impl SigContext {
+    pub const fn new(tag: &'static str) -> Self {
+        Self { tag }
+    }
+
+    /// The bytes actually signed for `data` under this context.
+    pub fn frame(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + self.tag.len() + data.len());
+        out.push(self.tag.len() as u8);
+        out.extend_from_slice(self.tag.as_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+}
+
+pub const SIG_SCOPE_STATE_V1: SigContext = SigContext::new("mo-sig|scope-state|v1");
+pub const SIG_RESOURCE_GRANT_V1: SigContext = SigContext::new("mo-sig|resource-grant|v1");
+pub const SIG_KEY_ENVELOPE_V1: SigContext = SigContext::new("mo-sig|key-envelope|v1");
+pub const SIG_SIGNING_DELEGATION_V1: SigContext = SigContext::new("mo-sig|signing-delegation|v1");
+pub const SIG_AUDIT_ENTRY_V1: SigContext = SigContext::new("mo-sig|audit-entry|v1");
+/// Arbitrary application payloads passed to `KeyService::sign`.
+pub const SIG_APP_PAYLOAD_V1: SigContext = SigContext::new("mo-sig|app-payload|v1");
+/// Tree heads signed by a key transparency log; see [`crate::transparency`].
+pub const SIG_TREE_HEAD_V1: SigContext = SigContext::new("mo-sig|tree-head|v1");
+pub const SIG_CGKA_COMMIT_V1: SigContext = SigContext::new("mo-sig|cgka-commit|v1");
+pub const SIG_CGKA_WELCOME_V1: SigContext = SigContext::new("mo-sig|cgka-welcome|v1");
+pub const SIG_PREKEY_BUNDLE_V1: SigContext = SigContext::new("mo-sig|prekey-bundle|v1");
+pub const SIG_KEYVAULT_MANIFEST_V1: SigContext = SigContext::new("mo-sig|keyvault-manifest|v1");
+
+pub const SIG_CONTEXTS: &[SigContext] = &[
+    SIG_SCOPE_STATE_V1,
+    SIG_RESOURCE_GRANT_V1,
+    SIG_KEY_ENVELOPE_V1,
+    SIG_SIGNING_DELEGATION_V1,
+    SIG_AUDIT_ENTRY_V1,
+    SIG_APP_PAYLOAD_V1,
+    SIG_TREE_HEAD_V1,
+    SIG_CGKA_COMMIT_V1,
+    SIG_CGKA_WELCOME_V1,
+    SIG_PREKEY_BUNDLE_V1,
+    SIG_KEYVAULT_MANIFEST_V1,
+];
+
+/// Field 0 of each key transparency leaf; see [`crate::transparency::TransparencyLeaf`].
+pub const TRANSPARENCY_LEAF_V1: &str = "mo-transparency-leaf-v1";
+
+/// Field 0 of the map hashed into a device's attestation `clientDataHash`; see
+/// [`crate::attestation::device_attestation_challenge`].
+pub const DEVICE_ATTESTATION_CHALLENGE_V1: &str = "mo-device-attestation-challenge-v1";
+
+/// Field 0 of the CGKA group context that epoch secrets are bound to; see
+/// [`crate::cgka::group_context`].
+pub const CGKA_GROUP_CONTEXT_V1: &str = "mo-cgka-group-context-v1";
+
+/// SHA-256 prefix of the WebAuthn PRF salt, followed by vault and user ids.
+pub const USER_PRESENCE_SALT_V1: &[u8] = b"mo-user-presence|salt-v1";
+/// WebAuthn PRF maps a salt to the CTAP2 hmac-secret salt as
+/// `SHA-256(WEBAUTHN_PRF_SALT_PREFIX || salt)`; fixed by the WebAuthn spec.
+pub const WEBAUTHN_PRF_SALT_PREFIX: &[u8] = b"WebAuthn PRF\x00";
+
+/// Field 0 of each CBOR AAD map built in [`crate::aad`].
+pub const AAD_KEYVAULT_KEYWRAP_V1: &str = "mo-keyvault-keywrap-aad-v1";
+pub const AAD_KEYVAULT_RECORD_V1: &str = "mo-keyvault-record-aad-v1";
+pub const AAD_KEYVAULT_HEAD_MARKER_V1: &str = "mo-keyvault-head-marker-aad-v1";
+pub const AAD_KEYVAULT_INDEX_V1: &str = "mo-keyvault-index-aad-v1";
+pub const AAD_KEYVAULT_KMS_WRAP_V1: &str = "mo-keyvault-kms-wrap-aad-v1";
+pub const AAD_KEY_ENVELOPE_V1: &str = "mo-key-envelope-aad-v1";
+pub const AAD_RESOURCE_GRANT_V1: &str = "mo-resource-grant-aad-v1";
+pub const AAD_USER_PRESENCE_WRAP_V1: &str = "mo-user-presence-wrap-aad-v1";
+pub const AAD_CAPABILITY_TOKEN_WRAP_V1: &str = "mo-capability-token-wrap-aad-v1";
+pub const AAD_CGKA_PATH_SECRET_V1: &str = "mo-cgka-path-secret-aad-v1";
+pub const AAD_CGKA_WELCOME_V1: &str = "mo-cgka-welcome-aad-v1";
+pub const AAD_KEY_ENVELOPE_PREKEY_V1: &str = "mo-key-envelope-prekey-aad-v1";
+pub const AAD_DEVICE_SHARE_SEAL_V1: &str = "mo-device-share-seal-aad-v1";
+pub const AAD_DEVICE_SHARE_TRANSFER_V1: &str = "mo-device-share-transfer-aad-v1";
+pub const AAD_KEYVAULT_PASSPHRASE_CHECK_V1: &str = "mo-keyvault-passphrase-check-aad-v1";
+/// Field 0 of the CBOR HKDF info for resource sub-keys.
+pub const INFO_RESOURCE_SUBKEY_V1: &str = "mo-resource-subkey-info-v1";
+
+pub const AAD_TAGS: &[&str] = &[
+    AAD_KEYVAULT_KEYWRAP_V1,
+    AAD_KEYVAULT_RECORD_V1,
+    AAD_KEYVAULT_HEAD_MARKER_V1,
+    AAD_KEYVAULT_INDEX_V1,
+    AAD_KEYVAULT_KMS_WRAP_V1,
+    AAD_KEY_ENVELOPE_V1,
+    AAD_RESOURCE_GRANT_V1,
+    AAD_USER_PRESENCE_WRAP_V1,
+    AAD_CAPABILITY_TOKEN_WRAP_V1,
+    AAD_CGKA_PATH_SECRET_V1,
+    AAD_CGKA_WELCOME_V1,
+    AAD_KEY_ENVELOPE_PREKEY_V1,
+    AAD_DEVICE_SHARE_SEAL_V1,
+    AAD_DEVICE_SHARE_TRANSFER_V1,
+    AAD_KEYVAULT_PASSPHRASE_CHECK_V1,
+    INFO_RESOURCE_SUBKEY_V1,
+];
@@ -0,0 +1,116 @@
+//! Versioned framing for `encrypt` output: `version || aead || nonce || ct`.
+//!
+//! Ratcheted handles write version 2, `version || aead || chain_id || message || nonce || ct`
+//! with a big-endian u64 message number; see [`crate::ratchet`]. Unframed `nonce || ct` from
+//! earlier releases is still accepted on decrypt.
+
+use crate::ratchet::RATCHET_CHAIN_ID_LEN;
+use crate::types::AeadId;
+
+pub const CIPHERTEXT_ENVELOPE_V1: u8 = 1;
+pub const CIPHERTEXT_ENVELOPE_RATCHET_V2: u8 = 2;
+pub const CIPHERTEXT_NONCE_LEN: usize = 12;
+pub const CIPHERTEXT_ENVELOPE_HEADER_LEN: usize = 2 + CIPHERTEXT_NONCE_LEN;
+pub const CIPHERTEXT_RATCHET_HEADER_LEN: usize =
+    2 + RATCHET_CHAIN_ID_LEN + 8 + CIPHERTEXT_NONCE_LEN;
+/// AEAD tag every ciphertext ends with; shorter bodies cannot authenticate.
+pub const CIPHERTEXT_TAG_LEN: usize = 16;
+
+/// One-byte wire id for an AEAD inside the envelope.
+pub fn aead_wire_id(aead: AeadId) -> u8 {
+    match aead {
+        AeadId::Aead1 => 1,
+    }
+}
+
+fn aead_from_wire_id(id: u8) -> Option<AeadId> {
+    match id {
+        1 => Some(AeadId::Aead1),
+        _ => None,
+    }
+}
+
+/// Appends the v1 envelope header to `out`.
+pub fn write_ciphertext_envelope_header(out: &mut Vec<u8>, aead: AeadId, nonce: &[u8]) {
+    out.push(CIPHERTEXT_ENVELOPE_V1);
+    out.push(aead_wire_id(aead));
+    out.extend_from_slice(nonce);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CiphertextEnvelope<'a> {
+    pub aead: AeadId,
+    pub nonce: &'a [u8],
+    pub ct: &'a [u8],
+}
+
+/// Parses a v1 envelope; `None` if `bytes` do not start with a known version and aead id.
+///
+/// A legacy ciphertext whose random nonce happens to look like a header also parses here,
+/// so callers fall back to [`parse_legacy_ciphertext`] when the framed decrypt fails.
+pub fn parse_ciphertext_envelope(bytes: &[u8]) -> Option<CiphertextEnvelope<'_>> {
+    if bytes.len() < CIPHERTEXT_ENVELOPE_HEADER_LEN || bytes[0] != CIPHERTEXT_ENVELOPE_V1 {
+        return None;
+    }
+    let aead = aead_from_wire_id(bytes[1])?;
+    let (nonce, ct) = bytes[2..].split_at(CIPHERTEXT_NONCE_LEN);
+    Some(CiphertextEnvelope { aead, nonce, ct })
+}
+
+/// Appends the v2 (ratchet) envelope header to `out`.
+pub fn write_ratchet_envelope_header(
+    out: &mut Vec<u8>,
+    aead: AeadId,
+    chain_id: &[u8; RATCHET_CHAIN_ID_LEN],
+    message: u64,
+    nonce: &[u8],
+) {
+    out.push(CIPHERTEXT_ENVELOPE_RATCHET_V2);
+    out.push(aead_wire_id(aead));
+    out.extend_from_slice(chain_id);
+    out.extend_from_slice(&message.to_be_bytes());
+    out.extend_from_slice(nonce);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RatchetCiphertextEnvelope<'a> {
+    pub aead: AeadId,
+    pub chain_id: &'a [u8],
+    pub message: u64,
+    pub nonce: &'a [u8],
+    pub ct: &'a [u8],
+}
+
+/// Parses a v2 envelope; like [`parse_ciphertext_envelope`], a legacy ciphertext may also
+/// parse here.
+pub fn parse_ratchet_envelope(bytes: &[u8]) -> Option<RatchetCiphertextEnvelope<'_>> {
+    if bytes.len() < CIPHERTEXT_RATCHET_HEADER_LEN + CIPHERTEXT_TAG_LEN
+        || bytes[0] != CIPHERTEXT_ENVELOPE_RATCHET_V2
+    {
+        return None;
+    }
+    let aead = aead_from_wire_id(bytes[1])?;
+    let (chain_id, rest) = bytes[2..].split_at(RATCHET_CHAIN_ID_LEN);
+    let (message, rest) = rest.split_at(8);
+    let (nonce, ct) = rest.split_at(CIPHERTEXT_NONCE_LEN);
+    Some(RatchetCiphertextEnvelope {
+        aead,
+        chain_id,
+        message: u64::from_be_bytes(message.try_into().ok()?),
+        nonce,
+        ct,
+    })
+}
+
+/// Splits unframed `nonce || ct` output from before the envelope existed.
+pub fn parse_legacy_ciphertext(bytes: &[u8]) -> Option<CiphertextEnvelope<'_>> {
+    if bytes.len() < CIPHERTEXT_NONCE_LEN {
+        return None;
+    }
+    let (nonce, ct) = bytes.split_at(CIPHERTEXT_NONCE_LEN);
+    Some(CiphertextEnvelope {
+        aead: AeadId::Aead1,
+        nonce,
+        ct,
+    })
+}
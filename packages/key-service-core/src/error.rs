@@ -1,3 +1,8 @@
+//! Core error type and the serializable [`ErrorReport`] that bindings hand to callers.
+
+use crate::cbor::{cbor_map, cbor_text, cbor_uint, encode_canonical_value};
+use ciborium::value::Value;
+
 #[derive(Debug, thiserror::Error)]
 pub enum CoreError {
     #[error("cbor error: {0}")]
@@ -8,6 +13,142 @@ pub enum CoreError {
     Crypto(String),
     #[error("entropy error: {0}")]
     Entropy(String),
+    #[error("operation cancelled")]
+    Cancelled,
+    /// KDF parameters below [`KdfMinimums`](crate::crypto::KdfMinimums); `param` is the
+    /// camelCase name of the weak field.
+    #[error("kdf {param} {value} below minimum {minimum}")]
+    WeakKdf {
+        param: &'static str,
+        value: u64,
+        minimum: u64,
+    },
 }
 
 pub type CoreResult<T> = Result<T, CoreError>;
+
+impl CoreError {
+    /// Stable code; the same one the error carries once converted to a
+    /// [`KeyServiceError`](crate::key_service::KeyServiceError).
+    pub fn code(&self) -> &'static str {
+        match self {
+            CoreError::Cbor(_) => "InvalidCbor",
+            CoreError::Format(_) => "InvalidFormat",
+            CoreError::Crypto(_) | CoreError::Entropy(_) => "CryptoError",
+            CoreError::Cancelled => "Cancelled",
+            CoreError::WeakKdf { .. } => "WeakKdfParams",
+        }
+    }
+
+    pub fn report(&self) -> ErrorReport {
+        let context = match self {
+            CoreError::WeakKdf {
+                param,
+                value,
+                minimum,
+            } => vec![
+                ("param", ErrorDetail::Text(param.to_string())),
+                ("value", ErrorDetail::Uint(*value)),
+                ("minimum", ErrorDetail::Uint(*minimum)),
+            ],
+            _ => Vec::new(),
+        };
+        ErrorReport {
+            code: self.code(),
+            message: self.to_string(),
+            retryable: false,
+            context,
+        }
+    }
+}
+
+/// Typed value in [`ErrorReport::context`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorDetail {
+    Text(String),
+    Uint(u64),
+}
+
+/// An error as bindings (WASM, FFI, CLI) pass it on: a stable `code` to branch on, a
+/// human-readable `message`, and the variant's structured fields under camelCase names.
+///
+/// Bindings should forward this as is rather than match on error variants themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorReport {
+    pub code: &'static str,
+    pub message: String,
+    pub retryable: bool,
+    pub context: Vec<(&'static str, ErrorDetail)>,
+}
+
+impl ErrorReport {
+    /// Canonical CBOR: `{0: code, 1: message, 2: retryable, 3: {name: value}}`; key 3 is omitted
+    /// when there is no context.
+    pub fn to_cbor(&self) -> CoreResult<Vec<u8>> {
+        let mut entries = vec![
+            (0, cbor_text(self.code)),
+            (1, cbor_text(&self.message)),
+            (2, Value::Bool(self.retryable)),
+        ];
+        if !self.context.is_empty() {
+            let context = self
+                .context
+                .iter()
+                .map(|(name, detail)| (cbor_text(name), detail_value(detail)))
+                .collect();
+            entries.push((3, Value::Map(context)));
+        }
+        encode_canonical_value(&cbor_map(entries))
+    }
+
+    /// `{"code", "message", "retryable", "context"?}`, matching the worker protocol's
+    /// `KeyServiceError` shape.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"code\":");
+        push_json_string(&mut out, self.code);
+        out.push_str(",\"message\":");
+        push_json_string(&mut out, &self.message);
+        out.push_str(",\"retryable\":");
+        out.push_str(if self.retryable { "true" } else { "false" });
+        if !self.context.is_empty() {
+            out.push_str(",\"context\":{");
+            for (i, (name, detail)) in self.context.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                push_json_string(&mut out, name);
+                out.push(':');
+                match detail {
+                    ErrorDetail::Text(text) => push_json_string(&mut out, text),
+                    ErrorDetail::Uint(value) => out.push_str(&value.to_string()),
+                }
+            }
+            out.push('}');
+        }
+        out.push('}');
+        out
+    }
+}
+
+fn detail_value(detail: &ErrorDetail) -> Value {
+    match detail {
+        ErrorDetail::Text(text) => cbor_text(text),
+        ErrorDetail::Uint(value) => cbor_uint(*value),
+    }
+}
+
+fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
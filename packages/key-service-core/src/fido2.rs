@@ -0,0 +1,512 @@
+//! Native FIDO2 `hmac-secret` for user-presence unlock (`fido2` feature).
+//!
+//! [`Fido2Authenticator`] speaks CTAPHID and CTAP2 over a [`HidAuthenticatorAdapter`] and
+//! returns the same 32 bytes a browser's WebAuthn PRF extension would for the same credential
+//! and salt, so a security key enrolled on the web unlocks the vault on desktop and vice versa.
+//! Assertions request user presence only; a browser enrollment made with user verification
+//! yields a different secret and needs re-enrolling here.
+
+use ciborium::value::{Integer, Value};
+use hmac::{Hmac, Mac};
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::{EncodedPoint, FieldBytes, PublicKey, SecretKey};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::adapters::{EntropyAdapter, HidAuthenticatorAdapter};
+use crate::cbor::{cbor_item_len, encode_canonical_value};
+use crate::crypto::hkdf_sha256;
+use crate::domains::WEBAUTHN_PRF_SALT_PREFIX;
+use crate::error::CoreError;
+use crate::hash::sha256;
+
+const REPORT_LEN: usize = 64;
+const INIT_DATA_LEN: usize = REPORT_LEN - 7;
+const CONT_DATA_LEN: usize = REPORT_LEN - 5;
+/// Largest CTAPHID message: one init packet and 128 continuation packets.
+const MAX_MESSAGE_LEN: usize = INIT_DATA_LEN + 128 * CONT_DATA_LEN;
+const BROADCAST_CID: [u8; 4] = [0xff; 4];
+
+const CTAPHID_INIT: u8 = 0x86;
+const CTAPHID_CBOR: u8 = 0x90;
+const CTAPHID_KEEPALIVE: u8 = 0xbb;
+const CTAPHID_ERROR: u8 = 0xbf;
+
+const CTAP2_MAKE_CREDENTIAL: u8 = 0x01;
+const CTAP2_GET_ASSERTION: u8 = 0x02;
+const CTAP2_GET_INFO: u8 = 0x04;
+const CTAP2_CLIENT_PIN: u8 = 0x06;
+const CLIENT_PIN_GET_KEY_AGREEMENT: u64 = 0x02;
+
+const AUTH_DATA_FLAG_AT: u8 = 0x40;
+const AUTH_DATA_FLAG_ED: u8 = 0x80;
+const COSE_ALG_ES256: i64 = -7;
+const COSE_ALG_EDDSA: i64 = -8;
+const COSE_ALG_ECDH_ES_HKDF_256: i64 = -25;
+
+/// How long one report read may block. Authenticators send keepalives every ~100 ms while
+/// waiting for a touch, so this bounds a silent device, not the user.
+pub const FIDO2_READ_TIMEOUT_MS: u32 = 30_000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Fido2Error {
+    #[error("hid transport: {0}")]
+    Transport(String),
+    #[error("authenticator timed out")]
+    Timeout,
+    #[error("ctaphid error 0x{0:02x}")]
+    Hid(u8),
+    #[error("ctap2 error 0x{0:02x}")]
+    Ctap(u8),
+    #[error("authenticator does not support {0}")]
+    Unsupported(&'static str),
+    #[error("malformed authenticator response: {0}")]
+    Malformed(&'static str),
+    #[error(transparent)]
+    Core(#[from] CoreError),
+}
+
+pub type Fido2Result<T> = Result<T, Fido2Error>;
+
+/// A CTAP2 authenticator on an allocated CTAPHID channel.
+pub struct Fido2Authenticator<H: HidAuthenticatorAdapter> {
+    hid: H,
+    cid: [u8; 4],
+}
+
+impl<H: HidAuthenticatorAdapter> Fido2Authenticator<H> {
+    /// Allocates a channel with `CTAPHID_INIT`.
+    pub fn open(hid: H, entropy: &impl EntropyAdapter) -> Fido2Result<Self> {
+        let nonce = entropy.random_bytes(8);
+        let mut authenticator = Self {
+            hid,
+            cid: BROADCAST_CID,
+        };
+        let response = authenticator.transact(CTAPHID_INIT, &nonce)?;
+        if response.len() < 12 || response[..8] != nonce[..] {
+            return Err(Fido2Error::Malformed("ctaphid init response"));
+        }
+        authenticator.cid.copy_from_slice(&response[8..12]);
+        Ok(authenticator)
+    }
+
+    pub fn into_inner(self) -> H {
+        self.hid
+    }
+
+    /// Creates a non-discoverable credential with `hmac-secret` enabled; returns its id. The
+    /// user touches the authenticator once.
+    pub fn make_credential(
+        &self,
+        rp_id: &str,
+        user_handle: &[u8],
+        entropy: &impl EntropyAdapter,
+    ) -> Fido2Result<Vec<u8>> {
+        self.require_hmac_secret()?;
+        let pub_key_cred_params = [COSE_ALG_ES256, COSE_ALG_EDDSA]
+            .into_iter()
+            .map(|alg| {
+                text_map(vec![
+                    ("alg", int(alg)),
+                    ("type", Value::Text("public-key".to_string())),
+                ])
+            })
+            .collect();
+        let request = int_map(vec![
+            (1, Value::Bytes(entropy.random_bytes(32))),
+            (2, text_map(vec![("id", Value::Text(rp_id.to_string()))])),
+            (
+                3,
+                text_map(vec![
+                    ("id", Value::Bytes(user_handle.to_vec())),
+                    ("name", Value::Text(hex::encode(user_handle))),
+                ]),
+            ),
+            (4, Value::Array(pub_key_cred_params)),
+            (6, text_map(vec![("hmac-secret", Value::Bool(true))])),
+        ]);
+        let response = self.ctap(CTAP2_MAKE_CREDENTIAL, Some(&request))?;
+        let auth_data = bytes_at(&response, 2)?;
+        let parsed = parse_auth_data(&auth_data)?;
+        let credential_id = parsed
+            .credential_id
+            .ok_or(Fido2Error::Malformed("no attested credential"))?;
+        match parsed.hmac_secret {
+            Some(Value::Bool(true)) => Ok(credential_id),
+            _ => Err(Fido2Error::Unsupported("hmac-secret on this credential")),
+        }
+    }
+
+    /// The WebAuthn PRF output for `prf_salt` under `credential_id`: the `hmac-secret` output
+    /// for `SHA-256("WebAuthn PRF" || 0x00 || prf_salt)`. The user touches the authenticator.
+    pub fn prf(
+        &self,
+        rp_id: &str,
+        credential_id: &[u8],
+        prf_salt: &[u8],
+        entropy: &impl EntropyAdapter,
+    ) -> Fido2Result<Zeroizing<Vec<u8>>> {
+        let protocol = self.require_hmac_secret()?;
+        let shared = self.key_agreement(protocol, entropy)?;
+        let salt = sha256(&[WEBAUTHN_PRF_SALT_PREFIX, prf_salt].concat());
+        let salt_enc = shared.encrypt(&salt, entropy)?;
+        let salt_auth = shared.authenticate(&salt_enc)?;
+        let mut hmac_secret = vec![
+            (1, shared.platform_key.clone()),
+            (2, Value::Bytes(salt_enc)),
+            (3, Value::Bytes(salt_auth)),
+        ];
+        if protocol == PinUvProtocol::Two {
+            hmac_secret.push((4, int(2)));
+        }
+        let request = int_map(vec![
+            (1, Value::Text(rp_id.to_string())),
+            (2, Value::Bytes(entropy.random_bytes(32))),
+            (
+                3,
+                Value::Array(vec![text_map(vec![
+                    ("id", Value::Bytes(credential_id.to_vec())),
+                    ("type", Value::Text("public-key".to_string())),
+                ])]),
+            ),
+            (4, text_map(vec![("hmac-secret", int_map(hmac_secret))])),
+            (5, text_map(vec![("up", Value::Bool(true))])),
+        ]);
+        let response = self.ctap(CTAP2_GET_ASSERTION, Some(&request))?;
+        let auth_data = bytes_at(&response, 2)?;
+        let output = match parse_auth_data(&auth_data)?.hmac_secret {
+            Some(Value::Bytes(encrypted)) => shared.decrypt(&encrypted)?,
+            _ => return Err(Fido2Error::Malformed("no hmac-secret output")),
+        };
+        if output.len() != 32 {
+            return Err(Fido2Error::Malformed("hmac-secret output length"));
+        }
+        Ok(output)
+    }
+
+    /// Checks `authenticatorGetInfo` for `hmac-secret` and picks the newest PIN/UV protocol.
+    fn require_hmac_secret(&self) -> Fido2Result<PinUvProtocol> {
+        let info = self.ctap(CTAP2_GET_INFO, None)?;
+        let supports_extension = match map_value(&info, 2) {
+            Some(Value::Array(extensions)) => extensions
+                .iter()
+                .any(|ext| matches!(ext, Value::Text(name) if name == "hmac-secret")),
+            _ => false,
+        };
+        if !supports_extension {
+            return Err(Fido2Error::Unsupported("hmac-secret"));
+        }
+        let protocols = match map_value(&info, 6) {
+            Some(Value::Array(protocols)) => protocols.iter().filter_map(as_i64).collect(),
+            // CTAP 2.0 authenticators predate the field and speak protocol one.
+            _ => vec![1],
+        };
+        if protocols.contains(&2) {
+            Ok(PinUvProtocol::Two)
+        } else if protocols.contains(&1) {
+            Ok(PinUvProtocol::One)
+        } else {
+            Err(Fido2Error::Unsupported("pin/uv auth protocol 1 or 2"))
+        }
+    }
+
+    fn key_agreement(
+        &self,
+        protocol: PinUvProtocol,
+        entropy: &impl EntropyAdapter,
+    ) -> Fido2Result<SharedSecret> {
+        let request = int_map(vec![
+            (1, int(protocol as i64)),
+            (2, int(CLIENT_PIN_GET_KEY_AGREEMENT as i64)),
+        ]);
+        let response = self.ctap(CTAP2_CLIENT_PIN, Some(&request))?;
+        let cose = map_value(&response, 1).ok_or(Fido2Error::Malformed("no key agreement"))?;
+        let (x, y) = (cose_coordinate(cose, -2)?, cose_coordinate(cose, -3)?);
+        let encoded = EncodedPoint::from_affine_coordinates(
+            FieldBytes::from_slice(&x),
+            FieldBytes::from_slice(&y),
+            false,
+        );
+        let peer = Option::<PublicKey>::from(PublicKey::from_encoded_point(&encoded))
+            .ok_or(Fido2Error::Malformed("key agreement point"))?;
+
+        let secret = loop {
+            let bytes = Zeroizing::new(entropy.random_bytes(32));
+            if let Ok(secret) = SecretKey::from_slice(&bytes) {
+                break secret;
+            }
+        };
+        let z = p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), peer.as_affine());
+        let z = z.raw_secret_bytes().as_slice();
+        let (hmac_key, aes_key) = match protocol {
+            PinUvProtocol::One => {
+                let key = Zeroizing::new(sha256(z).to_vec());
+                (key.clone(), key)
+            }
+            // HKDF with an all-zero salt equals HKDF without one.
+            PinUvProtocol::Two => (
+                hkdf_sha256(z, b"CTAP2 HMAC key", 32)?,
+                hkdf_sha256(z, b"CTAP2 AES key", 32)?,
+            ),
+        };
+        let point = secret.public_key().to_encoded_point(false);
+        let coordinate =
+            |c: Option<&FieldBytes>| Value::Bytes(c.map(|c| c.to_vec()).unwrap_or_default());
+        let platform_key = Value::Map(vec![
+            (int(1), int(2)),
+            (int(3), int(COSE_ALG_ECDH_ES_HKDF_256)),
+            (int(-1), int(1)),
+            (int(-2), coordinate(point.x())),
+            (int(-3), coordinate(point.y())),
+        ]);
+        Ok(SharedSecret {
+            protocol,
+            hmac_key,
+            aes_key,
+            platform_key,
+        })
+    }
+
+    /// Sends a CTAP2 command and returns its decoded response map.
+    fn ctap(&self, command: u8, params: Option<&Value>) -> Fido2Result<Value> {
+        let mut message = vec![command];
+        if let Some(params) = params {
+            message.extend_from_slice(&encode_canonical_value(params)?);
+        }
+        let response = self.transact(CTAPHID_CBOR, &message)?;
+        let (&status, body) = response
+            .split_first()
+            .ok_or(Fido2Error::Malformed("empty ctap2 response"))?;
+        if status != 0 {
+            return Err(Fido2Error::Ctap(status));
+        }
+        if body.is_empty() {
+            return Ok(Value::Map(Vec::new()));
+        }
+        ciborium::de::from_reader(body).map_err(|_| Fido2Error::Malformed("ctap2 cbor"))
+    }
+
+    /// One CTAPHID request/response on this channel, skipping keepalives.
+    fn transact(&self, command: u8, payload: &[u8]) -> Fido2Result<Vec<u8>> {
+        if payload.len() > MAX_MESSAGE_LEN {
+            return Err(Fido2Error::Malformed("ctaphid request too large"));
+        }
+        let mut report = [0u8; REPORT_LEN];
+        report[..4].copy_from_slice(&self.cid);
+        report[4] = command;
+        report[5..7].copy_from_slice(&(payload.len() as u16).to_be_bytes());
+        let first = payload.len().min(INIT_DATA_LEN);
+        report[7..7 + first].copy_from_slice(&payload[..first]);
+        self.write(&report)?;
+        for (seq, chunk) in payload[first..].chunks(CONT_DATA_LEN).enumerate() {
+            let mut report = [0u8; REPORT_LEN];
+            report[..4].copy_from_slice(&self.cid);
+            report[4] = seq as u8;
+            report[5..5 + chunk.len()].copy_from_slice(chunk);
+            self.write(&report)?;
+        }
+
+        let (response_command, len, mut response) = loop {
+            let report = self.read()?;
+            if report[..4] != self.cid[..] {
+                continue;
+            }
+            match report[4] {
+                CTAPHID_KEEPALIVE => continue,
+                CTAPHID_ERROR => return Err(Fido2Error::Hid(report[7])),
+                cmd if cmd & 0x80 != 0 => {
+                    let len = u16::from_be_bytes([report[5], report[6]]) as usize;
+                    let first = len.min(INIT_DATA_LEN);
+                    break (cmd, len, report[7..7 + first].to_vec());
+                }
+                _ => continue,
+            }
+        };
+        if response_command != command || len > MAX_MESSAGE_LEN {
+            return Err(Fido2Error::Malformed("ctaphid response"));
+        }
+        let mut expected_seq = 0u8;
+        while response.len() < len {
+            let report = self.read()?;
+            if report[..4] != self.cid[..] {
+                continue;
+            }
+            if report[4] != expected_seq {
+                return Err(Fido2Error::Malformed("ctaphid continuation sequence"));
+            }
+            let take = (len - response.len()).min(CONT_DATA_LEN);
+            response.extend_from_slice(&report[5..5 + take]);
+            expected_seq += 1;
+        }
+        Ok(response)
+    }
+
+    fn write(&self, report: &[u8; REPORT_LEN]) -> Fido2Result<()> {
+        self.hid
+            .write_report(report)
+            .map_err(|e| Fido2Error::Transport(format!("{e:?}")))
+    }
+
+    fn read(&self) -> Fido2Result<[u8; REPORT_LEN]> {
+        self.hid
+            .read_report(FIDO2_READ_TIMEOUT_MS)
+            .map_err(|e| Fido2Error::Transport(format!("{e:?}")))?
+            .ok_or(Fido2Error::Timeout)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PinUvProtocol {
+    One = 1,
+    Two = 2,
+}
+
+/// PIN/UV auth protocol keys from one `getKeyAgreement` exchange.
+struct SharedSecret {
+    protocol: PinUvProtocol,
+    hmac_key: Zeroizing<Vec<u8>>,
+    aes_key: Zeroizing<Vec<u8>>,
+    platform_key: Value,
+}
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+impl SharedSecret {
+    /// Protocol one uses a zero IV; protocol two prepends a random one.
+    fn encrypt(&self, plaintext: &[u8], entropy: &impl EntropyAdapter) -> Fido2Result<Vec<u8>> {
+        use cbc::cipher::{block_padding::NoPadding, BlockEncryptMut, KeyIvInit};
+        let iv = match self.protocol {
+            PinUvProtocol::One => vec![0u8; 16],
+            PinUvProtocol::Two => entropy.random_bytes(16),
+        };
+        let cipher = Aes256CbcEnc::new_from_slices(&self.aes_key, &iv)
+            .map_err(|_| CoreError::Crypto("aes-cbc key".to_string()))?;
+        let ciphertext = cipher.encrypt_padded_vec_mut::<NoPadding>(plaintext);
+        Ok(match self.protocol {
+            PinUvProtocol::One => ciphertext,
+            PinUvProtocol::Two => [iv, ciphertext].concat(),
+        })
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Fido2Result<Zeroizing<Vec<u8>>> {
+        use cbc::cipher::{block_padding::NoPadding, BlockDecryptMut, KeyIvInit};
+        let (iv, ciphertext) = match self.protocol {
+            PinUvProtocol::One => (&[0u8; 16][..], data),
+            PinUvProtocol::Two if data.len() >= 16 => data.split_at(16),
+            PinUvProtocol::Two => return Err(Fido2Error::Malformed("hmac-secret output length")),
+        };
+        let cipher = Aes256CbcDec::new_from_slices(&self.aes_key, iv)
+            .map_err(|_| CoreError::Crypto("aes-cbc key".to_string()))?;
+        cipher
+            .decrypt_padded_vec_mut::<NoPadding>(ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| Fido2Error::Malformed("hmac-secret output length"))
+    }
+
+    /// HMAC-SHA-256, truncated to 16 bytes under protocol one.
+    fn authenticate(&self, message: &[u8]) -> Fido2Result<Vec<u8>> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.hmac_key)
+            .map_err(|_| CoreError::Crypto("hmac key".to_string()))?;
+        mac.update(message);
+        let mut tag = mac.finalize().into_bytes().to_vec();
+        if self.protocol == PinUvProtocol::One {
+            tag.truncate(16);
+        }
+        Ok(tag)
+    }
+}
+
+struct AuthData {
+    credential_id: Option<Vec<u8>>,
+    hmac_secret: Option<Value>,
+}
+
+/// `rpIdHash(32) || flags || signCount(4) || [attestedCredentialData] || [extensions]`.
+fn parse_auth_data(auth_data: &[u8]) -> Fido2Result<AuthData> {
+    const HEADER_LEN: usize = 37;
+    if auth_data.len() < HEADER_LEN {
+        return Err(Fido2Error::Malformed("authenticator data length"));
+    }
+    let flags = auth_data[32];
+    let mut rest = &auth_data[HEADER_LEN..];
+    let mut credential_id = None;
+    if flags & AUTH_DATA_FLAG_AT != 0 {
+        // aaguid(16) || credentialIdLength(2) || credentialId || COSE public key
+        if rest.len() < 18 {
+            return Err(Fido2Error::Malformed("attested credential data"));
+        }
+        let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+        let id_end = 18 + id_len;
+        if rest.len() < id_end {
+            return Err(Fido2Error::Malformed("credential id length"));
+        }
+        credential_id = Some(rest[18..id_end].to_vec());
+        let key_len = cbor_item_len(&rest[id_end..])?
+            .ok_or(Fido2Error::Malformed("credential public key"))?;
+        rest = &rest[id_end + key_len..];
+    }
+    let mut hmac_secret = None;
+    if flags & AUTH_DATA_FLAG_ED != 0 {
+        let extensions: Value =
+            ciborium::de::from_reader(rest).map_err(|_| Fido2Error::Malformed("extensions"))?;
+        if let Value::Map(entries) = extensions {
+            hmac_secret = entries
+                .into_iter()
+                .find(|(k, _)| matches!(k, Value::Text(name) if name == "hmac-secret"))
+                .map(|(_, v)| v);
+        }
+    }
+    Ok(AuthData {
+        credential_id,
+        hmac_secret,
+    })
+}
+
+fn int(value: i64) -> Value {
+    Value::Integer(Integer::from(value))
+}
+
+fn int_map(entries: Vec<(i64, Value)>) -> Value {
+    Value::Map(entries.into_iter().map(|(k, v)| (int(k), v)).collect())
+}
+
+fn text_map(entries: Vec<(&str, Value)>) -> Value {
+    Value::Map(
+        entries
+            .into_iter()
+            .map(|(k, v)| (Value::Text(k.to_string()), v))
+            .collect(),
+    )
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(i) => i64::try_from(*i).ok(),
+        _ => None,
+    }
+}
+
+fn map_value(map: &Value, key: i64) -> Option<&Value> {
+    match map {
+        Value::Map(entries) => entries
+            .iter()
+            .find(|(k, _)| as_i64(k) == Some(key))
+            .map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+fn bytes_at(map: &Value, key: i64) -> Fido2Result<Vec<u8>> {
+    match map_value(map, key) {
+        Some(Value::Bytes(bytes)) => Ok(bytes.clone()),
+        _ => Err(Fido2Error::Malformed("missing byte string")),
+    }
+}
+
+fn cose_coordinate(cose: &Value, label: i64) -> Fido2Result<Vec<u8>> {
+    match map_value(cose, label) {
+        Some(Value::Bytes(bytes)) if bytes.len() == 32 => Ok(bytes.clone()),
+        _ => Err(Fido2Error::Malformed("cose key coordinate")),
+    }
+}
@@ -1,18 +1,24 @@
 //! Canonical wire formats for KeyVault, scope state, and grants.
 
 use crate::cbor::{
-    as_array, as_map, cbor_array, cbor_bytes, cbor_map, cbor_text, cbor_uint,
-    decode_canonical_value, encode_canonical_value, opt_bytes, req_bytes, req_text, req_uint,
+    as_array, as_map, cbor_array, cbor_bytes, cbor_context, cbor_head, cbor_item_len, cbor_map,
+    cbor_text, cbor_uint, decode_canonical_value, encode_canonical_value, encode_cbor_head,
+    opt_bytes, opt_text, opt_uint, req_array, req_bytes, req_text, req_uint, zeroize_value,
     CborLimits,
 };
-use crate::crypto::KdfParams;
+use crate::ciphersuite::{SignerKeys, ED25519_PUBLIC_KEY_LEN, MLDSA65_PUBLIC_KEY_LEN};
+use crate::compress::{compress, decompress};
+use crate::cosign::{CosignCommitment, COSIGN_ELEMENT_LEN, COSIGN_PROOF_LEN};
+use crate::crypto::{KdfMinimums, KdfParams};
+use crate::device_shares::{DEVICE_SHARE_SPLIT_ID_LEN, MAX_SHARE_DEVICES};
 use crate::error::{CoreError, CoreResult};
 use crate::hash::sha256;
 use crate::types::{
-    AeadId, DeviceId, KemCiphersuiteId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId,
-    SigCiphersuiteId, UserId,
+    AeadId, CapabilityOps, DeviceId, EpochRetirement, KemCiphersuiteId, KeyWrapAlg, ResourceId,
+    ResourceKeyId, ScopeEpoch, ScopeId, ScopeRole, SigCiphersuiteId, SnapshotCompression, UserId,
 };
 use ciborium::value::Value;
+use std::fmt;
 
 #[derive(Clone, Debug)]
 pub struct ScopeStateV1 {
@@ -29,13 +35,18 @@ pub struct ScopeStateV1 {
 }
 
 impl ScopeStateV1 {
+    /// Errors are prefixed with `scope_state` and the key path.
     pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("scope_state"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
         let map = as_map(&value)?;
         let v = req_uint(map, 0)?;
         let scope_id = ScopeId(req_text(map, 1)?);
         let scope_state_seq = req_uint(map, 2)?;
         let prev_hash = req_bytes(map, 3)?;
-        require_len(&prev_hash, 32, "scope_state.prev_hash")?;
+        require_len(&prev_hash, 32, 3, "prev_hash")?;
         let scope_epoch = req_uint(map, 4)?;
         let kind = req_uint(map, 5)?;
         let payload = map_get(map, 6)?.clone();
@@ -93,6 +104,283 @@ impl ScopeStateV1 {
     pub fn scope_state_ref(&self) -> CoreResult<String> {
         Ok(hex::encode(self.scope_state_ref_bytes()?))
     }
+
+    pub fn typed_payload(&self) -> CoreResult<ScopeStatePayload> {
+        ScopeStatePayload::from_cbor(self.kind, &self.payload)
+    }
+}
+
+pub const SCOPE_STATE_KIND_GENESIS: u64 = 0;
+pub const SCOPE_STATE_KIND_EPOCH_BUMP: u64 = 1;
+pub const SCOPE_STATE_KIND_MEMBERSHIP_CHANGE: u64 = 2;
+
+/// Typed `ScopeStateV1.payload`, keyed by `kind`.
+///
+/// Every kind carries the owner's signer public keys at payload keys 1 (Ed25519) and 2 (ML-DSA).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScopeStatePayload {
+    Genesis {
+        signer: SignerKeys,
+    },
+    EpochBump {
+        signer: SignerKeys,
+        from_epoch: u64,
+        /// Must equal the scope state's `scope_epoch`.
+        to_epoch: u64,
+        retirement: EpochRetirement,
+    },
+    MembershipChange {
+        signer: SignerKeys,
+        added: Vec<UserId>,
+        removed: Vec<UserId>,
+        /// Role assignments for signer devices (key 5, omitted when empty).
+        roles: Vec<(DeviceId, ScopeRole)>,
+        /// Public KEM keys of users in `added` (key 6, omitted when empty), which members cache
+        /// in their recipient key directory.
+        member_keys: Vec<(UserId, Vec<u8>)>,
+    },
+}
+
+impl ScopeStatePayload {
+    /// Strict decode: every field of `kind` must be present with the right type and length, and
+    /// unknown kinds or payload keys are rejected. Errors name the offending field.
+    pub fn from_cbor(kind: u64, value: &Value) -> CoreResult<Self> {
+        let allowed: &[u64] = match kind {
+            SCOPE_STATE_KIND_GENESIS => &[1, 2],
+            SCOPE_STATE_KIND_EPOCH_BUMP => &[1, 2, 3, 4, 5],
+            SCOPE_STATE_KIND_MEMBERSHIP_CHANGE => &[1, 2, 3, 4, 5, 6],
+            _ => return Err(payload_error("kind", &format!("unknown kind {kind}"))),
+        };
+        let map = as_map(value).map_err(|_| payload_error("payload", "expected a map"))?;
+        for (key, _) in map {
+            let known = match key {
+                Value::Integer(int) => u64::try_from(*int).is_ok_and(|key| allowed.contains(&key)),
+                _ => false,
+            };
+            if !known {
+                return Err(payload_error(
+                    "payload",
+                    &format!("unknown key {key:?} for kind {kind}"),
+                ));
+            }
+        }
+        let signer = SignerKeys {
+            sig_suite: SigCiphersuiteId::HybridSig1,
+            ed25519_pub: payload_key(map, 1, "ed25519_pub", ED25519_PUBLIC_KEY_LEN)?,
+            mldsa_pub: payload_key(map, 2, "mldsa_pub", MLDSA65_PUBLIC_KEY_LEN)?,
+        };
+        match kind {
+            SCOPE_STATE_KIND_GENESIS => Ok(Self::Genesis { signer }),
+            SCOPE_STATE_KIND_EPOCH_BUMP => Ok(Self::EpochBump {
+                signer,
+                from_epoch: payload_uint(map, 3, "from_epoch")?,
+                to_epoch: payload_uint(map, 4, "to_epoch")?,
+                retirement: EpochRetirement::from_u64(payload_uint(map, 5, "retirement")?)
+                    .ok_or_else(|| payload_error("payload.retirement", "unknown policy"))?,
+            }),
+            _ => {
+                let added = decode_user_ids(payload_field(map, 3, "added")?, "added")?;
+                let removed = decode_user_ids(payload_field(map, 4, "removed")?, "removed")?;
+                let roles = map_get_opt(map, 5)
+                    .map(decode_role_assignments)
+                    .transpose()?
+                    .unwrap_or_default();
+                let member_keys = map_get_opt(map, 6)
+                    .map(decode_member_keys)
+                    .transpose()?
+                    .unwrap_or_default();
+                if added.is_empty() && removed.is_empty() && roles.is_empty() {
+                    return Err(payload_error(
+                        "payload",
+                        "membership change changes nothing",
+                    ));
+                }
+                Ok(Self::MembershipChange {
+                    signer,
+                    added,
+                    removed,
+                    roles,
+                    member_keys,
+                })
+            }
+        }
+    }
+
+    pub fn kind(&self) -> u64 {
+        match self {
+            Self::Genesis { .. } => SCOPE_STATE_KIND_GENESIS,
+            Self::EpochBump { .. } => SCOPE_STATE_KIND_EPOCH_BUMP,
+            Self::MembershipChange { .. } => SCOPE_STATE_KIND_MEMBERSHIP_CHANGE,
+        }
+    }
+
+    pub fn signer(&self) -> &SignerKeys {
+        match self {
+            Self::Genesis { signer }
+            | Self::EpochBump { signer, .. }
+            | Self::MembershipChange { signer, .. } => signer,
+        }
+    }
+
+    pub fn to_cbor(&self) -> Value {
+        let signer = self.signer();
+        let mut entries = vec![
+            (1, cbor_bytes(&signer.ed25519_pub)),
+            (2, cbor_bytes(&signer.mldsa_pub)),
+        ];
+        match self {
+            Self::Genesis { .. } => {}
+            Self::EpochBump {
+                from_epoch,
+                to_epoch,
+                retirement,
+                ..
+            } => {
+                entries.push((3, cbor_uint(*from_epoch)));
+                entries.push((4, cbor_uint(*to_epoch)));
+                entries.push((5, cbor_uint(retirement.as_u64())));
+            }
+            Self::MembershipChange {
+                added,
+                removed,
+                roles,
+                member_keys,
+                ..
+            } => {
+                entries.push((3, encode_user_ids(added)));
+                entries.push((4, encode_user_ids(removed)));
+                if !roles.is_empty() {
+                    entries.push((5, encode_role_assignments(roles)));
+                }
+                if !member_keys.is_empty() {
+                    entries.push((6, encode_member_keys(member_keys)));
+                }
+            }
+        }
+        cbor_map(entries)
+    }
+}
+
+fn payload_error(field: &str, problem: &str) -> CoreError {
+    CoreError::Format(format!("scope_state.{field}: {problem}"))
+}
+
+fn payload_field<'a>(map: &'a [(Value, Value)], key: u64, name: &str) -> CoreResult<&'a Value> {
+    map_get_opt(map, key)
+        .ok_or_else(|| payload_error(&format!("payload.{name}"), &format!("missing key {key}")))
+}
+
+fn payload_uint(map: &[(Value, Value)], key: u64, name: &str) -> CoreResult<u64> {
+    match payload_field(map, key, name)? {
+        Value::Integer(int) => u64::try_from(*int).ok(),
+        _ => None,
+    }
+    .ok_or_else(|| payload_error(&format!("payload.{name}"), "expected u64"))
+}
+
+fn payload_key(map: &[(Value, Value)], key: u64, name: &str, len: usize) -> CoreResult<Vec<u8>> {
+    let field = format!("payload.{name}");
+    match payload_field(map, key, name)? {
+        Value::Bytes(bytes) if bytes.len() == len => Ok(bytes.clone()),
+        Value::Bytes(bytes) => Err(payload_error(
+            &field,
+            &format!("expected {len} bytes, got {}", bytes.len()),
+        )),
+        _ => Err(payload_error(&field, "expected bytes")),
+    }
+}
+
+fn decode_user_ids(value: &Value, name: &str) -> CoreResult<Vec<UserId>> {
+    let field = format!("payload.{name}");
+    let items = as_array(value).map_err(|_| payload_error(&field, "expected an array"))?;
+    let mut ids: Vec<UserId> = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        let id = match item {
+            Value::Text(text) if !text.is_empty() => UserId(text.clone()),
+            _ => {
+                return Err(payload_error(
+                    &format!("{field}[{i}]"),
+                    "expected non-empty user id text",
+                ))
+            }
+        };
+        if ids.contains(&id) {
+            return Err(payload_error(&format!("{field}[{i}]"), "duplicate user id"));
+        }
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+fn encode_user_ids(ids: &[UserId]) -> Value {
+    cbor_array(ids.iter().map(|id| cbor_text(&id.0)).collect())
+}
+
+/// `[[device_id, role], ...]`.
+fn decode_role_assignments(value: &Value) -> CoreResult<Vec<(DeviceId, ScopeRole)>> {
+    let items = as_array(value).map_err(|_| payload_error("payload.roles", "expected an array"))?;
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let field = format!("payload.roles[{i}]");
+            match as_array(item).map_err(|_| payload_error(&field, "expected [device_id, role]"))? {
+                [Value::Text(device_id), Value::Integer(role)] if !device_id.is_empty() => {
+                    u64::try_from(*role)
+                        .ok()
+                        .and_then(ScopeRole::from_u64)
+                        .map(|role| (DeviceId(device_id.clone()), role))
+                        .ok_or_else(|| payload_error(&field, "unknown scope role"))
+                }
+                _ => Err(payload_error(&field, "expected [device_id, role]")),
+            }
+        })
+        .collect()
+}
+
+fn encode_role_assignments(roles: &[(DeviceId, ScopeRole)]) -> Value {
+    cbor_array(
+        roles
+            .iter()
+            .map(|(device_id, role)| {
+                cbor_array(vec![cbor_text(&device_id.0), cbor_uint(role.as_u64())])
+            })
+            .collect(),
+    )
+}
+
+/// `[[user_id, public_bytes], ...]`.
+fn decode_member_keys(value: &Value) -> CoreResult<Vec<(UserId, Vec<u8>)>> {
+    let items =
+        as_array(value).map_err(|_| payload_error("payload.member_keys", "expected an array"))?;
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let field = format!("payload.member_keys[{i}]");
+            match as_array(item)
+                .map_err(|_| payload_error(&field, "expected [user_id, public_bytes]"))?
+            {
+                [Value::Text(user_id), Value::Bytes(public_bytes)]
+                    if !user_id.is_empty() && !public_bytes.is_empty() =>
+                {
+                    Ok((UserId(user_id.clone()), public_bytes.clone()))
+                }
+                _ => Err(payload_error(&field, "expected [user_id, public_bytes]")),
+            }
+        })
+        .collect()
+}
+
+fn encode_member_keys(member_keys: &[(UserId, Vec<u8>)]) -> Value {
+    cbor_array(
+        member_keys
+            .iter()
+            .map(|(user_id, public_bytes)| {
+                cbor_array(vec![cbor_text(&user_id.0), cbor_bytes(public_bytes)])
+            })
+            .collect(),
+    )
 }
 
 #[derive(Clone, Debug)]
@@ -116,16 +404,21 @@ pub struct ResourceGrantV1 {
 }
 
 impl ResourceGrantV1 {
+    /// Errors are prefixed with `resource_grant` and the key path.
     pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("resource_grant"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
         let map = as_map(&value)?;
         let v = req_uint(map, 0)?;
         let grant_id = req_text(map, 1)?;
         let scope_id = ScopeId(req_text(map, 2)?);
         let grant_seq = req_uint(map, 3)?;
         let prev_hash = req_bytes(map, 4)?;
-        require_len(&prev_hash, 32, "resource_grant.prev_hash")?;
+        require_len(&prev_hash, 32, 4, "prev_hash")?;
         let scope_state_ref = req_bytes(map, 5)?;
-        require_len(&scope_state_ref, 32, "resource_grant.scope_state_ref")?;
+        require_len(&scope_state_ref, 32, 5, "scope_state_ref")?;
         let scope_epoch = req_uint(map, 6)?;
         let resource_id = ResourceId(req_text(map, 7)?);
         let resource_key_id = ResourceKeyId(req_text(map, 8)?);
@@ -133,7 +426,7 @@ impl ResourceGrantV1 {
         let aead = AeadId::try_from(req_text(map, 10)?.as_str())
             .map_err(|e| CoreError::Format(e.to_string()))?;
         let nonce = req_bytes(map, 11)?;
-        require_len(&nonce, 12, "resource_grant.nonce")?;
+        require_len(&nonce, 12, 11, "nonce")?;
         let wrapped_key = req_bytes(map, 12)?;
         let signer_device_id = DeviceId(req_text(map, 13)?);
         let sig_suite = SigCiphersuiteId::try_from(req_text(map, 14)?.as_str())
@@ -232,10 +525,18 @@ pub struct KeyEnvelopeV1 {
     pub sig_suite: SigCiphersuiteId,
     pub signature: Vec<u8>,
     pub recipient_uk_pub_fingerprint: Option<Vec<u8>>,
+    /// One-time prekey the scope key is sealed to (key 15), instead of the user key; see
+    /// [`PrekeyBundleV1`]. Such envelopes must carry `recipient_uk_pub_fingerprint`.
+    pub prekey_id: Option<u64>,
 }
 
 impl KeyEnvelopeV1 {
+    /// Errors are prefixed with `key_envelope` and the key path.
     pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("key_envelope"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
         let map = as_map(&value)?;
         let v = req_uint(map, 0)?;
         let envelope_id = req_text(map, 1)?;
@@ -243,20 +544,26 @@ impl KeyEnvelopeV1 {
         let scope_epoch = ScopeEpoch(req_uint(map, 3)?);
         let recipient_user_id = UserId(req_text(map, 4)?);
         let scope_state_ref = req_bytes(map, 5)?;
-        require_len(&scope_state_ref, 32, "key_envelope.scope_state_ref")?;
+        require_len(&scope_state_ref, 32, 5, "scope_state_ref")?;
         let kem = KemCiphersuiteId::try_from(req_text(map, 6)?.as_str())
             .map_err(|e| CoreError::Format(e.to_string()))?;
         let aead = AeadId::try_from(req_text(map, 7)?.as_str())
             .map_err(|e| CoreError::Format(e.to_string()))?;
         let enc = req_bytes(map, 8)?;
         let nonce = req_bytes(map, 9)?;
-        require_len(&nonce, 12, "key_envelope.nonce")?;
+        require_len(&nonce, 12, 9, "nonce")?;
         let wrapped_scope_key = req_bytes(map, 10)?;
         let signer_device_id = DeviceId(req_text(map, 11)?);
         let sig_suite = SigCiphersuiteId::try_from(req_text(map, 12)?.as_str())
             .map_err(|e| CoreError::Format(e.to_string()))?;
         let signature = req_bytes(map, 13)?;
         let recipient_uk_pub_fingerprint = opt_bytes(map, 14)?;
+        let prekey_id = opt_uint(map, 15)?;
+        if prekey_id.is_some() && recipient_uk_pub_fingerprint.is_none() {
+            return Err(CoreError::Format(
+                "prekey envelope without recipient_uk_pub_fingerprint".to_string(),
+            ));
+        }
 
         Ok(Self {
             v,
@@ -274,6 +581,7 @@ impl KeyEnvelopeV1 {
             sig_suite,
             signature,
             recipient_uk_pub_fingerprint,
+            prekey_id,
         })
     }
 
@@ -296,6 +604,9 @@ impl KeyEnvelopeV1 {
         if let Some(fp) = &self.recipient_uk_pub_fingerprint {
             entries.push((14, cbor_bytes(fp)));
         }
+        if let Some(prekey_id) = self.prekey_id {
+            entries.push((15, cbor_uint(prekey_id)));
+        }
         let value = cbor_map(entries);
         encode_canonical_value(&value)
     }
@@ -306,6 +617,11 @@ pub struct VaultKeyWrapV1 {
     pub aead: AeadId,
     pub nonce: Vec<u8>,
     pub ct: Vec<u8>,
+    /// Key commitment (see `crypto::committing_wrap`); absent on legacy and AES-KWP wraps.
+    pub commitment: Option<Vec<u8>>,
+    /// Encoded only when not [`KeyWrapAlg::AesGcm`], so older headers decode unchanged. AES-KWP
+    /// wraps have an empty `nonce`.
+    pub alg: KeyWrapAlg,
 }
 
 #[derive(Clone, Debug)]
@@ -317,6 +633,40 @@ pub struct KeyVaultHeaderV1 {
     pub aead: AeadId,
     pub records: Vec<KeyVaultRecordContainerV1>,
     pub vault_key_wrap: VaultKeyWrapV1,
+    /// Key 7, set in KMS mode: `vault_key_wrap` then seals the KMS ciphertext of `K_vault`
+    /// under this KMS key id rather than `K_vault` itself.
+    pub kms_key_id: Option<String>,
+    /// Key 8, set while `K_vault` is split across the user's devices; see
+    /// [`crate::device_shares`].
+    pub device_shares: Option<DeviceShareConfigV1>,
+    /// Key 9, absent on vaults whose passphrase was last set before the check existed.
+    pub passphrase_check: Option<PassphraseCheckV1>,
+}
+
+/// A check value sealed under the passphrase KEK beside `vault_key_wrap`, so that a KEK that
+/// opens the check but not the wrap points at a damaged header rather than a wrong passphrase.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassphraseCheckV1 {
+    pub nonce: Vec<u8>,
+    pub ct: Vec<u8>,
+}
+
+impl PassphraseCheckV1 {
+    fn from_cbor(value: &Value) -> CoreResult<Self> {
+        let map = as_map(value)?;
+        let nonce = req_bytes(map, 0)?;
+        require_len(&nonce, 12, 0, "nonce")?;
+        let ct = req_bytes(map, 1)?;
+        require_len(&ct, 48, 1, "ct")?;
+        Ok(Self { nonce, ct })
+    }
+
+    fn to_value(&self) -> Value {
+        cbor_map(vec![
+            (0, cbor_bytes(&self.nonce)),
+            (1, cbor_bytes(&self.ct)),
+        ])
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -329,17 +679,192 @@ pub struct KeyVaultRecordContainerV1 {
     pub ct: Vec<u8>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct KeyVaultRecordPlainV1 {
     pub record_id: String,
     pub kind: u64,
     pub payload: Value,
+    /// Present on version 2 records; `None` encodes the original v1 layout.
+    pub provenance: Option<KeyVaultRecordProvenance>,
+}
+
+/// When and from which device a keyvault record was appended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultRecordProvenance {
+    pub created_at_ms: u64,
+    /// `None` for records appended before the vault held a device signing key.
+    pub origin_device_id: Option<DeviceId>,
+}
+
+impl fmt::Debug for KeyVaultRecordPlainV1 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyVaultRecordPlainV1")
+            .field("record_id", &self.record_id)
+            .field("kind", &self.kind)
+            .field("payload", &"<redacted>")
+            .field("provenance", &self.provenance)
+            .finish()
+    }
+}
+
+impl Drop for KeyVaultRecordPlainV1 {
+    fn drop(&mut self) {
+        zeroize_value(&mut self.payload);
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct KeyVaultSnapshotV1 {
     pub header: KeyVaultHeaderV1,
     pub records: Vec<KeyVaultRecordContainerV1>,
+    /// Key 4; absent from snapshots exported before manifests or by a vault without a device.
+    pub manifest: Option<KeyVaultManifestV1>,
+}
+
+/// Signed summary of an exported snapshot, so import can tell a tampered or truncated backup
+/// before it overwrites local state. Signed by the exporting device under
+/// `mo-sig|keyvault-manifest|v1`; its keys travel with the manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultManifestV1 {
+    pub v: u64,
+    pub vault_id: String,
+    /// SHA-256 of the encoded snapshot header.
+    pub header_hash: Vec<u8>,
+    pub record_count: u64,
+    /// SHA-256 of the last record container, or 32 zero bytes without records.
+    pub head_hash: Vec<u8>,
+    pub created_at_ms: u64,
+    pub signer_device_id: DeviceId,
+    pub signer: SignerKeys,
+    pub signature: Vec<u8>,
+}
+
+impl KeyVaultManifestV1 {
+    /// Errors are prefixed with `keyvault_manifest` and the key path.
+    pub fn from_cbor(value: &Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("keyvault_manifest"))
+    }
+
+    fn from_cbor_inner(value: &Value) -> CoreResult<Self> {
+        let map = as_map(value)?;
+        let header_hash = req_bytes(map, 2)?;
+        require_len(&header_hash, 32, 2, "header_hash")?;
+        let head_hash = req_bytes(map, 4)?;
+        require_len(&head_hash, 32, 4, "head_hash")?;
+        let ed25519_pub = req_bytes(map, 7)?;
+        require_len(&ed25519_pub, ED25519_PUBLIC_KEY_LEN, 7, "ed25519_pub")?;
+        let mldsa_pub = req_bytes(map, 8)?;
+        require_len(&mldsa_pub, MLDSA65_PUBLIC_KEY_LEN, 8, "mldsa_pub")?;
+        let sig_suite = SigCiphersuiteId::try_from(req_text(map, 9)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        Ok(Self {
+            v: req_uint(map, 0)?,
+            vault_id: req_text(map, 1)?,
+            header_hash,
+            record_count: req_uint(map, 3)?,
+            head_hash,
+            created_at_ms: req_uint(map, 5)?,
+            signer_device_id: DeviceId(req_text(map, 6)?),
+            signer: SignerKeys {
+                sig_suite,
+                ed25519_pub,
+                mldsa_pub,
+            },
+            signature: req_bytes(map, 10)?,
+        })
+    }
+
+    fn signed_entries(&self) -> Vec<(u64, Value)> {
+        vec![
+            (0, cbor_uint(self.v)),
+            (1, cbor_text(&self.vault_id)),
+            (2, cbor_bytes(&self.header_hash)),
+            (3, cbor_uint(self.record_count)),
+            (4, cbor_bytes(&self.head_hash)),
+            (5, cbor_uint(self.created_at_ms)),
+            (6, cbor_text(&self.signer_device_id.0)),
+            (7, cbor_bytes(&self.signer.ed25519_pub)),
+            (8, cbor_bytes(&self.signer.mldsa_pub)),
+            (9, cbor_text(self.signer.sig_suite.as_str())),
+        ]
+    }
+
+    pub fn to_be_signed_bytes(&self) -> CoreResult<Vec<u8>> {
+        encode_canonical_value(&cbor_map(self.signed_entries()))
+    }
+
+    fn to_value(&self) -> Value {
+        let mut entries = self.signed_entries();
+        entries.push((10, cbor_bytes(&self.signature)));
+        cbor_map(entries)
+    }
+}
+
+/// An n-of-m split of `K_vault`: device `devices[i]` holds the share at index `i + 1`, and any
+/// `threshold` of them unlock.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceShareConfigV1 {
+    pub split_id: Vec<u8>,
+    pub threshold: u64,
+    pub devices: Vec<DeviceId>,
+    /// [`crate::device_shares::device_share_key_check`] of `K_vault` for this split.
+    pub key_check: Vec<u8>,
+}
+
+impl DeviceShareConfigV1 {
+    /// 1-based share index of `device_id`, if the split names it.
+    pub fn share_index(&self, device_id: &DeviceId) -> Option<u64> {
+        self.devices
+            .iter()
+            .position(|device| device == device_id)
+            .map(|pos| pos as u64 + 1)
+    }
+
+    fn from_cbor(value: &Value) -> CoreResult<Self> {
+        let map = as_map(value)?;
+        let split_id = req_bytes(map, 0)?;
+        require_len(&split_id, DEVICE_SHARE_SPLIT_ID_LEN, 0, "split_id")?;
+        let threshold = req_uint(map, 1)?;
+        let devices = req_array(map, 2)?
+            .iter()
+            .map(|item| match item {
+                Value::Text(device_id) => Ok(DeviceId(device_id.clone())),
+                _ => Err(CoreError::Format(
+                    "[2]: expected text device id".to_string(),
+                )),
+            })
+            .collect::<CoreResult<Vec<_>>>()?;
+        let key_check = req_bytes(map, 3)?;
+        require_len(&key_check, 32, 3, "key_check")?;
+        if devices.len() > MAX_SHARE_DEVICES
+            || (1..devices.len()).any(|i| devices[..i].contains(&devices[i]))
+        {
+            return Err(CoreError::Format("[2]: invalid share devices".to_string()));
+        }
+        if threshold < 2 || threshold > devices.len() as u64 {
+            return Err(CoreError::Format(format!(
+                "[1]: threshold {threshold} out of range"
+            )));
+        }
+        Ok(Self {
+            split_id,
+            threshold,
+            devices,
+            key_check,
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        cbor_map(vec![
+            (0, cbor_bytes(&self.split_id)),
+            (1, cbor_uint(self.threshold)),
+            (
+                2,
+                cbor_array(self.devices.iter().map(|d| cbor_text(&d.0)).collect()),
+            ),
+            (3, cbor_bytes(&self.key_check)),
+        ])
+    }
 }
 
 pub fn encode_keyvault_header_v1(header: &KeyVaultHeaderV1) -> CoreResult<Vec<u8>> {
@@ -355,12 +880,19 @@ pub fn encode_keyvault_header_v1(header: &KeyVaultHeaderV1) -> CoreResult<Vec<u8
             ]),
         ),
     ]);
-    let vault_key_wrap = cbor_map(vec![
+    let mut vault_key_wrap_entries = vec![
         (0, cbor_text(header.vault_key_wrap.aead.as_str())),
         (1, cbor_bytes(&header.vault_key_wrap.nonce)),
         (2, cbor_bytes(&header.vault_key_wrap.ct)),
-    ]);
-    let value = cbor_map(vec![
+    ];
+    if let Some(commitment) = &header.vault_key_wrap.commitment {
+        vault_key_wrap_entries.push((3, cbor_bytes(commitment)));
+    }
+    if header.vault_key_wrap.alg != KeyWrapAlg::AesGcm {
+        vault_key_wrap_entries.push((4, cbor_text(header.vault_key_wrap.alg.as_str())));
+    }
+    let vault_key_wrap = cbor_map(vault_key_wrap_entries);
+    let mut entries = vec![
         (0, cbor_uint(header.v)),
         (1, cbor_text(&header.vault_id)),
         (2, cbor_text(&header.user_id)),
@@ -377,24 +909,46 @@ pub fn encode_keyvault_header_v1(header: &KeyVaultHeaderV1) -> CoreResult<Vec<u8
             ),
         ),
         (6, vault_key_wrap),
-    ]);
-    encode_canonical_value(&value)
+    ];
+    if let Some(kms_key_id) = &header.kms_key_id {
+        entries.push((7, cbor_text(kms_key_id)));
+    }
+    if let Some(device_shares) = &header.device_shares {
+        entries.push((8, device_shares.to_value()));
+    }
+    if let Some(passphrase_check) = &header.passphrase_check {
+        entries.push((9, passphrase_check.to_value()));
+    }
+    encode_canonical_value(&cbor_map(entries))
 }
 
-pub fn decode_keyvault_header_v1(bytes: &[u8]) -> CoreResult<KeyVaultHeaderV1> {
-    let value = decode_canonical_value(bytes, &CborLimits::default())?;
-    let map = as_map(&value)?;
+/// Errors are prefixed with `keyvault_header` and the key path. KDF parameters below
+/// `minimums` fail with [`CoreError::WeakKdf`].
+pub fn decode_keyvault_header_v1(
+    bytes: &[u8],
+    minimums: &KdfMinimums,
+) -> CoreResult<KeyVaultHeaderV1> {
+    let header = decode_canonical_value(bytes, &CborLimits::default())
+        .and_then(|value| decode_keyvault_header_value(&value))
+        .map_err(cbor_context("keyvault_header"))?;
+    minimums.check(&header.kdf)?;
+    Ok(header)
+}
+
+fn decode_keyvault_header_value(value: &Value) -> CoreResult<KeyVaultHeaderV1> {
+    let map = as_map(value)?;
     let v = req_uint(map, 0)?;
     let vault_id = req_text(map, 1)?;
     let user_id = req_text(map, 2)?;
     let kdf_value = map_get(map, 3)?;
-    let kdf = decode_kdf(kdf_value)?;
+    let kdf = decode_kdf(kdf_value).map_err(cbor_context("[3]"))?;
     let aead = AeadId::try_from(req_text(map, 4)?.as_str())
-        .map_err(|e| CoreError::Format(e.to_string()))?;
+        .map_err(|e| CoreError::Format(format!("[4]: {e}")))?;
     let records_value = map_get(map, 5)?;
-    let records = decode_record_containers(records_value)?;
+    let records = decode_record_containers(records_value).map_err(cbor_context("[5]"))?;
     let vault_key_wrap_value = map_get(map, 6)?;
-    let vault_key_wrap = decode_vault_key_wrap(vault_key_wrap_value)?;
+    let vault_key_wrap =
+        decode_vault_key_wrap(vault_key_wrap_value).map_err(cbor_context("[6]"))?;
     Ok(KeyVaultHeaderV1 {
         v,
         vault_id,
@@ -403,6 +957,13 @@ pub fn decode_keyvault_header_v1(bytes: &[u8]) -> CoreResult<KeyVaultHeaderV1> {
         aead,
         records,
         vault_key_wrap,
+        kms_key_id: opt_text(map, 7)?,
+        device_shares: map_get_opt(map, 8)
+            .map(|value| DeviceShareConfigV1::from_cbor(value).map_err(cbor_context("[8]")))
+            .transpose()?,
+        passphrase_check: map_get_opt(map, 9)
+            .map(|value| PassphraseCheckV1::from_cbor(value).map_err(cbor_context("[9]")))
+            .transpose()?,
     })
 }
 
@@ -414,29 +975,60 @@ pub fn encode_keyvault_record_container_v1(
 }
 
 pub fn decode_keyvault_record_container_v1(bytes: &[u8]) -> CoreResult<KeyVaultRecordContainerV1> {
-    let value = decode_canonical_value(bytes, &CborLimits::default())?;
-    decode_record_container(value)
+    decode_canonical_value(bytes, &CborLimits::default())
+        .and_then(decode_record_container)
+        .map_err(cbor_context("keyvault_record"))
 }
 
 pub fn encode_keyvault_record_plain_v1(record: &KeyVaultRecordPlainV1) -> CoreResult<Vec<u8>> {
-    let value = cbor_map(vec![
+    let mut entries = vec![
         (0, cbor_text(&record.record_id)),
         (1, cbor_uint(record.kind)),
         (2, record.payload.clone()),
-    ]);
-    encode_canonical_value(&value)
+    ];
+    if let Some(provenance) = &record.provenance {
+        entries.push((3, cbor_uint(2)));
+        entries.push((4, cbor_uint(provenance.created_at_ms)));
+        if let Some(device_id) = &provenance.origin_device_id {
+            entries.push((5, cbor_text(&device_id.0)));
+        }
+    }
+    let mut value = cbor_map(entries);
+    let encoded = encode_canonical_value(&value);
+    zeroize_value(&mut value);
+    encoded
 }
 
 pub fn decode_keyvault_record_plain_v1(bytes: &[u8]) -> CoreResult<KeyVaultRecordPlainV1> {
-    let value = decode_canonical_value(bytes, &CborLimits::default())?;
-    let map = as_map(&value)?;
+    let mut value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("keyvault_record_plain"))?;
+    let record = decode_keyvault_record_plain_value(&value);
+    zeroize_value(&mut value);
+    record.map_err(cbor_context("keyvault_record_plain"))
+}
+
+fn decode_keyvault_record_plain_value(value: &Value) -> CoreResult<KeyVaultRecordPlainV1> {
+    let map = as_map(value)?;
     let record_id = req_text(map, 0)?;
     let kind = req_uint(map, 1)?;
+    let provenance = match opt_uint(map, 3)? {
+        None => None,
+        Some(2) => Some(KeyVaultRecordProvenance {
+            created_at_ms: req_uint(map, 4)?,
+            origin_device_id: opt_text(map, 5)?.map(DeviceId),
+        }),
+        Some(_) => {
+            return Err(CoreError::Format(
+                "unsupported keyvault record version".to_string(),
+            ))
+        }
+    };
     let payload = map_get(map, 2)?.clone();
     Ok(KeyVaultRecordPlainV1 {
         record_id,
         kind,
         payload,
+        provenance,
     })
 }
 
@@ -450,27 +1042,330 @@ pub fn encode_keyvault_snapshot_v1(snapshot: &KeyVaultSnapshotV1) -> CoreResult<
         .iter()
         .map(encode_record_container_value)
         .collect();
-    let value = cbor_map(vec![(0, header_value), (1, cbor_array(record_values))]);
-    encode_canonical_value(&value)
+    let mut entries = vec![(0, header_value), (1, cbor_array(record_values))];
+    if let Some(manifest) = &snapshot.manifest {
+        entries.push((4, manifest.to_value()));
+    }
+    encode_canonical_value(&cbor_map(entries))
+}
+
+/// Like [`encode_keyvault_snapshot_v1`], with the record array encoded as canonical CBOR and
+/// compressed: `{0: header, 1: compressed records, 2: compression, 3: uncompressed length}`,
+/// plus the manifest under key 4 as in v1.
+pub fn encode_keyvault_snapshot_v2(
+    snapshot: &KeyVaultSnapshotV1,
+    compression: SnapshotCompression,
+) -> CoreResult<Vec<u8>> {
+    let header_value = decode_canonical_value(
+        &encode_keyvault_header_v1(&snapshot.header)?,
+        &CborLimits::default(),
+    )?;
+    let records = encode_canonical_value(&cbor_array(
+        snapshot
+            .records
+            .iter()
+            .map(encode_record_container_value)
+            .collect(),
+    ))?;
+    let mut entries = vec![
+        (0, header_value),
+        (1, cbor_bytes(&compress(compression, &records)?)),
+        (2, cbor_uint(compression.as_u64())),
+        (3, cbor_uint(records.len() as u64)),
+    ];
+    if let Some(manifest) = &snapshot.manifest {
+        entries.push((4, manifest.to_value()));
+    }
+    encode_canonical_value(&cbor_map(entries))
+}
+
+/// Decodes a v1 or v2 snapshot. The decompressed record section is held to `limits` like a
+/// v1 blob would be. Errors are prefixed with `keyvault_snapshot` and the key path.
+pub fn decode_keyvault_snapshot(
+    value: Value,
+    limits: &CborLimits,
+) -> CoreResult<KeyVaultSnapshotV1> {
+    decode_keyvault_snapshot_inner(value, limits).map_err(cbor_context("keyvault_snapshot"))
+}
+
+fn decode_keyvault_snapshot_inner(
+    value: Value,
+    limits: &CborLimits,
+) -> CoreResult<KeyVaultSnapshotV1> {
+    let map = as_map(&value)?;
+    let Some(compression) = opt_uint(map, 2)? else {
+        return KeyVaultSnapshotV1::from_cbor_inner(value);
+    };
+    let compression = SnapshotCompression::from_u64(compression)
+        .ok_or_else(|| CoreError::Format("unknown snapshot compression".to_string()))?;
+    let records_len = req_uint(map, 3)?;
+    if records_len > limits.max_bytes as u64 {
+        return Err(CoreError::Format("snapshot records too large".to_string()));
+    }
+    let records = decompress(compression, &req_bytes(map, 1)?, records_len as usize)?;
+    if records.len() as u64 != records_len {
+        return Err(CoreError::Format(
+            "snapshot records length mismatch".to_string(),
+        ));
+    }
+    Ok(KeyVaultSnapshotV1 {
+        header: decode_keyvault_header_value(map_get(map, 0)?).map_err(cbor_context("[0]"))?,
+        records: decode_canonical_value(&records, limits)
+            .and_then(|records| decode_record_containers(&records))
+            .map_err(cbor_context("[1]"))?,
+        manifest: decode_snapshot_manifest(map)?,
+    })
+}
+
+fn decode_snapshot_manifest(map: &[(Value, Value)]) -> CoreResult<Option<KeyVaultManifestV1>> {
+    map_get_opt(map, 4)
+        .map(|value| KeyVaultManifestV1::from_cbor_inner(value).map_err(cbor_context("[4]")))
+        .transpose()
 }
 
 impl KeyVaultSnapshotV1 {
+    /// Errors are prefixed with `keyvault_snapshot` and the key path.
     pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("keyvault_snapshot"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
         let map = as_map(&value)?;
-        let header_value = map_get(map, 0)?;
-        let header_bytes = encode_canonical_value(header_value)?;
-        let header = decode_keyvault_header_v1(&header_bytes)?;
-        let records_value = map_get(map, 1)?;
-        let records = decode_record_containers(records_value)?;
-        Ok(Self { header, records })
+        let header = decode_keyvault_header_value(map_get(map, 0)?).map_err(cbor_context("[0]"))?;
+        let records = decode_record_containers(map_get(map, 1)?).map_err(cbor_context("[1]"))?;
+        let manifest = decode_snapshot_manifest(map)?;
+        Ok(Self {
+            header,
+            records,
+            manifest,
+        })
+    }
+}
+
+/// Emits the bytes of [`encode_keyvault_snapshot_v1`] piece by piece (map head, header, then one
+/// record container at a time, then the manifest), so exporting never builds the whole blob.
+pub fn write_keyvault_snapshot_v1<E: From<CoreError>>(
+    snapshot: &KeyVaultSnapshotV1,
+    sink: &mut dyn FnMut(&[u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    let entries = if snapshot.manifest.is_some() { 3 } else { 2 };
+    sink(&encode_cbor_head(5, entries))?;
+    sink(&encode_cbor_head(0, 0))?;
+    sink(&encode_keyvault_header_v1(&snapshot.header)?)?;
+    sink(&encode_cbor_head(0, 1))?;
+    sink(&encode_cbor_head(4, snapshot.records.len() as u64))?;
+    for record in &snapshot.records {
+        sink(&encode_keyvault_record_container_v1(record)?)?;
+    }
+    if let Some(manifest) = &snapshot.manifest {
+        sink(&encode_cbor_head(0, 4))?;
+        sink(&encode_canonical_value(&manifest.to_value())?)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SnapshotDecodeStage {
+    MapHead,
+    HeaderKey,
+    Header,
+    RecordsKey,
+    RecordsHead,
+    Records { remaining: u64 },
+    ManifestKey,
+    Manifest,
+    Done,
+}
+
+/// Decodes a v1 snapshot fed in arbitrary pieces. Only the item being read is buffered, and
+/// `limits` apply to the header and to each record container on their own rather than to
+/// the whole blob. Compressed (v2) snapshots are rejected.
+pub struct KeyVaultSnapshotDecoder {
+    limits: CborLimits,
+    buf: Vec<u8>,
+    /// Snapshot bytes consumed so far, i.e. the offset of `buf[0]`.
+    offset: u64,
+    stage: SnapshotDecodeStage,
+    declared_records: Option<u64>,
+    /// Whether the map head announced a manifest after the records.
+    expects_manifest: bool,
+    header: Option<KeyVaultHeaderV1>,
+    records: Vec<KeyVaultRecordContainerV1>,
+    manifest: Option<KeyVaultManifestV1>,
+}
+
+impl fmt::Debug for KeyVaultSnapshotDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyVaultSnapshotDecoder")
+            .field("stage", &self.stage)
+            .field("offset", &self.offset)
+            .field("buffered", &self.buf.len())
+            .field("declared_records", &self.declared_records)
+            .field("records", &self.records.len())
+            .finish()
+    }
+}
+
+impl KeyVaultSnapshotDecoder {
+    pub fn new(limits: CborLimits) -> Self {
+        Self {
+            limits,
+            buf: Vec::new(),
+            offset: 0,
+            stage: SnapshotDecodeStage::MapHead,
+            declared_records: None,
+            expects_manifest: false,
+            header: None,
+            records: Vec::new(),
+            manifest: None,
+        }
+    }
+
+    /// Record count from the snapshot's array head, once it has been read.
+    pub fn declared_records(&self) -> Option<u64> {
+        self.declared_records
+    }
+
+    /// Record containers decoded so far.
+    pub fn records(&self) -> &[KeyVaultRecordContainerV1] {
+        &self.records
+    }
+
+    /// A failed push leaves the decoder unusable. Errors name the item's key path and the
+    /// snapshot byte offset where it starts.
+    pub fn push(&mut self, chunk: &[u8]) -> CoreResult<()> {
+        let mut buf = std::mem::take(&mut self.buf);
+        buf.extend_from_slice(chunk);
+        let mut pos = 0;
+        while let Some(consumed) = self.step(&buf[pos..])? {
+            pos += consumed;
+            self.offset += consumed as u64;
+        }
+        buf.drain(..pos);
+        self.buf = buf;
+        if self.buf.len() > self.limits.max_bytes {
+            return Err(CoreError::Cbor("cbor too large".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> CoreResult<KeyVaultSnapshotV1> {
+        match (self.stage, self.header) {
+            (SnapshotDecodeStage::Done, Some(header)) => Ok(KeyVaultSnapshotV1 {
+                header,
+                records: self.records,
+                manifest: self.manifest,
+            }),
+            _ => Err(CoreError::Format("truncated keyvault snapshot".to_string())),
+        }
+    }
+
+    /// Consumes one head or item from the front of `bytes`; `None` when more input is needed.
+    fn step(&mut self, bytes: &[u8]) -> CoreResult<Option<usize>> {
+        match self.stage {
+            SnapshotDecodeStage::Done => {
+                if bytes.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(CoreError::Cbor(format!(
+                        "trailing bytes after snapshot at byte {}",
+                        self.offset
+                    )))
+                }
+            }
+            SnapshotDecodeStage::Header
+            | SnapshotDecodeStage::Records { .. }
+            | SnapshotDecodeStage::Manifest => {
+                let path = match self.stage {
+                    SnapshotDecodeStage::Records { .. } => format!("[1][{}]", self.records.len()),
+                    SnapshotDecodeStage::Manifest => "[4]".to_string(),
+                    _ => "[0]".to_string(),
+                };
+                let offset = self.offset;
+                let item_error = |err: CoreError| {
+                    let err = cbor_context("keyvault_snapshot")(cbor_context(&path)(err));
+                    match err {
+                        CoreError::Cbor(msg) => {
+                            CoreError::Cbor(format!("{msg} (item at byte {offset})"))
+                        }
+                        CoreError::Format(msg) => {
+                            CoreError::Format(format!("{msg} (item at byte {offset})"))
+                        }
+                        other => other,
+                    }
+                };
+                let Some(len) = cbor_item_len(bytes).map_err(item_error)? else {
+                    return Ok(None);
+                };
+                let value =
+                    decode_canonical_value(&bytes[..len], &self.limits).map_err(item_error)?;
+                self.stage = match self.stage {
+                    SnapshotDecodeStage::Records { remaining } => {
+                        self.records
+                            .push(decode_record_container(value).map_err(item_error)?);
+                        self.records_stage(remaining - 1)
+                    }
+                    SnapshotDecodeStage::Manifest => {
+                        self.manifest =
+                            Some(KeyVaultManifestV1::from_cbor_inner(&value).map_err(item_error)?);
+                        SnapshotDecodeStage::Done
+                    }
+                    _ => {
+                        self.header =
+                            Some(decode_keyvault_header_value(&value).map_err(item_error)?);
+                        SnapshotDecodeStage::RecordsKey
+                    }
+                };
+                Ok(Some(len))
+            }
+            stage => {
+                let Some((major, arg, len)) = cbor_head(bytes)? else {
+                    return Ok(None);
+                };
+                self.stage = match (stage, major, arg) {
+                    (SnapshotDecodeStage::MapHead, 5, 2 | 3) => {
+                        self.expects_manifest = arg == 3;
+                        SnapshotDecodeStage::HeaderKey
+                    }
+                    (SnapshotDecodeStage::MapHead, 5, 4 | 5) => {
+                        return Err(CoreError::Format(
+                            "compressed snapshots cannot be decoded incrementally".to_string(),
+                        ))
+                    }
+                    (SnapshotDecodeStage::HeaderKey, 0, 0) => SnapshotDecodeStage::Header,
+                    (SnapshotDecodeStage::RecordsKey, 0, 1) => SnapshotDecodeStage::RecordsHead,
+                    (SnapshotDecodeStage::RecordsHead, 4, count) => {
+                        self.declared_records = Some(count);
+                        self.records_stage(count)
+                    }
+                    (SnapshotDecodeStage::ManifestKey, 0, 4) => SnapshotDecodeStage::Manifest,
+                    _ => {
+                        return Err(CoreError::Format(format!(
+                            "invalid keyvault snapshot at byte {}",
+                            self.offset
+                        )))
+                    }
+                };
+                Ok(Some(len))
+            }
+        }
+    }
+
+    fn records_stage(&self, remaining: u64) -> SnapshotDecodeStage {
+        match remaining {
+            0 if self.expects_manifest => SnapshotDecodeStage::ManifestKey,
+            0 => SnapshotDecodeStage::Done,
+            remaining => SnapshotDecodeStage::Records { remaining },
+        }
     }
 }
 
 fn decode_record_containers(value: &Value) -> CoreResult<Vec<KeyVaultRecordContainerV1>> {
     let arr = as_array(value)?;
     let mut records = Vec::new();
-    for item in arr {
-        records.push(decode_record_container(item.clone())?);
+    for (i, item) in arr.iter().enumerate() {
+        records
+            .push(decode_record_container(item.clone()).map_err(cbor_context(&format!("[{i}]")))?);
     }
     Ok(records)
 }
@@ -489,9 +1384,9 @@ fn encode_record_container_value(record: &KeyVaultRecordContainerV1) -> Value {
 fn decode_record_container(value: Value) -> CoreResult<KeyVaultRecordContainerV1> {
     let map = as_map(&value)?;
     let prev_hash = req_bytes(map, 2)?;
-    require_len(&prev_hash, 32, "keyvault.prev_hash")?;
+    require_len(&prev_hash, 32, 2, "prev_hash")?;
     let nonce = req_bytes(map, 4)?;
-    require_len(&nonce, 12, "keyvault.nonce")?;
+    require_len(&nonce, 12, 4, "nonce")?;
     Ok(KeyVaultRecordContainerV1 {
         v: req_uint(map, 0)?,
         seq: req_uint(map, 1)?,
@@ -506,11 +1401,8 @@ fn decode_kdf(value: &Value) -> CoreResult<KdfParams> {
     let map = as_map(value)?;
     let id = req_text(map, 0)?;
     let salt = req_bytes(map, 1)?;
-    let params_value = map_get(map, 2)?;
-    let params_map = as_map(params_value)?;
-    let memory_kib = req_uint(params_map, 0)? as u32;
-    let iterations = req_uint(params_map, 1)? as u32;
-    let parallelism = req_uint(params_map, 2)? as u32;
+    let (memory_kib, iterations, parallelism) =
+        decode_kdf_costs(map_get(map, 2)?).map_err(cbor_context("[2]"))?;
     Ok(KdfParams {
         id,
         salt,
@@ -520,45 +1412,1002 @@ fn decode_kdf(value: &Value) -> CoreResult<KdfParams> {
     })
 }
 
+fn decode_kdf_costs(value: &Value) -> CoreResult<(u32, u32, u32)> {
+    let map = as_map(value)?;
+    Ok((
+        req_uint(map, 0)? as u32,
+        req_uint(map, 1)? as u32,
+        req_uint(map, 2)? as u32,
+    ))
+}
+
 fn decode_vault_key_wrap(value: &Value) -> CoreResult<VaultKeyWrapV1> {
     let map = as_map(value)?;
     let aead = AeadId::try_from(req_text(map, 0)?.as_str())
         .map_err(|e| CoreError::Format(e.to_string()))?;
     let nonce = req_bytes(map, 1)?;
-    require_len(&nonce, 12, "vault_key_wrap.nonce")?;
     let ct = req_bytes(map, 2)?;
-    Ok(VaultKeyWrapV1 { aead, nonce, ct })
+    let commitment = opt_bytes(map, 3)?;
+    let alg = match opt_text(map, 4)? {
+        Some(alg) => KeyWrapAlg::try_from(alg.as_str()).map_err(CoreError::Format)?,
+        None => KeyWrapAlg::AesGcm,
+    };
+    check_key_wrap_fields(alg, &nonce, &ct, commitment.as_deref())?;
+    Ok(VaultKeyWrapV1 {
+        aead,
+        nonce,
+        ct,
+        commitment,
+        alg,
+    })
 }
 
-fn require_len(bytes: &[u8], expected: usize, name: &str) -> CoreResult<()> {
-    if bytes.len() != expected {
-        return Err(CoreError::Format(format!("invalid {name} length")));
+/// Field shapes each wrap algorithm allows; shared with the PRF wrap, which uses the same
+/// keys (1 nonce, 2 ct, 3 commitment).
+pub fn check_key_wrap_fields(
+    alg: KeyWrapAlg,
+    nonce: &[u8],
+    ct: &[u8],
+    commitment: Option<&[u8]>,
+) -> CoreResult<()> {
+    match alg {
+        KeyWrapAlg::AesGcm => {
+            require_len(nonce, 12, 1, "nonce")?;
+            if let Some(commitment) = commitment {
+                require_len(commitment, 32, 3, "commitment")?;
+            }
+        }
+        KeyWrapAlg::AesKwp => {
+            if !nonce.is_empty() || commitment.is_some() {
+                return Err(CoreError::Format(
+                    "unexpected nonce or commitment for aes-kwp".to_string(),
+                ));
+            }
+            if ct.len() < 16 || !ct.len().is_multiple_of(8) {
+                return Err(CoreError::Format(format!(
+                    "[2]: invalid aes-kwp ct length {}",
+                    ct.len()
+                )));
+            }
+        }
     }
     Ok(())
 }
 
-fn map_get(map: &[(Value, Value)], key: u64) -> CoreResult<&Value> {
-    map.iter()
-        .find_map(|(k, v)| match k {
-            Value::Integer(int) => {
-                let int_value: Result<u64, _> = (*int).try_into();
-                if int_value.ok()? == key {
-                    Some(v)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        })
-        .ok_or_else(|| CoreError::Format(format!("missing key {key}")))
+/// Attenuated hand-off of an open resource key to another session of the same vault.
+///
+/// `wrapped_key` is sealed under the vault key; its AAD
+/// ([`crate::aad::aad_capability_token_wrap_v1`]) binds every other field.
+#[derive(Clone, Debug)]
+pub struct CapabilityTokenV1 {
+    pub v: u64,
+    pub vault_id: String,
+    pub resource_id: ResourceId,
+    pub resource_key_id: ResourceKeyId,
+    pub aad_prefix: Vec<u8>,
+    pub ops: CapabilityOps,
+    pub issued_at_ms: u64,
+    pub expires_at_ms: u64,
+    pub aead: AeadId,
+    pub nonce: Vec<u8>,
+    pub wrapped_key: Vec<u8>,
 }
 
-fn map_get_opt(map: &[(Value, Value)], key: u64) -> Option<&Value> {
-    map.iter().find_map(|(k, v)| match k {
-        Value::Integer(int) => {
-            let int_value: Result<u64, _> = (*int).try_into();
-            if int_value.ok()? == key {
-                Some(v)
+impl CapabilityTokenV1 {
+    /// Errors are prefixed with `capability_token` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("capability_token"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let vault_id = req_text(map, 1)?;
+        let resource_id = ResourceId(req_text(map, 2)?);
+        let resource_key_id = ResourceKeyId(req_text(map, 3)?);
+        let aad_prefix = req_bytes(map, 4)?;
+        let ops = CapabilityOps::from_bits(req_uint(map, 5)?)
+            .ok_or_else(|| CoreError::Format("unknown capability ops".to_string()))?;
+        let issued_at_ms = req_uint(map, 6)?;
+        let expires_at_ms = req_uint(map, 7)?;
+        let aead = AeadId::try_from(req_text(map, 8)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let nonce = req_bytes(map, 9)?;
+        require_len(&nonce, 12, 9, "nonce")?;
+        let wrapped_key = req_bytes(map, 10)?;
+        Ok(Self {
+            v,
+            vault_id,
+            resource_id,
+            resource_key_id,
+            aad_prefix,
+            ops,
+            issued_at_ms,
+            expires_at_ms,
+            aead,
+            nonce,
+            wrapped_key,
+        })
+    }
+}
+
+pub fn encode_capability_token_v1(token: &CapabilityTokenV1) -> CoreResult<Vec<u8>> {
+    let value = cbor_map(vec![
+        (0, cbor_uint(token.v)),
+        (1, cbor_text(&token.vault_id)),
+        (2, cbor_text(&token.resource_id.0)),
+        (3, cbor_text(&token.resource_key_id.0)),
+        (4, cbor_bytes(&token.aad_prefix)),
+        (5, cbor_uint(token.ops.bits())),
+        (6, cbor_uint(token.issued_at_ms)),
+        (7, cbor_uint(token.expires_at_ms)),
+        (8, cbor_text(token.aead.as_str())),
+        (9, cbor_bytes(&token.nonce)),
+        (10, cbor_bytes(&token.wrapped_key)),
+    ]);
+    encode_canonical_value(&value)
+}
+
+pub fn decode_capability_token_v1(bytes: &[u8]) -> CoreResult<CapabilityTokenV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("capability_token"))?;
+    CapabilityTokenV1::from_cbor(value)
+}
+
+/// A trusted signer device's authorization for a secondary signing key in one scope.
+///
+/// Signed by `delegator_device_id`; the delegate may sign grants and data until
+/// `expires_at_ms` but never scope states.
+#[derive(Clone, Debug)]
+pub struct SigningDelegationV1 {
+    pub v: u64,
+    pub scope_id: ScopeId,
+    pub delegator_device_id: DeviceId,
+    pub delegate_device_id: DeviceId,
+    pub delegate_ed25519_pub: Vec<u8>,
+    pub delegate_mldsa_pub: Vec<u8>,
+    pub issued_at_ms: u64,
+    pub expires_at_ms: u64,
+    pub sig_suite: SigCiphersuiteId,
+    pub signature: Vec<u8>,
+}
+
+impl SigningDelegationV1 {
+    /// Errors are prefixed with `signing_delegation` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("signing_delegation"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let scope_id = ScopeId(req_text(map, 1)?);
+        let delegator_device_id = DeviceId(req_text(map, 2)?);
+        let delegate_device_id = DeviceId(req_text(map, 3)?);
+        let delegate_ed25519_pub = req_bytes(map, 4)?;
+        let delegate_mldsa_pub = req_bytes(map, 5)?;
+        let issued_at_ms = req_uint(map, 6)?;
+        let expires_at_ms = req_uint(map, 7)?;
+        let sig_suite = SigCiphersuiteId::try_from(req_text(map, 8)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let signature = req_bytes(map, 9)?;
+        Ok(Self {
+            v,
+            scope_id,
+            delegator_device_id,
+            delegate_device_id,
+            delegate_ed25519_pub,
+            delegate_mldsa_pub,
+            issued_at_ms,
+            expires_at_ms,
+            sig_suite,
+            signature,
+        })
+    }
+
+    fn signed_entries(&self) -> Vec<(u64, Value)> {
+        vec![
+            (0, cbor_uint(self.v)),
+            (1, cbor_text(&self.scope_id.0)),
+            (2, cbor_text(&self.delegator_device_id.0)),
+            (3, cbor_text(&self.delegate_device_id.0)),
+            (4, cbor_bytes(&self.delegate_ed25519_pub)),
+            (5, cbor_bytes(&self.delegate_mldsa_pub)),
+            (6, cbor_uint(self.issued_at_ms)),
+            (7, cbor_uint(self.expires_at_ms)),
+            (8, cbor_text(self.sig_suite.as_str())),
+        ]
+    }
+
+    pub fn to_be_signed_bytes(&self) -> CoreResult<Vec<u8>> {
+        encode_canonical_value(&cbor_map(self.signed_entries()))
+    }
+
+    pub fn delegate_keys(&self) -> SignerKeys {
+        SignerKeys {
+            sig_suite: self.sig_suite,
+            ed25519_pub: self.delegate_ed25519_pub.clone(),
+            mldsa_pub: self.delegate_mldsa_pub.clone(),
+        }
+    }
+}
+
+pub fn encode_signing_delegation_v1(delegation: &SigningDelegationV1) -> CoreResult<Vec<u8>> {
+    let mut entries = delegation.signed_entries();
+    entries.push((9, cbor_bytes(&delegation.signature)));
+    encode_canonical_value(&cbor_map(entries))
+}
+
+pub fn decode_signing_delegation_v1(bytes: &[u8]) -> CoreResult<SigningDelegationV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("signing_delegation"))?;
+    SigningDelegationV1::from_cbor(value)
+}
+
+/// A member a CGKA commit adds: a user and the hybrid KEM public key its leaf holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CgkaMemberV1 {
+    pub user_id: UserId,
+    pub public_bytes: Vec<u8>,
+}
+
+/// A path secret encrypted to one node; `enc` is the hybrid KEM encapsulation.
+#[derive(Clone, Debug)]
+pub struct CgkaCiphertextV1 {
+    pub enc: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ct: Vec<u8>,
+}
+
+/// One node of the committer's direct path, leaf side first: its new public key and its path
+/// secret encrypted to each node in the resolution of the copath child, in resolution order.
+#[derive(Clone, Debug)]
+pub struct CgkaPathNodeV1 {
+    pub public_bytes: Vec<u8>,
+    pub ciphertexts: Vec<CgkaCiphertextV1>,
+}
+
+/// Moves a CGKA group to `scope_epoch`: removes leaves, adds members, and replaces the
+/// committer's direct path. Signed by `signer_device_id`; see [`crate::cgka`].
+#[derive(Clone, Debug)]
+pub struct CgkaCommitV1 {
+    pub v: u64,
+    pub scope_id: ScopeId,
+    pub scope_epoch: ScopeEpoch,
+    pub scope_state_ref: Vec<u8>,
+    pub committer_leaf: u64,
+    pub removes: Vec<u64>,
+    pub adds: Vec<CgkaMemberV1>,
+    pub kem: KemCiphersuiteId,
+    pub aead: AeadId,
+    pub path: Vec<CgkaPathNodeV1>,
+    pub confirmation_tag: Vec<u8>,
+    pub signer_device_id: DeviceId,
+    pub sig_suite: SigCiphersuiteId,
+    pub signature: Vec<u8>,
+}
+
+impl CgkaCommitV1 {
+    /// Errors are prefixed with `cgka_commit` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("cgka_commit"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let scope_id = ScopeId(req_text(map, 1)?);
+        let scope_epoch = ScopeEpoch(req_uint(map, 2)?);
+        let scope_state_ref = req_bytes(map, 3)?;
+        require_len(&scope_state_ref, 32, 3, "scope_state_ref")?;
+        let committer_leaf = req_uint(map, 4)?;
+        let removes = req_array(map, 5)?
+            .iter()
+            .map(|item| match item {
+                Value::Integer(int) => u64::try_from(*int)
+                    .map_err(|_| CoreError::Format("[5]: expected leaf indices".to_string())),
+                _ => Err(CoreError::Format("[5]: expected leaf indices".to_string())),
+            })
+            .collect::<CoreResult<Vec<_>>>()?;
+        let adds = req_array(map, 6)?
+            .iter()
+            .map(decode_cgka_member)
+            .collect::<CoreResult<Vec<_>>>()?;
+        let kem = KemCiphersuiteId::try_from(req_text(map, 7)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let aead = AeadId::try_from(req_text(map, 8)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let path = req_array(map, 9)?
+            .iter()
+            .map(decode_cgka_path_node)
+            .collect::<CoreResult<Vec<_>>>()?;
+        let confirmation_tag = req_bytes(map, 10)?;
+        require_len(&confirmation_tag, 32, 10, "confirmation_tag")?;
+        let signer_device_id = DeviceId(req_text(map, 11)?);
+        let sig_suite = SigCiphersuiteId::try_from(req_text(map, 12)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let signature = req_bytes(map, 13)?;
+        Ok(Self {
+            v,
+            scope_id,
+            scope_epoch,
+            scope_state_ref,
+            committer_leaf,
+            removes,
+            adds,
+            kem,
+            aead,
+            path,
+            confirmation_tag,
+            signer_device_id,
+            sig_suite,
+            signature,
+        })
+    }
+
+    fn signed_entries(&self) -> Vec<(u64, Value)> {
+        vec![
+            (0, cbor_uint(self.v)),
+            (1, cbor_text(&self.scope_id.0)),
+            (2, cbor_uint(self.scope_epoch.0)),
+            (3, cbor_bytes(&self.scope_state_ref)),
+            (4, cbor_uint(self.committer_leaf)),
+            (
+                5,
+                cbor_array(self.removes.iter().map(|leaf| cbor_uint(*leaf)).collect()),
+            ),
+            (
+                6,
+                cbor_array(self.adds.iter().map(encode_cgka_member).collect()),
+            ),
+            (7, cbor_text(self.kem.as_str())),
+            (8, cbor_text(self.aead.as_str())),
+            (
+                9,
+                cbor_array(self.path.iter().map(encode_cgka_path_node).collect()),
+            ),
+            (10, cbor_bytes(&self.confirmation_tag)),
+            (11, cbor_text(&self.signer_device_id.0)),
+            (12, cbor_text(self.sig_suite.as_str())),
+        ]
+    }
+
+    pub fn to_be_signed_bytes(&self) -> CoreResult<Vec<u8>> {
+        encode_canonical_value(&cbor_map(self.signed_entries()))
+    }
+}
+
+/// The joiner and path secrets of one new member, encrypted to its leaf key. The plaintext
+/// is `{0: joiner_secret, 1: path_secret, 2: path_node}`; see [`crate::cgka`].
+#[derive(Clone, Debug)]
+pub struct CgkaWelcomeSecretV1 {
+    pub user_id: UserId,
+    pub enc: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ct: Vec<u8>,
+}
+
+/// Lets the members a [`CgkaCommitV1`] adds join its epoch: the new public tree plus one
+/// encrypted secret per new member. Signed by the committer.
+#[derive(Clone, Debug)]
+pub struct CgkaWelcomeV1 {
+    pub v: u64,
+    pub scope_id: ScopeId,
+    pub scope_epoch: ScopeEpoch,
+    pub scope_state_ref: Vec<u8>,
+    pub committer_leaf: u64,
+    /// Canonical CBOR of the public tree after the commit.
+    pub tree: Vec<u8>,
+    pub kem: KemCiphersuiteId,
+    pub aead: AeadId,
+    pub secrets: Vec<CgkaWelcomeSecretV1>,
+    pub signer_device_id: DeviceId,
+    pub sig_suite: SigCiphersuiteId,
+    pub signature: Vec<u8>,
+}
+
+impl CgkaWelcomeV1 {
+    /// Errors are prefixed with `cgka_welcome` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("cgka_welcome"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let scope_id = ScopeId(req_text(map, 1)?);
+        let scope_epoch = ScopeEpoch(req_uint(map, 2)?);
+        let scope_state_ref = req_bytes(map, 3)?;
+        require_len(&scope_state_ref, 32, 3, "scope_state_ref")?;
+        let committer_leaf = req_uint(map, 4)?;
+        let tree = req_bytes(map, 5)?;
+        let kem = KemCiphersuiteId::try_from(req_text(map, 6)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let aead = AeadId::try_from(req_text(map, 7)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let secrets = req_array(map, 8)?
+            .iter()
+            .map(|item| {
+                let entry = as_map(item)?;
+                let nonce = req_bytes(entry, 2)?;
+                require_len(&nonce, 12, 2, "nonce")?;
+                Ok(CgkaWelcomeSecretV1 {
+                    user_id: UserId(req_text(entry, 0)?),
+                    enc: req_bytes(entry, 1)?,
+                    nonce,
+                    ct: req_bytes(entry, 3)?,
+                })
+            })
+            .collect::<CoreResult<Vec<_>>>()?;
+        let signer_device_id = DeviceId(req_text(map, 9)?);
+        let sig_suite = SigCiphersuiteId::try_from(req_text(map, 10)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let signature = req_bytes(map, 11)?;
+        Ok(Self {
+            v,
+            scope_id,
+            scope_epoch,
+            scope_state_ref,
+            committer_leaf,
+            tree,
+            kem,
+            aead,
+            secrets,
+            signer_device_id,
+            sig_suite,
+            signature,
+        })
+    }
+
+    fn signed_entries(&self) -> Vec<(u64, Value)> {
+        let secrets = self
+            .secrets
+            .iter()
+            .map(|secret| {
+                cbor_map(vec![
+                    (0, cbor_text(&secret.user_id.0)),
+                    (1, cbor_bytes(&secret.enc)),
+                    (2, cbor_bytes(&secret.nonce)),
+                    (3, cbor_bytes(&secret.ct)),
+                ])
+            })
+            .collect();
+        vec![
+            (0, cbor_uint(self.v)),
+            (1, cbor_text(&self.scope_id.0)),
+            (2, cbor_uint(self.scope_epoch.0)),
+            (3, cbor_bytes(&self.scope_state_ref)),
+            (4, cbor_uint(self.committer_leaf)),
+            (5, cbor_bytes(&self.tree)),
+            (6, cbor_text(self.kem.as_str())),
+            (7, cbor_text(self.aead.as_str())),
+            (8, cbor_array(secrets)),
+            (9, cbor_text(&self.signer_device_id.0)),
+            (10, cbor_text(self.sig_suite.as_str())),
+        ]
+    }
+
+    pub fn to_be_signed_bytes(&self) -> CoreResult<Vec<u8>> {
+        encode_canonical_value(&cbor_map(self.signed_entries()))
+    }
+}
+
+fn decode_cgka_member(value: &Value) -> CoreResult<CgkaMemberV1> {
+    let map = as_map(value)?;
+    Ok(CgkaMemberV1 {
+        user_id: UserId(req_text(map, 0)?),
+        public_bytes: req_bytes(map, 1)?,
+    })
+}
+
+fn encode_cgka_member(member: &CgkaMemberV1) -> Value {
+    cbor_map(vec![
+        (0, cbor_text(&member.user_id.0)),
+        (1, cbor_bytes(&member.public_bytes)),
+    ])
+}
+
+fn decode_cgka_path_node(value: &Value) -> CoreResult<CgkaPathNodeV1> {
+    let map = as_map(value)?;
+    let ciphertexts = req_array(map, 1)?
+        .iter()
+        .map(|item| {
+            let entry = as_map(item)?;
+            let nonce = req_bytes(entry, 1)?;
+            require_len(&nonce, 12, 1, "nonce")?;
+            Ok(CgkaCiphertextV1 {
+                enc: req_bytes(entry, 0)?,
+                nonce,
+                ct: req_bytes(entry, 2)?,
+            })
+        })
+        .collect::<CoreResult<Vec<_>>>()?;
+    Ok(CgkaPathNodeV1 {
+        public_bytes: req_bytes(map, 0)?,
+        ciphertexts,
+    })
+}
+
+fn encode_cgka_path_node(node: &CgkaPathNodeV1) -> Value {
+    let ciphertexts = node
+        .ciphertexts
+        .iter()
+        .map(|ciphertext| {
+            cbor_map(vec![
+                (0, cbor_bytes(&ciphertext.enc)),
+                (1, cbor_bytes(&ciphertext.nonce)),
+                (2, cbor_bytes(&ciphertext.ct)),
+            ])
+        })
+        .collect();
+    cbor_map(vec![
+        (0, cbor_bytes(&node.public_bytes)),
+        (1, cbor_array(ciphertexts)),
+    ])
+}
+
+pub fn encode_cgka_commit_v1(commit: &CgkaCommitV1) -> CoreResult<Vec<u8>> {
+    let mut entries = commit.signed_entries();
+    entries.push((13, cbor_bytes(&commit.signature)));
+    encode_canonical_value(&cbor_map(entries))
+}
+
+pub fn decode_cgka_commit_v1(bytes: &[u8]) -> CoreResult<CgkaCommitV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("cgka_commit"))?;
+    CgkaCommitV1::from_cbor(value)
+}
+
+pub fn encode_cgka_welcome_v1(welcome: &CgkaWelcomeV1) -> CoreResult<Vec<u8>> {
+    let mut entries = welcome.signed_entries();
+    entries.push((11, cbor_bytes(&welcome.signature)));
+    encode_canonical_value(&cbor_map(entries))
+}
+
+pub fn decode_cgka_welcome_v1(bytes: &[u8]) -> CoreResult<CgkaWelcomeV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("cgka_welcome"))?;
+    CgkaWelcomeV1::from_cbor(value)
+}
+
+/// Most prekeys `KeyService::publish_prekeys` puts in one bundle.
+pub const MAX_PREKEYS_PER_BUNDLE: u64 = 100;
+
+/// A one-time prekey: an id and the hybrid KEM public key derived for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrekeyV1 {
+    pub prekey_id: u64,
+    pub public_bytes: Vec<u8>,
+}
+
+/// User to server: one-time prekeys a sender can seal a key envelope to while the user is
+/// offline. Signed by one of the user's devices, whose keys travel with the bundle so a
+/// sender can pin them by fingerprint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrekeyBundleV1 {
+    pub v: u64,
+    pub user_id: UserId,
+    /// SHA-256 of the user public key the prekeys are derived from.
+    pub uk_pub_fingerprint: Vec<u8>,
+    pub prekeys: Vec<PrekeyV1>,
+    pub signer_device_id: DeviceId,
+    pub signer: SignerKeys,
+    pub signature: Vec<u8>,
+}
+
+impl PrekeyBundleV1 {
+    /// Errors are prefixed with `prekey_bundle` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("prekey_bundle"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let user_id = UserId(req_text(map, 1)?);
+        let uk_pub_fingerprint = req_bytes(map, 2)?;
+        require_len(&uk_pub_fingerprint, 32, 2, "uk_pub_fingerprint")?;
+        let prekeys = req_array(map, 3)?
+            .iter()
+            .map(|item| {
+                let entry = as_map(item)?;
+                Ok(PrekeyV1 {
+                    prekey_id: req_uint(entry, 0)?,
+                    public_bytes: req_bytes(entry, 1)?,
+                })
+            })
+            .collect::<CoreResult<Vec<_>>>()?;
+        let signer_device_id = DeviceId(req_text(map, 4)?);
+        let ed25519_pub = req_bytes(map, 5)?;
+        require_len(&ed25519_pub, ED25519_PUBLIC_KEY_LEN, 5, "ed25519_pub")?;
+        let mldsa_pub = req_bytes(map, 6)?;
+        require_len(&mldsa_pub, MLDSA65_PUBLIC_KEY_LEN, 6, "mldsa_pub")?;
+        let sig_suite = SigCiphersuiteId::try_from(req_text(map, 7)?.as_str())
+            .map_err(|e| CoreError::Format(e.to_string()))?;
+        let signature = req_bytes(map, 8)?;
+        Ok(Self {
+            v,
+            user_id,
+            uk_pub_fingerprint,
+            prekeys,
+            signer_device_id,
+            signer: SignerKeys {
+                sig_suite,
+                ed25519_pub,
+                mldsa_pub,
+            },
+            signature,
+        })
+    }
+
+    fn signed_entries(&self) -> Vec<(u64, Value)> {
+        let prekeys = self
+            .prekeys
+            .iter()
+            .map(|prekey| {
+                cbor_map(vec![
+                    (0, cbor_uint(prekey.prekey_id)),
+                    (1, cbor_bytes(&prekey.public_bytes)),
+                ])
+            })
+            .collect();
+        vec![
+            (0, cbor_uint(self.v)),
+            (1, cbor_text(&self.user_id.0)),
+            (2, cbor_bytes(&self.uk_pub_fingerprint)),
+            (3, cbor_array(prekeys)),
+            (4, cbor_text(&self.signer_device_id.0)),
+            (5, cbor_bytes(&self.signer.ed25519_pub)),
+            (6, cbor_bytes(&self.signer.mldsa_pub)),
+            (7, cbor_text(self.signer.sig_suite.as_str())),
+        ]
+    }
+
+    pub fn to_be_signed_bytes(&self) -> CoreResult<Vec<u8>> {
+        encode_canonical_value(&cbor_map(self.signed_entries()))
+    }
+}
+
+pub fn encode_prekey_bundle_v1(bundle: &PrekeyBundleV1) -> CoreResult<Vec<u8>> {
+    let mut entries = bundle.signed_entries();
+    entries.push((8, cbor_bytes(&bundle.signature)));
+    encode_canonical_value(&cbor_map(entries))
+}
+
+pub fn decode_prekey_bundle_v1(bytes: &[u8]) -> CoreResult<PrekeyBundleV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("prekey_bundle"))?;
+    PrekeyBundleV1::from_cbor(value)
+}
+
+/// Locked device to its peers, over the host's pairing channel: send me your share of split
+/// `split_id`, sealed to this one-time hybrid KEM key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceShareRequestV1 {
+    pub v: u64,
+    pub vault_id: String,
+    pub split_id: Vec<u8>,
+    /// Random; binds each response to this request.
+    pub request_id: Vec<u8>,
+    /// `hybrid-kem-1` public key the shares are sealed to.
+    pub kem_public: Vec<u8>,
+}
+
+pub fn encode_device_share_request_v1(request: &DeviceShareRequestV1) -> CoreResult<Vec<u8>> {
+    encode_canonical_value(&cbor_map(vec![
+        (0, cbor_uint(request.v)),
+        (1, cbor_text(&request.vault_id)),
+        (2, cbor_bytes(&request.split_id)),
+        (3, cbor_bytes(&request.request_id)),
+        (4, cbor_bytes(&request.kem_public)),
+    ]))
+}
+
+/// Errors are prefixed with `device_share_request` and the key path.
+pub fn decode_device_share_request_v1(bytes: &[u8]) -> CoreResult<DeviceShareRequestV1> {
+    let decode = || {
+        let value = decode_canonical_value(bytes, &CborLimits::default())?;
+        let map = as_map(&value)?;
+        let split_id = req_bytes(map, 2)?;
+        require_len(&split_id, DEVICE_SHARE_SPLIT_ID_LEN, 2, "split_id")?;
+        let request_id = req_bytes(map, 3)?;
+        require_len(&request_id, 16, 3, "request_id")?;
+        Ok(DeviceShareRequestV1 {
+            v: req_uint(map, 0)?,
+            vault_id: req_text(map, 1)?,
+            split_id,
+            request_id,
+            kem_public: req_bytes(map, 4)?,
+        })
+    };
+    decode().map_err(cbor_context("device_share_request"))
+}
+
+/// Peer to locked device: this device's share, AES-GCM under the KEM wrap key from `enc`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceShareResponseV1 {
+    pub v: u64,
+    pub request_id: Vec<u8>,
+    pub device_id: DeviceId,
+    pub share_index: u64,
+    pub enc: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ct: Vec<u8>,
+}
+
+pub fn encode_device_share_response_v1(response: &DeviceShareResponseV1) -> CoreResult<Vec<u8>> {
+    encode_canonical_value(&cbor_map(vec![
+        (0, cbor_uint(response.v)),
+        (1, cbor_bytes(&response.request_id)),
+        (2, cbor_text(&response.device_id.0)),
+        (3, cbor_uint(response.share_index)),
+        (4, cbor_bytes(&response.enc)),
+        (5, cbor_bytes(&response.nonce)),
+        (6, cbor_bytes(&response.ct)),
+    ]))
+}
+
+/// Errors are prefixed with `device_share_response` and the key path.
+pub fn decode_device_share_response_v1(bytes: &[u8]) -> CoreResult<DeviceShareResponseV1> {
+    let decode = || {
+        let value = decode_canonical_value(bytes, &CborLimits::default())?;
+        let map = as_map(&value)?;
+        let nonce = req_bytes(map, 5)?;
+        require_len(&nonce, 12, 5, "nonce")?;
+        Ok(DeviceShareResponseV1 {
+            v: req_uint(map, 0)?,
+            request_id: req_bytes(map, 1)?,
+            device_id: DeviceId(req_text(map, 2)?),
+            share_index: req_uint(map, 3)?,
+            enc: req_bytes(map, 4)?,
+            nonce,
+            ct: req_bytes(map, 6)?,
+        })
+    };
+    decode().map_err(cbor_context("device_share_response"))
+}
+
+/// Device to server: enroll this device's share of a co-signed Ed25519 key.
+///
+/// `proof` is a Schnorr proof of possession of `share_pub` bound to `device_id`; see
+/// [`crate::cosign`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CosignEnrollRequestV1 {
+    pub v: u64,
+    pub device_id: DeviceId,
+    pub share_pub: Vec<u8>,
+    pub proof: Vec<u8>,
+}
+
+impl CosignEnrollRequestV1 {
+    /// Errors are prefixed with `cosign_enroll_request` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("cosign_enroll_request"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let device_id = DeviceId(req_text(map, 1)?);
+        let share_pub = req_bytes(map, 2)?;
+        require_len(&share_pub, COSIGN_ELEMENT_LEN, 2, "share_pub")?;
+        let proof = req_bytes(map, 3)?;
+        require_len(&proof, COSIGN_PROOF_LEN, 3, "proof")?;
+        Ok(Self {
+            v,
+            device_id,
+            share_pub,
+            proof,
+        })
+    }
+}
+
+pub fn encode_cosign_enroll_request_v1(request: &CosignEnrollRequestV1) -> CoreResult<Vec<u8>> {
+    encode_canonical_value(&cbor_map(vec![
+        (0, cbor_uint(request.v)),
+        (1, cbor_text(&request.device_id.0)),
+        (2, cbor_bytes(&request.share_pub)),
+        (3, cbor_bytes(&request.proof)),
+    ]))
+}
+
+pub fn decode_cosign_enroll_request_v1(bytes: &[u8]) -> CoreResult<CosignEnrollRequestV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("cosign_enroll_request"))?;
+    CosignEnrollRequestV1::from_cbor(value)
+}
+
+/// Server to device: the server's share public key, with its proof bound to the same
+/// `device_id`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CosignEnrollResponseV1 {
+    pub v: u64,
+    pub share_pub: Vec<u8>,
+    pub proof: Vec<u8>,
+}
+
+impl CosignEnrollResponseV1 {
+    /// Errors are prefixed with `cosign_enroll_response` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("cosign_enroll_response"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let share_pub = req_bytes(map, 1)?;
+        require_len(&share_pub, COSIGN_ELEMENT_LEN, 1, "share_pub")?;
+        let proof = req_bytes(map, 2)?;
+        require_len(&proof, COSIGN_PROOF_LEN, 2, "proof")?;
+        Ok(Self {
+            v,
+            share_pub,
+            proof,
+        })
+    }
+}
+
+pub fn encode_cosign_enroll_response_v1(response: &CosignEnrollResponseV1) -> CoreResult<Vec<u8>> {
+    encode_canonical_value(&cbor_map(vec![
+        (0, cbor_uint(response.v)),
+        (1, cbor_bytes(&response.share_pub)),
+        (2, cbor_bytes(&response.proof)),
+    ]))
+}
+
+pub fn decode_cosign_enroll_response_v1(bytes: &[u8]) -> CoreResult<CosignEnrollResponseV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("cosign_enroll_response"))?;
+    CosignEnrollResponseV1::from_cbor(value)
+}
+
+/// Device to server: one co-signing attempt.
+///
+/// `message` is the exact Ed25519 input, i.e. the [`crate::domains::SigContext`]-framed bytes,
+/// so the server can check the context tag before contributing its share.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CosignSignRequestV1 {
+    pub v: u64,
+    pub device_id: DeviceId,
+    pub group_pub: Vec<u8>,
+    pub message: Vec<u8>,
+    pub commitment: CosignCommitment,
+}
+
+impl CosignSignRequestV1 {
+    /// Errors are prefixed with `cosign_sign_request` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("cosign_sign_request"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let device_id = DeviceId(req_text(map, 1)?);
+        let group_pub = req_bytes(map, 2)?;
+        require_len(&group_pub, ED25519_PUBLIC_KEY_LEN, 2, "group_pub")?;
+        let message = req_bytes(map, 3)?;
+        let commitment = decode_cosign_commitment(map, 4, 5)?;
+        Ok(Self {
+            v,
+            device_id,
+            group_pub,
+            message,
+            commitment,
+        })
+    }
+}
+
+pub fn encode_cosign_sign_request_v1(request: &CosignSignRequestV1) -> CoreResult<Vec<u8>> {
+    encode_canonical_value(&cbor_map(vec![
+        (0, cbor_uint(request.v)),
+        (1, cbor_text(&request.device_id.0)),
+        (2, cbor_bytes(&request.group_pub)),
+        (3, cbor_bytes(&request.message)),
+        (4, cbor_bytes(&request.commitment.hiding)),
+        (5, cbor_bytes(&request.commitment.binding)),
+    ]))
+}
+
+pub fn decode_cosign_sign_request_v1(bytes: &[u8]) -> CoreResult<CosignSignRequestV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("cosign_sign_request"))?;
+    CosignSignRequestV1::from_cbor(value)
+}
+
+/// Server to device: the server's nonce commitment and its signature share `z_server`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CosignSignResponseV1 {
+    pub v: u64,
+    pub commitment: CosignCommitment,
+    pub share: Vec<u8>,
+}
+
+impl CosignSignResponseV1 {
+    /// Errors are prefixed with `cosign_sign_response` and the key path.
+    pub fn from_cbor(value: Value) -> CoreResult<Self> {
+        Self::from_cbor_inner(value).map_err(cbor_context("cosign_sign_response"))
+    }
+
+    fn from_cbor_inner(value: Value) -> CoreResult<Self> {
+        let map = as_map(&value)?;
+        let v = req_uint(map, 0)?;
+        let commitment = decode_cosign_commitment(map, 1, 2)?;
+        let share = req_bytes(map, 3)?;
+        require_len(&share, COSIGN_ELEMENT_LEN, 3, "share")?;
+        Ok(Self {
+            v,
+            commitment,
+            share,
+        })
+    }
+}
+
+pub fn encode_cosign_sign_response_v1(response: &CosignSignResponseV1) -> CoreResult<Vec<u8>> {
+    encode_canonical_value(&cbor_map(vec![
+        (0, cbor_uint(response.v)),
+        (1, cbor_bytes(&response.commitment.hiding)),
+        (2, cbor_bytes(&response.commitment.binding)),
+        (3, cbor_bytes(&response.share)),
+    ]))
+}
+
+pub fn decode_cosign_sign_response_v1(bytes: &[u8]) -> CoreResult<CosignSignResponseV1> {
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("cosign_sign_response"))?;
+    CosignSignResponseV1::from_cbor(value)
+}
+
+fn decode_cosign_commitment(
+    map: &[(Value, Value)],
+    hiding_key: u64,
+    binding_key: u64,
+) -> CoreResult<CosignCommitment> {
+    let element = |key: u64, name: &str| -> CoreResult<[u8; 32]> {
+        let bytes = req_bytes(map, key)?;
+        require_len(&bytes, COSIGN_ELEMENT_LEN, key, name)?;
+        let mut out = [0u8; COSIGN_ELEMENT_LEN];
+        out.copy_from_slice(&bytes);
+        Ok(out)
+    };
+    Ok(CosignCommitment {
+        hiding: element(hiding_key, "hiding")?,
+        binding: element(binding_key, "binding")?,
+    })
+}
+
+fn require_len(bytes: &[u8], expected: usize, key: u64, name: &str) -> CoreResult<()> {
+    if bytes.len() != expected {
+        return Err(CoreError::Format(format!(
+            "[{key}]: expected {expected}-byte {name}, got {}",
+            bytes.len()
+        )));
+    }
+    Ok(())
+}
+
+fn map_get(map: &[(Value, Value)], key: u64) -> CoreResult<&Value> {
+    map.iter()
+        .find_map(|(k, v)| match k {
+            Value::Integer(int) => {
+                let int_value: Result<u64, _> = (*int).try_into();
+                if int_value.ok()? == key {
+                    Some(v)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+        .ok_or_else(|| CoreError::Format(format!("[{key}]: missing")))
+}
+
+fn map_get_opt(map: &[(Value, Value)], key: u64) -> Option<&Value> {
+    map.iter().find_map(|(k, v)| match k {
+        Value::Integer(int) => {
+            let int_value: Result<u64, _> = (*int).try_into();
+            if int_value.ok()? == key {
+                Some(v)
             } else {
                 None
             }
@@ -630,21 +2479,27 @@ pub fn encode_key_envelope_v1(envelope: &KeyEnvelopeV1) -> CoreResult<Vec<u8>> {
     if let Some(fp) = &envelope.recipient_uk_pub_fingerprint {
         entries.push((14, cbor_bytes(fp)));
     }
+    if let Some(prekey_id) = envelope.prekey_id {
+        entries.push((15, cbor_uint(prekey_id)));
+    }
     let value = cbor_map(entries);
     encode_canonical_value(&value)
 }
 
 pub fn decode_scope_state_v1(bytes: &[u8]) -> CoreResult<ScopeStateV1> {
-    let value = decode_canonical_value(bytes, &CborLimits::default())?;
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("scope_state"))?;
     ScopeStateV1::from_cbor(value)
 }
 
 pub fn decode_resource_grant_v1(bytes: &[u8]) -> CoreResult<ResourceGrantV1> {
-    let value = decode_canonical_value(bytes, &CborLimits::default())?;
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("resource_grant"))?;
     ResourceGrantV1::from_cbor(value)
 }
 
 pub fn decode_key_envelope_v1(bytes: &[u8]) -> CoreResult<KeyEnvelopeV1> {
-    let value = decode_canonical_value(bytes, &CborLimits::default())?;
+    let value = decode_canonical_value(bytes, &CborLimits::default())
+        .map_err(cbor_context("key_envelope"))?;
     KeyEnvelopeV1::from_cbor(value)
 }
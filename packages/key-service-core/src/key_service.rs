@@ -1,40 +1,155 @@
 //! Service orchestration and session policy for the Key Service core.
 
 use crate::aad::{
-    aad_key_envelope_wrap_v1, aad_keyvault_keywrap_v1, aad_resource_grant_wrap_v1,
-    aad_user_presence_wrap_v1,
+    aad_capability_token_wrap_v1, aad_device_share_seal_v1, aad_device_share_transfer_v1,
+    aad_key_envelope_prekey_wrap_v1, aad_key_envelope_wrap_v1, aad_keyvault_head_marker_v1,
+    aad_keyvault_keywrap_v1, aad_keyvault_kms_wrap_v1, aad_keyvault_passphrase_check_v1,
+    aad_resource_grant_wrap_v1, aad_user_presence_wrap_v1, hkdf_info_resource_subkey_v1,
 };
-use crate::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
+#[cfg(feature = "fido2")]
+use crate::adapters::HidAuthenticatorAdapter;
+use crate::adapters::{
+    ClockAdapter, DeviceAnchorAdapter, EntropyAdapter, KmsAdapter, LogAdapter, MaybeSend, MetricOp,
+    MetricsAdapter, MonotonicCounterAdapter, SessionEvent, SessionEventsAdapter, StorageAdapter,
+    TimerAdapter, TransparencyAdapter, VaultEvent, VaultEventsAdapter,
+};
+#[cfg(feature = "attestation")]
+use crate::attestation::verify_device_attestation;
+use crate::attestation::{
+    device_attestation_challenge, AttestationFormat, AttestationPolicy, VerifiedAttestation,
+};
+use crate::audit::{
+    audit_entry_key, decode_audit_entry_v1, encode_audit_entry_v1, verify_audit_chain,
+    AuditEntryV1, AuditEventKind, AuditVerifyReport, AUDIT_HEAD_KEY, AUDIT_NAMESPACE,
+};
+use crate::cancel::CancellationToken;
 use crate::cbor::{
-    cbor_array, cbor_text, decode_canonical_value, encode_canonical_value, CborLimits,
+    as_map, cbor_array, cbor_context, cbor_text, decode_canonical_value, encode_canonical_value,
+    opt_uint, CborLimits,
 };
+use crate::cgka::{CgkaCommitContext, CgkaGroup, CGKA_SECRET_LEN};
 use crate::ciphersuite::{
-    derive_hybrid_kem_wrap_key, generate_device_signing_keypair, generate_user_keypair,
-    hybrid_sign, hybrid_verify, HybridKemRecipient, SignerKeys,
+    decode_user_public_bytes, derive_hybrid_kem_wrap_key, derive_prekey_keypair,
+    encode_xwing_public_key, generate_device_signing_keypair, generate_user_keypair,
+    hybrid_kem_encapsulate, hybrid_sign, hybrid_sign_with_ed25519, hybrid_verify,
+    hybrid_verify_or_unbound, user_keypair_public, HybridKemRecipient, HybridKemRecipientPublic,
+    HybridSignatureKeypair, SignerKeys,
+};
+use crate::cosign::{
+    cosign_commit, cosign_group_key, cosign_verify_possession, CosignDeviceShare, CosignKeyShare,
+    CosignNonces, CosignParticipant, CosignTranscript,
+};
+use crate::crypto::{
+    aead_decrypt, aead_decrypt_in_place, aead_encrypt, aead_encrypt_in_place, committing_wrap,
+    ct_eq, derive_kek, derive_kek_with_progress, hkdf_sha256, kwp_wrap, sha256_bytes,
+    unwrap_key_with, KdfMinimums, KdfParams, INSECURE_TEST_KDF_ID,
+};
+use crate::device_shares::{
+    combine_device_shares, device_share, device_share_key_check, DEVICE_SHARE_SPLIT_ID_LEN,
+    MAX_SHARE_DEVICES,
+};
+use crate::diagnostics::{
+    redact_identifier, DiagnosticsReport, RecentError, VaultDiagnostics, DIAGNOSTICS_ERROR_LIMIT,
+};
+use crate::domains::{
+    SigContext, KEYVAULT_PASSPHRASE_CHECK_KEY_V1, KEYVAULT_PASSPHRASE_CHECK_V1, SIG_APP_PAYLOAD_V1,
+    SIG_AUDIT_ENTRY_V1, SIG_CGKA_COMMIT_V1, SIG_CGKA_WELCOME_V1, SIG_KEYVAULT_MANIFEST_V1,
+    SIG_KEY_ENVELOPE_V1, SIG_PREKEY_BUNDLE_V1, SIG_RESOURCE_GRANT_V1, SIG_SCOPE_STATE_V1,
+    SIG_SIGNING_DELEGATION_V1, USER_PRESENCE_SALT_V1, USER_PRESENCE_UNWRAP_K_VAULT_V1,
 };
-use crate::crypto::{aead_decrypt, aead_encrypt, derive_kek, hkdf_sha256, sha256_bytes};
-use crate::error::CoreError;
+use crate::envelope::{
+    parse_ciphertext_envelope, parse_legacy_ciphertext, parse_ratchet_envelope,
+    write_ciphertext_envelope_header, write_ratchet_envelope_header,
+    CIPHERTEXT_ENVELOPE_HEADER_LEN, CIPHERTEXT_RATCHET_HEADER_LEN,
+};
+use crate::error::{CoreError, ErrorDetail, ErrorReport};
+#[cfg(feature = "fido2")]
+use crate::fido2::{Fido2Authenticator, Fido2Error};
 use crate::formats::{
-    decode_keyvault_header_v1, decode_keyvault_record_container_v1, encode_keyvault_header_v1,
-    encode_keyvault_record_container_v1, encode_keyvault_snapshot_v1, KeyEnvelopeV1,
-    KeyVaultHeaderV1, KeyVaultRecordContainerV1, KeyVaultSnapshotV1, ResourceGrantV1, ScopeStateV1,
+    check_key_wrap_fields, decode_capability_token_v1, decode_cosign_enroll_response_v1,
+    decode_cosign_sign_response_v1, decode_device_share_request_v1,
+    decode_device_share_response_v1, decode_keyvault_header_v1,
+    decode_keyvault_record_container_v1, decode_keyvault_snapshot, encode_capability_token_v1,
+    encode_cgka_commit_v1, encode_cgka_welcome_v1, encode_cosign_enroll_request_v1,
+    encode_cosign_sign_request_v1, encode_device_share_request_v1, encode_device_share_response_v1,
+    encode_key_envelope_v1, encode_keyvault_header_v1, encode_keyvault_record_container_v1,
+    encode_keyvault_record_plain_v1, encode_keyvault_snapshot_v1, encode_keyvault_snapshot_v2,
+    encode_prekey_bundle_v1, encode_resource_grant_v1, write_keyvault_snapshot_v1,
+    CapabilityTokenV1, CgkaCommitV1, CgkaMemberV1, CgkaWelcomeV1, CosignEnrollRequestV1,
+    CosignSignRequestV1, DeviceShareConfigV1, DeviceShareRequestV1, DeviceShareResponseV1,
+    KeyEnvelopeV1, KeyVaultHeaderV1, KeyVaultManifestV1, KeyVaultRecordContainerV1,
+    KeyVaultRecordPlainV1, KeyVaultRecordProvenance, KeyVaultSnapshotDecoder, KeyVaultSnapshotV1,
+    PassphraseCheckV1, PrekeyBundleV1, PrekeyV1, ResourceGrantV1, ScopeStatePayload, ScopeStateV1,
+    SigningDelegationV1, VaultKeyWrapV1, MAX_PREKEYS_PER_BUNDLE,
 };
 use crate::hash::sha256;
 use crate::keyvault::{
+    apply_record_plain, make_cgka_group_record, make_checkpoint_record, make_contact_record,
+    make_cosign_share_record, make_prekey_consumed_record, make_prekeys_published_record,
+    make_rotation_policy_record, make_seen_id_record, make_store_app_blob_record,
     make_store_device_signing_key_record, make_store_resource_key_record,
-    make_store_scope_key_record, make_store_user_key_record, KeyVaultMaterialized, KeyVaultState,
+    make_store_scope_key_record, make_store_user_key_record, rechain_containers,
+    scope_key_lookup_key, ContactKey, KeyVaultCheckpoint, KeyVaultDamagedRecord, KeyVaultIndex,
+    KeyVaultMaterialized, KeyVaultRecordMetadata, KeyVaultSalvageReport, KeyVaultState,
+    KeyVaultStats, SeenIdKind, CHECKPOINT_RECORD_KIND,
+};
+use crate::logging::{LogEvent, LogLevel};
+use crate::opaque::{OpaqueConfig, OpaqueError, OpaqueKsf, OpaqueLogin, OpaqueRegistration};
+use crate::ratchet::{
+    ratchet_chain_start, ratchet_message_key, ratchet_step, MAX_RATCHET_MESSAGES,
+    RATCHET_CHAIN_ID_LEN,
+};
+use crate::secret::SecretBytes;
+use crate::session::{
+    HandleEntry, HandleRestriction, ResourceRatchet, Session, SessionManager, StreamEntry,
+};
+use crate::stream::{StreamDecryptor, StreamEncryptor, STREAM_HEADER_RANDOM_LEN};
+use crate::transparency::{
+    verify_consistency, verify_inclusion, InclusionProof, SignedTreeHead, TransparencyLeaf,
+    TransparencyLogKey,
 };
-use crate::session::{HandleEntry, Session, SessionManager};
 use crate::types::{
-    AeadId, DeviceId, KeyHandle, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, SessionAssurance,
-    SessionId, SessionKind, SigCiphersuiteId, UserId,
+    AeadId, CapabilityOps, DeviceId, EpochRetirement, IdPolicy, KemCiphersuiteId, KeyHandle,
+    KeyWrapAlg, ResourceId, ResourceKeyId, RotationReason, ScopeEpoch, ScopeId, ScopeRole,
+    ScopeRotationPolicy, SensitiveOperation, SessionAssurance, SessionId, SessionKind,
+    SigCiphersuiteId, SnapshotCompression, StreamId, UserId,
 };
 use aes_gcm::Aes256Gcm;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
+use zeroize::Zeroizing;
 
 const APP_MASTER_RESOURCE_ID: &str = "app-master-key";
 const APP_MASTER_RESOURCE_KEY_ID: &str = "v1";
+pub(crate) const HEAD_MARKER_ANCHOR_LABEL: &str = "mo-keyvault-head-marker";
+/// `(aad, sealed bytes)` of a stored value the device anchor has to open.
+pub(crate) type AnchoredBytes = (Vec<u8>, Vec<u8>);
+const ENCRYPT_OP: CapabilityOps = CapabilityOps {
+    encrypt: true,
+    decrypt: false,
+};
+const DECRYPT_OP: CapabilityOps = CapabilityOps {
+    encrypt: false,
+    decrypt: true,
+};
+/// Counter values reserved per storage write in [`NonceMode::Counter`].
+const NONCE_COUNTER_LEASE: u64 = 1024;
+/// Encrypt operations counted per storage write against a [`ScopeRotationPolicy`].
+const ROTATION_OPS_LEASE: u64 = 64;
+/// Sub-key lengths `derive_subkey` accepts.
+const SUBKEY_LEN_RANGE: std::ops::RangeInclusive<usize> = 16..=64;
+/// Vault blobs are for a handful of small items; bulk data belongs in resources.
+const MAX_VAULT_BLOB_LABEL_BYTES: usize = 128;
+const MAX_VAULT_BLOB_BYTES: usize = 64 * 1024;
+/// User-presence unlock opens `K_vault` without the KMS, so it cannot coexist with KMS mode.
+const USER_PRESENCE_BYPASSES_KMS: &str = "user presence unlock bypasses the KMS";
+/// Device shares recombine `K_vault` itself, so they cannot coexist with KMS mode either.
+const DEVICE_SHARES_BYPASS_KMS: &str = "device shares bypass the KMS";
+/// Anchor label of this device's sealed share at `keyvault/device_share`.
+const DEVICE_SHARE_ANCHOR_LABEL: &str = "mo-device-share";
+/// Scratch location written by [`KeyService::health_check`].
+pub const HEALTH_NAMESPACE: &str = "health";
+pub const HEALTH_PROBE_KEY: &str = "probe";
 
 #[derive(Debug, thiserror::Error)]
 pub enum KeyServiceError {
@@ -50,12 +165,16 @@ pub enum KeyServiceError {
     SessionInvalid,
     #[error("step-up required")]
     StepUpRequired,
+    #[error("operation needs a {} session", assurance_label(*.required))]
+    AssuranceRequired { required: SessionAssurance },
     #[error("scope signer not trusted")]
     UntrustedSigner,
     #[error("unknown scope")]
     UnknownScope,
     #[error("unknown key handle")]
     UnknownHandle,
+    #[error("unknown stream")]
+    UnknownStream,
     #[error("resource key not found")]
     ResourceKeyMissing,
     #[error("scope key not found")]
@@ -64,6 +183,407 @@ pub enum KeyServiceError {
     FingerprintMismatch,
     #[error("signer fingerprint required for first use")]
     SignerFingerprintRequired,
+    #[error("keyvault rollback detected")]
+    RollbackDetected,
+    #[error("passphrase too weak (score {score}, minimum {min_score})")]
+    WeakPassphrase { score: u8, min_score: u8 },
+    #[error("kdf {param} {value} below minimum {minimum}")]
+    WeakKdfParams {
+        param: &'static str,
+        value: u64,
+        minimum: u64,
+    },
+    #[error("payload exceeds max_plaintext_bytes")]
+    PayloadTooLarge,
+    #[error("keyvault would exceed {limit}")]
+    VaultLimitExceeded { limit: &'static str },
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("invalid {kind}: {reason}")]
+    InvalidId {
+        kind: &'static str,
+        reason: &'static str,
+    },
+    #[error("clock anomaly: {reason} (now {now_ms}, last seen {last_ms})")]
+    ClockAnomaly {
+        reason: &'static str,
+        now_ms: u64,
+        last_ms: u64,
+    },
+    #[error("transparency check failed: {reason}")]
+    TransparencyCheckFailed { reason: &'static str },
+    #[error("invariant violated: {invariant}")]
+    InvariantViolated { invariant: &'static str },
+    #[error("device attestation failed: {reason}")]
+    AttestationFailed { reason: &'static str },
+    #[error("group key agreement failed: {reason}")]
+    CgkaFailed { reason: &'static str },
+    #[error("one-time prekey rejected: {reason}")]
+    PrekeyRejected { reason: &'static str },
+    #[error("resource ratchet exhausted; open the resource again")]
+    RatchetExhausted,
+    #[error("export manifest check failed: {reason}")]
+    ExportManifestInvalid { reason: &'static str },
+    #[error("kms error: {0}")]
+    KmsError(String),
+    #[error("kms wrap rejected: {reason}")]
+    KmsWrapRejected { reason: &'static str },
+    #[error("device share rejected: {reason}")]
+    DeviceShareRejected { reason: &'static str },
+    #[error("wrong passphrase")]
+    WrongPassphrase,
+    #[error("opaque server authentication failed")]
+    OpaqueServerAuthFailed,
+    #[error("device key is co-signed; sign through the co-signing server")]
+    CosignRequired,
+    #[error("co-signing server response invalid")]
+    CosignServerInvalid,
+    #[error("vault key unwrap failed")]
+    VaultKeyUnwrapFailed,
+    #[error("keyvault corrupted: {reason}")]
+    VaultCorrupted { reason: &'static str },
+    #[error("vault key does not match the session")]
+    VaultKeyMismatch,
+    #[error("no keyvault on this device")]
+    VaultMissing,
+    #[error("keyvault not loaded")]
+    VaultNotLoaded,
+    #[error("user key not provisioned")]
+    UserKeyMissing,
+    #[error("device signing key not provisioned")]
+    DeviceKeyMissing,
+    #[error("user presence unlock not enabled")]
+    UserPresenceNotEnabled,
+    #[error("authenticator error: {0}")]
+    AuthenticatorError(String),
+    #[error("{format} signature invalid")]
+    SignatureInvalid { format: SignedFormat },
+    #[error("unknown scopeStateRef for scope {}", scope_id.0)]
+    UnknownScopeStateRef { scope_id: ScopeId },
+    #[error("grant chain broken for scope {}", scope_id.0)]
+    GrantChainBroken { scope_id: ScopeId },
+    #[error("{kind} unwrap failed")]
+    KeyUnwrapFailed { kind: WrappedKeyKind },
+    #[error("decrypt failed")]
+    DecryptFailed,
+    #[error("unsupported ciphersuite")]
+    UnsupportedSuite,
+    #[error("nonce counter exhausted")]
+    NonceCounterExhausted,
+    #[error("operation cancelled")]
+    Cancelled,
+    #[error("session is read-only")]
+    ReadOnlySession,
+    #[error("capability does not allow this operation")]
+    CapabilityDenied,
+    #[error("capability expired")]
+    CapabilityExpired,
+    #[error("signer's role in scope {} does not allow this change", scope_id.0)]
+    ScopeRoleDenied { scope_id: ScopeId },
+    #[error("signing delegation expired")]
+    DelegationExpired,
+    #[error("scope {} epoch {} is retired for encryption", scope_id.0, scope_epoch.0)]
+    EpochRetired {
+        scope_id: ScopeId,
+        scope_epoch: ScopeEpoch,
+    },
+    #[error("scope {} epoch {} is past its rotation deadline", scope_id.0, scope_epoch.0)]
+    RotationOverdue {
+        scope_id: ScopeId,
+        scope_epoch: ScopeEpoch,
+    },
+    #[error("no directory key for user {}", user_id.0)]
+    UnknownContact { user_id: UserId },
+}
+
+impl KeyServiceError {
+    /// Every code [`Self::code`] returns, for bindings that generate their own error types.
+    pub const CODES: &'static [&'static str] = &[
+        "StorageError",
+        "InvalidCbor",
+        "InvalidFormat",
+        "CryptoError",
+        "SessionInvalid",
+        "StepUpRequired",
+        "AssuranceRequired",
+        "UntrustedSigner",
+        "UnknownScope",
+        "UnknownHandle",
+        "UnknownStream",
+        "ResourceKeyMissing",
+        "ScopeKeyMissing",
+        "FingerprintMismatch",
+        "SignerFingerprintRequired",
+        "RollbackDetected",
+        "WeakPassphrase",
+        "WeakKdfParams",
+        "PayloadTooLarge",
+        "VaultLimitExceeded",
+        "InvalidConfig",
+        "InvalidId",
+        "ClockAnomaly",
+        "TransparencyCheckFailed",
+        "InvariantViolated",
+        "AttestationFailed",
+        "CgkaFailed",
+        "PrekeyRejected",
+        "RatchetExhausted",
+        "ExportManifestInvalid",
+        "KmsError",
+        "KmsWrapRejected",
+        "DeviceShareRejected",
+        "WrongPassphrase",
+        "OpaqueServerAuthFailed",
+        "CosignRequired",
+        "CosignServerInvalid",
+        "VaultKeyUnwrapFailed",
+        "VaultCorrupted",
+        "VaultKeyMismatch",
+        "VaultMissing",
+        "VaultNotLoaded",
+        "UserKeyMissing",
+        "DeviceKeyMissing",
+        "UserPresenceNotEnabled",
+        "AuthenticatorError",
+        "SignatureInvalid",
+        "UnknownScopeStateRef",
+        "GrantChainBroken",
+        "KeyUnwrapFailed",
+        "DecryptFailed",
+        "UnsupportedSuite",
+        "NonceCounterExhausted",
+        "Cancelled",
+        "ReadOnlySession",
+        "CapabilityDenied",
+        "CapabilityExpired",
+        "ScopeRoleDenied",
+        "DelegationExpired",
+        "EpochRetired",
+        "RotationOverdue",
+        "UnknownContact",
+    ];
+
+    /// Stable code for callers across the WASM boundary.
+    pub fn code(&self) -> &'static str {
+        match self {
+            KeyServiceError::StorageError(_) => "StorageError",
+            KeyServiceError::InvalidCbor(_) => "InvalidCbor",
+            KeyServiceError::InvalidFormat(_) => "InvalidFormat",
+            KeyServiceError::CryptoError(_) => "CryptoError",
+            KeyServiceError::SessionInvalid => "SessionInvalid",
+            KeyServiceError::StepUpRequired => "StepUpRequired",
+            KeyServiceError::AssuranceRequired { .. } => "AssuranceRequired",
+            KeyServiceError::UntrustedSigner => "UntrustedSigner",
+            KeyServiceError::UnknownScope => "UnknownScope",
+            KeyServiceError::UnknownHandle => "UnknownHandle",
+            KeyServiceError::UnknownStream => "UnknownStream",
+            KeyServiceError::ResourceKeyMissing => "ResourceKeyMissing",
+            KeyServiceError::ScopeKeyMissing => "ScopeKeyMissing",
+            KeyServiceError::FingerprintMismatch => "FingerprintMismatch",
+            KeyServiceError::SignerFingerprintRequired => "SignerFingerprintRequired",
+            KeyServiceError::RollbackDetected => "RollbackDetected",
+            KeyServiceError::WeakPassphrase { .. } => "WeakPassphrase",
+            KeyServiceError::WeakKdfParams { .. } => "WeakKdfParams",
+            KeyServiceError::PayloadTooLarge => "PayloadTooLarge",
+            KeyServiceError::VaultLimitExceeded { .. } => "VaultLimitExceeded",
+            KeyServiceError::InvalidConfig(_) => "InvalidConfig",
+            KeyServiceError::InvalidId { .. } => "InvalidId",
+            KeyServiceError::ClockAnomaly { .. } => "ClockAnomaly",
+            KeyServiceError::TransparencyCheckFailed { .. } => "TransparencyCheckFailed",
+            KeyServiceError::InvariantViolated { .. } => "InvariantViolated",
+            KeyServiceError::AttestationFailed { .. } => "AttestationFailed",
+            KeyServiceError::CgkaFailed { .. } => "CgkaFailed",
+            KeyServiceError::PrekeyRejected { .. } => "PrekeyRejected",
+            KeyServiceError::RatchetExhausted => "RatchetExhausted",
+            KeyServiceError::ExportManifestInvalid { .. } => "ExportManifestInvalid",
+            KeyServiceError::KmsError(_) => "KmsError",
+            KeyServiceError::KmsWrapRejected { .. } => "KmsWrapRejected",
+            KeyServiceError::DeviceShareRejected { .. } => "DeviceShareRejected",
+            KeyServiceError::WrongPassphrase => "WrongPassphrase",
+            KeyServiceError::OpaqueServerAuthFailed => "OpaqueServerAuthFailed",
+            KeyServiceError::CosignRequired => "CosignRequired",
+            KeyServiceError::CosignServerInvalid => "CosignServerInvalid",
+            KeyServiceError::VaultKeyUnwrapFailed => "VaultKeyUnwrapFailed",
+            KeyServiceError::VaultCorrupted { .. } => "VaultCorrupted",
+            KeyServiceError::VaultKeyMismatch => "VaultKeyMismatch",
+            KeyServiceError::VaultMissing => "VaultMissing",
+            KeyServiceError::VaultNotLoaded => "VaultNotLoaded",
+            KeyServiceError::UserKeyMissing => "UserKeyMissing",
+            KeyServiceError::DeviceKeyMissing => "DeviceKeyMissing",
+            KeyServiceError::UserPresenceNotEnabled => "UserPresenceNotEnabled",
+            KeyServiceError::AuthenticatorError(_) => "AuthenticatorError",
+            KeyServiceError::SignatureInvalid { .. } => "SignatureInvalid",
+            KeyServiceError::UnknownScopeStateRef { .. } => "UnknownScopeStateRef",
+            KeyServiceError::GrantChainBroken { .. } => "GrantChainBroken",
+            KeyServiceError::KeyUnwrapFailed { .. } => "KeyUnwrapFailed",
+            KeyServiceError::DecryptFailed => "DecryptFailed",
+            KeyServiceError::UnsupportedSuite => "UnsupportedSuite",
+            KeyServiceError::NonceCounterExhausted => "NonceCounterExhausted",
+            KeyServiceError::Cancelled => "Cancelled",
+            KeyServiceError::ReadOnlySession => "ReadOnlySession",
+            KeyServiceError::CapabilityDenied => "CapabilityDenied",
+            KeyServiceError::CapabilityExpired => "CapabilityExpired",
+            KeyServiceError::ScopeRoleDenied { .. } => "ScopeRoleDenied",
+            KeyServiceError::DelegationExpired => "DelegationExpired",
+            KeyServiceError::EpochRetired { .. } => "EpochRetired",
+            KeyServiceError::RotationOverdue { .. } => "RotationOverdue",
+            KeyServiceError::UnknownContact { .. } => "UnknownContact",
+        }
+    }
+
+    /// True when the same call may succeed unchanged later (e.g. a transient storage failure).
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            KeyServiceError::StorageError(_) | KeyServiceError::KmsError(_)
+        )
+    }
+
+    /// The error as bindings forward it; see [`ErrorReport`].
+    pub fn report(&self) -> ErrorReport {
+        let text = |value: &str| ErrorDetail::Text(value.to_string());
+        let context = match self {
+            KeyServiceError::WeakPassphrase { score, min_score } => vec![
+                ("score", ErrorDetail::Uint(u64::from(*score))),
+                ("minScore", ErrorDetail::Uint(u64::from(*min_score))),
+            ],
+            KeyServiceError::WeakKdfParams {
+                param,
+                value,
+                minimum,
+            } => vec![
+                ("param", text(param)),
+                ("value", ErrorDetail::Uint(*value)),
+                ("minimum", ErrorDetail::Uint(*minimum)),
+            ],
+            KeyServiceError::VaultLimitExceeded { limit } => vec![("limit", text(limit))],
+            KeyServiceError::InvalidId { kind, reason } => {
+                vec![("kind", text(kind)), ("reason", text(reason))]
+            }
+            KeyServiceError::ClockAnomaly {
+                reason,
+                now_ms,
+                last_ms,
+            } => vec![
+                ("reason", text(reason)),
+                ("nowMs", ErrorDetail::Uint(*now_ms)),
+                ("lastMs", ErrorDetail::Uint(*last_ms)),
+            ],
+            KeyServiceError::TransparencyCheckFailed { reason } => vec![("reason", text(reason))],
+            KeyServiceError::AssuranceRequired { required } => {
+                vec![("required", text(assurance_label(*required)))]
+            }
+            KeyServiceError::InvariantViolated { invariant } => {
+                vec![("invariant", text(invariant))]
+            }
+            KeyServiceError::AttestationFailed { reason }
+            | KeyServiceError::CgkaFailed { reason }
+            | KeyServiceError::PrekeyRejected { reason }
+            | KeyServiceError::ExportManifestInvalid { reason }
+            | KeyServiceError::KmsWrapRejected { reason }
+            | KeyServiceError::DeviceShareRejected { reason }
+            | KeyServiceError::VaultCorrupted { reason } => vec![("reason", text(reason))],
+            KeyServiceError::SignatureInvalid { format } => {
+                vec![("format", text(format.as_str()))]
+            }
+            KeyServiceError::UnknownScopeStateRef { scope_id }
+            | KeyServiceError::GrantChainBroken { scope_id }
+            | KeyServiceError::ScopeRoleDenied { scope_id } => {
+                vec![("scopeId", text(&scope_id.0))]
+            }
+            KeyServiceError::KeyUnwrapFailed { kind } => vec![("kind", text(kind.as_str()))],
+            KeyServiceError::EpochRetired {
+                scope_id,
+                scope_epoch,
+            }
+            | KeyServiceError::RotationOverdue {
+                scope_id,
+                scope_epoch,
+            } => vec![
+                ("scopeId", text(&scope_id.0)),
+                ("scopeEpoch", ErrorDetail::Uint(scope_epoch.0)),
+            ],
+            KeyServiceError::UnknownContact { user_id } => vec![("userId", text(&user_id.0))],
+            _ => Vec::new(),
+        };
+        ErrorReport {
+            code: self.code(),
+            message: self.to_string(),
+            retryable: self.is_retryable(),
+            context,
+        }
+    }
+}
+
+/// Signed wire format named by [`KeyServiceError::SignatureInvalid`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignedFormat {
+    ScopeState,
+    ResourceGrant,
+    KeyEnvelope,
+    SigningDelegation,
+    CgkaCommit,
+    CgkaWelcome,
+    PrekeyBundle,
+}
+
+impl SignedFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignedFormat::ScopeState => "scope state",
+            SignedFormat::ResourceGrant => "resource grant",
+            SignedFormat::KeyEnvelope => "key envelope",
+            SignedFormat::SigningDelegation => "signing delegation",
+            SignedFormat::CgkaCommit => "cgka commit",
+            SignedFormat::CgkaWelcome => "cgka welcome",
+            SignedFormat::PrekeyBundle => "prekey bundle",
+        }
+    }
+
+    /// The context signatures over this format are bound to.
+    pub fn sig_context(&self) -> SigContext {
+        match self {
+            SignedFormat::ScopeState => SIG_SCOPE_STATE_V1,
+            SignedFormat::ResourceGrant => SIG_RESOURCE_GRANT_V1,
+            SignedFormat::KeyEnvelope => SIG_KEY_ENVELOPE_V1,
+            SignedFormat::SigningDelegation => SIG_SIGNING_DELEGATION_V1,
+            SignedFormat::CgkaCommit => SIG_CGKA_COMMIT_V1,
+            SignedFormat::CgkaWelcome => SIG_CGKA_WELCOME_V1,
+            SignedFormat::PrekeyBundle => SIG_PREKEY_BUNDLE_V1,
+        }
+    }
+}
+
+impl std::fmt::Display for SignedFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Wrapped key named by [`KeyServiceError::KeyUnwrapFailed`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrappedKeyKind {
+    ScopeKey,
+    ResourceKey,
+    HeadMarker,
+    CapabilityToken,
+}
+
+impl WrappedKeyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WrappedKeyKind::ScopeKey => "scope key",
+            WrappedKeyKind::ResourceKey => "resource key",
+            WrappedKeyKind::HeadMarker => "head marker",
+            WrappedKeyKind::CapabilityToken => "capability token",
+        }
+    }
+}
+
+impl std::fmt::Display for WrappedKeyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl From<CoreError> for KeyServiceError {
@@ -73,10 +593,51 @@ impl From<CoreError> for KeyServiceError {
             CoreError::Format(msg) => KeyServiceError::InvalidFormat(msg),
             CoreError::Crypto(msg) => KeyServiceError::CryptoError(msg),
             CoreError::Entropy(msg) => KeyServiceError::CryptoError(msg),
+            CoreError::Cancelled => KeyServiceError::Cancelled,
+            CoreError::WeakKdf {
+                param,
+                value,
+                minimum,
+            } => KeyServiceError::WeakKdfParams {
+                param,
+                value,
+                minimum,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "fido2")]
+impl From<Fido2Error> for KeyServiceError {
+    fn from(err: Fido2Error) -> Self {
+        match err {
+            Fido2Error::Core(err) => err.into(),
+            err => KeyServiceError::AuthenticatorError(err.to_string()),
+        }
+    }
+}
+
+impl From<OpaqueError> for KeyServiceError {
+    fn from(err: OpaqueError) -> Self {
+        match err {
+            OpaqueError::InvalidMessage(msg) => KeyServiceError::InvalidFormat(msg.to_string()),
+            OpaqueError::EnvelopeRecovery => KeyServiceError::WrongPassphrase,
+            OpaqueError::ServerAuthentication => KeyServiceError::OpaqueServerAuthFailed,
+            OpaqueError::Core(err) => err.into(),
         }
     }
 }
 
+/// How `encrypt` picks AES-GCM nonces for resource keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonceMode {
+    /// 12 random bytes from the `EntropyAdapter`.
+    #[default]
+    Random,
+    /// A random 4-byte prefix per handle plus a 64-bit counter persisted across reopen.
+    Counter,
+}
+
 #[derive(Clone, Debug)]
 pub struct KeyServicePolicy {
     pub normal_session_ttl_ms: u64,
@@ -87,6 +648,58 @@ pub struct KeyServicePolicy {
     pub max_cbor_items: usize,
     pub max_cbor_text_bytes: usize,
     pub max_scope_state_refs_per_scope: usize,
+    /// Minimum zxcvbn score (0-4) for new passphrases; needs the `passphrase-strength` feature.
+    pub min_passphrase_score: Option<u8>,
+    /// Upper bound for one-shot `encrypt`/`decrypt`; larger payloads go through streams.
+    pub max_plaintext_bytes: usize,
+    pub nonce_mode: NonceMode,
+    /// How long before expiry [`SessionEvent::ExpiringSoon`] is sent; 0 disables it.
+    pub session_expiry_warning_ms: u64,
+    /// Every session is read-only, as if unlocked with the `*_read_only` variants.
+    pub read_only_sessions: bool,
+    /// Largest encoded keyvault record; imports compare record ciphertext minus the AEAD tag.
+    pub max_record_bytes: usize,
+    /// Most records a keyvault may hold, checked on append and import.
+    pub max_total_records: u64,
+    /// Unlock skips decrypting resource-key records listed in the stored keyvault index and
+    /// loads each one on first use.
+    pub lazy_resource_keys: bool,
+    /// Algorithm for new passphrase and PRF wraps of `K_vault`; existing wraps keep theirs.
+    pub key_wrap_alg: KeyWrapAlg,
+    /// Also accept signatures made before messages were bound to a
+    /// [`crate::domains::SigContext`]. New signatures are always bound; this only keeps
+    /// already-stored objects verifying and is slated to default to `false`.
+    pub accept_unbound_signatures: bool,
+    /// Accept [`crate::crypto::INSECURE_TEST_KDF_ID`] vaults. Only valid with the `test-utils`
+    /// feature.
+    pub allow_insecure_test_kdf: bool,
+    /// Weakest KDF parameters accepted when creating, importing, or loading a vault.
+    pub kdf_minimums: KdfMinimums,
+    /// Length and charset every id passed in or decoded from ingested data must satisfy.
+    pub ids: IdPolicy,
+    /// How far the clock may step back behind the latest reading a session check saw. A larger
+    /// step fails with `ClockAnomaly` until the clock catches up, so rewinding it cannot
+    /// stretch a session.
+    pub max_clock_rewind_ms: u64,
+    /// Largest forward jump between two session checks; a larger one locks every session with
+    /// `ClockAnomaly`. `None` accepts any jump, e.g. after the device slept.
+    pub max_clock_jump_ms: Option<u64>,
+    /// Fail scope state ingest and user key checks when no transparency log is set, instead
+    /// of trusting without one.
+    pub require_transparency: bool,
+    /// Oldest transparency tree head accepted, measured from its timestamp.
+    pub max_tree_head_age_ms: u64,
+    /// Scope id -> what a device must attest to before a scope state may add it as a signer;
+    /// see [`KeyService::ingest_device_attestation`]. Scopes not listed add signers freely.
+    pub scope_attestation: BTreeMap<String, AttestationPolicy>,
+    /// Parameters shared with the OPAQUE server for server-assisted unlock.
+    pub opaque: OpaqueConfig,
+    /// Refuse to import snapshots without a manifest signed by one of this vault's devices.
+    /// Otherwise a manifest is still checked when present, but its signer may be any device.
+    pub require_export_manifest: bool,
+    /// Operation -> weakest [`SessionAssurance`] its step-up session may have, on top of the
+    /// step-up itself. Operations not listed take any assurance.
+    pub min_assurance: BTreeMap<SensitiveOperation, SessionAssurance>,
 }
 
 impl Default for KeyServicePolicy {
@@ -100,7 +713,254 @@ impl Default for KeyServicePolicy {
             max_cbor_items: 4096,
             max_cbor_text_bytes: 64 * 1024,
             max_scope_state_refs_per_scope: 64,
+            min_passphrase_score: None,
+            max_plaintext_bytes: 16 * 1024 * 1024,
+            nonce_mode: NonceMode::Random,
+            session_expiry_warning_ms: 30 * 1000,
+            read_only_sessions: false,
+            lazy_resource_keys: false,
+            max_record_bytes: 256 * 1024,
+            max_total_records: 1_000_000,
+            key_wrap_alg: KeyWrapAlg::AesGcm,
+            accept_unbound_signatures: true,
+            allow_insecure_test_kdf: false,
+            kdf_minimums: KdfMinimums::default(),
+            ids: IdPolicy::default(),
+            max_clock_rewind_ms: 5 * 60 * 1000,
+            max_clock_jump_ms: None,
+            require_transparency: false,
+            max_tree_head_age_ms: 24 * 60 * 60 * 1000,
+            scope_attestation: BTreeMap::new(),
+            opaque: OpaqueConfig::default(),
+            require_export_manifest: false,
+            min_assurance: BTreeMap::new(),
+        }
+    }
+}
+
+impl KeyServicePolicy {
+    pub fn builder() -> KeyServicePolicyBuilder {
+        KeyServicePolicyBuilder::default()
+    }
+
+    /// Rejects policies that would lock users out or defeat the configured limits.
+    pub fn validate(&self) -> Result<(), KeyServiceError> {
+        let invalid = |msg: &str| Err(KeyServiceError::InvalidConfig(msg.to_string()));
+        if self.normal_session_ttl_ms == 0 || self.step_up_session_ttl_ms == 0 {
+            return invalid("session TTLs must be non-zero");
+        }
+        if self.step_up_session_ttl_ms > self.normal_session_ttl_ms {
+            return invalid("step-up TTL must not exceed normal TTL");
+        }
+        if self.max_handles_per_session == 0 {
+            return invalid("max_handles_per_session must be non-zero");
         }
+        if self.max_cbor_bytes == 0 || self.max_cbor_items == 0 {
+            return invalid("CBOR byte and item limits must be non-zero");
+        }
+        if self.max_cbor_depth == 0 || self.max_cbor_depth > 256 {
+            return invalid("max_cbor_depth must be between 1 and 256");
+        }
+        if self.max_cbor_text_bytes == 0 || self.max_cbor_text_bytes > self.max_cbor_bytes {
+            return invalid("max_cbor_text_bytes must be non-zero and within max_cbor_bytes");
+        }
+        if self.max_scope_state_refs_per_scope == 0 {
+            return invalid("max_scope_state_refs_per_scope must be non-zero");
+        }
+        if self.min_passphrase_score.is_some_and(|score| score > 4) {
+            return invalid("min_passphrase_score must be between 0 and 4");
+        }
+        if self.max_plaintext_bytes == 0 {
+            return invalid("max_plaintext_bytes must be non-zero");
+        }
+        if self.max_record_bytes == 0 || self.max_total_records == 0 {
+            return invalid("max_record_bytes and max_total_records must be non-zero");
+        }
+        if self.ids.max_bytes == 0 {
+            return invalid("ids.max_bytes must be non-zero");
+        }
+        if self.max_clock_jump_ms == Some(0) {
+            return invalid("max_clock_jump_ms must be non-zero");
+        }
+        if self.max_tree_head_age_ms == 0 {
+            return invalid("max_tree_head_age_ms must be non-zero");
+        }
+        for attestation in self.scope_attestation.values() {
+            if attestation.formats.is_empty() {
+                return invalid("scope_attestation policies must accept a format");
+            }
+            if attestation.trust_roots.is_empty() && !attestation.allow_self_attestation {
+                return invalid("scope_attestation policies need trust roots or self attestation");
+            }
+        }
+        if self.allow_insecure_test_kdf && !cfg!(feature = "test-utils") {
+            return invalid("allow_insecure_test_kdf needs the test-utils feature");
+        }
+        if self.opaque.ksf == OpaqueKsf::Identity && !self.allow_insecure_test_kdf {
+            return invalid("the identity OPAQUE KSF needs allow_insecure_test_kdf");
+        }
+        self.opaque
+            .validate()
+            .map_err(KeyServiceError::InvalidConfig)?;
+        Ok(())
+    }
+}
+
+/// Fluent, validated construction of a [`KeyServicePolicy`] starting from the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct KeyServicePolicyBuilder {
+    policy: KeyServicePolicy,
+}
+
+impl KeyServicePolicyBuilder {
+    pub fn normal_ttl(mut self, ms: u64) -> Self {
+        self.policy.normal_session_ttl_ms = ms;
+        self
+    }
+
+    pub fn step_up_ttl(mut self, ms: u64) -> Self {
+        self.policy.step_up_session_ttl_ms = ms;
+        self
+    }
+
+    pub fn max_handles(mut self, max: usize) -> Self {
+        self.policy.max_handles_per_session = max;
+        self
+    }
+
+    pub fn cbor_limits(mut self, limits: CborLimits) -> Self {
+        self.policy.max_cbor_bytes = limits.max_bytes;
+        self.policy.max_cbor_depth = limits.max_depth;
+        self.policy.max_cbor_items = limits.max_items;
+        self.policy.max_cbor_text_bytes = limits.max_text_bytes;
+        self
+    }
+
+    pub fn max_scope_state_refs_per_scope(mut self, max: usize) -> Self {
+        self.policy.max_scope_state_refs_per_scope = max;
+        self
+    }
+
+    pub fn min_passphrase_score(mut self, score: u8) -> Self {
+        self.policy.min_passphrase_score = Some(score);
+        self
+    }
+
+    pub fn max_plaintext_bytes(mut self, max: usize) -> Self {
+        self.policy.max_plaintext_bytes = max;
+        self
+    }
+
+    pub fn nonce_mode(mut self, mode: NonceMode) -> Self {
+        self.policy.nonce_mode = mode;
+        self
+    }
+
+    pub fn expiry_warning(mut self, ms: u64) -> Self {
+        self.policy.session_expiry_warning_ms = ms;
+        self
+    }
+
+    pub fn read_only_sessions(mut self, read_only: bool) -> Self {
+        self.policy.read_only_sessions = read_only;
+        self
+    }
+
+    pub fn max_record_bytes(mut self, max: usize) -> Self {
+        self.policy.max_record_bytes = max;
+        self
+    }
+
+    pub fn max_total_records(mut self, max: u64) -> Self {
+        self.policy.max_total_records = max;
+        self
+    }
+
+    pub fn lazy_resource_keys(mut self, lazy: bool) -> Self {
+        self.policy.lazy_resource_keys = lazy;
+        self
+    }
+
+    pub fn key_wrap_alg(mut self, alg: KeyWrapAlg) -> Self {
+        self.policy.key_wrap_alg = alg;
+        self
+    }
+
+    pub fn accept_unbound_signatures(mut self, accept: bool) -> Self {
+        self.policy.accept_unbound_signatures = accept;
+        self
+    }
+
+    pub fn opaque(mut self, config: OpaqueConfig) -> Self {
+        self.policy.opaque = config;
+        self
+    }
+
+    pub fn kdf_minimums(mut self, minimums: KdfMinimums) -> Self {
+        self.policy.kdf_minimums = minimums;
+        self
+    }
+
+    pub fn id_policy(mut self, ids: IdPolicy) -> Self {
+        self.policy.ids = ids;
+        self
+    }
+
+    pub fn max_clock_rewind(mut self, ms: u64) -> Self {
+        self.policy.max_clock_rewind_ms = ms;
+        self
+    }
+
+    pub fn max_clock_jump(mut self, ms: u64) -> Self {
+        self.policy.max_clock_jump_ms = Some(ms);
+        self
+    }
+
+    pub fn require_transparency(mut self, require: bool) -> Self {
+        self.policy.require_transparency = require;
+        self
+    }
+
+    pub fn max_tree_head_age(mut self, ms: u64) -> Self {
+        self.policy.max_tree_head_age_ms = ms;
+        self
+    }
+
+    pub fn require_export_manifest(mut self, require: bool) -> Self {
+        self.policy.require_export_manifest = require;
+        self
+    }
+
+    pub fn min_assurance(
+        mut self,
+        operation: SensitiveOperation,
+        assurance: SessionAssurance,
+    ) -> Self {
+        self.policy.min_assurance.insert(operation, assurance);
+        self
+    }
+
+    pub fn scope_attestation(
+        mut self,
+        scope_id: impl Into<String>,
+        attestation: AttestationPolicy,
+    ) -> Self {
+        self.policy
+            .scope_attestation
+            .insert(scope_id.into(), attestation);
+        self
+    }
+
+    /// INSECURE: accepts [`crate::crypto::KdfParams::insecure_fast_for_tests`] vaults.
+    #[cfg(feature = "test-utils")]
+    pub fn allow_insecure_test_kdf(mut self, allow: bool) -> Self {
+        self.policy.allow_insecure_test_kdf = allow;
+        self
+    }
+
+    pub fn build(self) -> Result<KeyServicePolicy, KeyServiceError> {
+        self.policy.validate()?;
+        Ok(self.policy)
     }
 }
 
@@ -109,6 +969,29 @@ pub struct KeyServiceConfig {
     pub policy: KeyServicePolicy,
 }
 
+impl KeyServiceConfig {
+    pub fn builder() -> KeyServiceConfigBuilder {
+        KeyServiceConfigBuilder::default()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct KeyServiceConfigBuilder {
+    config: KeyServiceConfig,
+}
+
+impl KeyServiceConfigBuilder {
+    pub fn policy(mut self, policy: KeyServicePolicy) -> Self {
+        self.config.policy = policy;
+        self
+    }
+
+    pub fn build(self) -> Result<KeyServiceConfig, KeyServiceError> {
+        self.config.policy.validate()?;
+        Ok(self.config)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct UnlockResponse {
     pub session_id: SessionId,
@@ -116,6 +999,68 @@ pub struct UnlockResponse {
     pub expires_at_ms: u64,
     pub kind: SessionKind,
     pub assurance: SessionAssurance,
+    /// False until `init_identity` has stored a user keypair.
+    pub has_user_key: bool,
+    /// Devices with a signing key in this vault, sorted.
+    pub device_ids: Vec<DeviceId>,
+    pub read_only: bool,
+}
+
+/// An OPAQUE unlock: the session plus KE3 for the server.
+#[derive(Clone, Debug)]
+pub struct OpaqueUnlockResponse {
+    pub unlock: UnlockResponse,
+    pub ke3: Vec<u8>,
+}
+
+/// Device-share unlock in progress; consumed by [`KeyService::unlock_device_shares`]. Holds
+/// the one-time KEM key that peers seal their shares to.
+#[derive(Debug)]
+pub struct DeviceShareUnlock {
+    request: DeviceShareRequestV1,
+    request_bytes: Vec<u8>,
+    recipient: HybridKemRecipient,
+}
+
+impl DeviceShareUnlock {
+    /// Encoded [`DeviceShareRequestV1`] to send to peer devices.
+    pub fn request(&self) -> &[u8] {
+        &self.request_bytes
+    }
+}
+
+/// Co-signing enrollment in progress; consumed by [`KeyService::finish_cosign_enrollment`].
+#[derive(Debug)]
+pub struct CosignEnrollment {
+    device_id: DeviceId,
+    share: CosignKeyShare,
+}
+
+/// One co-signing attempt; consumed by [`KeyService::finish_cosign`], so its nonces are
+/// never reused.
+#[derive(Debug)]
+pub struct CosignSigning {
+    device_id: DeviceId,
+    group_pub: [u8; 32],
+    context: SigContext,
+    data: Vec<u8>,
+    nonces: CosignNonces,
+    commitment: crate::cosign::CosignCommitment,
+}
+
+#[derive(Clone, Debug)]
+pub struct CosignEnrollResponse {
+    pub device_id: DeviceId,
+    /// The device's new `ed25519_pub`, as reported by `get_device_public_keys`.
+    pub group_pub: Vec<u8>,
+}
+
+/// A salvage unlock: the session plus what was found. `unlock.read_only` is set while the
+/// report has a `break_seq`.
+#[derive(Clone, Debug)]
+pub struct SalvageUnlockResponse {
+    pub unlock: UnlockResponse,
+    pub report: KeyVaultSalvageReport,
 }
 
 #[derive(Clone, Debug)]
@@ -124,6 +1069,14 @@ pub struct StepUpResponse {
     pub expires_at_ms: u64,
 }
 
+/// Outcome of [`KeyService::change_passphrase`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangePassphraseResponse {
+    /// True when an enrolled user-presence unlock was removed because its wrap was bound to
+    /// the old KDF parameters; re-enroll it with the new header.
+    pub user_presence_disabled: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct RenewSessionResponse {
     pub issued_at_ms: u64,
@@ -138,72 +1091,404 @@ pub struct GetUserPresenceUnlockInfoResponse {
     pub aead: AeadId,
 }
 
+/// Public header facts, readable before unlock.
+#[derive(Clone, Debug)]
+pub struct VaultInfoResponse {
+    pub vault_id: String,
+    pub user_id: UserId,
+    pub kdf: crate::crypto::KdfParams,
+    pub aead: AeadId,
+    pub record_count: u64,
+    pub user_presence_enabled: bool,
+    /// Set when unlock also needs the KMS; see [`KeyService::enable_kms_wrap`].
+    pub kms_key_id: Option<String>,
+    /// Shares needed for [`KeyService::unlock_device_shares`], when a split is enabled.
+    pub device_share_threshold: Option<u64>,
+}
+
+/// Outcome of [`KeyService::reload_from_storage`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReloadResponse {
+    /// False when storage still matched the loaded header and chain head.
+    pub changed: bool,
+    pub head_seq: u64,
+}
+
+/// Outcome of [`KeyService::health_check`]; `problems` has one line per failed probe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheckResponse {
+    pub ok: bool,
+    pub storage_ok: bool,
+    pub entropy_ok: bool,
+    pub clock_ok: bool,
+    pub problems: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceAttestationResponse {
+    pub format: AttestationFormat,
+    /// Authenticator model; all zero for authenticators that do not disclose it.
+    pub aaguid: [u8; 16],
+    /// The statement was signed by the credential key itself, not an attestation certificate.
+    pub self_attested: bool,
+    /// Fingerprint of the signer keys the attestation is bound to.
+    pub fingerprint: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct IngestScopeStateResponse {
     pub scope_id: ScopeId,
     pub scope_state_ref: String,
+    pub warnings: Vec<IngestWarning>,
+}
+
+/// Soft issue noticed while ingesting a scope state or key envelope. The ingest itself
+/// succeeded; callers may surface these without failing a sync.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IngestWarning {
+    /// A new signer key was trusted on the caller's expected fingerprint.
+    FingerprintPinned { device_id: DeviceId },
+    /// The newly pinned signer joined a scope that already had trusted signers, and no
+    /// membership change named it beforehand.
+    SignerPreviouslyUnseen { device_id: DeviceId },
+    /// The item's epoch is older than the newest one known for the scope.
+    OlderEpoch {
+        scope_epoch: ScopeEpoch,
+        latest_epoch: ScopeEpoch,
+    },
+    /// A membership change carried a key for a user the recipient key directory already holds
+    /// a different key for; the directory kept its key.
+    ContactKeyConflict { user_id: UserId },
+}
+
+impl IngestWarning {
+    /// Stable code, in the style of [`KeyServiceError::code`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            IngestWarning::FingerprintPinned { .. } => "FingerprintPinned",
+            IngestWarning::SignerPreviouslyUnseen { .. } => "SignerPreviouslyUnseen",
+            IngestWarning::OlderEpoch { .. } => "OlderEpoch",
+            IngestWarning::ContactKeyConflict { .. } => "ContactKeyConflict",
+        }
+    }
+}
+
+impl std::fmt::Display for IngestWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IngestWarning::FingerprintPinned { device_id } => {
+                write!(f, "fingerprint pin created for device {}", device_id.0)
+            }
+            IngestWarning::SignerPreviouslyUnseen { device_id } => {
+                write!(f, "signer {} previously unseen in the scope", device_id.0)
+            }
+            IngestWarning::OlderEpoch {
+                scope_epoch,
+                latest_epoch,
+            } => write!(
+                f,
+                "epoch {} older than known epoch {}",
+                scope_epoch.0, latest_epoch.0
+            ),
+            IngestWarning::ContactKeyConflict { user_id } => {
+                write!(f, "kept the directory's key for user {}", user_id.0)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IngestDelegationResponse {
+    pub scope_id: ScopeId,
+    pub delegate_device_id: DeviceId,
+    pub expires_at_ms: u64,
 }
 
 #[derive(Clone, Debug)]
 pub struct IngestKeyEnvelopeResponse {
     pub scope_id: ScopeId,
     pub scope_epoch: ScopeEpoch,
+    /// False when the envelope id was already ingested for the scope; a replay changes nothing.
+    pub is_new: bool,
+    /// Always empty for a replay.
+    pub warnings: Vec<IngestWarning>,
 }
 
 #[derive(Clone, Debug)]
-pub struct OpenScopeResponse {
-    pub scope_key_handle: KeyHandle,
+pub struct PublishPrekeysResponse {
+    /// Encoded [`PrekeyBundleV1`] for the server to hand out, one prekey per sender.
+    pub bundle: Vec<u8>,
+    pub prekey_ids: Vec<u64>,
 }
 
 #[derive(Clone, Debug)]
-pub struct OpenResourceResponse {
-    pub resource_key_handle: KeyHandle,
+pub struct SealKeyEnvelopeResponse {
+    pub envelope_id: String,
+    /// Encoded [`KeyEnvelopeV1`] for the recipient's `ingest_key_envelope`.
+    pub envelope: Vec<u8>,
 }
 
 #[derive(Clone, Debug)]
-pub struct EncryptResponse {
-    pub ciphertext: Vec<u8>,
+pub struct CgkaCommitResponse {
+    pub scope_epoch: ScopeEpoch,
+    /// Encoded [`CgkaCommitV1`] for the group's other members.
+    pub commit: Vec<u8>,
+    /// Encoded [`CgkaWelcomeV1`] for the members the commit adds, if any.
+    pub welcome: Option<Vec<u8>>,
 }
 
 #[derive(Clone, Debug)]
-pub struct DecryptResponse {
-    pub plaintext: Vec<u8>,
+pub struct IngestCgkaResponse {
+    pub scope_id: ScopeId,
+    pub scope_epoch: ScopeEpoch,
+    /// False when the group is already at or past the epoch; nothing changed.
+    pub is_new: bool,
 }
 
 #[derive(Clone, Debug)]
-pub struct SignResponse {
-    pub signature: Vec<u8>,
-    pub ciphersuite: SigCiphersuiteId,
+pub struct OpenScopeResponse {
+    pub scope_key_handle: KeyHandle,
 }
 
 #[derive(Clone, Debug)]
-pub struct VerifyResponse {
-    pub ok: bool,
+pub struct UserPublicKeyResponse {
+    pub kem: KemCiphersuiteId,
+    pub public_bytes: Vec<u8>,
+    /// SHA-256 of `public_bytes`, as carried in `KeyEnvelopeV1.recipient_uk_pub_fingerprint`.
+    pub fingerprint: Vec<u8>,
+    /// `fingerprint` as lowercase hex, for display and key directories.
+    pub fingerprint_hex: String,
+    /// The same key in X-Wing encoding, for senders that encapsulate with `hybrid-kem-xwing`.
+    pub xwing_public_bytes: Vec<u8>,
 }
 
-#[derive(Debug)]
-pub struct KeyServiceState {
-    pub keyvault_header: KeyVaultHeaderV1,
-    pub keyvault_state: KeyVaultState,
-    pub keyvault_materialized: KeyVaultMaterialized,
-    pub signer_roster: SignerRoster,
+/// Another user's public KEM key in the recipient key directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Contact {
+    pub user_id: UserId,
+    pub public_bytes: Vec<u8>,
+    /// Lowercase hex SHA-256 of `public_bytes`, as in [`UserPublicKeyResponse::fingerprint_hex`].
+    pub fingerprint_hex: String,
+    /// Scope whose signed membership change supplied the key; `None` for [`KeyService::add_contact`].
+    pub scope_id: Option<ScopeId>,
+    pub added_at_ms: Option<u64>,
 }
 
 #[derive(Clone, Debug)]
-pub struct SignerRoster {
-    pub scopes: HashMap<String, HashMap<String, SignerKeys>>,
-    pub scope_state_refs: HashMap<String, ScopeStateRefTracker>,
-    pub grant_chains: HashMap<String, GrantChainState>,
-    pub max_scope_state_refs_per_scope: usize,
+pub struct ListContactsResponse {
+    /// Sorted by user id.
+    pub contacts: Vec<Contact>,
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct ScopeStateRefTracker {
-    refs: VecDeque<String>,
-    set: HashSet<String>,
+#[derive(Clone, Debug)]
+pub struct DevicePublicKey {
+    pub device_id: DeviceId,
+    pub signer: SignerKeys,
+    /// Hex fingerprint peers pass as `expected_owner_signer_fingerprint`.
+    pub fingerprint: String,
 }
 
-impl ScopeStateRefTracker {
+#[derive(Clone, Debug)]
+pub struct DevicePublicKeysResponse {
+    pub devices: Vec<DevicePublicKey>,
+}
+
+/// What the unlocked vault and signer roster know about one scope.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScopeSummary {
+    pub scope_id: ScopeId,
+    /// Epochs with a materialized scope key, ascending.
+    pub epochs: Vec<ScopeEpoch>,
+    pub has_scope_key: bool,
+    pub trusted_signer_count: usize,
+}
+
+/// An open session as listed by [`KeyService::list_sessions`]; no key material.
+#[derive(Clone, Debug)]
+pub struct SessionSummary {
+    pub session_id: SessionId,
+    pub kind: SessionKind,
+    pub assurance: SessionAssurance,
+    pub issued_at_ms: u64,
+    pub expires_at_ms: u64,
+    pub read_only: bool,
+    pub handle_count: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ListScopesResponse {
+    pub scopes: Vec<ScopeSummary>,
+}
+
+/// A scope whose newest key is due for rotation under its [`ScopeRotationPolicy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DueRotation {
+    pub scope_id: ScopeId,
+    pub scope_epoch: ScopeEpoch,
+    /// The limit that was reached first.
+    pub reason: RotationReason,
+    pub due_at_ms: u64,
+    /// `encrypt` under the key fails with `RotationOverdue` after this.
+    pub hard_deadline_ms: u64,
+    /// Encrypt operations counted under the key; may run up to a storage lease ahead.
+    pub operations: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct DueRotationsResponse {
+    /// Ascending by hard deadline.
+    pub rotations: Vec<DueRotation>,
+}
+
+/// KeyVault health report; `ok` only if every check passed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyKeyVaultResponse {
+    pub ok: bool,
+    pub record_count: u64,
+    /// Seq of the last record in the valid chain prefix.
+    pub head_seq: u64,
+    pub first_invalid_seq: Option<u64>,
+    pub problem: Option<String>,
+    /// Records embedded in the header are byte-identical to their stored copies.
+    pub header_records_match: bool,
+    /// The device head marker is still on the stored chain.
+    pub head_marker_match: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct OpenResourceResponse {
+    pub resource_key_handle: KeyHandle,
+    /// False when the grant id was already ingested for the scope; the handle is still issued,
+    /// but nothing is written to the vault.
+    pub is_new: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReissueGrantResponse {
+    /// Canonical CBOR of the re-signed [`ResourceGrantV1`].
+    pub grant: Vec<u8>,
+    pub grant_seq: u64,
+}
+
+/// What a capability token minted by [`KeyService::mint_capability`] allows.
+#[derive(Clone, Debug)]
+pub struct CapabilityRequest {
+    /// AADs used with the redeemed handle must start with these bytes.
+    pub aad_prefix: Vec<u8>,
+    pub ops: CapabilityOps,
+    pub ttl_ms: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct MintCapabilityResponse {
+    pub token: Vec<u8>,
+    pub expires_at_ms: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct RedeemCapabilityResponse {
+    pub resource_key_handle: KeyHandle,
+    pub ops: CapabilityOps,
+    pub expires_at_ms: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct EncryptResponse {
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DecryptResponse {
+    pub plaintext: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EncryptInitResponse {
+    pub stream_id: StreamId,
+    pub header: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DecryptInitResponse {
+    pub stream_id: StreamId,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportKeyVaultInitResponse {
+    pub stream_id: StreamId,
+}
+
+#[derive(Clone, Debug)]
+pub struct SignResponse {
+    pub signature: Vec<u8>,
+    pub ciphersuite: SigCiphersuiteId,
+}
+
+#[derive(Clone, Debug)]
+pub struct VerifyResponse {
+    pub ok: bool,
+}
+
+#[derive(Debug)]
+pub struct KeyServiceState {
+    pub keyvault_header: KeyVaultHeaderV1,
+    pub keyvault_state: KeyVaultState,
+    pub keyvault_materialized: KeyVaultMaterialized,
+    pub signer_roster: SignerRoster,
+    /// Set by a salvage unlock that found damage, until `repair_keyvault` persists it.
+    pub salvage: Option<KeyVaultSalvageReport>,
+}
+
+impl KeyServiceState {
+    /// Newest epoch of `scope_id` among ingested scope states and stored scope keys; 0 if none.
+    fn latest_epoch(&self, scope_id: &ScopeId) -> u64 {
+        let from_states = self
+            .signer_roster
+            .latest_epochs
+            .get(&scope_id.0)
+            .copied()
+            .unwrap_or(0);
+        self.keyvault_materialized
+            .scope_keys
+            .keys()
+            .filter(|(id, _)| *id == scope_id.0)
+            .map(|(_, epoch)| *epoch)
+            .fold(from_states, u64::max)
+    }
+
+    #[cfg(feature = "paranoid")]
+    fn check_invariants(&self) -> Result<(), &'static str> {
+        self.keyvault_state
+            .check_invariants(&self.keyvault_materialized)?;
+        self.signer_roster.check_invariants()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SignerRoster {
+    pub scopes: HashMap<String, HashMap<String, SignerKeys>>,
+    /// Scope id -> signer device id -> role; includes devices assigned a role before first use.
+    pub roles: HashMap<String, HashMap<String, ScopeRole>>,
+    /// Scope id -> delegate device id -> delegated key.
+    pub delegations: HashMap<String, HashMap<String, DelegatedSigner>>,
+    /// Scope id -> newest decrypt-only epoch; every older epoch is decrypt-only too.
+    pub retired_epochs: HashMap<String, u64>,
+    /// Scope id -> highest epoch among ingested scope states.
+    pub latest_epochs: HashMap<String, u64>,
+    pub scope_state_refs: HashMap<String, ScopeStateRefTracker>,
+    pub grant_chains: HashMap<String, GrantChainState>,
+    /// Scope id -> device id -> signer fingerprint its attestation was bound to.
+    pub attested_devices: HashMap<String, HashMap<String, String>>,
+    pub max_scope_state_refs_per_scope: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScopeStateRefTracker {
+    refs: VecDeque<String>,
+    set: HashSet<String>,
+}
+
+impl ScopeStateRefTracker {
     fn insert(&mut self, scope_state_ref_hex: String, max: usize) {
         if self.set.contains(&scope_state_ref_hex) {
             return;
@@ -222,6 +1507,14 @@ impl ScopeStateRefTracker {
     }
 }
 
+/// Secondary signing key a scope signer authorized through a [`SigningDelegationV1`].
+#[derive(Clone, Debug)]
+pub struct DelegatedSigner {
+    pub keys: SignerKeys,
+    pub delegator_device_id: DeviceId,
+    pub expires_at_ms: u64,
+}
+
 #[derive(Clone, Debug)]
 pub struct GrantChainState {
     pub last_seq: u64,
@@ -232,8 +1525,13 @@ impl SignerRoster {
     fn new(max_scope_state_refs_per_scope: usize) -> Self {
         Self {
             scopes: HashMap::new(),
+            roles: HashMap::new(),
+            delegations: HashMap::new(),
+            retired_epochs: HashMap::new(),
+            latest_epochs: HashMap::new(),
             scope_state_refs: HashMap::new(),
             grant_chains: HashMap::new(),
+            attested_devices: HashMap::new(),
             max_scope_state_refs_per_scope,
         }
     }
@@ -249,6 +1547,100 @@ impl SignerRoster {
         scope.insert(device_id.0.clone(), signer);
     }
 
+    /// A scope signer's own key, else the key of an unexpired delegate.
+    fn resolve_signer(
+        &self,
+        scope_id: &ScopeId,
+        device_id: &DeviceId,
+        now: u64,
+    ) -> Result<&SignerKeys, KeyServiceError> {
+        if let Some(signer) = self.get_signer(scope_id, device_id) {
+            return Ok(signer);
+        }
+        let delegate = self
+            .delegations
+            .get(&scope_id.0)
+            .and_then(|scope| scope.get(&device_id.0))
+            .ok_or(KeyServiceError::UntrustedSigner)?;
+        if now > delegate.expires_at_ms {
+            return Err(KeyServiceError::DelegationExpired);
+        }
+        Ok(&delegate.keys)
+    }
+
+    /// Names the first roster invariant that no longer holds.
+    #[cfg(feature = "paranoid")]
+    fn check_invariants(&self) -> Result<(), &'static str> {
+        for (scope_id, signers) in &self.scopes {
+            let roles = self.roles.get(scope_id);
+            if signers
+                .keys()
+                .any(|device_id| roles.is_none_or(|roles| !roles.contains_key(device_id)))
+            {
+                return Err("scope signer without a role");
+            }
+        }
+        for (scope_id, delegates) in &self.delegations {
+            let signers = self.scopes.get(scope_id);
+            if delegates.values().any(|delegate| {
+                signers.is_none_or(|signers| !signers.contains_key(&delegate.delegator_device_id.0))
+            }) {
+                return Err("delegation from a device that is not a scope signer");
+            }
+        }
+        if self
+            .scope_state_refs
+            .values()
+            .any(|tracker| tracker.refs.len() > self.max_scope_state_refs_per_scope)
+        {
+            return Err("scope state refs over the per-scope limit");
+        }
+        for (scope_id, retired) in &self.retired_epochs {
+            if self
+                .latest_epochs
+                .get(scope_id)
+                .is_none_or(|latest| retired >= latest)
+            {
+                return Err("retired epoch not older than the latest epoch");
+            }
+        }
+        Ok(())
+    }
+
+    fn retire_through(&mut self, scope_id: &ScopeId, scope_epoch: u64) {
+        let retired = self.retired_epochs.entry(scope_id.0.clone()).or_default();
+        *retired = (*retired).max(scope_epoch);
+    }
+
+    /// Fails with `EpochRetired` when `scope` names an epoch an epoch bump retired.
+    fn ensure_epoch_active(
+        &self,
+        scope: Option<&(ScopeId, ScopeEpoch)>,
+    ) -> Result<(), KeyServiceError> {
+        let Some((scope_id, scope_epoch)) = scope else {
+            return Ok(());
+        };
+        match self.retired_epochs.get(&scope_id.0) {
+            Some(retired) if scope_epoch.0 <= *retired => Err(KeyServiceError::EpochRetired {
+                scope_id: scope_id.clone(),
+                scope_epoch: *scope_epoch,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn role(&self, scope_id: &ScopeId, device_id: &DeviceId) -> Option<ScopeRole> {
+        self.roles
+            .get(&scope_id.0)
+            .and_then(|scope| scope.get(&device_id.0))
+            .copied()
+    }
+
+    fn set_role(&mut self, scope_id: &ScopeId, device_id: &DeviceId, role: ScopeRole) {
+        let scope = self.roles.entry(scope_id.0.clone()).or_default();
+        scope.insert(device_id.0.clone(), role);
+    }
+
     fn insert_scope_state_ref(&mut self, scope_id: &ScopeId, scope_state_ref_hex: String) {
         let tracker = self.scope_state_refs.entry(scope_id.0.clone()).or_default();
         tracker.insert(scope_state_ref_hex, self.max_scope_state_refs_per_scope);
@@ -261,6 +1653,43 @@ impl SignerRoster {
             .unwrap_or(false)
     }
 
+    /// Most recently ingested scope state ref of `scope_id`.
+    fn latest_scope_state_ref(&self, scope_id: &ScopeId) -> Option<&str> {
+        self.scope_state_refs
+            .get(&scope_id.0)
+            .and_then(|tracker| tracker.refs.back())
+            .map(String::as_str)
+    }
+
+    /// `(grant_seq, prev_hash)` for the next grant of `scope_id`.
+    fn next_grant_link(&self, scope_id: &ScopeId) -> (u64, [u8; 32]) {
+        match self.grant_chains.get(&scope_id.0) {
+            Some(existing) => (existing.last_seq + 1, existing.last_hash),
+            None => (0, [0u8; 32]),
+        }
+    }
+
+    /// Takes a replay of a grant that was verified when first seen. The chain lives only in
+    /// memory, so after unlock a replay re-links it; a replay at or behind the head leaves it
+    /// as is, and one past the head must link like a new grant.
+    fn relink_grant_chain(&mut self, grant: &ResourceGrantV1) -> Result<(), KeyServiceError> {
+        match self.grant_chains.get(&grant.scope_id.0) {
+            Some(existing) if grant.grant_seq <= existing.last_seq => Ok(()),
+            Some(_) => self.verify_and_update_grant_chain(grant),
+            None => {
+                let grant_hash = grant.grant_ref_bytes().map_err(KeyServiceError::from)?;
+                self.grant_chains.insert(
+                    grant.scope_id.0.clone(),
+                    GrantChainState {
+                        last_seq: grant.grant_seq,
+                        last_hash: hash_array(&grant_hash)?,
+                    },
+                );
+                Ok(())
+            }
+        }
+    }
+
     fn verify_and_update_grant_chain(
         &mut self,
         grant: &ResourceGrantV1,
@@ -272,21 +1701,21 @@ impl SignerRoster {
         match state {
             Some(existing) => {
                 if grant.grant_seq != existing.last_seq + 1 {
-                    return Err(KeyServiceError::InvalidFormat(
-                        "grant sequence out of order".to_string(),
-                    ));
+                    return Err(KeyServiceError::GrantChainBroken {
+                        scope_id: grant.scope_id.clone(),
+                    });
                 }
-                if prev_hash != existing.last_hash {
-                    return Err(KeyServiceError::InvalidFormat(
-                        "grant prev_hash mismatch".to_string(),
-                    ));
+                if !ct_eq(&prev_hash, &existing.last_hash) {
+                    return Err(KeyServiceError::GrantChainBroken {
+                        scope_id: grant.scope_id.clone(),
+                    });
                 }
             }
             None => {
-                if grant.grant_seq != 0 || prev_hash != [0u8; 32] {
-                    return Err(KeyServiceError::InvalidFormat(
-                        "grant genesis mismatch".to_string(),
-                    ));
+                if grant.grant_seq != 0 || !ct_eq(&prev_hash, &[0u8; 32]) {
+                    return Err(KeyServiceError::GrantChainBroken {
+                        scope_id: grant.scope_id.clone(),
+                    });
                 }
             }
         }
@@ -314,6 +1743,119 @@ pub struct KeyService<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> {
     config: KeyServiceConfig,
     sessions: SessionManager,
     state: Option<KeyServiceState>,
+    device_anchor: Option<Box<dyn ErasedDeviceAnchor>>,
+    kms: Option<Box<dyn ErasedKms>>,
+    monotonic: Option<Box<dyn ErasedMonotonicCounter>>,
+    /// Counter value written into a head marker whose storage write is still buffered; the
+    /// counter is advanced only once the write is durable (see `defer_counter_advance`).
+    pending_counter_advance: Option<(String, u64)>,
+    defer_counter_advance: bool,
+    metrics: Option<Box<dyn MetricsAdapter>>,
+    logger: Option<Box<dyn LogAdapter>>,
+    vault_events: Option<Box<dyn VaultEventsAdapter>>,
+    session_events: Option<Box<dyn SessionEventsAdapter>>,
+    timer: Option<Box<dyn TimerAdapter>>,
+    /// Wake-up currently scheduled on `timer`, in clock time.
+    timer_deadline: Option<u64>,
+    recent_errors: VecDeque<RecentError>,
+    /// Set by [`KeyService::invalidate`]; the next session check reloads from storage.
+    stale: bool,
+    /// Latest clock reading a session check accepted.
+    clock_high_water_ms: Option<u64>,
+    transparency: Option<TransparencyLog>,
+    /// Encrypt counts per `(scope_id, scope_epoch)` under a rotation policy.
+    rotation_usage: HashMap<(String, u64), RotationUsage>,
+}
+
+/// Encrypt operations under one scope key; `next` runs ahead of storage by at most a lease.
+struct RotationUsage {
+    next: u64,
+    leased_until: u64,
+    /// When the count reached the policy's `max_operations`.
+    due_at_ms: Option<u64>,
+}
+
+/// A configured transparency log and the newest tree head verified against it.
+struct TransparencyLog {
+    key: TransparencyLogKey,
+    adapter: Box<dyn ErasedTransparency>,
+    trusted_head: Option<SignedTreeHead>,
+}
+
+/// Object-safe view of a [`TransparencyAdapter`]; fetch errors only surface as a reason.
+trait ErasedTransparency: MaybeSend {
+    fn tree_head(&self) -> Option<SignedTreeHead>;
+    fn inclusion_proof(
+        &self,
+        leaf_hash: &[u8; 32],
+        tree_size: u64,
+    ) -> Option<Option<InclusionProof>>;
+    fn consistency_proof(&self, first_size: u64, second_size: u64) -> Option<Vec<[u8; 32]>>;
+}
+
+impl<T: TransparencyAdapter> ErasedTransparency for T {
+    fn tree_head(&self) -> Option<SignedTreeHead> {
+        TransparencyAdapter::tree_head(self).ok()
+    }
+
+    fn inclusion_proof(
+        &self,
+        leaf_hash: &[u8; 32],
+        tree_size: u64,
+    ) -> Option<Option<InclusionProof>> {
+        TransparencyAdapter::inclusion_proof(self, leaf_hash, tree_size).ok()
+    }
+
+    fn consistency_proof(&self, first_size: u64, second_size: u64) -> Option<Vec<[u8; 32]>> {
+        TransparencyAdapter::consistency_proof(self, first_size, second_size).ok()
+    }
+}
+
+/// Object-safe view of a [`DeviceAnchorAdapter`] so the service stays generic over S/C/E only.
+trait ErasedDeviceAnchor: MaybeSend {
+    fn seal(&self, label: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String>;
+    fn unseal(&self, label: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+impl<A: DeviceAnchorAdapter> ErasedDeviceAnchor for A {
+    fn seal(&self, label: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        DeviceAnchorAdapter::seal(self, label, aad, plaintext).map_err(|e| format!("{e:?}"))
+    }
+
+    fn unseal(&self, label: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        DeviceAnchorAdapter::unseal(self, label, aad, ciphertext).map_err(|e| format!("{e:?}"))
+    }
+}
+
+/// Object-safe view of a [`KmsAdapter`].
+trait ErasedKms: MaybeSend {
+    fn wrap(&self, key_id: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String>;
+    fn unwrap(&self, key_id: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+impl<K: KmsAdapter> ErasedKms for K {
+    fn wrap(&self, key_id: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        KmsAdapter::wrap(self, key_id, aad, plaintext).map_err(|e| format!("{e:?}"))
+    }
+
+    fn unwrap(&self, key_id: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        KmsAdapter::unwrap(self, key_id, aad, ciphertext).map_err(|e| format!("{e:?}"))
+    }
+}
+
+trait ErasedMonotonicCounter: MaybeSend {
+    fn read(&self, name: &str) -> Result<u64, String>;
+    fn advance(&self, name: &str, value: u64) -> Result<(), String>;
+}
+
+impl<M: MonotonicCounterAdapter> ErasedMonotonicCounter for M {
+    fn read(&self, name: &str) -> Result<u64, String> {
+        MonotonicCounterAdapter::read(self, name).map_err(|e| format!("{e:?}"))
+    }
+
+    fn advance(&self, name: &str, value: u64) -> Result<(), String> {
+        MonotonicCounterAdapter::advance(self, name, value).map_err(|e| format!("{e:?}"))
+    }
 }
 
 impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E> {
@@ -325,7 +1867,99 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
             config,
             sessions: SessionManager::new(),
             state: None,
+            device_anchor: None,
+            kms: None,
+            monotonic: None,
+            pending_counter_advance: None,
+            defer_counter_advance: false,
+            metrics: None,
+            logger: None,
+            vault_events: None,
+            session_events: None,
+            timer: None,
+            timer_deadline: None,
+            recent_errors: VecDeque::new(),
+            stale: false,
+            clock_high_water_ms: None,
+            transparency: None,
+            rotation_usage: HashMap::new(),
+        }
+    }
+
+    /// Seals the anti-rollback head marker with a device-bound anchor.
+    pub fn set_device_anchor<A: DeviceAnchorAdapter + 'static>(&mut self, anchor: A) {
+        self.device_anchor = Some(Box::new(anchor));
+    }
+
+    /// Remote KMS for vaults in KMS mode; see [`Self::enable_kms_wrap`].
+    pub fn set_kms_adapter<K: KmsAdapter + 'static>(&mut self, kms: K) {
+        self.kms = Some(Box::new(kms));
+    }
+
+    /// Binds the head marker to a counter outside vault storage. Each marker write advances
+    /// it, and unlock fails with [`KeyServiceError::RollbackDetected`] when the stored marker
+    /// is missing or older than the counter, so restoring old storage together with its
+    /// marker is caught. Without one, only the marker's authenticity is checked.
+    pub fn set_monotonic_counter<M: MonotonicCounterAdapter + 'static>(&mut self, counter: M) {
+        self.monotonic = Some(Box::new(counter));
+    }
+
+    /// Holds counter advances until [`Self::commit_counter_advance`], for callers that buffer
+    /// storage writes and must not advance the counter past a marker that is not yet durable.
+    pub(crate) fn defer_counter_advance(&mut self) {
+        self.defer_counter_advance = true;
+    }
+
+    /// Advances the counter to the value in the last buffered marker, once it is durable.
+    pub(crate) fn commit_counter_advance(&mut self) -> Result<(), KeyServiceError> {
+        let (Some(counter), Some((name, value))) =
+            (&self.monotonic, self.pending_counter_advance.take())
+        else {
+            return Ok(());
+        };
+        counter
+            .advance(&name, value)
+            .map_err(KeyServiceError::StorageError)
+    }
+
+    /// Reports counts and durations for unlock, KDF, record apply, sign/verify, and encrypt/decrypt.
+    pub fn set_metrics_adapter<M: MetricsAdapter + 'static>(&mut self, metrics: M) {
+        self.metrics = Some(Box::new(metrics));
+    }
+
+    pub fn set_log_adapter<L: LogAdapter + 'static>(&mut self, logger: L) {
+        self.logger = Some(Box::new(logger));
+    }
+
+    pub fn set_vault_events_adapter<V: VaultEventsAdapter + 'static>(&mut self, events: V) {
+        self.vault_events = Some(Box::new(events));
+    }
+
+    pub fn set_session_events_adapter<V: SessionEventsAdapter + 'static>(&mut self, events: V) {
+        self.session_events = Some(Box::new(events));
+    }
+
+    /// Checks scope states and user public keys against the log signed by `key` before they
+    /// are trusted. Replaces any earlier log along with the tree head trusted for it.
+    pub fn set_transparency_adapter<T: TransparencyAdapter + 'static>(
+        &mut self,
+        key: TransparencyLogKey,
+        adapter: T,
+    ) {
+        self.transparency = Some(TransparencyLog {
+            key,
+            adapter: Box::new(adapter),
+            trusted_head: None,
+        });
+    }
+
+    /// Schedules wake-ups for session expiry and expiry warnings; see [`Self::on_timer`].
+    pub fn set_timer_adapter<T: TimerAdapter + 'static>(&mut self, timer: T) {
+        if let Some(previous) = self.timer.replace(Box::new(timer)) {
+            previous.cancel();
         }
+        self.timer_deadline = None;
+        self.schedule_timer();
     }
 
     pub fn create_new_vault(
@@ -333,29 +1967,62 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         user_id: UserId,
         passphrase_utf8: &[u8],
         kdf_params: crate::crypto::KdfParams,
+    ) -> Result<(), KeyServiceError> {
+        self.enforce_passphrase_policy(passphrase_utf8)?;
+        self.create_vault_with_secret(user_id, passphrase_utf8, kdf_params)
+    }
+
+    /// Begins OPAQUE registration; send the returned request to the server. The password is
+    /// checked against `min_passphrase_score` here since the vault only sees the export key.
+    pub fn start_opaque_registration(
+        &self,
+        password_utf8: &[u8],
+    ) -> Result<(OpaqueRegistration, Vec<u8>), KeyServiceError> {
+        self.enforce_passphrase_policy(password_utf8)?;
+        Ok(OpaqueRegistration::start(password_utf8, &self.entropy)?)
+    }
+
+    /// Finishes OPAQUE registration with the server's response and creates a vault whose KEK
+    /// is derived from the export key. Returns the record to upload to the server.
+    pub fn create_new_vault_opaque(
+        &mut self,
+        user_id: UserId,
+        registration: OpaqueRegistration,
+        response: &[u8],
+        kdf_params: crate::crypto::KdfParams,
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        let finish = registration.finish(response, &self.config.policy.opaque, &self.entropy)?;
+        self.create_vault_with_secret(user_id, &finish.export_key, kdf_params)?;
+        Ok(finish.record)
+    }
+
+    fn create_vault_with_secret(
+        &mut self,
+        user_id: UserId,
+        passphrase_utf8: &[u8],
+        kdf_params: crate::crypto::KdfParams,
     ) -> Result<(), KeyServiceError> {
         let vault_id = uuid_like(&self.entropy.random_bytes(16));
-        let kek = derive_kek(passphrase_utf8, &kdf_params)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let vault_key = self.entropy.random_bytes(32);
+        self.check_ids([("userId", user_id.0.as_str())])?;
+        self.config.policy.kdf_minimums.check(&kdf_params)?;
+        let kek = self.run_kdf(passphrase_utf8, &kdf_params, None)?;
+        let vault_key = Zeroizing::new(self.entropy.random_bytes(32));
         let aad = aad_keyvault_keywrap_v1(&vault_id, &user_id.0, &kdf_params, AeadId::Aead1)?;
-        let nonce = self.entropy.random_bytes(12);
-        let ct = aead_encrypt::<Aes256Gcm>(&kek, &aad, &vault_key, &nonce)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        let vault_key_wrap = self.seal_key_wrap(&kek, &aad, &vault_key)?;
 
-        let header = KeyVaultHeaderV1 {
+        let mut header = KeyVaultHeaderV1 {
             v: 1,
             vault_id,
             user_id: user_id.0.clone(),
             kdf: kdf_params.clone(),
             aead: AeadId::Aead1,
             records: Vec::new(),
-            vault_key_wrap: crate::formats::VaultKeyWrapV1 {
-                aead: AeadId::Aead1,
-                nonce,
-                ct,
-            },
+            vault_key_wrap,
+            kms_key_id: None,
+            device_shares: None,
+            passphrase_check: None,
         };
+        header.passphrase_check = Some(self.seal_passphrase_check(&header, &kek)?);
 
         let header_bytes = encode_keyvault_header_v1(&header)
             .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
@@ -363,11 +2030,13 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         self.storage
             .put("keyvault", "header", &header_bytes)
             .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        self.emit_vault_event(VaultEvent::HeaderUpdated);
 
         self.storage
             .put("keyvault", "record_index", &[])
             .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
 
+        self.log(|| LogEvent::new(LogLevel::Info, "vault.created").with("user_id", &user_id.0));
         Ok(())
     }
 
@@ -375,47 +2044,354 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         &mut self,
         passphrase_utf8: &[u8],
     ) -> Result<UnlockResponse, KeyServiceError> {
-        let header = self.load_header()?;
-        let kek = derive_kek(passphrase_utf8, &header.kdf)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let aad =
-            aad_keyvault_keywrap_v1(&header.vault_id, &header.user_id, &header.kdf, header.aead)?;
-        let vault_key = aead_decrypt::<Aes256Gcm>(
-            &kek,
-            &aad,
-            &header.vault_key_wrap.nonce,
-            &header.vault_key_wrap.ct,
+        self.unlock_passphrase_with(passphrase_utf8, false, &CancellationToken::new(), None)
+    }
+
+    /// Like [`Self::unlock_passphrase`], calling `on_pass(done, total)` after each KDF pass so
+    /// the host can show determinate progress. The callback must not call back into the service.
+    pub fn unlock_passphrase_with_progress(
+        &mut self,
+        passphrase_utf8: &[u8],
+        on_pass: &mut dyn FnMut(u32, u32),
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.unlock_passphrase_with(
+            passphrase_utf8,
+            false,
+            &CancellationToken::new(),
+            Some(on_pass),
         )
-        .map_err(|_| KeyServiceError::CryptoError("vault key unwrap failed".to_string()))?;
+    }
+
+    /// Like [`Self::unlock_passphrase`], checked for cancellation around the KDF and before
+    /// each record while loading the vault. The KDF itself runs to completion once started.
+    pub fn unlock_passphrase_cancellable(
+        &mut self,
+        passphrase_utf8: &[u8],
+        cancel: &CancellationToken,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.unlock_passphrase_with(passphrase_utf8, false, cancel, None)
+    }
+
+    /// Like [`Self::unlock_passphrase`], but the session can only read the vault
+    /// (see [`KeyServiceError::ReadOnlySession`]).
+    pub fn unlock_passphrase_read_only(
+        &mut self,
+        passphrase_utf8: &[u8],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.unlock_passphrase_with(passphrase_utf8, true, &CancellationToken::new(), None)
+    }
+
+    /// Whether `passphrase_utf8` opens the stored vault key wrap. Reads only the header: no
+    /// session is issued, loaded state is left alone, and a KMS-mode vault makes no KMS call.
+    /// A wrap that the passphrase check says is damaged still fails as
+    /// [`KeyServiceError::VaultCorrupted`].
+    pub fn verify_passphrase(&self, passphrase_utf8: &[u8]) -> Result<bool, KeyServiceError> {
+        let header = self.load_header()?;
+        let kek = self.run_kdf(passphrase_utf8, &header.kdf, None)?;
+        match unwrap_vault_key(&header, &kek) {
+            Ok(_) => Ok(true),
+            Err(KeyServiceError::WrongPassphrase) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Begins an OPAQUE login; send the returned KE1 to the server.
+    pub fn start_opaque_login(
+        &self,
+        password_utf8: &[u8],
+    ) -> Result<(OpaqueLogin, Vec<u8>), KeyServiceError> {
+        Ok(OpaqueLogin::start(password_utf8, &self.entropy)?)
+    }
+
+    /// Finishes an OPAQUE login with the server's KE2 and unlocks with the export key. A wrong
+    /// password fails as [`KeyServiceError::WrongPassphrase`] before any KDF runs; KE3 must
+    /// still be sent so the server can count the attempt as successful.
+    pub fn unlock_opaque(
+        &mut self,
+        login: OpaqueLogin,
+        ke2: &[u8],
+    ) -> Result<OpaqueUnlockResponse, KeyServiceError> {
+        let finish = login.finish(ke2, &self.config.policy.opaque)?;
+        let unlock = self.unlock_passphrase_with(
+            &finish.export_key,
+            false,
+            &CancellationToken::new(),
+            None,
+        )?;
+        Ok(OpaqueUnlockResponse {
+            unlock,
+            ke3: finish.ke3,
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip_all))]
+    fn unlock_passphrase_with(
+        &mut self,
+        passphrase_utf8: &[u8],
+        read_only: bool,
+        cancel: &CancellationToken,
+        on_pass: Option<&mut dyn FnMut(u32, u32)>,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.unlock_passphrase_inner(passphrase_utf8, read_only, cancel, on_pass);
+        self.metrics_finish(MetricOp::Unlock, started, result.is_ok());
+        self.note_error(MetricOp::Unlock, &result);
+        self.log_unlock(&result, "passphrase");
+        result
+    }
+
+    fn unlock_passphrase_inner(
+        &mut self,
+        passphrase_utf8: &[u8],
+        read_only: bool,
+        cancel: &CancellationToken,
+        on_pass: Option<&mut dyn FnMut(u32, u32)>,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let header = self.load_header()?;
+        cancel.check()?;
+        let kek = self.run_kdf(passphrase_utf8, &header.kdf, on_pass)?;
+        cancel.check()?;
+        let vault_key = self.open_vault_key_wrap(&header, &kek)?;
         self.finish_unlock(
             header,
             vault_key,
             SessionAssurance::Passphrase,
             SessionKind::Normal,
+            read_only,
+            cancel,
         )
     }
 
+    /// Unlocks a keyvault whose record log is damaged. The longest valid prefix is applied in
+    /// memory and the log is truncated at the break; later records are reported. While damage
+    /// is pending the session is read-only and nothing is written back.
+    /// [`Self::repair_keyvault`] persists the result. An intact log unlocks as usual.
+    ///
+    /// The head marker is checked either way: a stored log that does not reach the last sealed
+    /// head is [`KeyServiceError::RollbackDetected`], and a salvaged chain that ends before it
+    /// is reported in `sealed_head_seq`.
+    pub fn unlock_with_salvage(
+        &mut self,
+        passphrase_utf8: &[u8],
+    ) -> Result<SalvageUnlockResponse, KeyServiceError> {
+        self.unlock_with_salvage_with(passphrase_utf8, false)
+    }
+
+    /// Like [`Self::unlock_with_salvage`], but every record after the break that still
+    /// decrypts is renumbered onto the valid prefix instead of being dropped.
+    pub fn unlock_with_salvage_rechaining(
+        &mut self,
+        passphrase_utf8: &[u8],
+    ) -> Result<SalvageUnlockResponse, KeyServiceError> {
+        self.unlock_with_salvage_with(passphrase_utf8, true)
+    }
+
+    fn unlock_with_salvage_with(
+        &mut self,
+        passphrase_utf8: &[u8],
+        rechain: bool,
+    ) -> Result<SalvageUnlockResponse, KeyServiceError> {
+        let header = self.load_header()?;
+        let kek = self.run_kdf(passphrase_utf8, &header.kdf, None)?;
+        let vault_key = self.open_vault_key_wrap(&header, &kek)?;
+        let (containers, damaged) = self.load_record_containers_salvaging()?;
+        let salvage =
+            KeyVaultState::salvage_containers(&header, &vault_key, &containers, damaged, rechain)
+                .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        let mut report = salvage.report;
+        let damaged = report.break_seq.is_some();
+        let seen_head = self.load_head_marker(&header, &vault_key)?;
+        if damaged {
+            report.sealed_head_seq =
+                check_salvage_not_rolled_back(&salvage.state, &report, &containers, seen_head)?;
+        } else {
+            check_not_rolled_back(&salvage.state, seen_head.as_ref())?;
+            if seen_head.as_ref().map(|(seq, _)| *seq) != Some(salvage.state.head_seq) {
+                self.store_head_marker(&header, &salvage.state, &vault_key)?;
+            }
+        }
+        let state = KeyServiceState {
+            keyvault_header: header,
+            keyvault_state: salvage.state,
+            keyvault_materialized: salvage.materialized,
+            signer_roster: SignerRoster::new(self.config.policy.max_scope_state_refs_per_scope),
+            salvage: damaged.then(|| report.clone()),
+        };
+        let unlock = self.open_session(
+            state,
+            vault_key,
+            SessionAssurance::Passphrase,
+            SessionKind::Normal,
+            damaged,
+        )?;
+        Ok(SalvageUnlockResponse { unlock, report })
+    }
+
+    /// Persists a pending salvage: rewrites the renumbered records, drops damaged and
+    /// truncated ones from the record index, and moves the head marker and keyvault index to
+    /// the new head. Needs a step-up on a salvage session; the session is writable afterwards.
+    /// Dropped record bytes stay in storage but are no longer referenced. The head marker is
+    /// re-checked first, so a marker or counter that moved since the salvage unlock fails with
+    /// [`KeyServiceError::RollbackDetected`].
+    pub fn repair_keyvault(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<KeyVaultSalvageReport, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::RepairKeyVault)?;
+        if self.config.policy.read_only_sessions {
+            return Err(KeyServiceError::ReadOnlySession);
+        }
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let report = state.salvage.clone().ok_or_else(|| {
+            KeyServiceError::InvalidFormat("keyvault has no pending salvage".to_string())
+        })?;
+        let seen_head =
+            self.load_head_marker(&state.keyvault_header, self.session_vault_key(session_id)?)?;
+        let known_seq = report
+            .sealed_head_seq
+            .unwrap_or(0)
+            .max(state.keyvault_state.head_seq);
+        if seen_head.is_some_and(|(seq, _)| seq > known_seq) {
+            return Err(KeyServiceError::RollbackDetected);
+        }
+        for container in &state.keyvault_state.records {
+            if report.rechained_record_ids.contains(&container.record_id) {
+                let bytes = encode_keyvault_record_container_v1(container)
+                    .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+                self.storage
+                    .put(
+                        "keyvault",
+                        &format!("record:{}", container.record_id),
+                        &bytes,
+                    )
+                    .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+            }
+        }
+        let record_ids: Vec<String> = state
+            .keyvault_state
+            .records
+            .iter()
+            .map(|container| container.record_id.clone())
+            .collect();
+        self.store_record_index(&record_ids)?;
+        let header = state.keyvault_header.clone();
+        let (head_seq, head_hash) = (
+            state.keyvault_state.head_seq,
+            state.keyvault_state.head_hash.clone(),
+        );
+        let vault_key = Zeroizing::new(self.session_vault_key(session_id)?.to_vec());
+        self.store_head_marker_at(&header, head_seq, &head_hash, &vault_key)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        self.store_keyvault_index(
+            &state.keyvault_header,
+            &state.keyvault_materialized.index,
+            &vault_key,
+        )?;
+
+        if let Some(state) = self.state.as_mut() {
+            state.salvage = None;
+        }
+        if let Some(session) = self.sessions.get_mut(session_id) {
+            session.read_only = false;
+        }
+        self.record_audit(
+            AuditEventKind::RepairKeyVault,
+            format!(
+                "rechained={} dropped={}",
+                report.rechained_record_ids.len(),
+                report.damaged.len()
+            ),
+        )?;
+        Ok(report)
+    }
+
     pub fn unlock_user_presence(
         &mut self,
         user_presence_secret: &[u8],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.unlock_user_presence_with(user_presence_secret, SessionAssurance::UserPresence, false)
+    }
+
+    /// Like [`Self::unlock_user_presence`], but the session can only read the vault.
+    pub fn unlock_user_presence_read_only(
+        &mut self,
+        user_presence_secret: &[u8],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        self.unlock_user_presence_with(user_presence_secret, SessionAssurance::UserPresence, true)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip_all))]
+    fn unlock_user_presence_with(
+        &mut self,
+        user_presence_secret: &[u8],
+        assurance: SessionAssurance,
+        read_only: bool,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.unlock_user_presence_inner(user_presence_secret, assurance, read_only);
+        self.metrics_finish(MetricOp::Unlock, started, result.is_ok());
+        self.note_error(MetricOp::Unlock, &result);
+        self.log_unlock(&result, assurance_label(assurance));
+        result
+    }
+
+    /// Unlocks with both the passphrase and the user-presence secret, issuing a
+    /// [`SessionAssurance::MultiFactor`] session. Both wraps must open to the same `K_vault`
+    /// ([`KeyServiceError::VaultKeyMismatch`]), and user-presence unlock must be enrolled.
+    pub fn unlock_multi_factor(
+        &mut self,
+        passphrase_utf8: &[u8],
+        user_presence_secret: &[u8],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.unlock_multi_factor_inner(passphrase_utf8, user_presence_secret);
+        self.metrics_finish(MetricOp::Unlock, started, result.is_ok());
+        self.note_error(MetricOp::Unlock, &result);
+        self.log_unlock(&result, "multi-factor");
+        result
+    }
+
+    fn unlock_multi_factor_inner(
+        &mut self,
+        passphrase_utf8: &[u8],
+        user_presence_secret: &[u8],
     ) -> Result<UnlockResponse, KeyServiceError> {
         let header = self.load_header()?;
-        let prf_key = hkdf_sha256(
-            user_presence_secret,
-            b"mo-user-presence|unwrap-k-vault|v1",
-            32,
+        let prf_info = self.load_user_presence_unlock()?;
+        let presence_key = open_user_presence_wrap(&header, &prf_info, user_presence_secret)?;
+        let kek = self.run_kdf(passphrase_utf8, &header.kdf, None)?;
+        let vault_key = self.open_vault_key_wrap(&header, &kek)?;
+        if !ct_eq(&vault_key, &presence_key) {
+            return Err(KeyServiceError::VaultKeyMismatch);
+        }
+        self.finish_unlock(
+            header,
+            vault_key,
+            SessionAssurance::MultiFactor,
+            SessionKind::Normal,
+            false,
+            &CancellationToken::new(),
         )
-        .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let aad =
-            aad_user_presence_wrap_v1(&header.vault_id, &header.user_id, &header.kdf, header.aead)?;
+    }
+
+    fn unlock_user_presence_inner(
+        &mut self,
+        user_presence_secret: &[u8],
+        assurance: SessionAssurance,
+        read_only: bool,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let header = self.load_header()?;
         let prf_info = self.load_user_presence_unlock()?;
-        let vault_key = aead_decrypt::<Aes256Gcm>(&prf_key, &aad, &prf_info.nonce, &prf_info.ct)
-            .map_err(|_| KeyServiceError::CryptoError("vault key unwrap failed".to_string()))?;
+        let vault_key = open_user_presence_wrap(&header, &prf_info, user_presence_secret)?;
         self.finish_unlock(
             header,
             vault_key,
-            SessionAssurance::UserPresence,
+            assurance,
             SessionKind::Normal,
+            read_only,
+            &CancellationToken::new(),
         )
     }
 
@@ -426,37 +2402,75 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
     ) -> Result<StepUpResponse, KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let header = self.load_header()?;
-        let kek = derive_kek(passphrase_utf8, &header.kdf)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let aad =
-            aad_keyvault_keywrap_v1(&header.vault_id, &header.user_id, &header.kdf, header.aead)?;
-        let vault_key = aead_decrypt::<Aes256Gcm>(
-            &kek,
-            &aad,
-            &header.vault_key_wrap.nonce,
-            &header.vault_key_wrap.ct,
-        )
-        .map_err(|_| KeyServiceError::CryptoError("vault key unwrap failed".to_string()))?;
+        // A valid session means the loaded header is current; a wrong passphrase fails
+        // without touching storage.
+        let header = self
+            .state
+            .as_ref()
+            .ok_or(KeyServiceError::VaultNotLoaded)?
+            .keyvault_header
+            .clone();
+        let kek = self.run_kdf(passphrase_utf8, &header.kdf, None)?;
+        let vault_key = self.open_vault_key_wrap(&header, &kek)?;
+        self.finish_step_up(now, session_id, &vault_key, SessionAssurance::Passphrase)
+    }
+
+    /// Like [`Self::step_up`], re-proving the user-presence secret as well; the step-up
+    /// session is [`SessionAssurance::MultiFactor`]. Factors proven at unlock do not carry
+    /// over, so this is the only way to a multi-factor step-up session.
+    pub fn step_up_multi_factor(
+        &mut self,
+        session_id: &SessionId,
+        passphrase_utf8: &[u8],
+        user_presence_secret: &[u8],
+    ) -> Result<StepUpResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let header = self
+            .state
+            .as_ref()
+            .ok_or(KeyServiceError::VaultNotLoaded)?
+            .keyvault_header
+            .clone();
+        let prf_info = self.load_user_presence_unlock()?;
+        let presence_key = open_user_presence_wrap(&header, &prf_info, user_presence_secret)?;
+        let kek = self.run_kdf(passphrase_utf8, &header.kdf, None)?;
+        let vault_key = self.open_vault_key_wrap(&header, &kek)?;
+        if !ct_eq(&vault_key, &presence_key) {
+            return Err(KeyServiceError::VaultKeyMismatch);
+        }
+        self.finish_step_up(now, session_id, &vault_key, SessionAssurance::MultiFactor)
+    }
+
+    /// Turns `session_id` into a step-up session with `assurance`, the factors just proven.
+    fn finish_step_up(
+        &mut self,
+        now: u64,
+        session_id: &SessionId,
+        vault_key: &[u8],
+        assurance: SessionAssurance,
+    ) -> Result<StepUpResponse, KeyServiceError> {
+        let expires_at_ms = session_expiry(now, self.config.policy.step_up_session_ttl_ms)?;
         let session = self
             .sessions
             .get_mut(session_id)
             .ok_or(KeyServiceError::SessionInvalid)?;
-        if vault_key != session.vault_key {
-            return Err(KeyServiceError::CryptoError(
-                "vault key mismatch".to_string(),
-            ));
+        if !ct_eq(vault_key, &session.vault_key) {
+            return Err(KeyServiceError::VaultKeyMismatch);
         }
 
         session.kind = SessionKind::StepUp;
-        session.assurance = SessionAssurance::Passphrase;
+        session.assurance = assurance;
         session.issued_at_ms = now;
-        session.expires_at_ms = now + self.config.policy.step_up_session_ttl_ms;
-
-        Ok(StepUpResponse {
+        session.expires_at_ms = expires_at_ms;
+        let response = StepUpResponse {
             issued_at_ms: session.issued_at_ms,
             expires_at_ms: session.expires_at_ms,
-        })
+        };
+        self.schedule_timer();
+
+        self.record_audit(AuditEventKind::StepUp, String::new())?;
+        Ok(response)
     }
 
     pub fn renew_session(
@@ -465,6 +2479,7 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
     ) -> Result<RenewSessionResponse, KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
+        let expires_at_ms = session_expiry(now, self.config.policy.normal_session_ttl_ms)?;
         let session = self
             .sessions
             .get_mut(session_id)
@@ -472,12 +2487,18 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         if session.kind == SessionKind::StepUp {
             return Err(KeyServiceError::StepUpRequired);
         }
+        // Renewing proves no factor, so a stronger unlock's assurance does not outlive its TTL.
+        if !SessionAssurance::Passphrase.satisfies(session.assurance) {
+            session.assurance = SessionAssurance::Passphrase;
+        }
         session.issued_at_ms = now;
-        session.expires_at_ms = now + self.config.policy.normal_session_ttl_ms;
-        Ok(RenewSessionResponse {
+        session.expires_at_ms = expires_at_ms;
+        let response = RenewSessionResponse {
             issued_at_ms: session.issued_at_ms,
             expires_at_ms: session.expires_at_ms,
-        })
+        };
+        self.schedule_timer();
+        Ok(response)
     }
 
     pub fn lock(&mut self, session_id: &SessionId) -> Result<(), KeyServiceError> {
@@ -488,116 +2509,688 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         session.clear();
         self.sessions.remove(session_id);
         self.state = None;
+        self.log(|| LogEvent::new(LogLevel::Info, "session.locked").with_session(session_id));
+        self.emit_session_event(SessionEvent::Locked {
+            session_id: session_id.clone(),
+        });
+        self.schedule_timer();
         Ok(())
     }
 
-    pub fn export_keyvault(&mut self, session_id: &SessionId) -> Result<Vec<u8>, KeyServiceError> {
+    /// Locks every session at once, wiping its handles, streams, and vault key, and drops the
+    /// loaded vault state. Sends [`SessionEvent::Locked`] per session; returns how many there were.
+    pub fn lock_all(&mut self) -> usize {
+        let locked = self.sessions.remove_all();
+        self.state = None;
+        for session_id in &locked {
+            self.log(|| LogEvent::new(LogLevel::Info, "session.locked").with_session(session_id));
+            self.emit_session_event(SessionEvent::Locked {
+                session_id: session_id.clone(),
+            });
+        }
+        self.schedule_timer();
+        locked.len()
+    }
+
+    /// Unexpired sessions, oldest first. Expired ones are dropped first, as by
+    /// [`Self::sweep_expired_sessions`].
+    pub fn list_sessions(&mut self) -> Vec<SessionSummary> {
+        self.sweep_expired_sessions();
+        let mut sessions: Vec<SessionSummary> = self
+            .sessions
+            .iter()
+            .map(|session| SessionSummary {
+                session_id: session.session_id.clone(),
+                kind: session.kind,
+                assurance: session.assurance,
+                issued_at_ms: session.issued_at_ms,
+                expires_at_ms: session.expires_at_ms,
+                read_only: session.read_only,
+                handle_count: session.handle_count(),
+            })
+            .collect();
+        sessions.sort_by(|a, b| {
+            (a.issued_at_ms, &a.session_id.0).cmp(&(b.issued_at_ms, &b.session_id.0))
+        });
+        sessions
+    }
+
+    /// Drops expired sessions now instead of on their next use; returns how many were removed.
+    /// Loaded vault state is dropped once no session is left.
+    pub fn sweep_expired_sessions(&mut self) -> usize {
+        let expired = self.drop_expired_sessions();
+        self.schedule_timer();
+        expired
+    }
+
+    /// Called by the host when the [`TimerAdapter`] fires: locks expired sessions, sends
+    /// [`SessionEvent::ExpiringSoon`] for sessions close to expiry, and schedules the next wake-up.
+    /// Early or spurious calls are harmless.
+    pub fn on_timer(&mut self) {
+        self.timer_deadline = None;
+        self.drop_expired_sessions();
         let now = self.clock.now_ms();
-        self.ensure_session_valid(now, session_id)?;
-        let kind = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            session.kind
-        };
-        if kind != SessionKind::StepUp {
-            return Err(KeyServiceError::StepUpRequired);
+        let warning_ms = self.config.policy.session_expiry_warning_ms;
+        for (session_id, expires_at_ms) in self.sessions.take_expiring(now, warning_ms) {
+            self.emit_session_event(SessionEvent::ExpiringSoon {
+                session_id,
+                expires_at_ms,
+            });
         }
-        let header = self.load_header()?;
-        let records = self.load_all_record_containers()?;
-        let snapshot = KeyVaultSnapshotV1 { header, records };
-        encode_keyvault_snapshot_v1(&snapshot)
-            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))
+        self.schedule_timer();
     }
 
-    pub fn import_keyvault(
+    fn drop_expired_sessions(&mut self) -> usize {
+        let now = self.clock.now_ms();
+        let expired = self.sessions.sweep_expired(now);
+        for session_id in &expired {
+            self.log(|| LogEvent::new(LogLevel::Info, "session.expired").with_session(session_id));
+            self.emit_session_event(SessionEvent::Expired {
+                session_id: session_id.clone(),
+            });
+        }
+        if !expired.is_empty() && self.sessions.is_empty() {
+            self.state = None;
+        }
+        expired.len()
+    }
+
+    /// Marks loaded vault state as possibly outdated because another instance wrote the shared
+    /// storage. The next session-checked call reloads it; see [`Self::reload_from_storage`].
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Re-reads the header and record chain now. Fails with `RollbackDetected` if storage is
+    /// behind or diverges from the loaded chain, and `VaultKeyMismatch` if the vault was replaced.
+    pub fn reload_from_storage(
         &mut self,
         session_id: &SessionId,
-        blob: &[u8],
-    ) -> Result<(), KeyServiceError> {
+    ) -> Result<ReloadResponse, KeyServiceError> {
         let now = self.clock.now_ms();
+        self.stale = false;
         self.ensure_session_valid(now, session_id)?;
-        let kind = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            session.kind
-        };
-        if kind != SessionKind::StepUp {
-            return Err(KeyServiceError::StepUpRequired);
-        }
-        let limits = self.cbor_limits();
-        let value = decode_canonical_value(blob, &limits)
-            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
-        let snapshot = KeyVaultSnapshotV1::from_cbor(value)
-            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.reload_state(session_id)
+    }
 
-        let header_bytes = encode_keyvault_header_v1(&snapshot.header)
-            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
-        self.storage
-            .put("keyvault", "header", &header_bytes)
-            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+    pub fn export_keyvault(&mut self, session_id: &SessionId) -> Result<Vec<u8>, KeyServiceError> {
+        self.export_keyvault_as(session_id, None, None)
+    }
 
-        let mut index = Vec::new();
-        for record in &snapshot.records {
-            let bytes = encode_keyvault_record_container_v1(record)
-                .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
-            let key = format!("record:{}", record.record_id);
-            self.storage
-                .put("keyvault", &key, &bytes)
-                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
-            index.push(record.record_id.clone());
+    /// Exports only the records of the given kinds, renumbered into a chain of their own so the
+    /// snapshot imports like a full one. Checkpoints cannot be selected, since they pin the
+    /// original chain.
+    pub fn export_keyvault_filtered(
+        &mut self,
+        session_id: &SessionId,
+        kinds: &[u64],
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        if kinds.is_empty() {
+            return Err(KeyServiceError::InvalidFormat(
+                "no record kinds selected".to_string(),
+            ));
         }
-        let index_value = cbor_array(index.iter().map(|id| cbor_text(id)).collect());
-        let index_bytes = encode_canonical_value(&index_value)
-            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
-        self.storage
-            .put("keyvault", "record_index", &index_bytes)
-            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        if kinds.contains(&CHECKPOINT_RECORD_KIND) {
+            return Err(KeyServiceError::InvalidFormat(
+                "checkpoint records cannot be exported selectively".to_string(),
+            ));
+        }
+        self.export_keyvault_as(session_id, None, Some(kinds))
+    }
 
-        Ok(())
+    /// Exports a v2 snapshot whose record section is compressed. [`Self::import_keyvault`]
+    /// accepts both versions.
+    pub fn export_keyvault_compressed(
+        &mut self,
+        session_id: &SessionId,
+        compression: SnapshotCompression,
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        self.export_keyvault_as(session_id, Some(compression), None)
     }
 
-    pub fn change_passphrase(
+    /// Streams the bytes of [`Self::export_keyvault`] to `sink` in chunks of `chunk_len` (the
+    /// last one may be shorter), encoding one record at a time. An error from `sink` stops the
+    /// export and is returned as-is.
+    pub fn export_keyvault_chunked(
         &mut self,
         session_id: &SessionId,
-        new_passphrase_utf8: &[u8],
+        chunk_len: usize,
+        sink: &mut dyn FnMut(&[u8]) -> Result<(), KeyServiceError>,
     ) -> Result<(), KeyServiceError> {
-        let mut header = self.load_header()?;
-        let now = self.clock.now_ms();
-        self.ensure_session_valid(now, session_id)?;
-        let vault_key = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            if session.kind != SessionKind::StepUp {
-                return Err(KeyServiceError::StepUpRequired);
+        if chunk_len == 0 {
+            return Err(KeyServiceError::InvalidFormat(
+                "chunk length must be positive".to_string(),
+            ));
+        }
+        let snapshot = self.export_snapshot(session_id, None)?;
+        let mut chunk = Vec::with_capacity(chunk_len);
+        write_keyvault_snapshot_v1::<KeyServiceError>(&snapshot, &mut |mut bytes: &[u8]| {
+            while !bytes.is_empty() {
+                let take = bytes.len().min(chunk_len - chunk.len());
+                chunk.extend_from_slice(&bytes[..take]);
+                bytes = &bytes[take..];
+                if chunk.len() == chunk_len {
+                    sink(&chunk)?;
+                    chunk.clear();
+                }
             }
-            session.vault_key.clone()
+            Ok(())
+        })?;
+        if !chunk.is_empty() {
+            sink(&chunk)?;
+        }
+        self.record_audit(
+            AuditEventKind::ExportKeyVault,
+            format!("records={} chunked", snapshot.records.len()),
+        )?;
+        Ok(())
+    }
+
+    fn export_keyvault_as(
+        &mut self,
+        session_id: &SessionId,
+        compression: Option<SnapshotCompression>,
+        kinds: Option<&[u64]>,
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        let snapshot = self.export_snapshot(session_id, kinds)?;
+        let record_count = snapshot.records.len();
+        let blob = match compression {
+            None => encode_keyvault_snapshot_v1(&snapshot)
+                .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?,
+            Some(compression) => encode_keyvault_snapshot_v2(&snapshot, compression)
+                .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?,
         };
-        let new_kdf = crate::crypto::KdfParams::new_random()
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let kek = derive_kek(new_passphrase_utf8, &new_kdf)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let aad =
-            aad_keyvault_keywrap_v1(&header.vault_id, &header.user_id, &new_kdf, header.aead)?;
-        let nonce = self.entropy.random_bytes(12);
-        let ct = aead_encrypt::<Aes256Gcm>(&kek, &aad, &vault_key, &nonce)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        header.kdf = new_kdf;
-        header.vault_key_wrap = crate::formats::VaultKeyWrapV1 {
-            aead: AeadId::Aead1,
-            nonce,
-            ct,
+        let mut detail = format!("records={record_count}");
+        if let Some(compression) = compression {
+            detail.push_str(&format!(" compression={}", compression.as_str()));
+        }
+        if let Some(kinds) = kinds {
+            let kinds: Vec<String> = kinds.iter().map(u64::to_string).collect();
+            detail.push_str(&format!(" kinds={}", kinds.join(",")));
+        }
+        self.record_audit(AuditEventKind::ExportKeyVault, detail)?;
+        Ok(blob)
+    }
+
+    fn export_snapshot(
+        &mut self,
+        session_id: &SessionId,
+        kinds: Option<&[u64]>,
+    ) -> Result<KeyVaultSnapshotV1, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::ExportKeyVault)?;
+        let header = self.load_header()?;
+        let mut records = self.load_all_record_containers()?;
+        if let Some(kinds) = kinds {
+            let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+            let selected: BTreeSet<u64> = state
+                .keyvault_materialized
+                .record_metadata
+                .iter()
+                .filter(|record| kinds.contains(&record.kind))
+                .map(|record| record.seq)
+                .collect();
+            records.retain(|record| selected.contains(&record.seq));
+            records = rechain_containers(&records)
+                .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+        }
+        let manifest = self.sign_export_manifest(&header, &records)?;
+        Ok(KeyVaultSnapshotV1 {
+            header,
+            records,
+            manifest,
+        })
+    }
+
+    /// Signs what an export contains with the local device key, like audit entries, so it
+    /// works on co-signing devices too. A vault without a device exports no manifest.
+    fn sign_export_manifest(
+        &self,
+        header: &KeyVaultHeaderV1,
+        records: &[KeyVaultRecordContainerV1],
+    ) -> Result<Option<KeyVaultManifestV1>, KeyServiceError> {
+        let Ok((device_id, signing)) = self.local_signing_key() else {
+            return Ok(None);
+        };
+        let mut manifest = KeyVaultManifestV1 {
+            v: 1,
+            vault_id: header.vault_id.clone(),
+            header_hash: sha256(&encode_keyvault_header_v1(header)?).to_vec(),
+            record_count: records.len() as u64,
+            head_hash: snapshot_head_hash(records)?,
+            created_at_ms: self.clock.now_ms(),
+            signer_device_id: device_id,
+            signer: SignerKeys {
+                sig_suite: SigCiphersuiteId::HybridSig1,
+                ed25519_pub: signing.ed25519_pub.clone(),
+                mldsa_pub: signing.mldsa_pub.clone(),
+            },
+            signature: Vec::new(),
+        };
+        manifest.signature = hybrid_sign(
+            SIG_KEYVAULT_MANIFEST_V1,
+            &manifest.to_be_signed_bytes()?,
+            signing,
+        )
+        .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        Ok(Some(manifest))
+    }
+
+    /// Checks an imported snapshot against its manifest before anything is written.
+    fn verify_export_manifest(&self, snapshot: &KeyVaultSnapshotV1) -> Result<(), KeyServiceError> {
+        let require = self.config.policy.require_export_manifest;
+        let Some(manifest) = &snapshot.manifest else {
+            if require {
+                return Err(KeyServiceError::ExportManifestInvalid {
+                    reason: "snapshot has no manifest",
+                });
+            }
+            return Ok(());
+        };
+        let invalid = |reason| Err(KeyServiceError::ExportManifestInvalid { reason });
+        if !hybrid_verify(
+            SIG_KEYVAULT_MANIFEST_V1,
+            &manifest.to_be_signed_bytes()?,
+            &manifest.signature,
+            &manifest.signer,
+        ) {
+            return invalid("manifest signature invalid");
+        }
+        if manifest.vault_id != snapshot.header.vault_id {
+            return invalid("manifest names another vault");
+        }
+        let header_hash = sha256(&encode_keyvault_header_v1(&snapshot.header)?);
+        if !ct_eq(&manifest.header_hash, &header_hash) {
+            return invalid("header does not match the manifest");
+        }
+        if manifest.record_count != snapshot.records.len() as u64 {
+            return invalid("record count does not match the manifest");
+        }
+        if !ct_eq(&manifest.head_hash, &snapshot_head_hash(&snapshot.records)?) {
+            return invalid("chain head does not match the manifest");
+        }
+        // The signer travels in the manifest, so its signature alone proves nothing about
+        // who exported the snapshot.
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let known = state
+            .keyvault_materialized
+            .device_signing_keys
+            .get(&manifest.signer_device_id.0)
+            .is_some_and(|keypair| {
+                ct_eq(&keypair.ed25519_pub, &manifest.signer.ed25519_pub)
+                    && ct_eq(&keypair.mldsa_pub, &manifest.signer.mldsa_pub)
+            });
+        if !known {
+            return Err(KeyServiceError::UntrustedSigner);
+        }
+        Ok(())
+    }
+
+    pub fn import_keyvault(
+        &mut self,
+        session_id: &SessionId,
+        blob: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        self.import_keyvault_cancellable(session_id, blob, &CancellationToken::new())
+    }
+
+    /// Like [`Self::import_keyvault`], checked for cancellation before each record. Records are
+    /// encoded up front, so a cancelled import leaves storage untouched.
+    pub fn import_keyvault_cancellable(
+        &mut self,
+        session_id: &SessionId,
+        blob: &[u8],
+        cancel: &CancellationToken,
+    ) -> Result<(), KeyServiceError> {
+        self.ensure_import_allowed(session_id)?;
+        let limits = self.cbor_limits();
+        let value = decode_canonical_value(blob, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("keyvault_snapshot")(e).to_string())
+        })?;
+        let snapshot = decode_keyvault_snapshot(value, &limits)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        import_limits_ok(
+            &self.config.policy,
+            snapshot.records.len() as u64,
+            &snapshot.records,
+        )?;
+        self.store_imported_snapshot(session_id, &snapshot, cancel)
+    }
+
+    /// Starts an incremental import of a v1 snapshot fed through [`Self::import_keyvault_push`].
+    /// The CBOR size limit applies to the header and to each record rather than the whole
+    /// blob, so vaults larger than one buffer can be restored. Storage is only written by
+    /// [`Self::import_keyvault_finish`]; locking the session drops a pending import.
+    pub fn import_keyvault_init(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<ImportKeyVaultInitResponse, KeyServiceError> {
+        self.ensure_import_allowed(session_id)?;
+        let decoder = KeyVaultSnapshotDecoder::new(self.cbor_limits());
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let stream_id = session.insert_stream(StreamEntry::Import(Box::new(decoder)))?;
+        Ok(ImportKeyVaultInitResponse { stream_id })
+    }
+
+    /// Feeds the next piece of the snapshot; pieces may split items anywhere. Record limits are
+    /// checked as records complete, and any error discards the import.
+    pub fn import_keyvault_push(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let policy = self.config.policy.clone();
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let result = match session.get_stream_mut(stream_id) {
+            Some(StreamEntry::Import(decoder)) => {
+                let before = decoder.records().len();
+                decoder
+                    .push(chunk)
+                    .map_err(KeyServiceError::from)
+                    .and_then(|()| {
+                        import_limits_ok(
+                            &policy,
+                            decoder.declared_records().unwrap_or(0),
+                            &decoder.records()[before..],
+                        )
+                    })
+            }
+            _ => return Err(KeyServiceError::UnknownStream),
+        };
+        if result.is_err() {
+            session.remove_stream(stream_id);
+        }
+        result
+    }
+
+    /// Checks that the snapshot is complete and writes it like [`Self::import_keyvault`].
+    pub fn import_keyvault_finish(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+    ) -> Result<(), KeyServiceError> {
+        self.ensure_import_allowed(session_id)?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        if !matches!(
+            session.get_stream_mut(stream_id),
+            Some(StreamEntry::Import(_))
+        ) {
+            return Err(KeyServiceError::UnknownStream);
+        }
+        let snapshot = match session.remove_stream(stream_id) {
+            Some(StreamEntry::Import(decoder)) => decoder.finish()?,
+            _ => return Err(KeyServiceError::UnknownStream),
+        };
+        self.store_imported_snapshot(session_id, &snapshot, &CancellationToken::new())
+    }
+
+    fn ensure_import_allowed(&mut self, session_id: &SessionId) -> Result<(), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::ImportKeyVault)?;
+        self.ensure_writable(session_id)
+    }
+
+    fn store_imported_snapshot(
+        &mut self,
+        session_id: &SessionId,
+        snapshot: &KeyVaultSnapshotV1,
+        cancel: &CancellationToken,
+    ) -> Result<(), KeyServiceError> {
+        self.verify_export_manifest(snapshot)?;
+        self.config
+            .policy
+            .kdf_minimums
+            .check(&snapshot.header.kdf)?;
+        self.check_ids(
+            [
+                ("vaultId", snapshot.header.vault_id.as_str()),
+                ("userId", &snapshot.header.user_id),
+            ]
+            .into_iter()
+            .chain(
+                snapshot
+                    .records
+                    .iter()
+                    .map(|record| ("recordId", record.record_id.as_str())),
+            ),
+        )?;
+        let header_bytes = encode_keyvault_header_v1(&snapshot.header)
+            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+        let mut encoded = Vec::with_capacity(snapshot.records.len());
+        for record in &snapshot.records {
+            cancel.check()?;
+            let bytes = encode_keyvault_record_container_v1(record)
+                .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+            encoded.push((record.record_id.clone(), bytes));
+        }
+        cancel.check()?;
+
+        self.storage
+            .put("keyvault", "header", &header_bytes)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        self.emit_vault_event(VaultEvent::HeaderUpdated);
+
+        let imported_head = snapshot.records.last().zip(encoded.last()).map_or_else(
+            || (0, vec![0u8; 32]),
+            |(record, (_, bytes))| (record.seq, sha256(bytes).to_vec()),
+        );
+        let mut index = Vec::new();
+        for (record_id, bytes) in encoded {
+            let key = format!("record:{record_id}");
+            self.storage
+                .put("keyvault", &key, &bytes)
+                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+            index.push(record_id);
+        }
+        let index_value = cbor_array(index.iter().map(|id| cbor_text(id)).collect());
+        let index_bytes = encode_canonical_value(&index_value)
+            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+        self.storage
+            .put("keyvault", "record_index", &index_bytes)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        // An explicit, step-up authorised restore re-anchors the rollback marker: a backup of
+        // the loaded vault is sealed as the new head now, so the counter stays ahead of any
+        // older copy; another vault's marker is written on its next unlock.
+        let same_vault = self
+            .state
+            .as_ref()
+            .is_some_and(|state| state.keyvault_header.vault_id == snapshot.header.vault_id);
+        if same_vault {
+            let vault_key = Zeroizing::new(self.session_vault_key(session_id)?.to_vec());
+            let (head_seq, head_hash) = imported_head;
+            self.store_head_marker_at(&snapshot.header, head_seq, &head_hash, &vault_key)?;
+        } else {
+            self.storage
+                .put("keyvault", "head_marker", &[])
+                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        }
+
+        self.record_audit(
+            AuditEventKind::ImportKeyVault,
+            format!("records={}", index.len()),
+        )?;
+        Ok(())
+    }
+
+    /// Re-wraps `K_vault` under a new passphrase and fresh KDF parameters. The user-presence
+    /// wrap is bound to the KDF parameters and cannot be re-sealed without its secret, so an
+    /// enrolled user-presence unlock is removed; the response says so. Use
+    /// [`Self::change_passphrase_keeping_user_presence`] to carry it over instead.
+    pub fn change_passphrase(
+        &mut self,
+        session_id: &SessionId,
+        new_passphrase_utf8: &[u8],
+    ) -> Result<ChangePassphraseResponse, KeyServiceError> {
+        self.change_passphrase_with(session_id, new_passphrase_utf8, None)
+    }
+
+    /// Like [`Self::change_passphrase`], re-sealing the enrolled user-presence wrap for the new
+    /// KDF parameters. `user_presence_secret` must open the current wrap
+    /// ([`KeyServiceError::VaultKeyUnwrapFailed`]), and user-presence unlock must be enrolled
+    /// ([`KeyServiceError::UserPresenceNotEnabled`]).
+    pub fn change_passphrase_keeping_user_presence(
+        &mut self,
+        session_id: &SessionId,
+        new_passphrase_utf8: &[u8],
+        user_presence_secret: &[u8],
+    ) -> Result<ChangePassphraseResponse, KeyServiceError> {
+        self.change_passphrase_with(session_id, new_passphrase_utf8, Some(user_presence_secret))
+    }
+
+    fn change_passphrase_with(
+        &mut self,
+        session_id: &SessionId,
+        new_passphrase_utf8: &[u8],
+        user_presence_secret: Option<&[u8]>,
+    ) -> Result<ChangePassphraseResponse, KeyServiceError> {
+        let mut header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::ChangePassphrase)?;
+        self.ensure_writable(session_id)?;
+        self.enforce_passphrase_policy(new_passphrase_utf8)?;
+        let new_kdf = next_kdf_params(&header.kdf, &self.config.policy.kdf_minimums)?;
+        let kek = self.run_kdf(new_passphrase_utf8, &new_kdf, None)?;
+        let aad =
+            aad_keyvault_keywrap_v1(&header.vault_id, &header.user_id, &new_kdf, header.aead)?;
+        header.vault_key_wrap =
+            self.seal_vault_key_wrap(&header, &kek, &aad, self.session_vault_key(session_id)?)?;
+        let old_kdf = std::mem::replace(&mut header.kdf, new_kdf);
+        header.passphrase_check = Some(self.seal_passphrase_check(&header, &kek)?);
+        let header_bytes = encode_keyvault_header_v1(&header)
+            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+
+        // Replace or clear the user-presence wrap before the header, and put it back if the
+        // header write fails, so no stored wrap is ever bound to KDF parameters it cannot match.
+        let enrolled = self.enrolled_user_presence_unlock()?;
+        let user_presence_bytes = match (&enrolled, user_presence_secret) {
+            (None, None) => None,
+            (None, Some(_)) => return Err(KeyServiceError::UserPresenceNotEnabled),
+            (Some(_), None) => Some(Vec::new()),
+            (Some(info), Some(secret)) => {
+                let old_header = KeyVaultHeaderV1 {
+                    kdf: old_kdf,
+                    ..header.clone()
+                };
+                let vault_key = open_user_presence_wrap(&old_header, info, secret)?;
+                if !ct_eq(&vault_key, self.session_vault_key(session_id)?) {
+                    return Err(KeyServiceError::VaultKeyMismatch);
+                }
+                let resealed = self.seal_user_presence_wrap(
+                    &header,
+                    info.credential_id.clone(),
+                    secret,
+                    &vault_key,
+                )?;
+                Some(resealed.encode().map_err(KeyServiceError::from)?)
+            }
+        };
+        let previous_user_presence = match &user_presence_bytes {
+            Some(bytes) => {
+                let previous = self
+                    .storage
+                    .get("keyvault", "user_presence")
+                    .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+                    .unwrap_or_default();
+                self.storage
+                    .put("keyvault", "user_presence", bytes)
+                    .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+                Some(previous)
+            }
+            None => None,
+        };
+        if let Err(e) = self.storage.put("keyvault", "header", &header_bytes) {
+            if let Some(previous) = previous_user_presence {
+                let _ = self.storage.put("keyvault", "user_presence", &previous);
+            }
+            return Err(KeyServiceError::StorageError(format!("{e:?}")));
+        }
+        self.emit_vault_event(VaultEvent::HeaderUpdated);
+        if let Some(state) = self.state.as_mut() {
+            state.keyvault_header = header;
+        }
+        let user_presence_disabled = enrolled.is_some() && user_presence_secret.is_none();
+        let detail = match (enrolled.is_some(), user_presence_disabled) {
+            (false, _) => String::new(),
+            (true, true) => "userPresence=disabled".to_string(),
+            (true, false) => "userPresence=kept".to_string(),
         };
+        self.record_audit(AuditEventKind::ChangePassphrase, detail)?;
+        Ok(ChangePassphraseResponse {
+            user_presence_disabled,
+        })
+    }
+
+    /// Moves the vault to KMS mode: `K_vault` is wrapped by `kms_key_id` on the KMS adapter,
+    /// and that ciphertext by the passphrase KEK, so every later unlock needs both the
+    /// passphrase and a KMS call. Needs a step-up session and the current passphrase, and
+    /// refuses while user-presence unlock is enrolled. There is no way back short of export.
+    pub fn enable_kms_wrap(
+        &mut self,
+        session_id: &SessionId,
+        passphrase_utf8: &[u8],
+        kms_key_id: &str,
+    ) -> Result<(), KeyServiceError> {
+        self.check_ids([("kmsKeyId", kms_key_id)])?;
+        let mut header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::EnableKmsWrap)?;
+        self.ensure_writable(session_id)?;
+        if header.kms_key_id.is_some() {
+            return Err(KeyServiceError::KmsWrapRejected {
+                reason: "vault already in KMS mode",
+            });
+        }
+        if self.load_user_presence_unlock().is_ok() {
+            return Err(KeyServiceError::KmsWrapRejected {
+                reason: USER_PRESENCE_BYPASSES_KMS,
+            });
+        }
+        if header.device_shares.is_some() {
+            return Err(KeyServiceError::KmsWrapRejected {
+                reason: DEVICE_SHARES_BYPASS_KMS,
+            });
+        }
+        let kek = self.run_kdf(passphrase_utf8, &header.kdf, None)?;
+        let vault_key = unwrap_vault_key(&header, &kek)?;
+        if !ct_eq(&vault_key, self.session_vault_key(session_id)?) {
+            return Err(KeyServiceError::VaultKeyMismatch);
+        }
+        let aad =
+            aad_keyvault_keywrap_v1(&header.vault_id, &header.user_id, &header.kdf, header.aead)?;
+        header.kms_key_id = Some(kms_key_id.to_string());
+        header.vault_key_wrap = self.seal_vault_key_wrap(&header, &kek, &aad, &vault_key)?;
+        // Refuse to persist a wrap the KMS will not open again.
+        if !ct_eq(&self.open_vault_key_wrap(&header, &kek)?, &vault_key) {
+            return Err(KeyServiceError::KmsWrapRejected {
+                reason: "kms unwrap did not return the vault key",
+            });
+        }
         let header_bytes = encode_keyvault_header_v1(&header)
             .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
         self.storage
             .put("keyvault", "header", &header_bytes)
             .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        self.emit_vault_event(VaultEvent::HeaderUpdated);
+        if let Some(state) = self.state.as_mut() {
+            state.keyvault_header = header;
+        }
+        self.record_audit(AuditEventKind::EnableKmsWrap, format!("keyId={kms_key_id}"))?;
         Ok(())
     }
 
@@ -608,7 +3201,7 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         let prf = self.load_user_presence_unlock().ok();
         let prf_salt = sha256_bytes(
             &[
-                b"mo-user-presence|salt-v1",
+                USER_PRESENCE_SALT_V1,
                 header.vault_id.as_bytes(),
                 header.user_id.as_bytes(),
             ]
@@ -622,6 +3215,93 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         })
     }
 
+    /// Safe before unlock: reads only the header, record index, and user-presence flag.
+    pub fn get_vault_info(&mut self) -> Result<VaultInfoResponse, KeyServiceError> {
+        let header = self.load_header()?;
+        let record_count = self.load_all_record_containers()?.len() as u64;
+        let user_presence_enabled = self.load_user_presence_unlock().is_ok();
+        Ok(VaultInfoResponse {
+            vault_id: header.vault_id,
+            user_id: UserId(header.user_id),
+            kdf: header.kdf,
+            aead: header.aead,
+            record_count,
+            user_presence_enabled,
+            kms_key_id: header.kms_key_id,
+            device_share_threshold: header.device_shares.map(|config| config.threshold),
+        })
+    }
+
+    /// Probes the storage, entropy, and clock adapters; needs no vault or session.
+    pub fn health_check(&self) -> HealthCheckResponse {
+        let mut problems = Vec::new();
+
+        let probe = self.entropy.random_bytes(16);
+        let storage_ok = match self
+            .storage
+            .put(HEALTH_NAMESPACE, HEALTH_PROBE_KEY, &probe)
+            .and_then(|_| self.storage.get(HEALTH_NAMESPACE, HEALTH_PROBE_KEY))
+        {
+            Ok(Some(read)) if read == probe => true,
+            Ok(_) => {
+                problems.push("storage: probe read back differently".to_string());
+                false
+            }
+            Err(e) => {
+                problems.push(format!("storage: {e:?}"));
+                false
+            }
+        };
+
+        let first = self.entropy.random_bytes(32);
+        let second = self.entropy.random_bytes(32);
+        let entropy_ok = if first.len() != 32 || second.len() != 32 {
+            problems.push("entropy: wrong output length".to_string());
+            false
+        } else if first.iter().all(|b| *b == 0) || first == second {
+            problems.push("entropy: output is all-zero or repeating".to_string());
+            false
+        } else {
+            true
+        };
+
+        let t0 = self.clock.now_ms();
+        let t1 = self.clock.now_ms();
+        let clock_ok = if t0 == 0 {
+            problems.push("clock: reads zero".to_string());
+            false
+        } else if t1 < t0 {
+            problems.push("clock: went backwards".to_string());
+            false
+        } else {
+            true
+        };
+
+        HealthCheckResponse {
+            ok: problems.is_empty(),
+            storage_ok,
+            entropy_ok,
+            clock_ok,
+            problems,
+        }
+    }
+
+    /// Redacted vault structure, chain head, policy, and recent error codes for bug reports.
+    /// Reads no key material and needs no session; ids and hashes are replaced by tags.
+    pub fn export_diagnostics(&self) -> DiagnosticsReport {
+        let mut problems = Vec::new();
+        let vault = self.diagnose_vault(&mut problems);
+        DiagnosticsReport {
+            generated_at_ms: self.clock.now_ms(),
+            vault,
+            unlocked: self.state.is_some(),
+            session_count: self.sessions.len() as u64,
+            policy: self.config.policy.clone(),
+            recent_errors: self.recent_errors.iter().copied().collect(),
+            problems,
+        }
+    }
+
     pub fn enable_user_presence_unlock(
         &mut self,
         session_id: &SessionId,
@@ -631,32 +3311,18 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         let header = self.load_header()?;
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let vault_key = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            if session.kind != SessionKind::StepUp {
-                return Err(KeyServiceError::StepUpRequired);
-            }
-            session.vault_key.clone()
-        };
-        let prf_key = hkdf_sha256(
-            &user_presence_secret,
-            b"mo-user-presence|unwrap-k-vault|v1",
-            32,
-        )
-        .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let aad =
-            aad_user_presence_wrap_v1(&header.vault_id, &header.user_id, &header.kdf, header.aead)?;
-        let nonce = self.entropy.random_bytes(12);
-        let ct = aead_encrypt::<Aes256Gcm>(&prf_key, &aad, &vault_key, &nonce)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let info = UserPresenceUnlockV1 {
+        self.ensure_step_up(session_id, SensitiveOperation::UserPresenceUnlock)?;
+        if header.kms_key_id.is_some() {
+            return Err(KeyServiceError::KmsWrapRejected {
+                reason: USER_PRESENCE_BYPASSES_KMS,
+            });
+        }
+        let info = self.seal_user_presence_wrap(
+            &header,
             credential_id,
-            nonce,
-            ct,
-        };
+            &user_presence_secret,
+            self.session_vault_key(session_id)?,
+        )?;
         let bytes = info.encode().map_err(KeyServiceError::from)?;
         self.storage
             .put("keyvault", "user_presence", &bytes)
@@ -670,41 +3336,369 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
     ) -> Result<(), KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let kind = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            session.kind
-        };
-        if kind != SessionKind::StepUp {
-            return Err(KeyServiceError::StepUpRequired);
-        }
+        self.ensure_step_up(session_id, SensitiveOperation::UserPresenceUnlock)?;
         self.storage
             .put("keyvault", "user_presence", &[])
             .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
         Ok(())
     }
 
-    pub fn ingest_scope_state(
+    /// Splits `K_vault` so that shares from any `threshold` of `device_ids` unlock the vault;
+    /// see [`crate::device_shares`]. Each listed device then runs
+    /// [`Self::enroll_device_share`]. Needs a step-up session and replaces any earlier split,
+    /// whose shares stop working. The passphrase still unlocks.
+    pub fn enable_device_share_unlock(
         &mut self,
         session_id: &SessionId,
-        scope_state_cbor: &[u8],
-        expected_owner_signer_fingerprint: Option<String>,
-    ) -> Result<IngestScopeStateResponse, KeyServiceError> {
+        threshold: u64,
+        device_ids: Vec<DeviceId>,
+    ) -> Result<(), KeyServiceError> {
+        self.check_ids(device_ids.iter().map(|d| ("deviceId", d.0.as_str())))?;
+        let mut header = self.load_header()?;
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-
-        let limits = self.cbor_limits();
-        let value = decode_canonical_value(scope_state_cbor, &limits)
+        self.ensure_step_up(session_id, SensitiveOperation::DeviceShareUnlock)?;
+        self.ensure_writable(session_id)?;
+        if header.kms_key_id.is_some() {
+            return Err(KeyServiceError::DeviceShareRejected {
+                reason: DEVICE_SHARES_BYPASS_KMS,
+            });
+        }
+        if device_ids.len() > MAX_SHARE_DEVICES
+            || (1..device_ids.len()).any(|i| device_ids[..i].contains(&device_ids[i]))
+        {
+            return Err(KeyServiceError::DeviceShareRejected {
+                reason: "device list has duplicates or too many devices",
+            });
+        }
+        if threshold < 2 || threshold > device_ids.len() as u64 {
+            return Err(KeyServiceError::DeviceShareRejected {
+                reason: "threshold out of range",
+            });
+        }
+        let split_id = self.entropy.random_bytes(DEVICE_SHARE_SPLIT_ID_LEN);
+        let key_check = device_share_key_check(self.session_vault_key(session_id)?, &split_id)?;
+        let device_count = device_ids.len();
+        header.device_shares = Some(DeviceShareConfigV1 {
+            split_id,
+            threshold,
+            devices: device_ids,
+            key_check,
+        });
+        let header_bytes = encode_keyvault_header_v1(&header)
             .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
-        let scope_state = ScopeStateV1::from_cbor(value)
-            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.storage
+            .put("keyvault", "header", &header_bytes)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        self.emit_vault_event(VaultEvent::HeaderUpdated);
+        if let Some(state) = self.state.as_mut() {
+            state.keyvault_header = header;
+        }
+        self.record_audit(
+            AuditEventKind::EnableDeviceShares,
+            format!("threshold={threshold} devices={device_count}"),
+        )?;
+        Ok(())
+    }
+
+    /// Removes the device-share split; enrolled shares stop working.
+    pub fn disable_device_share_unlock(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(), KeyServiceError> {
+        let mut header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::DeviceShareUnlock)?;
+        self.ensure_writable(session_id)?;
+        header.device_shares = None;
+        let header_bytes = encode_keyvault_header_v1(&header)
+            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+        self.storage
+            .put("keyvault", "header", &header_bytes)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        self.emit_vault_event(VaultEvent::HeaderUpdated);
+        if let Some(state) = self.state.as_mut() {
+            state.keyvault_header = header;
+        }
+        self.storage
+            .put("keyvault", "device_share", &[])
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        Ok(())
+    }
+
+    /// Seals this device's share of the current split with the device anchor, as
+    /// `device_id`. Needs a step-up session and [`Self::set_device_anchor`].
+    pub fn enroll_device_share(
+        &mut self,
+        session_id: &SessionId,
+        device_id: &DeviceId,
+    ) -> Result<(), KeyServiceError> {
+        self.check_ids([("deviceId", device_id.0.as_str())])?;
+        let header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::DeviceShareUnlock)?;
+        let config = device_share_config(&header)?;
+        let share_index =
+            config
+                .share_index(device_id)
+                .ok_or(KeyServiceError::DeviceShareRejected {
+                    reason: "device is not in the split",
+                })?;
+        let anchor = self
+            .device_anchor
+            .as_ref()
+            .ok_or(KeyServiceError::DeviceShareRejected {
+                reason: "no device anchor",
+            })?;
+        let share = device_share(
+            self.session_vault_key(session_id)?,
+            &config.split_id,
+            config.threshold as u8,
+            share_index as u8,
+        )?;
+        let aad = aad_device_share_seal_v1(
+            &header.vault_id,
+            &config.split_id,
+            &device_id.0,
+            share_index,
+        )?;
+        let sealed = anchor
+            .seal(DEVICE_SHARE_ANCHOR_LABEL, &aad, &share)
+            .map_err(KeyServiceError::CryptoError)?;
+        let slot = DeviceShareSlotV1 {
+            split_id: config.split_id.clone(),
+            device_id: device_id.clone(),
+            share_index,
+            sealed,
+        };
+        self.storage
+            .put("keyvault", "device_share", &slot.encode()?)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        Ok(())
+    }
+
+    /// Starts a device-share unlock on a locked device. Send [`DeviceShareUnlock::request`]
+    /// to peer devices over the pairing channel and pass their replies to
+    /// [`Self::unlock_device_shares`].
+    pub fn begin_device_share_unlock(&mut self) -> Result<DeviceShareUnlock, KeyServiceError> {
+        let header = self.load_header()?;
+        let config = device_share_config(&header)?;
+        let (recipient, _) =
+            generate_user_keypair().map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        let request = DeviceShareRequestV1 {
+            v: 1,
+            vault_id: header.vault_id.clone(),
+            split_id: config.split_id.clone(),
+            request_id: self.entropy.random_bytes(16),
+            kem_public: recipient.public_bytes.clone(),
+        };
+        let request_bytes = encode_device_share_request_v1(&request)?;
+        Ok(DeviceShareUnlock {
+            request,
+            request_bytes,
+            recipient,
+        })
+    }
+
+    /// Peer side of a device-share unlock: unseals this device's share and seals it to the
+    /// requester's one-time key. Needs no session, so a locked device can help. The request
+    /// itself is not authenticated: the host must get it over an authenticated pairing
+    /// channel and confirm it with the user before calling this.
+    pub fn release_device_share(&mut self, request: &[u8]) -> Result<Vec<u8>, KeyServiceError> {
+        let request = decode_device_share_request_v1(request)?;
+        let header = self.load_header()?;
+        let config = device_share_config(&header)?;
+        if request.vault_id != header.vault_id || !ct_eq(&request.split_id, &config.split_id) {
+            return Err(KeyServiceError::DeviceShareRejected {
+                reason: "request is for another split",
+            });
+        }
+        let (slot, share) = self.unseal_device_share(&header, config)?.ok_or(
+            KeyServiceError::DeviceShareRejected {
+                reason: "this device holds no share",
+            },
+        )?;
+        let recipient = decode_user_public_bytes(&request.kem_public)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        let encap = hybrid_kem_encapsulate(&recipient, KemCiphersuiteId::HybridKem1)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        let aad = aad_device_share_transfer_v1(
+            &header.vault_id,
+            &config.split_id,
+            &request.request_id,
+            &slot.device_id.0,
+            slot.share_index,
+        )?;
+        let nonce = self.entropy.random_bytes(12);
+        let ct = aead_encrypt::<Aes256Gcm>(&encap.wrap_key, &aad, &share, &nonce)?;
+        Ok(encode_device_share_response_v1(&DeviceShareResponseV1 {
+            v: 1,
+            request_id: request.request_id,
+            device_id: slot.device_id,
+            share_index: slot.share_index,
+            enc: encap.enc,
+            nonce,
+            ct,
+        })?)
+    }
+
+    /// Unlocks from peer `responses` plus this device's own share, if it holds one. Fails with
+    /// `DeviceShareRejected` unless they come to at least the split's threshold and
+    /// recombine to `K_vault`.
+    pub fn unlock_device_shares(
+        &mut self,
+        unlock: DeviceShareUnlock,
+        responses: &[Vec<u8>],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.unlock_device_shares_inner(&unlock, responses);
+        self.metrics_finish(MetricOp::Unlock, started, result.is_ok());
+        self.note_error(MetricOp::Unlock, &result);
+        self.log_unlock(&result, "device-shares");
+        result
+    }
+
+    fn unlock_device_shares_inner(
+        &mut self,
+        unlock: &DeviceShareUnlock,
+        responses: &[Vec<u8>],
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let header = self.load_header()?;
+        let config = device_share_config(&header)?;
+        let rejected = |reason| KeyServiceError::DeviceShareRejected { reason };
+        if !ct_eq(&unlock.request.split_id, &config.split_id) {
+            return Err(rejected("split changed since the request"));
+        }
+        let mut shares: Vec<(u8, Zeroizing<Vec<u8>>)> = Vec::new();
+        if let Some((slot, share)) = self.unseal_device_share(&header, config)? {
+            shares.push((slot.share_index as u8, share));
+        }
+        for bytes in responses {
+            let response = decode_device_share_response_v1(bytes)?;
+            if !ct_eq(&response.request_id, &unlock.request.request_id) {
+                return Err(rejected("response to another request"));
+            }
+            if config.share_index(&response.device_id) != Some(response.share_index) {
+                return Err(rejected("share index does not match the device"));
+            }
+            let index = response.share_index as u8;
+            if shares.iter().any(|(seen, _)| *seen == index) {
+                continue;
+            }
+            let wrap_key = derive_hybrid_kem_wrap_key(
+                &response.enc,
+                &unlock.recipient,
+                KemCiphersuiteId::HybridKem1,
+            )
+            .map_err(|_| rejected("share does not decrypt"))?;
+            let aad = aad_device_share_transfer_v1(
+                &header.vault_id,
+                &config.split_id,
+                &response.request_id,
+                &response.device_id.0,
+                response.share_index,
+            )?;
+            let share = aead_decrypt::<Aes256Gcm>(&wrap_key, &aad, &response.nonce, &response.ct)
+                .map_err(|_| rejected("share does not decrypt"))?;
+            shares.push((index, Zeroizing::new(share)));
+        }
+        if (shares.len() as u64) < config.threshold {
+            return Err(rejected("not enough shares"));
+        }
+        let parts: Vec<(u8, &[u8])> = shares
+            .iter()
+            .take(config.threshold as usize)
+            .map(|(index, share)| (*index, share.as_slice()))
+            .collect();
+        let vault_key = combine_device_shares(&parts)?;
+        let key_check = device_share_key_check(&vault_key, &config.split_id)?;
+        if !ct_eq(&key_check, &config.key_check) {
+            return Err(rejected("shares do not combine to the vault key"));
+        }
+        self.finish_unlock(
+            header,
+            vault_key,
+            SessionAssurance::DeviceShares,
+            SessionKind::Normal,
+            false,
+            &CancellationToken::new(),
+        )
+    }
+
+    /// Enrolls a security key for user-presence unlock: creates an `hmac-secret` credential
+    /// for `rp_id` and wraps `K_vault` under its PRF output. Needs a step-up session and two
+    /// touches; returns the credential id.
+    #[cfg(feature = "fido2")]
+    pub fn enable_user_presence_unlock_fido2<H: HidAuthenticatorAdapter>(
+        &mut self,
+        session_id: &SessionId,
+        authenticator: &Fido2Authenticator<H>,
+        rp_id: &str,
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::UserPresenceUnlock)?;
+        let header = self.load_header()?;
+        let credential_id =
+            authenticator.make_credential(rp_id, header.user_id.as_bytes(), &self.entropy)?;
+        let prf_salt = self.get_user_presence_unlock_info()?.prf_salt;
+        let secret = authenticator.prf(rp_id, &credential_id, &prf_salt, &self.entropy)?;
+        self.enable_user_presence_unlock(session_id, credential_id.clone(), secret.to_vec())?;
+        Ok(credential_id)
+    }
+
+    /// [`Self::unlock_user_presence`] with the secret from a security key over FIDO2
+    /// `hmac-secret`, using the enrolled credential id and PRF salt. The session is
+    /// [`SessionAssurance::HardwareBacked`].
+    #[cfg(feature = "fido2")]
+    pub fn unlock_user_presence_fido2<H: HidAuthenticatorAdapter>(
+        &mut self,
+        authenticator: &Fido2Authenticator<H>,
+        rp_id: &str,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let info = self.get_user_presence_unlock_info()?;
+        let credential_id = info
+            .credential_id
+            .filter(|_| info.enabled)
+            .ok_or(KeyServiceError::UserPresenceNotEnabled)?;
+        let secret = authenticator.prf(rp_id, &credential_id, &info.prf_salt, &self.entropy)?;
+        self.unlock_user_presence_with(&secret, SessionAssurance::HardwareBacked, false)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bytes = scope_state_cbor.len()))
+    )]
+    pub fn ingest_scope_state(
+        &mut self,
+        session_id: &SessionId,
+        scope_state_cbor: &[u8],
+        expected_owner_signer_fingerprint: Option<String>,
+    ) -> Result<IngestScopeStateResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+
+        let limits = self.cbor_limits();
+        let value = decode_canonical_value(scope_state_cbor, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("scope_state")(e).to_string())
+        })?;
+        let scope_state = ScopeStateV1::from_cbor(value)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
 
         let to_verify = scope_state
             .to_be_signed_bytes()
             .map_err(KeyServiceError::from)?;
-        let payload_signer_keys = extract_signer_keys(&scope_state)?;
+        let payload = check_scope_state_payload(&scope_state)?;
+        self.check_ids(scope_state_ids(&scope_state, &payload))?;
+        self.check_transparency(&TransparencyLeaf::ScopeState {
+            scope_id: scope_state.scope_id.clone(),
+            scope_state_ref: scope_state
+                .scope_state_ref_bytes()
+                .map_err(KeyServiceError::from)?,
+        })?;
+        let payload_signer_keys = payload.signer().clone();
 
         let header = self.load_header()?;
         let roster = self.state.get_or_insert_with(|| KeyServiceState {
@@ -712,45 +3706,164 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
             keyvault_state: KeyVaultState::default(),
             keyvault_materialized: KeyVaultMaterialized::default(),
             signer_roster: SignerRoster::new(self.config.policy.max_scope_state_refs_per_scope),
+            salvage: None,
         });
 
         let existing_signer = roster
             .signer_roster
             .get_signer(&scope_state.scope_id, &scope_state.signer_device_id);
+        let payload_fp = fingerprint_signer(&payload_signer_keys);
+        let mut signer_added = false;
+        let mut warnings = Vec::new();
 
         match existing_signer {
             Some(signer) => {
-                let payload_fp = fingerprint_signer(&payload_signer_keys);
                 let expected_fp = fingerprint_signer(signer);
-                if payload_fp != expected_fp {
+                if !ct_eq(payload_fp.as_bytes(), expected_fp.as_bytes()) {
                     return Err(KeyServiceError::FingerprintMismatch);
                 }
-                if !hybrid_verify(&to_verify, &scope_state.signature, signer) {
-                    return Err(KeyServiceError::CryptoError(
-                        "scope state signature invalid".to_string(),
-                    ));
+                if !hybrid_verify_or_unbound(
+                    SIG_SCOPE_STATE_V1,
+                    &to_verify,
+                    &scope_state.signature,
+                    signer,
+                    self.config.policy.accept_unbound_signatures,
+                ) {
+                    return Err(KeyServiceError::SignatureInvalid {
+                        format: SignedFormat::ScopeState,
+                    });
                 }
             }
             None => {
                 let expected = expected_owner_signer_fingerprint
                     .ok_or(KeyServiceError::SignerFingerprintRequired)?;
-                let payload_fp = fingerprint_signer(&payload_signer_keys);
-                if payload_fp != expected {
+                if !ct_eq(payload_fp.as_bytes(), expected.as_bytes()) {
                     return Err(KeyServiceError::FingerprintMismatch);
                 }
-                if !hybrid_verify(&to_verify, &scope_state.signature, &payload_signer_keys) {
-                    return Err(KeyServiceError::CryptoError(
-                        "scope state signature invalid".to_string(),
-                    ));
+                if !hybrid_verify_or_unbound(
+                    SIG_SCOPE_STATE_V1,
+                    &to_verify,
+                    &scope_state.signature,
+                    &payload_signer_keys,
+                    self.config.policy.accept_unbound_signatures,
+                ) {
+                    return Err(KeyServiceError::SignatureInvalid {
+                        format: SignedFormat::ScopeState,
+                    });
                 }
-                roster.signer_roster.upsert_signer(
-                    &scope_state.scope_id,
-                    &scope_state.signer_device_id,
-                    payload_signer_keys,
-                );
+                if self
+                    .config
+                    .policy
+                    .scope_attestation
+                    .contains_key(&scope_state.scope_id.0)
+                {
+                    let attested = roster
+                        .signer_roster
+                        .attested_devices
+                        .get(&scope_state.scope_id.0)
+                        .and_then(|devices| devices.get(&scope_state.signer_device_id.0))
+                        .is_some_and(|fp| ct_eq(fp.as_bytes(), payload_fp.as_bytes()));
+                    if !attested {
+                        return Err(KeyServiceError::AttestationFailed {
+                            reason: "device not attested for the scope",
+                        });
+                    }
+                }
+                signer_added = true;
+            }
+        }
+
+        // A signer trusted through the caller's owner fingerprint is an owner unless an earlier
+        // membership change assigned it a role.
+        let assigned_role = roster
+            .signer_roster
+            .role(&scope_state.scope_id, &scope_state.signer_device_id);
+        let signer_role = assigned_role.unwrap_or(if signer_added {
+            ScopeRole::Owner
+        } else {
+            ScopeRole::Member
+        });
+        let role_changes = match &payload {
+            ScopeStatePayload::Genesis { .. } => Vec::new(),
+            ScopeStatePayload::EpochBump { .. } | ScopeStatePayload::MembershipChange { .. }
+                if !signer_role.can_administer() =>
+            {
+                return Err(KeyServiceError::ScopeRoleDenied {
+                    scope_id: scope_state.scope_id.clone(),
+                });
             }
+            ScopeStatePayload::EpochBump { .. } => Vec::new(),
+            ScopeStatePayload::MembershipChange { roles, .. } => {
+                // Only owners may grant ownership or change an owner's role.
+                let touches_owner = roles.iter().any(|(device_id, role)| {
+                    *role == ScopeRole::Owner
+                        || roster.signer_roster.role(&scope_state.scope_id, device_id)
+                            == Some(ScopeRole::Owner)
+                });
+                if touches_owner && signer_role != ScopeRole::Owner {
+                    return Err(KeyServiceError::ScopeRoleDenied {
+                        scope_id: scope_state.scope_id.clone(),
+                    });
+                }
+                roles.clone()
+            }
+        };
+
+        if signer_added {
+            warnings.push(IngestWarning::FingerprintPinned {
+                device_id: scope_state.signer_device_id.clone(),
+            });
+            let scope_has_signers = roster
+                .signer_roster
+                .scopes
+                .get(&scope_state.scope_id.0)
+                .is_some_and(|signers| !signers.is_empty());
+            if scope_has_signers && assigned_role.is_none() {
+                warnings.push(IngestWarning::SignerPreviouslyUnseen {
+                    device_id: scope_state.signer_device_id.clone(),
+                });
+            }
+            roster.signer_roster.upsert_signer(
+                &scope_state.scope_id,
+                &scope_state.signer_device_id,
+                payload_signer_keys,
+            );
+            roster.signer_roster.set_role(
+                &scope_state.scope_id,
+                &scope_state.signer_device_id,
+                signer_role,
+            );
+        }
+        for (device_id, role) in &role_changes {
+            roster
+                .signer_roster
+                .set_role(&scope_state.scope_id, device_id, *role);
+        }
+        if let ScopeStatePayload::EpochBump {
+            from_epoch,
+            retirement: EpochRetirement::DecryptOnly,
+            ..
+        } = payload
+        {
+            roster
+                .signer_roster
+                .retire_through(&scope_state.scope_id, from_epoch);
         }
 
+        let latest_epoch = roster
+            .latest_epoch(&scope_state.scope_id)
+            .max(scope_state.scope_epoch);
+        if scope_state.scope_epoch < latest_epoch {
+            warnings.push(IngestWarning::OlderEpoch {
+                scope_epoch: ScopeEpoch(scope_state.scope_epoch),
+                latest_epoch: ScopeEpoch(latest_epoch),
+            });
+        }
+        roster
+            .signer_roster
+            .latest_epochs
+            .insert(scope_state.scope_id.0.clone(), latest_epoch);
+
         let scope_state_ref_bytes = scope_state
             .scope_state_ref_bytes()
             .map_err(KeyServiceError::from)?;
@@ -759,12 +3872,214 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
             .signer_roster
             .insert_scope_state_ref(&scope_state.scope_id, scope_state_ref.clone());
 
+        if signer_added {
+            self.record_audit(
+                AuditEventKind::SignerChange,
+                format!(
+                    "scope={} device={} fingerprint={}",
+                    scope_state.scope_id.0, scope_state.signer_device_id.0, payload_fp
+                ),
+            )?;
+            self.emit_vault_event(VaultEvent::SignerTrusted {
+                scope_id: scope_state.scope_id.clone(),
+                device_id: scope_state.signer_device_id.clone(),
+            });
+        }
+        for (device_id, role) in &role_changes {
+            self.record_audit(
+                AuditEventKind::SignerChange,
+                format!(
+                    "scope={} device={} role={}",
+                    scope_state.scope_id.0,
+                    device_id.0,
+                    role.as_str()
+                ),
+            )?;
+        }
+        if let ScopeStatePayload::MembershipChange { member_keys, .. } = &payload {
+            self.learn_member_keys(
+                session_id,
+                &scope_state.scope_id,
+                member_keys,
+                &mut warnings,
+            )?;
+        }
+
+        self.check_invariants()?;
         Ok(IngestScopeStateResponse {
             scope_id: scope_state.scope_id,
             scope_state_ref,
+            warnings,
+        })
+    }
+
+    /// Checks a WebAuthn `packed` or `tpm` attestation statement for `device_id` against
+    /// `scope_id`'s [`AttestationPolicy`], after which a scope state may add the device with
+    /// `signer`. The statement's `clientDataHash` must be [`device_attestation_challenge`] for
+    /// the device and `signer`. Like the roster, attestations are not persisted. Needs the
+    /// `attestation` feature; fails with `AttestationFailed`.
+    pub fn ingest_device_attestation(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        device_id: &DeviceId,
+        signer: &SignerKeys,
+        attestation_object: &[u8],
+    ) -> Result<DeviceAttestationResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_ids([("scopeId", scope_id.0.as_str()), ("deviceId", &device_id.0)])?;
+        let policy = self
+            .config
+            .policy
+            .scope_attestation
+            .get(&scope_id.0)
+            .ok_or(KeyServiceError::AttestationFailed {
+                reason: "scope has no attestation policy",
+            })?;
+        let challenge =
+            device_attestation_challenge(device_id, signer).map_err(KeyServiceError::from)?;
+        let verified = self.verify_attestation(attestation_object, &challenge, policy, now)?;
+
+        let fingerprint = fingerprint_signer(signer);
+        let header = self.load_header()?;
+        let state = self.state.get_or_insert_with(|| KeyServiceState {
+            keyvault_header: header,
+            keyvault_state: KeyVaultState::default(),
+            keyvault_materialized: KeyVaultMaterialized::default(),
+            signer_roster: SignerRoster::new(self.config.policy.max_scope_state_refs_per_scope),
+            salvage: None,
+        });
+        state
+            .signer_roster
+            .attested_devices
+            .entry(scope_id.0.clone())
+            .or_default()
+            .insert(device_id.0.clone(), fingerprint.clone());
+        self.record_audit(
+            AuditEventKind::SignerChange,
+            format!(
+                "scope={} device={} attested={} fingerprint={}",
+                scope_id.0,
+                device_id.0,
+                verified.format.as_str(),
+                fingerprint
+            ),
+        )?;
+        Ok(DeviceAttestationResponse {
+            format: verified.format,
+            aaguid: verified.aaguid,
+            self_attested: verified.self_attested,
+            fingerprint,
+        })
+    }
+
+    /// Trusts a delegate key for grants and [`Self::verify`] until the delegation expires.
+    /// The delegator must be a trusted scope signer; the delegate cannot sign scope states.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bytes = delegation_cbor.len()))
+    )]
+    pub fn ingest_delegation(
+        &mut self,
+        session_id: &SessionId,
+        delegation_cbor: &[u8],
+    ) -> Result<IngestDelegationResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+
+        let limits = self.cbor_limits();
+        let value = decode_canonical_value(delegation_cbor, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("signing_delegation")(e).to_string())
+        })?;
+        let delegation = SigningDelegationV1::from_cbor(value)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.check_ids([
+            ("scopeId", delegation.scope_id.0.as_str()),
+            ("deviceId", &delegation.delegator_device_id.0),
+            ("deviceId", &delegation.delegate_device_id.0),
+        ])?;
+        if delegation.v != 1 {
+            return Err(KeyServiceError::InvalidFormat(
+                "unsupported signing delegation version".to_string(),
+            ));
+        }
+        if delegation.sig_suite != SigCiphersuiteId::HybridSig1 {
+            return Err(KeyServiceError::UnsupportedSuite);
+        }
+        if delegation.expires_at_ms <= delegation.issued_at_ms {
+            return Err(KeyServiceError::InvalidFormat(
+                "signing delegation must expire after it is issued".to_string(),
+            ));
+        }
+        if now > delegation.expires_at_ms {
+            return Err(KeyServiceError::DelegationExpired);
+        }
+
+        let roster = self.state.as_mut().ok_or(KeyServiceError::UnknownScope)?;
+        let delegator = roster
+            .signer_roster
+            .get_signer(&delegation.scope_id, &delegation.delegator_device_id)
+            .ok_or(KeyServiceError::UntrustedSigner)?;
+        let to_verify = delegation
+            .to_be_signed_bytes()
+            .map_err(KeyServiceError::from)?;
+        if !hybrid_verify_or_unbound(
+            SIG_SIGNING_DELEGATION_V1,
+            &to_verify,
+            &delegation.signature,
+            delegator,
+            self.config.policy.accept_unbound_signatures,
+        ) {
+            return Err(KeyServiceError::SignatureInvalid {
+                format: SignedFormat::SigningDelegation,
+            });
+        }
+        if roster
+            .signer_roster
+            .get_signer(&delegation.scope_id, &delegation.delegate_device_id)
+            .is_some()
+        {
+            return Err(KeyServiceError::InvalidFormat(
+                "delegate device is already a scope signer".to_string(),
+            ));
+        }
+        roster
+            .signer_roster
+            .delegations
+            .entry(delegation.scope_id.0.clone())
+            .or_default()
+            .insert(
+                delegation.delegate_device_id.0.clone(),
+                DelegatedSigner {
+                    keys: delegation.delegate_keys(),
+                    delegator_device_id: delegation.delegator_device_id.clone(),
+                    expires_at_ms: delegation.expires_at_ms,
+                },
+            );
+        self.record_audit(
+            AuditEventKind::SignerChange,
+            format!(
+                "scope={} delegate={} delegator={} expires_at_ms={}",
+                delegation.scope_id.0,
+                delegation.delegate_device_id.0,
+                delegation.delegator_device_id.0,
+                delegation.expires_at_ms
+            ),
+        )?;
+        self.check_invariants()?;
+
+        Ok(IngestDelegationResponse {
+            scope_id: delegation.scope_id,
+            delegate_device_id: delegation.delegate_device_id,
+            expires_at_ms: delegation.expires_at_ms,
         })
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bytes = key_envelope_cbor.len()))
+    )]
     pub fn ingest_key_envelope(
         &mut self,
         session_id: &SessionId,
@@ -774,10 +4089,17 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         self.ensure_session_valid(now, session_id)?;
 
         let limits = self.cbor_limits();
-        let value = decode_canonical_value(key_envelope_cbor, &limits)
-            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+        let value = decode_canonical_value(key_envelope_cbor, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("key_envelope")(e).to_string())
+        })?;
         let envelope = KeyEnvelopeV1::from_cbor(value)
             .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.check_ids([
+            ("envelopeId", envelope.envelope_id.as_str()),
+            ("scopeId", &envelope.scope_id.0),
+            ("userId", &envelope.recipient_user_id.0),
+            ("deviceId", &envelope.signer_device_id.0),
+        ])?;
 
         let roster = self.state.as_ref().ok_or(KeyServiceError::UnknownScope)?;
         let signer = roster
@@ -790,40 +4112,97 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
             .signer_roster
             .has_scope_state_ref(&envelope.scope_id, &scope_state_ref_hex)
         {
-            return Err(KeyServiceError::InvalidFormat(
-                "unknown scopeStateRef".to_string(),
-            ));
+            return Err(KeyServiceError::UnknownScopeStateRef {
+                scope_id: envelope.scope_id.clone(),
+            });
         }
 
         let to_verify = envelope
             .to_be_signed_bytes()
             .map_err(KeyServiceError::from)?;
-        if !hybrid_verify(&to_verify, &envelope.signature, signer) {
-            return Err(KeyServiceError::CryptoError(
-                "key envelope signature invalid".to_string(),
-            ));
+        if !hybrid_verify_or_unbound(
+            SIG_KEY_ENVELOPE_V1,
+            &to_verify,
+            &envelope.signature,
+            signer,
+            self.config.policy.accept_unbound_signatures,
+        ) {
+            return Err(KeyServiceError::SignatureInvalid {
+                format: SignedFormat::KeyEnvelope,
+            });
+        }
+        if roster.keyvault_materialized.has_seen(
+            SeenIdKind::Envelope,
+            &envelope.scope_id,
+            &envelope.envelope_id,
+        ) {
+            return Ok(IngestKeyEnvelopeResponse {
+                scope_id: envelope.scope_id,
+                scope_epoch: envelope.scope_epoch,
+                is_new: false,
+                warnings: Vec::new(),
+            });
+        }
+        let latest_epoch = roster.latest_epoch(&envelope.scope_id);
+        let mut warnings = Vec::new();
+        if envelope.scope_epoch.0 < latest_epoch {
+            warnings.push(IngestWarning::OlderEpoch {
+                scope_epoch: envelope.scope_epoch,
+                latest_epoch: ScopeEpoch(latest_epoch),
+            });
         }
 
-        let recipient = self.load_user_keypair()?;
+        let user_key = self.load_user_keypair()?;
         if let Some(fingerprint) = &envelope.recipient_uk_pub_fingerprint {
-            let local_fp = fingerprint_bytes(&recipient.public_bytes);
-            if local_fp != *fingerprint {
+            let local_fp = fingerprint_bytes(&user_key.public_bytes);
+            if !ct_eq(&local_fp, fingerprint) {
                 return Err(KeyServiceError::FingerprintMismatch);
             }
         }
 
-        let wrap_key = derive_hybrid_kem_wrap_key(&envelope.enc, &recipient, envelope.kem)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-
-        let aad = aad_key_envelope_wrap_v1(
-            &envelope.scope_id.0,
-            envelope.scope_epoch.0,
-            &envelope.recipient_user_id.0,
-            &envelope.scope_state_ref,
-            envelope.kem,
-            envelope.aead,
-            envelope.recipient_uk_pub_fingerprint.as_ref(),
-        )?;
+        let (wrap_key, aad) = match (envelope.prekey_id, &envelope.recipient_uk_pub_fingerprint) {
+            (Some(prekey_id), Some(fingerprint)) => {
+                let materialized = &roster.keyvault_materialized;
+                if prekey_id >= materialized.next_prekey_id {
+                    return Err(KeyServiceError::PrekeyRejected {
+                        reason: "prekey was never published",
+                    });
+                }
+                if materialized.consumed_prekeys.contains(&prekey_id) {
+                    return Err(KeyServiceError::PrekeyRejected {
+                        reason: "prekey already consumed",
+                    });
+                }
+                let prekey = derive_prekey_keypair(user_key, prekey_id)?;
+                let wrap_key = derive_hybrid_kem_wrap_key(&envelope.enc, &prekey, envelope.kem)
+                    .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+                let aad = aad_key_envelope_prekey_wrap_v1(
+                    &envelope.scope_id.0,
+                    envelope.scope_epoch.0,
+                    &envelope.recipient_user_id.0,
+                    &envelope.scope_state_ref,
+                    envelope.kem,
+                    envelope.aead,
+                    fingerprint,
+                    prekey_id,
+                )?;
+                (wrap_key, aad)
+            }
+            _ => {
+                let wrap_key = derive_hybrid_kem_wrap_key(&envelope.enc, user_key, envelope.kem)
+                    .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+                let aad = aad_key_envelope_wrap_v1(
+                    &envelope.scope_id.0,
+                    envelope.scope_epoch.0,
+                    &envelope.recipient_user_id.0,
+                    &envelope.scope_state_ref,
+                    envelope.kem,
+                    envelope.aead,
+                    envelope.recipient_uk_pub_fingerprint.as_ref(),
+                )?;
+                (wrap_key, aad)
+            }
+        };
 
         let scope_key = aead_decrypt::<Aes256Gcm>(
             &wrap_key,
@@ -831,7 +4210,10 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
             &envelope.nonce,
             &envelope.wrapped_scope_key,
         )
-        .map_err(|_| KeyServiceError::CryptoError("scope key unwrap failed".to_string()))?;
+        .map(Zeroizing::new)
+        .map_err(|_| KeyServiceError::KeyUnwrapFailed {
+            kind: WrappedKeyKind::ScopeKey,
+        })?;
 
         self.persist_scope_key(
             session_id,
@@ -839,98 +4221,797 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
             envelope.scope_epoch,
             &scope_key,
         )?;
+        self.mark_seen(
+            session_id,
+            SeenIdKind::Envelope,
+            &envelope.scope_id,
+            &envelope.envelope_id,
+        )?;
+        if let Some(prekey_id) = envelope.prekey_id {
+            self.consume_prekey(session_id, prekey_id)?;
+        }
+        self.record_audit(
+            AuditEventKind::IngestKeyEnvelope,
+            format!(
+                "scope={} epoch={} signer={}",
+                envelope.scope_id.0, envelope.scope_epoch.0, envelope.signer_device_id.0
+            ),
+        )?;
 
         Ok(IngestKeyEnvelopeResponse {
             scope_id: envelope.scope_id,
             scope_epoch: envelope.scope_epoch,
+            is_new: true,
+            warnings,
         })
     }
 
-    pub fn open_scope(
+    /// Publishes `count` one-time prekeys after the ones already published, derived from the
+    /// user key (see [`derive_prekey_keypair`]) and signed by the local device. Senders seal
+    /// envelopes to them with [`Self::seal_key_envelope_to_prekey`] while this user is offline.
+    pub fn publish_prekeys(
         &mut self,
         session_id: &SessionId,
-        scope_id: ScopeId,
-        scope_epoch: ScopeEpoch,
-    ) -> Result<OpenScopeResponse, KeyServiceError> {
+        count: u64,
+    ) -> Result<PublishPrekeysResponse, KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let key = {
-            let state = self
-                .state
-                .as_ref()
-                .ok_or(KeyServiceError::ScopeKeyMissing)?;
-            state
-                .keyvault_materialized
-                .scope_keys
-                .get(&(scope_id.0.clone(), scope_epoch.0))
-                .ok_or(KeyServiceError::ScopeKeyMissing)?
-                .clone()
-        };
-        let session = self
-            .sessions
-            .get_mut(session_id)
-            .ok_or(KeyServiceError::SessionInvalid)?;
-        let handle = session
-            .insert_handle(HandleEntry::ScopeKey {
-                scope_id: scope_id.clone(),
-                scope_epoch,
-                key,
+        self.ensure_writable(session_id)?;
+        if count == 0 || count > MAX_PREKEYS_PER_BUNDLE {
+            return Err(KeyServiceError::PrekeyRejected {
+                reason: "prekey count out of range",
+            });
+        }
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let first = state.keyvault_materialized.next_prekey_id;
+        let next = first
+            .checked_add(count)
+            .ok_or(KeyServiceError::PrekeyRejected {
+                reason: "prekey ids exhausted",
+            })?;
+        let user_key = self.load_user_keypair()?;
+        let prekeys = (first..next)
+            .map(|prekey_id| {
+                Ok(PrekeyV1 {
+                    prekey_id,
+                    public_bytes: derive_prekey_keypair(user_key, prekey_id)?.public_bytes,
+                })
             })
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        Ok(OpenScopeResponse {
-            scope_key_handle: handle,
+            .collect::<Result<Vec<_>, KeyServiceError>>()?;
+        let (signer_device_id, signing) = self.local_signing_key()?;
+        let mut bundle = PrekeyBundleV1 {
+            v: 1,
+            user_id: UserId(state.keyvault_header.user_id.clone()),
+            uk_pub_fingerprint: fingerprint_bytes(&user_key.public_bytes),
+            prekeys,
+            signer_device_id,
+            signer: SignerKeys {
+                sig_suite: SigCiphersuiteId::HybridSig1,
+                ed25519_pub: signing.ed25519_pub.clone(),
+                mldsa_pub: signing.mldsa_pub.clone(),
+            },
+            signature: Vec::new(),
+        };
+        bundle.signature = self
+            .sign_inner(
+                session_id,
+                SIG_PREKEY_BUNDLE_V1,
+                &bundle.to_be_signed_bytes()?,
+            )?
+            .signature;
+
+        let header = self.load_header()?;
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_prekeys_published_record(&record_id, next);
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state.keyvault_materialized.next_prekey_id = next;
+        Ok(PublishPrekeysResponse {
+            bundle: encode_prekey_bundle_v1(&bundle)?,
+            prekey_ids: (first..next).collect(),
         })
     }
 
-    pub fn open_resource(
+    /// Seals this vault's key for `scope_id` at `scope_epoch` to `recipient_user_id`'s key in
+    /// the recipient key directory (see [`Self::add_contact`]), as a key envelope for the
+    /// recipient's [`Self::ingest_key_envelope`]. The local device must be a trusted signer of
+    /// the scope. Fails with `UnknownContact` when the directory has no key for the user.
+    pub fn seal_key_envelope(
         &mut self,
         session_id: &SessionId,
-        scope_key_handle: &KeyHandle,
-        grant_cbor: &[u8],
-    ) -> Result<OpenResourceResponse, KeyServiceError> {
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+        recipient_user_id: &UserId,
+    ) -> Result<SealKeyEnvelopeResponse, KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let scope_key = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            match session.get_handle(scope_key_handle) {
-                Some(HandleEntry::ScopeKey { key, .. }) => key.clone(),
-                _ => return Err(KeyServiceError::UnknownHandle),
-            }
-        };
-
+        self.check_ids([
+            ("scopeId", scope_id.0.as_str()),
+            ("userId", &recipient_user_id.0),
+        ])?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let contact = state
+            .keyvault_materialized
+            .contacts
+            .get(&recipient_user_id.0)
+            .ok_or_else(|| KeyServiceError::UnknownContact {
+                user_id: recipient_user_id.clone(),
+            })?;
+        let recipient_public = decode_user_public_bytes(&contact.public_bytes)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        let uk_pub_fingerprint = fingerprint_bytes(&contact.public_bytes);
+        self.seal_scope_key_envelope(
+            session_id,
+            scope_id,
+            scope_epoch,
+            recipient_user_id.clone(),
+            &recipient_public,
+            uk_pub_fingerprint,
+            None,
+        )
+    }
+
+    /// Seals this vault's key for `scope_id` at `scope_epoch` to prekey `prekey_id` of
+    /// `prekey_bundle_cbor`, as a key envelope the recipient ingests when back online. The
+    /// bundle's signer must match `expected_signer_fingerprint`, and the local device must be
+    /// a trusted signer of the scope. When the recipient key directory holds a key for the
+    /// bundle's user, the bundle must name that key. Use each prekey for one envelope only;
+    /// the recipient refuses a second.
+    pub fn seal_key_envelope_to_prekey(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+        prekey_bundle_cbor: &[u8],
+        prekey_id: u64,
+        expected_signer_fingerprint: &str,
+    ) -> Result<SealKeyEnvelopeResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_ids([("scopeId", scope_id.0.as_str())])?;
+
         let limits = self.cbor_limits();
-        let value = decode_canonical_value(grant_cbor, &limits)
-            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+        let value = decode_canonical_value(prekey_bundle_cbor, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("prekey_bundle")(e).to_string())
+        })?;
+        let bundle = PrekeyBundleV1::from_cbor(value)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.check_ids([
+            ("userId", bundle.user_id.0.as_str()),
+            ("deviceId", &bundle.signer_device_id.0),
+        ])?;
+        let bundle_fp = fingerprint_signer(&bundle.signer);
+        if !ct_eq(bundle_fp.as_bytes(), expected_signer_fingerprint.as_bytes()) {
+            return Err(KeyServiceError::FingerprintMismatch);
+        }
+        if !hybrid_verify(
+            SIG_PREKEY_BUNDLE_V1,
+            &bundle.to_be_signed_bytes()?,
+            &bundle.signature,
+            &bundle.signer,
+        ) {
+            return Err(KeyServiceError::SignatureInvalid {
+                format: SignedFormat::PrekeyBundle,
+            });
+        }
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        if let Some(contact) = state.keyvault_materialized.contacts.get(&bundle.user_id.0) {
+            if !ct_eq(
+                &fingerprint_bytes(&contact.public_bytes),
+                &bundle.uk_pub_fingerprint,
+            ) {
+                return Err(KeyServiceError::FingerprintMismatch);
+            }
+        }
+        let prekey = bundle
+            .prekeys
+            .iter()
+            .find(|prekey| prekey.prekey_id == prekey_id)
+            .ok_or(KeyServiceError::PrekeyRejected {
+                reason: "prekey not in the bundle",
+            })?;
+        let prekey_public = decode_user_public_bytes(&prekey.public_bytes)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.seal_scope_key_envelope(
+            session_id,
+            scope_id,
+            scope_epoch,
+            bundle.user_id,
+            &prekey_public,
+            bundle.uk_pub_fingerprint,
+            Some(prekey_id),
+        )
+    }
+
+    /// Wraps the local scope key to `recipient_public` and signs the envelope as the local
+    /// device. With `prekey_id`, `recipient_public` is that prekey of the user key with
+    /// fingerprint `uk_pub_fingerprint`.
+    #[allow(clippy::too_many_arguments)]
+    fn seal_scope_key_envelope(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+        recipient_user_id: UserId,
+        recipient_public: &HybridKemRecipientPublic,
+        uk_pub_fingerprint: Vec<u8>,
+        prekey_id: Option<u64>,
+    ) -> Result<SealKeyEnvelopeResponse, KeyServiceError> {
+        let (signer_device_id, _) = self.local_signing_key()?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        if state
+            .signer_roster
+            .get_signer(scope_id, &signer_device_id)
+            .is_none()
+        {
+            return Err(KeyServiceError::UntrustedSigner);
+        }
+        let scope_state_ref = state
+            .signer_roster
+            .latest_scope_state_ref(scope_id)
+            .and_then(|hex_ref| hex::decode(hex_ref).ok())
+            .ok_or_else(|| KeyServiceError::UnknownScopeStateRef {
+                scope_id: scope_id.clone(),
+            })?;
+        let scope_key = state
+            .keyvault_materialized
+            .scope_keys
+            .get(&(scope_id.0.clone(), scope_epoch.0))
+            .ok_or(KeyServiceError::ScopeKeyMissing)?;
+
+        let kem = KemCiphersuiteId::HybridKem1;
+        let aead = AeadId::Aead1;
+        let encap = hybrid_kem_encapsulate(recipient_public, kem)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        let aad = match prekey_id {
+            Some(prekey_id) => aad_key_envelope_prekey_wrap_v1(
+                &scope_id.0,
+                scope_epoch.0,
+                &recipient_user_id.0,
+                &scope_state_ref,
+                kem,
+                aead,
+                &uk_pub_fingerprint,
+                prekey_id,
+            )?,
+            None => aad_key_envelope_wrap_v1(
+                &scope_id.0,
+                scope_epoch.0,
+                &recipient_user_id.0,
+                &scope_state_ref,
+                kem,
+                aead,
+                Some(&uk_pub_fingerprint),
+            )?,
+        };
+        let nonce = self.entropy.random_bytes(12);
+        let wrapped_scope_key =
+            aead_encrypt::<Aes256Gcm>(&encap.wrap_key, &aad, scope_key, &nonce)?;
+        let mut envelope = KeyEnvelopeV1 {
+            v: 1,
+            envelope_id: uuid_like(&self.entropy.random_bytes(16)),
+            scope_id: scope_id.clone(),
+            scope_epoch,
+            recipient_user_id,
+            scope_state_ref,
+            kem,
+            aead,
+            enc: encap.enc,
+            nonce,
+            wrapped_scope_key,
+            signer_device_id,
+            sig_suite: SigCiphersuiteId::HybridSig1,
+            signature: Vec::new(),
+            recipient_uk_pub_fingerprint: Some(uk_pub_fingerprint),
+            prekey_id,
+        };
+        envelope.signature = self
+            .sign_inner(
+                session_id,
+                SIG_KEY_ENVELOPE_V1,
+                &envelope.to_be_signed_bytes()?,
+            )?
+            .signature;
+        Ok(SealKeyEnvelopeResponse {
+            envelope: encode_key_envelope_v1(&envelope)?,
+            envelope_id: envelope.envelope_id,
+        })
+    }
+
+    /// Starts a CGKA group for `scope_id` with this user as its only member, and stores a
+    /// fresh scope key for `scope_epoch`. Later epochs come from [`Self::commit_cgka`]; see
+    /// [`crate::cgka`]. The local device must administer the scope.
+    pub fn create_cgka_group(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+    ) -> Result<(), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_ids([("scopeId", scope_id.0.as_str())])?;
+        self.ensure_writable(session_id)?;
+        if self.load_cgka_group(scope_id)?.is_some() {
+            return Err(KeyServiceError::CgkaFailed {
+                reason: "group already exists",
+            });
+        }
+        self.local_cgka_committer(scope_id)?;
+        let member = self.local_cgka_member()?;
+        let epoch_secret = Zeroizing::new(self.entropy.random_bytes(CGKA_SECRET_LEN));
+        let (group, scope_key) =
+            CgkaGroup::create(scope_id.clone(), scope_epoch.0, &member, &epoch_secret)?;
+        self.persist_cgka_group(session_id, &group, &scope_key)
+    }
+
+    /// Moves `scope_id`'s CGKA group to the next epoch, removing `removes` and adding `adds`,
+    /// and stores that epoch's scope key. Publish the commit to the other members and the
+    /// welcome to the added ones. The local device must administer the scope.
+    pub fn commit_cgka(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        adds: &[CgkaMemberV1],
+        removes: &[UserId],
+    ) -> Result<CgkaCommitResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_ids(
+            std::iter::once(("scopeId", scope_id.0.as_str()))
+                .chain(
+                    adds.iter()
+                        .map(|member| ("userId", member.user_id.0.as_str())),
+                )
+                .chain(removes.iter().map(|user_id| ("userId", user_id.0.as_str()))),
+        )?;
+        self.ensure_writable(session_id)?;
+        let group = self
+            .load_cgka_group(scope_id)?
+            .ok_or(KeyServiceError::CgkaFailed {
+                reason: "no group for the scope",
+            })?;
+        let signer_device_id = self.local_cgka_committer(scope_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let scope_state_ref = state
+            .signer_roster
+            .latest_scope_state_ref(scope_id)
+            .and_then(|hex_ref| hex::decode(hex_ref).ok())
+            .ok_or_else(|| KeyServiceError::UnknownScopeStateRef {
+                scope_id: scope_id.clone(),
+            })?;
+        let removes = removes
+            .iter()
+            .map(|user_id| group.tree.find_member(user_id))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(KeyServiceError::CgkaFailed {
+                reason: "removed user is not a member",
+            })?;
+        let path_seed = Zeroizing::new(self.entropy.random_bytes(CGKA_SECRET_LEN));
+        let context = CgkaCommitContext {
+            scope_state_ref,
+            signer_device_id,
+            sig_suite: SigCiphersuiteId::HybridSig1,
+        };
+        let mut outcome = group
+            .commit(&removes, adds, &path_seed, context)
+            .map_err(|reason| KeyServiceError::CgkaFailed { reason })?;
+        outcome.commit.signature = self
+            .sign_inner(
+                session_id,
+                SIG_CGKA_COMMIT_V1,
+                &outcome.commit.to_be_signed_bytes()?,
+            )?
+            .signature;
+        let welcome = match outcome.welcome.as_mut() {
+            Some(welcome) => {
+                welcome.signature = self
+                    .sign_inner(
+                        session_id,
+                        SIG_CGKA_WELCOME_V1,
+                        &welcome.to_be_signed_bytes()?,
+                    )?
+                    .signature;
+                Some(encode_cgka_welcome_v1(welcome)?)
+            }
+            None => None,
+        };
+        let commit = encode_cgka_commit_v1(&outcome.commit)?;
+        self.persist_cgka_group(session_id, &outcome.group, &outcome.scope_key)?;
+        self.record_audit(
+            AuditEventKind::CgkaCommit,
+            format!(
+                "scope={} epoch={} adds={} removes={}",
+                scope_id.0,
+                outcome.group.epoch,
+                adds.len(),
+                removes.len()
+            ),
+        )?;
+        Ok(CgkaCommitResponse {
+            scope_epoch: ScopeEpoch(outcome.group.epoch),
+            commit,
+            welcome,
+        })
+    }
+
+    /// Applies another member's CGKA commit and stores the scope key of its epoch. The
+    /// committer must be a trusted signer administering the scope; a commit for an epoch the
+    /// group already reached is a no-op.
+    pub fn ingest_cgka_commit(
+        &mut self,
+        session_id: &SessionId,
+        commit_cbor: &[u8],
+    ) -> Result<IngestCgkaResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let limits = self.cbor_limits();
+        let value = decode_canonical_value(commit_cbor, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("cgka_commit")(e).to_string())
+        })?;
+        let commit = CgkaCommitV1::from_cbor(value)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.check_ids(
+            [
+                ("scopeId", commit.scope_id.0.as_str()),
+                ("deviceId", &commit.signer_device_id.0),
+            ]
+            .into_iter()
+            .chain(
+                commit
+                    .adds
+                    .iter()
+                    .map(|member| ("userId", member.user_id.0.as_str())),
+            ),
+        )?;
+        self.verify_cgka_signer(
+            &commit.scope_id,
+            &commit.signer_device_id,
+            &commit.scope_state_ref,
+            SignedFormat::CgkaCommit,
+            &commit.to_be_signed_bytes()?,
+            &commit.signature,
+        )?;
+        let group = self
+            .load_cgka_group(&commit.scope_id)?
+            .ok_or(KeyServiceError::CgkaFailed {
+                reason: "no group for the scope",
+            })?;
+        if commit.scope_epoch.0 <= group.epoch {
+            return Ok(IngestCgkaResponse {
+                scope_id: commit.scope_id,
+                scope_epoch: commit.scope_epoch,
+                is_new: false,
+            });
+        }
+        let (group, scope_key) = group
+            .process_commit(&commit, self.load_user_keypair()?)
+            .map_err(|reason| KeyServiceError::CgkaFailed { reason })?;
+        self.persist_cgka_group(session_id, &group, &scope_key)?;
+        self.record_audit(
+            AuditEventKind::CgkaCommit,
+            format!(
+                "scope={} epoch={} signer={}",
+                commit.scope_id.0, commit.scope_epoch.0, commit.signer_device_id.0
+            ),
+        )?;
+        Ok(IngestCgkaResponse {
+            scope_id: commit.scope_id,
+            scope_epoch: commit.scope_epoch,
+            is_new: true,
+        })
+    }
+
+    /// Joins a scope's CGKA group from a welcome addressed to this user and stores the scope
+    /// key of its epoch. Signer checks match [`Self::ingest_cgka_commit`].
+    pub fn ingest_cgka_welcome(
+        &mut self,
+        session_id: &SessionId,
+        welcome_cbor: &[u8],
+    ) -> Result<IngestCgkaResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let limits = self.cbor_limits();
+        let value = decode_canonical_value(welcome_cbor, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("cgka_welcome")(e).to_string())
+        })?;
+        let welcome = CgkaWelcomeV1::from_cbor(value)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.check_ids([
+            ("scopeId", welcome.scope_id.0.as_str()),
+            ("deviceId", &welcome.signer_device_id.0),
+        ])?;
+        self.verify_cgka_signer(
+            &welcome.scope_id,
+            &welcome.signer_device_id,
+            &welcome.scope_state_ref,
+            SignedFormat::CgkaWelcome,
+            &welcome.to_be_signed_bytes()?,
+            &welcome.signature,
+        )?;
+        if self
+            .load_cgka_group(&welcome.scope_id)?
+            .is_some_and(|group| group.epoch >= welcome.scope_epoch.0)
+        {
+            return Ok(IngestCgkaResponse {
+                scope_id: welcome.scope_id,
+                scope_epoch: welcome.scope_epoch,
+                is_new: false,
+            });
+        }
+        let user_id = UserId(self.load_header()?.user_id);
+        let (group, scope_key) =
+            CgkaGroup::join(&welcome, &user_id, self.load_user_keypair()?, &limits)
+                .map_err(|reason| KeyServiceError::CgkaFailed { reason })?;
+        self.persist_cgka_group(session_id, &group, &scope_key)?;
+        self.record_audit(
+            AuditEventKind::CgkaCommit,
+            format!(
+                "scope={} epoch={} signer={} joined",
+                welcome.scope_id.0, welcome.scope_epoch.0, welcome.signer_device_id.0
+            ),
+        )?;
+        Ok(IngestCgkaResponse {
+            scope_id: welcome.scope_id,
+            scope_epoch: welcome.scope_epoch,
+            is_new: true,
+        })
+    }
+
+    /// Scopes known from stored scope keys or trusted signers, sorted by scope id.
+    pub fn list_scopes(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<ListScopesResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let mut scopes: BTreeMap<&str, (Vec<ScopeEpoch>, usize)> = BTreeMap::new();
+        for (scope_id, epoch) in state.keyvault_materialized.scope_keys.keys() {
+            scopes
+                .entry(scope_id)
+                .or_default()
+                .0
+                .push(ScopeEpoch(*epoch));
+        }
+        for (scope_id, signers) in &state.signer_roster.scopes {
+            scopes.entry(scope_id).or_default().1 = signers.len();
+        }
+        let scopes = scopes
+            .into_iter()
+            .map(|(scope_id, (mut epochs, trusted_signer_count))| {
+                epochs.sort_by_key(|epoch| epoch.0);
+                ScopeSummary {
+                    scope_id: ScopeId(scope_id.to_string()),
+                    has_scope_key: !epochs.is_empty(),
+                    epochs,
+                    trusted_signer_count,
+                }
+            })
+            .collect();
+        Ok(ListScopesResponse { scopes })
+    }
+
+    /// Sets the rotation policy of `scope_id`, or clears it with `None`. Needs a step-up
+    /// session. The policy applies to every epoch of the scope; rotating means moving to a new
+    /// epoch, whose key starts with a fresh age and operation count.
+    pub fn set_rotation_policy(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        policy: Option<ScopeRotationPolicy>,
+    ) -> Result<(), KeyServiceError> {
+        self.check_ids([("scopeId", scope_id.0.as_str())])?;
+        let header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::RotationPolicy)?;
+        self.ensure_writable(session_id)?;
+        if let Some(policy) = &policy {
+            if policy.max_key_age_ms.is_none() && policy.max_operations.is_none() {
+                return Err(KeyServiceError::InvalidConfig(
+                    "rotation policy sets no limit".to_string(),
+                ));
+            }
+            if policy.max_key_age_ms == Some(0) || policy.max_operations == Some(0) {
+                return Err(KeyServiceError::InvalidConfig(
+                    "rotation policy limits must be positive".to_string(),
+                ));
+            }
+        }
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_rotation_policy_record(&record_id, &scope_id.0, policy.as_ref());
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let policies = &mut state.keyvault_materialized.rotation_policies;
+        let detail = match policy {
+            Some(policy) => {
+                policies.insert(scope_id.0.clone(), policy);
+                format!(
+                    "scope={} maxKeyAgeMs={} maxOperations={} graceMs={}",
+                    scope_id.0,
+                    policy
+                        .max_key_age_ms
+                        .map_or("-".to_string(), |v| v.to_string()),
+                    policy
+                        .max_operations
+                        .map_or("-".to_string(), |v| v.to_string()),
+                    policy.grace_ms
+                )
+            }
+            None => {
+                policies.remove(&scope_id.0);
+                format!("scope={} cleared", scope_id.0)
+            }
+        };
+        self.record_audit(AuditEventKind::SetRotationPolicy, detail)?;
+        Ok(())
+    }
+
+    /// Scopes whose newest epoch key is due for rotation, soonest hard deadline first. Keys
+    /// stored before records carried a creation time never come due by age.
+    pub fn due_rotations(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<DueRotationsResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let mut rotations = Vec::new();
+        for (scope_id, policy) in &state.keyvault_materialized.rotation_policies {
+            let scope_id = ScopeId(scope_id.clone());
+            let Some(scope_epoch) = state
+                .keyvault_materialized
+                .scope_keys
+                .keys()
+                .filter(|(id, _)| *id == scope_id.0)
+                .map(|(_, epoch)| ScopeEpoch(*epoch))
+                .max_by_key(|epoch| epoch.0)
+            else {
+                continue;
+            };
+            if let Some(due) = self.rotation_status(now, &scope_id, scope_epoch, policy)? {
+                rotations.push(due);
+            }
+        }
+        rotations.sort_by(|a, b| {
+            (a.hard_deadline_ms, &a.scope_id.0).cmp(&(b.hard_deadline_ms, &b.scope_id.0))
+        });
+        Ok(DueRotationsResponse { rotations })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(scope_epoch = scope_epoch.0))
+    )]
+    pub fn open_scope(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: ScopeId,
+        scope_epoch: ScopeEpoch,
+    ) -> Result<OpenScopeResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let key = {
+            let state = self
+                .state
+                .as_ref()
+                .ok_or(KeyServiceError::ScopeKeyMissing)?;
+            let scope_key = state
+                .keyvault_materialized
+                .scope_keys
+                .get(&(scope_id.0.clone(), scope_epoch.0))
+                .ok_or(KeyServiceError::ScopeKeyMissing)?;
+            SecretBytes::new(scope_key).map_err(|e| KeyServiceError::CryptoError(e.to_string()))?
+        };
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let handle = session
+            .insert_handle(HandleEntry::ScopeKey {
+                scope_id: scope_id.clone(),
+                scope_epoch,
+                key,
+            })
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        Ok(OpenScopeResponse {
+            scope_key_handle: handle,
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bytes = grant_cbor.len()))
+    )]
+    pub fn open_resource(
+        &mut self,
+        session_id: &SessionId,
+        scope_key_handle: &KeyHandle,
+        grant_cbor: &[u8],
+    ) -> Result<OpenResourceResponse, KeyServiceError> {
+        self.open_resource_with_ops(session_id, scope_key_handle, grant_cbor, CapabilityOps::ALL)
+    }
+
+    /// Like [`Self::open_resource`], limiting the handle to `ops` as well as to the ops the
+    /// grant's policy allows; fails with [`KeyServiceError::CapabilityDenied`] if none remain.
+    pub fn open_resource_with_ops(
+        &mut self,
+        session_id: &SessionId,
+        scope_key_handle: &KeyHandle,
+        grant_cbor: &[u8],
+        ops: CapabilityOps,
+    ) -> Result<OpenResourceResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let scope_key = {
+            let session = self
+                .sessions
+                .get_mut(session_id)
+                .ok_or(KeyServiceError::SessionInvalid)?;
+            match session.get_handle(scope_key_handle) {
+                Some(HandleEntry::ScopeKey { key, .. }) => key.clone(),
+                _ => return Err(KeyServiceError::UnknownHandle),
+            }
+        };
+
+        let limits = self.cbor_limits();
+        let value = decode_canonical_value(grant_cbor, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("resource_grant")(e).to_string())
+        })?;
         let grant = ResourceGrantV1::from_cbor(value)
             .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.check_ids(grant_ids(&grant))?;
+        let ops = grant_policy_ops(grant.policy.as_ref())?.intersection(&ops);
+        if ops.is_empty() {
+            return Err(KeyServiceError::CapabilityDenied);
+        }
+        let scope = (grant.scope_id.clone(), ScopeEpoch(grant.scope_epoch));
 
         let roster = self.state.as_mut().ok_or(KeyServiceError::UnknownScope)?;
+        // An encrypt-only handle would be unusable under a retired epoch.
+        if !ops.decrypt {
+            roster.signer_roster.ensure_epoch_active(Some(&scope))?;
+        }
         let signer = roster
             .signer_roster
-            .get_signer(&grant.scope_id, &grant.signer_device_id)
-            .cloned()
-            .ok_or(KeyServiceError::UntrustedSigner)?;
+            .resolve_signer(&grant.scope_id, &grant.signer_device_id, now)?
+            .clone();
 
         let scope_state_ref_hex = hex::encode(&grant.scope_state_ref);
         if !roster
             .signer_roster
             .has_scope_state_ref(&grant.scope_id, &scope_state_ref_hex)
         {
-            return Err(KeyServiceError::InvalidFormat(
-                "unknown scopeStateRef".to_string(),
-            ));
+            return Err(KeyServiceError::UnknownScopeStateRef {
+                scope_id: grant.scope_id.clone(),
+            });
         }
 
         let to_verify = grant.to_be_signed_bytes().map_err(KeyServiceError::from)?;
-        if !hybrid_verify(&to_verify, &grant.signature, &signer) {
-            return Err(KeyServiceError::CryptoError(
-                "resource grant signature invalid".to_string(),
-            ));
+        if !hybrid_verify_or_unbound(
+            SIG_RESOURCE_GRANT_V1,
+            &to_verify,
+            &grant.signature,
+            &signer,
+            self.config.policy.accept_unbound_signatures,
+        ) {
+            return Err(KeyServiceError::SignatureInvalid {
+                format: SignedFormat::ResourceGrant,
+            });
+        }
+        // Seen grants are keyed by their grant ref, so a different grant reusing a grant id is
+        // new and has to link onto the chain.
+        let grant_ref_hex = hex::encode(grant.grant_ref_bytes().map_err(KeyServiceError::from)?);
+        let is_new = !roster.keyvault_materialized.has_seen(
+            SeenIdKind::Grant,
+            &grant.scope_id,
+            &grant_ref_hex,
+        );
+        if is_new {
+            roster.signer_roster.verify_and_update_grant_chain(&grant)?;
+        } else {
+            roster.signer_roster.relink_grant_chain(&grant)?;
         }
-        roster.signer_roster.verify_and_update_grant_chain(&grant)?;
 
         let aad = aad_resource_grant_wrap_v1(
             &grant.scope_id.0,
@@ -941,16 +5022,27 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         )?;
 
         let resource_key =
-            aead_decrypt::<Aes256Gcm>(&scope_key, &aad, &grant.nonce, &grant.wrapped_key).map_err(
-                |_| KeyServiceError::CryptoError("resource key unwrap failed".to_string()),
-            )?;
+            aead_decrypt::<Aes256Gcm>(&scope_key, &aad, &grant.nonce, &grant.wrapped_key)
+                .map(Zeroizing::new)
+                .map_err(|_| KeyServiceError::KeyUnwrapFailed {
+                    kind: WrappedKeyKind::ResourceKey,
+                })?;
 
-        self.persist_resource_key(
-            session_id,
-            &grant.resource_id,
-            &grant.resource_key_id,
-            &resource_key,
-        )?;
+        // Read-only sessions keep the key in the handle without recording it in the vault.
+        if is_new && self.ensure_writable(session_id).is_ok() {
+            self.persist_resource_key(
+                session_id,
+                &grant.resource_id,
+                &grant.resource_key_id,
+                &resource_key,
+            )?;
+            self.mark_seen(
+                session_id,
+                SeenIdKind::Grant,
+                &grant.scope_id,
+                &grant_ref_hex,
+            )?;
+        }
 
         self.ensure_session_valid(now, session_id)?;
         let session = self
@@ -961,460 +5053,3267 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
             .insert_handle(HandleEntry::ResourceKey {
                 resource_id: grant.resource_id.clone(),
                 resource_key_id: grant.resource_key_id.clone(),
-                key: resource_key,
+                key: SecretBytes::new(&resource_key)
+                    .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?,
+                nonce_counter: None,
+                ops,
+                scope: Some(scope),
+                restriction: None,
+                ratchet: None,
             })
             .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
         Ok(OpenResourceResponse {
             resource_key_handle: handle,
+            is_new,
         })
     }
 
-    pub fn close_handle(
-        &mut self,
-        session_id: &SessionId,
-        key_handle: &KeyHandle,
-    ) -> Result<(), KeyServiceError> {
-        let now = self.clock.now_ms();
-        self.ensure_session_valid(now, session_id)?;
-        let session = self
-            .sessions
-            .get_mut(session_id)
-            .ok_or(KeyServiceError::SessionInvalid)?;
-        session.remove_handle(key_handle);
-        Ok(())
-    }
-
-    pub fn encrypt(
+    /// HKDF-SHA256 sub-key of a resource key for `path` (e.g. `[b"chunk", b"17"]`), so callers
+    /// derive per-object keys without exporting the resource key. Needs a handle with both ops
+    /// and no capability limits, since the sub-key can be used for either.
+    pub fn derive_subkey(
         &mut self,
         session_id: &SessionId,
         resource_key_handle: &KeyHandle,
-        aad: &[u8],
-        plaintext: &[u8],
-    ) -> Result<EncryptResponse, KeyServiceError> {
+        path: &[&[u8]],
+        len: usize,
+    ) -> Result<Zeroizing<Vec<u8>>, KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
+        if path.is_empty() || !SUBKEY_LEN_RANGE.contains(&len) {
+            return Err(KeyServiceError::InvalidFormat(format!(
+                "sub-key needs a non-empty path and a length of {}-{} bytes",
+                SUBKEY_LEN_RANGE.start(),
+                SUBKEY_LEN_RANGE.end()
+            )));
+        }
         let session = self
             .sessions
             .get_mut(session_id)
             .ok_or(KeyServiceError::SessionInvalid)?;
-        let resource_key = match session.get_handle(resource_key_handle) {
-            Some(HandleEntry::ResourceKey { key, .. }) => key.clone(),
+        let (resource_id, resource_key_id, key) = match session.get_handle(resource_key_handle) {
+            Some(HandleEntry::ResourceKey {
+                resource_id,
+                resource_key_id,
+                key,
+                ops: CapabilityOps::ALL,
+                restriction: None,
+                ..
+            }) => (resource_id, resource_key_id, key),
+            Some(HandleEntry::ResourceKey { .. }) => return Err(KeyServiceError::CapabilityDenied),
             _ => return Err(KeyServiceError::UnknownHandle),
         };
-        let nonce = self.entropy.random_bytes(12);
-        let ct = aead_encrypt::<Aes256Gcm>(&resource_key, aad, plaintext, &nonce)
-            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let mut ciphertext = nonce;
-        ciphertext.extend_from_slice(&ct);
-        Ok(EncryptResponse { ciphertext })
+        let info = hkdf_info_resource_subkey_v1(&resource_id.0, &resource_key_id.0, path, len)?;
+        hkdf_sha256(key, &info, len).map_err(|e| KeyServiceError::CryptoError(e.to_string()))
     }
 
-    pub fn decrypt(
+    /// Re-wraps the key of `grant_cbor` under the newer epoch of `scope_key_handle` and signs
+    /// the result as the first local device (by id) that is a signer of the scope. The old grant
+    /// is verified as in [`Self::open_resource`] without consuming the grant chain; the new one
+    /// references the latest scope state and extends the local chain, so the issuing device
+    /// does not open it again.
+    pub fn reissue_grant(
         &mut self,
         session_id: &SessionId,
-        resource_key_handle: &KeyHandle,
-        aad: &[u8],
-        ciphertext: &[u8],
-    ) -> Result<DecryptResponse, KeyServiceError> {
+        grant_cbor: &[u8],
+        scope_key_handle: &KeyHandle,
+    ) -> Result<ReissueGrantResponse, KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let session = self
-            .sessions
-            .get_mut(session_id)
-            .ok_or(KeyServiceError::SessionInvalid)?;
-        let resource_key = match session.get_handle(resource_key_handle) {
-            Some(HandleEntry::ResourceKey { key, .. }) => key.clone(),
-            _ => return Err(KeyServiceError::UnknownHandle),
+        let (new_scope, new_scope_key) = {
+            let session = self
+                .sessions
+                .get_mut(session_id)
+                .ok_or(KeyServiceError::SessionInvalid)?;
+            match session.get_handle(scope_key_handle) {
+                Some(HandleEntry::ScopeKey {
+                    scope_id,
+                    scope_epoch,
+                    key,
+                }) => ((scope_id.clone(), *scope_epoch), key.clone()),
+                _ => return Err(KeyServiceError::UnknownHandle),
+            }
         };
-        if ciphertext.len() < 12 {
-            return Err(KeyServiceError::CryptoError(
-                "ciphertext too short".to_string(),
+
+        let limits = self.cbor_limits();
+        let value = decode_canonical_value(grant_cbor, &limits).map_err(|e| {
+            KeyServiceError::InvalidCbor(cbor_context("resource_grant")(e).to_string())
+        })?;
+        let grant = ResourceGrantV1::from_cbor(value)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.check_ids(grant_ids(&grant))?;
+        if grant.scope_id != new_scope.0 || grant.scope_epoch >= new_scope.1 .0 {
+            return Err(KeyServiceError::InvalidFormat(
+                "reissue needs a scope key handle for a newer epoch of the grant's scope"
+                    .to_string(),
             ));
         }
-        let (nonce, ct) = ciphertext.split_at(12);
-        let pt = aead_decrypt::<Aes256Gcm>(&resource_key, aad, nonce, ct)
-            .map_err(|_| KeyServiceError::CryptoError("decrypt failed".to_string()))?;
-        Ok(DecryptResponse { plaintext: pt })
+
+        let state = self.state.as_ref().ok_or(KeyServiceError::UnknownScope)?;
+        let roster = &state.signer_roster;
+        roster.ensure_epoch_active(Some(&new_scope))?;
+        let signer = roster.resolve_signer(&grant.scope_id, &grant.signer_device_id, now)?;
+        if !roster.has_scope_state_ref(&grant.scope_id, &hex::encode(&grant.scope_state_ref)) {
+            return Err(KeyServiceError::UnknownScopeStateRef {
+                scope_id: grant.scope_id.clone(),
+            });
+        }
+        let to_verify = grant.to_be_signed_bytes().map_err(KeyServiceError::from)?;
+        if !hybrid_verify_or_unbound(
+            SIG_RESOURCE_GRANT_V1,
+            &to_verify,
+            &grant.signature,
+            signer,
+            self.config.policy.accept_unbound_signatures,
+        ) {
+            return Err(KeyServiceError::SignatureInvalid {
+                format: SignedFormat::ResourceGrant,
+            });
+        }
+        let scope_state_ref = roster
+            .latest_scope_state_ref(&grant.scope_id)
+            .and_then(|hex_ref| hex::decode(hex_ref).ok())
+            .ok_or(KeyServiceError::UnknownScope)?;
+
+        let device_keys = &state.keyvault_materialized.device_signing_keys;
+        if device_keys.is_empty() {
+            return Err(KeyServiceError::DeviceKeyMissing);
+        }
+        let mut local_ids: Vec<&String> = device_keys.keys().collect();
+        local_ids.sort();
+        let (issuer_id, issuer) = local_ids
+            .into_iter()
+            .find_map(|device_id| {
+                let keypair = &device_keys[device_id];
+                let trusted = roster.get_signer(&grant.scope_id, &DeviceId(device_id.clone()))?;
+                (trusted.ed25519_pub == keypair.ed25519_pub
+                    && trusted.mldsa_pub == keypair.mldsa_pub)
+                    .then_some((DeviceId(device_id.clone()), keypair))
+            })
+            .ok_or(KeyServiceError::UntrustedSigner)?;
+
+        let old_scope_key = state
+            .keyvault_materialized
+            .scope_keys
+            .get(&(grant.scope_id.0.clone(), grant.scope_epoch))
+            .ok_or(KeyServiceError::ScopeKeyMissing)?;
+        let old_aad = aad_resource_grant_wrap_v1(
+            &grant.scope_id.0,
+            &grant.resource_id.0,
+            grant.scope_epoch,
+            &grant.resource_key_id.0,
+            grant.aead,
+        )?;
+        let resource_key =
+            aead_decrypt::<Aes256Gcm>(old_scope_key, &old_aad, &grant.nonce, &grant.wrapped_key)
+                .map(Zeroizing::new)
+                .map_err(|_| KeyServiceError::KeyUnwrapFailed {
+                    kind: WrappedKeyKind::ResourceKey,
+                })?;
+
+        let (grant_seq, prev_hash) = roster.next_grant_link(&grant.scope_id);
+        let new_aad = aad_resource_grant_wrap_v1(
+            &grant.scope_id.0,
+            &grant.resource_id.0,
+            new_scope.1 .0,
+            &grant.resource_key_id.0,
+            grant.aead,
+        )?;
+        let nonce = self.entropy.random_bytes(12);
+        let mut reissued = ResourceGrantV1 {
+            v: 1,
+            grant_id: uuid_like(&self.entropy.random_bytes(16)),
+            scope_id: grant.scope_id.clone(),
+            grant_seq,
+            prev_hash: prev_hash.to_vec(),
+            scope_state_ref,
+            scope_epoch: new_scope.1 .0,
+            resource_id: grant.resource_id,
+            resource_key_id: grant.resource_key_id,
+            policy: grant.policy,
+            aead: grant.aead,
+            wrapped_key: aead_encrypt::<Aes256Gcm>(
+                &new_scope_key,
+                &new_aad,
+                &resource_key,
+                &nonce,
+            )?,
+            nonce,
+            signer_device_id: issuer_id,
+            sig_suite: SigCiphersuiteId::HybridSig1,
+            signature: Vec::new(),
+        };
+        let to_sign = reissued
+            .to_be_signed_bytes()
+            .map_err(KeyServiceError::from)?;
+        reissued.signature = hybrid_sign(SIG_RESOURCE_GRANT_V1, &to_sign, issuer)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+
+        let roster = &mut self
+            .state
+            .as_mut()
+            .ok_or(KeyServiceError::UnknownScope)?
+            .signer_roster;
+        roster.verify_and_update_grant_chain(&reissued)?;
+        Ok(ReissueGrantResponse {
+            grant: encode_resource_grant_v1(&reissued)?,
+            grant_seq,
+        })
     }
 
-    pub fn sign(
+    pub fn close_handle(
         &mut self,
         session_id: &SessionId,
-        data: &[u8],
-    ) -> Result<SignResponse, KeyServiceError> {
+        key_handle: &KeyHandle,
+    ) -> Result<(), KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let materialized = self.state.as_ref().ok_or(KeyServiceError::CryptoError(
-            "keyvault not loaded".to_string(),
-        ))?;
-        let signing = materialized
-            .keyvault_materialized
-            .device_signing_keys
-            .values()
-            .next()
-            .ok_or(KeyServiceError::CryptoError(
-                "no device signing key".to_string(),
-            ))?;
-        let sig =
-            hybrid_sign(data, signing).map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        Ok(SignResponse {
-            signature: sig,
-            ciphersuite: SigCiphersuiteId::HybridSig1,
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        session.remove_handle(key_handle);
+        Ok(())
+    }
+
+    /// Wraps the resource key behind `resource_key_handle` into a token that another session of
+    /// the same vault can redeem until it expires, limited to `request`'s AAD prefix and ops.
+    /// Tokens minted from a redeemed handle can only narrow its limits.
+    pub fn mint_capability(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        request: &CapabilityRequest,
+    ) -> Result<MintCapabilityResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        if request.ops.is_empty() || request.ttl_ms == 0 {
+            return Err(KeyServiceError::InvalidFormat(
+                "capability needs at least one op and a non-zero ttl".to_string(),
+            ));
+        }
+        let header = &self
+            .state
+            .as_ref()
+            .ok_or(KeyServiceError::VaultNotLoaded)?
+            .keyvault_header;
+        let (vault_id, user_id, aead) =
+            (header.vault_id.clone(), header.user_id.clone(), header.aead);
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let (resource_id, resource_key_id, key, ops, scope, restriction) = match session
+            .get_handle(resource_key_handle)
+        {
+            Some(HandleEntry::ResourceKey {
+                resource_id,
+                resource_key_id,
+                key,
+                ops,
+                scope,
+                restriction,
+                ratchet: None,
+                ..
+            }) => (
+                resource_id.clone(),
+                resource_key_id.clone(),
+                key.clone(),
+                *ops,
+                scope.clone(),
+                restriction.clone(),
+            ),
+            // A ratchet's chain key must not leave the handle.
+            Some(HandleEntry::ResourceKey { .. }) => return Err(KeyServiceError::CapabilityDenied),
+            _ => return Err(KeyServiceError::UnknownHandle),
+        };
+        if !ops.contains(&request.ops) {
+            return Err(KeyServiceError::CapabilityDenied);
+        }
+        if request.ops.encrypt {
+            if let Some(state) = &self.state {
+                state.signer_roster.ensure_epoch_active(scope.as_ref())?;
+            }
+        }
+        let mut expires_at_ms = now.saturating_add(request.ttl_ms);
+        if let Some(parent) = restriction {
+            if now > parent.expires_at_ms {
+                return Err(KeyServiceError::CapabilityExpired);
+            }
+            if !request.aad_prefix.starts_with(&parent.aad_prefix) {
+                return Err(KeyServiceError::CapabilityDenied);
+            }
+            expires_at_ms = expires_at_ms.min(parent.expires_at_ms);
+        }
+        let mut token = CapabilityTokenV1 {
+            v: 1,
+            vault_id,
+            resource_id,
+            resource_key_id,
+            aad_prefix: request.aad_prefix.clone(),
+            ops: request.ops,
+            issued_at_ms: now,
+            expires_at_ms,
+            aead,
+            nonce: self.entropy.random_bytes(12),
+            wrapped_key: Vec::new(),
+        };
+        let aad = aad_capability_token_wrap_v1(&user_id, &token)?;
+        token.wrapped_key =
+            aead_encrypt::<Aes256Gcm>(&session.vault_key, &aad, &key, &token.nonce)?;
+        Ok(MintCapabilityResponse {
+            token: encode_capability_token_v1(&token)?,
+            expires_at_ms,
         })
     }
 
-    pub fn verify(
+    /// Opens a handle from a token minted by [`Self::mint_capability`] in any session of this
+    /// vault. A token can be redeemed any number of times until it expires.
+    pub fn redeem_capability(
         &mut self,
-        scope_id: ScopeId,
-        signer_device_id: DeviceId,
-        data: &[u8],
-        signature: &[u8],
-        ciphersuite: SigCiphersuiteId,
-    ) -> Result<VerifyResponse, KeyServiceError> {
-        if ciphersuite != SigCiphersuiteId::HybridSig1 {
+        session_id: &SessionId,
+        token_bytes: &[u8],
+    ) -> Result<RedeemCapabilityResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let token = decode_capability_token_v1(token_bytes)?;
+        self.check_ids([
+            ("resourceId", token.resource_id.0.as_str()),
+            ("resourceKeyId", &token.resource_key_id.0),
+        ])?;
+        if token.v != 1 {
             return Err(KeyServiceError::InvalidFormat(
-                "unsupported signature suite".to_string(),
+                "unsupported capability token version".to_string(),
             ));
         }
-        let roster = self.state.as_ref().ok_or(KeyServiceError::UnknownScope)?;
-        let signer = roster
-            .signer_roster
-            .get_signer(&scope_id, &signer_device_id)
-            .ok_or(KeyServiceError::UntrustedSigner)?;
-        let ok = hybrid_verify(data, signature, signer);
-        Ok(VerifyResponse { ok })
+        let header = &self
+            .state
+            .as_ref()
+            .ok_or(KeyServiceError::VaultNotLoaded)?
+            .keyvault_header;
+        if token.vault_id != header.vault_id {
+            return Err(KeyServiceError::VaultKeyMismatch);
+        }
+        if now > token.expires_at_ms {
+            return Err(KeyServiceError::CapabilityExpired);
+        }
+        let aad = aad_capability_token_wrap_v1(&header.user_id, &token)?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let key =
+            aead_decrypt::<Aes256Gcm>(&session.vault_key, &aad, &token.nonce, &token.wrapped_key)
+                .map(Zeroizing::new)
+                .map_err(|_| KeyServiceError::KeyUnwrapFailed {
+                    kind: WrappedKeyKind::CapabilityToken,
+                })?;
+        let handle = session
+            .insert_handle(HandleEntry::ResourceKey {
+                resource_id: token.resource_id,
+                resource_key_id: token.resource_key_id,
+                key: SecretBytes::new(&key)
+                    .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?,
+                nonce_counter: None,
+                ops: token.ops,
+                scope: None,
+                restriction: Some(HandleRestriction {
+                    aad_prefix: token.aad_prefix,
+                    expires_at_ms: token.expires_at_ms,
+                }),
+                ratchet: None,
+            })
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        Ok(RedeemCapabilityResponse {
+            resource_key_handle: handle,
+            ops: token.ops,
+            expires_at_ms: token.expires_at_ms,
+        })
+    }
+
+    pub fn encrypt(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<EncryptResponse, KeyServiceError> {
+        let mut ciphertext =
+            Vec::with_capacity(CIPHERTEXT_ENVELOPE_HEADER_LEN + plaintext.len() + 16);
+        self.encrypt_into(
+            session_id,
+            resource_key_handle,
+            aad,
+            plaintext,
+            &mut ciphertext,
+        )?;
+        Ok(EncryptResponse { ciphertext })
+    }
+
+    /// Writes `version || aead || nonce || ct` into `out`, reusing its allocation.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bytes = plaintext.len()))
+    )]
+    pub fn encrypt_into(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        plaintext: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.encrypt_into_inner(session_id, resource_key_handle, aad, plaintext, out);
+        self.metrics_finish(MetricOp::Encrypt, started, result.is_ok());
+        self.note_error(MetricOp::Encrypt, &result);
+        result
+    }
+
+    fn encrypt_into_inner(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        plaintext: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        if plaintext.len() > self.config.policy.max_plaintext_bytes {
+            return Err(KeyServiceError::PayloadTooLarge);
+        }
+        self.check_capability(now, session_id, resource_key_handle, ENCRYPT_OP, aad)?;
+        let ratcheted = matches!(
+            self.sessions
+                .get_mut(session_id)
+                .and_then(|session| session.get_handle(resource_key_handle)),
+            Some(HandleEntry::ResourceKey {
+                ratchet: Some(_),
+                ..
+            })
+        );
+        // Every ratchet message has its own key, so random nonces need no counter.
+        let nonce = match self.config.policy.nonce_mode {
+            NonceMode::Counter if !ratcheted => {
+                self.next_counter_nonce(session_id, resource_key_handle)?
+            }
+            _ => self.entropy.random_bytes(12),
+        };
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        match session.get_handle_mut(resource_key_handle) {
+            Some(HandleEntry::ResourceKey {
+                key,
+                ratchet: Some(ratchet),
+                ..
+            }) => {
+                if ratchet.next_message >= MAX_RATCHET_MESSAGES {
+                    return Err(KeyServiceError::RatchetExhausted);
+                }
+                let (message_key, chain_key) =
+                    ratchet_step(key).map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+                *key = SecretBytes::new(&chain_key)
+                    .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+                let message = ratchet.next_message;
+                ratchet.next_message += 1;
+                seal_ratchet_payload(
+                    &message_key,
+                    &ratchet.chain_id,
+                    message,
+                    aad,
+                    &nonce,
+                    plaintext,
+                    out,
+                )
+            }
+            Some(HandleEntry::ResourceKey { key, .. }) => {
+                seal_resource_payload(key, aad, &nonce, plaintext, out)
+            }
+            _ => Err(KeyServiceError::UnknownHandle),
+        }
+    }
+
+    /// Turns `resource_key_handle` into a forward-secret ratchet: the handle swaps the
+    /// resource key for the first key of a fresh chain (see [`crate::ratchet`]), and each
+    /// later `encrypt` advances it. Ciphertexts use framing v2 and open with any decrypt
+    /// handle for the same resource key. The handle becomes encrypt-only and cannot stream,
+    /// derive sub-keys, or mint capabilities; open the resource again for those.
+    pub fn enable_resource_ratchet(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+    ) -> Result<(), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let mut chain_id = [0u8; RATCHET_CHAIN_ID_LEN];
+        chain_id.copy_from_slice(&self.entropy.random_bytes(RATCHET_CHAIN_ID_LEN));
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let Some(HandleEntry::ResourceKey {
+            key,
+            nonce_counter,
+            ops,
+            ratchet,
+            ..
+        }) = session.get_handle_mut(resource_key_handle)
+        else {
+            return Err(KeyServiceError::UnknownHandle);
+        };
+        if !ops.encrypt || ratchet.is_some() {
+            return Err(KeyServiceError::CapabilityDenied);
+        }
+        let chain_key = ratchet_chain_start(key, &chain_id)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        *key = SecretBytes::new(&chain_key)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        *nonce_counter = None;
+        *ops = CapabilityOps {
+            encrypt: true,
+            decrypt: false,
+        };
+        *ratchet = Some(ResourceRatchet {
+            chain_id,
+            next_message: 0,
+        });
+        Ok(())
+    }
+
+    pub fn decrypt(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<DecryptResponse, KeyServiceError> {
+        let mut plaintext = Vec::with_capacity(ciphertext.len());
+        self.decrypt_into(
+            session_id,
+            resource_key_handle,
+            aad,
+            ciphertext,
+            &mut plaintext,
+        )?;
+        Ok(DecryptResponse { plaintext })
+    }
+
+    /// Writes the plaintext into `out`, reusing its allocation; `out` is left empty on failure.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(bytes = ciphertext.len()))
+    )]
+    pub fn decrypt_into(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        ciphertext: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.decrypt_into_inner(session_id, resource_key_handle, aad, ciphertext, out);
+        self.metrics_finish(MetricOp::Decrypt, started, result.is_ok());
+        self.note_error(MetricOp::Decrypt, &result);
+        result
+    }
+
+    fn decrypt_into_inner(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        ciphertext: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        out.clear();
+        // Checked before copying so an oversized input is never duplicated on the heap.
+        let max_ciphertext =
+            self.config.policy.max_plaintext_bytes + CIPHERTEXT_RATCHET_HEADER_LEN + 16;
+        if ciphertext.len() > max_ciphertext {
+            return Err(KeyServiceError::PayloadTooLarge);
+        }
+        self.check_capability(now, session_id, resource_key_handle, DECRYPT_OP, aad)?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let resource_key = match session.get_handle(resource_key_handle) {
+            Some(HandleEntry::ResourceKey { key, .. }) => key,
+            _ => return Err(KeyServiceError::UnknownHandle),
+        };
+        open_resource_payload(resource_key, aad, ciphertext, out)
+    }
+
+    pub fn encrypt_init(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+    ) -> Result<EncryptInitResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_capability(now, session_id, resource_key_handle, ENCRYPT_OP, aad)?;
+        let header_random = self.entropy.random_bytes(STREAM_HEADER_RANDOM_LEN);
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let (encryptor, header) = match session.get_handle(resource_key_handle) {
+            Some(HandleEntry::ResourceKey {
+                key, ratchet: None, ..
+            }) => StreamEncryptor::new(key, aad, &header_random)?,
+            // Streams do not ratchet; a ratcheted handle no longer holds the resource key.
+            Some(HandleEntry::ResourceKey { .. }) => return Err(KeyServiceError::CapabilityDenied),
+            _ => return Err(KeyServiceError::UnknownHandle),
+        };
+        let stream_id = session.insert_stream(StreamEntry::Encrypt(encryptor))?;
+        Ok(EncryptInitResponse { stream_id, header })
+    }
+
+    pub fn encrypt_push(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<EncryptResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let ciphertext = match session.get_stream_mut(stream_id) {
+            Some(StreamEntry::Encrypt(encryptor)) => encryptor.push(chunk),
+            _ => return Err(KeyServiceError::UnknownStream),
+        };
+        match ciphertext {
+            Ok(ciphertext) => Ok(EncryptResponse { ciphertext }),
+            Err(err) => {
+                session.remove_stream(stream_id);
+                Err(err.into())
+            }
+        }
+    }
+
+    pub fn encrypt_finish(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<EncryptResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        if !matches!(
+            session.get_stream_mut(stream_id),
+            Some(StreamEntry::Encrypt(_))
+        ) {
+            return Err(KeyServiceError::UnknownStream);
+        }
+        match session.remove_stream(stream_id) {
+            Some(StreamEntry::Encrypt(encryptor)) => Ok(EncryptResponse {
+                ciphertext: encryptor.finish(chunk)?,
+            }),
+            _ => Err(KeyServiceError::UnknownStream),
+        }
+    }
+
+    pub fn decrypt_init(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        header: &[u8],
+    ) -> Result<DecryptInitResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_capability(now, session_id, resource_key_handle, DECRYPT_OP, aad)?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let decryptor = match session.get_handle(resource_key_handle) {
+            Some(HandleEntry::ResourceKey { key, .. }) => StreamDecryptor::new(key, aad, header)?,
+            _ => return Err(KeyServiceError::UnknownHandle),
+        };
+        let stream_id = session.insert_stream(StreamEntry::Decrypt(decryptor))?;
+        Ok(DecryptInitResponse { stream_id })
+    }
+
+    pub fn decrypt_push(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<DecryptResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let plaintext = match session.get_stream_mut(stream_id) {
+            Some(StreamEntry::Decrypt(decryptor)) => decryptor.push(chunk),
+            _ => return Err(KeyServiceError::UnknownStream),
+        };
+        match plaintext {
+            Ok(plaintext) => Ok(DecryptResponse { plaintext }),
+            Err(_) => {
+                session.remove_stream(stream_id);
+                Err(KeyServiceError::DecryptFailed)
+            }
+        }
+    }
+
+    pub fn decrypt_finish(
+        &mut self,
+        session_id: &SessionId,
+        stream_id: &StreamId,
+        chunk: &[u8],
+    ) -> Result<DecryptResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        if !matches!(
+            session.get_stream_mut(stream_id),
+            Some(StreamEntry::Decrypt(_))
+        ) {
+            return Err(KeyServiceError::UnknownStream);
+        }
+        match session.remove_stream(stream_id) {
+            Some(StreamEntry::Decrypt(decryptor)) => {
+                let plaintext = decryptor
+                    .finish(chunk)
+                    .map_err(|_| KeyServiceError::DecryptFailed)?;
+                Ok(DecryptResponse { plaintext })
+            }
+            _ => Err(KeyServiceError::UnknownStream),
+        }
+    }
+
+    pub fn sign(
+        &mut self,
+        session_id: &SessionId,
+        data: &[u8],
+    ) -> Result<SignResponse, KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.sign_inner(session_id, SIG_APP_PAYLOAD_V1, data);
+        self.metrics_finish(MetricOp::Sign, started, result.is_ok());
+        self.note_error(MetricOp::Sign, &result);
+        result
+    }
+
+    /// Signs the `to_be_signed_bytes` of a `format` message, bound to that format's context.
+    /// [`Self::sign`] is for application payloads and cannot produce these signatures.
+    pub fn sign_format(
+        &mut self,
+        session_id: &SessionId,
+        format: SignedFormat,
+        to_be_signed: &[u8],
+    ) -> Result<SignResponse, KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.sign_inner(session_id, format.sig_context(), to_be_signed);
+        self.metrics_finish(MetricOp::Sign, started, result.is_ok());
+        self.note_error(MetricOp::Sign, &result);
+        result
+    }
+
+    fn sign_inner(
+        &mut self,
+        session_id: &SessionId,
+        context: SigContext,
+        data: &[u8],
+    ) -> Result<SignResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let (device_id, signing) = self.local_signing_key()?;
+        let materialized = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        if materialized
+            .keyvault_materialized
+            .cosign_shares
+            .contains_key(&device_id.0)
+        {
+            return Err(KeyServiceError::CosignRequired);
+        }
+        let sig = hybrid_sign(context, data, signing)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        Ok(SignResponse {
+            signature: sig,
+            ciphersuite: SigCiphersuiteId::HybridSig1,
+        })
+    }
+
+    /// Starts co-signing for this device: a new Ed25519 key share and the enrollment request
+    /// for the server. Needs a step-up session.
+    pub fn start_cosign_enrollment(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(CosignEnrollment, Vec<u8>), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_step_up(session_id, SensitiveOperation::CosignEnrollment)?;
+        let (device_id, _) = self.local_signing_key()?;
+        let share = CosignKeyShare::generate(&self.entropy)?;
+        let proof = share.prove_possession(device_id.0.as_bytes(), &self.entropy)?;
+        let request = encode_cosign_enroll_request_v1(&CosignEnrollRequestV1 {
+            v: 1,
+            device_id: device_id.clone(),
+            share_pub: share.public.to_vec(),
+            proof: proof.to_vec(),
+        })?;
+        Ok((CosignEnrollment { device_id, share }, request))
+    }
+
+    /// Checks the server's share and stores the device share. From then on the device's
+    /// `ed25519_pub` is the group key, and [`Self::sign`] and [`Self::sign_format`] fail with
+    /// `CosignRequired`; sign with [`Self::start_cosign`] instead.
+    pub fn finish_cosign_enrollment(
+        &mut self,
+        session_id: &SessionId,
+        enrollment: CosignEnrollment,
+        response: &[u8],
+    ) -> Result<CosignEnrollResponse, KeyServiceError> {
+        let header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let response = decode_cosign_enroll_response_v1(response)?;
+        if response.v != 1
+            || !cosign_verify_possession(
+                &response.share_pub,
+                enrollment.device_id.0.as_bytes(),
+                &response.proof,
+            )
+        {
+            return Err(KeyServiceError::CosignServerInvalid);
+        }
+        let server_pub: [u8; 32] = response
+            .share_pub
+            .as_slice()
+            .try_into()
+            .map_err(|_| KeyServiceError::CosignServerInvalid)?;
+        let group_pub = cosign_group_key(&enrollment.share.public, &server_pub)
+            .map_err(|_| KeyServiceError::CosignServerInvalid)?;
+        let device_share = CosignDeviceShare {
+            share: enrollment.share,
+            server_pub,
+            group_pub,
+        };
+
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_cosign_share_record(&record_id, &enrollment.device_id.0, &device_share);
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .cosign_shares
+            .insert(enrollment.device_id.0.clone(), device_share);
+        self.record_audit(
+            AuditEventKind::SignerChange,
+            format!(
+                "cosign-enroll device={} group={}",
+                enrollment.device_id.0,
+                hex::encode(group_pub)
+            ),
+        )?;
+        Ok(CosignEnrollResponse {
+            device_id: enrollment.device_id,
+            group_pub: group_pub.to_vec(),
+        })
+    }
+
+    /// Co-signing counterpart of [`Self::sign`]: returns the pending attempt and the request
+    /// for the server.
+    pub fn start_cosign(
+        &mut self,
+        session_id: &SessionId,
+        data: &[u8],
+    ) -> Result<(CosignSigning, Vec<u8>), KeyServiceError> {
+        self.start_cosign_inner(session_id, SIG_APP_PAYLOAD_V1, data)
+    }
+
+    /// Co-signing counterpart of [`Self::sign_format`].
+    pub fn start_cosign_format(
+        &mut self,
+        session_id: &SessionId,
+        format: SignedFormat,
+        to_be_signed: &[u8],
+    ) -> Result<(CosignSigning, Vec<u8>), KeyServiceError> {
+        self.start_cosign_inner(session_id, format.sig_context(), to_be_signed)
+    }
+
+    fn start_cosign_inner(
+        &mut self,
+        session_id: &SessionId,
+        context: SigContext,
+        data: &[u8],
+    ) -> Result<(CosignSigning, Vec<u8>), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let (device_id, _) = self.local_signing_key()?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let share = state
+            .keyvault_materialized
+            .cosign_shares
+            .get(&device_id.0)
+            .ok_or(KeyServiceError::DeviceKeyMissing)?;
+        let (nonces, commitment) = cosign_commit(&share.share, &self.entropy)?;
+        let request = encode_cosign_sign_request_v1(&CosignSignRequestV1 {
+            v: 1,
+            device_id: device_id.clone(),
+            group_pub: share.group_pub.to_vec(),
+            message: context.frame(data),
+            commitment,
+        })?;
+        let pending = CosignSigning {
+            device_id,
+            group_pub: share.group_pub,
+            context,
+            data: data.to_vec(),
+            nonces,
+            commitment,
+        };
+        Ok((pending, request))
+    }
+
+    /// Checks the server's share, adds the device share and the local ML-DSA signature, and
+    /// returns the hybrid signature, verified under the group key before it is released.
+    pub fn finish_cosign(
+        &mut self,
+        session_id: &SessionId,
+        pending: CosignSigning,
+        response: &[u8],
+    ) -> Result<SignResponse, KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.finish_cosign_inner(session_id, pending, response);
+        self.metrics_finish(MetricOp::Sign, started, result.is_ok());
+        self.note_error(MetricOp::Sign, &result);
+        result
+    }
+
+    fn finish_cosign_inner(
+        &mut self,
+        session_id: &SessionId,
+        pending: CosignSigning,
+        response: &[u8],
+    ) -> Result<SignResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let response = decode_cosign_sign_response_v1(response)?;
+        if response.v != 1 {
+            return Err(KeyServiceError::CosignServerInvalid);
+        }
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let materialized = &state.keyvault_materialized;
+        let share = materialized
+            .cosign_shares
+            .get(&pending.device_id.0)
+            .filter(|share| share.group_pub == pending.group_pub)
+            .ok_or(KeyServiceError::DeviceKeyMissing)?;
+        let keypair = materialized
+            .device_signing_keys
+            .get(&pending.device_id.0)
+            .ok_or(KeyServiceError::DeviceKeyMissing)?;
+
+        let framed = pending.context.frame(&pending.data);
+        let transcript = CosignTranscript::new(
+            &share.group_pub,
+            &framed,
+            &pending.commitment,
+            &response.commitment,
+        )
+        .map_err(|_| KeyServiceError::CosignServerInvalid)?;
+        if !transcript.verify_share(
+            CosignParticipant::Server,
+            &share.server_pub,
+            &response.commitment,
+            &response.share,
+        ) {
+            return Err(KeyServiceError::CosignServerInvalid);
+        }
+        let device_z =
+            transcript.sign_share(CosignParticipant::Device, pending.nonces, &share.share)?;
+        let ed_sig = transcript.aggregate(&device_z, &response.share)?;
+        let signature = hybrid_sign_with_ed25519(&framed, &ed_sig, keypair)?;
+
+        let signer = SignerKeys {
+            sig_suite: SigCiphersuiteId::HybridSig1,
+            ed25519_pub: share.group_pub.to_vec(),
+            mldsa_pub: keypair.mldsa_pub.clone(),
+        };
+        if !hybrid_verify(pending.context, &pending.data, &signature, &signer) {
+            return Err(KeyServiceError::CosignServerInvalid);
+        }
+        Ok(SignResponse {
+            signature,
+            ciphersuite: SigCiphersuiteId::HybridSig1,
+        })
+    }
+
+    /// The device key `sign` uses: the first one in the vault.
+    fn local_signing_key(&self) -> Result<(DeviceId, &HybridSignatureKeypair), KeyServiceError> {
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .device_signing_keys
+            .iter()
+            .next()
+            .map(|(device_id, keypair)| (DeviceId(device_id.clone()), keypair))
+            .ok_or(KeyServiceError::DeviceKeyMissing)
+    }
+
+    pub fn verify(
+        &mut self,
+        scope_id: ScopeId,
+        signer_device_id: DeviceId,
+        data: &[u8],
+        signature: &[u8],
+        ciphersuite: SigCiphersuiteId,
+    ) -> Result<VerifyResponse, KeyServiceError> {
+        let started = self.metrics_start();
+        let result = self.verify_inner(scope_id, signer_device_id, data, signature, ciphersuite);
+        self.metrics_finish(MetricOp::Verify, started, result.is_ok());
+        self.note_error(MetricOp::Verify, &result);
+        result
+    }
+
+    fn verify_inner(
+        &mut self,
+        scope_id: ScopeId,
+        signer_device_id: DeviceId,
+        data: &[u8],
+        signature: &[u8],
+        ciphersuite: SigCiphersuiteId,
+    ) -> Result<VerifyResponse, KeyServiceError> {
+        if ciphersuite != SigCiphersuiteId::HybridSig1 {
+            return Err(KeyServiceError::UnsupportedSuite);
+        }
+        let now = self.clock.now_ms();
+        let roster = self.state.as_ref().ok_or(KeyServiceError::UnknownScope)?;
+        let signer = roster
+            .signer_roster
+            .resolve_signer(&scope_id, &signer_device_id, now)?;
+        let ok = hybrid_verify_or_unbound(
+            SIG_APP_PAYLOAD_V1,
+            data,
+            signature,
+            signer,
+            self.config.policy.accept_unbound_signatures,
+        );
+        Ok(VerifyResponse { ok })
+    }
+
+    pub fn get_user_public_key(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<UserPublicKeyResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let recipient = self.load_user_keypair()?;
+        Ok(UserPublicKeyResponse {
+            kem: KemCiphersuiteId::HybridKem1,
+            public_bytes: recipient.public_bytes.clone(),
+            fingerprint: fingerprint_bytes(&recipient.public_bytes),
+            fingerprint_hex: fingerprint_bytes_hex(&recipient.public_bytes),
+            xwing_public_bytes: encode_xwing_public_key(&user_keypair_public(recipient)),
+        })
+    }
+
+    /// Checks that the transparency log holds `public_bytes` as `user_id`'s key, before a
+    /// caller wraps scope keys to it. Fails with `TransparencyCheckFailed`.
+    pub fn verify_user_public_key(
+        &mut self,
+        session_id: &SessionId,
+        user_id: &UserId,
+        public_bytes: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_ids([("userId", user_id.0.as_str())])?;
+        self.check_transparency(&TransparencyLeaf::UserPublicKey {
+            user_id: user_id.clone(),
+            public_bytes: public_bytes.to_vec(),
+        })
+    }
+
+    /// Adds `user_id`'s public KEM key to the recipient key directory, replacing any key held
+    /// for the user. `expected_fingerprint` is the hex fingerprint the user shared out of band
+    /// ([`UserPublicKeyResponse::fingerprint_hex`]), and the key must pass the transparency
+    /// check of [`Self::verify_user_public_key`]. [`Self::seal_key_envelope`] then finds the key
+    /// by user id.
+    pub fn add_contact(
+        &mut self,
+        session_id: &SessionId,
+        user_id: &UserId,
+        public_bytes: &[u8],
+        expected_fingerprint: &str,
+    ) -> Result<Contact, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_ids([("userId", user_id.0.as_str())])?;
+        self.ensure_writable(session_id)?;
+        if !ct_eq(
+            fingerprint_bytes_hex(public_bytes).as_bytes(),
+            expected_fingerprint.as_bytes(),
+        ) {
+            return Err(KeyServiceError::FingerprintMismatch);
+        }
+        decode_user_public_bytes(public_bytes)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        self.check_transparency(&TransparencyLeaf::UserPublicKey {
+            user_id: user_id.clone(),
+            public_bytes: public_bytes.to_vec(),
+        })?;
+        self.store_contact(session_id, user_id, Some(public_bytes), None)?;
+        self.get_contact(session_id, user_id)
+    }
+
+    /// Drops `user_id` from the recipient key directory; false when it held no key for the user.
+    pub fn remove_contact(
+        &mut self,
+        session_id: &SessionId,
+        user_id: &UserId,
+    ) -> Result<bool, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.check_ids([("userId", user_id.0.as_str())])?;
+        self.ensure_writable(session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        if !state
+            .keyvault_materialized
+            .contacts
+            .contains_key(&user_id.0)
+        {
+            return Ok(false);
+        }
+        self.store_contact(session_id, user_id, None, None)?;
+        Ok(true)
+    }
+
+    /// The directory's key for `user_id`; fails with `UnknownContact` when there is none.
+    pub fn get_contact(
+        &mut self,
+        session_id: &SessionId,
+        user_id: &UserId,
+    ) -> Result<Contact, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .contacts
+            .get(&user_id.0)
+            .map(|contact| contact_entry(&user_id.0, contact))
+            .ok_or_else(|| KeyServiceError::UnknownContact {
+                user_id: user_id.clone(),
+            })
+    }
+
+    pub fn list_contacts(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<ListContactsResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let mut contacts: Vec<Contact> = state
+            .keyvault_materialized
+            .contacts
+            .iter()
+            .map(|(user_id, contact)| contact_entry(user_id, contact))
+            .collect();
+        contacts.sort_by(|a, b| a.user_id.0.cmp(&b.user_id.0));
+        Ok(ListContactsResponse { contacts })
+    }
+
+    /// Hybrid signing public keys for every device key in the vault, sorted by device id.
+    pub fn get_device_public_keys(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<DevicePublicKeysResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let mut devices: Vec<DevicePublicKey> = state
+            .keyvault_materialized
+            .device_signing_keys
+            .iter()
+            .map(|(device_id, keypair)| {
+                // A co-signing device is known to peers by its group key.
+                let ed25519_pub = match state.keyvault_materialized.cosign_shares.get(device_id) {
+                    Some(share) => share.group_pub.to_vec(),
+                    None => keypair.ed25519_pub.clone(),
+                };
+                let signer = SignerKeys {
+                    sig_suite: SigCiphersuiteId::HybridSig1,
+                    ed25519_pub,
+                    mldsa_pub: keypair.mldsa_pub.clone(),
+                };
+                DevicePublicKey {
+                    device_id: DeviceId(device_id.clone()),
+                    fingerprint: fingerprint_signer(&signer),
+                    signer,
+                }
+            })
+            .collect();
+        devices.sort_by(|a, b| a.device_id.0.cmp(&b.device_id.0));
+        Ok(DevicePublicKeysResponse { devices })
+    }
+
+    /// Returns the audit log in seq order without verifying it.
+    pub fn read_audit_log(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<Vec<AuditEntryV1>, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.load_audit_entries()
+    }
+
+    /// Verifies the audit chain against this vault's device signing keys.
+    pub fn verify_audit_log(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<AuditVerifyReport, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let entries = self.load_audit_entries()?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let signing_keys = &state.keyvault_materialized.device_signing_keys;
+        let mut report = verify_audit_chain(
+            &entries,
+            |device_id| {
+                signing_keys.get(&device_id.0).map(|keypair| SignerKeys {
+                    sig_suite: SigCiphersuiteId::HybridSig1,
+                    ed25519_pub: keypair.ed25519_pub.clone(),
+                    mldsa_pub: keypair.mldsa_pub.clone(),
+                })
+            },
+            self.config.policy.accept_unbound_signatures,
+        )?;
+        // The head pins the last entry, so dropping trailing entries is detected too.
+        let (_, head_hash) = self.load_audit_head()?;
+        let tail_hash = match entries.last() {
+            Some(entry) => entry.entry_hash()?,
+            None => Vec::new(),
+        };
+        if report.ok && !ct_eq(&tail_hash, &head_hash) {
+            report.ok = false;
+            report.first_invalid_seq = Some(entries.len() as u64);
+        }
+        Ok(report)
+    }
+
+    /// Re-checks stored KeyVault integrity without exporting or changing anything.
+    pub fn verify_keyvault(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<VerifyKeyVaultResponse, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let header = self.load_header()?;
+        let records = self.load_all_record_containers()?;
+        let vault_key = self.session_vault_key(session_id)?;
+        let chain = KeyVaultState::verify_containers(&header, vault_key, &records)?;
+
+        let mut header_records_match = true;
+        for embedded in &header.records {
+            let stored = records
+                .iter()
+                .find(|record| record.record_id == embedded.record_id);
+            header_records_match &= match stored {
+                Some(stored) => ct_eq(
+                    &encode_keyvault_record_container_v1(stored)?,
+                    &encode_keyvault_record_container_v1(embedded)?,
+                ),
+                None => false,
+            };
+        }
+
+        let head_marker_match = match self.load_head_marker(&header, vault_key) {
+            Ok(None) => true,
+            Ok(Some((0, _))) => true,
+            Ok(Some((seen_seq, seen_hash))) => {
+                match records.iter().find(|record| record.seq == seen_seq) {
+                    Some(record) if seen_seq <= chain.head_seq => ct_eq(
+                        &sha256(&encode_keyvault_record_container_v1(record)?),
+                        &seen_hash,
+                    ),
+                    _ => false,
+                }
+            }
+            Err(_) => false,
+        };
+
+        let problem = chain.problem.or_else(|| {
+            if !header_records_match {
+                Some("header records differ from stored records".to_string())
+            } else if !head_marker_match {
+                Some("head marker not on stored chain".to_string())
+            } else {
+                None
+            }
+        });
+        Ok(VerifyKeyVaultResponse {
+            ok: problem.is_none(),
+            record_count: chain.record_count,
+            head_seq: chain.head_seq,
+            first_invalid_seq: chain.first_invalid_seq,
+            problem,
+            header_records_match,
+            head_marker_match,
+        })
+    }
+
+    pub fn init_identity(
+        &mut self,
+        session_id: &SessionId,
+        device_id: &DeviceId,
+    ) -> Result<(), KeyServiceError> {
+        self.check_ids([("deviceId", device_id.0.as_str())])?;
+        let header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+
+        let (uk_recipient, uk_priv_bytes) =
+            generate_user_keypair().map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        let uk_pub = uk_recipient.public_bytes.clone();
+        let device_signer = generate_device_signing_keypair().map_err(KeyServiceError::from)?;
+
+        let user_record_id = uuid_like(&self.entropy.random_bytes(16));
+        let user_record = make_store_user_key_record(&user_record_id, &uk_priv_bytes, &uk_pub);
+        let device_record_id = uuid_like(&self.entropy.random_bytes(16));
+        let device_record = make_store_device_signing_key_record(
+            &device_record_id,
+            &device_id.0,
+            &device_signer.ed25519_priv,
+            &device_signer.ed25519_pub,
+            &device_signer.mldsa_priv,
+            &device_signer.mldsa_pub,
+            SigCiphersuiteId::HybridSig1,
+        );
+
+        self.append_and_persist_record(session_id, &header, &user_record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state.keyvault_materialized.user_key.replace(uk_recipient);
+
+        self.append_and_persist_record(session_id, &header, &device_record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .device_signing_keys
+            .insert(device_id.0.clone(), device_signer);
+
+        Ok(())
+    }
+
+    fn finish_unlock(
+        &mut self,
+        header: KeyVaultHeaderV1,
+        vault_key: Zeroizing<Vec<u8>>,
+        assurance: SessionAssurance,
+        kind: SessionKind,
+        read_only: bool,
+        cancel: &CancellationToken,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let (state, materialized) = self.load_keyvault_state(&header, &vault_key, cancel)?;
+        let seen_head = self.load_head_marker(&header, &vault_key)?;
+        check_not_rolled_back(&state, seen_head.as_ref())?;
+        if seen_head.as_ref().map(|(seq, _)| *seq) != Some(state.head_seq) {
+            self.store_head_marker(&header, &state, &vault_key)?;
+        }
+        let state = KeyServiceState {
+            keyvault_header: header,
+            keyvault_state: state,
+            keyvault_materialized: materialized,
+            signer_roster: SignerRoster::new(self.config.policy.max_scope_state_refs_per_scope),
+            salvage: None,
+        };
+        self.open_session(state, vault_key, assurance, kind, read_only)
+    }
+
+    /// Installs an already loaded keyvault and issues a session for it.
+    fn open_session(
+        &mut self,
+        state: KeyServiceState,
+        vault_key: Zeroizing<Vec<u8>>,
+        assurance: SessionAssurance,
+        kind: SessionKind,
+        read_only: bool,
+    ) -> Result<UnlockResponse, KeyServiceError> {
+        let vault_key = SecretBytes::new(&vault_key)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        let now = self.clock.now_ms();
+        self.check_clock(now)?;
+        let ttl = match kind {
+            SessionKind::Normal => self.config.policy.normal_session_ttl_ms,
+            SessionKind::StepUp => self.config.policy.step_up_session_ttl_ms,
+        };
+        let expires_at_ms = session_expiry(now, ttl)?;
+        let session_id = SessionId(hex_id(&self.entropy.random_bytes(16)));
+        let mut session = Session::new(
+            session_id.clone(),
+            now,
+            expires_at_ms,
+            kind,
+            assurance,
+            vault_key,
+        );
+        session.max_handles = self.config.policy.max_handles_per_session;
+        let read_only = read_only || self.config.policy.read_only_sessions;
+        session.read_only = read_only;
+
+        let materialized = &state.keyvault_materialized;
+        let has_user_key = materialized.user_key.is_some();
+        let mut device_ids: Vec<DeviceId> = materialized
+            .device_signing_keys
+            .keys()
+            .cloned()
+            .map(DeviceId)
+            .collect();
+        device_ids.sort_by(|a, b| a.0.cmp(&b.0));
+        // The audit entry is signed with the newly loaded keys, so install the state first and
+        // put back the previous one if the entry cannot be written; the session only goes live
+        // once the unlock has been recorded.
+        let previous = self.state.replace(state);
+        let was_stale = std::mem::replace(&mut self.stale, false);
+        if let Err(err) = self.record_audit(
+            AuditEventKind::Unlock,
+            format!("assurance={}", assurance_label(assurance)),
+        ) {
+            self.state = previous;
+            self.stale = was_stale;
+            return Err(err);
+        }
+        self.sessions.insert(session_id.clone(), session);
+        self.schedule_timer();
+        self.check_invariants()?;
+
+        Ok(UnlockResponse {
+            session_id,
+            issued_at_ms: now,
+            expires_at_ms,
+            kind,
+            assurance,
+            has_user_key,
+            device_ids,
+            read_only,
+        })
+    }
+
+    fn enforce_passphrase_policy(&self, passphrase_utf8: &[u8]) -> Result<(), KeyServiceError> {
+        let Some(min_score) = self.config.policy.min_passphrase_score else {
+            return Ok(());
+        };
+        #[cfg(feature = "passphrase-strength")]
+        {
+            let strength = crate::strength::estimate_passphrase_strength(passphrase_utf8)?;
+            if strength.score < min_score {
+                return Err(KeyServiceError::WeakPassphrase {
+                    score: strength.score,
+                    min_score,
+                });
+            }
+            Ok(())
+        }
+        #[cfg(not(feature = "passphrase-strength"))]
+        {
+            // Refuse rather than silently skip a configured minimum.
+            let _ = (passphrase_utf8, min_score);
+            Err(KeyServiceError::InvalidFormat(
+                "min_passphrase_score requires the passphrase-strength feature".to_string(),
+            ))
+        }
+    }
+
+    fn verify_attestation(
+        &self,
+        attestation_object: &[u8],
+        challenge: &[u8; 32],
+        policy: &AttestationPolicy,
+        now_ms: u64,
+    ) -> Result<VerifiedAttestation, KeyServiceError> {
+        #[cfg(feature = "attestation")]
+        {
+            verify_device_attestation(
+                attestation_object,
+                challenge,
+                policy,
+                now_ms,
+                &self.cbor_limits(),
+            )
+            .map_err(|reason| KeyServiceError::AttestationFailed { reason })
+        }
+        #[cfg(not(feature = "attestation"))]
+        {
+            // Refuse rather than let a scope's policy go unenforced.
+            let _ = (attestation_object, challenge, policy, now_ms);
+            Err(KeyServiceError::AttestationFailed {
+                reason: "attestation verification needs the attestation feature",
+            })
+        }
+    }
+
+    /// Refuses encryption under `scope`'s key once past its rotation deadline, then counts the
+    /// operation. Handles without a scope, and scopes without a policy, are not tracked.
+    fn enforce_rotation_policy(
+        &mut self,
+        now: u64,
+        scope: Option<(ScopeId, ScopeEpoch)>,
+    ) -> Result<(), KeyServiceError> {
+        let Some((scope_id, scope_epoch)) = scope else {
+            return Ok(());
+        };
+        let Some(policy) = self.state.as_ref().and_then(|state| {
+            state
+                .keyvault_materialized
+                .rotation_policies
+                .get(&scope_id.0)
+                .copied()
+        }) else {
+            return Ok(());
+        };
+        if let Some(due) = self.rotation_status(now, &scope_id, scope_epoch, &policy)? {
+            if now > due.hard_deadline_ms {
+                return Err(KeyServiceError::RotationOverdue {
+                    scope_id,
+                    scope_epoch,
+                });
+            }
+        }
+        let Some(max_operations) = policy.max_operations else {
+            return Ok(());
+        };
+        let lookup = scope_key_lookup_key(&scope_id, scope_epoch);
+        let usage = match self.rotation_usage.remove(&lookup) {
+            Some(usage) => usage,
+            None => self.load_rotation_usage(&scope_id, scope_epoch)?,
+        };
+        let usage = self.rotation_usage.entry(lookup).or_insert(usage);
+        let mut dirty = false;
+        if usage.next == usage.leased_until {
+            usage.leased_until = usage.next.saturating_add(ROTATION_OPS_LEASE);
+            dirty = true;
+        }
+        usage.next += 1;
+        if usage.due_at_ms.is_none() && usage.next >= max_operations {
+            usage.due_at_ms = Some(now);
+            dirty = true;
+        }
+        if dirty {
+            let mut entries = vec![(0, crate::cbor::cbor_uint(usage.leased_until))];
+            if let Some(due_at_ms) = usage.due_at_ms {
+                entries.push((1, crate::cbor::cbor_uint(due_at_ms)));
+            }
+            let bytes = encode_canonical_value(&crate::cbor::cbor_map(entries))?;
+            self.storage
+                .put(
+                    "keyvault",
+                    &rotation_usage_key(&scope_id, scope_epoch),
+                    &bytes,
+                )
+                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        }
+        Ok(())
+    }
+
+    /// Encrypt count of a scope key as stored; a fresh count starts at the stored lease, so
+    /// operations leased by an earlier instance count as used.
+    fn load_rotation_usage(
+        &self,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+    ) -> Result<RotationUsage, KeyServiceError> {
+        let stored = self
+            .storage
+            .get("keyvault", &rotation_usage_key(scope_id, scope_epoch))
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        let Some(bytes) = stored else {
+            return Ok(RotationUsage {
+                next: 0,
+                leased_until: 0,
+                due_at_ms: None,
+            });
+        };
+        let value = decode_canonical_value(&bytes, &CborLimits::default())?;
+        let map = crate::cbor::as_map(&value)?;
+        let leased_until = crate::cbor::req_uint(map, 0)?;
+        Ok(RotationUsage {
+            next: leased_until,
+            leased_until,
+            due_at_ms: crate::cbor::opt_uint(map, 1)?,
+        })
+    }
+
+    /// Why and since when the key of `scope_id` at `scope_epoch` is due under `policy`, if it is.
+    fn rotation_status(
+        &self,
+        now: u64,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+        policy: &ScopeRotationPolicy,
+    ) -> Result<Option<DueRotation>, KeyServiceError> {
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let lookup = scope_key_lookup_key(scope_id, scope_epoch);
+        let (operations, ops_due_at) = match self.rotation_usage.get(&lookup) {
+            Some(usage) => (usage.next, usage.due_at_ms),
+            None if policy.max_operations.is_some() => {
+                let usage = self.load_rotation_usage(scope_id, scope_epoch)?;
+                (usage.next, usage.due_at_ms)
+            }
+            None => (0, None),
+        };
+        let age_due_at = policy.max_key_age_ms.and_then(|max_age| {
+            state
+                .keyvault_materialized
+                .scope_key_created_ms
+                .get(&lookup)
+                .map(|created| created.saturating_add(max_age))
+        });
+        let due = [
+            (RotationReason::KeyAge, age_due_at),
+            (RotationReason::Operations, ops_due_at),
+        ]
+        .into_iter()
+        .filter_map(|(reason, due_at)| due_at.map(|due_at| (reason, due_at)))
+        .filter(|(_, due_at)| *due_at <= now)
+        .min_by_key(|(_, due_at)| *due_at);
+        Ok(due.map(|(reason, due_at_ms)| DueRotation {
+            scope_id: scope_id.clone(),
+            scope_epoch,
+            reason,
+            due_at_ms,
+            hard_deadline_ms: due_at_ms.saturating_add(policy.grace_ms),
+            operations,
+        }))
+    }
+
+    /// Next counter nonce for a resource-key handle, leasing a new counter block when needed.
+    fn next_counter_nonce(
+        &mut self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+    ) -> Result<Vec<u8>, KeyServiceError> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let Some(HandleEntry::ResourceKey {
+            resource_id,
+            resource_key_id,
+            nonce_counter,
+            ..
+        }) = session.get_handle_mut(resource_key_handle)
+        else {
+            return Err(KeyServiceError::UnknownHandle);
+        };
+        let storage_key = format!("nonce_counter:{}:{}", resource_id.0, resource_key_id.0);
+        let counter = nonce_counter.get_or_insert_with(|| {
+            let mut prefix = [0u8; 4];
+            prefix.copy_from_slice(&self.entropy.random_bytes(4));
+            crate::session::NonceCounter {
+                prefix,
+                next: 0,
+                leased_until: 0,
+            }
+        });
+        if counter.next == counter.leased_until {
+            // Other handles on the same key lease from the same mark, so start past whatever
+            // they have persisted rather than past this handle's own lease.
+            let stored = self
+                .storage
+                .get("keyvault", &storage_key)
+                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+            let stored_next = match stored {
+                Some(bytes) => {
+                    let value = decode_canonical_value(&bytes, &CborLimits::default())?;
+                    crate::cbor::req_uint(crate::cbor::as_map(&value)?, 0)?
+                }
+                None => 0,
+            };
+            let next = counter.next.max(stored_next);
+            let leased_until = next
+                .checked_add(NONCE_COUNTER_LEASE)
+                .ok_or(KeyServiceError::NonceCounterExhausted)?;
+            let bytes = encode_canonical_value(&crate::cbor::cbor_map(vec![(
+                0,
+                crate::cbor::cbor_uint(leased_until),
+            )]))?;
+            self.storage
+                .put("keyvault", &storage_key, &bytes)
+                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+            counter.next = next;
+            counter.leased_until = leased_until;
+        }
+        let nonce = counter.nonce();
+        counter.next += 1;
+        Ok(nonce.to_vec())
+    }
+
+    pub(crate) fn session_expires_at(&self, session_id: &SessionId) -> Option<u64> {
+        self.sessions
+            .get(session_id)
+            .map(|session| session.expires_at_ms)
+    }
+
+    /// True while [`Self::resource_key`] would still hand out this handle's key.
+    pub(crate) fn is_cacheable_handle(&self, session_id: &SessionId, handle: &KeyHandle) -> bool {
+        self.sessions
+            .get(session_id)
+            .and_then(|session| session.peek_handle(handle))
+            .is_some_and(|entry| cacheable_key(self.state.as_ref(), entry).is_some())
+    }
+
+    /// Copy of a resource key for callers that encrypt outside this service's borrow.
+    pub(crate) fn resource_key(
+        &mut self,
+        session_id: &SessionId,
+        handle: &KeyHandle,
+    ) -> Option<SecretBytes> {
+        let entry = self.sessions.get_mut(session_id)?.get_handle(handle)?;
+        cacheable_key(self.state.as_ref(), entry).cloned()
+    }
+
+    /// Applies the id policy to `(kind, id)` pairs, failing on the first rejected id.
+    fn check_ids<'a>(
+        &self,
+        ids: impl IntoIterator<Item = (&'static str, &'a str)>,
+    ) -> Result<(), KeyServiceError> {
+        for (kind, id) in ids {
+            if let Some(reason) = self.config.policy.ids.violation(id) {
+                return Err(KeyServiceError::InvalidId { kind, reason });
+            }
+        }
+        Ok(())
+    }
+
+    fn ensure_session_valid(
+        &mut self,
+        now: u64,
+        session_id: &SessionId,
+    ) -> Result<(), KeyServiceError> {
+        self.check_clock(now)?;
+        let expired = {
+            let session = self
+                .sessions
+                .get_mut(session_id)
+                .ok_or(KeyServiceError::SessionInvalid)?;
+            if now > session.expires_at_ms {
+                session.clear();
+                true
+            } else {
+                false
+            }
+        };
+        if expired {
+            self.sessions.remove(session_id);
+            self.state = None;
+            self.log(|| LogEvent::new(LogLevel::Info, "session.expired").with_session(session_id));
+            self.emit_session_event(SessionEvent::Expired {
+                session_id: session_id.clone(),
+            });
+            self.schedule_timer();
+            return Err(KeyServiceError::SessionInvalid);
+        }
+        if self.stale && self.state.is_some() {
+            self.reload_state(session_id)?;
+        }
+        self.check_invariants()
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    fn load_header(&self) -> Result<KeyVaultHeaderV1, KeyServiceError> {
+        let bytes = self
+            .storage
+            .get("keyvault", "header")
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+            .ok_or(KeyServiceError::VaultMissing)?;
+        decode_keyvault_header_v1(&bytes, &self.config.policy.kdf_minimums).map_err(header_error)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(record_count = tracing::field::Empty))
+    )]
+    fn load_all_record_containers(
+        &self,
+    ) -> Result<Vec<KeyVaultRecordContainerV1>, KeyServiceError> {
+        let index_bytes = self
+            .storage
+            .get("keyvault", "record_index")
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+            .unwrap_or_default();
+        if index_bytes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let value = decode_canonical_value(&index_bytes, &self.cbor_limits())
+            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+        let arr = crate::cbor::as_array(&value)
+            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+        let mut records = Vec::new();
+        for item in arr {
+            let record_id = match item {
+                ciborium::value::Value::Text(text) => text.clone(),
+                _ => {
+                    return Err(KeyServiceError::InvalidCbor(
+                        "record index invalid".to_string(),
+                    ))
+                }
+            };
+            let key = format!("record:{}", record_id);
+            if let Some(bytes) = self
+                .storage
+                .get("keyvault", &key)
+                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+            {
+                let record = decode_keyvault_record_container_v1(&bytes)
+                    .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+                records.push(record);
+            }
+        }
+        records.sort_by_key(|r| r.seq);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("record_count", records.len());
+        Ok(records)
+    }
+
+    /// Like [`Self::load_all_record_containers`], but records that are missing or do not
+    /// decode are reported instead of failing the load.
+    fn load_record_containers_salvaging(
+        &self,
+    ) -> Result<(Vec<KeyVaultRecordContainerV1>, Vec<KeyVaultDamagedRecord>), KeyServiceError> {
+        let mut records = Vec::new();
+        let mut damaged = Vec::new();
+        for record_id in self.load_record_index()? {
+            let stored = self
+                .storage
+                .get("keyvault", &format!("record:{record_id}"))
+                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+            let reason = match stored.map(|bytes| decode_keyvault_record_container_v1(&bytes)) {
+                Some(Ok(record)) => {
+                    records.push(record);
+                    continue;
+                }
+                Some(Err(e)) => e.to_string(),
+                None => "record missing from storage".to_string(),
+            };
+            damaged.push(KeyVaultDamagedRecord {
+                record_id,
+                seq: None,
+                reason,
+            });
+        }
+        records.sort_by_key(|r| r.seq);
+        Ok((records, damaged))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(record_count = tracing::field::Empty))
+    )]
+    fn load_keyvault_state(
+        &self,
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+        cancel: &CancellationToken,
+    ) -> Result<(KeyVaultState, KeyVaultMaterialized), KeyServiceError> {
+        let records = self.load_all_record_containers()?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("record_count", records.len());
+        let stored_index = self.load_keyvault_index(header, vault_key)?;
+        let deferred = match &stored_index {
+            Some(index) if self.config.policy.lazy_resource_keys => {
+                index.deferrable_resource_records()
+            }
+            _ => HashMap::new(),
+        };
+        let started = self.metrics_start();
+        let result = KeyVaultState::apply_containers_deferring(
+            header, vault_key, &records, &deferred, cancel,
+        )
+        .map_err(|e| match e {
+            CoreError::Cancelled => KeyServiceError::Cancelled,
+            e => KeyServiceError::InvalidFormat(e.to_string()),
+        });
+        self.metrics_finish(MetricOp::RecordApply, started, result.is_ok());
+        let (state, materialized) = result?;
+        if !stored_index.is_some_and(|index| index.covers(&state)) {
+            self.store_keyvault_index(header, &materialized.index, vault_key)?;
+        }
+        Ok((state, materialized))
+    }
+
+    /// The sealed index, or `None` if it is missing or was sealed under another vault key
+    /// (e.g. before an import); it is rebuilt from the records in that case.
+    fn load_keyvault_index(
+        &self,
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+    ) -> Result<Option<KeyVaultIndex>, KeyServiceError> {
+        let stored = self
+            .storage
+            .get("keyvault", "index")
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        Ok(stored.and_then(|bytes| {
+            KeyVaultIndex::open(header, vault_key, &bytes, &self.cbor_limits()).ok()
+        }))
+    }
+
+    fn store_keyvault_index(
+        &self,
+        header: &KeyVaultHeaderV1,
+        index: &KeyVaultIndex,
+        vault_key: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        let bytes = index
+            .seal(header, vault_key)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        self.storage
+            .put("keyvault", "index", &bytes)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))
+    }
+
+    /// Decrypts a resource key deferred by a lazy unlock, if the index has one for the target.
+    fn load_deferred_resource_key(
+        &mut self,
+        session_id: &SessionId,
+        resource_id: &ResourceId,
+        resource_key_id: &ResourceKeyId,
+    ) -> Result<(), KeyServiceError> {
+        let target = (resource_id.0.clone(), resource_key_id.0.clone());
+        let vault_key = self.session_vault_key(session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let materialized = &state.keyvault_materialized;
+        if materialized.resource_keys.contains_key(&target) {
+            return Ok(());
+        }
+        let Some(record_id) = materialized.index.resource_keys.get(&target) else {
+            return Ok(());
+        };
+        let record = state
+            .keyvault_state
+            .open_record(&state.keyvault_header, vault_key, record_id)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        let mut loaded = KeyVaultMaterialized::default();
+        apply_record_plain(&record, &mut loaded)
+            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+        let key = loaded
+            .resource_keys
+            .remove(&target)
+            .filter(|_| record.kind == 4)
+            .ok_or_else(|| {
+                KeyServiceError::InvalidFormat(
+                    "keyvault index points at the wrong record".to_string(),
+                )
+            })?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .resource_keys
+            .insert(target, key);
+        Ok(())
+    }
+
+    fn run_kdf(
+        &self,
+        passphrase_utf8: &[u8],
+        params: &crate::crypto::KdfParams,
+        on_pass: Option<&mut dyn FnMut(u32, u32)>,
+    ) -> Result<Zeroizing<Vec<u8>>, KeyServiceError> {
+        if params.id == INSECURE_TEST_KDF_ID && !self.config.policy.allow_insecure_test_kdf {
+            return Err(KeyServiceError::InvalidConfig(
+                "insecure test KDF is not allowed by policy".to_string(),
+            ));
+        }
+        let started = self.metrics_start();
+        let result = match on_pass {
+            Some(on_pass) => derive_kek_with_progress(passphrase_utf8, params, on_pass),
+            None => derive_kek(passphrase_utf8, params),
+        }
+        .map_err(|e| KeyServiceError::CryptoError(e.to_string()));
+        self.metrics_finish(MetricOp::Kdf, started, result.is_ok());
+        result
+    }
+
+    /// Reads the clock only when a metrics adapter is installed.
+    fn metrics_start(&self) -> Option<u64> {
+        self.metrics.as_ref().map(|_| self.clock.now_ms())
+    }
+
+    fn metrics_finish(&self, op: MetricOp, started: Option<u64>, ok: bool) {
+        if let (Some(metrics), Some(started)) = (&self.metrics, started) {
+            metrics.increment(op, ok);
+            metrics.observe(op, self.clock.now_ms().saturating_sub(started));
+        }
+    }
+
+    fn load_user_keypair(&self) -> Result<&HybridKemRecipient, KeyServiceError> {
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .user_key
+            .as_ref()
+            .ok_or(KeyServiceError::UserKeyMissing)
+    }
+
+    fn reload_state(&mut self, session_id: &SessionId) -> Result<ReloadResponse, KeyServiceError> {
+        let vault_key = Zeroizing::new(self.session_vault_key(session_id)?.to_vec());
+        let header = self.load_header()?;
+        let current = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        if header.vault_id != current.keyvault_header.vault_id {
+            return Err(KeyServiceError::VaultKeyMismatch);
+        }
+        let loaded_head = (
+            current.keyvault_state.head_seq,
+            current.keyvault_state.head_hash.clone(),
+        );
+        let loaded_header = encode_keyvault_header_v1(&current.keyvault_header)
+            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
+
+        let (state, materialized) =
+            self.load_keyvault_state(&header, &vault_key, &CancellationToken::new())?;
+        check_not_rolled_back(&state, Some(&loaded_head))?;
+        let seen_head = self.load_head_marker(&header, &vault_key)?;
+        check_not_rolled_back(&state, seen_head.as_ref())?;
+        if seen_head.as_ref().map(|(seq, _)| *seq) != Some(state.head_seq) {
+            self.store_head_marker(&header, &state, &vault_key)?;
+        }
+        let changed = state.head_seq != loaded_head.0
+            || encode_keyvault_header_v1(&header)
+                .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?
+                != loaded_header;
+        let head_seq = state.head_seq;
+        let current = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        current.keyvault_header = header;
+        current.keyvault_state = state;
+        current.keyvault_materialized = materialized;
+        current.salvage = None;
+        self.stale = false;
+        Ok(ReloadResponse { changed, head_seq })
+    }
+
+    fn session_vault_key(&self, session_id: &SessionId) -> Result<&[u8], KeyServiceError> {
+        self.sessions
+            .get(session_id)
+            .map(|session| &*session.vault_key)
+            .ok_or(KeyServiceError::SessionInvalid)
+    }
+
+    /// Enforces a resource-key handle's ops, epoch retirement for encrypt and, for redeemed
+    /// capabilities, its AAD prefix and expiry. Unknown handles pass; the caller reports them.
+    fn check_capability(
+        &mut self,
+        now: u64,
+        session_id: &SessionId,
+        handle: &KeyHandle,
+        op: CapabilityOps,
+        aad: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        let Some(HandleEntry::ResourceKey {
+            ops,
+            scope,
+            restriction,
+            ..
+        }) = session.get_handle(handle)
+        else {
+            return Ok(());
+        };
+        if let Some(restriction) = restriction {
+            if now > restriction.expires_at_ms {
+                return Err(KeyServiceError::CapabilityExpired);
+            }
+            if !aad.starts_with(&restriction.aad_prefix) {
+                return Err(KeyServiceError::CapabilityDenied);
+            }
+        }
+        if !ops.contains(&op) {
+            return Err(KeyServiceError::CapabilityDenied);
+        }
+        let scope = scope.clone();
+        if op.encrypt {
+            if let Some(state) = &self.state {
+                state.signer_roster.ensure_epoch_active(scope.as_ref())?;
+            }
+            self.enforce_rotation_policy(now, scope)?;
+        }
+        Ok(())
+    }
+
+    /// Fails unless the session is a step-up session whose assurance meets the policy's
+    /// minimum for `operation`, if it sets one.
+    fn ensure_step_up(
+        &self,
+        session_id: &SessionId,
+        operation: SensitiveOperation,
+    ) -> Result<(), KeyServiceError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        if session.kind != SessionKind::StepUp {
+            return Err(KeyServiceError::StepUpRequired);
+        }
+        match self.config.policy.min_assurance.get(&operation) {
+            Some(&required) if !session.assurance.satisfies(required) => {
+                Err(KeyServiceError::AssuranceRequired { required })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails with [`KeyServiceError::ReadOnlySession`] for sessions that may not change the vault.
+    fn ensure_writable(&self, session_id: &SessionId) -> Result<(), KeyServiceError> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or(KeyServiceError::SessionInvalid)?;
+        if session.read_only {
+            return Err(KeyServiceError::ReadOnlySession);
+        }
+        Ok(())
+    }
+
+    /// Appends `record` to the hash chain under the session's vault key and persists it.
+    fn append_and_persist_record(
+        &mut self,
+        session_id: &SessionId,
+        header: &KeyVaultHeaderV1,
+        record: &KeyVaultRecordPlainV1,
+    ) -> Result<(), KeyServiceError> {
+        self.ensure_writable(session_id)?;
+        let now = self.clock.now_ms();
+        let container = {
+            let session = self
+                .sessions
+                .get(session_id)
+                .ok_or(KeyServiceError::SessionInvalid)?;
+            let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+            let mut record = record.clone();
+            record.provenance = Some(KeyVaultRecordProvenance {
+                created_at_ms: now,
+                origin_device_id: state
+                    .keyvault_materialized
+                    .device_signing_keys
+                    .keys()
+                    .min()
+                    .cloned()
+                    .map(DeviceId),
+            });
+            let seq = state.keyvault_state.head_seq + 1;
+            let policy = &self.config.policy;
+            if state.keyvault_state.head_seq >= policy.max_total_records {
+                return Err(KeyServiceError::VaultLimitExceeded {
+                    limit: "max_total_records",
+                });
+            }
+            let record_len = encode_keyvault_record_plain_v1(&record)
+                .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?
+                .len();
+            if record_len > policy.max_record_bytes {
+                return Err(KeyServiceError::VaultLimitExceeded {
+                    limit: "max_record_bytes",
+                });
+            }
+            let container = state
+                .keyvault_state
+                .append_record(header, &session.vault_key, &record, seq)
+                .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+            let materialized = &mut state.keyvault_materialized;
+            materialized
+                .record_metadata
+                .push(KeyVaultRecordMetadata::new(seq, &record));
+            materialized
+                .index
+                .note_record(&record)
+                .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+            materialized
+                .index
+                .advance(seq, &state.keyvault_state.head_hash);
+            container
+        };
+        self.persist_record_container(&container)?;
+        let head_hash = self
+            .state
+            .as_ref()
+            .ok_or(KeyServiceError::VaultNotLoaded)?
+            .keyvault_state
+            .head_hash
+            .clone();
+        let vault_key = Zeroizing::new(self.session_vault_key(session_id)?.to_vec());
+        self.store_head_marker_at(header, container.seq, &head_hash, &vault_key)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        self.store_keyvault_index(
+            header,
+            &state.keyvault_materialized.index,
+            self.session_vault_key(session_id)?,
+        )?;
+        self.emit_vault_event(VaultEvent::RecordAppended {
+            seq: container.seq,
+            record_id: container.record_id,
+        });
+        self.check_invariants()
+    }
+
+    /// Fetches the log's latest tree head, checks it against the trusted one, and then checks
+    /// that `leaf` is in it. Without a log this passes unless `require_transparency` is set.
+    fn check_transparency(&mut self, leaf: &TransparencyLeaf) -> Result<(), KeyServiceError> {
+        let fail = |reason| KeyServiceError::TransparencyCheckFailed { reason };
+        let policy = &self.config.policy;
+        let Some(log) = self.transparency.as_mut() else {
+            if policy.require_transparency {
+                return Err(fail("no transparency log configured"));
+            }
+            return Ok(());
+        };
+        let head = log
+            .adapter
+            .tree_head()
+            .ok_or(fail("transparency log unavailable"))?;
+        if !head.verify(&log.key) {
+            return Err(fail("tree head signature invalid"));
+        }
+        let now = self.clock.now_ms();
+        if now.saturating_sub(head.timestamp_ms) > policy.max_tree_head_age_ms {
+            return Err(fail("tree head too old"));
+        }
+        if let Some(trusted) = &log.trusted_head {
+            if head.tree_size < trusted.tree_size {
+                return Err(fail("tree head older than the trusted one"));
+            }
+            let proof = if head.tree_size == trusted.tree_size {
+                Vec::new()
+            } else {
+                log.adapter
+                    .consistency_proof(trusted.tree_size, head.tree_size)
+                    .ok_or(fail("transparency log unavailable"))?
+            };
+            if !verify_consistency(
+                trusted.tree_size,
+                &trusted.root_hash,
+                head.tree_size,
+                &head.root_hash,
+                &proof,
+            ) {
+                return Err(fail("tree head inconsistent with the trusted one"));
+            }
+        }
+        let leaf_hash = leaf.leaf_hash().map_err(KeyServiceError::from)?;
+        let proof = log
+            .adapter
+            .inclusion_proof(&leaf_hash, head.tree_size)
+            .ok_or(fail("transparency log unavailable"))?;
+        let included = proof.is_some_and(|proof| {
+            proof.tree_size == head.tree_size
+                && verify_inclusion(&leaf_hash, &proof, &head.root_hash)
+        });
+        log.trusted_head = Some(head);
+        if !included {
+            return Err(fail("entry not in the transparency log"));
+        }
+        Ok(())
+    }
+
+    /// Re-verifies the keyvault chain head, session handle bookkeeping, and signer roster.
+    /// A violation panics in debug builds and fails with `InvariantViolated` otherwise.
+    #[cfg(feature = "paranoid")]
+    fn check_invariants(&self) -> Result<(), KeyServiceError> {
+        let violation = self
+            .sessions
+            .iter()
+            .find_map(|session| session.check_invariants().err())
+            .or_else(|| self.state.as_ref()?.check_invariants().err());
+        match violation {
+            None => Ok(()),
+            Some(invariant) if cfg!(debug_assertions) => {
+                panic!("key service invariant violated: {invariant}")
+            }
+            Some(invariant) => Err(KeyServiceError::InvariantViolated { invariant }),
+        }
+    }
+
+    #[cfg(not(feature = "paranoid"))]
+    fn check_invariants(&self) -> Result<(), KeyServiceError> {
+        Ok(())
+    }
+
+    fn emit_vault_event(&self, event: VaultEvent) {
+        if let Some(events) = &self.vault_events {
+            events.on_vault_event(&event);
+        }
+    }
+
+    fn emit_session_event(&self, event: SessionEvent) {
+        if let Some(events) = &self.session_events {
+            events.on_session_event(&event);
+        }
+    }
+
+    /// Compares `now` with the latest accepted reading; see
+    /// [`KeyServicePolicy::max_clock_rewind_ms`] and [`KeyServicePolicy::max_clock_jump_ms`].
+    fn check_clock(&mut self, now: u64) -> Result<(), KeyServiceError> {
+        let Some(last) = self.clock_high_water_ms else {
+            self.clock_high_water_ms = Some(now);
+            return Ok(());
+        };
+        let anomaly = |reason| KeyServiceError::ClockAnomaly {
+            reason,
+            now_ms: now,
+            last_ms: last,
+        };
+        if last - now.min(last) > self.config.policy.max_clock_rewind_ms {
+            return Err(anomaly("clock went backwards"));
+        }
+        self.clock_high_water_ms = Some(last.max(now));
+        if let Some(max_jump) = self.config.policy.max_clock_jump_ms {
+            if now.saturating_sub(last) > max_jump {
+                self.lock_all();
+                return Err(anomaly("clock jumped forward"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Points the timer at the next session deadline, or cancels it when there is none.
+    fn schedule_timer(&mut self) {
+        let Some(timer) = &self.timer else {
+            return;
+        };
+        let deadline = self
+            .sessions
+            .next_deadline(self.config.policy.session_expiry_warning_ms);
+        if deadline == self.timer_deadline {
+            return;
+        }
+        self.timer_deadline = deadline;
+        match deadline {
+            Some(at_ms) => timer.schedule(at_ms.saturating_sub(self.clock.now_ms())),
+            None => timer.cancel(),
+        }
+    }
+
+    /// AAD and stored bytes of the head marker as the device anchor sees them, so an async
+    /// anchor can unseal it before a sync read. `None` without a header or marker.
+    pub(crate) fn anchored_head_marker(&self) -> Result<Option<AnchoredBytes>, KeyServiceError> {
+        let header = match self.load_header() {
+            Ok(header) => header,
+            Err(KeyServiceError::VaultMissing) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let stored = self
+            .storage
+            .get("keyvault", "head_marker")
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+            .unwrap_or_default();
+        if stored.is_empty() {
+            return Ok(None);
+        }
+        let aad = aad_keyvault_head_marker_v1(&header.vault_id, &header.user_id, header.aead)?;
+        Ok(Some((aad, stored)))
+    }
+
+    /// Last chain head this device saw, as `(head_seq, head_hash)`. With a monotonic counter,
+    /// a missing marker or one written before the counter's value is a rollback.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    fn load_head_marker(
+        &self,
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+    ) -> Result<Option<(u64, Vec<u8>)>, KeyServiceError> {
+        let counter = self.head_marker_counter(header)?;
+        let stored = self
+            .storage
+            .get("keyvault", "head_marker")
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+            .unwrap_or_default();
+        if stored.is_empty() {
+            return match counter {
+                Some((_, value)) if value > 0 => Err(KeyServiceError::RollbackDetected),
+                _ => Ok(None),
+            };
+        }
+        let aad = aad_keyvault_head_marker_v1(&header.vault_id, &header.user_id, header.aead)?;
+        let bytes = match &self.device_anchor {
+            Some(anchor) => anchor
+                .unseal(HEAD_MARKER_ANCHOR_LABEL, &aad, &stored)
+                .map_err(KeyServiceError::CryptoError)?,
+            None => stored,
+        };
+        let marker = HeadMarkerV1::decode(&bytes)?;
+        let plaintext = aead_decrypt::<Aes256Gcm>(vault_key, &aad, &marker.nonce, &marker.ct)
+            .map_err(|_| KeyServiceError::KeyUnwrapFailed {
+                kind: WrappedKeyKind::HeadMarker,
+            })?;
+        let value = decode_canonical_value(&plaintext, &CborLimits::default())?;
+        let map = crate::cbor::as_map(&value)?;
+        let head_seq = crate::cbor::req_uint(map, 0)?;
+        let head_hash = crate::cbor::req_bytes(map, 1)?;
+        if let Some((_, value)) = counter {
+            if crate::cbor::opt_uint(map, 2)?.unwrap_or(0) < value {
+                return Err(KeyServiceError::RollbackDetected);
+            }
+        }
+        Ok(Some((head_seq, head_hash)))
+    }
+
+    /// The head marker counter's name and current value, counting a buffered advance.
+    fn head_marker_counter(
+        &self,
+        header: &KeyVaultHeaderV1,
+    ) -> Result<Option<(String, u64)>, KeyServiceError> {
+        let Some(counter) = &self.monotonic else {
+            return Ok(None);
+        };
+        let name = format!("head-marker:{}", header.vault_id);
+        let mut value = counter.read(&name).map_err(KeyServiceError::StorageError)?;
+        if let Some((pending_name, pending)) = &self.pending_counter_advance {
+            if *pending_name == name {
+                value = value.max(*pending);
+            }
+        }
+        Ok(Some((name, value)))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(head_seq = state.head_seq))
+    )]
+    fn store_head_marker(
+        &mut self,
+        header: &KeyVaultHeaderV1,
+        state: &KeyVaultState,
+        vault_key: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        self.store_head_marker_at(header, state.head_seq, &state.head_hash, vault_key)
+    }
+
+    /// Seals `(head_seq, head_hash)` as the head marker, then advances the counter past it.
+    fn store_head_marker_at(
+        &mut self,
+        header: &KeyVaultHeaderV1,
+        head_seq: u64,
+        head_hash: &[u8],
+        vault_key: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        let aad = aad_keyvault_head_marker_v1(&header.vault_id, &header.user_id, header.aead)?;
+        let counter = self
+            .head_marker_counter(header)?
+            .map(|(name, value)| (name, value + 1));
+        let mut fields = vec![
+            (0, crate::cbor::cbor_uint(head_seq)),
+            (1, crate::cbor::cbor_bytes(head_hash)),
+        ];
+        if let Some((_, value)) = &counter {
+            fields.push((2, crate::cbor::cbor_uint(*value)));
+        }
+        let plaintext = encode_canonical_value(&crate::cbor::cbor_map(fields))?;
+        let nonce = self.entropy.random_bytes(12);
+        let ct = aead_encrypt::<Aes256Gcm>(vault_key, &aad, &plaintext, &nonce)?;
+        let mut bytes = HeadMarkerV1 { nonce, ct }.encode()?;
+        if let Some(anchor) = &self.device_anchor {
+            bytes = anchor
+                .seal(HEAD_MARKER_ANCHOR_LABEL, &aad, &bytes)
+                .map_err(KeyServiceError::CryptoError)?;
+        }
+        self.storage
+            .put("keyvault", "head_marker", &bytes)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        // The marker goes first: a crash in between leaves it ahead of the counter, which is
+        // accepted, rather than behind it, which would read as a rollback.
+        if let Some((name, value)) = counter {
+            self.pending_counter_advance = Some((name, value));
+            if !self.defer_counter_advance {
+                self.commit_counter_advance()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends an entry to the audit log, signed by the device key once one exists.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(kind = kind.as_str()))
+    )]
+    fn record_audit(
+        &mut self,
+        kind: AuditEventKind,
+        detail: String,
+    ) -> Result<(), KeyServiceError> {
+        let (head_seq, prev_hash) = self.load_audit_head()?;
+        let mut entry = AuditEntryV1 {
+            seq: head_seq + 1,
+            prev_hash,
+            at_ms: self.clock.now_ms(),
+            kind,
+            detail,
+            signer_device_id: None,
+            signature: None,
+        };
+        let signing = self.state.as_ref().and_then(|state| {
+            state
+                .keyvault_materialized
+                .device_signing_keys
+                .iter()
+                .min_by(|a, b| a.0.cmp(b.0))
+        });
+        if let Some((device_id, keypair)) = signing {
+            entry.signer_device_id = Some(DeviceId(device_id.clone()));
+            let signature = hybrid_sign(SIG_AUDIT_ENTRY_V1, &entry.to_be_signed_bytes()?, keypair)
+                .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+            entry.signature = Some(signature);
+        }
+        let bytes = encode_audit_entry_v1(&entry)?;
+        self.storage
+            .put(AUDIT_NAMESPACE, &audit_entry_key(entry.seq), &bytes)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        let head = encode_canonical_value(&crate::cbor::cbor_map(vec![
+            (0, crate::cbor::cbor_uint(entry.seq)),
+            (1, crate::cbor::cbor_bytes(&entry.entry_hash()?)),
+        ]))?;
+        self.storage
+            .put(AUDIT_NAMESPACE, AUDIT_HEAD_KEY, &head)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
+        self.log(|| {
+            LogEvent::new(LogLevel::Info, "audit.appended")
+                .with("kind", entry.kind.as_str())
+                .with("seq", entry.seq)
+        });
+        Ok(())
+    }
+
+    /// Builds the event only when a log adapter is installed.
+    fn log(&self, event: impl FnOnce() -> LogEvent) {
+        if let Some(logger) = &self.logger {
+            logger.log(&event());
+        }
+    }
+
+    fn diagnose_vault(&self, problems: &mut Vec<String>) -> Option<VaultDiagnostics> {
+        let mut note =
+            |part: &str, err: KeyServiceError| problems.push(format!("{part}: {}", err.code()));
+        let header_bytes = match self.storage.get("keyvault", "header") {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return None,
+            Err(e) => {
+                note("header", KeyServiceError::StorageError(format!("{e:?}")));
+                return None;
+            }
+        };
+        let header =
+            match decode_keyvault_header_v1(&header_bytes, &self.config.policy.kdf_minimums) {
+                Ok(header) => header,
+                Err(e) => {
+                    note("header", header_error(e));
+                    return None;
+                }
+            };
+
+        let mut records = self.load_all_record_containers().unwrap_or_else(|e| {
+            note("records", e);
+            Vec::new()
+        });
+        records.sort_by_key(|r| r.seq);
+        let mut chain_linked = true;
+        let mut prev_hash = vec![0u8; 32];
+        let mut record_bytes_total = 0u64;
+        let mut record_bytes_max = 0u64;
+        for (expected_seq, record) in (1u64..).zip(&records) {
+            let bytes = match encode_keyvault_record_container_v1(record) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    note("records", KeyServiceError::InvalidCbor(e.to_string()));
+                    chain_linked = false;
+                    continue;
+                }
+            };
+            record_bytes_total += bytes.len() as u64;
+            record_bytes_max = record_bytes_max.max(bytes.len() as u64);
+            if record.seq != expected_seq || record.prev_hash != prev_hash {
+                chain_linked = false;
+            }
+            prev_hash = sha256(&bytes).to_vec();
+        }
+
+        let head_marker_present = match self.storage.get("keyvault", "head_marker") {
+            Ok(bytes) => bytes.is_some_and(|b| !b.is_empty()),
+            Err(e) => {
+                note(
+                    "head_marker",
+                    KeyServiceError::StorageError(format!("{e:?}")),
+                );
+                false
+            }
+        };
+        let audit_head_seq = self.load_audit_head().map_or_else(
+            |e| {
+                note("audit", e);
+                0
+            },
+            |(seq, _)| seq,
+        );
+
+        Some(VaultDiagnostics {
+            vault_id_tag: redact_identifier("vault_id", header.vault_id.as_bytes()),
+            user_id_tag: redact_identifier("user_id", header.user_id.as_bytes()),
+            kdf_memory_kib: header.kdf.memory_kib,
+            kdf_iterations: header.kdf.iterations,
+            kdf_parallelism: header.kdf.parallelism,
+            aead: header.aead,
+            header_bytes: header_bytes.len() as u64,
+            header_record_count: header.records.len() as u64,
+            record_count: records.len() as u64,
+            record_bytes_total,
+            record_bytes_max,
+            head_seq: records.last().map_or(0, |r| r.seq),
+            head_hash_tag: if records.is_empty() {
+                String::new()
+            } else {
+                redact_identifier("head_hash", &prev_hash)
+            },
+            chain_linked,
+            head_marker_present,
+            user_presence_enabled: self.load_user_presence_unlock().is_ok(),
+            audit_head_seq,
+        })
+    }
+
+    /// Keeps the last [`DIAGNOSTICS_ERROR_LIMIT`] failure codes for `export_diagnostics`.
+    fn note_error<T>(&mut self, op: MetricOp, result: &Result<T, KeyServiceError>) {
+        if let Err(err) = result {
+            if self.recent_errors.len() == DIAGNOSTICS_ERROR_LIMIT {
+                self.recent_errors.pop_front();
+            }
+            self.recent_errors.push_back(RecentError {
+                at_ms: self.clock.now_ms(),
+                op,
+                code: err.code(),
+            });
+        }
+    }
+
+    fn log_unlock(&self, result: &Result<UnlockResponse, KeyServiceError>, method: &str) {
+        self.log(|| match result {
+            Ok(response) => LogEvent::new(LogLevel::Info, "session.unlocked")
+                .with_session(&response.session_id)
+                .with("method", method),
+            Err(err) => LogEvent::new(LogLevel::Warn, "session.unlock_failed")
+                .with("method", method)
+                .with("code", err.code()),
+        });
+    }
+
+    /// Current audit head as `(seq, entry_hash)`; `(0, [])` for an empty log.
+    fn load_audit_head(&self) -> Result<(u64, Vec<u8>), KeyServiceError> {
+        let bytes = self
+            .storage
+            .get(AUDIT_NAMESPACE, AUDIT_HEAD_KEY)
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+            .unwrap_or_default();
+        if bytes.is_empty() {
+            return Ok((0, Vec::new()));
+        }
+        let value = decode_canonical_value(&bytes, &CborLimits::default())?;
+        let map = crate::cbor::as_map(&value)?;
+        Ok((
+            crate::cbor::req_uint(map, 0)?,
+            crate::cbor::req_bytes(map, 1)?,
+        ))
+    }
+
+    fn load_audit_entries(&self) -> Result<Vec<AuditEntryV1>, KeyServiceError> {
+        let (head_seq, _) = self.load_audit_head()?;
+        let mut entries = Vec::new();
+        for seq in 1..=head_seq {
+            let bytes = self
+                .storage
+                .get(AUDIT_NAMESPACE, &audit_entry_key(seq))
+                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+                .ok_or_else(|| {
+                    KeyServiceError::InvalidFormat(format!("audit entry {seq} missing"))
+                })?;
+            entries.push(decode_audit_entry_v1(&bytes)?);
+        }
+        Ok(entries)
+    }
+
+    /// Appends a scope key record unless the same key is already current for the scope epoch;
+    /// a different key supersedes the earlier record.
+    pub fn persist_scope_key(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+        scope_key: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        self.check_ids([("scopeId", scope_id.0.as_str())])?;
+        let header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_writable(session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        if state
+            .keyvault_materialized
+            .holds_scope_key(scope_id, scope_epoch, scope_key)
+        {
+            return Ok(());
+        }
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_store_scope_key_record(&record_id, &scope_id.0, scope_epoch.0, scope_key);
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state.keyvault_materialized.scope_keys.insert(
+            (scope_id.0.clone(), scope_epoch.0),
+            Zeroizing::new(scope_key.to_vec()),
+        );
+        state
+            .keyvault_materialized
+            .scope_key_created_ms
+            .insert((scope_id.0.clone(), scope_epoch.0), now);
+        self.emit_vault_event(VaultEvent::ScopeKeyAdded {
+            scope_id: scope_id.clone(),
+            scope_epoch,
+        });
+        Ok(())
+    }
+
+    fn load_cgka_group(&self, scope_id: &ScopeId) -> Result<Option<CgkaGroup>, KeyServiceError> {
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let Some(bytes) = state.keyvault_materialized.cgka_groups.get(&scope_id.0) else {
+            return Ok(None);
+        };
+        Ok(Some(CgkaGroup::from_bytes(bytes, &self.cbor_limits())?))
+    }
+
+    /// Stores `group` and the scope key of its epoch.
+    fn persist_cgka_group(
+        &mut self,
+        session_id: &SessionId,
+        group: &CgkaGroup,
+        scope_key: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        self.persist_scope_key(
+            session_id,
+            &group.scope_id,
+            ScopeEpoch(group.epoch),
+            scope_key,
+        )?;
+        let header = self.load_header()?;
+        let bytes = Zeroizing::new(group.to_bytes()?);
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_cgka_group_record(&record_id, &group.scope_id.0, &bytes);
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .cgka_groups
+            .insert(group.scope_id.0.clone(), bytes);
+        Ok(())
+    }
+
+    /// This user's leaf: its user id and user KEM key.
+    fn local_cgka_member(&self) -> Result<CgkaMemberV1, KeyServiceError> {
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        Ok(CgkaMemberV1 {
+            user_id: UserId(state.keyvault_header.user_id.clone()),
+            public_bytes: self.load_user_keypair()?.public_bytes.clone(),
+        })
+    }
+
+    /// The local device, which must be a trusted signer administering `scope_id`.
+    fn local_cgka_committer(&self, scope_id: &ScopeId) -> Result<DeviceId, KeyServiceError> {
+        let (device_id, _) = self.local_signing_key()?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        if state
+            .signer_roster
+            .get_signer(scope_id, &device_id)
+            .is_none()
+        {
+            return Err(KeyServiceError::UntrustedSigner);
+        }
+        if !state
+            .signer_roster
+            .role(scope_id, &device_id)
+            .is_some_and(|role| role.can_administer())
+        {
+            return Err(KeyServiceError::ScopeRoleDenied {
+                scope_id: scope_id.clone(),
+            });
+        }
+        Ok(device_id)
+    }
+
+    /// Checks a CGKA commit or welcome: signed by a trusted signer administering the scope,
+    /// under a known scope state.
+    fn verify_cgka_signer(
+        &self,
+        scope_id: &ScopeId,
+        device_id: &DeviceId,
+        scope_state_ref: &[u8],
+        format: SignedFormat,
+        to_verify: &[u8],
+        signature: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        let state = self.state.as_ref().ok_or(KeyServiceError::UnknownScope)?;
+        let roster = &state.signer_roster;
+        let signer = roster
+            .get_signer(scope_id, device_id)
+            .ok_or(KeyServiceError::UntrustedSigner)?;
+        if !roster.has_scope_state_ref(scope_id, &hex::encode(scope_state_ref)) {
+            return Err(KeyServiceError::UnknownScopeStateRef {
+                scope_id: scope_id.clone(),
+            });
+        }
+        if !hybrid_verify(format.sig_context(), to_verify, signature, signer) {
+            return Err(KeyServiceError::SignatureInvalid { format });
+        }
+        if !roster
+            .role(scope_id, device_id)
+            .is_some_and(|role| role.can_administer())
+        {
+            return Err(KeyServiceError::ScopeRoleDenied {
+                scope_id: scope_id.clone(),
+            });
+        }
+        Ok(())
     }
 
-    pub fn init_identity(
+    /// Records one-time prekey `prekey_id` as used, so no later envelope can reuse it.
+    fn consume_prekey(
         &mut self,
         session_id: &SessionId,
-        device_id: &DeviceId,
+        prekey_id: u64,
     ) -> Result<(), KeyServiceError> {
         let header = self.load_header()?;
-        let now = self.clock.now_ms();
-        self.ensure_session_valid(now, session_id)?;
-        let vault_key = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            session.vault_key.clone()
-        };
-
-        let (uk_recipient, uk_priv_bytes) =
-            generate_user_keypair().map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
-        let uk_pub = uk_recipient.public_bytes.clone();
-        let device_signer = generate_device_signing_keypair().map_err(KeyServiceError::from)?;
-
-        let user_record_id = uuid_like(&self.entropy.random_bytes(16));
-        let user_record = make_store_user_key_record(&user_record_id, &uk_priv_bytes, &uk_pub);
-        let device_record_id = uuid_like(&self.entropy.random_bytes(16));
-        let device_record = make_store_device_signing_key_record(
-            &device_record_id,
-            &device_id.0,
-            &device_signer.ed25519_priv,
-            &device_signer.ed25519_pub,
-            &device_signer.mldsa_priv,
-            &device_signer.mldsa_pub,
-            SigCiphersuiteId::HybridSig1,
-        );
-
-        let container1 = {
-            let state = self.state.as_mut().ok_or(KeyServiceError::CryptoError(
-                "keyvault not loaded".to_string(),
-            ))?;
-            let seq1 = state.keyvault_state.head_seq + 1;
-            state
-                .keyvault_state
-                .append_record(&header, &vault_key, &user_record, seq1)
-                .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?
-        };
-        self.persist_record_container(&container1)?;
-        let state = self.state.as_mut().ok_or(KeyServiceError::CryptoError(
-            "keyvault not loaded".to_string(),
-        ))?;
-        state.keyvault_materialized.user_key.replace(uk_recipient);
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_prekey_consumed_record(&record_id, prekey_id);
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .consumed_prekeys
+            .insert(prekey_id);
+        Ok(())
+    }
 
-        let container2 = {
-            let state = self.state.as_mut().ok_or(KeyServiceError::CryptoError(
-                "keyvault not loaded".to_string(),
-            ))?;
-            let seq2 = state.keyvault_state.head_seq + 1;
-            state
-                .keyvault_state
-                .append_record(&header, &vault_key, &device_record, seq2)
-                .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?
-        };
-        self.persist_record_container(&container2)?;
-        let state = self.state.as_mut().ok_or(KeyServiceError::CryptoError(
-            "keyvault not loaded".to_string(),
-        ))?;
+    /// Records `id` as ingested for `scope_id`, so a replay of the same item is a no-op.
+    fn mark_seen(
+        &mut self,
+        session_id: &SessionId,
+        kind: SeenIdKind,
+        scope_id: &ScopeId,
+        id: &str,
+    ) -> Result<(), KeyServiceError> {
+        let header = self.load_header()?;
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_seen_id_record(&record_id, kind, &scope_id.0, id);
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
         state
             .keyvault_materialized
-            .device_signing_keys
-            .insert(device_id.0.clone(), device_signer);
+            .seen_ids
+            .insert((kind, scope_id.0.clone(), id.to_string()));
+        Ok(())
+    }
 
+    /// Caches the member keys of a verified membership change in the recipient key directory.
+    /// A key never replaces a different one already held for the user, and read-only sessions
+    /// leave the directory as it is.
+    fn learn_member_keys(
+        &mut self,
+        session_id: &SessionId,
+        scope_id: &ScopeId,
+        member_keys: &[(UserId, Vec<u8>)],
+        warnings: &mut Vec<IngestWarning>,
+    ) -> Result<(), KeyServiceError> {
+        if self.ensure_writable(session_id).is_err() {
+            return Ok(());
+        }
+        for (user_id, public_bytes) in member_keys {
+            let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+            match state.keyvault_materialized.contacts.get(&user_id.0) {
+                Some(contact) if ct_eq(&contact.public_bytes, public_bytes) => {}
+                Some(_) => warnings.push(IngestWarning::ContactKeyConflict {
+                    user_id: user_id.clone(),
+                }),
+                None => {
+                    self.store_contact(session_id, user_id, Some(public_bytes), Some(scope_id))?
+                }
+            }
+        }
         Ok(())
     }
 
-    fn finish_unlock(
+    /// Appends a recipient key directory record for `user_id`; `public_bytes` of `None` removes
+    /// the user.
+    fn store_contact(
         &mut self,
-        header: KeyVaultHeaderV1,
-        vault_key: Vec<u8>,
-        assurance: SessionAssurance,
-        kind: SessionKind,
-    ) -> Result<UnlockResponse, KeyServiceError> {
+        session_id: &SessionId,
+        user_id: &UserId,
+        public_bytes: Option<&[u8]>,
+        scope_id: Option<&ScopeId>,
+    ) -> Result<(), KeyServiceError> {
+        let header = self.load_header()?;
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_contact_record(
+            &record_id,
+            &user_id.0,
+            public_bytes,
+            scope_id.map(|scope_id| scope_id.0.as_str()),
+        );
+        self.append_and_persist_record(session_id, &header, &record)?;
         let now = self.clock.now_ms();
-        let ttl = match kind {
-            SessionKind::Normal => self.config.policy.normal_session_ttl_ms,
-            SessionKind::StepUp => self.config.policy.step_up_session_ttl_ms,
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        let contacts = &mut state.keyvault_materialized.contacts;
+        let detail = match public_bytes {
+            Some(public_bytes) => {
+                contacts.insert(
+                    user_id.0.clone(),
+                    ContactKey {
+                        public_bytes: public_bytes.to_vec(),
+                        scope_id: scope_id.cloned(),
+                        added_at_ms: Some(now),
+                    },
+                );
+                format!(
+                    "user={} fingerprint={}",
+                    user_id.0,
+                    fingerprint_bytes_hex(public_bytes)
+                )
+            }
+            None => {
+                contacts.remove(&user_id.0);
+                format!("user={} removed", user_id.0)
+            }
         };
-        let session_id = SessionId(hex_id(&self.entropy.random_bytes(16)));
-        let mut session = Session::new(
-            session_id.clone(),
-            now,
-            now + ttl,
-            kind,
-            assurance,
-            vault_key.clone(),
-        );
-        session.max_handles = self.config.policy.max_handles_per_session;
-        self.sessions.insert(session_id.clone(), session);
-
-        let (state, materialized) = self.load_keyvault_state(&header, &vault_key)?;
-        self.state = Some(KeyServiceState {
-            keyvault_header: header,
-            keyvault_state: state,
-            keyvault_materialized: materialized,
-            signer_roster: SignerRoster::new(self.config.policy.max_scope_state_refs_per_scope),
-        });
-
-        Ok(UnlockResponse {
-            session_id,
-            issued_at_ms: now,
-            expires_at_ms: now + ttl,
-            kind,
-            assurance,
-        })
+        self.record_audit(AuditEventKind::ContactChange, detail)
     }
 
-    fn ensure_session_valid(
+    /// Like [`Self::persist_scope_key`], for resource keys.
+    pub fn persist_resource_key(
         &mut self,
-        now: u64,
         session_id: &SessionId,
+        resource_id: &ResourceId,
+        resource_key_id: &ResourceKeyId,
+        resource_key: &[u8],
     ) -> Result<(), KeyServiceError> {
-        let expired = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            if now > session.expires_at_ms {
-                session.clear();
-                true
-            } else {
-                false
-            }
-        };
-        if expired {
-            self.sessions.remove(session_id);
-            self.state = None;
-            return Err(KeyServiceError::SessionInvalid);
+        self.check_ids([
+            ("resourceId", resource_id.0.as_str()),
+            ("resourceKeyId", &resource_key_id.0),
+        ])?;
+        let header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.ensure_writable(session_id)?;
+        self.load_deferred_resource_key(session_id, resource_id, resource_key_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        if state.keyvault_materialized.holds_resource_key(
+            resource_id,
+            resource_key_id,
+            resource_key,
+        ) {
+            return Ok(());
         }
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_store_resource_key_record(
+            &record_id,
+            &resource_id.0,
+            &resource_key_id.0,
+            resource_key,
+        );
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state.keyvault_materialized.resource_keys.insert(
+            (resource_id.0.clone(), resource_key_id.0.clone()),
+            Zeroizing::new(resource_key.to_vec()),
+        );
         Ok(())
     }
 
-    fn load_header(&self) -> Result<KeyVaultHeaderV1, KeyServiceError> {
-        let bytes = self
-            .storage
-            .get("keyvault", "header")
-            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
-            .ok_or(KeyServiceError::InvalidFormat(
-                "missing keyvault header".to_string(),
-            ))?;
-        decode_keyvault_header_v1(&bytes).map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))
+    pub fn store_app_master_key(
+        &mut self,
+        session_id: &SessionId,
+        master_key: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        let resource_id = ResourceId(APP_MASTER_RESOURCE_ID.to_string());
+        let resource_key_id = ResourceKeyId(APP_MASTER_RESOURCE_KEY_ID.to_string());
+        self.persist_resource_key(session_id, &resource_id, &resource_key_id, master_key)
     }
 
-    fn load_all_record_containers(
-        &self,
-    ) -> Result<Vec<KeyVaultRecordContainerV1>, KeyServiceError> {
-        let index_bytes = self
-            .storage
-            .get("keyvault", "record_index")
-            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
-            .unwrap_or_default();
-        if index_bytes.is_empty() {
-            return Ok(Vec::new());
-        }
-        let value = decode_canonical_value(&index_bytes, &self.cbor_limits())
-            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
-        let arr = crate::cbor::as_array(&value)
-            .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
-        let mut records = Vec::new();
-        for item in arr {
-            let record_id = match item {
-                ciborium::value::Value::Text(text) => text.clone(),
-                _ => {
-                    return Err(KeyServiceError::InvalidCbor(
-                        "record index invalid".to_string(),
-                    ))
-                }
-            };
-            let key = format!("record:{}", record_id);
-            if let Some(bytes) = self
-                .storage
-                .get("keyvault", &key)
-                .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
-            {
-                let record = decode_keyvault_record_container_v1(&bytes)
-                    .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
-                records.push(record);
-            }
-        }
-        records.sort_by_key(|r| r.seq);
-        Ok(records)
+    pub fn get_app_master_key(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<Zeroizing<Vec<u8>>, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        self.load_deferred_resource_key(
+            session_id,
+            &ResourceId(APP_MASTER_RESOURCE_ID.to_string()),
+            &ResourceKeyId(APP_MASTER_RESOURCE_KEY_ID.to_string()),
+        )?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state
+            .keyvault_materialized
+            .resource_keys
+            .get(&(
+                APP_MASTER_RESOURCE_ID.to_string(),
+                APP_MASTER_RESOURCE_KEY_ID.to_string(),
+            ))
+            .cloned()
+            .ok_or(KeyServiceError::ResourceKeyMissing)
     }
 
-    fn load_keyvault_state(
-        &self,
-        header: &KeyVaultHeaderV1,
-        vault_key: &[u8],
-    ) -> Result<(KeyVaultState, KeyVaultMaterialized), KeyServiceError> {
-        let records = self.load_all_record_containers()?;
-        KeyVaultState::apply_containers(header, vault_key, &records)
-            .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))
+    /// Seq, kind, and provenance of every keyvault record, in seq order. Records written before
+    /// provenance was tracked have none.
+    pub fn list_records_metadata(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<Vec<KeyVaultRecordMetadata>, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        Ok(state.keyvault_materialized.record_metadata.clone())
     }
 
-    fn load_user_keypair(&self) -> Result<HybridKemRecipient, KeyServiceError> {
-        let state = self.state.as_ref().ok_or(KeyServiceError::CryptoError(
-            "keyvault not loaded".to_string(),
-        ))?;
-        let uk = state
-            .keyvault_materialized
-            .user_key
-            .as_ref()
-            .ok_or(KeyServiceError::CryptoError("missing user key".to_string()))?;
-        Ok(HybridKemRecipient {
-            x25519_secret: uk.x25519_secret,
-            x25519_public: uk.x25519_public,
-            mlkem_decaps_bytes: uk.mlkem_decaps_bytes.clone(),
-            mlkem_encaps_bytes: uk.mlkem_encaps_bytes.clone(),
-            public_bytes: uk.public_bytes.clone(),
-        })
+    pub fn vault_stats(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<KeyVaultStats, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        Ok(KeyVaultStats::new(
+            &state.keyvault_state,
+            &state.keyvault_materialized,
+        ))
     }
 
-    pub fn persist_scope_key(
+    /// Appends a checkpoint record pinning the current head and returns that checkpoint. If
+    /// the head is already a checkpoint record, returns it without appending another.
+    pub fn write_checkpoint(
         &mut self,
         session_id: &SessionId,
-        scope_id: &ScopeId,
-        scope_epoch: ScopeEpoch,
-        scope_key: &[u8],
-    ) -> Result<(), KeyServiceError> {
+    ) -> Result<KeyVaultCheckpoint, KeyServiceError> {
         let header = self.load_header()?;
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let vault_key = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            session.vault_key.clone()
+        let state = &self
+            .state
+            .as_ref()
+            .ok_or(KeyServiceError::VaultNotLoaded)?
+            .keyvault_state;
+        if let Some(last) = &state.last_checkpoint {
+            if last.seq + 1 == state.head_seq {
+                return Ok(last.clone());
+            }
+        }
+        let checkpoint = KeyVaultCheckpoint {
+            seq: state.head_seq,
+            hash: state.head_hash.clone(),
         };
         let record_id = uuid_like(&self.entropy.random_bytes(16));
-        let record = make_store_scope_key_record(&record_id, &scope_id.0, scope_epoch.0, scope_key);
-        let container = {
-            let state = self.state.as_mut().ok_or(KeyServiceError::CryptoError(
-                "keyvault not loaded".to_string(),
-            ))?;
-            let seq = state.keyvault_state.head_seq + 1;
-            state
-                .keyvault_state
-                .append_record(&header, &vault_key, &record, seq)
-                .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?
-        };
-        self.persist_record_container(&container)?;
-        let state = self.state.as_mut().ok_or(KeyServiceError::CryptoError(
-            "keyvault not loaded".to_string(),
-        ))?;
+        let record = make_checkpoint_record(&record_id, &checkpoint);
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
+        state.keyvault_state.last_checkpoint = Some(checkpoint.clone());
+        Ok(checkpoint)
+    }
+
+    /// The most recent checkpoint record in the vault, if any.
+    pub fn latest_checkpoint(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<Option<KeyVaultCheckpoint>, KeyServiceError> {
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        Ok(state.keyvault_state.last_checkpoint.clone())
+    }
+
+    /// Stores `bytes` under `label` as an application blob record; a later put for the same
+    /// label replaces the value.
+    pub fn put_vault_blob(
+        &mut self,
+        session_id: &SessionId,
+        label: &str,
+        bytes: &[u8],
+    ) -> Result<(), KeyServiceError> {
+        let header = self.load_header()?;
+        let now = self.clock.now_ms();
+        self.ensure_session_valid(now, session_id)?;
+        if label.is_empty() || label.len() > MAX_VAULT_BLOB_LABEL_BYTES {
+            return Err(KeyServiceError::InvalidFormat(format!(
+                "vault blob label must be 1-{MAX_VAULT_BLOB_LABEL_BYTES} bytes"
+            )));
+        }
+        if bytes.len() > MAX_VAULT_BLOB_BYTES {
+            return Err(KeyServiceError::InvalidFormat(format!(
+                "vault blob exceeds {MAX_VAULT_BLOB_BYTES} bytes"
+            )));
+        }
+        let record_id = uuid_like(&self.entropy.random_bytes(16));
+        let record = make_store_app_blob_record(&record_id, label, bytes);
+        self.append_and_persist_record(session_id, &header, &record)?;
+        let state = self.state.as_mut().ok_or(KeyServiceError::VaultNotLoaded)?;
         state
             .keyvault_materialized
-            .scope_keys
-            .insert((scope_id.0.clone(), scope_epoch.0), scope_key.to_vec());
+            .app_blobs
+            .insert(label.to_string(), Zeroizing::new(bytes.to_vec()));
         Ok(())
     }
 
-    pub fn persist_resource_key(
+    /// Bytes last stored under `label` by [`Self::put_vault_blob`], if any.
+    pub fn get_vault_blob(
         &mut self,
         session_id: &SessionId,
-        resource_id: &ResourceId,
-        resource_key_id: &ResourceKeyId,
-        resource_key: &[u8],
-    ) -> Result<(), KeyServiceError> {
-        let header = self.load_header()?;
+        label: &str,
+    ) -> Result<Option<Zeroizing<Vec<u8>>>, KeyServiceError> {
         let now = self.clock.now_ms();
         self.ensure_session_valid(now, session_id)?;
-        let vault_key = {
-            let session = self
-                .sessions
-                .get_mut(session_id)
-                .ok_or(KeyServiceError::SessionInvalid)?;
-            session.vault_key.clone()
+        let state = self.state.as_ref().ok_or(KeyServiceError::VaultNotLoaded)?;
+        Ok(state.keyvault_materialized.app_blobs.get(label).cloned())
+    }
+
+    /// Wraps `key` under `kek` with the policy's [`KeyWrapAlg`].
+    fn seal_key_wrap(
+        &self,
+        kek: &[u8],
+        aad: &[u8],
+        key: &[u8],
+    ) -> Result<VaultKeyWrapV1, KeyServiceError> {
+        let alg = self.config.policy.key_wrap_alg;
+        let (nonce, ct, commitment) = match alg {
+            KeyWrapAlg::AesGcm => {
+                let nonce = self.entropy.random_bytes(12);
+                let (ct, commitment) = committing_wrap(kek, aad, key, &nonce)?;
+                (nonce, ct, Some(commitment))
+            }
+            KeyWrapAlg::AesKwp => (Vec::new(), kwp_wrap(kek, aad, key)?, None),
         };
-        let record_id = uuid_like(&self.entropy.random_bytes(16));
-        let record = make_store_resource_key_record(
-            &record_id,
-            &resource_id.0,
-            &resource_key_id.0,
-            resource_key,
-        );
-        let container = {
-            let state = self.state.as_mut().ok_or(KeyServiceError::CryptoError(
-                "keyvault not loaded".to_string(),
-            ))?;
-            let seq = state.keyvault_state.head_seq + 1;
-            state
-                .keyvault_state
-                .append_record(&header, &vault_key, &record, seq)
-                .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?
+        Ok(VaultKeyWrapV1 {
+            aead: AeadId::Aead1,
+            nonce,
+            ct,
+            commitment,
+            alg,
+        })
+    }
+
+    /// This device's enrolled share of the current split, if it holds one.
+    fn unseal_device_share(
+        &self,
+        header: &KeyVaultHeaderV1,
+        config: &DeviceShareConfigV1,
+    ) -> Result<Option<UnsealedDeviceShare>, KeyServiceError> {
+        let stored = self
+            .storage
+            .get("keyvault", "device_share")
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
+            .unwrap_or_default();
+        if stored.is_empty() {
+            return Ok(None);
+        }
+        let slot = DeviceShareSlotV1::decode(&stored)?;
+        // A share from an earlier split is useless.
+        if !ct_eq(&slot.split_id, &config.split_id)
+            || config.share_index(&slot.device_id) != Some(slot.share_index)
+        {
+            return Ok(None);
+        }
+        let anchor = self
+            .device_anchor
+            .as_ref()
+            .ok_or(KeyServiceError::DeviceShareRejected {
+                reason: "no device anchor",
+            })?;
+        let aad = aad_device_share_seal_v1(
+            &header.vault_id,
+            &config.split_id,
+            &slot.device_id.0,
+            slot.share_index,
+        )?;
+        let share = anchor
+            .unseal(DEVICE_SHARE_ANCHOR_LABEL, &aad, &slot.sealed)
+            .map_err(KeyServiceError::CryptoError)?;
+        Ok(Some((slot, Zeroizing::new(share))))
+    }
+
+    /// Opens `K_vault` from the header: the passphrase wrap first, so a wrong passphrase fails
+    /// without a KMS call, then the KMS layer when the vault is in KMS mode.
+    fn open_vault_key_wrap(
+        &self,
+        header: &KeyVaultHeaderV1,
+        kek: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, KeyServiceError> {
+        let inner = unwrap_vault_key(header, kek)?;
+        let Some(kms_key_id) = &header.kms_key_id else {
+            return Ok(inner);
         };
-        self.persist_record_container(&container)?;
-        let state = self.state.as_mut().ok_or(KeyServiceError::CryptoError(
-            "keyvault not loaded".to_string(),
-        ))?;
-        state.keyvault_materialized.resource_keys.insert(
-            (resource_id.0.clone(), resource_key_id.0.clone()),
-            resource_key.to_vec(),
+        let kms = self
+            .kms
+            .as_ref()
+            .ok_or_else(|| KeyServiceError::KmsError("no KMS adapter set".to_string()))?;
+        let aad = aad_keyvault_kms_wrap_v1(&header.vault_id, &header.user_id, kms_key_id)?;
+        let vault_key = Zeroizing::new(
+            kms.unwrap(kms_key_id, &aad, &inner)
+                .map_err(KeyServiceError::KmsError)?,
         );
-        Ok(())
+        if vault_key.len() != 32 {
+            return Err(KeyServiceError::KmsWrapRejected {
+                reason: "kms returned a key of the wrong length",
+            });
+        }
+        Ok(vault_key)
     }
 
-    pub fn store_app_master_key(
-        &mut self,
-        session_id: &SessionId,
-        master_key: &[u8],
-    ) -> Result<(), KeyServiceError> {
-        let resource_id = ResourceId(APP_MASTER_RESOURCE_ID.to_string());
-        let resource_key_id = ResourceKeyId(APP_MASTER_RESOURCE_KEY_ID.to_string());
-        self.persist_resource_key(session_id, &resource_id, &resource_key_id, master_key)
+    /// Seals the header's passphrase check under a key derived from `kek`, for the header's
+    /// current KDF parameters.
+    fn seal_passphrase_check(
+        &self,
+        header: &KeyVaultHeaderV1,
+        kek: &[u8],
+    ) -> Result<PassphraseCheckV1, KeyServiceError> {
+        let aad = aad_keyvault_passphrase_check_v1(&header.vault_id, &header.user_id, &header.kdf)?;
+        let check_key = KEYVAULT_PASSPHRASE_CHECK_KEY_V1.derive(kek, 32)?;
+        let check = KEYVAULT_PASSPHRASE_CHECK_V1.derive(kek, 32)?;
+        let nonce = self.entropy.random_bytes(12);
+        let ct = aead_encrypt::<Aes256Gcm>(&check_key, &aad, &check, &nonce)?;
+        Ok(PassphraseCheckV1 { nonce, ct })
     }
 
-    pub fn get_app_master_key(
-        &mut self,
-        session_id: &SessionId,
-    ) -> Result<Vec<u8>, KeyServiceError> {
-        let now = self.clock.now_ms();
-        self.ensure_session_valid(now, session_id)?;
-        let state = self.state.as_ref().ok_or(KeyServiceError::CryptoError(
-            "keyvault not loaded".to_string(),
-        ))?;
-        state
-            .keyvault_materialized
-            .resource_keys
-            .get(&(
-                APP_MASTER_RESOURCE_ID.to_string(),
-                APP_MASTER_RESOURCE_KEY_ID.to_string(),
-            ))
-            .cloned()
-            .ok_or(KeyServiceError::ResourceKeyMissing)
+    /// Seals `K_vault` for the header under `kek`, through the KMS first in KMS mode.
+    fn seal_vault_key_wrap(
+        &self,
+        header: &KeyVaultHeaderV1,
+        kek: &[u8],
+        aad: &[u8],
+        vault_key: &[u8],
+    ) -> Result<VaultKeyWrapV1, KeyServiceError> {
+        let Some(kms_key_id) = &header.kms_key_id else {
+            return self.seal_key_wrap(kek, aad, vault_key);
+        };
+        let kms = self
+            .kms
+            .as_ref()
+            .ok_or_else(|| KeyServiceError::KmsError("no KMS adapter set".to_string()))?;
+        let kms_aad = aad_keyvault_kms_wrap_v1(&header.vault_id, &header.user_id, kms_key_id)?;
+        let inner = kms
+            .wrap(kms_key_id, &kms_aad, vault_key)
+            .map_err(KeyServiceError::KmsError)?;
+        self.seal_key_wrap(kek, aad, &inner)
     }
 
     fn cbor_limits(&self) -> CborLimits {
@@ -1426,22 +8325,57 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         }
     }
 
+    /// Seals `vault_key` for user-presence unlock under the header's current KDF parameters.
+    fn seal_user_presence_wrap(
+        &self,
+        header: &KeyVaultHeaderV1,
+        credential_id: Vec<u8>,
+        user_presence_secret: &[u8],
+        vault_key: &[u8],
+    ) -> Result<UserPresenceUnlockV1, KeyServiceError> {
+        let prf_key = USER_PRESENCE_UNWRAP_K_VAULT_V1
+            .derive(user_presence_secret, 32)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+        let aad =
+            aad_user_presence_wrap_v1(&header.vault_id, &header.user_id, &header.kdf, header.aead)?;
+        let wrap = self.seal_key_wrap(&prf_key, &aad, vault_key)?;
+        Ok(UserPresenceUnlockV1 {
+            credential_id,
+            nonce: wrap.nonce,
+            ct: wrap.ct,
+            commitment: wrap.commitment,
+            alg: wrap.alg,
+        })
+    }
+
+    /// Like [`Self::load_user_presence_unlock`], with "not enrolled" as `None`; storage and
+    /// decode errors still fail.
+    fn enrolled_user_presence_unlock(
+        &self,
+    ) -> Result<Option<UserPresenceUnlockV1>, KeyServiceError> {
+        match self.load_user_presence_unlock() {
+            Ok(info) => Ok(Some(info)),
+            Err(KeyServiceError::UserPresenceNotEnabled) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     fn load_user_presence_unlock(&self) -> Result<UserPresenceUnlockV1, KeyServiceError> {
         let bytes = self
             .storage
             .get("keyvault", "user_presence")
             .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?
-            .ok_or(KeyServiceError::InvalidFormat(
-                "missing user presence info".to_string(),
-            ))?;
+            .ok_or(KeyServiceError::UserPresenceNotEnabled)?;
         if bytes.is_empty() {
-            return Err(KeyServiceError::InvalidFormat(
-                "user presence not enabled".to_string(),
-            ));
+            return Err(KeyServiceError::UserPresenceNotEnabled);
         }
         UserPresenceUnlockV1::decode(&bytes).map_err(KeyServiceError::from)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(seq = container.seq))
+    )]
     fn persist_record_container(
         &mut self,
         container: &KeyVaultRecordContainerV1,
@@ -1457,13 +8391,16 @@ impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> KeyService<S, C, E>
         if !index.contains(&container.record_id) {
             index.push(container.record_id.clone());
         }
-        let index_value = cbor_array(index.iter().map(|id| cbor_text(id)).collect());
+        self.store_record_index(&index)
+    }
+
+    fn store_record_index(&self, record_ids: &[String]) -> Result<(), KeyServiceError> {
+        let index_value = cbor_array(record_ids.iter().map(|id| cbor_text(id)).collect());
         let index_bytes = encode_canonical_value(&index_value)
             .map_err(|e| KeyServiceError::InvalidCbor(e.to_string()))?;
         self.storage
             .put("keyvault", "record_index", &index_bytes)
-            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))?;
-        Ok(())
+            .map_err(|e| KeyServiceError::StorageError(format!("{e:?}")))
     }
 
     fn load_record_index(&self) -> Result<Vec<String>, KeyServiceError> {
@@ -1498,16 +8435,25 @@ struct UserPresenceUnlockV1 {
     credential_id: Vec<u8>,
     nonce: Vec<u8>,
     ct: Vec<u8>,
+    commitment: Option<Vec<u8>>,
+    /// Field 4, written only for non-default algorithms like the header's wrap.
+    alg: KeyWrapAlg,
 }
 
 impl UserPresenceUnlockV1 {
     fn encode(&self) -> Result<Vec<u8>, CoreError> {
-        let value = crate::cbor::cbor_map(vec![
+        let mut entries = vec![
             (0, crate::cbor::cbor_bytes(&self.credential_id)),
             (1, crate::cbor::cbor_bytes(&self.nonce)),
             (2, crate::cbor::cbor_bytes(&self.ct)),
-        ]);
-        encode_canonical_value(&value)
+        ];
+        if let Some(commitment) = &self.commitment {
+            entries.push((3, crate::cbor::cbor_bytes(commitment)));
+        }
+        if self.alg != KeyWrapAlg::AesGcm {
+            entries.push((4, crate::cbor::cbor_text(self.alg.as_str())));
+        }
+        encode_canonical_value(&crate::cbor::cbor_map(entries))
     }
 
     fn decode(bytes: &[u8]) -> Result<Self, CoreError> {
@@ -1517,14 +8463,362 @@ impl UserPresenceUnlockV1 {
         let credential_id = crate::cbor::req_bytes(map, 0)?;
         let nonce = crate::cbor::req_bytes(map, 1)?;
         let ct = crate::cbor::req_bytes(map, 2)?;
+        let commitment = crate::cbor::opt_bytes(map, 3)?;
+        let alg = match crate::cbor::opt_text(map, 4)? {
+            Some(alg) => KeyWrapAlg::try_from(alg.as_str()).map_err(CoreError::Format)?,
+            None => KeyWrapAlg::AesGcm,
+        };
+        if alg != KeyWrapAlg::AesGcm {
+            check_key_wrap_fields(alg, &nonce, &ct, commitment.as_deref())
+                .map_err(cbor_context("user_presence"))?;
+        }
         Ok(Self {
             credential_id,
             nonce,
             ct,
+            commitment,
+            alg,
+        })
+    }
+}
+
+/// A stored share slot with its unsealed share.
+type UnsealedDeviceShare = (DeviceShareSlotV1, Zeroizing<Vec<u8>>);
+
+#[derive(Clone, Debug)]
+/// This device's share of a device-share split, sealed by its device anchor.
+struct DeviceShareSlotV1 {
+    split_id: Vec<u8>,
+    device_id: DeviceId,
+    share_index: u64,
+    sealed: Vec<u8>,
+}
+
+impl DeviceShareSlotV1 {
+    fn encode(&self) -> Result<Vec<u8>, CoreError> {
+        let value = crate::cbor::cbor_map(vec![
+            (0, crate::cbor::cbor_bytes(&self.split_id)),
+            (1, crate::cbor::cbor_text(&self.device_id.0)),
+            (2, crate::cbor::cbor_uint(self.share_index)),
+            (3, crate::cbor::cbor_bytes(&self.sealed)),
+        ]);
+        encode_canonical_value(&value)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, CoreError> {
+        let value = decode_canonical_value(bytes, &CborLimits::default())?;
+        let map = crate::cbor::as_map(&value)?;
+        Ok(Self {
+            split_id: crate::cbor::req_bytes(map, 0)?,
+            device_id: DeviceId(crate::cbor::req_text(map, 1)?),
+            share_index: crate::cbor::req_uint(map, 2)?,
+            sealed: crate::cbor::req_bytes(map, 3)?,
+        })
+    }
+}
+
+struct HeadMarkerV1 {
+    nonce: Vec<u8>,
+    ct: Vec<u8>,
+}
+
+impl HeadMarkerV1 {
+    fn encode(&self) -> Result<Vec<u8>, CoreError> {
+        let value = crate::cbor::cbor_map(vec![
+            (0, crate::cbor::cbor_bytes(&self.nonce)),
+            (1, crate::cbor::cbor_bytes(&self.ct)),
+        ]);
+        encode_canonical_value(&value)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, CoreError> {
+        let value = decode_canonical_value(bytes, &CborLimits::default())?;
+        let map = crate::cbor::as_map(&value)?;
+        let nonce = crate::cbor::req_bytes(map, 0)?;
+        let ct = crate::cbor::req_bytes(map, 1)?;
+        Ok(Self { nonce, ct })
+    }
+}
+
+/// Writes `version || aead || nonce || ct` into `out`.
+pub(crate) fn seal_resource_payload(
+    resource_key: &[u8],
+    aad: &[u8],
+    nonce: &[u8],
+    plaintext: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), KeyServiceError> {
+    out.clear();
+    write_ciphertext_envelope_header(out, AeadId::Aead1, nonce);
+    out.extend_from_slice(plaintext);
+    aead_encrypt_in_place::<Aes256Gcm>(
+        resource_key,
+        aad,
+        nonce,
+        out,
+        CIPHERTEXT_ENVELOPE_HEADER_LEN,
+    )
+    .map_err(|e| KeyServiceError::CryptoError(e.to_string()))
+}
+
+/// Like [`seal_resource_payload`] with v2 framing, under ratchet message key `message_key`.
+fn seal_ratchet_payload(
+    message_key: &[u8],
+    chain_id: &[u8; RATCHET_CHAIN_ID_LEN],
+    message: u64,
+    aad: &[u8],
+    nonce: &[u8],
+    plaintext: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), KeyServiceError> {
+    out.clear();
+    write_ratchet_envelope_header(out, AeadId::Aead1, chain_id, message, nonce);
+    out.extend_from_slice(plaintext);
+    aead_encrypt_in_place::<Aes256Gcm>(message_key, aad, nonce, out, CIPHERTEXT_RATCHET_HEADER_LEN)
+        .map_err(|e| KeyServiceError::CryptoError(e.to_string()))
+}
+
+/// Opens framed output, falling back to legacy `nonce || ct`; expects `out` to be empty.
+pub(crate) fn open_resource_payload(
+    resource_key: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), KeyServiceError> {
+    let legacy = parse_legacy_ciphertext(ciphertext).ok_or(KeyServiceError::DecryptFailed)?;
+    if let Some(framed) = parse_ratchet_envelope(ciphertext) {
+        if let Ok(message_key) = ratchet_message_key(resource_key, framed.chain_id, framed.message)
+        {
+            out.extend_from_slice(framed.ct);
+            if aead_decrypt_in_place::<Aes256Gcm>(&message_key, aad, framed.nonce, out).is_ok() {
+                return Ok(());
+            }
+            out.clear();
+        }
+    }
+    if let Some(framed) = parse_ciphertext_envelope(ciphertext) {
+        out.extend_from_slice(framed.ct);
+        if aead_decrypt_in_place::<Aes256Gcm>(resource_key, aad, framed.nonce, out).is_ok() {
+            return Ok(());
+        }
+    }
+    out.clear();
+    out.extend_from_slice(legacy.ct);
+    aead_decrypt_in_place::<Aes256Gcm>(resource_key, aad, legacy.nonce, out)
+        .map_err(|_| KeyServiceError::DecryptFailed)
+}
+
+/// Stored headers that fail to decode are reported as invalid CBOR, except for weak KDF
+/// parameters, which keep their own error.
+fn header_error(err: CoreError) -> KeyServiceError {
+    match err {
+        CoreError::WeakKdf { .. } => err.into(),
+        err => KeyServiceError::InvalidCbor(err.to_string()),
+    }
+}
+
+/// Fresh parameters for a passphrase change, raised to `minimums` where those exceed the
+/// defaults. Test vaults stay on the fast profile; the policy check in `run_kdf` already
+/// allowed it.
+fn next_kdf_params(
+    current: &KdfParams,
+    minimums: &KdfMinimums,
+) -> Result<KdfParams, KeyServiceError> {
+    #[cfg(feature = "test-utils")]
+    if current.id == INSECURE_TEST_KDF_ID {
+        return Ok(KdfParams::insecure_fast_for_tests());
+    }
+    #[cfg(not(feature = "test-utils"))]
+    let _ = current;
+    let mut params =
+        KdfParams::new_random().map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+    params.memory_kib = params.memory_kib.max(minimums.memory_kib);
+    params.iterations = params.iterations.max(minimums.iterations);
+    params.parallelism = params.parallelism.max(minimums.parallelism);
+    if params.salt.len() < minimums.salt_bytes {
+        params.salt = crate::crypto::random_bytes(minimums.salt_bytes)
+            .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+    }
+    Ok(params)
+}
+
+fn device_share_config(header: &KeyVaultHeaderV1) -> Result<&DeviceShareConfigV1, KeyServiceError> {
+    header
+        .device_shares
+        .as_ref()
+        .ok_or(KeyServiceError::DeviceShareRejected {
+            reason: "device shares not enabled",
         })
+}
+
+/// Unwraps `K_vault` from the header with a passphrase-derived KEK. A failed unwrap is
+/// `VaultCorrupted` when `kek` opens the header's passphrase check, and `WrongPassphrase`
+/// otherwise, including on headers without a check.
+fn unwrap_vault_key(
+    header: &KeyVaultHeaderV1,
+    kek: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, KeyServiceError> {
+    let aad = aad_keyvault_keywrap_v1(&header.vault_id, &header.user_id, &header.kdf, header.aead)?;
+    unwrap_key_with(
+        header.vault_key_wrap.alg,
+        kek,
+        &aad,
+        &header.vault_key_wrap.nonce,
+        &header.vault_key_wrap.ct,
+        header.vault_key_wrap.commitment.as_deref(),
+    )
+    .or_else(|_| match passphrase_check_opens(header, kek)? {
+        true => Err(KeyServiceError::VaultCorrupted {
+            reason: "vault key wrap does not open under the passphrase key",
+        }),
+        false => Err(KeyServiceError::WrongPassphrase),
+    })
+}
+
+/// Opens a user-presence wrap sealed under the header's KDF parameters.
+fn open_user_presence_wrap(
+    header: &KeyVaultHeaderV1,
+    info: &UserPresenceUnlockV1,
+    user_presence_secret: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, KeyServiceError> {
+    let prf_key = USER_PRESENCE_UNWRAP_K_VAULT_V1
+        .derive(user_presence_secret, 32)
+        .map_err(|e| KeyServiceError::CryptoError(e.to_string()))?;
+    let aad =
+        aad_user_presence_wrap_v1(&header.vault_id, &header.user_id, &header.kdf, header.aead)?;
+    unwrap_key_with(
+        info.alg,
+        &prf_key,
+        &aad,
+        &info.nonce,
+        &info.ct,
+        info.commitment.as_deref(),
+    )
+    .map_err(|_| KeyServiceError::VaultKeyUnwrapFailed)
+}
+
+/// Whether `kek` opens the header's passphrase check; false when the header has none.
+fn passphrase_check_opens(header: &KeyVaultHeaderV1, kek: &[u8]) -> Result<bool, KeyServiceError> {
+    let Some(check) = &header.passphrase_check else {
+        return Ok(false);
+    };
+    let aad = aad_keyvault_passphrase_check_v1(&header.vault_id, &header.user_id, &header.kdf)?;
+    let check_key = KEYVAULT_PASSPHRASE_CHECK_KEY_V1.derive(kek, 32)?;
+    let expected = KEYVAULT_PASSPHRASE_CHECK_V1.derive(kek, 32)?;
+    Ok(
+        aead_decrypt::<Aes256Gcm>(&check_key, &aad, &check.nonce, &check.ct)
+            .is_ok_and(|opened| ct_eq(&opened, &expected)),
+    )
+}
+
+/// Imports measure record ciphertext minus the AEAD tag, matching the append-side check.
+fn import_limits_ok(
+    policy: &KeyServicePolicy,
+    record_count: u64,
+    records: &[KeyVaultRecordContainerV1],
+) -> Result<(), KeyServiceError> {
+    if record_count > policy.max_total_records {
+        return Err(KeyServiceError::VaultLimitExceeded {
+            limit: "max_total_records",
+        });
+    }
+    if records
+        .iter()
+        .any(|record| record.ct.len().saturating_sub(16) > policy.max_record_bytes)
+    {
+        return Err(KeyServiceError::VaultLimitExceeded {
+            limit: "max_record_bytes",
+        });
+    }
+    Ok(())
+}
+
+/// Hash of the highest-seq record container, as an export manifest pins it. Walks the chain
+/// so the head also vouches for every record before it.
+fn snapshot_head_hash(records: &[KeyVaultRecordContainerV1]) -> Result<Vec<u8>, KeyServiceError> {
+    let mut sorted: Vec<&KeyVaultRecordContainerV1> = records.iter().collect();
+    sorted.sort_by_key(|record| record.seq);
+    let mut head: Option<(u64, Vec<u8>)> = None;
+    for record in sorted {
+        if let Some((seq, hash)) = &head {
+            if record.seq != seq + 1 || !ct_eq(&record.prev_hash, hash) {
+                return Err(KeyServiceError::ExportManifestInvalid {
+                    reason: "record chain broken",
+                });
+            }
+        }
+        let hash = sha256(&encode_keyvault_record_container_v1(record)?).to_vec();
+        head = Some((record.seq, hash));
+    }
+    Ok(head.map_or_else(|| vec![0u8; 32], |(_, hash)| hash))
+}
+
+/// A damaged log may be salvaged to a chain that ends before the last seen head, but some
+/// readable stored record must still reach it; records are written before the marker, so a
+/// torn last write leaves the marker behind. Below the break the salvaged chain is checked like
+/// an intact one. Returns the seen head's seq when the salvaged chain is behind it.
+fn check_salvage_not_rolled_back(
+    state: &KeyVaultState,
+    report: &KeyVaultSalvageReport,
+    containers: &[KeyVaultRecordContainerV1],
+    seen_head: Option<(u64, Vec<u8>)>,
+) -> Result<Option<u64>, KeyServiceError> {
+    let Some((seen_seq, seen_hash)) = seen_head else {
+        return Ok(None);
+    };
+    if report
+        .break_seq
+        .is_some_and(|break_seq| seen_seq < break_seq)
+    {
+        check_not_rolled_back(state, Some(&(seen_seq, seen_hash)))?;
+        return Ok(None);
+    }
+    if containers.iter().all(|c| c.seq < seen_seq) {
+        return Err(KeyServiceError::RollbackDetected);
+    }
+    Ok((state.head_seq < seen_seq).then_some(seen_seq))
+}
+
+/// The loaded chain must reach the last seen head and contain the same record at that seq.
+fn check_not_rolled_back(
+    state: &KeyVaultState,
+    seen_head: Option<&(u64, Vec<u8>)>,
+) -> Result<(), KeyServiceError> {
+    let Some((seen_seq, seen_hash)) = seen_head else {
+        return Ok(());
+    };
+    if *seen_seq == 0 {
+        return Ok(());
+    }
+    if state.head_seq < *seen_seq {
+        return Err(KeyServiceError::RollbackDetected);
+    }
+    let container = usize::try_from(*seen_seq - 1)
+        .ok()
+        .and_then(|index| state.records.get(index))
+        .ok_or(KeyServiceError::RollbackDetected)?;
+    let hash = sha256(&encode_keyvault_record_container_v1(container)?);
+    if !ct_eq(&hash, seen_hash) {
+        return Err(KeyServiceError::RollbackDetected);
+    }
+    Ok(())
+}
+
+fn assurance_label(assurance: SessionAssurance) -> &'static str {
+    match assurance {
+        SessionAssurance::Passphrase => "passphrase",
+        SessionAssurance::UserPresence => "user-presence",
+        SessionAssurance::DeviceShares => "device-shares",
+        SessionAssurance::MultiFactor => "multi-factor",
+        SessionAssurance::HardwareBacked => "hardware-backed",
     }
 }
 
+/// Storage key of the encrypt count for a scope key under a rotation policy.
+fn rotation_usage_key(scope_id: &ScopeId, scope_epoch: ScopeEpoch) -> String {
+    format!("rotation_ops:{}:{}", scope_id.0, scope_epoch.0)
+}
+
 fn uuid_like(bytes: &[u8]) -> String {
     let hex = hex_id(bytes);
     format!(
@@ -1553,6 +8847,16 @@ fn fingerprint_bytes_hex(bytes: &[u8]) -> String {
     hex::encode(fingerprint_bytes(bytes))
 }
 
+fn contact_entry(user_id: &str, contact: &ContactKey) -> Contact {
+    Contact {
+        user_id: UserId(user_id.to_string()),
+        public_bytes: contact.public_bytes.clone(),
+        fingerprint_hex: fingerprint_bytes_hex(&contact.public_bytes),
+        scope_id: contact.scope_id.clone(),
+        added_at_ms: contact.added_at_ms,
+    }
+}
+
 fn fingerprint_signer(signer: &SignerKeys) -> String {
     let mut data = Vec::new();
     data.extend_from_slice(&signer.ed25519_pub);
@@ -1560,6 +8864,64 @@ fn fingerprint_signer(signer: &SignerKeys) -> String {
     fingerprint_bytes_hex(&data)
 }
 
+/// Only unrestricted handles under an active epoch, in a scope without a rotation policy,
+/// may be used without the checks in `check_capability`.
+fn cacheable_key<'a>(
+    state: Option<&KeyServiceState>,
+    entry: &'a HandleEntry,
+) -> Option<&'a SecretBytes> {
+    match entry {
+        HandleEntry::ResourceKey {
+            key,
+            ops: CapabilityOps::ALL,
+            scope,
+            restriction: None,
+            ..
+        } => {
+            let active = state.is_none_or(|state| {
+                let rotated = scope.as_ref().is_some_and(|(scope_id, _)| {
+                    state
+                        .keyvault_materialized
+                        .rotation_policies
+                        .contains_key(&scope_id.0)
+                });
+                !rotated
+                    && state
+                        .signer_roster
+                        .ensure_epoch_active(scope.as_ref())
+                        .is_ok()
+            });
+            active.then_some(key)
+        }
+        _ => None,
+    }
+}
+
+/// Ops a grant's policy allows: key 0 of the policy map holds [`CapabilityOps::bits`];
+/// without it (or without a policy) every op is allowed.
+fn grant_policy_ops(
+    policy: Option<&ciborium::value::Value>,
+) -> Result<CapabilityOps, KeyServiceError> {
+    let Some(policy) = policy else {
+        return Ok(CapabilityOps::ALL);
+    };
+    let map = as_map(policy).map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+    match opt_uint(map, 0).map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))? {
+        None => Ok(CapabilityOps::ALL),
+        Some(bits) => CapabilityOps::from_bits(bits)
+            .ok_or_else(|| KeyServiceError::InvalidFormat("invalid grant policy ops".to_string())),
+    }
+}
+
+/// `now + ttl`, or `ClockAnomaly` when a clock near `u64::MAX` would wrap it.
+fn session_expiry(now: u64, ttl: u64) -> Result<u64, KeyServiceError> {
+    now.checked_add(ttl).ok_or(KeyServiceError::ClockAnomaly {
+        reason: "session expiry overflows",
+        now_ms: now,
+        last_ms: now,
+    })
+}
+
 fn hash_array(bytes: &[u8]) -> Result<[u8; 32], KeyServiceError> {
     if bytes.len() != 32 {
         return Err(KeyServiceError::InvalidFormat(
@@ -1571,22 +8933,110 @@ fn hash_array(bytes: &[u8]) -> Result<[u8; 32], KeyServiceError> {
     Ok(out)
 }
 
-fn extract_signer_keys(scope_state: &ScopeStateV1) -> Result<SignerKeys, KeyServiceError> {
+fn scope_state_ids<'a>(
+    scope_state: &'a ScopeStateV1,
+    payload: &'a ScopeStatePayload,
+) -> Vec<(&'static str, &'a str)> {
+    let mut ids = vec![
+        ("scopeId", scope_state.scope_id.0.as_str()),
+        ("deviceId", &scope_state.signer_device_id.0),
+    ];
+    if let ScopeStatePayload::MembershipChange {
+        added,
+        removed,
+        roles,
+        ..
+    } = payload
+    {
+        ids.extend(
+            added
+                .iter()
+                .chain(removed)
+                .map(|user| ("userId", user.0.as_str())),
+        );
+        ids.extend(
+            roles
+                .iter()
+                .map(|(device, _)| ("deviceId", device.0.as_str())),
+        );
+    }
+    ids
+}
+
+fn grant_ids(grant: &ResourceGrantV1) -> [(&'static str, &str); 5] {
+    [
+        ("grantId", &grant.grant_id),
+        ("scopeId", &grant.scope_id.0),
+        ("resourceId", &grant.resource_id.0),
+        ("resourceKeyId", &grant.resource_key_id.0),
+        ("deviceId", &grant.signer_device_id.0),
+    ]
+}
+
+/// Decodes the typed payload and applies per-kind checks that need no roster state.
+fn check_scope_state_payload(
+    scope_state: &ScopeStateV1,
+) -> Result<ScopeStatePayload, KeyServiceError> {
     if scope_state.sig_suite != SigCiphersuiteId::HybridSig1 {
-        return Err(KeyServiceError::InvalidFormat(
-            "unsupported sig suite".to_string(),
-        ));
+        return Err(KeyServiceError::UnsupportedSuite);
     }
-    let payload = scope_state.payload.clone();
-    let map =
-        crate::cbor::as_map(&payload).map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
-    let ed25519_pub = crate::cbor::req_bytes(map, 1)
+    let payload = scope_state
+        .typed_payload()
         .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
-    let mldsa_pub = crate::cbor::req_bytes(map, 2)
-        .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
-    Ok(SignerKeys {
-        sig_suite: SigCiphersuiteId::HybridSig1,
-        ed25519_pub,
-        mldsa_pub,
-    })
+    match &payload {
+        ScopeStatePayload::Genesis { .. } => {
+            if !ct_eq(&scope_state.prev_hash, &[0u8; 32]) {
+                return Err(KeyServiceError::InvalidFormat(
+                    "genesis scope state must have zero prev_hash".to_string(),
+                ));
+            }
+        }
+        ScopeStatePayload::EpochBump {
+            from_epoch,
+            to_epoch,
+            ..
+        } => {
+            if *to_epoch != scope_state.scope_epoch || from_epoch.checked_add(1) != Some(*to_epoch)
+            {
+                return Err(KeyServiceError::InvalidFormat(
+                    "epoch bump must advance scope_epoch by one".to_string(),
+                ));
+            }
+        }
+        ScopeStatePayload::MembershipChange {
+            added,
+            removed,
+            roles,
+            member_keys,
+            ..
+        } => {
+            if added.iter().any(|user| removed.contains(user)) {
+                return Err(KeyServiceError::InvalidFormat(
+                    "membership change adds and removes the same user".to_string(),
+                ));
+            }
+            if roles
+                .iter()
+                .enumerate()
+                .any(|(i, (device_id, _))| roles[..i].iter().any(|(seen, _)| seen == device_id))
+            {
+                return Err(KeyServiceError::InvalidFormat(
+                    "membership change assigns a device more than one role".to_string(),
+                ));
+            }
+            for (i, (user_id, public_bytes)) in member_keys.iter().enumerate() {
+                if !added.contains(user_id)
+                    || member_keys[..i].iter().any(|(seen, _)| seen == user_id)
+                {
+                    return Err(KeyServiceError::InvalidFormat(
+                        "membership change has a member key for a user it does not add once"
+                            .to_string(),
+                    ));
+                }
+                decode_user_public_bytes(public_bytes)
+                    .map_err(|e| KeyServiceError::InvalidFormat(e.to_string()))?;
+            }
+        }
+    }
+    Ok(payload)
 }
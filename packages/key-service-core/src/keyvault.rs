@@ -1,24 +1,101 @@
 //! KeyVault record storage, integrity checks, and merge logic.
 
-use crate::aad::aad_keyvault_record_v1;
-use crate::crypto::{aead_decrypt, encrypt_vault_record};
+use crate::aad::{aad_keyvault_index_v1, aad_keyvault_record_v1};
+use crate::cancel::CancellationToken;
+use crate::crypto::{aead_decrypt, ct_eq, encrypt_vault_record};
 use crate::error::{CoreError, CoreResult};
 use crate::formats::{
     decode_keyvault_record_plain_v1, encode_keyvault_record_container_v1,
     encode_keyvault_record_plain_v1, KeyVaultHeaderV1, KeyVaultRecordContainerV1,
-    KeyVaultRecordPlainV1,
+    KeyVaultRecordPlainV1, KeyVaultRecordProvenance,
 };
 use crate::hash::sha256;
-use crate::types::{AeadId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId};
+use crate::types::{AeadId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, ScopeRotationPolicy};
 use aes_gcm::Aes256Gcm;
-use std::collections::{HashMap, HashSet};
-use zeroize::Zeroize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use zeroize::Zeroizing;
+
+/// Record kinds reserved for opaque application data; the core never interprets their contents.
+pub const APP_RECORD_KINDS: std::ops::RangeInclusive<u64> = 0x100..=0x1ff;
+/// Labelled application blob, written by `KeyService::put_vault_blob`.
+pub const APP_BLOB_RECORD_KIND: u64 = 0x100;
+
+/// Record kind pinning the chain position just before it; see [`KeyVaultCheckpoint`].
+pub const CHECKPOINT_RECORD_KIND: u64 = 5;
+/// Record kind holding a device's share of a co-signed Ed25519 key; see [`crate::cosign`].
+pub const COSIGN_SHARE_RECORD_KIND: u64 = 6;
+/// Record kind marking a key envelope or resource grant id as ingested; see [`SeenIdKind`].
+pub const SEEN_ID_RECORD_KIND: u64 = 7;
+/// Record kind holding this member's private state of a scope's CGKA group; the latest record
+/// per scope wins. See [`crate::cgka`].
+pub const CGKA_GROUP_RECORD_KIND: u64 = 8;
+/// Record kind raising the next unpublished one-time prekey id; see
+/// [`crate::ciphersuite::derive_prekey_keypair`].
+pub const PREKEYS_PUBLISHED_RECORD_KIND: u64 = 9;
+/// Record kind marking a one-time prekey as consumed by an ingested key envelope.
+pub const PREKEY_CONSUMED_RECORD_KIND: u64 = 10;
+/// Record kind setting or clearing a scope's [`ScopeRotationPolicy`]; the latest record per
+/// scope wins.
+pub const ROTATION_POLICY_RECORD_KIND: u64 = 11;
+/// Record kind adding, replacing, or removing another user's public KEM key in the recipient
+/// key directory; the latest record per user wins. See [`ContactKey`].
+pub const CONTACT_RECORD_KIND: u64 = 12;
+
+/// Which id a [`SEEN_ID_RECORD_KIND`] record holds. Ids are tracked per scope, so the same
+/// id in two scopes counts as two items.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SeenIdKind {
+    Envelope,
+    Grant,
+}
+
+impl SeenIdKind {
+    fn tag(self) -> u64 {
+        match self {
+            Self::Envelope => 0,
+            Self::Grant => 1,
+        }
+    }
+
+    fn from_tag(tag: u64) -> CoreResult<Self> {
+        match tag {
+            0 => Ok(Self::Envelope),
+            1 => Ok(Self::Grant),
+            _ => Err(CoreError::Format("unknown seen id kind".to_string())),
+        }
+    }
+}
+
+/// Seq and record hash of the chain head at some point. Every record hash covers its
+/// predecessor's, so the hash commits to the whole chain up to `seq`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultCheckpoint {
+    pub seq: u64,
+    pub hash: Vec<u8>,
+}
+
+impl KeyVaultCheckpoint {
+    fn from_record(record: &KeyVaultRecordPlainV1) -> CoreResult<Self> {
+        let map = crate::cbor::as_map(&record.payload)?;
+        Ok(Self {
+            seq: crate::cbor::req_uint(map, 0)?,
+            hash: crate::cbor::req_bytes(map, 1)?,
+        })
+    }
+
+    /// Whether this checkpoint matches the chain head before the record carrying it.
+    fn matches(&self, seq: u64, hash: &[u8]) -> bool {
+        self.seq == seq && ct_eq(&self.hash, hash)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct KeyVaultState {
     pub head_seq: u64,
     pub head_hash: Vec<u8>,
     pub records: Vec<KeyVaultRecordContainerV1>,
+    /// Most recent checkpoint record applied, already checked against the chain.
+    pub last_checkpoint: Option<KeyVaultCheckpoint>,
 }
 
 impl Default for KeyVaultState {
@@ -27,16 +104,294 @@ impl Default for KeyVaultState {
             head_seq: 0,
             head_hash: vec![0u8; 32],
             records: Vec::new(),
+            last_checkpoint: None,
+        }
+    }
+}
+
+/// Outcome of `KeyVaultState::verify_containers`; `head_seq`/`head_hash` cover the valid prefix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultChainReport {
+    pub record_count: u64,
+    pub head_seq: u64,
+    pub head_hash: Vec<u8>,
+    pub first_invalid_seq: Option<u64>,
+    pub problem: Option<String>,
+}
+
+/// A stored record that salvage could not keep.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultDamagedRecord {
+    pub record_id: String,
+    /// `None` if the stored container could not be decoded at all.
+    pub seq: Option<u64>,
+    pub reason: String,
+}
+
+/// What a salvage unlock found and would change on repair.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultSalvageReport {
+    /// Seq at which the stored chain stops being valid; `None` if it was intact.
+    pub break_seq: Option<u64>,
+    /// Readable records from after the break, renumbered onto the valid prefix.
+    pub rechained_record_ids: Vec<String>,
+    /// Records from after the break left out because the log was truncated there instead.
+    pub truncated_record_ids: Vec<String>,
+    pub damaged: Vec<KeyVaultDamagedRecord>,
+    /// Seq of the head marker when the salvaged chain ends before it: repairing moves the
+    /// marker back and gives up the records in between.
+    pub sealed_head_seq: Option<u64>,
+}
+
+/// Result of [`KeyVaultState::salvage_containers`].
+#[derive(Debug)]
+pub struct KeyVaultSalvage {
+    pub state: KeyVaultState,
+    pub materialized: KeyVaultMaterialized,
+    pub report: KeyVaultSalvageReport,
+}
+
+/// Non-secret description of one applied keyvault record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultRecordMetadata {
+    pub seq: u64,
+    pub record_id: String,
+    pub kind: u64,
+    pub provenance: Option<KeyVaultRecordProvenance>,
+}
+
+impl KeyVaultRecordMetadata {
+    pub fn new(seq: u64, record: &KeyVaultRecordPlainV1) -> Self {
+        Self {
+            seq,
+            record_id: record.record_id.clone(),
+            kind: record.kind,
+            provenance: record.provenance.clone(),
+        }
+    }
+}
+
+/// Size and shape of an unlocked keyvault, without any key material.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultStats {
+    /// Applied record count per kind, ascending by kind.
+    pub records_by_kind: BTreeMap<u64, u64>,
+    /// Sum of record ciphertext lengths.
+    pub encrypted_bytes: u64,
+    pub chain_length: u64,
+    pub scope_count: u64,
+    /// Scope keys held, counting each epoch of a scope separately.
+    pub scope_epoch_count: u64,
+    pub resource_count: u64,
+    /// Creation time of the newest record that carries provenance.
+    pub last_append_ms: Option<u64>,
+}
+
+impl KeyVaultStats {
+    pub fn new(state: &KeyVaultState, materialized: &KeyVaultMaterialized) -> Self {
+        let mut records_by_kind = BTreeMap::new();
+        for record in &materialized.record_metadata {
+            *records_by_kind.entry(record.kind).or_insert(0) += 1;
+        }
+        let scopes: HashSet<&String> = materialized.scope_keys.keys().map(|(id, _)| id).collect();
+        // Deferred resource keys are only in the index.
+        let resources: HashSet<&String> = materialized
+            .index
+            .resource_keys
+            .keys()
+            .map(|(id, _)| id)
+            .collect();
+        Self {
+            records_by_kind,
+            encrypted_bytes: state.records.iter().map(|r| r.ct.len() as u64).sum(),
+            chain_length: state.head_seq,
+            scope_count: scopes.len() as u64,
+            scope_epoch_count: materialized.scope_keys.len() as u64,
+            resource_count: resources.len() as u64,
+            last_append_ms: materialized
+                .record_metadata
+                .iter()
+                .rev()
+                .find_map(|record| record.provenance.as_ref())
+                .map(|provenance| provenance.created_at_ms),
+        }
+    }
+}
+
+/// Which record currently holds each scope and resource key, as of `head_seq`. Stored sealed
+/// under the vault key so a lazy unlock can skip decrypting resource-key records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVaultIndex {
+    pub head_seq: u64,
+    pub head_hash: Vec<u8>,
+    /// `(scope_id, scope_epoch)` to record id.
+    pub scope_keys: BTreeMap<(String, u64), String>,
+    /// `(resource_id, resource_key_id)` to record id.
+    pub resource_keys: BTreeMap<(String, String), String>,
+}
+
+impl Default for KeyVaultIndex {
+    fn default() -> Self {
+        Self {
+            head_seq: 0,
+            head_hash: vec![0u8; 32],
+            scope_keys: BTreeMap::new(),
+            resource_keys: BTreeMap::new(),
         }
     }
 }
 
+impl KeyVaultIndex {
+    /// Whether the index was written at `state`'s head.
+    pub fn covers(&self, state: &KeyVaultState) -> bool {
+        self.head_seq == state.head_seq && ct_eq(&self.head_hash, &state.head_hash)
+    }
+
+    /// Resource-key records a lazy unlock may skip, keyed by record id.
+    pub fn deferrable_resource_records(&self) -> HashMap<String, (String, String)> {
+        self.resource_keys
+            .iter()
+            .map(|(target, record_id)| (record_id.clone(), target.clone()))
+            .collect()
+    }
+
+    /// Points the record's target, if it has one, at the record. Latest wins, as in the merge.
+    pub fn note_record(&mut self, record: &KeyVaultRecordPlainV1) -> CoreResult<()> {
+        match record.kind {
+            3 => {
+                let map = crate::cbor::as_map(&record.payload)?;
+                let target = (
+                    crate::cbor::req_text(map, 0)?,
+                    crate::cbor::req_uint(map, 1)?,
+                );
+                self.scope_keys.insert(target, record.record_id.clone());
+            }
+            4 => {
+                let map = crate::cbor::as_map(&record.payload)?;
+                let target = (
+                    crate::cbor::req_text(map, 0)?,
+                    crate::cbor::req_text(map, 1)?,
+                );
+                self.resource_keys.insert(target, record.record_id.clone());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn advance(&mut self, seq: u64, hash: &[u8]) {
+        self.head_seq = seq;
+        self.head_hash = hash.to_vec();
+    }
+
+    pub fn seal(&self, header: &KeyVaultHeaderV1, vault_key: &[u8]) -> CoreResult<Vec<u8>> {
+        let scope_keys = self
+            .scope_keys
+            .iter()
+            .map(|((scope_id, epoch), record_id)| {
+                crate::cbor::cbor_map(vec![
+                    (0, crate::cbor::cbor_text(scope_id)),
+                    (1, crate::cbor::cbor_uint(*epoch)),
+                    (2, crate::cbor::cbor_text(record_id)),
+                ])
+            })
+            .collect();
+        let resource_keys = self
+            .resource_keys
+            .iter()
+            .map(|((resource_id, resource_key_id), record_id)| {
+                crate::cbor::cbor_map(vec![
+                    (0, crate::cbor::cbor_text(resource_id)),
+                    (1, crate::cbor::cbor_text(resource_key_id)),
+                    (2, crate::cbor::cbor_text(record_id)),
+                ])
+            })
+            .collect();
+        let plaintext = crate::cbor::encode_canonical_value(&crate::cbor::cbor_map(vec![
+            (0, crate::cbor::cbor_uint(self.head_seq)),
+            (1, crate::cbor::cbor_bytes(&self.head_hash)),
+            (2, crate::cbor::cbor_array(scope_keys)),
+            (3, crate::cbor::cbor_array(resource_keys)),
+        ]))?;
+        let aad = aad_keyvault_index_v1(&header.vault_id, &header.user_id, header.aead)?;
+        let (nonce, ct) = encrypt_vault_record(vault_key, &aad, &plaintext)?;
+        crate::cbor::encode_canonical_value(&crate::cbor::cbor_map(vec![
+            (0, crate::cbor::cbor_bytes(&nonce)),
+            (1, crate::cbor::cbor_bytes(&ct)),
+        ]))
+    }
+
+    pub fn open(
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+        bytes: &[u8],
+        limits: &crate::cbor::CborLimits,
+    ) -> CoreResult<Self> {
+        let sealed = crate::cbor::decode_canonical_value(bytes, limits)?;
+        let sealed = crate::cbor::as_map(&sealed)?;
+        let nonce = crate::cbor::req_bytes(sealed, 0)?;
+        let ct = crate::cbor::req_bytes(sealed, 1)?;
+        let aad = aad_keyvault_index_v1(&header.vault_id, &header.user_id, header.aead)?;
+        let plaintext = aead_decrypt::<Aes256Gcm>(vault_key, &aad, &nonce, &ct)
+            .map_err(|_| CoreError::Format("keyvault index decrypt failed".to_string()))?;
+        let value = crate::cbor::decode_canonical_value(&plaintext, limits)?;
+        let map = crate::cbor::as_map(&value)?;
+        let mut index = Self {
+            head_seq: crate::cbor::req_uint(map, 0)?,
+            head_hash: crate::cbor::req_bytes(map, 1)?,
+            ..Self::default()
+        };
+        for entry in crate::cbor::req_array(map, 2)? {
+            let entry = crate::cbor::as_map(entry)?;
+            index.scope_keys.insert(
+                (
+                    crate::cbor::req_text(entry, 0)?,
+                    crate::cbor::req_uint(entry, 1)?,
+                ),
+                crate::cbor::req_text(entry, 2)?,
+            );
+        }
+        for entry in crate::cbor::req_array(map, 3)? {
+            let entry = crate::cbor::as_map(entry)?;
+            index.resource_keys.insert(
+                (
+                    crate::cbor::req_text(entry, 0)?,
+                    crate::cbor::req_text(entry, 1)?,
+                ),
+                crate::cbor::req_text(entry, 2)?,
+            );
+        }
+        Ok(index)
+    }
+}
+
 #[derive(Default)]
 pub struct KeyVaultMaterialized {
     pub user_key: Option<crate::ciphersuite::HybridKemRecipient>,
     pub device_signing_keys: HashMap<String, crate::ciphersuite::HybridSignatureKeypair>,
-    pub scope_keys: HashMap<(String, u64), Vec<u8>>,
-    pub resource_keys: HashMap<(String, String), Vec<u8>>,
+    pub scope_keys: HashMap<(String, u64), Zeroizing<Vec<u8>>>,
+    /// Creation time of each scope key record that carries provenance.
+    pub scope_key_created_ms: HashMap<(String, u64), u64>,
+    /// Rotation policy per scope id.
+    pub rotation_policies: HashMap<String, ScopeRotationPolicy>,
+    pub resource_keys: HashMap<(String, String), Zeroizing<Vec<u8>>>,
+    /// Co-signing shares by device id; such a device signs only with the server.
+    pub cosign_shares: HashMap<String, crate::cosign::CosignDeviceShare>,
+    /// Latest bytes per application blob label.
+    pub app_blobs: HashMap<String, Zeroizing<Vec<u8>>>,
+    /// `(kind, scope_id, id)` of every ingested envelope and grant.
+    pub seen_ids: HashSet<(SeenIdKind, String, String)>,
+    /// Latest encoded [`crate::cgka::CgkaGroup`] per scope id.
+    pub cgka_groups: HashMap<String, Zeroizing<Vec<u8>>>,
+    /// Prekey ids below this have been published.
+    pub next_prekey_id: u64,
+    /// Published prekey ids an ingested envelope has already used.
+    pub consumed_prekeys: HashSet<u64>,
+    /// Recipient key directory by user id.
+    pub contacts: HashMap<String, ContactKey>,
+    /// One entry per applied record, in seq order.
+    pub record_metadata: Vec<KeyVaultRecordMetadata>,
+    pub index: KeyVaultIndex,
 }
 
 impl std::fmt::Debug for KeyVaultMaterialized {
@@ -44,26 +399,62 @@ impl std::fmt::Debug for KeyVaultMaterialized {
         f.debug_struct("KeyVaultMaterialized")
             .field("user_key", &self.user_key.as_ref().map(|_| "<redacted>"))
             .field("device_signing_keys", &self.device_signing_keys.len())
+            .field("cosign_shares", &self.cosign_shares.len())
             .field("scope_keys", &self.scope_keys.len())
             .field("resource_keys", &self.resource_keys.len())
+            .field("app_blobs", &self.app_blobs.len())
+            .field("seen_ids", &self.seen_ids.len())
+            .field("cgka_groups", &self.cgka_groups.len())
+            .field("next_prekey_id", &self.next_prekey_id)
+            .field("consumed_prekeys", &self.consumed_prekeys.len())
+            .field("rotation_policies", &self.rotation_policies.len())
+            .field("contacts", &self.contacts.len())
+            .field("record_metadata", &self.record_metadata.len())
+            .field("index_head_seq", &self.index.head_seq)
             .finish()
     }
 }
 
-impl Drop for KeyVaultMaterialized {
-    fn drop(&mut self) {
-        if let Some(mut user) = self.user_key.take() {
-            user.zeroize();
-        }
-        for (_, mut keypair) in self.device_signing_keys.drain() {
-            keypair.zeroize();
-        }
-        for (_, mut key) in self.scope_keys.drain() {
-            key.zeroize();
-        }
-        for (_, mut key) in self.resource_keys.drain() {
-            key.zeroize();
-        }
+/// Another user's public KEM key as the recipient key directory holds it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContactKey {
+    /// Encoded hybrid KEM public key, as `UserPublicKeyResponse.public_bytes`.
+    pub public_bytes: Vec<u8>,
+    /// Scope whose signed membership change carried the key; `None` when added by hand.
+    pub scope_id: Option<ScopeId>,
+    /// Creation time of the record, when it carries provenance.
+    pub added_at_ms: Option<u64>,
+}
+
+impl KeyVaultMaterialized {
+    /// Whether `scope_key` is already current for its target, making a new record redundant.
+    pub fn holds_scope_key(
+        &self,
+        scope_id: &ScopeId,
+        scope_epoch: ScopeEpoch,
+        scope_key: &[u8],
+    ) -> bool {
+        self.scope_keys
+            .get(&scope_key_lookup_key(scope_id, scope_epoch))
+            .is_some_and(|current| ct_eq(current, scope_key))
+    }
+
+    /// Whether `resource_key` is already current for its target, making a new record redundant.
+    pub fn holds_resource_key(
+        &self,
+        resource_id: &ResourceId,
+        resource_key_id: &ResourceKeyId,
+        resource_key: &[u8],
+    ) -> bool {
+        self.resource_keys
+            .get(&resource_key_lookup_key(resource_id, resource_key_id))
+            .is_some_and(|current| ct_eq(current, resource_key))
+    }
+
+    /// Whether an envelope or grant with `id` was already ingested for `scope_id`.
+    pub fn has_seen(&self, kind: SeenIdKind, scope_id: &ScopeId, id: &str) -> bool {
+        self.seen_ids
+            .contains(&(kind, scope_id.0.clone(), id.to_string()))
     }
 }
 
@@ -72,53 +463,323 @@ impl KeyVaultState {
         header: &KeyVaultHeaderV1,
         vault_key: &[u8],
         containers: &[KeyVaultRecordContainerV1],
+    ) -> CoreResult<(KeyVaultState, KeyVaultMaterialized)> {
+        Self::apply_containers_cancellable(header, vault_key, containers, &CancellationToken::new())
+    }
+
+    /// Like [`Self::apply_containers`], checking `cancel` before each record.
+    pub fn apply_containers_cancellable(
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+        containers: &[KeyVaultRecordContainerV1],
+        cancel: &CancellationToken,
+    ) -> CoreResult<(KeyVaultState, KeyVaultMaterialized)> {
+        Self::apply_containers_deferring(header, vault_key, containers, &HashMap::new(), cancel)
+    }
+
+    /// Like [`Self::apply_containers_cancellable`], but the resource-key records in `deferred`
+    /// (record id to target, from a [`KeyVaultIndex`]) are chain-checked without being
+    /// decrypted. Their keys stay out of the materialized state until loaded with
+    /// [`Self::open_record`], and their metadata carries no provenance.
+    pub fn apply_containers_deferring(
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+        containers: &[KeyVaultRecordContainerV1],
+        deferred: &HashMap<String, (String, String)>,
+        cancel: &CancellationToken,
     ) -> CoreResult<(KeyVaultState, KeyVaultMaterialized)> {
         let mut state = KeyVaultState::default();
         let mut materialized = KeyVaultMaterialized::default();
-        let mut prev_hash = vec![0u8; 32];
-        let mut expected_seq = 1u64;
         let mut seen_record_ids = HashSet::new();
 
         let mut sorted = containers.to_vec();
         sorted.sort_by_key(|r| r.seq);
 
         for container in sorted {
-            if container.seq != expected_seq {
-                return Err(CoreError::Format("keyvault seq mismatch".to_string()));
+            cancel.check()?;
+            state.apply_container(
+                header,
+                vault_key,
+                container,
+                &mut materialized,
+                &mut seen_record_ids,
+                deferred,
+            )?;
+        }
+
+        Ok((state, materialized))
+    }
+
+    /// Applies the longest valid prefix of `containers`. With `rechain`, every later record
+    /// that still decrypts is renumbered onto it; otherwise later records are reported as
+    /// truncated. Records that cannot be kept are reported, not applied. `damaged` seeds the
+    /// report with records that failed before this point, e.g. to decode.
+    pub fn salvage_containers(
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+        containers: &[KeyVaultRecordContainerV1],
+        mut damaged: Vec<KeyVaultDamagedRecord>,
+        rechain: bool,
+    ) -> CoreResult<KeyVaultSalvage> {
+        let mut state = KeyVaultState::default();
+        let mut materialized = KeyVaultMaterialized::default();
+        let mut seen_record_ids = HashSet::new();
+        let mut break_seq = None;
+        let mut rechained = Vec::new();
+        let mut truncated = Vec::new();
+
+        let mut sorted = containers.to_vec();
+        sorted.sort_by_key(|r| r.seq);
+
+        for mut container in sorted {
+            let original_seq = container.seq;
+            let linked = container.seq == state.head_seq + 1
+                && ct_eq(&container.prev_hash, &state.head_hash);
+            if !linked {
+                break_seq.get_or_insert(state.head_seq + 1);
             }
-            expected_seq = expected_seq.saturating_add(1);
-            if !seen_record_ids.insert(container.record_id.clone()) {
-                return Err(CoreError::Format(
-                    "duplicate keyvault record_id".to_string(),
-                ));
+            if break_seq.is_some() && !rechain {
+                truncated.push(container.record_id);
+                continue;
             }
-            let container_bytes = encode_keyvault_record_container_v1(&container)?;
-            let hash = sha256(&container_bytes).to_vec();
-            if container.prev_hash != prev_hash {
-                return Err(CoreError::Format("keyvault chain mismatch".to_string()));
+            if break_seq.is_some() {
+                container.seq = state.head_seq + 1;
+                container.prev_hash = state.head_hash.clone();
             }
+            let record_id = container.record_id.clone();
+            match state.apply_container(
+                header,
+                vault_key,
+                container,
+                &mut materialized,
+                &mut seen_record_ids,
+                &HashMap::new(),
+            ) {
+                Ok(()) if break_seq.is_some() => rechained.push(record_id),
+                Ok(()) => {}
+                Err(e) => {
+                    break_seq.get_or_insert(state.head_seq + 1);
+                    damaged.push(KeyVaultDamagedRecord {
+                        record_id,
+                        seq: Some(original_seq),
+                        reason: e.to_string(),
+                    });
+                }
+            }
+        }
+        if !damaged.is_empty() && break_seq.is_none() {
+            break_seq = Some(state.head_seq + 1);
+        }
+
+        Ok(KeyVaultSalvage {
+            state,
+            materialized,
+            report: KeyVaultSalvageReport {
+                break_seq,
+                rechained_record_ids: rechained,
+                truncated_record_ids: truncated,
+                damaged,
+                sealed_head_seq: None,
+            },
+        })
+    }
+
+    /// Checks one container against the head and folds it in. Nothing is changed on error.
+    fn apply_container(
+        &mut self,
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+        container: KeyVaultRecordContainerV1,
+        materialized: &mut KeyVaultMaterialized,
+        seen_record_ids: &mut HashSet<String>,
+        deferred: &HashMap<String, (String, String)>,
+    ) -> CoreResult<()> {
+        if container.seq != self.head_seq + 1 {
+            return Err(CoreError::Format("keyvault seq mismatch".to_string()));
+        }
+        if seen_record_ids.contains(&container.record_id) {
+            return Err(CoreError::Format(
+                "duplicate keyvault record_id".to_string(),
+            ));
+        }
+        let container_bytes = encode_keyvault_record_container_v1(&container)?;
+        let hash = sha256(&container_bytes).to_vec();
+        if !ct_eq(&container.prev_hash, &self.head_hash) {
+            return Err(CoreError::Format("keyvault chain mismatch".to_string()));
+        }
+        if let Some(target) = deferred.get(&container.record_id) {
+            materialized.resource_keys.remove(target);
+            materialized
+                .index
+                .resource_keys
+                .insert(target.clone(), container.record_id.clone());
+            materialized.record_metadata.push(KeyVaultRecordMetadata {
+                seq: container.seq,
+                record_id: container.record_id.clone(),
+                kind: 4,
+                provenance: None,
+            });
+        } else {
             let aad = aad_keyvault_record_v1(
                 &header.vault_id,
                 &header.user_id,
                 header.aead,
                 &container.record_id,
             )?;
-            let plaintext =
+            let plaintext = Zeroizing::new(
                 aead_decrypt::<Aes256Gcm>(vault_key, &aad, &container.nonce, &container.ct)
-                    .map_err(|_| CoreError::Format("keyvault record decrypt failed".to_string()))?;
+                    .map_err(|_| CoreError::Format("keyvault record decrypt failed".to_string()))?,
+            );
             let record_plain = decode_keyvault_record_plain_v1(&plaintext)?;
             if record_plain.record_id != container.record_id {
                 return Err(CoreError::Format("record id mismatch".to_string()));
             }
-            apply_record_plain(&record_plain, &mut materialized)?;
+            if record_plain.kind == CHECKPOINT_RECORD_KIND {
+                let checkpoint = KeyVaultCheckpoint::from_record(&record_plain)?;
+                if !checkpoint.matches(self.head_seq, &self.head_hash) {
+                    return Err(CoreError::Format(
+                        "keyvault checkpoint mismatch".to_string(),
+                    ));
+                }
+                self.last_checkpoint = Some(checkpoint);
+            }
+            apply_record_plain(&record_plain, materialized)?;
+            materialized
+                .record_metadata
+                .push(KeyVaultRecordMetadata::new(container.seq, &record_plain));
+            materialized.index.note_record(&record_plain)?;
+        }
+        materialized.index.advance(container.seq, &hash);
+        seen_record_ids.insert(container.record_id.clone());
+        self.head_seq = container.seq;
+        self.head_hash = hash;
+        self.records.push(container);
+        Ok(())
+    }
+
+    /// Re-walks stored containers like `apply_containers`, but reports the first
+    /// broken link instead of failing, and does not materialize any keys.
+    pub fn verify_containers(
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+        containers: &[KeyVaultRecordContainerV1],
+    ) -> CoreResult<KeyVaultChainReport> {
+        let mut report = KeyVaultChainReport {
+            record_count: containers.len() as u64,
+            head_seq: 0,
+            head_hash: vec![0u8; 32],
+            first_invalid_seq: None,
+            problem: None,
+        };
+        let mut seen_record_ids = HashSet::new();
+
+        let mut sorted = containers.to_vec();
+        sorted.sort_by_key(|r| r.seq);
+
+        for container in sorted {
+            let problem = if container.seq != report.head_seq + 1 {
+                Some("keyvault seq mismatch")
+            } else if !seen_record_ids.insert(container.record_id.clone()) {
+                Some("duplicate keyvault record_id")
+            } else if !ct_eq(&container.prev_hash, &report.head_hash) {
+                Some("keyvault chain mismatch")
+            } else {
+                let aad = aad_keyvault_record_v1(
+                    &header.vault_id,
+                    &header.user_id,
+                    header.aead,
+                    &container.record_id,
+                )?;
+                match aead_decrypt::<Aes256Gcm>(vault_key, &aad, &container.nonce, &container.ct)
+                    .map(Zeroizing::new)
+                {
+                    Err(_) => Some("keyvault record decrypt failed"),
+                    Ok(plaintext) => match decode_keyvault_record_plain_v1(&plaintext) {
+                        Err(_) => Some("keyvault record plaintext invalid"),
+                        Ok(plain) if plain.record_id != container.record_id => {
+                            Some("record id mismatch")
+                        }
+                        Ok(plain) if plain.kind == CHECKPOINT_RECORD_KIND => {
+                            match KeyVaultCheckpoint::from_record(&plain) {
+                                Ok(checkpoint)
+                                    if checkpoint.matches(report.head_seq, &report.head_hash) =>
+                                {
+                                    None
+                                }
+                                _ => Some("keyvault checkpoint mismatch"),
+                            }
+                        }
+                        Ok(_) => None,
+                    },
+                }
+            };
+            if let Some(problem) = problem {
+                report.first_invalid_seq = Some(container.seq);
+                report.problem = Some(problem.to_string());
+                break;
+            }
+            report.head_seq = container.seq;
+            report.head_hash = sha256(&encode_keyvault_record_container_v1(&container)?).to_vec();
+        }
 
-            prev_hash = hash.clone();
-            state.head_seq = container.seq;
-            state.head_hash = hash;
-            state.records.push(container);
+        Ok(report)
+    }
+
+    /// Checks seq order and hash links of the containers after `anchor` without decrypting
+    /// them, so a verifier that already trusts a checkpoint need not re-walk the chain from
+    /// genesis. Containers at or before `anchor.seq` are ignored. Returns the new head.
+    pub fn verify_chain_from(
+        anchor: &KeyVaultCheckpoint,
+        containers: &[KeyVaultRecordContainerV1],
+    ) -> CoreResult<KeyVaultCheckpoint> {
+        let mut sorted: Vec<&KeyVaultRecordContainerV1> = containers
+            .iter()
+            .filter(|container| container.seq > anchor.seq)
+            .collect();
+        sorted.sort_by_key(|container| container.seq);
+        let mut head = anchor.clone();
+        for container in sorted {
+            if container.seq != head.seq + 1 {
+                return Err(CoreError::Format("keyvault seq mismatch".to_string()));
+            }
+            if !ct_eq(&container.prev_hash, &head.hash) {
+                return Err(CoreError::Format("keyvault chain mismatch".to_string()));
+            }
+            head = KeyVaultCheckpoint {
+                seq: container.seq,
+                hash: sha256(&encode_keyvault_record_container_v1(container)?).to_vec(),
+            };
         }
+        Ok(head)
+    }
 
-        Ok((state, materialized))
+    /// Decrypts one record already on the verified chain, e.g. a deferred resource key.
+    pub fn open_record(
+        &self,
+        header: &KeyVaultHeaderV1,
+        vault_key: &[u8],
+        record_id: &str,
+    ) -> CoreResult<KeyVaultRecordPlainV1> {
+        let container = self
+            .records
+            .iter()
+            .find(|container| container.record_id == record_id)
+            .ok_or_else(|| CoreError::Format("keyvault record not found".to_string()))?;
+        let aad = aad_keyvault_record_v1(
+            &header.vault_id,
+            &header.user_id,
+            header.aead,
+            &container.record_id,
+        )?;
+        let plaintext = Zeroizing::new(
+            aead_decrypt::<Aes256Gcm>(vault_key, &aad, &container.nonce, &container.ct)
+                .map_err(|_| CoreError::Format("keyvault record decrypt failed".to_string()))?,
+        );
+        let record = decode_keyvault_record_plain_v1(&plaintext)?;
+        if record.record_id != container.record_id {
+            return Err(CoreError::Format("record id mismatch".to_string()));
+        }
+        Ok(record)
     }
 
     pub fn append_record(
@@ -140,7 +801,7 @@ impl KeyVaultState {
                 "duplicate keyvault record_id".to_string(),
             ));
         }
-        let plaintext = encode_keyvault_record_plain_v1(record)?;
+        let plaintext = Zeroizing::new(encode_keyvault_record_plain_v1(record)?);
         let aad = aad_keyvault_record_v1(
             &header.vault_id,
             &header.user_id,
@@ -163,16 +824,49 @@ impl KeyVaultState {
         self.records.push(container.clone());
         Ok(container)
     }
+
+    /// Names the first chain-head or materialization invariant that no longer holds.
+    #[cfg(feature = "paranoid")]
+    pub fn check_invariants(
+        &self,
+        materialized: &KeyVaultMaterialized,
+    ) -> Result<(), &'static str> {
+        let expected_hash = match self.records.last() {
+            Some(last) => {
+                if last.seq != self.head_seq {
+                    return Err("keyvault head seq differs from the last record");
+                }
+                let bytes = encode_keyvault_record_container_v1(last)
+                    .map_err(|_| "keyvault head record does not encode")?;
+                sha256(&bytes).to_vec()
+            }
+            None if self.head_seq != 0 => return Err("keyvault head seq set without records"),
+            None => vec![0u8; 32],
+        };
+        if !ct_eq(&expected_hash, &self.head_hash) {
+            return Err("keyvault head hash differs from the last record");
+        }
+        if materialized.record_metadata.len() != self.records.len() {
+            return Err("record metadata out of step with the chain");
+        }
+        if !materialized.index.covers(self) {
+            return Err("keyvault index behind the chain head");
+        }
+        Ok(())
+    }
 }
 
-fn apply_record_plain(
+/// Folds one record into `materialized`. Key records are latest-wins per target: a scope key
+/// record supersedes any earlier one for the same `(scope_id, scope_epoch)`, and a resource
+/// key record any earlier one for the same `(resource_id, resource_key_id)`.
+pub(crate) fn apply_record_plain(
     record: &KeyVaultRecordPlainV1,
     materialized: &mut KeyVaultMaterialized,
 ) -> CoreResult<()> {
     match record.kind {
         1 => {
             let map = crate::cbor::as_map(&record.payload)?;
-            let uk_priv = crate::cbor::req_bytes(map, 0)?;
+            let uk_priv = Zeroizing::new(crate::cbor::req_bytes(map, 0)?);
             let uk_pub = crate::cbor::req_bytes(map, 1)?;
             let user = crate::ciphersuite::decode_user_keypair(&uk_priv, &uk_pub)?;
             materialized.user_key = Some(user);
@@ -180,7 +874,7 @@ fn apply_record_plain(
         2 => {
             let map = crate::cbor::as_map(&record.payload)?;
             let device_id = crate::cbor::req_text(map, 0)?;
-            let ed_priv = crate::cbor::req_bytes(map, 1)?;
+            let ed_priv = Zeroizing::new(crate::cbor::req_bytes(map, 1)?);
             let ed_pub = crate::cbor::req_bytes(map, 2)?;
             let sig_suite = crate::cbor::req_text(map, 3)?;
             let suite = crate::types::SigCiphersuiteId::try_from(sig_suite.as_str())
@@ -188,7 +882,7 @@ fn apply_record_plain(
             if suite != crate::types::SigCiphersuiteId::HybridSig1 {
                 return Err(CoreError::Format("unsupported signing suite".to_string()));
             }
-            let ml_priv = crate::cbor::req_bytes(map, 4)?;
+            let ml_priv = Zeroizing::new(crate::cbor::req_bytes(map, 4)?);
             let ml_pub = crate::cbor::req_bytes(map, 5)?;
             let keypair = crate::ciphersuite::HybridSignatureKeypair {
                 ed25519_priv: ed_priv,
@@ -202,7 +896,13 @@ fn apply_record_plain(
             let map = crate::cbor::as_map(&record.payload)?;
             let scope_id = ScopeId(crate::cbor::req_text(map, 0)?);
             let scope_epoch = ScopeEpoch(crate::cbor::req_uint(map, 1)?);
-            let scope_key = crate::cbor::req_bytes(map, 2)?;
+            let scope_key = Zeroizing::new(crate::cbor::req_bytes(map, 2)?);
+            if let Some(provenance) = &record.provenance {
+                materialized.scope_key_created_ms.insert(
+                    (scope_id.0.clone(), scope_epoch.0),
+                    provenance.created_at_ms,
+                );
+            }
             materialized
                 .scope_keys
                 .insert((scope_id.0, scope_epoch.0), scope_key);
@@ -211,11 +911,105 @@ fn apply_record_plain(
             let map = crate::cbor::as_map(&record.payload)?;
             let resource_id = ResourceId(crate::cbor::req_text(map, 0)?);
             let resource_key_id = ResourceKeyId(crate::cbor::req_text(map, 1)?);
-            let resource_key = crate::cbor::req_bytes(map, 2)?;
+            let resource_key = Zeroizing::new(crate::cbor::req_bytes(map, 2)?);
             materialized
                 .resource_keys
                 .insert((resource_id.0, resource_key_id.0), resource_key);
         }
+        COSIGN_SHARE_RECORD_KIND => {
+            let map = crate::cbor::as_map(&record.payload)?;
+            let device_id = crate::cbor::req_text(map, 0)?;
+            let secret = Zeroizing::new(crate::cbor::req_bytes(map, 1)?);
+            let share = crate::cosign::CosignKeyShare::from_secret(&secret)?;
+            let element = |key: u64| -> CoreResult<[u8; 32]> {
+                crate::cbor::req_bytes(map, key)?
+                    .try_into()
+                    .map_err(|_| CoreError::Format("invalid cosign share record".to_string()))
+            };
+            let server_pub = element(2)?;
+            let group_pub = element(3)?;
+            if crate::cosign::cosign_group_key(&share.public, &server_pub)? != group_pub {
+                return Err(CoreError::Format(
+                    "cosign group key does not match its shares".to_string(),
+                ));
+            }
+            materialized.cosign_shares.insert(
+                device_id,
+                crate::cosign::CosignDeviceShare {
+                    share,
+                    server_pub,
+                    group_pub,
+                },
+            );
+        }
+        APP_BLOB_RECORD_KIND => {
+            let map = crate::cbor::as_map(&record.payload)?;
+            let label = crate::cbor::req_text(map, 0)?;
+            let bytes = Zeroizing::new(crate::cbor::req_bytes(map, 1)?);
+            materialized.app_blobs.insert(label, bytes);
+        }
+        SEEN_ID_RECORD_KIND => {
+            let map = crate::cbor::as_map(&record.payload)?;
+            let kind = SeenIdKind::from_tag(crate::cbor::req_uint(map, 0)?)?;
+            let scope_id = crate::cbor::req_text(map, 1)?;
+            let id = crate::cbor::req_text(map, 2)?;
+            materialized.seen_ids.insert((kind, scope_id, id));
+        }
+        CGKA_GROUP_RECORD_KIND => {
+            let map = crate::cbor::as_map(&record.payload)?;
+            let scope_id = crate::cbor::req_text(map, 0)?;
+            let group = Zeroizing::new(crate::cbor::req_bytes(map, 1)?);
+            materialized.cgka_groups.insert(scope_id, group);
+        }
+        PREKEYS_PUBLISHED_RECORD_KIND => {
+            let map = crate::cbor::as_map(&record.payload)?;
+            let next_prekey_id = crate::cbor::req_uint(map, 0)?;
+            materialized.next_prekey_id = materialized.next_prekey_id.max(next_prekey_id);
+        }
+        PREKEY_CONSUMED_RECORD_KIND => {
+            let map = crate::cbor::as_map(&record.payload)?;
+            let prekey_id = crate::cbor::req_uint(map, 0)?;
+            materialized.consumed_prekeys.insert(prekey_id);
+        }
+        ROTATION_POLICY_RECORD_KIND => {
+            let map = crate::cbor::as_map(&record.payload)?;
+            let scope_id = crate::cbor::req_text(map, 0)?;
+            let max_key_age_ms = crate::cbor::opt_uint(map, 1)?;
+            let max_operations = crate::cbor::opt_uint(map, 2)?;
+            if max_key_age_ms.is_none() && max_operations.is_none() {
+                materialized.rotation_policies.remove(&scope_id);
+            } else {
+                let grace_ms = crate::cbor::req_uint(map, 3)?;
+                materialized.rotation_policies.insert(
+                    scope_id,
+                    ScopeRotationPolicy {
+                        max_key_age_ms,
+                        max_operations,
+                        grace_ms,
+                    },
+                );
+            }
+        }
+        CONTACT_RECORD_KIND => {
+            let map = crate::cbor::as_map(&record.payload)?;
+            let user_id = crate::cbor::req_text(map, 0)?;
+            match crate::cbor::opt_bytes(map, 1)? {
+                Some(public_bytes) => {
+                    let contact = ContactKey {
+                        public_bytes,
+                        scope_id: crate::cbor::opt_text(map, 2)?.map(ScopeId),
+                        added_at_ms: record
+                            .provenance
+                            .as_ref()
+                            .map(|provenance| provenance.created_at_ms),
+                    };
+                    materialized.contacts.insert(user_id, contact);
+                }
+                None => {
+                    materialized.contacts.remove(&user_id);
+                }
+            }
+        }
         _ => {}
     }
     Ok(())
@@ -234,6 +1028,7 @@ pub fn make_store_user_key_record(
         record_id: record_id.to_string(),
         kind: 1,
         payload,
+        provenance: None,
     }
 }
 
@@ -258,6 +1053,7 @@ pub fn make_store_device_signing_key_record(
         record_id: record_id.to_string(),
         kind: 2,
         payload,
+        provenance: None,
     }
 }
 
@@ -276,6 +1072,7 @@ pub fn make_store_scope_key_record(
         record_id: record_id.to_string(),
         kind: 3,
         payload,
+        provenance: None,
     }
 }
 
@@ -294,7 +1091,182 @@ pub fn make_store_resource_key_record(
         record_id: record_id.to_string(),
         kind: 4,
         payload,
+        provenance: None,
+    }
+}
+
+pub fn make_store_app_blob_record(
+    record_id: &str,
+    label: &str,
+    bytes: &[u8],
+) -> KeyVaultRecordPlainV1 {
+    let payload = crate::cbor::cbor_map(vec![
+        (0, crate::cbor::cbor_text(label)),
+        (1, crate::cbor::cbor_bytes(bytes)),
+    ]);
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: APP_BLOB_RECORD_KIND,
+        payload,
+        provenance: None,
+    }
+}
+
+pub fn make_cosign_share_record(
+    record_id: &str,
+    device_id: &str,
+    share: &crate::cosign::CosignDeviceShare,
+) -> KeyVaultRecordPlainV1 {
+    let payload = crate::cbor::cbor_map(vec![
+        (0, crate::cbor::cbor_text(device_id)),
+        (1, crate::cbor::cbor_bytes(share.share.secret.as_slice())),
+        (2, crate::cbor::cbor_bytes(&share.server_pub)),
+        (3, crate::cbor::cbor_bytes(&share.group_pub)),
+    ]);
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: COSIGN_SHARE_RECORD_KIND,
+        payload,
+        provenance: None,
+    }
+}
+
+pub fn make_seen_id_record(
+    record_id: &str,
+    kind: SeenIdKind,
+    scope_id: &str,
+    id: &str,
+) -> KeyVaultRecordPlainV1 {
+    let payload = crate::cbor::cbor_map(vec![
+        (0, crate::cbor::cbor_uint(kind.tag())),
+        (1, crate::cbor::cbor_text(scope_id)),
+        (2, crate::cbor::cbor_text(id)),
+    ]);
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: SEEN_ID_RECORD_KIND,
+        payload,
+        provenance: None,
+    }
+}
+
+pub fn make_cgka_group_record(
+    record_id: &str,
+    scope_id: &str,
+    group: &[u8],
+) -> KeyVaultRecordPlainV1 {
+    let payload = crate::cbor::cbor_map(vec![
+        (0, crate::cbor::cbor_text(scope_id)),
+        (1, crate::cbor::cbor_bytes(group)),
+    ]);
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: CGKA_GROUP_RECORD_KIND,
+        payload,
+        provenance: None,
+    }
+}
+
+pub fn make_prekeys_published_record(
+    record_id: &str,
+    next_prekey_id: u64,
+) -> KeyVaultRecordPlainV1 {
+    let payload = crate::cbor::cbor_map(vec![(0, crate::cbor::cbor_uint(next_prekey_id))]);
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: PREKEYS_PUBLISHED_RECORD_KIND,
+        payload,
+        provenance: None,
+    }
+}
+
+pub fn make_prekey_consumed_record(record_id: &str, prekey_id: u64) -> KeyVaultRecordPlainV1 {
+    let payload = crate::cbor::cbor_map(vec![(0, crate::cbor::cbor_uint(prekey_id))]);
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: PREKEY_CONSUMED_RECORD_KIND,
+        payload,
+        provenance: None,
+    }
+}
+
+/// `policy` of `None` clears the scope's policy.
+pub fn make_rotation_policy_record(
+    record_id: &str,
+    scope_id: &str,
+    policy: Option<&ScopeRotationPolicy>,
+) -> KeyVaultRecordPlainV1 {
+    let mut entries = vec![(0, crate::cbor::cbor_text(scope_id))];
+    if let Some(policy) = policy {
+        if let Some(max_key_age_ms) = policy.max_key_age_ms {
+            entries.push((1, crate::cbor::cbor_uint(max_key_age_ms)));
+        }
+        if let Some(max_operations) = policy.max_operations {
+            entries.push((2, crate::cbor::cbor_uint(max_operations)));
+        }
+        entries.push((3, crate::cbor::cbor_uint(policy.grace_ms)));
+    }
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: ROTATION_POLICY_RECORD_KIND,
+        payload: crate::cbor::cbor_map(entries),
+        provenance: None,
+    }
+}
+
+/// `public_bytes` of `None` removes the user from the directory; `scope_id` names the scope
+/// state the key came from.
+pub fn make_contact_record(
+    record_id: &str,
+    user_id: &str,
+    public_bytes: Option<&[u8]>,
+    scope_id: Option<&str>,
+) -> KeyVaultRecordPlainV1 {
+    let mut entries = vec![(0, crate::cbor::cbor_text(user_id))];
+    if let Some(public_bytes) = public_bytes {
+        entries.push((1, crate::cbor::cbor_bytes(public_bytes)));
+    }
+    if let Some(scope_id) = scope_id {
+        entries.push((2, crate::cbor::cbor_text(scope_id)));
+    }
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: CONTACT_RECORD_KIND,
+        payload: crate::cbor::cbor_map(entries),
+        provenance: None,
+    }
+}
+
+pub fn make_checkpoint_record(
+    record_id: &str,
+    checkpoint: &KeyVaultCheckpoint,
+) -> KeyVaultRecordPlainV1 {
+    let payload = crate::cbor::cbor_map(vec![
+        (0, crate::cbor::cbor_uint(checkpoint.seq)),
+        (1, crate::cbor::cbor_bytes(&checkpoint.hash)),
+    ]);
+    KeyVaultRecordPlainV1 {
+        record_id: record_id.to_string(),
+        kind: CHECKPOINT_RECORD_KIND,
+        payload,
+        provenance: None,
+    }
+}
+
+/// Renumbers `containers` into a fresh chain from seq 1, e.g. for a partial export. Record AAD
+/// binds only the record id, so the ciphertexts carry over unchanged.
+pub fn rechain_containers(
+    containers: &[KeyVaultRecordContainerV1],
+) -> CoreResult<Vec<KeyVaultRecordContainerV1>> {
+    let mut sorted = containers.to_vec();
+    sorted.sort_by_key(|r| r.seq);
+    let mut prev_hash = vec![0u8; 32];
+    for (i, container) in sorted.iter_mut().enumerate() {
+        container.seq = i as u64 + 1;
+        container.prev_hash = prev_hash;
+        prev_hash = sha256(&encode_keyvault_record_container_v1(container)?).to_vec();
     }
+    Ok(sorted)
 }
 
 pub fn scope_key_lookup_key(scope_id: &ScopeId, scope_epoch: ScopeEpoch) -> (String, u64) {
@@ -1,30 +1,73 @@
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "memlock"), forbid(unsafe_code))]
+// `memlock` needs raw allocation; the only unsafe code lives in `secret::imp`.
+#![cfg_attr(feature = "memlock", deny(unsafe_code))]
 //! Core Key Service implementation: formats, crypto, KeyVault integrity, and session policy.
 
 pub mod aad;
 pub mod adapters;
+mod argon2id;
 pub mod async_key_service;
+pub mod attestation;
+pub mod audit;
+pub mod cancel;
 pub mod cbor;
+pub mod cgka;
 pub mod ciphersuite;
+pub mod compress;
+pub mod cosign;
 pub mod crypto;
+pub mod device_shares;
+pub mod diagnostics;
+pub mod domains;
+pub mod envelope;
 pub mod error;
+#[cfg(feature = "fido2")]
+pub mod fido2;
 pub mod formats;
 pub mod hash;
 pub mod key_service;
 pub mod keyvault;
+pub mod logging;
+pub mod opaque;
+pub mod ratchet;
+pub mod secret;
 pub mod session;
+pub mod shared_key_service;
+pub mod stream;
+pub mod strength;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+#[cfg(feature = "tokio")]
+pub mod timer;
+pub mod transparency;
 pub mod types;
 
 pub use aad::*;
 pub use adapters::*;
 pub use async_key_service::*;
+pub use attestation::*;
+pub use audit::*;
+pub use cancel::*;
 pub use cbor::*;
+pub use cgka::*;
 pub use ciphersuite::*;
 pub use crypto::*;
+pub use device_shares::*;
+pub use diagnostics::*;
+pub use domains::*;
+pub use envelope::*;
 pub use error::*;
 pub use formats::*;
 pub use hash::*;
 pub use key_service::*;
 pub use keyvault::*;
+pub use logging::*;
+pub use opaque::*;
+pub use ratchet::*;
+pub use secret::*;
 pub use session::*;
+pub use shared_key_service::*;
+pub use stream::*;
+pub use strength::*;
+pub use transparency::*;
 pub use types::*;
@@ -0,0 +1,56 @@
+//! Structured log events for a [`LogAdapter`](crate::adapters::LogAdapter), redacted at construction.
+
+use crate::hash::sha256;
+use crate::types::SessionId;
+use std::fmt;
+
+pub const REDACTED: &str = "<redacted>";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+}
+
+/// A named event with string fields. Secrets never reach `fields` in the clear:
+/// session ids become a short hash tag and key material/passphrases become [`REDACTED`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEvent {
+    pub level: LogLevel,
+    pub name: &'static str,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl LogEvent {
+    pub fn new(level: LogLevel, name: &'static str) -> Self {
+        Self {
+            level,
+            name,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a non-secret field.
+    pub fn with(mut self, key: &'static str, value: impl fmt::Display) -> Self {
+        self.fields.push((key, value.to_string()));
+        self
+    }
+
+    /// Adds a session id as a stable tag that correlates events without being a bearer token.
+    pub fn with_session(mut self, session_id: &SessionId) -> Self {
+        self.fields.push(("session", redact_session_id(session_id)));
+        self
+    }
+
+    /// Records that a secret field was present without its value.
+    pub fn with_secret(mut self, key: &'static str) -> Self {
+        self.fields.push((key, REDACTED.to_string()));
+        self
+    }
+}
+
+/// First 8 hex chars of SHA-256 over the session id.
+pub fn redact_session_id(session_id: &SessionId) -> String {
+    hex::encode(&sha256(session_id.0.as_bytes())[..4])
+}
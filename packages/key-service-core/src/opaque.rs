@@ -0,0 +1,609 @@
+//! OPAQUE-3DH client (RFC 9807) over the ristretto255-SHA512 OPRF (RFC 9497).
+//!
+//! For deployments where a server assists unlock: the OPAQUE export key is used as the
+//! passphrase input to `derive_kek`, so the server can rate-limit login attempts without ever
+//! seeing password-equivalent material. The suite is fixed: ristretto255, SHA-512, HKDF-SHA512,
+//! HMAC-SHA512, and the KSF from [`OpaqueKsf`]. Message layouts follow the RFC byte for byte.
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha512};
+use std::fmt;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::adapters::EntropyAdapter;
+use crate::crypto::ct_eq;
+use crate::domains::{OPAQUE_DERIVE_DH_KEY_PAIR_INFO, OPRF_RISTRETTO255_SHA512_CONTEXT};
+use crate::error::CoreError;
+
+/// Nonce length (`Nn`).
+pub const OPAQUE_NONCE_LEN: usize = 32;
+/// Serialized ristretto255 element or scalar (`Noe`, `Npk`, `Nsk`).
+pub const OPAQUE_ELEMENT_LEN: usize = 32;
+/// SHA-512 output, MAC, and KDF key length (`Nh`, `Nm`, `Nx`).
+pub const OPAQUE_HASH_LEN: usize = 64;
+const SEED_LEN: usize = 32;
+const ENVELOPE_LEN: usize = OPAQUE_NONCE_LEN + OPAQUE_HASH_LEN;
+const MASKED_RESPONSE_LEN: usize = OPAQUE_ELEMENT_LEN + ENVELOPE_LEN;
+const CREDENTIAL_RESPONSE_LEN: usize = OPAQUE_ELEMENT_LEN + OPAQUE_NONCE_LEN + MASKED_RESPONSE_LEN;
+
+pub const OPAQUE_REGISTRATION_REQUEST_LEN: usize = OPAQUE_ELEMENT_LEN;
+pub const OPAQUE_REGISTRATION_RESPONSE_LEN: usize = 2 * OPAQUE_ELEMENT_LEN;
+/// `client_public_key || masking_key || envelope`.
+pub const OPAQUE_REGISTRATION_RECORD_LEN: usize =
+    OPAQUE_ELEMENT_LEN + OPAQUE_HASH_LEN + ENVELOPE_LEN;
+pub const OPAQUE_KE1_LEN: usize = OPAQUE_ELEMENT_LEN + OPAQUE_NONCE_LEN + OPAQUE_ELEMENT_LEN;
+pub const OPAQUE_KE2_LEN: usize =
+    CREDENTIAL_RESPONSE_LEN + OPAQUE_NONCE_LEN + OPAQUE_ELEMENT_LEN + OPAQUE_HASH_LEN;
+pub const OPAQUE_KE3_LEN: usize = OPAQUE_HASH_LEN;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpaqueError {
+    #[error("invalid opaque message: {0}")]
+    InvalidMessage(&'static str),
+    /// The envelope did not authenticate: the password is wrong or the record was altered.
+    #[error("opaque envelope recovery failed")]
+    EnvelopeRecovery,
+    /// The server's KE2 MAC did not verify.
+    #[error("opaque server authentication failed")]
+    ServerAuthentication,
+    #[error(transparent)]
+    Core(#[from] CoreError),
+}
+
+pub type OpaqueResult<T> = Result<T, OpaqueError>;
+
+/// Key stretching applied to the OPRF output (RFC 9807 `Stretch`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OpaqueKsf {
+    /// INSECURE: no stretching, as in the RFC 9807 test vectors. `derive_kek` still runs
+    /// Argon2id on the export key locally, but a compromised server can then test guesses at
+    /// the cost of one OPRF evaluation each. Needs `KeyServicePolicy::allow_insecure_test_kdf`.
+    Identity,
+    /// Argon2id with a zero salt and a 64-byte output, as in RFC 9807.
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+/// Argon2id at the cost of a new vault's own KDF (64 MiB, three passes, one lane).
+impl Default for OpaqueKsf {
+    fn default() -> Self {
+        OpaqueKsf::Argon2id {
+            memory_kib: 65536,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+/// Deployment parameters that client and server must agree on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpaqueConfig {
+    pub ksf: OpaqueKsf,
+    /// Application context bound into the handshake preamble.
+    pub context: Vec<u8>,
+    /// Defaults to the server public key.
+    pub server_identity: Option<Vec<u8>>,
+    /// Defaults to the client public key.
+    pub client_identity: Option<Vec<u8>>,
+}
+
+impl OpaqueConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let too_long = |bytes: &[u8]| bytes.len() > u16::MAX as usize;
+        if too_long(&self.context) {
+            return Err("opaque context is too long".to_string());
+        }
+        for identity in [&self.server_identity, &self.client_identity]
+            .into_iter()
+            .flatten()
+        {
+            if identity.is_empty() || too_long(identity) {
+                return Err("opaque identities must be 1..=65535 bytes".to_string());
+            }
+        }
+        if let OpaqueKsf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        } = self.ksf
+        {
+            argon2::Params::new(memory_kib, iterations, parallelism, Some(OPAQUE_HASH_LEN))
+                .map_err(|e| format!("opaque argon2id params: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Client state between the registration request and the server's response.
+pub struct OpaqueRegistration {
+    password: Zeroizing<Vec<u8>>,
+    blind: Zeroizing<Scalar>,
+}
+
+impl fmt::Debug for OpaqueRegistration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OpaqueRegistration(<redacted>)")
+    }
+}
+
+pub struct OpaqueRegistrationFinish {
+    /// Upload to the server; it holds no password-equivalent material.
+    pub record: Vec<u8>,
+    pub export_key: Zeroizing<Vec<u8>>,
+}
+
+impl OpaqueRegistration {
+    /// Returns the state and the registration request for the server.
+    pub fn start(password: &[u8], entropy: &impl EntropyAdapter) -> OpaqueResult<(Self, Vec<u8>)> {
+        let (blind, request) = blind(password, entropy)?;
+        Ok((
+            Self {
+                password: Zeroizing::new(password.to_vec()),
+                blind,
+            },
+            request.to_vec(),
+        ))
+    }
+
+    /// Finishes registration with the server's `evaluated_message || server_public_key`.
+    pub fn finish(
+        self,
+        response: &[u8],
+        config: &OpaqueConfig,
+        entropy: &impl EntropyAdapter,
+    ) -> OpaqueResult<OpaqueRegistrationFinish> {
+        if response.len() != OPAQUE_REGISTRATION_RESPONSE_LEN {
+            return Err(OpaqueError::InvalidMessage("registration response length"));
+        }
+        let (evaluated, server_public_key) = response.split_at(OPAQUE_ELEMENT_LEN);
+        deserialize_element(server_public_key)?;
+        let randomized_password =
+            randomized_password(&self.password, &self.blind, evaluated, config)?;
+
+        let nonce = entropy.random_bytes(OPAQUE_NONCE_LEN);
+        let masking_key = expand(&randomized_password, b"MaskingKey", OPAQUE_HASH_LEN)?;
+        let keys = envelope_keys(&randomized_password, &nonce)?;
+        let (_, client_public_key) = derive_dh_key_pair(&keys.seed)?;
+        let credentials = cleartext_credentials(server_public_key, &client_public_key, config);
+        let auth_tag = mac(&keys.auth_key, &[nonce.as_slice(), &credentials].concat())?;
+
+        let record = [
+            client_public_key.as_slice(),
+            &masking_key,
+            &nonce,
+            &auth_tag,
+        ]
+        .concat();
+        Ok(OpaqueRegistrationFinish {
+            record,
+            export_key: keys.export_key,
+        })
+    }
+}
+
+/// Client state between KE1 and KE2.
+pub struct OpaqueLogin {
+    password: Zeroizing<Vec<u8>>,
+    blind: Zeroizing<Scalar>,
+    client_secret: Zeroizing<Scalar>,
+    ke1: Vec<u8>,
+}
+
+impl fmt::Debug for OpaqueLogin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OpaqueLogin(<redacted>)")
+    }
+}
+
+pub struct OpaqueLoginFinish {
+    /// Send to the server so it can authenticate the client.
+    pub ke3: Vec<u8>,
+    pub session_key: Zeroizing<Vec<u8>>,
+    pub export_key: Zeroizing<Vec<u8>>,
+}
+
+impl OpaqueLogin {
+    /// Returns the state and KE1 for the server.
+    pub fn start(password: &[u8], entropy: &impl EntropyAdapter) -> OpaqueResult<(Self, Vec<u8>)> {
+        let (blind, blinded) = blind(password, entropy)?;
+        let client_nonce = entropy.random_bytes(OPAQUE_NONCE_LEN);
+        let keyshare_seed = Zeroizing::new(entropy.random_bytes(SEED_LEN));
+        let (client_secret, client_keyshare) = derive_dh_key_pair(&keyshare_seed)?;
+        let ke1 = [blinded.as_slice(), &client_nonce, &client_keyshare].concat();
+        Ok((
+            Self {
+                password: Zeroizing::new(password.to_vec()),
+                blind,
+                client_secret,
+                ke1: ke1.clone(),
+            },
+            ke1,
+        ))
+    }
+
+    /// Recovers the credentials from KE2, authenticates the server, and returns KE3 with the
+    /// session and export keys.
+    pub fn finish(self, ke2: &[u8], config: &OpaqueConfig) -> OpaqueResult<OpaqueLoginFinish> {
+        if ke2.len() != OPAQUE_KE2_LEN {
+            return Err(OpaqueError::InvalidMessage("ke2 length"));
+        }
+        let (credential_response, auth_response) = ke2.split_at(CREDENTIAL_RESPONSE_LEN);
+        let (evaluated, rest) = credential_response.split_at(OPAQUE_ELEMENT_LEN);
+        let (masking_nonce, masked_response) = rest.split_at(OPAQUE_NONCE_LEN);
+        let (server_nonce, rest) = auth_response.split_at(OPAQUE_NONCE_LEN);
+        let (server_keyshare, server_mac) = rest.split_at(OPAQUE_ELEMENT_LEN);
+
+        let randomized_password =
+            randomized_password(&self.password, &self.blind, evaluated, config)?;
+        let masking_key = expand(&randomized_password, b"MaskingKey", OPAQUE_HASH_LEN)?;
+        let pad = expand(
+            &masking_key,
+            &[masking_nonce, b"CredentialResponsePad"].concat(),
+            MASKED_RESPONSE_LEN,
+        )?;
+        let unmasked: Zeroizing<Vec<u8>> = Zeroizing::new(
+            pad.iter()
+                .zip(masked_response)
+                .map(|(a, b)| a ^ b)
+                .collect(),
+        );
+        let (server_public_key, envelope) = unmasked.split_at(OPAQUE_ELEMENT_LEN);
+        let (envelope_nonce, auth_tag) = envelope.split_at(OPAQUE_NONCE_LEN);
+
+        let envelope_keys = envelope_keys(&randomized_password, envelope_nonce)?;
+        let (client_private_key, client_public_key) = derive_dh_key_pair(&envelope_keys.seed)?;
+        let credentials = cleartext_credentials(server_public_key, &client_public_key, config);
+        let expected_tag = mac(
+            &envelope_keys.auth_key,
+            &[envelope_nonce, &credentials].concat(),
+        )?;
+        if !ct_eq(&expected_tag, auth_tag) {
+            return Err(OpaqueError::EnvelopeRecovery);
+        }
+
+        let server_keyshare_point = deserialize_element(server_keyshare)?;
+        let server_public_point = deserialize_element(server_public_key)?;
+        let ikm = Zeroizing::new(
+            [
+                (*self.client_secret * server_keyshare_point)
+                    .compress()
+                    .to_bytes(),
+                (*self.client_secret * server_public_point)
+                    .compress()
+                    .to_bytes(),
+                (*client_private_key * server_keyshare_point)
+                    .compress()
+                    .to_bytes(),
+            ]
+            .concat(),
+        );
+        let preamble = preamble(
+            config,
+            &client_public_key,
+            server_public_key,
+            &self.ke1,
+            credential_response,
+            server_nonce,
+            server_keyshare,
+        );
+        let keys = derive_handshake_keys(&ikm, &preamble)?;
+        let transcript = Sha512::digest(&preamble);
+        let expected_server_mac = mac(&keys.server_mac_key, &transcript)?;
+        if !ct_eq(&expected_server_mac, server_mac) {
+            return Err(OpaqueError::ServerAuthentication);
+        }
+        let ke3 = mac(
+            &keys.client_mac_key,
+            &Sha512::digest([preamble.as_slice(), &expected_server_mac].concat()),
+        )?;
+        Ok(OpaqueLoginFinish {
+            ke3,
+            session_key: keys.session_key,
+            export_key: envelope_keys.export_key,
+        })
+    }
+}
+
+/// RFC 9497 `DeriveKeyPair` for ristretto255-SHA512; returns the secret scalar bytes and the
+/// serialized public element.
+pub fn oprf_derive_key_pair(seed: &[u8], info: &[u8]) -> OpaqueResult<([u8; 32], [u8; 32])> {
+    let (secret, public) = derive_key_pair(seed, info)?;
+    Ok((secret.to_bytes(), public))
+}
+
+/// RFC 9497 `Blind` with a caller-chosen blind, for test vectors.
+pub fn oprf_blind_with(input: &[u8], blind: &[u8; 32]) -> OpaqueResult<[u8; 32]> {
+    let blind = scalar_from_bytes(blind)?;
+    Ok((blind * hash_to_group(input)?).compress().to_bytes())
+}
+
+/// RFC 9497 `BlindEvaluate`: the server's OPRF key applied to a blinded element.
+pub fn oprf_evaluate(secret: &[u8; 32], blinded: &[u8]) -> OpaqueResult<[u8; 32]> {
+    let secret = scalar_from_bytes(secret)?;
+    Ok((secret * deserialize_element(blinded)?)
+        .compress()
+        .to_bytes())
+}
+
+/// RFC 9497 `Finalize`: the 64-byte OPRF output for `input`.
+pub fn oprf_finalize(input: &[u8], blind: &[u8; 32], evaluated: &[u8]) -> OpaqueResult<Vec<u8>> {
+    let blind = Zeroizing::new(scalar_from_bytes(blind)?);
+    Ok(finalize(input, &blind, evaluated)?.to_vec())
+}
+
+/// Keys an OPAQUE server derives per login; shared with `testkit::OpaqueTestServer`.
+pub(crate) struct HandshakeKeys {
+    pub(crate) server_mac_key: Zeroizing<Vec<u8>>,
+    pub(crate) client_mac_key: Zeroizing<Vec<u8>>,
+    pub(crate) session_key: Zeroizing<Vec<u8>>,
+}
+
+struct EnvelopeKeys {
+    auth_key: Zeroizing<Vec<u8>>,
+    export_key: Zeroizing<Vec<u8>>,
+    seed: Zeroizing<Vec<u8>>,
+}
+
+fn envelope_keys(randomized_password: &[u8], nonce: &[u8]) -> OpaqueResult<EnvelopeKeys> {
+    let label = |name: &[u8]| [nonce, name].concat();
+    Ok(EnvelopeKeys {
+        auth_key: expand(randomized_password, &label(b"AuthKey"), OPAQUE_HASH_LEN)?,
+        export_key: expand(randomized_password, &label(b"ExportKey"), OPAQUE_HASH_LEN)?,
+        seed: expand(randomized_password, &label(b"PrivateKey"), SEED_LEN)?,
+    })
+}
+
+fn blind(
+    input: &[u8],
+    entropy: &impl EntropyAdapter,
+) -> OpaqueResult<(Zeroizing<Scalar>, [u8; 32])> {
+    let point = hash_to_group(input)?;
+    let blind = Zeroizing::new(random_scalar(entropy));
+    let blinded = (*blind * point).compress().to_bytes();
+    Ok((blind, blinded))
+}
+
+fn finalize(input: &[u8], blind: &Scalar, evaluated: &[u8]) -> OpaqueResult<Zeroizing<[u8; 64]>> {
+    let unblinded = (blind.invert() * deserialize_element(evaluated)?)
+        .compress()
+        .to_bytes();
+    let digest = Sha512::new()
+        .chain_update(i2osp2(input.len())?)
+        .chain_update(input)
+        .chain_update(i2osp2(unblinded.len())?)
+        .chain_update(unblinded)
+        .chain_update(b"Finalize")
+        .finalize();
+    Ok(Zeroizing::new(digest.into()))
+}
+
+fn randomized_password(
+    password: &[u8],
+    blind: &Scalar,
+    evaluated: &[u8],
+    config: &OpaqueConfig,
+) -> OpaqueResult<Zeroizing<Vec<u8>>> {
+    let oprf_output = finalize(password, blind, evaluated)?;
+    let stretched = stretch(oprf_output.as_slice(), &config.ksf)?;
+    let ikm = Zeroizing::new([oprf_output.as_slice(), &stretched].concat());
+    Ok(extract(&ikm))
+}
+
+fn stretch(input: &[u8], ksf: &OpaqueKsf) -> OpaqueResult<Zeroizing<Vec<u8>>> {
+    match *ksf {
+        OpaqueKsf::Identity => Ok(Zeroizing::new(input.to_vec())),
+        OpaqueKsf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => {
+            let params =
+                argon2::Params::new(memory_kib, iterations, parallelism, Some(OPAQUE_HASH_LEN))
+                    .map_err(|e| CoreError::Crypto(format!("opaque argon2id params: {e}")))?;
+            let argon =
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+            let mut out = Zeroizing::new(vec![0u8; OPAQUE_HASH_LEN]);
+            argon
+                .hash_password_into(input, &[0u8; 16], &mut out)
+                .map_err(|e| CoreError::Crypto(format!("opaque argon2id: {e}")))?;
+            Ok(out)
+        }
+    }
+}
+
+/// `server_public_key || server_identity<2> || client_identity<2>`, identities defaulting to
+/// the public keys.
+pub(crate) fn cleartext_credentials(
+    server_public_key: &[u8],
+    client_public_key: &[u8],
+    config: &OpaqueConfig,
+) -> Vec<u8> {
+    let server_identity = config
+        .server_identity
+        .as_deref()
+        .unwrap_or(server_public_key);
+    let client_identity = config
+        .client_identity
+        .as_deref()
+        .unwrap_or(client_public_key);
+    let mut out = server_public_key.to_vec();
+    for identity in [server_identity, client_identity] {
+        out.extend_from_slice(&(identity.len() as u16).to_be_bytes());
+        out.extend_from_slice(identity);
+    }
+    out
+}
+
+pub(crate) fn preamble(
+    config: &OpaqueConfig,
+    client_public_key: &[u8],
+    server_public_key: &[u8],
+    ke1: &[u8],
+    credential_response: &[u8],
+    server_nonce: &[u8],
+    server_keyshare: &[u8],
+) -> Vec<u8> {
+    let client_identity = config
+        .client_identity
+        .as_deref()
+        .unwrap_or(client_public_key);
+    let server_identity = config
+        .server_identity
+        .as_deref()
+        .unwrap_or(server_public_key);
+    let mut out = b"OPAQUEv1-".to_vec();
+    out.extend_from_slice(&(config.context.len() as u16).to_be_bytes());
+    out.extend_from_slice(&config.context);
+    out.extend_from_slice(&(client_identity.len() as u16).to_be_bytes());
+    out.extend_from_slice(client_identity);
+    out.extend_from_slice(ke1);
+    out.extend_from_slice(&(server_identity.len() as u16).to_be_bytes());
+    out.extend_from_slice(server_identity);
+    out.extend_from_slice(credential_response);
+    out.extend_from_slice(server_nonce);
+    out.extend_from_slice(server_keyshare);
+    out
+}
+
+pub(crate) fn derive_handshake_keys(ikm: &[u8], preamble: &[u8]) -> OpaqueResult<HandshakeKeys> {
+    let prk = extract(ikm);
+    let transcript = Sha512::digest(preamble);
+    let handshake_secret = derive_secret(&prk, b"HandshakeSecret", &transcript)?;
+    Ok(HandshakeKeys {
+        session_key: derive_secret(&prk, b"SessionKey", &transcript)?,
+        server_mac_key: derive_secret(&handshake_secret, b"ServerMAC", b"")?,
+        client_mac_key: derive_secret(&handshake_secret, b"ClientMAC", b"")?,
+    })
+}
+
+/// RFC 9807 `Derive-Secret`: `Expand-Label(secret, label, context, Nx)`.
+fn derive_secret(secret: &[u8], label: &[u8], context: &[u8]) -> OpaqueResult<Zeroizing<Vec<u8>>> {
+    let full_label = [b"OPAQUE-".as_slice(), label].concat();
+    let mut info = (OPAQUE_HASH_LEN as u16).to_be_bytes().to_vec();
+    info.push(full_label.len() as u8);
+    info.extend_from_slice(&full_label);
+    info.push(context.len() as u8);
+    info.extend_from_slice(context);
+    expand(secret, &info, OPAQUE_HASH_LEN)
+}
+
+/// `DeriveDiffieHellmanKeyPair`: the OPRF `DeriveKeyPair` under the OPAQUE info string.
+pub(crate) fn derive_dh_key_pair(seed: &[u8]) -> OpaqueResult<(Zeroizing<Scalar>, [u8; 32])> {
+    let (secret, public) = derive_key_pair(seed, OPAQUE_DERIVE_DH_KEY_PAIR_INFO)?;
+    Ok((Zeroizing::new(secret), public))
+}
+
+pub(crate) fn derive_key_pair(seed: &[u8], info: &[u8]) -> OpaqueResult<(Scalar, [u8; 32])> {
+    let mut input = seed.to_vec();
+    input.extend_from_slice(&i2osp2(info.len())?);
+    input.extend_from_slice(info);
+    let dst = [
+        b"DeriveKeyPair".as_slice(),
+        OPRF_RISTRETTO255_SHA512_CONTEXT,
+    ]
+    .concat();
+    for counter in 0..=u8::MAX {
+        input.push(counter);
+        let secret = Scalar::from_bytes_mod_order_wide(&expand_message_xmd(&input, &dst));
+        input.pop();
+        if secret != Scalar::ZERO {
+            input.zeroize();
+            let public = (secret * RISTRETTO_BASEPOINT_POINT).compress().to_bytes();
+            return Ok((secret, public));
+        }
+    }
+    input.zeroize();
+    Err(CoreError::Crypto("opaque derive key pair failed".to_string()).into())
+}
+
+pub(crate) fn hash_to_group(input: &[u8]) -> OpaqueResult<RistrettoPoint> {
+    let dst = [b"HashToGroup-".as_slice(), OPRF_RISTRETTO255_SHA512_CONTEXT].concat();
+    let point = RistrettoPoint::from_uniform_bytes(&expand_message_xmd(input, &dst));
+    if point == RistrettoPoint::identity() {
+        return Err(OpaqueError::InvalidMessage("input hashes to the identity"));
+    }
+    Ok(point)
+}
+
+/// `expand_message_xmd` (RFC 9380) with SHA-512 for one 64-byte block.
+fn expand_message_xmd(msg: &[u8], dst: &[u8]) -> [u8; 64] {
+    let dst_prime = [dst, &[dst.len() as u8]].concat();
+    let b0 = Sha512::new()
+        .chain_update([0u8; 128])
+        .chain_update(msg)
+        .chain_update((OPAQUE_HASH_LEN as u16).to_be_bytes())
+        .chain_update([0u8])
+        .chain_update(&dst_prime)
+        .finalize();
+    Sha512::new()
+        .chain_update(b0)
+        .chain_update([1u8])
+        .chain_update(&dst_prime)
+        .finalize()
+        .into()
+}
+
+pub(crate) fn random_scalar(entropy: &impl EntropyAdapter) -> Scalar {
+    loop {
+        let mut wide = [0u8; 64];
+        wide.copy_from_slice(&entropy.random_bytes(64));
+        let scalar = Scalar::from_bytes_mod_order_wide(&wide);
+        wide.zeroize();
+        if scalar != Scalar::ZERO {
+            return scalar;
+        }
+    }
+}
+
+pub(crate) fn deserialize_element(bytes: &[u8]) -> OpaqueResult<RistrettoPoint> {
+    let point = CompressedRistretto::from_slice(bytes)
+        .ok()
+        .and_then(|compressed| compressed.decompress())
+        .ok_or(OpaqueError::InvalidMessage("invalid group element"))?;
+    if point == RistrettoPoint::identity() {
+        return Err(OpaqueError::InvalidMessage("identity group element"));
+    }
+    Ok(point)
+}
+
+fn scalar_from_bytes(bytes: &[u8; 32]) -> OpaqueResult<Scalar> {
+    Option::from(Scalar::from_canonical_bytes(*bytes))
+        .filter(|scalar| *scalar != Scalar::ZERO)
+        .ok_or(OpaqueError::InvalidMessage("invalid scalar"))
+}
+
+fn i2osp2(len: usize) -> OpaqueResult<[u8; 2]> {
+    u16::try_from(len)
+        .map(u16::to_be_bytes)
+        .map_err(|_| OpaqueError::InvalidMessage("input longer than 65535 bytes"))
+}
+
+/// HKDF-SHA512 `Extract` with an empty salt.
+pub(crate) fn extract(ikm: &[u8]) -> Zeroizing<Vec<u8>> {
+    let (prk, _) = Hkdf::<Sha512>::extract(None, ikm);
+    Zeroizing::new(prk.to_vec())
+}
+
+pub(crate) fn expand(prk: &[u8], info: &[u8], len: usize) -> OpaqueResult<Zeroizing<Vec<u8>>> {
+    let hkdf = Hkdf::<Sha512>::from_prk(prk)
+        .map_err(|_| CoreError::Crypto("opaque hkdf prk".to_string()))?;
+    let mut out = Zeroizing::new(vec![0u8; len]);
+    hkdf.expand(info, &mut out)
+        .map_err(|_| CoreError::Crypto("opaque hkdf expand".to_string()))?;
+    Ok(out)
+}
+
+pub(crate) fn mac(key: &[u8], data: &[u8]) -> OpaqueResult<Vec<u8>> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key)
+        .map_err(|_| CoreError::Crypto("opaque hmac key".to_string()))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
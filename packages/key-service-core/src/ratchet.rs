@@ -0,0 +1,53 @@
+//! Forward-secret send chains for resource encryption.
+//!
+//! A ratcheted handle starts a chain from the resource key and a random chain id, then keeps
+//! only the current chain key. Message `n` is sealed under a key derived from chain key `n`,
+//! and the handle moves on to chain key `n + 1`, wiping the old one. A handle captured after
+//! message `n` therefore cannot derive the keys of earlier messages. Readers holding the
+//! resource key walk the chain from its start; the chain id and message number travel in the
+//! ciphertext framing (see [`crate::envelope`]).
+
+use crate::domains::{
+    RESOURCE_RATCHET_CHAIN_V1, RESOURCE_RATCHET_MESSAGE_V1, RESOURCE_RATCHET_NEXT_V1,
+};
+use crate::error::{CoreError, CoreResult};
+use zeroize::Zeroizing;
+
+pub const RATCHET_CHAIN_ID_LEN: usize = 16;
+/// Messages one chain may carry. Opening message `n` costs `n` HKDF steps from the chain
+/// start, so this also bounds the work a crafted frame can make a reader do.
+pub const MAX_RATCHET_MESSAGES: u64 = 1 << 12;
+const RATCHET_KEY_LEN: usize = 32;
+
+type RatchetKey = Zeroizing<Vec<u8>>;
+
+/// Chain key 0 of chain `chain_id` under `resource_key`.
+pub fn ratchet_chain_start(resource_key: &[u8], chain_id: &[u8]) -> CoreResult<RatchetKey> {
+    RESOURCE_RATCHET_CHAIN_V1.derive_with(resource_key, chain_id, RATCHET_KEY_LEN)
+}
+
+/// Message key for `chain_key` and the chain key that follows it.
+pub fn ratchet_step(chain_key: &[u8]) -> CoreResult<(RatchetKey, RatchetKey)> {
+    Ok((
+        RESOURCE_RATCHET_MESSAGE_V1.derive(chain_key, RATCHET_KEY_LEN)?,
+        RESOURCE_RATCHET_NEXT_V1.derive(chain_key, RATCHET_KEY_LEN)?,
+    ))
+}
+
+/// Key of message `message` in chain `chain_id`, walked from `resource_key`.
+pub fn ratchet_message_key(
+    resource_key: &[u8],
+    chain_id: &[u8],
+    message: u64,
+) -> CoreResult<RatchetKey> {
+    if message >= MAX_RATCHET_MESSAGES {
+        return Err(CoreError::Format(
+            "ratchet message number out of range".to_string(),
+        ));
+    }
+    let mut chain_key = ratchet_chain_start(resource_key, chain_id)?;
+    for _ in 0..message {
+        chain_key = RESOURCE_RATCHET_NEXT_V1.derive(&chain_key, RATCHET_KEY_LEN)?;
+    }
+    RESOURCE_RATCHET_MESSAGE_V1.derive(&chain_key, RATCHET_KEY_LEN)
+}
@@ -0,0 +1,151 @@
+//! Owned secret byte buffers; page-locked and guard-paged under the `memlock` feature.
+
+use crate::error::CoreResult;
+use std::fmt;
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+/// Key bytes that are wiped on drop and, with `memlock`, never swapped to disk.
+pub struct SecretBytes {
+    inner: imp::Buffer,
+}
+
+impl SecretBytes {
+    pub fn new(bytes: &[u8]) -> CoreResult<Self> {
+        Ok(Self {
+            inner: imp::Buffer::copy_from(bytes)?,
+        })
+    }
+
+    /// Whether the buffer lives in locked, guard-paged memory.
+    pub fn is_locked(&self) -> bool {
+        imp::LOCKED
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.inner.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for SecretBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.duplicate(),
+        }
+    }
+}
+
+impl Zeroize for SecretBytes {
+    fn zeroize(&mut self) {
+        self.inner.wipe();
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretBytes")
+            .field("len", &self.len())
+            .field("bytes", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(not(feature = "memlock"))]
+mod imp {
+    use crate::error::CoreResult;
+    use zeroize::{Zeroize, Zeroizing};
+
+    pub const LOCKED: bool = false;
+
+    pub struct Buffer(Zeroizing<Vec<u8>>);
+
+    impl Buffer {
+        pub fn copy_from(bytes: &[u8]) -> CoreResult<Self> {
+            Ok(Self(Zeroizing::new(bytes.to_vec())))
+        }
+
+        pub fn duplicate(&self) -> Self {
+            Self(self.0.clone())
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            &self.0
+        }
+
+        pub fn wipe(&mut self) {
+            self.0.as_mut_slice().zeroize();
+        }
+    }
+}
+
+#[cfg(feature = "memlock")]
+#[allow(unsafe_code)]
+mod imp {
+    use crate::error::{CoreError, CoreResult};
+    use std::ptr::NonNull;
+
+    pub const LOCKED: bool = true;
+
+    pub struct Buffer {
+        ptr: NonNull<[u8]>,
+        len: usize,
+    }
+
+    // The allocation is uniquely owned; access goes through &self/&mut self.
+    unsafe impl Send for Buffer {}
+    unsafe impl Sync for Buffer {}
+
+    impl Buffer {
+        pub fn copy_from(bytes: &[u8]) -> CoreResult<Self> {
+            // SAFETY: memsec returns a fresh allocation of exactly `bytes.len()` bytes
+            // that we own until `free` in Drop.
+            let ptr = unsafe { memsec::malloc_sized(bytes.len()) }
+                .ok_or_else(|| CoreError::Crypto("secure allocation failed".to_string()))?;
+            let buffer = Self {
+                ptr,
+                len: bytes.len(),
+            };
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    bytes.as_ptr(),
+                    buffer.ptr.as_ptr() as *mut u8,
+                    buffer.len,
+                );
+            }
+            Ok(buffer)
+        }
+
+        pub fn duplicate(&self) -> Self {
+            // Cloning runs inside infallible paths; running out of locked pages is fatal.
+            Self::copy_from(self.as_slice()).expect("secure allocation failed")
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            // SAFETY: `ptr` is valid for `len` bytes for the lifetime of `self`.
+            unsafe { std::slice::from_raw_parts(self.ptr.as_ptr() as *const u8, self.len) }
+        }
+
+        pub fn wipe(&mut self) {
+            // SAFETY: `ptr` is valid and writable for `len` bytes.
+            unsafe { memsec::memzero(self.ptr.as_ptr() as *mut u8, self.len) };
+        }
+    }
+
+    impl Drop for Buffer {
+        fn drop(&mut self) {
+            self.wipe();
+            // SAFETY: `ptr` came from `memsec::malloc_sized` and is freed exactly once.
+            unsafe { memsec::free(self.ptr) };
+        }
+    }
+}
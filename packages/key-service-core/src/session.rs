@@ -1,9 +1,13 @@
 //! Session tracking, handle management, and TTL enforcement.
 
 use crate::error::{CoreError, CoreResult};
+use crate::formats::KeyVaultSnapshotDecoder;
+use crate::ratchet::RATCHET_CHAIN_ID_LEN;
+use crate::secret::SecretBytes;
+use crate::stream::{StreamDecryptor, StreamEncryptor};
 use crate::types::{
-    KeyHandle, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, SessionAssurance, SessionId,
-    SessionKind,
+    CapabilityOps, KeyHandle, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, SessionAssurance,
+    SessionId, SessionKind, StreamId,
 };
 use getrandom::getrandom;
 use std::collections::{HashMap, VecDeque};
@@ -16,10 +20,15 @@ pub struct Session {
     pub expires_at_ms: u64,
     pub kind: SessionKind,
     pub assurance: SessionAssurance,
-    pub vault_key: Vec<u8>,
+    pub vault_key: SecretBytes,
     pub max_handles: usize,
+    /// Vault record appends and header changes fail with `ReadOnlySession`.
+    pub read_only: bool,
+    /// `expires_at_ms` that an expiring-soon warning was last sent for.
+    pub warned_for_expiry_ms: Option<u64>,
     handles: HashMap<String, HandleEntry>,
     handle_order: VecDeque<String>,
+    streams: HashMap<String, StreamEntry>,
 }
 
 impl fmt::Debug for Session {
@@ -32,7 +41,10 @@ impl fmt::Debug for Session {
             .field("assurance", &self.assurance)
             .field("vault_key", &"<redacted>")
             .field("max_handles", &self.max_handles)
+            .field("read_only", &self.read_only)
+            .field("warned_for_expiry_ms", &self.warned_for_expiry_ms)
             .field("handles", &self.handles.len())
+            .field("streams", &self.streams.len())
             .finish()
     }
 }
@@ -44,7 +56,7 @@ impl Session {
         expires_at_ms: u64,
         kind: SessionKind,
         assurance: SessionAssurance,
-        vault_key: Vec<u8>,
+        vault_key: SecretBytes,
     ) -> Self {
         Self {
             session_id,
@@ -54,17 +66,18 @@ impl Session {
             assurance,
             vault_key,
             max_handles: 256,
+            read_only: false,
+            warned_for_expiry_ms: None,
             handles: HashMap::new(),
             handle_order: VecDeque::new(),
+            streams: HashMap::new(),
         }
     }
 
     pub fn insert_handle(&mut self, entry: HandleEntry) -> CoreResult<KeyHandle> {
         while self.handles.len() >= self.max_handles {
             if let Some(key) = self.handle_order.pop_front() {
-                if let Some(mut removed) = self.handles.remove(&key) {
-                    removed.zeroize();
-                }
+                self.handles.remove(&key);
             } else {
                 break;
             }
@@ -75,6 +88,16 @@ impl Session {
         Ok(KeyHandle(id))
     }
 
+    /// Like [`Self::get_handle`] without refreshing the handle's LRU position.
+    pub fn has_handle(&self, handle: &KeyHandle) -> bool {
+        self.handles.contains_key(&handle.0)
+    }
+
+    /// Like [`Self::get_handle`] without refreshing the handle's LRU position.
+    pub fn peek_handle(&self, handle: &KeyHandle) -> Option<&HandleEntry> {
+        self.handles.get(&handle.0)
+    }
+
     pub fn get_handle(&mut self, handle: &KeyHandle) -> Option<&HandleEntry> {
         if self.handles.contains_key(&handle.0) {
             self.touch_handle(&handle.0);
@@ -82,21 +105,66 @@ impl Session {
         self.handles.get(&handle.0)
     }
 
-    pub fn remove_handle(&mut self, handle: &KeyHandle) {
-        if let Some(mut entry) = self.handles.remove(&handle.0) {
-            entry.zeroize();
+    pub fn get_handle_mut(&mut self, handle: &KeyHandle) -> Option<&mut HandleEntry> {
+        if self.handles.contains_key(&handle.0) {
+            self.touch_handle(&handle.0);
         }
+        self.handles.get_mut(&handle.0)
+    }
+
+    pub fn remove_handle(&mut self, handle: &KeyHandle) {
+        self.handles.remove(&handle.0);
         self.handle_order.retain(|key| key != &handle.0);
     }
 
-    pub fn clear(&mut self) {
-        for (_, mut entry) in self.handles.drain() {
-            entry.zeroize();
+    pub fn insert_stream(&mut self, entry: StreamEntry) -> CoreResult<StreamId> {
+        if self.streams.len() >= self.max_handles {
+            return Err(CoreError::Crypto("too many open streams".to_string()));
         }
+        let id = random_handle_id()?;
+        self.streams.insert(id.clone(), entry);
+        Ok(StreamId(id))
+    }
+
+    pub fn get_stream_mut(&mut self, stream_id: &StreamId) -> Option<&mut StreamEntry> {
+        self.streams.get_mut(&stream_id.0)
+    }
+
+    pub fn remove_stream(&mut self, stream_id: &StreamId) -> Option<StreamEntry> {
+        self.streams.remove(&stream_id.0)
+    }
+
+    pub fn handle_count(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.handles.clear();
         self.handle_order.clear();
+        self.streams.clear();
         self.vault_key.zeroize();
     }
 
+    /// Names the first handle-bookkeeping invariant that no longer holds.
+    #[cfg(feature = "paranoid")]
+    pub fn check_invariants(&self) -> Result<(), &'static str> {
+        if self.handles.len() != self.handle_order.len()
+            || self
+                .handle_order
+                .iter()
+                .any(|key| !self.handles.contains_key(key))
+        {
+            return Err("handle order out of step with handles");
+        }
+        if self.handles.len() > self.max_handles {
+            return Err("handle count over max_handles");
+        }
+        if self.streams.len() > self.max_handles {
+            return Err("stream count over max_handles");
+        }
+        Ok(())
+    }
+
     fn touch_handle(&mut self, key: &str) {
         if let Some(pos) = self.handle_order.iter().position(|entry| entry == key) {
             self.handle_order.remove(pos);
@@ -110,21 +178,58 @@ pub enum HandleEntry {
     ScopeKey {
         scope_id: ScopeId,
         scope_epoch: ScopeEpoch,
-        key: Vec<u8>,
+        key: SecretBytes,
     },
     ResourceKey {
         resource_id: ResourceId,
         resource_key_id: ResourceKeyId,
-        key: Vec<u8>,
+        key: SecretBytes,
+        /// Set on first encrypt when the policy uses counter nonces.
+        nonce_counter: Option<NonceCounter>,
+        /// Operations `encrypt`/`decrypt` (and their streaming forms) may use this key for.
+        ops: CapabilityOps,
+        /// Scope and epoch of the grant the key came from; `None` for redeemed capabilities.
+        scope: Option<(ScopeId, ScopeEpoch)>,
+        /// Set for handles redeemed from a capability token.
+        restriction: Option<HandleRestriction>,
+        /// Set once `enable_resource_ratchet` turns the handle into an encrypt-only ratchet;
+        /// `key` then holds the current chain key instead of the resource key.
+        ratchet: Option<ResourceRatchet>,
     },
 }
 
-impl HandleEntry {
-    fn zeroize(&mut self) {
-        match self {
-            HandleEntry::ScopeKey { key, .. } => key.zeroize(),
-            HandleEntry::ResourceKey { key, .. } => key.zeroize(),
-        }
+/// Position of a ratcheted handle's send chain; see [`crate::ratchet`].
+#[derive(Clone, Debug)]
+pub struct ResourceRatchet {
+    pub chain_id: [u8; RATCHET_CHAIN_ID_LEN],
+    /// Message number the next `encrypt` uses.
+    pub next_message: u64,
+}
+
+/// Limits on a handle redeemed from a capability token.
+#[derive(Clone, Debug)]
+pub struct HandleRestriction {
+    /// Every AAD passed with this handle must start with these bytes.
+    pub aad_prefix: Vec<u8>,
+    pub expires_at_ms: u64,
+}
+
+/// Counter nonce state for one resource-key handle: `prefix || counter` (big-endian).
+///
+/// `leased_until` is the persisted high-water mark; the counter never passes it unpersisted.
+#[derive(Clone, Debug)]
+pub struct NonceCounter {
+    pub prefix: [u8; 4],
+    pub next: u64,
+    pub leased_until: u64,
+}
+
+impl NonceCounter {
+    pub fn nonce(&self) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[..4].copy_from_slice(&self.prefix);
+        nonce[4..].copy_from_slice(&self.next.to_be_bytes());
+        nonce
     }
 }
 
@@ -144,17 +249,33 @@ impl fmt::Debug for HandleEntry {
             HandleEntry::ResourceKey {
                 resource_id,
                 resource_key_id,
+                ops,
+                scope,
+                restriction,
+                ratchet,
                 ..
             } => f
                 .debug_struct("HandleEntry::ResourceKey")
                 .field("resource_id", resource_id)
                 .field("resource_key_id", resource_key_id)
+                .field("ops", ops)
+                .field("scope", scope)
+                .field("restriction", restriction)
+                .field("ratchet", ratchet)
                 .field("key", &"<redacted>")
                 .finish(),
         }
     }
 }
 
+/// In-progress chunked AEAD stream; dropping it wipes the derived stream key.
+#[derive(Debug)]
+pub enum StreamEntry {
+    Encrypt(StreamEncryptor),
+    Decrypt(StreamDecryptor),
+    Import(Box<KeyVaultSnapshotDecoder>),
+}
+
 #[derive(Default)]
 pub struct SessionManager {
     sessions: HashMap<String, Session>,
@@ -171,15 +292,95 @@ impl SessionManager {
         self.sessions.insert(session_id.0.clone(), session);
     }
 
+    pub fn get(&self, session_id: &SessionId) -> Option<&Session> {
+        self.sessions.get(&session_id.0)
+    }
+
     pub fn get_mut(&mut self, session_id: &SessionId) -> Option<&mut Session> {
         self.sessions.get_mut(&session_id.0)
     }
 
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Clears (zeroizing handles and the vault key) and removes every session past its expiry.
+    pub fn sweep_expired(&mut self, now: u64) -> Vec<SessionId> {
+        let expired: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| now > session.expires_at_ms)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &expired {
+            if let Some(mut session) = self.sessions.remove(id) {
+                session.clear();
+            }
+        }
+        expired.into_iter().map(SessionId).collect()
+    }
+
+    /// Sessions within `warning_ms` of expiry that were not yet warned for their current expiry,
+    /// soonest first, as `(session_id, expires_at_ms)`; marks them warned.
+    pub fn take_expiring(&mut self, now: u64, warning_ms: u64) -> Vec<(SessionId, u64)> {
+        if warning_ms == 0 {
+            return Vec::new();
+        }
+        let mut expiring = Vec::new();
+        for (id, session) in &mut self.sessions {
+            let expires_at_ms = session.expires_at_ms;
+            if session.warned_for_expiry_ms != Some(expires_at_ms)
+                && now >= expires_at_ms.saturating_sub(warning_ms)
+                && now <= expires_at_ms
+            {
+                session.warned_for_expiry_ms = Some(expires_at_ms);
+                expiring.push((SessionId(id.clone()), expires_at_ms));
+            }
+        }
+        expiring.sort_by_key(|(_, expires_at_ms)| *expires_at_ms);
+        expiring
+    }
+
+    /// Earliest time a session is due an expiring-soon warning or an expiry sweep.
+    pub fn next_deadline(&self, warning_ms: u64) -> Option<u64> {
+        self.sessions
+            .values()
+            .map(|session| {
+                let expires_at_ms = session.expires_at_ms;
+                let expiry = expires_at_ms.saturating_add(1);
+                if warning_ms > 0 && session.warned_for_expiry_ms != Some(expires_at_ms) {
+                    expires_at_ms.saturating_sub(warning_ms)
+                } else {
+                    expiry
+                }
+            })
+            .min()
+    }
+
     pub fn remove(&mut self, session_id: &SessionId) {
         if let Some(mut session) = self.sessions.remove(&session_id.0) {
             session.clear();
         }
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.values()
+    }
+
+    /// Clears and removes every session; returns their ids.
+    pub fn remove_all(&mut self) -> Vec<SessionId> {
+        self.sessions
+            .drain()
+            .map(|(id, mut session)| {
+                session.clear();
+                SessionId(id)
+            })
+            .collect()
+    }
 }
 
 fn random_handle_id() -> CoreResult<String> {
@@ -0,0 +1,216 @@
+//! Thread-safe facade over [`KeyService`] for hosts serving several sessions at once.
+
+use crate::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
+use crate::envelope::{CIPHERTEXT_ENVELOPE_HEADER_LEN, CIPHERTEXT_RATCHET_HEADER_LEN};
+use crate::key_service::{
+    open_resource_payload, seal_resource_payload, DecryptResponse, EncryptResponse, KeyService,
+    KeyServiceConfig, KeyServiceError, NonceMode,
+};
+use crate::secret::SecretBytes;
+use crate::types::{KeyHandle, SessionId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Per-session cache of what encrypt/decrypt need, checked against the core after every call
+/// that goes through it.
+#[derive(Default)]
+struct SessionLane {
+    expires_at_ms: u64,
+    resource_keys: HashMap<KeyHandle, SecretBytes>,
+}
+
+type Lanes = RwLock<HashMap<SessionId, Arc<Mutex<SessionLane>>>>;
+
+/// Wraps a [`KeyService`] so independent sessions do not serialize on one lock.
+///
+/// Everything goes through the core service under a single mutex ([`Self::with_core`]) except
+/// `encrypt`/`decrypt` on a resource handle that session already used: those take only that
+/// session's lock and run in parallel with other sessions and with core calls. Cached calls skip
+/// the metrics adapter and the diagnostics error log. `NonceMode::Counter` always uses the core,
+/// since counter leases are persisted.
+pub struct SharedKeyService<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> {
+    core: Mutex<KeyService<S, Arc<C>, Arc<E>>>,
+    clock: Arc<C>,
+    entropy: Arc<E>,
+    lanes: Lanes,
+    max_plaintext_bytes: usize,
+    counter_nonces: bool,
+}
+
+impl<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter> SharedKeyService<S, C, E> {
+    pub fn new(storage: S, clock: C, entropy: E, config: KeyServiceConfig) -> Self {
+        let clock = Arc::new(clock);
+        let entropy = Arc::new(entropy);
+        let max_plaintext_bytes = config.policy.max_plaintext_bytes;
+        let counter_nonces = config.policy.nonce_mode == NonceMode::Counter;
+        Self {
+            core: Mutex::new(KeyService::new(
+                storage,
+                clock.clone(),
+                entropy.clone(),
+                config,
+            )),
+            clock,
+            entropy,
+            lanes: RwLock::new(HashMap::new()),
+            max_plaintext_bytes,
+            counter_nonces,
+        }
+    }
+
+    /// Runs `f` on the core service under the global lock, then drops cached keys for sessions
+    /// and handles it no longer has.
+    pub fn with_core<R>(&self, f: impl FnOnce(&mut KeyService<S, Arc<C>, Arc<E>>) -> R) -> R {
+        let mut core = self.core.lock().expect("key service lock");
+        let result = f(&mut core);
+        self.sync_lanes(&core);
+        result
+    }
+
+    pub fn encrypt(
+        &self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<EncryptResponse, KeyServiceError> {
+        let mut ciphertext =
+            Vec::with_capacity(CIPHERTEXT_ENVELOPE_HEADER_LEN + plaintext.len() + 16);
+        self.encrypt_into(
+            session_id,
+            resource_key_handle,
+            aad,
+            plaintext,
+            &mut ciphertext,
+        )?;
+        Ok(EncryptResponse { ciphertext })
+    }
+
+    pub fn encrypt_into(
+        &self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        plaintext: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), KeyServiceError> {
+        if !self.counter_nonces && plaintext.len() <= self.max_plaintext_bytes {
+            let cached = self.with_cached_key(session_id, resource_key_handle, |key| {
+                let nonce = self.entropy.random_bytes(12);
+                seal_resource_payload(key, aad, &nonce, plaintext, out)
+            });
+            if let Some(result) = cached {
+                return result;
+            }
+        }
+        self.with_core_caching(session_id, resource_key_handle, |core| {
+            core.encrypt_into(session_id, resource_key_handle, aad, plaintext, out)
+        })
+    }
+
+    pub fn decrypt(
+        &self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<DecryptResponse, KeyServiceError> {
+        let mut plaintext = Vec::with_capacity(ciphertext.len());
+        self.decrypt_into(
+            session_id,
+            resource_key_handle,
+            aad,
+            ciphertext,
+            &mut plaintext,
+        )?;
+        Ok(DecryptResponse { plaintext })
+    }
+
+    pub fn decrypt_into(
+        &self,
+        session_id: &SessionId,
+        resource_key_handle: &KeyHandle,
+        aad: &[u8],
+        ciphertext: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), KeyServiceError> {
+        let max_ciphertext = self.max_plaintext_bytes + CIPHERTEXT_RATCHET_HEADER_LEN + 16;
+        if ciphertext.len() <= max_ciphertext {
+            let cached = self.with_cached_key(session_id, resource_key_handle, |key| {
+                out.clear();
+                open_resource_payload(key, aad, ciphertext, out)
+            });
+            if let Some(result) = cached {
+                return result;
+            }
+        }
+        self.with_core_caching(session_id, resource_key_handle, |core| {
+            core.decrypt_into(session_id, resource_key_handle, aad, ciphertext, out)
+        })
+    }
+
+    /// Runs `f` with the cached key under the session's lock; `None` if the session or handle
+    /// is not cached or the session is past its expiry.
+    fn with_cached_key<R>(
+        &self,
+        session_id: &SessionId,
+        handle: &KeyHandle,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Option<R> {
+        let lane = self
+            .lanes
+            .read()
+            .expect("session lanes lock")
+            .get(session_id)
+            .cloned()?;
+        let lane = lane.lock().expect("session lane lock");
+        if self.clock.now_ms() > lane.expires_at_ms {
+            return None;
+        }
+        lane.resource_keys.get(handle).map(|key| f(key))
+    }
+
+    /// Runs a resource-key call on the core and, if it succeeds, caches that key for the session.
+    fn with_core_caching(
+        &self,
+        session_id: &SessionId,
+        handle: &KeyHandle,
+        f: impl FnOnce(&mut KeyService<S, Arc<C>, Arc<E>>) -> Result<(), KeyServiceError>,
+    ) -> Result<(), KeyServiceError> {
+        self.with_core(|core| {
+            f(core)?;
+            if let (Some(expires_at_ms), Some(key)) = (
+                core.session_expires_at(session_id),
+                core.resource_key(session_id, handle),
+            ) {
+                let mut lanes = self.lanes.write().expect("session lanes lock");
+                let lane = lanes.entry(session_id.clone()).or_default();
+                let mut lane = lane.lock().expect("session lane lock");
+                lane.expires_at_ms = expires_at_ms;
+                lane.resource_keys.insert(handle.clone(), key);
+            }
+            Ok(())
+        })
+    }
+
+    fn sync_lanes(&self, core: &KeyService<S, Arc<C>, Arc<E>>) {
+        let mut lanes = self.lanes.write().expect("session lanes lock");
+        lanes.retain(|session_id, lane| {
+            let mut lane = lane.lock().expect("session lane lock");
+            match core.session_expires_at(session_id) {
+                Some(expires_at_ms) => {
+                    lane.expires_at_ms = expires_at_ms;
+                    lane.resource_keys
+                        .retain(|handle, _| core.is_cacheable_handle(session_id, handle));
+                    true
+                }
+                None => {
+                    // Callers already waiting on this lane find it empty and go to the core.
+                    lane.expires_at_ms = 0;
+                    lane.resource_keys.clear();
+                    false
+                }
+            }
+        });
+    }
+}
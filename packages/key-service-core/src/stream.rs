@@ -0,0 +1,137 @@
+//! Chunked STREAM-style AEAD for large payloads under a resource key.
+
+use crate::crypto::{aead_decrypt, aead_encrypt};
+use crate::domains::STREAM_AEAD_KEY_V1;
+use crate::error::{CoreError, CoreResult};
+use crate::secret::SecretBytes;
+use aes_gcm::Aes256Gcm;
+use std::fmt;
+
+pub const STREAM_HEADER_VERSION_V1: u8 = 1;
+pub const STREAM_SALT_LEN: usize = 16;
+pub const STREAM_NONCE_PREFIX_LEN: usize = 7;
+pub const STREAM_HEADER_LEN: usize = 1 + STREAM_SALT_LEN + STREAM_NONCE_PREFIX_LEN;
+/// Random bytes a caller must supply to [`StreamEncryptor::new`].
+pub const STREAM_HEADER_RANDOM_LEN: usize = STREAM_SALT_LEN + STREAM_NONCE_PREFIX_LEN;
+
+struct StreamCore {
+    key: SecretBytes,
+    nonce_prefix: [u8; STREAM_NONCE_PREFIX_LEN],
+    aad: Vec<u8>,
+    counter: u32,
+    exhausted: bool,
+}
+
+impl StreamCore {
+    fn from_header(resource_key: &[u8], aad: &[u8], header: &[u8]) -> CoreResult<Self> {
+        if header.len() != STREAM_HEADER_LEN {
+            return Err(CoreError::Format(
+                "invalid stream header length".to_string(),
+            ));
+        }
+        if header[0] != STREAM_HEADER_VERSION_V1 {
+            return Err(CoreError::Format("unsupported stream version".to_string()));
+        }
+        let salt = &header[1..1 + STREAM_SALT_LEN];
+        let key = SecretBytes::new(&STREAM_AEAD_KEY_V1.derive_with(resource_key, salt, 32)?)?;
+        let mut nonce_prefix = [0u8; STREAM_NONCE_PREFIX_LEN];
+        nonce_prefix.copy_from_slice(&header[1 + STREAM_SALT_LEN..]);
+        Ok(Self {
+            key,
+            nonce_prefix,
+            aad: aad.to_vec(),
+            counter: 0,
+            exhausted: false,
+        })
+    }
+
+    // nonce = prefix || counter_be32 || last_flag, so reordered or truncated streams fail auth.
+    fn next_nonce(&mut self, last: bool) -> CoreResult<[u8; 12]> {
+        if self.exhausted {
+            return Err(CoreError::Crypto("stream counter exhausted".to_string()));
+        }
+        let mut nonce = [0u8; 12];
+        nonce[..STREAM_NONCE_PREFIX_LEN].copy_from_slice(&self.nonce_prefix);
+        nonce[STREAM_NONCE_PREFIX_LEN..11].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[11] = u8::from(last);
+        match self.counter.checked_add(1) {
+            Some(next) => self.counter = next,
+            None => self.exhausted = true,
+        }
+        Ok(nonce)
+    }
+}
+
+pub struct StreamEncryptor {
+    core: StreamCore,
+}
+
+impl fmt::Debug for StreamEncryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamEncryptor")
+            .field("key", &"<redacted>")
+            .field("counter", &self.core.counter)
+            .finish()
+    }
+}
+
+impl StreamEncryptor {
+    /// Starts a stream. `header_random` must be `STREAM_HEADER_RANDOM_LEN` fresh random bytes.
+    pub fn new(
+        resource_key: &[u8],
+        aad: &[u8],
+        header_random: &[u8],
+    ) -> CoreResult<(Self, Vec<u8>)> {
+        if header_random.len() != STREAM_HEADER_RANDOM_LEN {
+            return Err(CoreError::Crypto(
+                "invalid stream randomness length".to_string(),
+            ));
+        }
+        let mut header = Vec::with_capacity(STREAM_HEADER_LEN);
+        header.push(STREAM_HEADER_VERSION_V1);
+        header.extend_from_slice(header_random);
+        let core = StreamCore::from_header(resource_key, aad, &header)?;
+        Ok((Self { core }, header))
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> CoreResult<Vec<u8>> {
+        let nonce = self.core.next_nonce(false)?;
+        aead_encrypt::<Aes256Gcm>(&self.core.key, &self.core.aad, chunk, &nonce)
+    }
+
+    pub fn finish(mut self, chunk: &[u8]) -> CoreResult<Vec<u8>> {
+        let nonce = self.core.next_nonce(true)?;
+        aead_encrypt::<Aes256Gcm>(&self.core.key, &self.core.aad, chunk, &nonce)
+    }
+}
+
+pub struct StreamDecryptor {
+    core: StreamCore,
+}
+
+impl fmt::Debug for StreamDecryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamDecryptor")
+            .field("key", &"<redacted>")
+            .field("counter", &self.core.counter)
+            .finish()
+    }
+}
+
+impl StreamDecryptor {
+    pub fn new(resource_key: &[u8], aad: &[u8], header: &[u8]) -> CoreResult<Self> {
+        Ok(Self {
+            core: StreamCore::from_header(resource_key, aad, header)?,
+        })
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> CoreResult<Vec<u8>> {
+        let nonce = self.core.next_nonce(false)?;
+        aead_decrypt::<Aes256Gcm>(&self.core.key, &self.core.aad, &nonce, chunk)
+    }
+
+    pub fn finish(mut self, chunk: &[u8]) -> CoreResult<Vec<u8>> {
+        let nonce = self.core.next_nonce(true)?;
+        aead_decrypt::<Aes256Gcm>(&self.core.key, &self.core.aad, &nonce, chunk)
+    }
+}
@@ -0,0 +1,25 @@
+//! Passphrase strength estimation; the zxcvbn estimator is behind the `passphrase-strength` feature.
+
+#[cfg(feature = "passphrase-strength")]
+use crate::error::{CoreError, CoreResult};
+
+/// zxcvbn score (0 = too guessable … 4 = very unguessable) and log10 of estimated guesses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PassphraseStrength {
+    pub score: u8,
+    pub guesses_log10: f64,
+}
+
+/// Estimates the strength of a UTF-8 passphrase.
+///
+/// The estimator keeps its own copies of substrings while matching; those are not zeroized.
+#[cfg(feature = "passphrase-strength")]
+pub fn estimate_passphrase_strength(passphrase_utf8: &[u8]) -> CoreResult<PassphraseStrength> {
+    let passphrase = std::str::from_utf8(passphrase_utf8)
+        .map_err(|_| CoreError::Format("passphrase is not valid utf-8".to_string()))?;
+    let entropy = zxcvbn::zxcvbn(passphrase, &[]);
+    Ok(PassphraseStrength {
+        score: u8::from(entropy.score()),
+        guesses_log10: entropy.guesses_log10(),
+    })
+}
@@ -0,0 +1,730 @@
+//! Deterministic adapters for tests (`testkit` feature). Never use these outside tests:
+//! [`SeededEntropy`] is predictable by design.
+
+use crate::aad::aad_resource_grant_wrap_v1;
+use crate::adapters::{
+    ClockAdapter, EntropyAdapter, ListSinceResult, MonotonicCounterAdapter, StorageAdapter,
+    TransparencyAdapter,
+};
+use crate::cbor::{cbor_bytes, cbor_map};
+use crate::ciphersuite::{
+    generate_device_signing_keypair, hybrid_sign, HybridSignatureKeypair, SignerKeys,
+};
+use crate::cosign::{
+    cosign_commit, cosign_group_key, cosign_verify_possession, CosignKeyShare, CosignParticipant,
+    CosignTranscript,
+};
+use crate::crypto::{aead_encrypt, ct_eq, KdfParams};
+use crate::domains::{
+    SigContext, OPAQUE_DERIVE_OPRF_KEY_PAIR_INFO, SIG_RESOURCE_GRANT_V1, SIG_SCOPE_STATE_V1,
+    SIG_TREE_HEAD_V1,
+};
+use crate::error::{CoreError, CoreResult};
+use crate::formats::{
+    decode_cosign_enroll_request_v1, decode_cosign_sign_request_v1,
+    encode_cosign_enroll_response_v1, encode_cosign_sign_response_v1, encode_resource_grant_v1,
+    encode_scope_state_v1, CosignEnrollResponseV1, CosignSignResponseV1, ResourceGrantV1,
+    ScopeStateV1,
+};
+use crate::hash::sha256;
+use crate::key_service::{KeyService, KeyServiceConfig, KeyServicePolicy, KeyServicePolicyBuilder};
+use crate::opaque::{
+    derive_dh_key_pair, derive_handshake_keys, deserialize_element, expand, mac,
+    oprf_derive_key_pair, oprf_evaluate, preamble, OpaqueConfig, OpaqueError, OpaqueResult,
+    OPAQUE_ELEMENT_LEN, OPAQUE_HASH_LEN, OPAQUE_KE1_LEN, OPAQUE_NONCE_LEN,
+    OPAQUE_REGISTRATION_RECORD_LEN,
+};
+use crate::transparency::{
+    consistency_path, inclusion_path, merkle_root, InclusionProof, SignedTreeHead,
+    TransparencyLeaf, TransparencyLogKey,
+};
+use crate::types::{
+    AeadId, DeviceId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, SessionId, SigCiphersuiteId,
+};
+use aes_gcm::Aes256Gcm;
+use ciborium::value::Value;
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use zeroize::Zeroizing;
+
+/// The default policy plus [`KeyServicePolicy::allow_insecure_test_kdf`], so suites can create
+/// vaults with [`fast_kdf`]. Nothing else is relaxed.
+pub fn test_policy() -> KeyServicePolicyBuilder {
+    KeyServicePolicy::builder().allow_insecure_test_kdf(true)
+}
+
+/// [`KeyServiceConfig`] with [`test_policy`].
+pub fn test_config() -> KeyServiceConfig {
+    KeyServiceConfig::builder()
+        .policy(test_policy().build().expect("test policy"))
+        .build()
+        .expect("test config")
+}
+
+/// [`KdfParams::insecure_fast_for_tests`], for vaults created under [`test_config`].
+pub fn fast_kdf() -> KdfParams {
+    KdfParams::insecure_fast_for_tests()
+}
+
+/// Hex SHA-256 over the Ed25519 and ML-DSA public keys, as pinned when ingesting a scope.
+pub fn signer_fingerprint(signer: &SignerKeys) -> String {
+    let mut data = Vec::new();
+    data.extend_from_slice(&signer.ed25519_pub);
+    data.extend_from_slice(&signer.mldsa_pub);
+    hex::encode(sha256(&data))
+}
+
+/// Ingests a scope state for `scope-1` signed by `signer` as `device-1`, persists scope key
+/// `[3; 32]` at epoch 1, and returns one encoded grant of resource key `[4; 32]` for `res-1`.
+pub fn ingest_scope_and_grant<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter>(
+    core: &mut KeyService<S, C, E>,
+    session_id: &SessionId,
+    signer: &HybridSignatureKeypair,
+) -> Vec<u8> {
+    ingest_scope_and_grants(core, session_id, signer, &[None])
+        .pop()
+        .expect("one grant")
+}
+
+/// [`ingest_scope_and_grant`] with one chained grant per entry of `policies`, in order, with
+/// grant ids `grant-0`, `grant-1`, and so on.
+pub fn ingest_scope_and_grants<S: StorageAdapter, C: ClockAdapter, E: EntropyAdapter>(
+    core: &mut KeyService<S, C, E>,
+    session_id: &SessionId,
+    signer: &HybridSignatureKeypair,
+    policies: &[Option<Value>],
+) -> Vec<Vec<u8>> {
+    let device_id = DeviceId("device-1".to_string());
+    let scope_id = ScopeId("scope-1".to_string());
+    let scope_key = vec![3u8; 32];
+    let mut scope_state = ScopeStateV1 {
+        v: 1,
+        scope_id: scope_id.clone(),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: 0,
+        payload: cbor_map(vec![
+            (1, cbor_bytes(&signer.ed25519_pub)),
+            (2, cbor_bytes(&signer.mldsa_pub)),
+        ]),
+        signer_device_id: device_id.clone(),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    scope_state.signature = hybrid_sign(
+        SIG_SCOPE_STATE_V1,
+        &scope_state.to_be_signed_bytes().expect("scope state"),
+        signer,
+    )
+    .expect("sign scope state");
+    let fingerprint = signer_fingerprint(&SignerKeys {
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        ed25519_pub: signer.ed25519_pub.clone(),
+        mldsa_pub: signer.mldsa_pub.clone(),
+    });
+    core.ingest_scope_state(
+        session_id,
+        &encode_scope_state_v1(&scope_state).expect("encode scope state"),
+        Some(fingerprint),
+    )
+    .expect("ingest scope state");
+    core.persist_scope_key(session_id, &scope_id, ScopeEpoch(1), &scope_key)
+        .expect("persist scope key");
+
+    let resource_id = ResourceId("res-1".to_string());
+    let resource_key_id = ResourceKeyId("rk-1".to_string());
+    let aad = aad_resource_grant_wrap_v1(
+        &scope_id.0,
+        &resource_id.0,
+        1,
+        &resource_key_id.0,
+        AeadId::Aead1,
+    )
+    .expect("grant aad");
+    let nonce = vec![9u8; 12];
+    let mut grants = Vec::new();
+    let mut prev_hash = vec![0u8; 32];
+    for (grant_seq, policy) in policies.iter().enumerate() {
+        let mut grant = ResourceGrantV1 {
+            v: 1,
+            grant_id: format!("grant-{grant_seq}"),
+            scope_id: scope_id.clone(),
+            grant_seq: grant_seq as u64,
+            prev_hash,
+            scope_state_ref: scope_state
+                .scope_state_ref_bytes()
+                .expect("scope state ref"),
+            scope_epoch: 1,
+            resource_id: resource_id.clone(),
+            resource_key_id: resource_key_id.clone(),
+            policy: policy.clone(),
+            aead: AeadId::Aead1,
+            nonce: nonce.clone(),
+            wrapped_key: aead_encrypt::<Aes256Gcm>(&scope_key, &aad, &[4u8; 32], &nonce)
+                .expect("wrap resource key"),
+            signer_device_id: device_id.clone(),
+            sig_suite: SigCiphersuiteId::HybridSig1,
+            signature: Vec::new(),
+        };
+        grant.signature = hybrid_sign(
+            SIG_RESOURCE_GRANT_V1,
+            &grant.to_be_signed_bytes().expect("grant"),
+            signer,
+        )
+        .expect("sign grant");
+        prev_hash = grant.grant_ref_bytes().expect("grant ref");
+        grants.push(encode_resource_grant_v1(&grant).expect("encode grant"));
+    }
+    grants
+}
+
+/// SHA-256 in counter mode over a fixed seed: the same seed yields the same byte stream.
+#[derive(Debug)]
+pub struct SeededEntropy {
+    seed: u64,
+    counter: AtomicU64,
+}
+
+impl SeededEntropy {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            counter: AtomicU64::new(0),
+        }
+    }
+}
+
+impl EntropyAdapter for SeededEntropy {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            let block = self.counter.fetch_add(1, Ordering::Relaxed);
+            let mut input = [0u8; 16];
+            input[..8].copy_from_slice(&self.seed.to_be_bytes());
+            input[8..].copy_from_slice(&block.to_be_bytes());
+            out.extend_from_slice(&sha256(&input));
+        }
+        out.truncate(len);
+        out
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a test can keep one
+/// and hand another to the service.
+#[derive(Clone, Debug, Default)]
+pub struct VirtualClock {
+    now_ms: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    pub fn new(start_ms: u64) -> Self {
+        Self {
+            now_ms: Arc::new(AtomicU64::new(start_ms)),
+        }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now_ms.store(now_ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, delta_ms: u64) {
+        self.now_ms.fetch_add(delta_ms, Ordering::SeqCst);
+    }
+}
+
+impl ClockAdapter for VirtualClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum MemoryStorageError {
+    /// Write number `write` (1-based since creation) was scheduled to fail.
+    #[error("injected failure on write {write}")]
+    InjectedWriteFailure { write: u64 },
+    #[error("storage lock poisoned")]
+    Poisoned,
+}
+
+/// Full storage contents, for rollback and corruption tests.
+pub type StorageSnapshot = BTreeMap<(String, String), Vec<u8>>;
+
+#[derive(Debug, Default)]
+struct MemoryStorageState {
+    data: StorageSnapshot,
+    writes: u64,
+    fail_write: Option<u64>,
+}
+
+/// In-memory [`StorageAdapter`] with write fault injection. Clones share the same data.
+///
+/// `list_since` returns keys strictly after `cursor` in order, up to `limit`, with the last
+/// returned key as the next cursor.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    state: Arc<Mutex<MemoryStorageState>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the `n`th write from now fail (1 = the next write); the write is not applied.
+    pub fn fail_nth_write(&self, n: u64) {
+        if let Ok(mut state) = self.lock() {
+            state.fail_write = Some(state.writes + n.max(1));
+        }
+    }
+
+    pub fn clear_faults(&self) {
+        if let Ok(mut state) = self.lock() {
+            state.fail_write = None;
+        }
+    }
+
+    /// Writes attempted so far, including failed ones.
+    pub fn write_count(&self) -> u64 {
+        self.lock().map(|state| state.writes).unwrap_or_default()
+    }
+
+    pub fn snapshot(&self) -> StorageSnapshot {
+        self.lock()
+            .map(|state| state.data.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn restore(&self, snapshot: StorageSnapshot) {
+        if let Ok(mut state) = self.lock() {
+            state.data = snapshot;
+        }
+    }
+
+    /// Overwrites or removes a value without counting as a write; for tampering tests.
+    pub fn set_raw(&self, namespace: &str, key: &str, value: Option<Vec<u8>>) {
+        if let Ok(mut state) = self.lock() {
+            let entry = (namespace.to_string(), key.to_string());
+            match value {
+                Some(value) => state.data.insert(entry, value),
+                None => state.data.remove(&entry),
+            };
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, MemoryStorageState>, MemoryStorageError> {
+        self.state.lock().map_err(|_| MemoryStorageError::Poisoned)
+    }
+}
+
+impl StorageAdapter for MemoryStorage {
+    type Error = MemoryStorageError;
+
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self
+            .lock()?
+            .data
+            .get(&(namespace.to_string(), key.to_string()))
+            .cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+        let mut state = self.lock()?;
+        state.writes += 1;
+        if state.fail_write == Some(state.writes) {
+            state.fail_write = None;
+            return Err(MemoryStorageError::InjectedWriteFailure {
+                write: state.writes,
+            });
+        }
+        state
+            .data
+            .insert((namespace.to_string(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+    fn list_since(
+        &self,
+        namespace: &str,
+        cursor: &str,
+        limit: usize,
+    ) -> Result<ListSinceResult, Self::Error> {
+        let state = self.lock()?;
+        let start = (namespace.to_string(), cursor.to_string());
+        let lower = if cursor.is_empty() {
+            Bound::Included(start)
+        } else {
+            Bound::Excluded(start)
+        };
+        let out: Vec<(String, Vec<u8>)> = state
+            .data
+            .range((lower, Bound::Unbounded))
+            .take_while(|((ns, _), _)| ns == namespace)
+            .take(limit)
+            .map(|((_, key), value)| (key.clone(), value.clone()))
+            .collect();
+        let next = out
+            .last()
+            .map_or_else(|| cursor.to_string(), |(key, _)| key.clone());
+        Ok((out, next))
+    }
+}
+
+/// In-memory [`MonotonicCounterAdapter`] that lives apart from any [`MemoryStorage`], so
+/// restoring a storage snapshot leaves it where it was. Clones share the same counters.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCounter {
+    values: Arc<Mutex<BTreeMap<String, u64>>>,
+}
+
+impl MemoryCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MonotonicCounterAdapter for MemoryCounter {
+    type Error = MemoryStorageError;
+
+    fn read(&self, name: &str) -> Result<u64, Self::Error> {
+        let values = self
+            .values
+            .lock()
+            .map_err(|_| MemoryStorageError::Poisoned)?;
+        Ok(values.get(name).copied().unwrap_or(0))
+    }
+
+    fn advance(&self, name: &str, value: u64) -> Result<(), Self::Error> {
+        let mut values = self
+            .values
+            .lock()
+            .map_err(|_| MemoryStorageError::Poisoned)?;
+        let current = values.entry(name.to_string()).or_insert(0);
+        *current = (*current).max(value);
+        Ok(())
+    }
+}
+
+/// In-memory OPAQUE server (RFC 9807 server side) for exercising the client in tests. Records
+/// and pending logins are keyed by credential id.
+pub struct OpaqueTestServer {
+    config: OpaqueConfig,
+    entropy: SeededEntropy,
+    oprf_seed: Zeroizing<Vec<u8>>,
+    server_private_key: Zeroizing<Scalar>,
+    server_public_key: [u8; 32],
+    records: Mutex<BTreeMap<Vec<u8>, Vec<u8>>>,
+    pending: Mutex<BTreeMap<Vec<u8>, PendingLogin>>,
+}
+
+struct PendingLogin {
+    expected_client_mac: Vec<u8>,
+    session_key: Zeroizing<Vec<u8>>,
+}
+
+impl OpaqueTestServer {
+    pub fn new(seed: u64, config: OpaqueConfig) -> Self {
+        let entropy = SeededEntropy::new(seed);
+        let oprf_seed = Zeroizing::new(entropy.random_bytes(OPAQUE_HASH_LEN));
+        let (server_private_key, server_public_key) =
+            derive_dh_key_pair(&entropy.random_bytes(32)).expect("server key pair");
+        Self {
+            config,
+            entropy,
+            oprf_seed,
+            server_private_key,
+            server_public_key,
+            records: Mutex::new(BTreeMap::new()),
+            pending: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn server_public_key(&self) -> [u8; 32] {
+        self.server_public_key
+    }
+
+    /// `evaluated_message || server_public_key` for a registration request.
+    pub fn registration_response(
+        &self,
+        credential_id: &[u8],
+        request: &[u8],
+    ) -> OpaqueResult<Vec<u8>> {
+        let evaluated = self.evaluate(credential_id, request)?;
+        Ok([evaluated.as_slice(), &self.server_public_key].concat())
+    }
+
+    pub fn store(&self, credential_id: &[u8], record: &[u8]) -> OpaqueResult<()> {
+        if record.len() != OPAQUE_REGISTRATION_RECORD_LEN {
+            return Err(OpaqueError::InvalidMessage("registration record length"));
+        }
+        lock(&self.records).insert(credential_id.to_vec(), record.to_vec());
+        Ok(())
+    }
+
+    /// Builds KE2 and remembers the client MAC it expects in KE3.
+    pub fn login_response(&self, credential_id: &[u8], ke1: &[u8]) -> OpaqueResult<Vec<u8>> {
+        if ke1.len() != OPAQUE_KE1_LEN {
+            return Err(OpaqueError::InvalidMessage("ke1 length"));
+        }
+        let record = lock(&self.records)
+            .get(credential_id)
+            .cloned()
+            .ok_or(OpaqueError::InvalidMessage("unknown credential"))?;
+        let (client_public_key, rest) = record.split_at(OPAQUE_ELEMENT_LEN);
+        let (masking_key, envelope) = rest.split_at(OPAQUE_HASH_LEN);
+        let (blinded, rest) = ke1.split_at(OPAQUE_ELEMENT_LEN);
+        let client_keyshare = deserialize_element(&rest[OPAQUE_NONCE_LEN..])?;
+
+        let evaluated = self.evaluate(credential_id, blinded)?;
+        let masking_nonce = self.entropy.random_bytes(OPAQUE_NONCE_LEN);
+        let plaintext = [self.server_public_key.as_slice(), envelope].concat();
+        let pad = expand(
+            masking_key,
+            &[masking_nonce.as_slice(), b"CredentialResponsePad"].concat(),
+            plaintext.len(),
+        )?;
+        let masked: Vec<u8> = pad.iter().zip(&plaintext).map(|(a, b)| a ^ b).collect();
+        let credential_response = [evaluated.as_slice(), &masking_nonce, &masked].concat();
+
+        let server_nonce = self.entropy.random_bytes(OPAQUE_NONCE_LEN);
+        let (server_secret, server_keyshare) = derive_dh_key_pair(&self.entropy.random_bytes(32))?;
+        let ikm = Zeroizing::new(
+            [
+                (*server_secret * client_keyshare).compress().to_bytes(),
+                (*self.server_private_key * client_keyshare)
+                    .compress()
+                    .to_bytes(),
+                (*server_secret * deserialize_element(client_public_key)?)
+                    .compress()
+                    .to_bytes(),
+            ]
+            .concat(),
+        );
+        let preamble = preamble(
+            &self.config,
+            client_public_key,
+            &self.server_public_key,
+            ke1,
+            &credential_response,
+            &server_nonce,
+            &server_keyshare,
+        );
+        let keys = derive_handshake_keys(&ikm, &preamble)?;
+        let server_mac = mac(&keys.server_mac_key, &Sha512::digest(&preamble))?;
+        let expected_client_mac = mac(
+            &keys.client_mac_key,
+            &Sha512::digest([preamble.as_slice(), &server_mac].concat()),
+        )?;
+        lock(&self.pending).insert(
+            credential_id.to_vec(),
+            PendingLogin {
+                expected_client_mac,
+                session_key: keys.session_key,
+            },
+        );
+        Ok([
+            credential_response.as_slice(),
+            &server_nonce,
+            &server_keyshare,
+            &server_mac,
+        ]
+        .concat())
+    }
+
+    /// Checks KE3 and returns the shared session key if the client authenticated.
+    pub fn login_finish(&self, credential_id: &[u8], ke3: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+        let pending = lock(&self.pending).remove(credential_id)?;
+        ct_eq(&pending.expected_client_mac, ke3).then_some(pending.session_key)
+    }
+
+    fn evaluate(&self, credential_id: &[u8], blinded: &[u8]) -> OpaqueResult<[u8; 32]> {
+        let seed = expand(&self.oprf_seed, &[credential_id, b"OprfKey"].concat(), 32)?;
+        let (oprf_key, _) = oprf_derive_key_pair(&seed, OPAQUE_DERIVE_OPRF_KEY_PAIR_INFO)?;
+        oprf_evaluate(&oprf_key, blinded)
+    }
+}
+
+/// In-memory co-signing server for [`crate::cosign`]: one key share per device id. It only
+/// co-signs messages framed with one of `allowed` contexts, as a real server would.
+pub struct CosignTestServer {
+    entropy: SeededEntropy,
+    allowed: Vec<SigContext>,
+    devices: Mutex<BTreeMap<String, CosignServerDevice>>,
+}
+
+struct CosignServerDevice {
+    share: CosignKeyShare,
+    group_pub: [u8; 32],
+}
+
+impl CosignTestServer {
+    pub fn new(seed: u64, allowed: &[SigContext]) -> Self {
+        Self {
+            entropy: SeededEntropy::new(seed),
+            allowed: allowed.to_vec(),
+            devices: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Answers a `CosignEnrollRequestV1`, replacing any earlier share for the device.
+    pub fn enroll(&self, request: &[u8]) -> CoreResult<Vec<u8>> {
+        let request = decode_cosign_enroll_request_v1(request)?;
+        let binding = request.device_id.0.as_bytes();
+        if !cosign_verify_possession(&request.share_pub, binding, &request.proof) {
+            return Err(CoreError::Crypto("device proof of possession".to_string()));
+        }
+        let share = CosignKeyShare::generate(&self.entropy)?;
+        let proof = share.prove_possession(binding, &self.entropy)?;
+        let group_pub = cosign_group_key(&request.share_pub, &share.public)?;
+        let response = encode_cosign_enroll_response_v1(&CosignEnrollResponseV1 {
+            v: 1,
+            share_pub: share.public.to_vec(),
+            proof: proof.to_vec(),
+        })?;
+        lock(&self.devices).insert(request.device_id.0, CosignServerDevice { share, group_pub });
+        Ok(response)
+    }
+
+    /// Answers a `CosignSignRequestV1` with fresh nonces and the server's signature share.
+    pub fn sign(&self, request: &[u8]) -> CoreResult<Vec<u8>> {
+        let request = decode_cosign_sign_request_v1(request)?;
+        if !self
+            .allowed
+            .iter()
+            .any(|context| request.message.starts_with(&context.frame(&[])))
+        {
+            return Err(CoreError::Crypto("context not allowed".to_string()));
+        }
+        let devices = lock(&self.devices);
+        let device = devices
+            .get(&request.device_id.0)
+            .filter(|device| device.group_pub.as_slice() == request.group_pub)
+            .ok_or_else(|| CoreError::Crypto("unknown cosign device".to_string()))?;
+        let (nonces, commitment) = cosign_commit(&device.share, &self.entropy)?;
+        let transcript = CosignTranscript::new(
+            &device.group_pub,
+            &request.message,
+            &request.commitment,
+            &commitment,
+        )?;
+        let share = transcript.sign_share(CosignParticipant::Server, nonces, &device.share)?;
+        encode_cosign_sign_response_v1(&CosignSignResponseV1 {
+            v: 1,
+            commitment,
+            share: share.to_vec(),
+        })
+    }
+}
+
+/// In-memory key transparency log. Entries are appended with [`Self::append`] and become
+/// visible once [`Self::publish`] signs a tree head over them. Clones share one log.
+#[derive(Clone)]
+pub struct TransparencyTestLog {
+    log_id: String,
+    keypair: Arc<HybridSignatureKeypair>,
+    state: Arc<Mutex<TransparencyTestLogState>>,
+}
+
+#[derive(Default)]
+struct TransparencyTestLogState {
+    leaves: Vec<[u8; 32]>,
+    head: Option<SignedTreeHead>,
+}
+
+impl TransparencyTestLog {
+    pub fn new(log_id: &str) -> CoreResult<Self> {
+        Ok(Self {
+            log_id: log_id.to_string(),
+            keypair: Arc::new(generate_device_signing_keypair()?),
+            state: Arc::new(Mutex::new(TransparencyTestLogState::default())),
+        })
+    }
+
+    pub fn log_key(&self) -> TransparencyLogKey {
+        TransparencyLogKey {
+            log_id: self.log_id.clone(),
+            signer: SignerKeys {
+                sig_suite: crate::types::SigCiphersuiteId::HybridSig1,
+                ed25519_pub: self.keypair.ed25519_pub.clone(),
+                mldsa_pub: self.keypair.mldsa_pub.clone(),
+            },
+        }
+    }
+
+    pub fn append(&self, leaf: &TransparencyLeaf) -> CoreResult<()> {
+        let leaf_hash = leaf.leaf_hash()?;
+        lock(&self.state).leaves.push(leaf_hash);
+        Ok(())
+    }
+
+    /// Replaces the leaf at `index`, as a log rewriting its history would.
+    pub fn rewrite(&self, index: usize, leaf: &TransparencyLeaf) -> CoreResult<()> {
+        let leaf_hash = leaf.leaf_hash()?;
+        lock(&self.state).leaves[index] = leaf_hash;
+        Ok(())
+    }
+
+    /// Signs a tree head over every leaf appended so far.
+    pub fn publish(&self, timestamp_ms: u64) -> CoreResult<SignedTreeHead> {
+        let mut state = lock(&self.state);
+        let mut head = SignedTreeHead {
+            log_id: self.log_id.clone(),
+            tree_size: state.leaves.len() as u64,
+            root_hash: merkle_root(&state.leaves),
+            timestamp_ms,
+            signature: Vec::new(),
+        };
+        head.signature = hybrid_sign(SIG_TREE_HEAD_V1, &head.to_be_signed_bytes()?, &self.keypair)?;
+        state.head = Some(head.clone());
+        Ok(head)
+    }
+}
+
+impl TransparencyAdapter for TransparencyTestLog {
+    type Error = CoreError;
+
+    fn tree_head(&self) -> Result<SignedTreeHead, CoreError> {
+        lock(&self.state)
+            .head
+            .clone()
+            .ok_or_else(|| CoreError::Format("no tree head published".to_string()))
+    }
+
+    fn inclusion_proof(
+        &self,
+        leaf_hash: &[u8; 32],
+        tree_size: u64,
+    ) -> Result<Option<InclusionProof>, CoreError> {
+        let state = lock(&self.state);
+        let leaves = &state.leaves[..(tree_size as usize).min(state.leaves.len())];
+        Ok(leaves
+            .iter()
+            .position(|leaf| leaf == leaf_hash)
+            .map(|index| InclusionProof {
+                leaf_index: index as u64,
+                tree_size,
+                audit_path: inclusion_path(leaves, index),
+            }))
+    }
+
+    fn consistency_proof(
+        &self,
+        first_size: u64,
+        second_size: u64,
+    ) -> Result<Vec<[u8; 32]>, CoreError> {
+        let state = lock(&self.state);
+        let leaves = &state.leaves[..(second_size as usize).min(state.leaves.len())];
+        Ok(consistency_path(leaves, first_size as usize))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,64 @@
+//! [`TimerAdapter`] backed by the tokio runtime (`tokio` feature).
+
+use crate::adapters::TimerAdapter;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+/// Runs `on_fire` on a tokio runtime once each scheduled delay passes; rescheduling or dropping
+/// the timer aborts the pending wake-up.
+///
+/// `on_fire` typically upgrades a `Weak` to the service and calls `on_timer`; holding an `Arc`
+/// would keep the service alive through its own timer.
+pub struct TokioTimer {
+    runtime: Handle,
+    on_fire: Arc<dyn Fn() + Send + Sync>,
+    pending: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl TokioTimer {
+    /// Uses the current runtime; panics outside one, like `tokio::spawn`.
+    pub fn new(on_fire: impl Fn() + Send + Sync + 'static) -> Self {
+        Self::with_handle(Handle::current(), on_fire)
+    }
+
+    pub fn with_handle(runtime: Handle, on_fire: impl Fn() + Send + Sync + 'static) -> Self {
+        Self {
+            runtime,
+            on_fire: Arc::new(on_fire),
+            pending: Mutex::new(None),
+        }
+    }
+
+    fn replace(&self, task: Option<JoinHandle<()>>) {
+        let mut pending = match self.pending.lock() {
+            Ok(pending) => pending,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(previous) = std::mem::replace(&mut *pending, task) {
+            previous.abort();
+        }
+    }
+}
+
+impl TimerAdapter for TokioTimer {
+    fn schedule(&self, delay_ms: u64) {
+        let on_fire = self.on_fire.clone();
+        let task = self.runtime.spawn(async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            on_fire();
+        });
+        self.replace(Some(task));
+    }
+
+    fn cancel(&self) {
+        self.replace(None);
+    }
+}
+
+impl Drop for TokioTimer {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -0,0 +1,268 @@
+//! Key transparency: signed tree heads and Merkle proofs over an append-only log of scope
+//! states and user public keys.
+//!
+//! The tree is the RFC 9162 Merkle tree over SHA-256: a leaf hashes as `H(0x00 || leaf)`,
+//! an interior node as `H(0x01 || left || right)`. A log signs each tree head with a hybrid
+//! key under [`SIG_TREE_HEAD_V1`]. Clients check that every head is consistent with the last
+//! one they trusted, so a log cannot show them a history it later rewrites.
+
+use crate::cbor::{cbor_bytes, cbor_map, cbor_text, cbor_uint, encode_canonical_value};
+use crate::ciphersuite::{hybrid_verify, SignerKeys};
+use crate::domains::{SIG_TREE_HEAD_V1, TRANSPARENCY_LEAF_V1};
+use crate::error::CoreResult;
+use crate::hash::sha256;
+use crate::types::{ScopeId, UserId};
+
+/// A log's identity and the key its tree heads are signed with.
+#[derive(Clone, Debug)]
+pub struct TransparencyLogKey {
+    pub log_id: String,
+    pub signer: SignerKeys,
+}
+
+/// The log's commitment to its first `tree_size` leaves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedTreeHead {
+    pub log_id: String,
+    pub tree_size: u64,
+    pub root_hash: [u8; 32],
+    pub timestamp_ms: u64,
+    pub signature: Vec<u8>,
+}
+
+impl SignedTreeHead {
+    pub fn to_be_signed_bytes(&self) -> CoreResult<Vec<u8>> {
+        let value = cbor_map(vec![
+            (0, cbor_text(&self.log_id)),
+            (1, cbor_uint(self.tree_size)),
+            (2, cbor_bytes(&self.root_hash)),
+            (3, cbor_uint(self.timestamp_ms)),
+        ]);
+        encode_canonical_value(&value)
+    }
+
+    /// Whether `key` names this head's log and signed it.
+    pub fn verify(&self, key: &TransparencyLogKey) -> bool {
+        self.log_id == key.log_id
+            && self.to_be_signed_bytes().is_ok_and(|bytes| {
+                hybrid_verify(SIG_TREE_HEAD_V1, &bytes, &self.signature, &key.signer)
+            })
+    }
+}
+
+/// Audit path from leaf `leaf_index` to the root of the tree of `tree_size` leaves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub audit_path: Vec<[u8; 32]>,
+}
+
+/// What a log entry commits to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransparencyLeaf {
+    /// A scope state, by its `scope_state_ref`.
+    ScopeState {
+        scope_id: ScopeId,
+        scope_state_ref: Vec<u8>,
+    },
+    /// A user's encoded public key, as returned by `get_user_public_key`.
+    UserPublicKey {
+        user_id: UserId,
+        public_bytes: Vec<u8>,
+    },
+}
+
+impl TransparencyLeaf {
+    /// The leaf input: `{0: tag, 1: kind, 2: subject id, 3: commitment}`. User keys are
+    /// committed to by their SHA-256, scope states by their ref, which is already a hash.
+    pub fn to_bytes(&self) -> CoreResult<Vec<u8>> {
+        let (kind, subject, commitment) = match self {
+            Self::ScopeState {
+                scope_id,
+                scope_state_ref,
+            } => (0, &scope_id.0, scope_state_ref.clone()),
+            Self::UserPublicKey {
+                user_id,
+                public_bytes,
+            } => (1, &user_id.0, sha256(public_bytes).to_vec()),
+        };
+        encode_canonical_value(&cbor_map(vec![
+            (0, cbor_text(TRANSPARENCY_LEAF_V1)),
+            (1, cbor_uint(kind)),
+            (2, cbor_text(subject)),
+            (3, cbor_bytes(&commitment)),
+        ]))
+    }
+
+    pub fn leaf_hash(&self) -> CoreResult<[u8; 32]> {
+        Ok(leaf_hash(&self.to_bytes()?))
+    }
+}
+
+pub fn leaf_hash(leaf: &[u8]) -> [u8; 32] {
+    sha256(&[&[0x00][..], leaf].concat())
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    sha256(&[&[0x01][..], left, right].concat())
+}
+
+/// RFC 9162 §2.1.3.2: whether `proof` leads from `leaf_hash` to `root_hash`.
+pub fn verify_inclusion(
+    leaf_hash: &[u8; 32],
+    proof: &InclusionProof,
+    root_hash: &[u8; 32],
+) -> bool {
+    if proof.leaf_index >= proof.tree_size {
+        return false;
+    }
+    let mut index = proof.leaf_index;
+    let mut last = proof.tree_size - 1;
+    let mut hash = *leaf_hash;
+    for sibling in &proof.audit_path {
+        if last == 0 {
+            return false;
+        }
+        if index & 1 == 1 || index == last {
+            hash = node_hash(sibling, &hash);
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            hash = node_hash(&hash, sibling);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+    last == 0 && hash == *root_hash
+}
+
+/// RFC 9162 §2.1.4.2: whether `proof` shows the tree of `first_size` leaves with root
+/// `first_root` is a prefix of the tree of `second_size` leaves with root `second_root`.
+pub fn verify_consistency(
+    first_size: u64,
+    first_root: &[u8; 32],
+    second_size: u64,
+    second_root: &[u8; 32],
+    proof: &[[u8; 32]],
+) -> bool {
+    if first_size > second_size {
+        return false;
+    }
+    if first_size == second_size {
+        return proof.is_empty() && first_root == second_root;
+    }
+    if first_size == 0 {
+        return proof.is_empty();
+    }
+    if proof.is_empty() {
+        return false;
+    }
+    let mut path = proof.iter();
+    let seed = if first_size.is_power_of_two() {
+        *first_root
+    } else {
+        match path.next() {
+            Some(hash) => *hash,
+            None => return false,
+        }
+    };
+    let mut index = first_size - 1;
+    let mut last = second_size - 1;
+    while index & 1 == 1 {
+        index >>= 1;
+        last >>= 1;
+    }
+    let (mut first_hash, mut second_hash) = (seed, seed);
+    for sibling in path {
+        if last == 0 {
+            return false;
+        }
+        if index & 1 == 1 || index == last {
+            first_hash = node_hash(sibling, &first_hash);
+            second_hash = node_hash(sibling, &second_hash);
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            second_hash = node_hash(&second_hash, sibling);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+    last == 0 && first_hash == *first_root && second_hash == *second_root
+}
+
+/// Root over `leaf_hashes`; the hash of the empty string for an empty tree.
+pub fn merkle_root(leaf_hashes: &[[u8; 32]]) -> [u8; 32] {
+    match leaf_hashes.len() {
+        0 => sha256(&[]),
+        1 => leaf_hashes[0],
+        n => {
+            let split = split_point(n);
+            node_hash(
+                &merkle_root(&leaf_hashes[..split]),
+                &merkle_root(&leaf_hashes[split..]),
+            )
+        }
+    }
+}
+
+/// Audit path for `index` among `leaf_hashes`, for logs and tests.
+pub fn inclusion_path(leaf_hashes: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+    if leaf_hashes.len() <= 1 {
+        return Vec::new();
+    }
+    let split = split_point(leaf_hashes.len());
+    let (left, right) = leaf_hashes.split_at(split);
+    if index < split {
+        let mut path = inclusion_path(left, index);
+        path.push(merkle_root(right));
+        path
+    } else {
+        let mut path = inclusion_path(right, index - split);
+        path.push(merkle_root(left));
+        path
+    }
+}
+
+/// Consistency proof from the first `first_size` of `leaf_hashes` to all of them.
+pub fn consistency_path(leaf_hashes: &[[u8; 32]], first_size: usize) -> Vec<[u8; 32]> {
+    if first_size == 0 || first_size >= leaf_hashes.len() {
+        return Vec::new();
+    }
+    subproof(first_size, leaf_hashes, true)
+}
+
+fn subproof(first_size: usize, leaf_hashes: &[[u8; 32]], complete: bool) -> Vec<[u8; 32]> {
+    if first_size == leaf_hashes.len() {
+        return if complete {
+            Vec::new()
+        } else {
+            vec![merkle_root(leaf_hashes)]
+        };
+    }
+    let split = split_point(leaf_hashes.len());
+    let (left, right) = leaf_hashes.split_at(split);
+    if first_size <= split {
+        let mut path = subproof(first_size, left, complete);
+        path.push(merkle_root(right));
+        path
+    } else {
+        let mut path = subproof(first_size - split, right, false);
+        path.push(merkle_root(left));
+        path
+    }
+}
+
+/// Largest power of two below `n` (for `n >= 2`).
+fn split_point(n: usize) -> usize {
+    let mut split = 1;
+    while split << 1 < n {
+        split <<= 1;
+    }
+    split
+}
@@ -18,6 +18,15 @@ impl fmt::Debug for KeyHandle {
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct StreamId(pub String);
+
+impl fmt::Debug for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StreamId(...)")
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct UserId(pub String);
 
@@ -36,16 +45,300 @@ pub struct ScopeEpoch(pub u64);
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct ResourceKeyId(pub String);
 
+/// Which characters [`IdPolicy`] allows in an identifier.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdCharset {
+    /// Any Unicode text without control characters.
+    #[default]
+    NoControl,
+    /// ASCII letters, digits, and `-`, `_`, `.`, `:`.
+    UrlSafe,
+}
+
+/// Shape every user, device, scope, resource, and record id must have. Ids end up in AADs,
+/// storage keys (`record:{id}`), logs, and JS, so they are checked wherever they enter the
+/// service rather than where they are used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdPolicy {
+    /// Longest id in UTF-8 bytes; empty ids are always rejected.
+    pub max_bytes: usize,
+    pub charset: IdCharset,
+}
+
+impl Default for IdPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: 256,
+            charset: IdCharset::NoControl,
+        }
+    }
+}
+
+impl IdPolicy {
+    /// Why `id` is rejected (`"empty"`, `"too long"`, or `"disallowed character"`), if it is.
+    pub fn violation(&self, id: &str) -> Option<&'static str> {
+        if id.is_empty() {
+            return Some("empty");
+        }
+        if id.len() > self.max_bytes {
+            return Some("too long");
+        }
+        let allowed = |c: char| match self.charset {
+            IdCharset::NoControl => !c.is_control(),
+            IdCharset::UrlSafe => c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'),
+        };
+        if !id.chars().all(allowed) {
+            return Some("disallowed character");
+        }
+        None
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum SessionKind {
     Normal,
     StepUp,
 }
 
+/// How a session was unlocked. Compared by [`Self::level`]: the single-factor methods share
+/// the lowest level, so a policy minimum of any of them accepts the others. `UserPresence`
+/// is among them because its secret may come from a synced passkey that leaves the device;
+/// `HardwareBacked` ranks above it since the FIDO2 `hmac-secret` never leaves the security
+/// key, though it is still one factor and ranks below `MultiFactor`.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum SessionAssurance {
     Passphrase,
     UserPresence,
+    /// Shares from enough of the user's own devices; see [`crate::device_shares`].
+    DeviceShares,
+    /// The user-presence secret read from a security key over FIDO2.
+    HardwareBacked,
+    /// The passphrase and the user-presence secret together.
+    MultiFactor,
+}
+
+impl SessionAssurance {
+    pub fn level(self) -> u8 {
+        match self {
+            SessionAssurance::Passphrase
+            | SessionAssurance::UserPresence
+            | SessionAssurance::DeviceShares => 0,
+            SessionAssurance::HardwareBacked => 1,
+            SessionAssurance::MultiFactor => 2,
+        }
+    }
+
+    /// Whether a session with this assurance meets a `required` minimum.
+    pub fn satisfies(self, required: Self) -> bool {
+        self.level() >= required.level()
+    }
+}
+
+/// Step-up operations that [`crate::key_service::KeyServicePolicy::min_assurance`] can
+/// reserve for stronger sessions.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum SensitiveOperation {
+    ExportKeyVault,
+    ImportKeyVault,
+    RepairKeyVault,
+    ChangePassphrase,
+    EnableKmsWrap,
+    /// Enabling or disabling user-presence unlock.
+    UserPresenceUnlock,
+    /// Splitting, enrolling, or dropping device-share unlock.
+    DeviceShareUnlock,
+    RotationPolicy,
+    CosignEnrollment,
+}
+
+/// Operations a resource-key handle, or a capability token minted from one, allows.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct CapabilityOps {
+    pub encrypt: bool,
+    pub decrypt: bool,
+}
+
+impl CapabilityOps {
+    pub const ALL: Self = Self {
+        encrypt: true,
+        decrypt: true,
+    };
+
+    pub fn is_empty(&self) -> bool {
+        !self.encrypt && !self.decrypt
+    }
+
+    pub fn intersection(&self, other: &CapabilityOps) -> CapabilityOps {
+        CapabilityOps {
+            encrypt: self.encrypt && other.encrypt,
+            decrypt: self.decrypt && other.decrypt,
+        }
+    }
+
+    /// True when every operation in `other` is also in `self`.
+    pub fn contains(&self, other: &CapabilityOps) -> bool {
+        (self.encrypt || !other.encrypt) && (self.decrypt || !other.decrypt)
+    }
+
+    /// Wire encoding: bit 0 = encrypt, bit 1 = decrypt.
+    pub fn bits(&self) -> u64 {
+        u64::from(self.encrypt) | (u64::from(self.decrypt) << 1)
+    }
+
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        (bits <= 3).then_some(Self {
+            encrypt: bits & 1 != 0,
+            decrypt: bits & 2 != 0,
+        })
+    }
+}
+
+/// Role of a signer device within a scope; only owners and admins may sign epoch bumps and
+/// membership changes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ScopeRole {
+    Owner,
+    Admin,
+    Member,
+}
+
+impl ScopeRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScopeRole::Owner => "owner",
+            ScopeRole::Admin => "admin",
+            ScopeRole::Member => "member",
+        }
+    }
+
+    /// Wire encoding in the membership-change payload.
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            ScopeRole::Owner => 0,
+            ScopeRole::Admin => 1,
+            ScopeRole::Member => 2,
+        }
+    }
+
+    pub fn from_u64(value: u64) -> Option<Self> {
+        match value {
+            0 => Some(ScopeRole::Owner),
+            1 => Some(ScopeRole::Admin),
+            2 => Some(ScopeRole::Member),
+            _ => None,
+        }
+    }
+
+    pub fn can_administer(&self) -> bool {
+        matches!(self, ScopeRole::Owner | ScopeRole::Admin)
+    }
+}
+
+/// What an epoch bump does to the epochs before it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum EpochRetirement {
+    /// Older epochs stay readable but refuse new encryption.
+    DecryptOnly,
+    /// Older epochs keep working as before.
+    Keep,
+}
+
+impl EpochRetirement {
+    /// Wire encoding in the epoch-bump payload.
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            EpochRetirement::DecryptOnly => 0,
+            EpochRetirement::Keep => 1,
+        }
+    }
+
+    pub fn from_u64(value: u64) -> Option<Self> {
+        match value {
+            0 => Some(EpochRetirement::DecryptOnly),
+            1 => Some(EpochRetirement::Keep),
+            _ => None,
+        }
+    }
+}
+
+/// When a scope's key is due for rotation. A key comes due once it is `max_key_age_ms` old or
+/// has encrypted `max_operations` times, and `encrypt` under it is refused `grace_ms` later.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ScopeRotationPolicy {
+    pub max_key_age_ms: Option<u64>,
+    pub max_operations: Option<u64>,
+    pub grace_ms: u64,
+}
+
+/// Which limit of a [`ScopeRotationPolicy`] made a key due.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RotationReason {
+    KeyAge,
+    Operations,
+}
+
+impl RotationReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RotationReason::KeyAge => "key-age",
+            RotationReason::Operations => "operations",
+        }
+    }
+}
+
+/// Compression of the record section in a v2 keyvault export.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SnapshotCompression {
+    /// Raw deflate; available on every target, including wasm.
+    Deflate,
+    /// Needs the `zstd` feature to export or import.
+    Zstd,
+}
+
+impl SnapshotCompression {
+    /// Zstd when the `zstd` feature is built in, else deflate.
+    pub fn preferred() -> Self {
+        if cfg!(feature = "zstd") {
+            SnapshotCompression::Zstd
+        } else {
+            SnapshotCompression::Deflate
+        }
+    }
+
+    /// Wire encoding in the v2 snapshot map.
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            SnapshotCompression::Deflate => 0,
+            SnapshotCompression::Zstd => 1,
+        }
+    }
+
+    pub fn from_u64(value: u64) -> Option<Self> {
+        match value {
+            0 => Some(SnapshotCompression::Deflate),
+            1 => Some(SnapshotCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnapshotCompression::Deflate => "deflate",
+            SnapshotCompression::Zstd => "zstd",
+        }
+    }
+}
+
+impl TryFrom<&str> for SnapshotCompression {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "deflate" => Ok(SnapshotCompression::Deflate),
+            "zstd" => Ok(SnapshotCompression::Zstd),
+            _ => Err(format!("unknown snapshot compression: {value}")),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -53,9 +346,21 @@ pub enum AeadId {
     Aead1,
 }
 
+/// How a key-encryption key wraps `K_vault`, for both the passphrase and the PRF wrap.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum KeyWrapAlg {
+    /// AES-256-GCM under a committing HKDF subkey; see `crypto::committing_wrap`.
+    #[default]
+    AesGcm,
+    /// AES-256 key wrap with padding (RFC 5649, SP 800-38F KWP); see `crypto::kwp_wrap`.
+    AesKwp,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum KemCiphersuiteId {
     HybridKem1,
+    /// X25519 + ML-KEM-768 with the X-Wing combiner (draft-connolly-cfrg-xwing-kem).
+    XWing,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -71,10 +376,20 @@ impl AeadId {
     }
 }
 
+impl KeyWrapAlg {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyWrapAlg::AesGcm => "aes-gcm",
+            KeyWrapAlg::AesKwp => "aes-kwp",
+        }
+    }
+}
+
 impl KemCiphersuiteId {
     pub fn as_str(&self) -> &'static str {
         match self {
             KemCiphersuiteId::HybridKem1 => "hybrid-kem-1",
+            KemCiphersuiteId::XWing => "hybrid-kem-xwing",
         }
     }
 }
@@ -98,12 +413,25 @@ impl TryFrom<&str> for AeadId {
     }
 }
 
+impl TryFrom<&str> for KeyWrapAlg {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "aes-gcm" => Ok(KeyWrapAlg::AesGcm),
+            "aes-kwp" => Ok(KeyWrapAlg::AesKwp),
+            _ => Err(format!("unknown key wrap alg: {value}")),
+        }
+    }
+}
+
 impl TryFrom<&str> for KemCiphersuiteId {
     type Error = String;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "hybrid-kem-1" => Ok(KemCiphersuiteId::HybridKem1),
+            "hybrid-kem-xwing" => Ok(KemCiphersuiteId::XWing),
             _ => Err(format!("unknown kem id: {value}")),
         }
     }
@@ -0,0 +1,53 @@
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{SensitiveOperation, SessionAssurance, UserId};
+
+#[test]
+fn single_factor_levels_are_interchangeable_and_weaker_than_the_rest() {
+    use SessionAssurance::*;
+    for single in [Passphrase, UserPresence, DeviceShares] {
+        assert!(single.satisfies(Passphrase));
+        assert!(Passphrase.satisfies(single));
+        assert!(!single.satisfies(HardwareBacked));
+        assert!(HardwareBacked.satisfies(single));
+    }
+    assert!(!HardwareBacked.satisfies(MultiFactor));
+    assert!(MultiFactor.satisfies(HardwareBacked));
+}
+
+#[test]
+fn the_policy_table_gates_each_listed_operation() {
+    let policy = test_policy()
+        .min_assurance(
+            SensitiveOperation::UserPresenceUnlock,
+            SessionAssurance::HardwareBacked,
+        )
+        .build()
+        .expect("policy");
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(111),
+        KeyServiceConfig { policy },
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert!(matches!(
+        core.enable_user_presence_unlock(&session_id, vec![1, 2], vec![7u8; 32]),
+        Err(KeyServiceError::StepUpRequired)
+    ));
+    core.step_up(&session_id, b"pass").expect("step up");
+    assert!(matches!(
+        core.enable_user_presence_unlock(&session_id, vec![1, 2], vec![7u8; 32]),
+        Err(KeyServiceError::AssuranceRequired {
+            required: SessionAssurance::HardwareBacked
+        })
+    ));
+
+    // Unlisted operations still take any step-up session.
+    core.export_keyvault(&session_id)
+        .expect("export is not listed");
+}
@@ -1,13 +1,20 @@
-use mo_key_service_core::adapters::{AsyncStorageAdapter, BoxFuture, ClockAdapter, EntropyAdapter};
-use mo_key_service_core::async_key_service::AsyncKeyService;
+use mo_key_service_core::adapters::{
+    AsyncDeviceAnchorAdapter, AsyncStorageAdapter, BoxFuture, ClockAdapter, DeviceAnchorAdapter,
+    EntropyAdapter, StorageAdapter, SyncDeviceAnchorAdapter, SyncStorageAdapter,
+};
+use mo_key_service_core::async_key_service::{run_session_sweeper, AsyncKeyService};
+use mo_key_service_core::audit::{AUDIT_HEAD_KEY, AUDIT_NAMESPACE};
 use mo_key_service_core::crypto::KdfParams;
-use mo_key_service_core::key_service::KeyServiceConfig;
+use mo_key_service_core::key_service::KeyServiceError;
+use mo_key_service_core::testkit::{fast_kdf, test_config, MemoryStorage, SeededEntropy};
 use mo_key_service_core::types::{SessionKind, UserId};
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::time::Duration;
 
 type StorageMap = HashMap<(String, String), Vec<u8>>;
 
@@ -106,6 +113,61 @@ impl EntropyAdapter for FixedEntropy {
     }
 }
 
+/// Toy async anchor: prefixes the label and counts calls.
+#[derive(Clone, Default)]
+struct CountingAnchor {
+    seals: Arc<AtomicUsize>,
+    unseals: Arc<AtomicUsize>,
+}
+
+impl AsyncDeviceAnchorAdapter for CountingAnchor {
+    type Error = String;
+
+    fn seal<'a>(
+        &'a self,
+        label: &'a str,
+        _aad: &'a [u8],
+        plaintext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, Self::Error>> {
+        Box::pin(async move {
+            self.seals.fetch_add(1, Ordering::SeqCst);
+            Ok([label.as_bytes(), plaintext].concat())
+        })
+    }
+
+    fn unseal<'a>(
+        &'a self,
+        label: &'a str,
+        _aad: &'a [u8],
+        ciphertext: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, Self::Error>> {
+        Box::pin(async move {
+            self.unseals.fetch_add(1, Ordering::SeqCst);
+            ciphertext
+                .strip_prefix(label.as_bytes())
+                .map(|rest| rest.to_vec())
+                .ok_or_else(|| "anchor mismatch".to_string())
+        })
+    }
+}
+
+struct PrefixAnchor;
+
+impl DeviceAnchorAdapter for PrefixAnchor {
+    type Error = String;
+
+    fn seal(&self, label: &str, _aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        Ok([label.as_bytes(), plaintext].concat())
+    }
+
+    fn unseal(&self, label: &str, _aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        ciphertext
+            .strip_prefix(label.as_bytes())
+            .map(|rest| rest.to_vec())
+            .ok_or_else(|| "anchor mismatch".to_string())
+    }
+}
+
 fn block_on<F: Future>(mut fut: F) -> F::Output {
     let waker = unsafe { Waker::from_raw(raw_waker()) };
     let mut ctx = Context::from_waker(&waker);
@@ -138,7 +200,7 @@ fn async_key_service_flushes_writes() {
     let storage = MemAsyncStorage::default();
     let clock = FixedClock { now: 42 };
     let entropy = FixedEntropy;
-    let config = KeyServiceConfig::default();
+    let config = test_config();
     let mut service = block_on(AsyncKeyService::new(
         storage.clone(),
         clock,
@@ -157,3 +219,173 @@ fn async_key_service_flushes_writes() {
     let unlock = service.unlock_passphrase(b"pass").expect("unlock");
     assert_eq!(unlock.kind, SessionKind::Normal);
 }
+
+#[test]
+fn failed_flush_keeps_unflushed_writes_in_order() {
+    let storage = MemoryStorage::new();
+    let mut service = block_on(AsyncKeyService::new(
+        SyncStorageAdapter(storage.clone()),
+        FixedClock { now: 42 },
+        SeededEntropy::new(1),
+        test_config(),
+    ))
+    .expect("async service");
+    let kdf = fast_kdf();
+
+    // A single transient failure is absorbed by the retry.
+    storage.fail_nth_write(2);
+    block_on(service.create_vault(UserId("user-1".to_string()), b"pass", kdf))
+        .expect("create vault");
+    assert!(service.unflushed_writes().is_empty());
+
+    service.set_flush_attempts(1);
+    let session_id = service
+        .unlock_passphrase(b"pass")
+        .expect("unlock")
+        .session_id;
+    storage.fail_nth_write(1);
+    let err = block_on(service.step_up(&session_id, b"pass")).expect_err("flush fails");
+    assert!(matches!(err, KeyServiceError::StorageError(_)));
+    assert!(err.is_retryable());
+    let unflushed = service.unflushed_writes();
+    assert!(!unflushed.is_empty());
+    let durable = storage.snapshot();
+    assert!(unflushed
+        .iter()
+        .any(|e| durable.get(&(e.namespace.clone(), e.key.clone())) != Some(&e.value)));
+
+    block_on(service.flush()).expect("re-flush");
+    assert!(service.unflushed_writes().is_empty());
+    let durable = storage.snapshot();
+    for entry in &unflushed {
+        let latest = unflushed
+            .iter()
+            .rev()
+            .find(|e| e.namespace == entry.namespace && e.key == entry.key)
+            .expect("entry");
+        assert_eq!(
+            durable.get(&(entry.namespace.clone(), entry.key.clone())),
+            Some(&latest.value)
+        );
+    }
+}
+
+#[test]
+fn pending_writes_coalesce_per_key() {
+    let storage = MemoryStorage::new();
+    let mut service = block_on(AsyncKeyService::new(
+        SyncStorageAdapter(storage.clone()),
+        FixedClock { now: 42 },
+        SeededEntropy::new(1),
+        test_config(),
+    ))
+    .expect("async service");
+    let kdf = fast_kdf();
+    block_on(service.create_vault(UserId("user-1".to_string()), b"pass", kdf))
+        .expect("create vault");
+
+    for _ in 0..3 {
+        service.unlock_passphrase(b"pass").expect("unlock");
+    }
+    let unflushed = service.unflushed_writes();
+    let heads: Vec<_> = unflushed
+        .iter()
+        .filter(|e| e.namespace == AUDIT_NAMESPACE && e.key == AUDIT_HEAD_KEY)
+        .collect();
+    assert_eq!(heads.len(), 1);
+    let mut slots: Vec<_> = unflushed
+        .iter()
+        .map(|e| (e.namespace.as_str(), e.key.as_str()))
+        .collect();
+    let total = slots.len();
+    slots.sort();
+    slots.dedup();
+    assert_eq!(slots.len(), total);
+
+    let writes_before = storage.write_count();
+    block_on(service.flush()).expect("flush");
+    assert_eq!(storage.write_count() - writes_before, total as u64);
+    assert_eq!(
+        storage
+            .get(AUDIT_NAMESPACE, AUDIT_HEAD_KEY)
+            .expect("get")
+            .as_ref(),
+        Some(&heads[0].value)
+    );
+}
+
+#[test]
+fn session_sweeper_runs_until_told_to_stop() {
+    let mut sweeps = 0;
+    let sleeps = Arc::new(Mutex::new(Vec::new()));
+    let recorded = sleeps.clone();
+    block_on(run_session_sweeper(
+        Duration::from_secs(30),
+        || {
+            sweeps += 1;
+            sweeps < 3
+        },
+        move |interval| {
+            recorded.lock().expect("sleeps lock").push(interval);
+            std::future::ready(())
+        },
+    ));
+    assert_eq!(sweeps, 3);
+    assert_eq!(
+        *sleeps.lock().expect("sleeps lock"),
+        vec![Duration::from_secs(30); 2]
+    );
+}
+
+#[test]
+fn async_device_anchor_seals_head_marker_before_flush() {
+    let storage = MemoryStorage::new();
+    let anchor = CountingAnchor::default();
+    let mut service = block_on(AsyncKeyService::new(
+        SyncStorageAdapter(storage.clone()),
+        FixedClock { now: 42 },
+        SeededEntropy::new(1),
+        test_config(),
+    ))
+    .expect("async service");
+    block_on(service.set_async_device_anchor(anchor.clone())).expect("set anchor");
+    let kdf = fast_kdf();
+    block_on(service.create_vault(UserId("user-1".to_string()), b"pass", kdf))
+        .expect("create vault");
+    let session_id = service
+        .unlock_passphrase(b"pass")
+        .expect("unlock")
+        .session_id;
+    block_on(service.step_up(&session_id, b"pass")).expect("step up");
+    assert!(anchor.seals.load(Ordering::SeqCst) > 0);
+    let marker = storage
+        .get("keyvault", "head_marker")
+        .expect("get")
+        .expect("head marker");
+    assert!(marker.starts_with(b"mo-keyvault-head-marker"));
+
+    // A fresh instance unseals the stored marker up front, so the sync unlock can check it.
+    let mut reopened = block_on(AsyncKeyService::new(
+        SyncStorageAdapter(storage.clone()),
+        FixedClock { now: 42 },
+        SeededEntropy::new(2),
+        test_config(),
+    ))
+    .expect("async service");
+    let unseals = anchor.unseals.load(Ordering::SeqCst);
+    block_on(reopened.set_async_device_anchor(anchor.clone())).expect("set anchor");
+    assert_eq!(anchor.unseals.load(Ordering::SeqCst), unseals + 1);
+    reopened.unlock_passphrase(b"pass").expect("unlock");
+
+    // A sync anchor behind the wrapper opens what the async anchor sealed.
+    let mut wrapped = block_on(AsyncKeyService::new(
+        SyncStorageAdapter(storage),
+        FixedClock { now: 42 },
+        SeededEntropy::new(3),
+        test_config(),
+    ))
+    .expect("async service");
+    block_on(wrapped.set_async_device_anchor(SyncDeviceAnchorAdapter(PrefixAnchor)))
+        .expect("set anchor");
+    wrapped.unlock_passphrase(b"pass").expect("unlock");
+}
@@ -0,0 +1,133 @@
+use mo_key_service_core::adapters::{SessionEvent, SessionEventsAdapter, SyncStorageAdapter};
+use mo_key_service_core::async_key_service::AsyncKeyService;
+use mo_key_service_core::key_service::{KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::timer::TokioTimer;
+use mo_key_service_core::types::{SessionKind, UserId};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+type Service = AsyncKeyService<SyncStorageAdapter<MemoryStorage>, VirtualClock, SeededEntropy>;
+
+struct RecordingSessionEvents {
+    events: Arc<std::sync::Mutex<Vec<SessionEvent>>>,
+}
+
+impl SessionEventsAdapter for RecordingSessionEvents {
+    fn on_session_event(&self, event: &SessionEvent) {
+        self.events.lock().expect("events lock").push(event.clone());
+    }
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn async_key_service_is_send() {
+    assert_send_sync::<Arc<Mutex<Service>>>();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn async_key_service_runs_on_multithreaded_runtime() {
+    let storage = MemoryStorage::new();
+    let service: Service = AsyncKeyService::new(
+        SyncStorageAdapter(storage.clone()),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(3),
+        test_config(),
+    )
+    .await
+    .expect("async service");
+    let shared = Arc::new(Mutex::new(service));
+
+    let create = {
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            shared
+                .lock()
+                .await
+                .create_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+                .await
+        })
+    };
+    create.await.expect("join").expect("create vault");
+
+    let unlock = {
+        let shared = shared.clone();
+        tokio::spawn(async move { shared.lock().await.unlock_passphrase(b"pass") })
+    };
+    let unlock = unlock.await.expect("join").expect("unlock");
+    assert_eq!(unlock.kind, SessionKind::Normal);
+    assert!(!storage.snapshot().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tokio_timer_wakes_the_service_at_session_deadlines() {
+    let clock = VirtualClock::new(1_000);
+    let policy = test_policy()
+        .normal_ttl(40)
+        .step_up_ttl(20)
+        .expiry_warning(20)
+        .build()
+        .expect("policy");
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    let mut service: Service = AsyncKeyService::new(
+        SyncStorageAdapter(MemoryStorage::new()),
+        clock.clone(),
+        SeededEntropy::new(4),
+        config,
+    )
+    .await
+    .expect("async service");
+    let (fired_tx, mut fired) = mpsc::unbounded_channel();
+    service.set_timer_adapter(TokioTimer::new(move || {
+        let _ = fired_tx.send(());
+    }));
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    service.set_session_events_adapter(RecordingSessionEvents {
+        events: events.clone(),
+    });
+    service
+        .create_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .await
+        .expect("create vault");
+    let session_id = service
+        .unlock_passphrase(b"pass")
+        .expect("unlock")
+        .session_id;
+
+    let wait = Duration::from_secs(5);
+    tokio::time::timeout(wait, fired.recv())
+        .await
+        .expect("warning wake-up");
+    clock.set(1_020);
+    service.on_timer();
+    assert_eq!(
+        events.lock().expect("events lock").as_slice(),
+        [SessionEvent::ExpiringSoon {
+            session_id: session_id.clone(),
+            expires_at_ms: 1_040,
+        }]
+    );
+
+    tokio::time::timeout(wait, fired.recv())
+        .await
+        .expect("expiry wake-up");
+    clock.set(1_041);
+    service.on_timer();
+    assert_eq!(
+        events.lock().expect("events lock").last(),
+        Some(&SessionEvent::Expired {
+            session_id: session_id.clone(),
+        })
+    );
+    assert!(matches!(
+        service.renew_session(&session_id),
+        Err(KeyServiceError::SessionInvalid)
+    ));
+}
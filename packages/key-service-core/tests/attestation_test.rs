@@ -0,0 +1,570 @@
+#![cfg(feature = "attestation")]
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use ciborium::value::{Integer, Value};
+use mo_key_service_core::attestation::{
+    device_attestation_challenge, AttestationFormat, AttestationPolicy,
+};
+use mo_key_service_core::cbor::encode_canonical_value;
+use mo_key_service_core::ciphersuite::SignerKeys;
+use mo_key_service_core::formats::{encode_scope_state_v1, ScopeStatePayload, ScopeStateV1};
+use mo_key_service_core::hash::sha256;
+use mo_key_service_core::key_service::{
+    DeviceAttestationResponse, KeyService, KeyServiceConfig, KeyServiceError, SignedFormat,
+};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{DeviceId, ScopeId, SessionId, SigCiphersuiteId, UserId};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{DerSignature, SigningKey};
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::der::asn1::{OctetString, UtcTime};
+use x509_cert::der::oid::{AssociatedOid, ObjectIdentifier};
+use x509_cert::der::{Decode, Encode, EncodeValue, FixedTag, Length, Tag, Writer};
+use x509_cert::ext::pkix::ExtendedKeyUsage;
+use x509_cert::ext::AsExtension;
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::time::{Time, Validity};
+use x509_cert::Certificate;
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+const AAGUID: [u8; 16] = [7u8; 16];
+/// Certificates in these tests expire ten years after the epoch.
+const NOT_AFTER_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+
+struct Authority {
+    key: SigningKey,
+    certificate: Certificate,
+}
+
+impl Authority {
+    fn root(seed: u8, name: &str) -> Self {
+        let key = signing_key(seed);
+        let certificate = issue(Profile::Root, name, &key, &key, Vec::new());
+        Self { key, certificate }
+    }
+
+    fn der(&self) -> Vec<u8> {
+        self.certificate.to_der().unwrap()
+    }
+
+    /// A packed attestation certificate for the authenticator key `key`.
+    fn packed_certificate(&self, key: &SigningKey, aaguid: [u8; 16]) -> Vec<u8> {
+        let profile = self.leaf_profile();
+        let subject = "CN=Authenticator,OU=Authenticator Attestation,O=Example,C=US";
+        issue(
+            profile,
+            subject,
+            key,
+            &self.key,
+            vec![Box::new(Aaguid(aaguid))],
+        )
+        .to_der()
+        .unwrap()
+    }
+
+    /// A TPM attestation identity key certificate: empty subject, AIK key usage.
+    fn aik_certificate(&self, key: &SigningKey) -> Vec<u8> {
+        let eku = ExtendedKeyUsage(vec![ObjectIdentifier::new_unwrap("2.23.133.8.3")]);
+        issue(self.leaf_profile(), "", key, &self.key, vec![Box::new(eku)])
+            .to_der()
+            .unwrap()
+    }
+
+    fn leaf_profile(&self) -> Profile {
+        Profile::Leaf {
+            issuer: self.certificate.tbs_certificate.subject.clone(),
+            enable_key_agreement: false,
+            enable_key_encipherment: false,
+        }
+    }
+}
+
+trait Extension {
+    fn add_to(&self, builder: &mut CertificateBuilder<'_, SigningKey>);
+}
+
+impl<E: AsExtension> Extension for E {
+    fn add_to(&self, builder: &mut CertificateBuilder<'_, SigningKey>) {
+        builder.add_extension(self).unwrap();
+    }
+}
+
+/// `id-fido-gen-ce-aaguid`: the authenticator model, as an OCTET STRING.
+struct Aaguid([u8; 16]);
+
+impl AssociatedOid for Aaguid {
+    const OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.45724.1.1.4");
+}
+
+impl FixedTag for Aaguid {
+    const TAG: Tag = Tag::OctetString;
+}
+
+impl EncodeValue for Aaguid {
+    fn value_len(&self) -> x509_cert::der::Result<Length> {
+        OctetString::new(self.0.to_vec())?.value_len()
+    }
+
+    fn encode_value(&self, writer: &mut impl Writer) -> x509_cert::der::Result<()> {
+        OctetString::new(self.0.to_vec())?.encode_value(writer)
+    }
+}
+
+impl AsExtension for Aaguid {
+    fn critical(&self, _subject: &Name, _extensions: &[x509_cert::ext::Extension]) -> bool {
+        false
+    }
+}
+
+fn issue(
+    profile: Profile,
+    subject: &str,
+    key: &SigningKey,
+    issuer_key: &SigningKey,
+    extensions: Vec<Box<dyn Extension>>,
+) -> Certificate {
+    let validity = Validity {
+        not_before: Time::UtcTime(UtcTime::from_unix_duration(Duration::ZERO).unwrap()),
+        not_after: Time::UtcTime(
+            UtcTime::from_unix_duration(Duration::from_secs(NOT_AFTER_SECS)).unwrap(),
+        ),
+    };
+    let subject = match subject {
+        "" => Name::default(),
+        subject => Name::from_str(subject).unwrap(),
+    };
+    let spki = SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap();
+    let mut builder = CertificateBuilder::new(
+        profile,
+        SerialNumber::from(1u32),
+        validity,
+        subject,
+        spki,
+        issuer_key,
+    )
+    .unwrap();
+    for extension in &extensions {
+        extension.add_to(&mut builder);
+    }
+    builder.build::<DerSignature>().unwrap()
+}
+
+fn signing_key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32].into()).unwrap()
+}
+
+fn int(value: i64) -> Value {
+    Value::Integer(Integer::from(value))
+}
+
+fn text_map(entries: Vec<(&str, Value)>) -> Value {
+    Value::Map(
+        entries
+            .into_iter()
+            .map(|(k, v)| (Value::Text(k.to_string()), v))
+            .collect(),
+    )
+}
+
+fn cose_es256(key: &SigningKey) -> Vec<u8> {
+    let point = key.verifying_key().to_encoded_point(false);
+    let value = Value::Map(vec![
+        (int(1), int(2)),
+        (int(3), int(-7)),
+        (int(-1), int(1)),
+        (int(-2), Value::Bytes(point.x().unwrap().to_vec())),
+        (int(-3), Value::Bytes(point.y().unwrap().to_vec())),
+    ]);
+    encode_canonical_value(&value).unwrap()
+}
+
+/// Authenticator data with an attested ES256 credential for `credential`.
+fn auth_data(credential: &SigningKey, aaguid: [u8; 16]) -> Vec<u8> {
+    let mut data = sha256(b"mo.example").to_vec();
+    data.push(0x41);
+    data.extend_from_slice(&0u32.to_be_bytes());
+    data.extend_from_slice(&aaguid);
+    data.extend_from_slice(&16u16.to_be_bytes());
+    data.extend_from_slice(&[9u8; 16]);
+    data.extend_from_slice(&cose_es256(credential));
+    data
+}
+
+fn attestation_object(fmt: &str, statement: Value, auth_data: Vec<u8>) -> Vec<u8> {
+    encode_canonical_value(&text_map(vec![
+        ("fmt", Value::Text(fmt.to_string())),
+        ("attStmt", statement),
+        ("authData", Value::Bytes(auth_data)),
+    ]))
+    .unwrap()
+}
+
+fn sign(key: &SigningKey, message: &[u8]) -> Vec<u8> {
+    let signature: DerSignature = key.sign(message);
+    signature.to_bytes().to_vec()
+}
+
+/// A packed statement over `challenge`, signed by `attestation_key` with `chain` as `x5c`,
+/// or self attestation when `chain` is empty.
+fn packed(
+    challenge: &[u8; 32],
+    credential: &SigningKey,
+    attestation_key: &SigningKey,
+    chain: Vec<Vec<u8>>,
+) -> Vec<u8> {
+    let data = auth_data(credential, AAGUID);
+    let signature = sign(attestation_key, &[data.as_slice(), challenge].concat());
+    let mut statement = vec![("alg", int(-7)), ("sig", Value::Bytes(signature))];
+    if !chain.is_empty() {
+        statement.push((
+            "x5c",
+            Value::Array(chain.into_iter().map(Value::Bytes).collect()),
+        ));
+    }
+    attestation_object("packed", text_map(statement), data)
+}
+
+fn sized(bytes: &[u8]) -> Vec<u8> {
+    [&(bytes.len() as u16).to_be_bytes()[..], bytes].concat()
+}
+
+/// `TPMT_PUBLIC` for a P-256 key with a SHA-256 name algorithm.
+fn tpm_pub_area(key: &SigningKey) -> Vec<u8> {
+    let point = key.verifying_key().to_encoded_point(false);
+    [
+        &0x0023u16.to_be_bytes()[..],
+        &0x000bu16.to_be_bytes(),
+        &0x0006_0472u32.to_be_bytes(),
+        &sized(&[]),
+        &0x0010u16.to_be_bytes(),
+        &0x0010u16.to_be_bytes(),
+        &0x0003u16.to_be_bytes(),
+        &0x0010u16.to_be_bytes(),
+        &sized(point.x().unwrap()),
+        &sized(point.y().unwrap()),
+    ]
+    .concat()
+}
+
+/// A TPM statement certifying `certified`'s public area, signed by `aik`.
+fn tpm(
+    challenge: &[u8; 32],
+    credential: &SigningKey,
+    certified: &SigningKey,
+    aik: &Authority,
+) -> Vec<u8> {
+    let data = auth_data(credential, AAGUID);
+    let pub_area = tpm_pub_area(certified);
+    let name = [&0x000bu16.to_be_bytes()[..], &sha256(&pub_area)].concat();
+    let extra_data = sha256(&[data.as_slice(), challenge].concat());
+    let cert_info = [
+        &0xff54_4347u32.to_be_bytes()[..],
+        &0x8017u16.to_be_bytes(),
+        &sized(&[1u8; 34]),
+        &sized(&extra_data),
+        &[0u8; 17],
+        &[0u8; 8],
+        &sized(&name),
+        &sized(&name),
+    ]
+    .concat();
+    let signature = sign(&aik.key, &cert_info);
+    let statement = text_map(vec![
+        ("alg", int(-7)),
+        ("sig", Value::Bytes(signature)),
+        ("ver", Value::Text("2.0".to_string())),
+        ("x5c", Value::Array(vec![Value::Bytes(aik.der())])),
+        ("pubArea", Value::Bytes(pub_area)),
+        ("certInfo", Value::Bytes(cert_info)),
+    ]);
+    attestation_object("tpm", statement, data)
+}
+
+fn unlocked(policy: AttestationPolicy, clock: VirtualClock) -> (Core, SessionId, SignerKeys) {
+    let policy = test_policy()
+        .scope_attestation("scope-1", policy)
+        .build()
+        .expect("policy");
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    let mut core = KeyService::new(MemoryStorage::new(), clock, SeededEntropy::new(81), config);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    let signer = core
+        .get_device_public_keys(&session_id)
+        .expect("device keys")
+        .devices
+        .remove(0)
+        .signer;
+    (core, session_id, signer)
+}
+
+fn device() -> DeviceId {
+    DeviceId("device-1".to_string())
+}
+
+fn attest(
+    core: &mut Core,
+    session_id: &SessionId,
+    scope_id: &str,
+    signer: &SignerKeys,
+    attestation_object: &[u8],
+) -> Result<DeviceAttestationResponse, KeyServiceError> {
+    core.ingest_device_attestation(
+        session_id,
+        &ScopeId(scope_id.to_string()),
+        &device(),
+        signer,
+        attestation_object,
+    )
+}
+
+fn ingest_genesis(
+    core: &mut Core,
+    session_id: &SessionId,
+    scope_id: &str,
+    signer: &SignerKeys,
+) -> Result<(), KeyServiceError> {
+    let payload = ScopeStatePayload::Genesis {
+        signer: signer.clone(),
+    };
+    let mut scope_state = ScopeStateV1 {
+        v: 1,
+        scope_id: ScopeId(scope_id.to_string()),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: payload.kind(),
+        payload: payload.to_cbor(),
+        signer_device_id: device(),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    scope_state.signature = core
+        .sign_format(
+            session_id,
+            SignedFormat::ScopeState,
+            &scope_state.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign scope state")
+        .signature;
+    let fingerprint = core
+        .get_device_public_keys(session_id)
+        .expect("device keys")
+        .devices
+        .remove(0)
+        .fingerprint;
+    core.ingest_scope_state(
+        session_id,
+        &encode_scope_state_v1(&scope_state).unwrap(),
+        Some(fingerprint),
+    )
+    .map(|_| ())
+}
+
+fn assert_reason<T: std::fmt::Debug>(result: Result<T, KeyServiceError>, expected: &str) {
+    match result {
+        Err(KeyServiceError::AttestationFailed { reason }) => assert_eq!(reason, expected),
+        other => panic!("expected AttestationFailed({expected}), got {other:?}"),
+    }
+}
+
+#[test]
+fn packed_attestation_gates_new_signers_of_attested_scopes() {
+    let root = Authority::root(1, "CN=Attestation Root,O=Example");
+    let policy = AttestationPolicy {
+        trust_roots: vec![root.der()],
+        ..AttestationPolicy::default()
+    };
+    let (mut core, session_id, signer) = unlocked(policy, VirtualClock::new(1_000));
+    let challenge = device_attestation_challenge(&device(), &signer).unwrap();
+    let credential = signing_key(2);
+    let authenticator = signing_key(3);
+    let statement = packed(
+        &challenge,
+        &credential,
+        &authenticator,
+        vec![root.packed_certificate(&authenticator, AAGUID)],
+    );
+
+    assert_reason(
+        ingest_genesis(&mut core, &session_id, "scope-1", &signer),
+        "device not attested for the scope",
+    );
+    let response =
+        attest(&mut core, &session_id, "scope-1", &signer, &statement).expect("attestation");
+    assert_eq!(response.format, AttestationFormat::Packed);
+    assert_eq!(response.aaguid, AAGUID);
+    assert!(!response.self_attested);
+    ingest_genesis(&mut core, &session_id, "scope-1", &signer).expect("attested signer");
+
+    // Scopes without a policy add signers as before and take no attestations.
+    ingest_genesis(&mut core, &session_id, "scope-2", &signer).expect("open scope");
+    assert_reason(
+        attest(&mut core, &session_id, "scope-2", &signer, &statement),
+        "scope has no attestation policy",
+    );
+}
+
+#[test]
+fn packed_attestation_rejects_unbound_and_untrusted_statements() {
+    let root = Authority::root(1, "CN=Attestation Root,O=Example");
+    let other_root = Authority::root(4, "CN=Other Root,O=Example");
+    let policy = AttestationPolicy {
+        trust_roots: vec![root.der()],
+        aaguids: vec![AAGUID],
+        ..AttestationPolicy::default()
+    };
+    let clock = VirtualClock::new(1_000);
+    let (mut core, session_id, signer) = unlocked(policy, clock.clone());
+    let challenge = device_attestation_challenge(&device(), &signer).unwrap();
+    let credential = signing_key(2);
+    let authenticator = signing_key(3);
+    let chain = vec![root.packed_certificate(&authenticator, AAGUID)];
+
+    // Made for another signing key, the statement does not verify for this one.
+    let mut other_signer = signer.clone();
+    other_signer.ed25519_pub[0] ^= 1;
+    let other_challenge = device_attestation_challenge(&device(), &other_signer).unwrap();
+    let statement = packed(&other_challenge, &credential, &authenticator, chain.clone());
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "attestation signature invalid",
+    );
+
+    let statement = packed(
+        &challenge,
+        &credential,
+        &authenticator,
+        vec![other_root.packed_certificate(&authenticator, AAGUID)],
+    );
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "attestation certificate chain not anchored at a trust root",
+    );
+
+    let statement = packed(
+        &challenge,
+        &credential,
+        &authenticator,
+        vec![root.packed_certificate(&authenticator, [8u8; 16])],
+    );
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "attestation certificate names another authenticator model",
+    );
+
+    let statement = packed(&challenge, &credential, &credential, Vec::new());
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "self attestation not allowed for the scope",
+    );
+
+    let statement = packed(&challenge, &credential, &authenticator, chain);
+    clock.set((NOT_AFTER_SECS + 1) * 1000);
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "attestation certificate outside its validity period",
+    );
+}
+
+#[test]
+fn self_attestation_and_model_allowlist() {
+    let policy = AttestationPolicy {
+        formats: vec![AttestationFormat::Packed],
+        allow_self_attestation: true,
+        aaguids: vec![[8u8; 16]],
+        ..AttestationPolicy::default()
+    };
+    let (mut core, session_id, signer) = unlocked(policy, VirtualClock::new(1_000));
+    let challenge = device_attestation_challenge(&device(), &signer).unwrap();
+    let credential = signing_key(2);
+    let statement = packed(&challenge, &credential, &credential, Vec::new());
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "authenticator model not allowed for the scope",
+    );
+
+    let policy = AttestationPolicy {
+        formats: vec![AttestationFormat::Packed],
+        allow_self_attestation: true,
+        ..AttestationPolicy::default()
+    };
+    let (mut core, session_id, signer) = unlocked(policy, VirtualClock::new(1_000));
+    let challenge = device_attestation_challenge(&device(), &signer).unwrap();
+    let statement = packed(&challenge, &credential, &credential, Vec::new());
+    let response =
+        attest(&mut core, &session_id, "scope-1", &signer, &statement).expect("self attestation");
+    assert!(response.self_attested);
+    ingest_genesis(&mut core, &session_id, "scope-1", &signer).expect("attested signer");
+
+    let aik = Authority::root(5, "CN=TPM Root,O=Example");
+    let statement = tpm(&challenge, &credential, &credential, &aik);
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "attestation format not allowed for the scope",
+    );
+
+    let empty = AttestationPolicy {
+        formats: Vec::new(),
+        ..AttestationPolicy::default()
+    };
+    assert!(test_policy()
+        .scope_attestation("scope-1", empty)
+        .build()
+        .is_err());
+    assert!(test_policy()
+        .scope_attestation("scope-1", AttestationPolicy::default())
+        .build()
+        .is_err());
+}
+
+#[test]
+fn tpm_attestation_certifies_the_credential_key() {
+    let root = Authority::root(1, "CN=TPM Root,O=Example");
+    let aik_key = signing_key(6);
+    let aik = Authority {
+        certificate: Certificate::from_der(&root.aik_certificate(&aik_key)).unwrap(),
+        key: aik_key,
+    };
+    let policy = AttestationPolicy {
+        trust_roots: vec![root.der()],
+        ..AttestationPolicy::default()
+    };
+    let (mut core, session_id, signer) = unlocked(policy, VirtualClock::new(1_000));
+    let challenge = device_attestation_challenge(&device(), &signer).unwrap();
+    let credential = signing_key(2);
+
+    let statement = tpm(&challenge, &credential, &signing_key(7), &aik);
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "tpm key differs from the credential key",
+    );
+    let mut other_signer = signer.clone();
+    other_signer.mldsa_pub[0] ^= 1;
+    let other_challenge = device_attestation_challenge(&device(), &other_signer).unwrap();
+    let statement = tpm(&other_challenge, &credential, &credential, &aik);
+    assert_reason(
+        attest(&mut core, &session_id, "scope-1", &signer, &statement),
+        "tpm attestation not bound to the device key",
+    );
+
+    let statement = tpm(&challenge, &credential, &credential, &aik);
+    let response =
+        attest(&mut core, &session_id, "scope-1", &signer, &statement).expect("tpm attestation");
+    assert_eq!(response.format, AttestationFormat::Tpm);
+    ingest_genesis(&mut core, &session_id, "scope-1", &signer).expect("attested signer");
+}
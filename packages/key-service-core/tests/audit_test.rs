@@ -0,0 +1,150 @@
+use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
+use mo_key_service_core::audit::{
+    audit_entry_key, decode_audit_entry_v1, encode_audit_entry_v1, AuditEventKind, AUDIT_NAMESPACE,
+};
+use mo_key_service_core::key_service::KeyService;
+use mo_key_service_core::testkit::{fast_kdf, test_config};
+use mo_key_service_core::types::{DeviceId, SessionId, UserId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type Data = Rc<RefCell<HashMap<(String, String), Vec<u8>>>>;
+
+#[derive(Clone, Default)]
+struct MemStorage {
+    data: Data,
+}
+
+impl StorageAdapter for MemStorage {
+    type Error = String;
+
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self
+            .data
+            .borrow()
+            .get(&(namespace.to_string(), key.to_string()))
+            .cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+        self.data
+            .borrow_mut()
+            .insert((namespace.to_string(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+    fn list_since(
+        &self,
+        namespace: &str,
+        cursor: &str,
+        _limit: usize,
+    ) -> Result<(Vec<(String, Vec<u8>)>, String), Self::Error> {
+        let mut out = Vec::new();
+        for ((ns, key), value) in self.data.borrow().iter() {
+            if ns == namespace && key.as_str() >= cursor {
+                out.push((key.clone(), value.clone()));
+            }
+        }
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok((out, "".to_string()))
+    }
+}
+
+struct FixedClock {
+    now: u64,
+}
+
+impl ClockAdapter for FixedClock {
+    fn now_ms(&self) -> u64 {
+        self.now
+    }
+}
+
+struct FixedEntropy {
+    counter: Cell<u8>,
+}
+
+impl EntropyAdapter for FixedEntropy {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let value = self.counter.get();
+        self.counter.set(value.wrapping_add(1));
+        vec![value; len]
+    }
+}
+
+fn make_service(storage: &MemStorage) -> KeyService<MemStorage, FixedClock, FixedEntropy> {
+    KeyService::new(
+        storage.clone(),
+        FixedClock { now: 1_000 },
+        FixedEntropy {
+            counter: Cell::new(3),
+        },
+        test_config(),
+    )
+}
+
+fn audited_service(
+    storage: &MemStorage,
+) -> (KeyService<MemStorage, FixedClock, FixedEntropy>, SessionId) {
+    let mut ks = make_service(storage);
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    ks.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    ks.step_up(&session_id, b"pass").expect("step up");
+    ks.export_keyvault(&session_id).expect("export");
+    (ks, session_id)
+}
+
+#[test]
+fn sensitive_operations_are_chained_and_signed() {
+    let storage = MemStorage::default();
+    let (mut ks, session_id) = audited_service(&storage);
+
+    let entries = ks.read_audit_log(&session_id).expect("read");
+    let kinds: Vec<_> = entries.iter().map(|entry| entry.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            AuditEventKind::Unlock,
+            AuditEventKind::StepUp,
+            AuditEventKind::ExportKeyVault
+        ]
+    );
+    // The unlock entry predates the device key; later entries are signed by it.
+    assert!(entries[0].signature.is_none());
+    assert_eq!(
+        entries[1].signer_device_id,
+        Some(DeviceId("device-1".to_string()))
+    );
+    assert_eq!(entries[1].prev_hash, entries[0].entry_hash().unwrap());
+
+    let report = ks.verify_audit_log(&session_id).expect("verify");
+    assert!(report.ok);
+    assert_eq!(report.entry_count, 3);
+    assert_eq!(report.unsigned_count, 1);
+}
+
+#[test]
+fn tampered_entry_fails_verification() {
+    let storage = MemStorage::default();
+    let (mut ks, session_id) = audited_service(&storage);
+
+    let key = audit_entry_key(2);
+    let mut entry = decode_audit_entry_v1(&storage.get(AUDIT_NAMESPACE, &key).unwrap().unwrap())
+        .expect("decode");
+    entry.detail = "forged".to_string();
+    storage
+        .put(
+            AUDIT_NAMESPACE,
+            &key,
+            &encode_audit_entry_v1(&entry).unwrap(),
+        )
+        .unwrap();
+
+    let report = ks.verify_audit_log(&session_id).expect("verify");
+    assert!(!report.ok);
+    assert_eq!(report.first_invalid_seq, Some(2));
+}
@@ -0,0 +1,95 @@
+use mo_key_service_core::cancel::CancellationToken;
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{ScopeEpoch, ScopeId, SessionId, UserId};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+type Service = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+/// A vault with a few scope-key records, plus the unlocked session that wrote them.
+fn vault_with_records(storage: &MemoryStorage) -> (Service, SessionId) {
+    let mut ks = KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(7),
+        test_config(),
+    );
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    for i in 0..3u8 {
+        ks.persist_scope_key(
+            &session_id,
+            &ScopeId(format!("scope-{i}")),
+            ScopeEpoch(1),
+            &[i; 32],
+        )
+        .expect("persist scope key");
+    }
+    (ks, session_id)
+}
+
+#[test]
+fn cancelled_unlock_stops_before_creating_a_session() {
+    let storage = MemoryStorage::new();
+    let (mut ks, _) = vault_with_records(&storage);
+    let writes = storage.write_count();
+
+    let token = CancellationToken::new();
+    token.clone().cancel();
+    assert!(token.is_cancelled());
+    let err = ks
+        .unlock_passphrase_cancellable(b"pass", &token)
+        .expect_err("cancelled before the kdf");
+    assert!(matches!(err, KeyServiceError::Cancelled));
+    assert_eq!(err.code(), "Cancelled");
+
+    // Checks 1 and 2 bracket the KDF; the third is the first record of the vault.
+    let probes = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&probes);
+    let token = CancellationToken::from_probe(move || counter.fetch_add(1, Ordering::SeqCst) >= 2);
+    let err = ks
+        .unlock_passphrase_cancellable(b"pass", &token)
+        .expect_err("cancelled while loading records");
+    assert!(matches!(err, KeyServiceError::Cancelled));
+    assert_eq!(probes.load(Ordering::SeqCst), 3);
+    assert_eq!(storage.write_count(), writes);
+
+    let unlock = ks
+        .unlock_passphrase_cancellable(b"pass", &CancellationToken::new())
+        .expect("uncancelled unlock");
+    ks.persist_scope_key(
+        &unlock.session_id,
+        &ScopeId("scope-after".to_string()),
+        ScopeEpoch(1),
+        &[9u8; 32],
+    )
+    .expect("session usable");
+}
+
+#[test]
+fn cancelled_import_leaves_storage_untouched() {
+    let storage = MemoryStorage::new();
+    let (mut ks, session_id) = vault_with_records(&storage);
+    ks.step_up(&session_id, b"pass").expect("step up");
+    let blob = ks.export_keyvault(&session_id).expect("export");
+    let before = storage.snapshot();
+    let writes = storage.write_count();
+
+    let probes = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&probes);
+    let token = CancellationToken::from_probe(move || counter.fetch_add(1, Ordering::SeqCst) >= 1);
+    let err = ks
+        .import_keyvault_cancellable(&session_id, &blob, &token)
+        .expect_err("cancelled on the second record");
+    assert!(matches!(err, KeyServiceError::Cancelled));
+    assert_eq!(probes.load(Ordering::SeqCst), 2);
+    assert_eq!(storage.write_count(), writes);
+    assert_eq!(storage.snapshot(), before);
+
+    ks.import_keyvault_cancellable(&session_id, &blob, &CancellationToken::new())
+        .expect("uncancelled import");
+}
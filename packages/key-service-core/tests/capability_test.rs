@@ -0,0 +1,148 @@
+use mo_key_service_core::ciphersuite::generate_device_signing_keypair;
+use mo_key_service_core::formats::{decode_capability_token_v1, encode_capability_token_v1};
+use mo_key_service_core::key_service::{
+    CapabilityRequest, KeyService, KeyServiceError, WrappedKeyKind,
+};
+use mo_key_service_core::testkit::{
+    fast_kdf, ingest_scope_and_grant, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    CapabilityOps, KeyHandle, ScopeEpoch, ScopeId, SessionId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn open_resource(core: &mut Core, session_id: &SessionId, grant: &[u8]) -> KeyHandle {
+    let scope_handle = core
+        .open_scope(session_id, ScopeId("scope-1".to_string()), ScopeEpoch(1))
+        .expect("open scope");
+    core.open_resource(session_id, &scope_handle.scope_key_handle, grant)
+        .expect("open resource")
+        .resource_key_handle
+}
+
+fn decrypt_only(aad_prefix: &[u8], ttl_ms: u64) -> CapabilityRequest {
+    CapabilityRequest {
+        aad_prefix: aad_prefix.to_vec(),
+        ops: CapabilityOps {
+            encrypt: false,
+            decrypt: true,
+        },
+        ttl_ms,
+    }
+}
+
+#[test]
+fn capability_tokens_hand_off_attenuated_access() {
+    let clock = VirtualClock::new(1_000);
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        clock.clone(),
+        SeededEntropy::new(3),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let owner = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let component = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let signer = generate_device_signing_keypair().expect("signer");
+    let grant = ingest_scope_and_grant(&mut core, &owner, &signer);
+    let handle = open_resource(&mut core, &owner, &grant);
+    let ciphertext = core
+        .encrypt(&owner, &handle, b"doc:1|body", b"hello")
+        .expect("encrypt")
+        .ciphertext;
+
+    let minted = core
+        .mint_capability(&owner, &handle, &decrypt_only(b"doc:1|", 1_000))
+        .expect("mint");
+    assert_eq!(minted.expires_at_ms, 2_000);
+    let redeemed = core
+        .redeem_capability(&component, &minted.token)
+        .expect("redeem");
+    let limited = redeemed.resource_key_handle;
+    assert!(!redeemed.ops.encrypt && redeemed.ops.decrypt);
+    let plaintext = core
+        .decrypt(&component, &limited, b"doc:1|body", &ciphertext)
+        .expect("decrypt within the prefix")
+        .plaintext;
+    assert_eq!(plaintext, b"hello");
+    assert!(matches!(
+        core.encrypt(&component, &limited, b"doc:1|body", b"x"),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    assert!(matches!(
+        core.decrypt(&component, &limited, b"doc:2|body", &ciphertext),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+
+    // Re-minting from a limited handle can only narrow it.
+    let widened = CapabilityRequest {
+        ops: CapabilityOps::ALL,
+        ..decrypt_only(b"doc:1|", 1_000)
+    };
+    assert!(matches!(
+        core.mint_capability(&component, &limited, &widened),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    assert!(matches!(
+        core.mint_capability(&component, &limited, &decrypt_only(b"doc:", 1_000)),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    let narrowed = core
+        .mint_capability(&component, &limited, &decrypt_only(b"doc:1|body", 5_000))
+        .expect("narrowed mint");
+    assert_eq!(narrowed.expires_at_ms, 2_000);
+
+    // The wrapped key is bound to the token's limits.
+    let mut tampered = decode_capability_token_v1(&minted.token).expect("decode token");
+    tampered.ops = CapabilityOps::ALL;
+    let tampered = encode_capability_token_v1(&tampered).expect("encode token");
+    assert!(matches!(
+        core.redeem_capability(&component, &tampered),
+        Err(KeyServiceError::KeyUnwrapFailed {
+            kind: WrappedKeyKind::CapabilityToken
+        })
+    ));
+
+    clock.set(2_001);
+    assert!(matches!(
+        core.decrypt(&component, &limited, b"doc:1|body", &ciphertext),
+        Err(KeyServiceError::CapabilityExpired)
+    ));
+    assert!(matches!(
+        core.redeem_capability(&component, &minted.token),
+        Err(KeyServiceError::CapabilityExpired)
+    ));
+    core.decrypt(&owner, &handle, b"doc:1|body", &ciphertext)
+        .expect("the source handle is unrestricted");
+}
+
+#[test]
+fn capability_tokens_do_not_cross_vaults() {
+    let make = |seed| {
+        let mut core: Core = KeyService::new(
+            MemoryStorage::new(),
+            VirtualClock::new(1_000),
+            SeededEntropy::new(seed),
+            test_config(),
+        );
+        core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+            .expect("create vault");
+        let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+        (core, session_id)
+    };
+    let (mut source, owner) = make(3);
+    let (mut other, stranger) = make(4);
+    let signer = generate_device_signing_keypair().expect("signer");
+    let grant = ingest_scope_and_grant(&mut source, &owner, &signer);
+    let handle = open_resource(&mut source, &owner, &grant);
+    let minted = source
+        .mint_capability(&owner, &handle, &decrypt_only(b"", 1_000))
+        .expect("mint");
+
+    assert!(matches!(
+        other.redeem_capability(&stranger, &minted.token),
+        Err(KeyServiceError::VaultKeyMismatch)
+    ));
+}
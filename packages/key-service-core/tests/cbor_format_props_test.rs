@@ -0,0 +1,370 @@
+use ciborium::value::Value;
+use mo_key_service_core::cbor::{
+    check_canonical, decode_canonical_value, encode_canonical_value, CborLimits,
+};
+use mo_key_service_core::crypto::{KdfMinimums, KdfParams};
+use mo_key_service_core::formats::{
+    decode_key_envelope_v1, decode_keyvault_header_v1, decode_keyvault_record_container_v1,
+    decode_keyvault_record_plain_v1, decode_resource_grant_v1, decode_scope_state_v1,
+    encode_key_envelope_v1, encode_keyvault_header_v1, encode_keyvault_record_container_v1,
+    encode_keyvault_record_plain_v1, encode_resource_grant_v1, encode_scope_state_v1,
+    KeyEnvelopeV1, KeyVaultHeaderV1, KeyVaultRecordContainerV1, KeyVaultRecordPlainV1,
+    KeyVaultRecordProvenance, ResourceGrantV1, ScopeStateV1, VaultKeyWrapV1,
+};
+use mo_key_service_core::types::{
+    AeadId, DeviceId, KemCiphersuiteId, KeyWrapAlg, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId,
+    SigCiphersuiteId, UserId,
+};
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+
+fn arb_bytes(max: usize) -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..max)
+}
+
+fn arb_fixed(len: usize) -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), len)
+}
+
+fn arb_text() -> impl Strategy<Value = String> {
+    ".{0,16}"
+}
+
+fn arb_map_key() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        any::<u64>().prop_map(|n| Value::Integer(n.into())),
+        arb_text().prop_map(Value::Text),
+    ]
+}
+
+/// Everything the wire formats use: no floats, no tags.
+fn arb_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        any::<u64>().prop_map(|n| Value::Integer(n.into())),
+        any::<i64>().prop_map(|n| Value::Integer(n.into())),
+        arb_bytes(32).prop_map(Value::Bytes),
+        arb_text().prop_map(Value::Text),
+        any::<bool>().prop_map(Value::Bool),
+        Just(Value::Null),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..8).prop_map(Value::Array),
+            vec((arb_map_key(), inner), 0..8).prop_map(Value::Map),
+        ]
+    })
+}
+
+fn arb_container() -> impl Strategy<Value = KeyVaultRecordContainerV1> {
+    (
+        any::<u64>(),
+        any::<u64>(),
+        arb_fixed(32),
+        arb_text(),
+        arb_fixed(12),
+        arb_bytes(64),
+    )
+        .prop_map(
+            |(v, seq, prev_hash, record_id, nonce, ct)| KeyVaultRecordContainerV1 {
+                v,
+                seq,
+                prev_hash,
+                record_id,
+                nonce,
+                ct,
+            },
+        )
+}
+
+fn arb_header() -> impl Strategy<Value = KeyVaultHeaderV1> {
+    (
+        (any::<u64>(), arb_text(), arb_text()),
+        (
+            arb_text(),
+            arb_bytes(32),
+            any::<u32>(),
+            any::<u32>(),
+            any::<u32>(),
+        ),
+        vec(arb_container(), 0..4),
+        (arb_fixed(12), arb_bytes(64), option::of(arb_fixed(32))),
+    )
+        .prop_map(
+            |(
+                (v, vault_id, user_id),
+                (kdf_id, salt, memory_kib, iterations, parallelism),
+                records,
+                (nonce, ct, commitment),
+            )| KeyVaultHeaderV1 {
+                v,
+                vault_id,
+                user_id,
+                kdf: KdfParams {
+                    id: kdf_id,
+                    salt,
+                    memory_kib,
+                    iterations,
+                    parallelism,
+                },
+                aead: AeadId::Aead1,
+                records,
+                vault_key_wrap: VaultKeyWrapV1 {
+                    aead: AeadId::Aead1,
+                    nonce,
+                    ct,
+                    commitment,
+                    alg: KeyWrapAlg::AesGcm,
+                },
+                kms_key_id: None,
+                device_shares: None,
+                passphrase_check: None,
+            },
+        )
+}
+
+fn arb_scope_state() -> impl Strategy<Value = ScopeStateV1> {
+    (
+        (any::<u64>(), arb_text(), any::<u64>(), arb_fixed(32)),
+        (any::<u64>(), any::<u64>(), arb_value()),
+        (arb_text(), arb_bytes(64)),
+    )
+        .prop_map(
+            |(
+                (v, scope_id, scope_state_seq, prev_hash),
+                (scope_epoch, kind, payload),
+                (signer, signature),
+            )| ScopeStateV1 {
+                v,
+                scope_id: ScopeId(scope_id),
+                scope_state_seq,
+                prev_hash,
+                scope_epoch,
+                kind,
+                payload,
+                signer_device_id: DeviceId(signer),
+                sig_suite: SigCiphersuiteId::HybridSig1,
+                signature,
+            },
+        )
+}
+
+fn arb_grant() -> impl Strategy<Value = ResourceGrantV1> {
+    (
+        (any::<u64>(), arb_text(), arb_text(), any::<u64>()),
+        (arb_fixed(32), arb_fixed(32), any::<u64>()),
+        (arb_text(), arb_text(), option::of(arb_value())),
+        (arb_fixed(12), arb_bytes(64), arb_text(), arb_bytes(64)),
+    )
+        .prop_map(
+            |(
+                (v, grant_id, scope_id, grant_seq),
+                (prev_hash, scope_state_ref, scope_epoch),
+                (resource_id, resource_key_id, policy),
+                (nonce, wrapped_key, signer, signature),
+            )| ResourceGrantV1 {
+                v,
+                grant_id,
+                scope_id: ScopeId(scope_id),
+                grant_seq,
+                prev_hash,
+                scope_state_ref,
+                scope_epoch,
+                resource_id: ResourceId(resource_id),
+                resource_key_id: ResourceKeyId(resource_key_id),
+                policy,
+                aead: AeadId::Aead1,
+                nonce,
+                wrapped_key,
+                signer_device_id: DeviceId(signer),
+                sig_suite: SigCiphersuiteId::HybridSig1,
+                signature,
+            },
+        )
+}
+
+fn arb_envelope() -> impl Strategy<Value = KeyEnvelopeV1> {
+    (
+        (
+            any::<u64>(),
+            arb_text(),
+            arb_text(),
+            any::<u64>(),
+            arb_text(),
+        ),
+        (arb_fixed(32), arb_bytes(64), arb_fixed(12), arb_bytes(64)),
+        (arb_text(), arb_bytes(64), option::of(arb_fixed(32))),
+    )
+        .prop_map(
+            |(
+                (v, envelope_id, scope_id, scope_epoch, recipient),
+                (scope_state_ref, enc, nonce, wrapped_scope_key),
+                (signer, signature, fingerprint),
+            )| KeyEnvelopeV1 {
+                v,
+                envelope_id,
+                scope_id: ScopeId(scope_id),
+                scope_epoch: ScopeEpoch(scope_epoch),
+                recipient_user_id: UserId(recipient),
+                scope_state_ref,
+                kem: KemCiphersuiteId::HybridKem1,
+                aead: AeadId::Aead1,
+                enc,
+                nonce,
+                wrapped_scope_key,
+                signer_device_id: DeviceId(signer),
+                sig_suite: SigCiphersuiteId::HybridSig1,
+                signature,
+                recipient_uk_pub_fingerprint: fingerprint,
+                prekey_id: None,
+            },
+        )
+}
+
+/// Runs every decoder; only panics matter, errors are expected.
+fn decode_all(bytes: &[u8]) {
+    let _ = decode_canonical_value(bytes, &CborLimits::default());
+    let _ = decode_keyvault_header_v1(bytes, &KdfMinimums::NONE);
+    let _ = decode_keyvault_record_container_v1(bytes);
+    let _ = decode_keyvault_record_plain_v1(bytes);
+    let _ = decode_scope_state_v1(bytes);
+    let _ = decode_resource_grant_v1(bytes);
+    let _ = decode_key_envelope_v1(bytes);
+}
+
+proptest! {
+    #[test]
+    fn canonical_value_round_trips(value in arb_value()) {
+        let bytes = encode_canonical_value(&value).expect("encode");
+        let decoded = decode_canonical_value(&bytes, &CborLimits::default()).expect("decode");
+        prop_assert_eq!(encode_canonical_value(&decoded).expect("re-encode"), bytes);
+    }
+
+    #[test]
+    fn canonicalization_is_idempotent(value in arb_value()) {
+        let once = encode_canonical_value(&value).expect("encode");
+        let reparsed: Value = ciborium::de::from_reader(once.as_slice()).expect("parse");
+        prop_assert_eq!(encode_canonical_value(&reparsed).expect("encode"), once);
+    }
+
+    #[test]
+    fn non_canonical_map_order_is_rejected(a in any::<u64>(), b in any::<u64>()) {
+        prop_assume!(a != b);
+        let (lo, hi) = (a.min(b), a.max(b));
+        let reversed = Value::Map(vec![
+            (Value::Integer(hi.into()), Value::Null),
+            (Value::Integer(lo.into()), Value::Null),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&reversed, &mut bytes).expect("encode");
+        prop_assert!(decode_canonical_value(&bytes, &CborLimits::default()).is_err());
+    }
+
+    #[test]
+    fn record_container_round_trips(container in arb_container()) {
+        let bytes = encode_keyvault_record_container_v1(&container).expect("encode");
+        let decoded = decode_keyvault_record_container_v1(&bytes).expect("decode");
+        prop_assert_eq!(encode_keyvault_record_container_v1(&decoded).expect("re-encode"), bytes);
+    }
+
+    #[test]
+    fn record_plain_round_trips(
+        record_id in arb_text(),
+        kind in any::<u64>(),
+        payload in arb_value(),
+        provenance in option::of((any::<u64>(), option::of(arb_text()))),
+    ) {
+        let provenance = provenance.map(|(created_at_ms, origin)| KeyVaultRecordProvenance {
+            created_at_ms,
+            origin_device_id: origin.map(DeviceId),
+        });
+        let record = KeyVaultRecordPlainV1 { record_id, kind, payload, provenance };
+        let bytes = encode_keyvault_record_plain_v1(&record).expect("encode");
+        let decoded = decode_keyvault_record_plain_v1(&bytes).expect("decode");
+        prop_assert_eq!(encode_keyvault_record_plain_v1(&decoded).expect("re-encode"), bytes);
+    }
+
+    #[test]
+    fn header_round_trips(header in arb_header()) {
+        let bytes = encode_keyvault_header_v1(&header).expect("encode");
+        let decoded = decode_keyvault_header_v1(&bytes, &KdfMinimums::NONE).expect("decode");
+        prop_assert_eq!(encode_keyvault_header_v1(&decoded).expect("re-encode"), bytes);
+    }
+
+    #[test]
+    fn scope_state_round_trips(scope_state in arb_scope_state()) {
+        let bytes = encode_scope_state_v1(&scope_state).expect("encode");
+        let decoded = decode_scope_state_v1(&bytes).expect("decode");
+        prop_assert_eq!(encode_scope_state_v1(&decoded).expect("re-encode"), bytes);
+    }
+
+    #[test]
+    fn resource_grant_round_trips(grant in arb_grant()) {
+        let bytes = encode_resource_grant_v1(&grant).expect("encode");
+        let decoded = decode_resource_grant_v1(&bytes).expect("decode");
+        prop_assert_eq!(encode_resource_grant_v1(&decoded).expect("re-encode"), bytes);
+    }
+
+    #[test]
+    fn key_envelope_round_trips(envelope in arb_envelope()) {
+        let bytes = encode_key_envelope_v1(&envelope).expect("encode");
+        let decoded = decode_key_envelope_v1(&bytes).expect("decode");
+        prop_assert_eq!(encode_key_envelope_v1(&decoded).expect("re-encode"), bytes);
+    }
+
+    #[test]
+    fn decoders_never_panic_on_arbitrary_bytes(bytes in arb_bytes(512)) {
+        decode_all(&bytes);
+    }
+
+    #[test]
+    fn decoders_never_panic_on_mutated_encodings(
+        header in arb_header(),
+        index in any::<prop::sample::Index>(),
+        flip in 1u8..,
+        cut in any::<prop::sample::Index>(),
+    ) {
+        let bytes = encode_keyvault_header_v1(&header).expect("encode");
+        let mut flipped = bytes.clone();
+        let at = index.index(flipped.len());
+        flipped[at] ^= flip;
+        decode_all(&flipped);
+        decode_all(&bytes[..cut.index(bytes.len())]);
+    }
+
+    #[test]
+    fn checker_accepts_only_the_canonical_encoding(
+        value in arb_value(),
+        index in any::<prop::sample::Index>(),
+        flip in 1u8..,
+    ) {
+        let bytes = encode_canonical_value(&value).expect("encode");
+        prop_assert!(check_canonical(&bytes, &CborLimits::default()).is_ok());
+        let mut flipped = bytes.clone();
+        let at = index.index(flipped.len());
+        flipped[at] ^= flip;
+        if check_canonical(&flipped, &CborLimits::default()).is_ok() {
+            if let Ok(parsed) = ciborium::de::from_reader::<Value, _>(flipped.as_slice()) {
+                prop_assert_eq!(encode_canonical_value(&parsed).expect("encode"), flipped);
+            }
+        }
+    }
+
+    #[test]
+    fn checker_agrees_with_the_encoder_on_float_width(float in any::<f64>()) {
+        let canonical = encode_canonical_value(&Value::Float(float)).expect("encode");
+        prop_assert!(check_canonical(&canonical, &CborLimits::default()).is_ok());
+        let wide = [&[0xfb][..], &float.to_bits().to_be_bytes()].concat();
+        prop_assert_eq!(
+            check_canonical(&wide, &CborLimits::default()).is_ok(),
+            wide == canonical
+        );
+        let narrow = [&[0xfa][..], &(float as f32).to_bits().to_be_bytes()].concat();
+        if f64::from(float as f32) == float || float.is_nan() {
+            prop_assert_eq!(
+                check_canonical(&narrow, &CborLimits::default()).is_ok(),
+                narrow == canonical
+            );
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use ciborium::value::Value;
+use mo_key_service_core::cbor::{
+    check_canonical, decode_canonical_value, decode_cbor_value, encode_canonical_value, CborLimits,
+    CborStrictness,
+};
+
+fn violation(bytes: &[u8]) -> String {
+    check_canonical(bytes, &CborLimits::default())
+        .expect_err("non-canonical")
+        .to_string()
+}
+
+#[test]
+fn violations_name_the_problem_and_offset() {
+    // {1: 0, 0: 0}
+    assert_eq!(
+        violation(&[0xa2, 0x01, 0x00, 0x00, 0x00]),
+        "cbor error: map key out of order at byte 3"
+    );
+    // {0: 0, 0: 1}
+    assert_eq!(
+        violation(&[0xa2, 0x00, 0x00, 0x00, 0x01]),
+        "cbor error: duplicate map key at byte 3"
+    );
+    // [h'01' in an indefinite-length byte string]
+    assert_eq!(
+        violation(&[0x81, 0x5f, 0x41, 0x01, 0xff]),
+        "cbor error: indefinite-length item at byte 1"
+    );
+    // [1] with the length in a one-byte argument
+    assert_eq!(
+        violation(&[0x98, 0x01, 0x01]),
+        "cbor error: non-minimal head at byte 0"
+    );
+    // 1.0 as a single-precision float
+    assert_eq!(
+        violation(&[0xfa, 0x3f, 0x80, 0x00, 0x00]),
+        "cbor error: non-shortest float at byte 0"
+    );
+    // undefined
+    assert_eq!(
+        violation(&[0xf7]),
+        "cbor error: unsupported simple value at byte 0"
+    );
+    // 2(h'01'): a bignum that fits a plain integer
+    assert_eq!(
+        violation(&[0xc2, 0x41, 0x01]),
+        "cbor error: non-canonical bignum at byte 0"
+    );
+    assert_eq!(
+        violation(&[0x82, 0x01]),
+        "cbor error: truncated cbor at byte 2"
+    );
+}
+
+#[test]
+fn canonical_encodings_pass_the_checker() {
+    let value = Value::Map(vec![
+        (Value::Integer(10.into()), Value::Float(1.0)),
+        (Value::Integer(2.into()), Value::Float(1.0e10)),
+        (Value::Text("a".into()), Value::Float(0.1)),
+        (Value::Integer(300.into()), Value::Float(f64::NAN)),
+        (
+            Value::Integer(u64::MAX.into()),
+            Value::Array(vec![Value::Bool(true), Value::Null]),
+        ),
+    ]);
+    let bytes = encode_canonical_value(&value).expect("encode");
+    check_canonical(&bytes, &CborLimits::default()).expect("canonical");
+    decode_canonical_value(&bytes, &CborLimits::default()).expect("decode");
+}
+
+#[test]
+fn lenient_mode_accepts_well_formed_non_canonical_input() {
+    // {1: h'01' (indefinite), 0: 0}
+    let bytes = [0xa2, 0x01, 0x5f, 0x41, 0x01, 0xff, 0x00, 0x00];
+    assert!(decode_canonical_value(&bytes, &CborLimits::default()).is_err());
+    let value = decode_cbor_value(&bytes, &CborLimits::default(), CborStrictness::Lenient)
+        .expect("lenient");
+    assert_eq!(
+        value,
+        Value::Map(vec![
+            (Value::Integer(1.into()), Value::Bytes(vec![1])),
+            (Value::Integer(0.into()), Value::Integer(0.into())),
+        ])
+    );
+}
+
+#[test]
+fn lenient_mode_still_enforces_limits_and_a_single_item() {
+    let limits = CborLimits {
+        max_text_bytes: 2,
+        ..CborLimits::default()
+    };
+    // ["abc"] with a non-minimal array head
+    let long_text = [0x98, 0x01, 0x63, b'a', b'b', b'c'];
+    assert_eq!(
+        decode_cbor_value(&long_text, &limits, CborStrictness::Lenient)
+            .expect_err("text limit")
+            .to_string(),
+        "cbor error: [0]: cbor text too large"
+    );
+    assert_eq!(
+        decode_cbor_value(&[0x01, 0x02], &limits, CborStrictness::Lenient)
+            .expect_err("two items")
+            .to_string(),
+        "cbor error: trailing bytes at byte 1"
+    );
+}
@@ -0,0 +1,323 @@
+use mo_key_service_core::cgka::{CgkaCommitContext, CgkaGroup};
+use mo_key_service_core::ciphersuite::generate_user_keypair;
+use mo_key_service_core::formats::{
+    decode_cgka_commit_v1, encode_cgka_commit_v1, encode_scope_state_v1, CgkaMemberV1,
+    ScopeStatePayload, ScopeStateV1,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError, SignedFormat};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    DeviceId, ScopeEpoch, ScopeId, SessionId, SigCiphersuiteId, UserId,
+};
+use mo_key_service_core::CborLimits;
+use mo_key_service_core::HybridKemRecipient;
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn scope() -> ScopeId {
+    ScopeId("scope-1".to_string())
+}
+
+fn member(user: &str) -> (CgkaMemberV1, HybridKemRecipient) {
+    let (keys, _) = generate_user_keypair().unwrap();
+    let member = CgkaMemberV1 {
+        user_id: UserId(user.to_string()),
+        public_bytes: keys.public_bytes.clone(),
+    };
+    (member, keys)
+}
+
+fn context() -> CgkaCommitContext {
+    CgkaCommitContext {
+        scope_state_ref: vec![0u8; 32],
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+    }
+}
+
+#[test]
+fn members_derive_the_same_scope_key_across_adds_and_removes() {
+    let (alice, alice_keys) = member("alice");
+    let (bob, bob_keys) = member("bob");
+    let (carol, carol_keys) = member("carol");
+    let (dave, dave_keys) = member("dave");
+    let (alice_group, _) = CgkaGroup::create(scope(), 1, &alice, &[7u8; 32]).unwrap();
+
+    let added = alice_group
+        .commit(&[], &[bob.clone(), carol.clone()], &[8u8; 32], context())
+        .unwrap();
+    assert_eq!(added.group.epoch, 2);
+    let welcome = added.welcome.expect("welcome for new members");
+    let limits = CborLimits::default();
+    let (bob_group, bob_key) = CgkaGroup::join(&welcome, &bob.user_id, &bob_keys, &limits).unwrap();
+    let (carol_group, carol_key) =
+        CgkaGroup::join(&welcome, &carol.user_id, &carol_keys, &limits).unwrap();
+    assert_eq!(bob_key, added.scope_key);
+    assert_eq!(carol_key, added.scope_key);
+
+    // Bob removes Carol; Alice follows and Carol is locked out.
+    let removed = bob_group
+        .commit(&[carol_group.own_leaf], &[], &[9u8; 32], context())
+        .unwrap();
+    assert!(removed.welcome.is_none());
+    let (alice_group, alice_key) = added
+        .group
+        .process_commit(&removed.commit, &alice_keys)
+        .unwrap();
+    assert_eq!(alice_key, removed.scope_key);
+    assert_ne!(alice_key, added.scope_key);
+    assert_eq!(
+        carol_group
+            .process_commit(&removed.commit, &carol_keys)
+            .unwrap_err(),
+        "removed from the group"
+    );
+
+    // Dave takes the freed leaf after a round trip through the stored form.
+    let alice_group = CgkaGroup::from_bytes(&alice_group.to_bytes().unwrap(), &limits).unwrap();
+    let grown = alice_group
+        .commit(&[], std::slice::from_ref(&dave), &[10u8; 32], context())
+        .unwrap();
+    let (_, bob_key) = removed
+        .group
+        .process_commit(&grown.commit, &bob_keys)
+        .unwrap();
+    let (dave_group, dave_key) = CgkaGroup::join(
+        grown.welcome.as_ref().unwrap(),
+        &dave.user_id,
+        &dave_keys,
+        &limits,
+    )
+    .unwrap();
+    assert_eq!(bob_key, grown.scope_key);
+    assert_eq!(dave_key, grown.scope_key);
+    assert_eq!(dave_group.epoch, 4);
+    assert_eq!(grown.group.tree.members().len(), 3);
+}
+
+#[test]
+fn tampered_commits_and_foreign_welcomes_are_refused() {
+    let (alice, _) = member("alice");
+    let (bob, bob_keys) = member("bob");
+    let (carol, carol_keys) = member("carol");
+    let (alice_group, _) = CgkaGroup::create(scope(), 1, &alice, &[7u8; 32]).unwrap();
+    let added = alice_group
+        .commit(&[], std::slice::from_ref(&bob), &[8u8; 32], context())
+        .unwrap();
+    let welcome = added.welcome.unwrap();
+    let limits = CborLimits::default();
+    assert_eq!(
+        CgkaGroup::join(&welcome, &carol.user_id, &carol_keys, &limits).unwrap_err(),
+        "not a member of the tree"
+    );
+    let (bob_group, _) = CgkaGroup::join(&welcome, &bob.user_id, &bob_keys, &limits).unwrap();
+
+    let rotated = added.group.commit(&[], &[], &[9u8; 32], context()).unwrap();
+    let mut tampered = rotated.commit.clone();
+    tampered.confirmation_tag[0] ^= 1;
+    assert_eq!(
+        bob_group.process_commit(&tampered, &bob_keys).unwrap_err(),
+        "confirmation tag mismatch"
+    );
+    let mut skipped = rotated.commit.clone();
+    skipped.scope_epoch = ScopeEpoch(4);
+    assert_eq!(
+        bob_group.process_commit(&skipped, &bob_keys).unwrap_err(),
+        "commit does not follow the current epoch"
+    );
+    let (_, key) = bob_group
+        .process_commit(&rotated.commit, &bob_keys)
+        .unwrap();
+    assert_eq!(key, rotated.scope_key);
+}
+
+fn vault(user: &str, device: &str, seed: u64) -> (Core, SessionId) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(UserId(user.to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId(device.to_string()))
+        .expect("init identity");
+    (core, session_id)
+}
+
+/// `scope-1` genesis signed by `owner`'s device, with that device's fingerprint.
+fn genesis(owner: &mut Core, session_id: &SessionId) -> (Vec<u8>, String) {
+    let device = owner
+        .get_device_public_keys(session_id)
+        .expect("device keys")
+        .devices
+        .remove(0);
+    let payload = ScopeStatePayload::Genesis {
+        signer: device.signer,
+    };
+    let mut state = ScopeStateV1 {
+        v: 1,
+        scope_id: scope(),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: payload.kind(),
+        payload: payload.to_cbor(),
+        signer_device_id: device.device_id,
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    state.signature = owner
+        .sign_format(
+            session_id,
+            SignedFormat::ScopeState,
+            &state.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign scope state")
+        .signature;
+    (encode_scope_state_v1(&state).unwrap(), device.fingerprint)
+}
+
+fn service_member(core: &mut Core, session_id: &SessionId, user: &str) -> CgkaMemberV1 {
+    CgkaMemberV1 {
+        user_id: UserId(user.to_string()),
+        public_bytes: core.get_user_public_key(session_id).unwrap().public_bytes,
+    }
+}
+
+fn epochs(core: &mut Core, session_id: &SessionId) -> Vec<ScopeEpoch> {
+    core.list_scopes(session_id).unwrap().scopes[0]
+        .epochs
+        .clone()
+}
+
+#[test]
+fn vaults_join_by_welcome_and_follow_commits() {
+    let (mut owner, owner_session) = vault("user-1", "device-1", 31);
+    let (mut bob, bob_session) = vault("user-2", "device-2", 32);
+    let (mut carol, carol_session) = vault("user-3", "device-3", 33);
+    let (genesis, fingerprint) = genesis(&mut owner, &owner_session);
+    owner
+        .ingest_scope_state(&owner_session, &genesis, Some(fingerprint.clone()))
+        .unwrap();
+    bob.ingest_scope_state(&bob_session, &genesis, Some(fingerprint.clone()))
+        .unwrap();
+    carol
+        .ingest_scope_state(&carol_session, &genesis, Some(fingerprint))
+        .unwrap();
+
+    owner
+        .create_cgka_group(&owner_session, &scope(), ScopeEpoch(1))
+        .unwrap();
+    let adds = [
+        service_member(&mut bob, &bob_session, "user-2"),
+        service_member(&mut carol, &carol_session, "user-3"),
+    ];
+    let added = owner
+        .commit_cgka(&owner_session, &scope(), &adds, &[])
+        .unwrap();
+    assert_eq!(added.scope_epoch, ScopeEpoch(2));
+    let welcome = added.welcome.expect("welcome");
+    assert!(
+        bob.ingest_cgka_welcome(&bob_session, &welcome)
+            .unwrap()
+            .is_new
+    );
+    assert!(
+        carol
+            .ingest_cgka_welcome(&carol_session, &welcome)
+            .unwrap()
+            .is_new
+    );
+    assert!(
+        !bob.ingest_cgka_welcome(&bob_session, &welcome)
+            .unwrap()
+            .is_new
+    );
+    assert_eq!(
+        epochs(&mut owner, &owner_session),
+        [ScopeEpoch(1), ScopeEpoch(2)]
+    );
+    assert_eq!(epochs(&mut bob, &bob_session), [ScopeEpoch(2)]);
+
+    let removed = owner
+        .commit_cgka(
+            &owner_session,
+            &scope(),
+            &[],
+            &[UserId("user-3".to_string())],
+        )
+        .unwrap();
+    assert!(removed.welcome.is_none());
+    let ingested = bob
+        .ingest_cgka_commit(&bob_session, &removed.commit)
+        .unwrap();
+    assert_eq!(ingested.scope_epoch, ScopeEpoch(3));
+    assert!(ingested.is_new);
+    assert!(
+        !bob.ingest_cgka_commit(&bob_session, &removed.commit)
+            .unwrap()
+            .is_new
+    );
+    assert!(matches!(
+        carol.ingest_cgka_commit(&carol_session, &removed.commit),
+        Err(KeyServiceError::CgkaFailed {
+            reason: "removed from the group"
+        })
+    ));
+    bob.open_scope(&bob_session, scope(), ScopeEpoch(3))
+        .expect("scope key of the new epoch");
+
+    // Bob's device is not a scope signer, so it cannot commit.
+    assert!(matches!(
+        bob.commit_cgka(&bob_session, &scope(), &[], &[]),
+        Err(KeyServiceError::UntrustedSigner)
+    ));
+}
+
+#[test]
+fn commit_with_bad_signature_is_rejected() {
+    let (mut owner, owner_session) = vault("user-1", "device-1", 41);
+    let (mut bob, bob_session) = vault("user-2", "device-2", 42);
+    let (genesis, fingerprint) = genesis(&mut owner, &owner_session);
+    owner
+        .ingest_scope_state(&owner_session, &genesis, Some(fingerprint.clone()))
+        .unwrap();
+    bob.ingest_scope_state(&bob_session, &genesis, Some(fingerprint))
+        .unwrap();
+    owner
+        .create_cgka_group(&owner_session, &scope(), ScopeEpoch(1))
+        .unwrap();
+    assert!(matches!(
+        owner.create_cgka_group(&owner_session, &scope(), ScopeEpoch(1)),
+        Err(KeyServiceError::CgkaFailed {
+            reason: "group already exists"
+        })
+    ));
+    let adds = [service_member(&mut bob, &bob_session, "user-2")];
+    let added = owner
+        .commit_cgka(&owner_session, &scope(), &adds, &[])
+        .unwrap();
+    bob.ingest_cgka_welcome(&bob_session, &added.welcome.unwrap())
+        .unwrap();
+
+    let rotated = owner
+        .commit_cgka(&owner_session, &scope(), &[], &[])
+        .unwrap();
+    let mut commit = decode_cgka_commit_v1(&rotated.commit).unwrap();
+    commit.signature[0] ^= 1;
+    assert!(matches!(
+        bob.ingest_cgka_commit(&bob_session, &encode_cgka_commit_v1(&commit).unwrap()),
+        Err(KeyServiceError::SignatureInvalid {
+            format: SignedFormat::CgkaCommit
+        })
+    ));
+    assert!(
+        bob.ingest_cgka_commit(&bob_session, &rotated.commit)
+            .unwrap()
+            .is_new
+    );
+}
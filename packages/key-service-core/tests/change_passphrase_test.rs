@@ -0,0 +1,117 @@
+use mo_key_service_core::key_service::{ChangePassphraseResponse, KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{SessionId, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+/// A vault with user-presence unlock enrolled, and a step-up session on it.
+fn enrolled_vault() -> (Core, SessionId) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(91),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.enable_user_presence_unlock(&session_id, vec![1, 2], vec![7u8; 32])
+        .expect("enable user presence");
+    (core, session_id)
+}
+
+#[test]
+fn plain_change_disables_user_presence_and_says_so() {
+    let (mut core, session_id) = enrolled_vault();
+    assert_eq!(
+        core.change_passphrase(&session_id, b"new pass").unwrap(),
+        ChangePassphraseResponse {
+            user_presence_disabled: true
+        }
+    );
+    assert!(!core.get_vault_info().unwrap().user_presence_enabled);
+    assert!(matches!(
+        core.unlock_user_presence(&[7u8; 32]),
+        Err(KeyServiceError::UserPresenceNotEnabled)
+    ));
+
+    assert_eq!(
+        core.change_passphrase(&session_id, b"newer pass").unwrap(),
+        ChangePassphraseResponse {
+            user_presence_disabled: false
+        }
+    );
+}
+
+#[test]
+fn keeping_user_presence_reseals_it_for_the_new_kdf() {
+    let (mut core, session_id) = enrolled_vault();
+    assert!(matches!(
+        core.change_passphrase_keeping_user_presence(&session_id, b"new pass", &[8u8; 32]),
+        Err(KeyServiceError::VaultKeyUnwrapFailed)
+    ));
+    assert_eq!(
+        core.change_passphrase_keeping_user_presence(&session_id, b"new pass", &[7u8; 32])
+            .unwrap(),
+        ChangePassphraseResponse {
+            user_presence_disabled: false
+        }
+    );
+    core.lock(&session_id).expect("lock");
+
+    core.unlock_user_presence(&[7u8; 32])
+        .expect("user presence after the change");
+    assert!(matches!(
+        core.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    let session_id = core.unlock_passphrase(b"new pass").unwrap().session_id;
+
+    core.step_up(&session_id, b"new pass").expect("step up");
+    core.disable_user_presence_unlock(&session_id)
+        .expect("disable user presence");
+    assert!(matches!(
+        core.change_passphrase_keeping_user_presence(&session_id, b"third pass", &[7u8; 32]),
+        Err(KeyServiceError::UserPresenceNotEnabled)
+    ));
+}
+
+#[test]
+fn unreadable_user_presence_wrap_fails_the_change() {
+    let storage = MemoryStorage::new();
+    let mut core = KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(92),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.enable_user_presence_unlock(&session_id, vec![1, 2], vec![7u8; 32])
+        .expect("enable user presence");
+    storage.set_raw("keyvault", "user_presence", Some(b"not a wrap".to_vec()));
+
+    assert!(core.change_passphrase(&session_id, b"new pass").is_err());
+    core.lock(&session_id).expect("lock");
+    core.unlock_passphrase(b"pass")
+        .expect("old passphrase still unlocks");
+}
+
+#[test]
+fn step_up_checks_the_new_passphrase_after_a_change() {
+    let (mut core, session_id) = enrolled_vault();
+    core.change_passphrase(&session_id, b"new pass")
+        .expect("change passphrase");
+
+    assert!(matches!(
+        core.step_up(&session_id, b"pass"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    core.step_up(&session_id, b"new pass")
+        .expect("new passphrase steps up");
+}
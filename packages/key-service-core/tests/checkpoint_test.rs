@@ -0,0 +1,51 @@
+use mo_key_service_core::key_service::KeyService;
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{ScopeEpoch, ScopeId, UserId};
+
+#[test]
+fn checkpoints_pin_the_head_and_anchor_later_records() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(37),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let persist = |core: &mut KeyService<_, _, _>, session_id, epoch| {
+        core.persist_scope_key(
+            session_id,
+            &ScopeId("scope-1".to_string()),
+            ScopeEpoch(epoch),
+            &[epoch as u8; 32],
+        )
+        .expect("persist scope key");
+    };
+    persist(&mut core, &session_id, 1);
+    persist(&mut core, &session_id, 2);
+    assert_eq!(core.latest_checkpoint(&session_id).unwrap(), None);
+
+    let checkpoint = core.write_checkpoint(&session_id).expect("checkpoint");
+    assert_eq!(checkpoint.seq, 2);
+    // A checkpoint at the head is not repeated.
+    assert_eq!(core.write_checkpoint(&session_id).unwrap(), checkpoint);
+    assert_eq!(core.list_records_metadata(&session_id).unwrap().len(), 3);
+
+    persist(&mut core, &session_id, 3);
+    core.lock(&session_id).expect("lock");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert_eq!(
+        core.latest_checkpoint(&session_id).unwrap(),
+        Some(checkpoint.clone())
+    );
+
+    let next = core
+        .write_checkpoint(&session_id)
+        .expect("second checkpoint");
+    assert_eq!(next.seq, 4);
+    assert_ne!(next.hash, checkpoint.hash);
+    assert!(core.verify_keyvault(&session_id).expect("verify").ok);
+}
@@ -0,0 +1,110 @@
+use mo_key_service_core::error::ErrorDetail;
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::UserId;
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn service(clock: VirtualClock, config: KeyServiceConfig) -> Core {
+    let mut core = KeyService::new(MemoryStorage::new(), clock, SeededEntropy::new(1), config);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    core
+}
+
+#[test]
+fn rewinding_the_clock_fails_without_extending_the_session() {
+    let clock = VirtualClock::new(1_000_000);
+    let mut core = service(clock.clone(), test_config());
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+
+    // Small steps back are tolerated.
+    clock.set(1_000_000 - 1_000);
+    core.list_records_metadata(&session_id).expect("small skew");
+
+    clock.set(1_000);
+    let err = core
+        .list_records_metadata(&session_id)
+        .expect_err("rewound clock");
+    assert!(matches!(
+        err,
+        KeyServiceError::ClockAnomaly {
+            reason: "clock went backwards",
+            now_ms: 1_000,
+            last_ms: 1_000_000,
+        }
+    ));
+    let report = err.report();
+    assert_eq!(report.code, "ClockAnomaly");
+    assert_eq!(
+        report.context[0],
+        (
+            "reason",
+            ErrorDetail::Text("clock went backwards".to_string())
+        )
+    );
+    assert!(matches!(
+        core.renew_session(&session_id),
+        Err(KeyServiceError::ClockAnomaly { .. })
+    ));
+
+    // Once the clock is back, the session carries on with its original expiry.
+    clock.set(1_000_000);
+    core.list_records_metadata(&session_id)
+        .expect("clock recovered");
+}
+
+#[test]
+fn forward_jumps_lock_sessions_only_when_bounded() {
+    let clock = VirtualClock::new(1_000);
+    let mut core = service(clock.clone(), test_config());
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    clock.advance(60 * 60 * 1000);
+    assert!(matches!(
+        core.list_records_metadata(&session_id),
+        Err(KeyServiceError::SessionInvalid)
+    ));
+
+    let policy = test_policy()
+        .max_clock_jump(10_000)
+        .build()
+        .expect("policy");
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    let clock = VirtualClock::new(1_000);
+    let mut core = service(clock.clone(), config);
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    clock.advance(5_000);
+    core.list_records_metadata(&session_id)
+        .expect("within bound");
+    clock.advance(20_000);
+    assert!(matches!(
+        core.list_records_metadata(&session_id),
+        Err(KeyServiceError::ClockAnomaly {
+            reason: "clock jumped forward",
+            ..
+        })
+    ));
+    assert!(core.list_sessions().is_empty());
+    core.unlock_passphrase(b"pass")
+        .expect("unlock after the jump");
+
+    assert!(test_policy().max_clock_jump(0).build().is_err());
+}
+
+#[test]
+fn expiry_past_the_end_of_time_is_an_anomaly() {
+    let clock = VirtualClock::new(u64::MAX - 10);
+    let mut core = service(clock, test_config());
+    assert!(matches!(
+        core.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::ClockAnomaly {
+            reason: "session expiry overflows",
+            ..
+        })
+    ));
+}
@@ -0,0 +1,108 @@
+use mo_key_service_core::cbor::{
+    as_map, cbor_bytes, cbor_map, cbor_uint, decode_canonical_value, encode_canonical_value,
+    req_bytes, req_uint, CborLimits,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{SessionId, SnapshotCompression, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn stepped_up(seed: u64) -> (Core, SessionId) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    (core, session_id)
+}
+
+/// Rewrites the compressed record section of a v2 export.
+fn with_records(blob: &[u8], edit: impl Fn(&mut Vec<u8>)) -> Vec<u8> {
+    let value = decode_canonical_value(blob, &CborLimits::default()).unwrap();
+    let map = as_map(&value).unwrap();
+    let mut records = req_bytes(map, 1).unwrap();
+    edit(&mut records);
+    encode_canonical_value(&cbor_map(vec![
+        (0, map[0].1.clone()),
+        (1, cbor_bytes(&records)),
+        (2, cbor_uint(req_uint(map, 2).unwrap())),
+        (3, cbor_uint(req_uint(map, 3).unwrap())),
+    ]))
+    .unwrap()
+}
+
+#[test]
+fn compressed_exports_import_into_a_fresh_vault() {
+    let (mut source, source_session) = stepped_up(41);
+    for i in 0..8 {
+        source
+            .put_vault_blob(&source_session, &format!("note-{i}"), &[b'a'; 512])
+            .expect("put blob");
+    }
+    let plain = source.export_keyvault(&source_session).expect("export v1");
+
+    for (compression, seed) in [
+        (SnapshotCompression::Deflate, 42),
+        (SnapshotCompression::Zstd, 43),
+    ] {
+        let blob = source
+            .export_keyvault_compressed(&source_session, compression)
+            .expect("export v2");
+        assert!(!blob.is_empty());
+
+        let (mut target, target_session) = stepped_up(seed);
+        target
+            .import_keyvault(&target_session, &blob)
+            .expect("import v2");
+        target.lock(&target_session).expect("lock");
+        let session_id = target
+            .unlock_passphrase(b"pass")
+            .expect("unlock")
+            .session_id;
+        assert_eq!(
+            target
+                .get_vault_blob(&session_id, "note-7")
+                .unwrap()
+                .unwrap()
+                .as_slice(),
+            &[b'a'; 512][..]
+        );
+
+        // v1 exports still import next to v2 ones.
+        target.step_up(&session_id, b"pass").expect("step up");
+        target
+            .import_keyvault(&session_id, &plain)
+            .expect("import v1");
+    }
+}
+
+#[test]
+fn damaged_compressed_sections_are_rejected() {
+    let (mut core, session_id) = stepped_up(44);
+    core.put_vault_blob(&session_id, "settings", b"{}")
+        .expect("put blob");
+    let blob = core
+        .export_keyvault_compressed(&session_id, SnapshotCompression::Deflate)
+        .expect("export v2");
+
+    let truncated = with_records(&blob, |records| records.truncate(records.len() / 2));
+    let corrupted = with_records(&blob, |records| {
+        for byte in records.iter_mut() {
+            *byte ^= 0x5a;
+        }
+    });
+    for damaged in [truncated, corrupted] {
+        assert!(matches!(
+            core.import_keyvault(&session_id, &damaged),
+            Err(KeyServiceError::InvalidFormat(_))
+        ));
+    }
+}
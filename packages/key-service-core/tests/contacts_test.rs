@@ -0,0 +1,329 @@
+use mo_key_service_core::audit::AuditEventKind;
+use mo_key_service_core::formats::{encode_scope_state_v1, ScopeStatePayload, ScopeStateV1};
+use mo_key_service_core::key_service::{IngestWarning, KeyService, KeyServiceError, SignedFormat};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    DeviceId, ScopeEpoch, ScopeId, SessionId, SigCiphersuiteId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn scope() -> ScopeId {
+    ScopeId("scope-1".to_string())
+}
+
+fn user(id: &str) -> UserId {
+    UserId(id.to_string())
+}
+
+fn vault(user_id: &str, device: &str, seed: u64) -> (Core, SessionId, String) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(user(user_id), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId(device.to_string()))
+        .expect("init identity");
+    let fingerprint = core
+        .get_device_public_keys(&session_id)
+        .expect("device keys")
+        .devices
+        .remove(0)
+        .fingerprint;
+    (core, session_id, fingerprint)
+}
+
+/// Signs `payload` as the owner's scope state `seq` for `scope-1` epoch 1.
+fn scope_state(
+    owner: &mut Core,
+    owner_session: &SessionId,
+    seq: u64,
+    prev_hash: Vec<u8>,
+    payload: ScopeStatePayload,
+) -> (Vec<u8>, Vec<u8>) {
+    let device = owner
+        .get_device_public_keys(owner_session)
+        .unwrap()
+        .devices
+        .remove(0);
+    let mut state = ScopeStateV1 {
+        v: 1,
+        scope_id: scope(),
+        scope_state_seq: seq,
+        prev_hash,
+        scope_epoch: 1,
+        kind: payload.kind(),
+        payload: payload.to_cbor(),
+        signer_device_id: device.device_id,
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    state.signature = owner
+        .sign_format(
+            owner_session,
+            SignedFormat::ScopeState,
+            &state.to_be_signed_bytes().unwrap(),
+        )
+        .unwrap()
+        .signature;
+    let next_prev = hex::decode(state.scope_state_ref().unwrap()).unwrap();
+    (encode_scope_state_v1(&state).unwrap(), next_prev)
+}
+
+/// An owner holding `scope-1` epoch 1 and a recipient trusting the owner's device for it.
+fn owner_and_recipient() -> (Core, SessionId, String, Core, SessionId) {
+    let (mut owner, owner_session, owner_fp) = vault("user-1", "device-1", 61);
+    let (mut recipient, recipient_session, _) = vault("user-2", "device-2", 62);
+    let signer = owner
+        .get_device_public_keys(&owner_session)
+        .unwrap()
+        .devices
+        .remove(0)
+        .signer;
+    let (genesis, _) = scope_state(
+        &mut owner,
+        &owner_session,
+        1,
+        vec![0u8; 32],
+        ScopeStatePayload::Genesis { signer },
+    );
+    owner
+        .ingest_scope_state(&owner_session, &genesis, Some(owner_fp.clone()))
+        .unwrap();
+    recipient
+        .ingest_scope_state(&recipient_session, &genesis, Some(owner_fp.clone()))
+        .unwrap();
+    owner
+        .persist_scope_key(&owner_session, &scope(), ScopeEpoch(1), &[9u8; 32])
+        .unwrap();
+    (owner, owner_session, owner_fp, recipient, recipient_session)
+}
+
+#[test]
+fn envelope_sealed_to_a_directory_key_opens_for_the_recipient() {
+    let (mut owner, owner_session, _, mut recipient, recipient_session) = owner_and_recipient();
+    assert!(matches!(
+        owner.seal_key_envelope(&owner_session, &scope(), ScopeEpoch(1), &user("user-2")),
+        Err(KeyServiceError::UnknownContact { user_id }) if user_id == user("user-2")
+    ));
+
+    let key = recipient.get_user_public_key(&recipient_session).unwrap();
+    assert!(matches!(
+        owner.add_contact(
+            &owner_session,
+            &user("user-2"),
+            &key.public_bytes,
+            &"00".repeat(32)
+        ),
+        Err(KeyServiceError::FingerprintMismatch)
+    ));
+    let contact = owner
+        .add_contact(
+            &owner_session,
+            &user("user-2"),
+            &key.public_bytes,
+            &key.fingerprint_hex,
+        )
+        .unwrap();
+    assert_eq!(contact.fingerprint_hex, key.fingerprint_hex);
+    assert_eq!(contact.scope_id, None);
+    assert_eq!(contact.added_at_ms, Some(1_000));
+
+    let sealed = owner
+        .seal_key_envelope(&owner_session, &scope(), ScopeEpoch(1), &user("user-2"))
+        .unwrap();
+    assert!(
+        recipient
+            .ingest_key_envelope(&recipient_session, &sealed.envelope)
+            .unwrap()
+            .is_new
+    );
+    recipient
+        .open_scope(&recipient_session, scope(), ScopeEpoch(1))
+        .expect("scope key from the envelope");
+
+    let kinds: Vec<_> = owner
+        .read_audit_log(&owner_session)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.kind)
+        .collect();
+    assert!(kinds.contains(&AuditEventKind::ContactChange));
+}
+
+#[test]
+fn directory_survives_a_fresh_unlock_and_removal() {
+    let (mut owner, owner_session, _, mut recipient, recipient_session) = owner_and_recipient();
+    let key = recipient.get_user_public_key(&recipient_session).unwrap();
+    owner
+        .add_contact(
+            &owner_session,
+            &user("user-2"),
+            &key.public_bytes,
+            &key.fingerprint_hex,
+        )
+        .unwrap();
+
+    owner.lock(&owner_session).unwrap();
+    let owner_session = owner.unlock_passphrase(b"pass").unwrap().session_id;
+    let listed = owner.list_contacts(&owner_session).unwrap().contacts;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].user_id, user("user-2"));
+    assert_eq!(listed[0].public_bytes, key.public_bytes);
+
+    assert!(owner
+        .remove_contact(&owner_session, &user("user-2"))
+        .unwrap());
+    assert!(!owner
+        .remove_contact(&owner_session, &user("user-2"))
+        .unwrap());
+    owner.lock(&owner_session).unwrap();
+    let owner_session = owner.unlock_passphrase(b"pass").unwrap().session_id;
+    assert!(owner
+        .list_contacts(&owner_session)
+        .unwrap()
+        .contacts
+        .is_empty());
+    assert!(matches!(
+        owner.get_contact(&owner_session, &user("user-2")),
+        Err(KeyServiceError::UnknownContact { .. })
+    ));
+}
+
+#[test]
+fn membership_change_fills_the_directory_without_replacing_keys() {
+    let (mut owner, owner_session, owner_fp, _, _) = owner_and_recipient();
+    let (mut third, third_session, _) = vault("user-3", "device-3", 63);
+    let third_key = third.get_user_public_key(&third_session).unwrap();
+
+    let (mut member, member_session, _) = vault("user-4", "device-4", 65);
+    let signer = owner
+        .get_device_public_keys(&owner_session)
+        .unwrap()
+        .devices
+        .remove(0)
+        .signer;
+    let (genesis, genesis_ref) = scope_state(
+        &mut owner,
+        &owner_session,
+        1,
+        vec![0u8; 32],
+        ScopeStatePayload::Genesis {
+            signer: signer.clone(),
+        },
+    );
+    member
+        .ingest_scope_state(&member_session, &genesis, Some(owner_fp.clone()))
+        .unwrap();
+    let (change, _) = scope_state(
+        &mut owner,
+        &owner_session,
+        2,
+        genesis_ref,
+        ScopeStatePayload::MembershipChange {
+            signer,
+            added: vec![user("user-3")],
+            removed: Vec::new(),
+            roles: Vec::new(),
+            member_keys: vec![(user("user-3"), third_key.public_bytes.clone())],
+        },
+    );
+
+    // A different key already held for the user stays in place.
+    let other_key = member.get_user_public_key(&member_session).unwrap();
+    member
+        .add_contact(
+            &member_session,
+            &user("user-3"),
+            &other_key.public_bytes,
+            &other_key.fingerprint_hex,
+        )
+        .unwrap();
+    let ingested = member
+        .ingest_scope_state(&member_session, &change, None)
+        .unwrap();
+    assert_eq!(
+        ingested.warnings,
+        vec![IngestWarning::ContactKeyConflict {
+            user_id: user("user-3")
+        }]
+    );
+    assert_eq!(
+        member
+            .get_contact(&member_session, &user("user-3"))
+            .unwrap()
+            .public_bytes,
+        other_key.public_bytes
+    );
+
+    // Without a held key, the signed change supplies it.
+    member
+        .remove_contact(&member_session, &user("user-3"))
+        .unwrap();
+    member
+        .ingest_scope_state(&member_session, &change, None)
+        .unwrap();
+    let contact = member
+        .get_contact(&member_session, &user("user-3"))
+        .unwrap();
+    assert_eq!(contact.public_bytes, third_key.public_bytes);
+    assert_eq!(contact.scope_id, Some(scope()));
+}
+
+#[test]
+fn prekey_sealing_checks_the_directory_key() {
+    let (mut owner, owner_session, _, mut recipient, recipient_session) = owner_and_recipient();
+    let recipient_fp = recipient
+        .get_device_public_keys(&recipient_session)
+        .unwrap()
+        .devices
+        .remove(0)
+        .fingerprint;
+    let published = recipient.publish_prekeys(&recipient_session, 1).unwrap();
+    let own_key = owner.get_user_public_key(&owner_session).unwrap();
+    owner
+        .add_contact(
+            &owner_session,
+            &user("user-2"),
+            &own_key.public_bytes,
+            &own_key.fingerprint_hex,
+        )
+        .unwrap();
+    assert!(matches!(
+        owner.seal_key_envelope_to_prekey(
+            &owner_session,
+            &scope(),
+            ScopeEpoch(1),
+            &published.bundle,
+            0,
+            &recipient_fp,
+        ),
+        Err(KeyServiceError::FingerprintMismatch)
+    ));
+
+    let key = recipient.get_user_public_key(&recipient_session).unwrap();
+    owner
+        .add_contact(
+            &owner_session,
+            &user("user-2"),
+            &key.public_bytes,
+            &key.fingerprint_hex,
+        )
+        .unwrap();
+    owner
+        .seal_key_envelope_to_prekey(
+            &owner_session,
+            &scope(),
+            ScopeEpoch(1),
+            &published.bundle,
+            0,
+            &recipient_fp,
+        )
+        .expect("bundle matches the directory key");
+}
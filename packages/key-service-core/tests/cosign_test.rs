@@ -0,0 +1,181 @@
+use mo_key_service_core::ciphersuite::hybrid_verify;
+use mo_key_service_core::cosign::{cosign_verify_possession, CosignKeyShare};
+use mo_key_service_core::domains::{SIG_APP_PAYLOAD_V1, SIG_SCOPE_STATE_V1};
+use mo_key_service_core::formats::{
+    decode_cosign_enroll_request_v1, decode_cosign_sign_response_v1,
+    encode_cosign_enroll_response_v1, encode_cosign_sign_response_v1, CosignEnrollResponseV1,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError, SignedFormat};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, CosignTestServer, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{DeviceId, SessionId, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn ready(seed: u64) -> (Core, SessionId) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    core.step_up(&session_id, b"pass").expect("step up");
+    (core, session_id)
+}
+
+fn enrolled(seed: u64) -> (Core, SessionId, CosignTestServer) {
+    let (mut core, session_id) = ready(seed);
+    let server = CosignTestServer::new(seed + 100, &[SIG_SCOPE_STATE_V1, SIG_APP_PAYLOAD_V1]);
+    let (enrollment, request) = core
+        .start_cosign_enrollment(&session_id)
+        .expect("start enrollment");
+    let response = server.enroll(&request).expect("server enroll");
+    core.finish_cosign_enrollment(&session_id, enrollment, &response)
+        .expect("finish enrollment");
+    (core, session_id, server)
+}
+
+#[test]
+fn cosigned_signature_verifies_under_the_group_key() {
+    let (mut core, session_id, server) = enrolled(1);
+    let (pending, request) = core
+        .start_cosign_format(&session_id, SignedFormat::ScopeState, b"scope state bytes")
+        .expect("start cosign");
+    let response = server.sign(&request).expect("server sign");
+    let signed = core
+        .finish_cosign(&session_id, pending, &response)
+        .expect("finish cosign");
+
+    let keys = core.get_device_public_keys(&session_id).expect("keys");
+    let signer = &keys.devices[0].signer;
+    assert!(hybrid_verify(
+        SIG_SCOPE_STATE_V1,
+        b"scope state bytes",
+        &signed.signature,
+        signer
+    ));
+    assert!(!hybrid_verify(
+        SIG_APP_PAYLOAD_V1,
+        b"scope state bytes",
+        &signed.signature,
+        signer
+    ));
+}
+
+#[test]
+fn enrolled_device_cannot_sign_alone() {
+    let (mut core, session_id, _server) = enrolled(2);
+    let err = core
+        .sign_format(&session_id, SignedFormat::ScopeState, b"state")
+        .expect_err("local signing refused");
+    assert!(matches!(err, KeyServiceError::CosignRequired));
+    let err = core.sign(&session_id, b"payload").expect_err("refused");
+    assert!(matches!(err, KeyServiceError::CosignRequired));
+}
+
+#[test]
+fn tampered_server_share_is_rejected() {
+    let (mut core, session_id, server) = enrolled(3);
+    let (pending, request) = core
+        .start_cosign(&session_id, b"payload")
+        .expect("start cosign");
+    let mut response = decode_cosign_sign_response_v1(&server.sign(&request).expect("server sign"))
+        .expect("decode");
+    response.share[0] ^= 1;
+    let tampered = encode_cosign_sign_response_v1(&response).expect("encode");
+    let err = core
+        .finish_cosign(&session_id, pending, &tampered)
+        .expect_err("bad share");
+    assert!(matches!(err, KeyServiceError::CosignServerInvalid));
+}
+
+#[test]
+fn server_refuses_contexts_outside_its_policy() {
+    let (mut core, session_id) = ready(4);
+    let server = CosignTestServer::new(104, &[SIG_SCOPE_STATE_V1]);
+    let (enrollment, request) = core.start_cosign_enrollment(&session_id).expect("start");
+    let response = server.enroll(&request).expect("enroll");
+    core.finish_cosign_enrollment(&session_id, enrollment, &response)
+        .expect("finish");
+    let (_pending, request) = core
+        .start_cosign(&session_id, b"payload")
+        .expect("start cosign");
+    assert!(server.sign(&request).is_err());
+}
+
+#[test]
+fn enrollment_rejects_a_server_proof_for_another_device() {
+    let (mut core, session_id) = ready(5);
+    let (enrollment, request) = core.start_cosign_enrollment(&session_id).expect("start");
+    let request = decode_cosign_enroll_request_v1(&request).expect("decode request");
+    assert!(cosign_verify_possession(
+        &request.share_pub,
+        b"device-1",
+        &request.proof
+    ));
+
+    let entropy = SeededEntropy::new(55);
+    let share = CosignKeyShare::generate(&entropy).expect("share");
+    let proof = share
+        .prove_possession(b"device-2", &entropy)
+        .expect("proof");
+    let response = encode_cosign_enroll_response_v1(&CosignEnrollResponseV1 {
+        v: 1,
+        share_pub: share.public.to_vec(),
+        proof: proof.to_vec(),
+    })
+    .expect("encode");
+    let err = core
+        .finish_cosign_enrollment(&session_id, enrollment, &response)
+        .expect_err("wrong binding");
+    assert!(matches!(err, KeyServiceError::CosignServerInvalid));
+}
+
+#[test]
+fn enrollment_requires_step_up() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(6),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    let err = core
+        .start_cosign_enrollment(&session_id)
+        .expect_err("needs step-up");
+    assert!(matches!(err, KeyServiceError::StepUpRequired));
+}
+
+#[test]
+fn cosign_share_survives_relock() {
+    let (mut core, session_id, server) = enrolled(7);
+    let before = core.get_device_public_keys(&session_id).expect("keys");
+    core.lock(&session_id).expect("lock");
+
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let after = core.get_device_public_keys(&session_id).expect("keys");
+    assert_eq!(before.devices[0].fingerprint, after.devices[0].fingerprint);
+    let (pending, request) = core
+        .start_cosign(&session_id, b"payload")
+        .expect("start cosign");
+    let response = server.sign(&request).expect("server sign");
+    let signed = core
+        .finish_cosign(&session_id, pending, &response)
+        .expect("finish cosign");
+    assert!(hybrid_verify(
+        SIG_APP_PAYLOAD_V1,
+        b"payload",
+        &signed.signature,
+        &after.devices[0].signer
+    ));
+}
@@ -0,0 +1,207 @@
+use ciborium::value::Value;
+use mo_key_service_core::cbor::{
+    cbor_array, cbor_map, cbor_text, cbor_uint, decode_canonical_value, encode_canonical_value,
+    CborLimits,
+};
+use mo_key_service_core::crypto::{KdfMinimums, KdfParams};
+use mo_key_service_core::formats::{
+    decode_keyvault_header_v1, decode_resource_grant_v1, encode_keyvault_header_v1,
+    encode_keyvault_record_container_v1, encode_keyvault_snapshot_v1, encode_resource_grant_v1,
+    KeyVaultHeaderV1, KeyVaultRecordContainerV1, KeyVaultSnapshotDecoder, KeyVaultSnapshotV1,
+    ResourceGrantV1, VaultKeyWrapV1,
+};
+use mo_key_service_core::types::{
+    AeadId, DeviceId, KeyWrapAlg, ResourceId, ResourceKeyId, ScopeId, SigCiphersuiteId,
+};
+
+fn grant() -> ResourceGrantV1 {
+    ResourceGrantV1 {
+        v: 1,
+        grant_id: "grant-1".to_string(),
+        scope_id: ScopeId("scope-1".to_string()),
+        grant_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_state_ref: vec![1u8; 32],
+        scope_epoch: 1,
+        resource_id: ResourceId("res-1".to_string()),
+        resource_key_id: ResourceKeyId("rk-1".to_string()),
+        policy: None,
+        aead: AeadId::Aead1,
+        nonce: vec![9u8; 12],
+        wrapped_key: vec![7u8; 32],
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: vec![4u8; 10],
+    }
+}
+
+fn header() -> KeyVaultHeaderV1 {
+    KeyVaultHeaderV1 {
+        v: 1,
+        vault_id: "vault-1".to_string(),
+        user_id: "user-1".to_string(),
+        kdf: KdfParams {
+            id: "kdf-1".to_string(),
+            salt: vec![7u8; 16],
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        },
+        aead: AeadId::Aead1,
+        records: Vec::new(),
+        vault_key_wrap: VaultKeyWrapV1 {
+            aead: AeadId::Aead1,
+            nonce: vec![1u8; 12],
+            ct: vec![2u8; 16],
+            commitment: None,
+            alg: KeyWrapAlg::AesGcm,
+        },
+        kms_key_id: None,
+        device_shares: None,
+        passphrase_check: None,
+    }
+}
+
+fn container(seq: u64, nonce_len: usize) -> KeyVaultRecordContainerV1 {
+    KeyVaultRecordContainerV1 {
+        v: 1,
+        seq,
+        prev_hash: vec![0u8; 32],
+        record_id: format!("rec-{seq}"),
+        nonce: vec![5u8; nonce_len],
+        ct: vec![6u8; 24],
+    }
+}
+
+/// Re-encodes `bytes` after replacing the value at `path` (map keys, outermost first).
+fn replace_at(bytes: &[u8], path: &[u64], replacement: Value) -> Vec<u8> {
+    fn walk(value: &mut Value, path: &[u64], replacement: Value) {
+        let Value::Map(entries) = value else {
+            panic!("expected map");
+        };
+        let (_, slot) = entries
+            .iter_mut()
+            .find(|(k, _)| *k == Value::Integer(path[0].into()))
+            .expect("key present");
+        if path.len() == 1 {
+            *slot = replacement;
+        } else {
+            walk(slot, &path[1..], replacement);
+        }
+    }
+    let mut value = decode_canonical_value(bytes, &CborLimits::default()).expect("decode");
+    walk(&mut value, path, replacement);
+    encode_canonical_value(&value).expect("encode")
+}
+
+fn error_text<T: std::fmt::Debug, E: std::fmt::Display>(result: Result<T, E>) -> String {
+    result.expect_err("decode fails").to_string()
+}
+
+#[test]
+fn field_errors_name_the_format_and_key() {
+    let bytes = encode_resource_grant_v1(&grant()).expect("encode");
+    let wrong_type = replace_at(&bytes, &[11], cbor_uint(1));
+    assert_eq!(
+        error_text(decode_resource_grant_v1(&wrong_type)),
+        "cbor error: resource_grant[11]: expected bytes"
+    );
+
+    let mut short = grant();
+    short.nonce = vec![9u8; 8];
+    let short = encode_resource_grant_v1(&short).expect("encode");
+    assert_eq!(
+        error_text(decode_resource_grant_v1(&short)),
+        "format error: resource_grant[11]: expected 12-byte nonce, got 8"
+    );
+}
+
+#[test]
+fn nested_errors_carry_the_full_key_path() {
+    let bytes = encode_keyvault_header_v1(&header()).expect("encode");
+    let iterations = replace_at(&bytes, &[3, 2, 1], cbor_text("1"));
+    assert_eq!(
+        error_text(decode_keyvault_header_v1(&iterations, &KdfMinimums::NONE)),
+        "cbor error: keyvault_header[3][2][1]: expected u64"
+    );
+
+    let mut with_record = header();
+    with_record.records = vec![container(1, 12), container(2, 4)];
+    let bytes = encode_keyvault_header_v1(&with_record).expect("encode");
+    assert_eq!(
+        error_text(decode_keyvault_header_v1(&bytes, &KdfMinimums::NONE)),
+        "format error: keyvault_header[5][1][4]: expected 12-byte nonce, got 4"
+    );
+}
+
+#[test]
+fn syntax_errors_carry_the_byte_offset() {
+    let bytes = encode_resource_grant_v1(&grant()).expect("encode");
+    let truncated = &bytes[..bytes.len() - 3];
+    assert_eq!(
+        error_text(decode_resource_grant_v1(truncated)),
+        format!(
+            "cbor error: resource_grant: truncated cbor at byte {}",
+            truncated.len()
+        )
+    );
+
+    // {1: 0, 0: 0}: keys out of canonical order from the second entry on.
+    let unsorted = [0xa2, 0x01, 0x00, 0x00, 0x00];
+    assert_eq!(
+        error_text(decode_canonical_value(&unsorted, &CborLimits::default())),
+        "cbor error: map key out of order at byte 3"
+    );
+
+    let mut trailing = encode_canonical_value(&cbor_uint(1)).expect("encode");
+    trailing.push(0x00);
+    assert_eq!(
+        error_text(decode_canonical_value(&trailing, &CborLimits::default())),
+        "cbor error: trailing bytes at byte 1"
+    );
+}
+
+#[test]
+fn limit_errors_carry_the_key_path() {
+    let limits = CborLimits {
+        max_text_bytes: 4,
+        ..CborLimits::default()
+    };
+    let value = cbor_map(vec![
+        (1, cbor_text("ok")),
+        (5, cbor_array(vec![cbor_text("ok"), cbor_text("too long")])),
+    ]);
+    let bytes = encode_canonical_value(&value).expect("encode");
+    assert_eq!(
+        error_text(decode_canonical_value(&bytes, &limits)),
+        "cbor error: [5][1]: cbor text too large"
+    );
+}
+
+#[test]
+fn streaming_snapshot_errors_name_the_record_and_its_offset() {
+    let bad = container(2, 4);
+    let snapshot = KeyVaultSnapshotV1 {
+        header: header(),
+        records: vec![container(1, 12), bad.clone()],
+        manifest: None,
+    };
+    let bytes = encode_keyvault_snapshot_v1(&snapshot).expect("encode");
+    let offset = bytes.len()
+        - encode_keyvault_record_container_v1(&bad)
+            .expect("encode")
+            .len();
+
+    let mut decoder = KeyVaultSnapshotDecoder::new(CborLimits::default());
+    let err = bytes
+        .chunks(7)
+        .try_for_each(|chunk| decoder.push(chunk))
+        .expect_err("bad record");
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "format error: keyvault_snapshot[1][1][4]: expected 12-byte nonce, got 4 \
+             (item at byte {offset})"
+        )
+    );
+}
@@ -0,0 +1,227 @@
+use aes_gcm::Aes256Gcm;
+use mo_key_service_core::aad::aad_resource_grant_wrap_v1;
+use mo_key_service_core::cbor::{cbor_bytes, cbor_map, cbor_uint};
+use mo_key_service_core::ciphersuite::{
+    generate_device_signing_keypair, hybrid_sign, HybridSignatureKeypair, SignerKeys,
+};
+use mo_key_service_core::crypto::aead_encrypt;
+use mo_key_service_core::domains::{
+    SIG_APP_PAYLOAD_V1, SIG_RESOURCE_GRANT_V1, SIG_SCOPE_STATE_V1, SIG_SIGNING_DELEGATION_V1,
+};
+use mo_key_service_core::formats::{
+    decode_signing_delegation_v1, encode_resource_grant_v1, encode_scope_state_v1,
+    encode_signing_delegation_v1, ResourceGrantV1, ScopeStateV1, SigningDelegationV1,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError, SignedFormat};
+use mo_key_service_core::testkit::{
+    fast_kdf, signer_fingerprint, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    AeadId, DeviceId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, SessionId, SigCiphersuiteId,
+    UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+/// Ingests a genesis scope state signed by `device-1` and persists its scope key; returns the
+/// scope state ref for grants.
+fn ingest_scope(
+    core: &mut Core,
+    session_id: &SessionId,
+    owner: &HybridSignatureKeypair,
+) -> Vec<u8> {
+    let mut scope_state = ScopeStateV1 {
+        v: 1,
+        scope_id: ScopeId("scope-1".to_string()),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: 0,
+        payload: cbor_map(vec![
+            (1, cbor_bytes(&owner.ed25519_pub)),
+            (2, cbor_bytes(&owner.mldsa_pub)),
+        ]),
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    scope_state.signature = hybrid_sign(
+        SIG_SCOPE_STATE_V1,
+        &scope_state.to_be_signed_bytes().unwrap(),
+        owner,
+    )
+    .unwrap();
+    let fingerprint = signer_fingerprint(&SignerKeys {
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        ed25519_pub: owner.ed25519_pub.clone(),
+        mldsa_pub: owner.mldsa_pub.clone(),
+    });
+    core.ingest_scope_state(
+        session_id,
+        &encode_scope_state_v1(&scope_state).unwrap(),
+        Some(fingerprint),
+    )
+    .expect("ingest scope state");
+    core.persist_scope_key(
+        session_id,
+        &ScopeId("scope-1".to_string()),
+        ScopeEpoch(1),
+        &[3u8; 32],
+    )
+    .expect("persist scope key");
+    scope_state.scope_state_ref_bytes().unwrap()
+}
+
+/// The first grant of `scope-1`, signed by `device_id`.
+fn grant(scope_state_ref: &[u8], device_id: &str, signer: &HybridSignatureKeypair) -> Vec<u8> {
+    let aad = aad_resource_grant_wrap_v1("scope-1", "res-1", 1, "rk-1", AeadId::Aead1).unwrap();
+    let nonce = vec![9u8; 12];
+    let mut grant = ResourceGrantV1 {
+        v: 1,
+        grant_id: "grant-1".to_string(),
+        scope_id: ScopeId("scope-1".to_string()),
+        grant_seq: 0,
+        prev_hash: vec![0u8; 32],
+        scope_state_ref: scope_state_ref.to_vec(),
+        scope_epoch: 1,
+        resource_id: ResourceId("res-1".to_string()),
+        resource_key_id: ResourceKeyId("rk-1".to_string()),
+        policy: None,
+        aead: AeadId::Aead1,
+        nonce: nonce.clone(),
+        wrapped_key: aead_encrypt::<Aes256Gcm>(&[3u8; 32], &aad, &[4u8; 32], &nonce).unwrap(),
+        signer_device_id: DeviceId(device_id.to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    grant.signature = hybrid_sign(
+        SIG_RESOURCE_GRANT_V1,
+        &grant.to_be_signed_bytes().unwrap(),
+        signer,
+    )
+    .unwrap();
+    encode_resource_grant_v1(&grant).unwrap()
+}
+
+fn delegation(
+    delegate: &HybridSignatureKeypair,
+    signed_by: &HybridSignatureKeypair,
+    expires_at_ms: u64,
+) -> Vec<u8> {
+    let mut delegation = SigningDelegationV1 {
+        v: 1,
+        scope_id: ScopeId("scope-1".to_string()),
+        delegator_device_id: DeviceId("device-1".to_string()),
+        delegate_device_id: DeviceId("kiosk-1".to_string()),
+        delegate_ed25519_pub: delegate.ed25519_pub.clone(),
+        delegate_mldsa_pub: delegate.mldsa_pub.clone(),
+        issued_at_ms: 1_000,
+        expires_at_ms,
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    delegation.signature = hybrid_sign(
+        SIG_SIGNING_DELEGATION_V1,
+        &delegation.to_be_signed_bytes().unwrap(),
+        signed_by,
+    )
+    .unwrap();
+    encode_signing_delegation_v1(&delegation).unwrap()
+}
+
+#[test]
+fn delegated_keys_sign_until_the_delegation_expires() {
+    let clock = VirtualClock::new(1_000);
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        clock.clone(),
+        SeededEntropy::new(13),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let owner = generate_device_signing_keypair().expect("owner");
+    let kiosk = generate_device_signing_keypair().expect("kiosk");
+    let scope_state_ref = ingest_scope(&mut core, &session_id, &owner);
+    let kiosk_grant = grant(&scope_state_ref, "kiosk-1", &kiosk);
+    let open = |core: &mut Core| {
+        let scope_handle = core
+            .open_scope(&session_id, ScopeId("scope-1".to_string()), ScopeEpoch(1))
+            .expect("open scope");
+        core.open_resource(&session_id, &scope_handle.scope_key_handle, &kiosk_grant)
+    };
+
+    assert!(matches!(
+        open(&mut core),
+        Err(KeyServiceError::UntrustedSigner)
+    ));
+    // The delegate cannot authorize itself.
+    assert!(matches!(
+        core.ingest_delegation(&session_id, &delegation(&kiosk, &kiosk, 5_000)),
+        Err(KeyServiceError::SignatureInvalid {
+            format: SignedFormat::SigningDelegation
+        })
+    ));
+
+    let signed = delegation(&kiosk, &owner, 5_000);
+    let decoded = decode_signing_delegation_v1(&signed).expect("decode delegation");
+    assert_eq!(decoded.delegate_keys().ed25519_pub, kiosk.ed25519_pub);
+    let response = core
+        .ingest_delegation(&session_id, &signed)
+        .expect("ingest delegation");
+    assert_eq!(response.delegate_device_id, DeviceId("kiosk-1".to_string()));
+    assert_eq!(response.expires_at_ms, 5_000);
+
+    open(&mut core).expect("open grant signed by the delegate");
+    let signature = hybrid_sign(SIG_APP_PAYLOAD_V1, b"payload", &kiosk).unwrap();
+    let verify = |core: &mut Core| {
+        core.verify(
+            ScopeId("scope-1".to_string()),
+            DeviceId("kiosk-1".to_string()),
+            b"payload",
+            &signature,
+            SigCiphersuiteId::HybridSig1,
+        )
+    };
+    assert!(verify(&mut core).expect("verify").ok);
+
+    // Delegates never sign scope states.
+    let mut bump = ScopeStateV1 {
+        v: 1,
+        scope_id: ScopeId("scope-1".to_string()),
+        scope_state_seq: 2,
+        prev_hash: scope_state_ref.clone(),
+        scope_epoch: 2,
+        kind: 1,
+        payload: cbor_map(vec![
+            (1, cbor_bytes(&kiosk.ed25519_pub)),
+            (2, cbor_bytes(&kiosk.mldsa_pub)),
+            (3, cbor_uint(1)),
+            (4, cbor_uint(2)),
+            (5, cbor_uint(0)),
+        ]),
+        signer_device_id: DeviceId("kiosk-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    bump.signature = hybrid_sign(
+        SIG_SCOPE_STATE_V1,
+        &bump.to_be_signed_bytes().unwrap(),
+        &kiosk,
+    )
+    .unwrap();
+    assert!(matches!(
+        core.ingest_scope_state(&session_id, &encode_scope_state_v1(&bump).unwrap(), None),
+        Err(KeyServiceError::SignerFingerprintRequired)
+    ));
+
+    clock.set(5_001);
+    let err = verify(&mut core).expect_err("expired delegation");
+    assert!(matches!(err, KeyServiceError::DelegationExpired));
+    assert_eq!(err.code(), "DelegationExpired");
+    assert!(matches!(
+        core.ingest_delegation(&session_id, &signed),
+        Err(KeyServiceError::DelegationExpired)
+    ));
+}
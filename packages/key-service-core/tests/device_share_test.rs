@@ -0,0 +1,243 @@
+use mo_key_service_core::adapters::DeviceAnchorAdapter;
+use mo_key_service_core::device_shares::{combine_device_shares, device_share};
+use mo_key_service_core::formats::{
+    decode_device_share_response_v1, encode_device_share_response_v1,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{DeviceId, SessionAssurance, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+/// Toy anchor bound to one device: blobs carry the device tag and AAD in the clear.
+struct TaggedAnchor(&'static str);
+
+impl DeviceAnchorAdapter for TaggedAnchor {
+    type Error = String;
+
+    fn seal(&self, _label: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        Ok([self.0.as_bytes(), aad, plaintext].concat())
+    }
+
+    fn unseal(&self, _label: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        ciphertext
+            .strip_prefix(self.0.as_bytes())
+            .and_then(|rest| rest.strip_prefix(aad))
+            .map(|share| share.to_vec())
+            .ok_or_else(|| "anchor mismatch".to_string())
+    }
+}
+
+fn device(storage: &MemoryStorage, name: &'static str) -> Core {
+    let config = KeyServiceConfig::builder()
+        .policy(test_policy().build().expect("policy"))
+        .build()
+        .expect("config");
+    let mut core = KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(name.len() as u64 + name.as_bytes()[0] as u64),
+        config,
+    );
+    core.set_device_anchor(TaggedAnchor(name));
+    core
+}
+
+fn device_id(name: &str) -> DeviceId {
+    DeviceId(name.to_string())
+}
+
+/// One vault on devices `a`, `b`, and `c`, split 2-of-3 and enrolled on each. Returns each
+/// device's storage.
+fn split_vault() -> [MemoryStorage; 3] {
+    let storage_a = MemoryStorage::new();
+    let mut a = device(&storage_a, "a");
+    a.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = a.unlock_passphrase(b"pass").expect("unlock").session_id;
+    a.step_up(&session_id, b"pass").expect("step up");
+    a.enable_device_share_unlock(
+        &session_id,
+        2,
+        vec![device_id("a"), device_id("b"), device_id("c")],
+    )
+    .expect("enable device shares");
+    a.enroll_device_share(&session_id, &device_id("a"))
+        .expect("enroll a");
+
+    let copy = |name: &'static str| {
+        let storage = MemoryStorage::new();
+        storage.restore(storage_a.snapshot());
+        storage.set_raw("keyvault", "head_marker", None);
+        storage.set_raw("keyvault", "device_share", None);
+        let mut core = device(&storage, name);
+        let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+        core.step_up(&session_id, b"pass").expect("step up");
+        core.enroll_device_share(&session_id, &device_id(name))
+            .expect("enroll");
+        storage
+    };
+    let storage_b = copy("b");
+    let storage_c = copy("c");
+    [storage_a, storage_b, storage_c]
+}
+
+fn rejected_reason<T: std::fmt::Debug>(result: Result<T, KeyServiceError>) -> &'static str {
+    match result {
+        Err(KeyServiceError::DeviceShareRejected { reason }) => reason,
+        other => panic!("expected DeviceShareRejected, got {other:?}"),
+    }
+}
+
+#[test]
+fn any_threshold_of_the_shares_recombines() {
+    let secret = [0x5au8; 32];
+    let split_id = [7u8; 16];
+    let shares: Vec<_> = (1..=5u8)
+        .map(|index| (index, device_share(&secret, &split_id, 3, index).unwrap()))
+        .collect();
+    for i in 0..5 {
+        for j in i + 1..5 {
+            for k in j + 1..5 {
+                let parts: Vec<(u8, &[u8])> = [i, j, k]
+                    .iter()
+                    .map(|&n| (shares[n].0, shares[n].1.as_slice()))
+                    .collect();
+                assert_eq!(*combine_device_shares(&parts).unwrap(), secret);
+            }
+        }
+    }
+    let two: Vec<(u8, &[u8])> = shares[..2]
+        .iter()
+        .map(|(index, share)| (*index, share.as_slice()))
+        .collect();
+    assert_ne!(*combine_device_shares(&two).unwrap(), secret);
+    assert!(combine_device_shares(&[(1, &shares[0].1), (1, &shares[1].1)]).is_err());
+}
+
+#[test]
+fn a_peer_share_unlocks_a_device_without_the_passphrase() {
+    let [storage_a, storage_b, storage_c] = split_vault();
+    let mut a = device(&storage_a, "a");
+    assert_eq!(a.get_vault_info().unwrap().device_share_threshold, Some(2));
+
+    // Device a holds one share itself, so one peer is enough.
+    let unlock = a.begin_device_share_unlock().expect("begin");
+    let mut b = device(&storage_b, "b");
+    let response = b.release_device_share(unlock.request()).expect("release");
+    let session = a
+        .unlock_device_shares(unlock, &[response])
+        .expect("unlock with shares");
+    assert_eq!(session.assurance, SessionAssurance::DeviceShares);
+
+    // A device without its own share needs two peers.
+    let mut c = device(&storage_c, "c");
+    c.set_device_anchor(TaggedAnchor("someone-else"));
+    let unlock = c.begin_device_share_unlock().expect("begin");
+    let from_a = device(&storage_a, "a")
+        .release_device_share(unlock.request())
+        .expect("release a");
+    assert!(matches!(
+        c.unlock_device_shares(unlock, std::slice::from_ref(&from_a)),
+        Err(KeyServiceError::CryptoError(_))
+    ));
+    let mut c = device(&storage_c, "c");
+    let unlock = c.begin_device_share_unlock().expect("begin");
+    let from_a = device(&storage_a, "a")
+        .release_device_share(unlock.request())
+        .expect("release a");
+    let from_b = b.release_device_share(unlock.request()).expect("release b");
+    c.unlock_device_shares(unlock, &[from_a, from_b])
+        .expect("unlock c");
+}
+
+#[test]
+fn bad_or_missing_shares_are_rejected() {
+    let [storage_a, storage_b, _] = split_vault();
+    let mut a = device(&storage_a, "a");
+    let mut b = device(&storage_b, "b");
+
+    let unlock = a.begin_device_share_unlock().expect("begin");
+    assert_eq!(
+        rejected_reason(a.unlock_device_shares(unlock, &[])),
+        "not enough shares"
+    );
+
+    let earlier = a.begin_device_share_unlock().expect("begin");
+    let stale = b.release_device_share(earlier.request()).expect("release");
+    let unlock = a.begin_device_share_unlock().expect("begin");
+    assert_eq!(
+        rejected_reason(a.unlock_device_shares(unlock, &[stale])),
+        "response to another request"
+    );
+
+    let unlock = a.begin_device_share_unlock().expect("begin");
+    let mut response =
+        decode_device_share_response_v1(&b.release_device_share(unlock.request()).unwrap())
+            .unwrap();
+    response.ct[0] ^= 1;
+    let tampered = encode_device_share_response_v1(&response).unwrap();
+    assert_eq!(
+        rejected_reason(a.unlock_device_shares(unlock, &[tampered])),
+        "share does not decrypt"
+    );
+
+    let unlock = a.begin_device_share_unlock().expect("begin");
+    let mut response =
+        decode_device_share_response_v1(&b.release_device_share(unlock.request()).unwrap())
+            .unwrap();
+    response.share_index = 3;
+    let relabeled = encode_device_share_response_v1(&response).unwrap();
+    assert_eq!(
+        rejected_reason(a.unlock_device_shares(unlock, &[relabeled])),
+        "share index does not match the device"
+    );
+}
+
+#[test]
+fn splits_are_validated_and_exclude_kms_mode() {
+    let [storage_a, _, _] = split_vault();
+    let mut a = device(&storage_a, "a");
+    let session_id = a.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert!(matches!(
+        a.enable_device_share_unlock(&session_id, 2, vec![device_id("a"), device_id("b")]),
+        Err(KeyServiceError::StepUpRequired)
+    ));
+    a.step_up(&session_id, b"pass").expect("step up");
+    assert_eq!(
+        rejected_reason(a.enable_device_share_unlock(
+            &session_id,
+            3,
+            vec![device_id("a"), device_id("b")]
+        )),
+        "threshold out of range"
+    );
+    assert_eq!(
+        rejected_reason(a.enable_device_share_unlock(
+            &session_id,
+            2,
+            vec![device_id("a"), device_id("a")]
+        )),
+        "device list has duplicates or too many devices"
+    );
+    assert_eq!(
+        rejected_reason(a.enroll_device_share(&session_id, &device_id("d"))),
+        "device is not in the split"
+    );
+    assert!(matches!(
+        a.enable_kms_wrap(&session_id, b"pass", "kms-key-1"),
+        Err(KeyServiceError::KmsWrapRejected {
+            reason: "device shares bypass the KMS"
+        })
+    ));
+
+    a.disable_device_share_unlock(&session_id)
+        .expect("disable device shares");
+    assert_eq!(a.get_vault_info().unwrap().device_share_threshold, None);
+    assert_eq!(
+        rejected_reason(a.begin_device_share_unlock()),
+        "device shares not enabled"
+    );
+}
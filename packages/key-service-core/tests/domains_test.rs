@@ -0,0 +1,52 @@
+use mo_key_service_core::crypto::{hkdf_sha256, HkdfHash};
+use mo_key_service_core::domains::{
+    HkdfDomain, AAD_TAGS, HKDF_DOMAINS, KEY_COMMIT_TAG_V1, KEY_ENVELOPE_HYBRID_KEM_1,
+    STREAM_AEAD_KEY_V1, USER_PRESENCE_UNWRAP_K_VAULT_V1,
+};
+use std::collections::HashSet;
+
+#[test]
+fn registry_entries_are_pinned_and_distinct() {
+    // Stored vaults and envelopes depend on these exact bytes.
+    assert_eq!(KEY_COMMIT_TAG_V1.info, b"mo-key-commit|tag|v1");
+    assert_eq!(
+        KEY_ENVELOPE_HYBRID_KEM_1.info,
+        b"mo-key-envelope|hybrid-kem-1"
+    );
+    assert_eq!(STREAM_AEAD_KEY_V1.info, b"mo-stream-aead|aead-1|v1");
+    assert_eq!(
+        USER_PRESENCE_UNWRAP_K_VAULT_V1.info,
+        b"mo-user-presence|unwrap-k-vault|v1"
+    );
+    assert!(HKDF_DOMAINS
+        .iter()
+        .all(|domain| domain.hash == HkdfHash::Sha256));
+
+    let infos: HashSet<&[u8]> = HKDF_DOMAINS.iter().map(|domain| domain.info).collect();
+    assert_eq!(infos.len(), HKDF_DOMAINS.len());
+    let tags: HashSet<&str> = AAD_TAGS.iter().copied().collect();
+    assert_eq!(tags.len(), AAD_TAGS.len());
+    assert!(AAD_TAGS.iter().all(|tag| tag.starts_with("mo-")));
+}
+
+#[test]
+fn domains_derive_with_their_hash() {
+    let ikm = [9u8; 32];
+    assert_eq!(
+        *KEY_COMMIT_TAG_V1.derive(&ikm, 32).unwrap(),
+        *hkdf_sha256(&ikm, b"mo-key-commit|tag|v1", 32).unwrap()
+    );
+    assert_eq!(
+        *STREAM_AEAD_KEY_V1.derive_with(&ikm, b"salt", 32).unwrap(),
+        *hkdf_sha256(&ikm, b"mo-stream-aead|aead-1|v1salt", 32).unwrap()
+    );
+
+    let wide = HkdfDomain::sha512(b"mo-test|wide|v1");
+    let narrow = HkdfDomain::sha256(b"mo-test|wide|v1");
+    assert_ne!(
+        *wide.derive(&ikm, 32).unwrap(),
+        *narrow.derive(&ikm, 32).unwrap()
+    );
+    assert_eq!(wide.derive(&ikm, 255 * 64).unwrap().len(), 255 * 64);
+    assert!(narrow.derive(&ikm, 255 * 64).is_err());
+}
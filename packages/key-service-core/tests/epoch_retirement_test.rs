@@ -0,0 +1,260 @@
+use aes_gcm::Aes256Gcm;
+use mo_key_service_core::aad::aad_resource_grant_wrap_v1;
+use mo_key_service_core::ciphersuite::{
+    generate_device_signing_keypair, hybrid_sign, HybridSignatureKeypair, SignerKeys,
+};
+use mo_key_service_core::crypto::aead_encrypt;
+use mo_key_service_core::domains::{SIG_RESOURCE_GRANT_V1, SIG_SCOPE_STATE_V1};
+use mo_key_service_core::formats::{
+    encode_resource_grant_v1, encode_scope_state_v1, ResourceGrantV1, ScopeStatePayload,
+    ScopeStateV1,
+};
+use mo_key_service_core::key_service::{CapabilityRequest, KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, signer_fingerprint, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    AeadId, CapabilityOps, DeviceId, EpochRetirement, KeyHandle, ResourceId, ResourceKeyId,
+    ScopeEpoch, ScopeId, SessionId, SigCiphersuiteId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn signer_keys(signer: &HybridSignatureKeypair) -> SignerKeys {
+    SignerKeys {
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        ed25519_pub: signer.ed25519_pub.clone(),
+        mldsa_pub: signer.mldsa_pub.clone(),
+    }
+}
+
+fn scope_key(epoch: u64) -> Vec<u8> {
+    vec![epoch as u8; 32]
+}
+
+/// Signs and ingests scope state `seq` of `scope-1`; returns its scope state ref.
+fn ingest_scope_state(
+    core: &mut Core,
+    session_id: &SessionId,
+    signer: &HybridSignatureKeypair,
+    seq: u64,
+    scope_epoch: u64,
+    payload: ScopeStatePayload,
+) -> Vec<u8> {
+    let mut scope_state = ScopeStateV1 {
+        v: 1,
+        scope_id: ScopeId("scope-1".to_string()),
+        scope_state_seq: seq,
+        prev_hash: vec![if seq == 1 { 0u8 } else { 7u8 }; 32],
+        scope_epoch,
+        kind: payload.kind(),
+        payload: payload.to_cbor(),
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    scope_state.signature = hybrid_sign(
+        SIG_SCOPE_STATE_V1,
+        &scope_state.to_be_signed_bytes().unwrap(),
+        signer,
+    )
+    .unwrap();
+    core.ingest_scope_state(
+        session_id,
+        &encode_scope_state_v1(&scope_state).unwrap(),
+        Some(signer_fingerprint(&signer_keys(signer))),
+    )
+    .expect("ingest scope state");
+    scope_state.scope_state_ref_bytes().unwrap()
+}
+
+/// Grants for `res-1`, chained in order, one per `(scope_epoch, resource_key_id)`.
+fn grants(
+    scope_state_ref: &[u8],
+    signer: &HybridSignatureKeypair,
+    keys: &[(u64, &str)],
+) -> Vec<Vec<u8>> {
+    let mut prev_hash = vec![0u8; 32];
+    let mut out = Vec::new();
+    for (grant_seq, (scope_epoch, resource_key_id)) in keys.iter().enumerate() {
+        let aad = aad_resource_grant_wrap_v1(
+            "scope-1",
+            "res-1",
+            *scope_epoch,
+            resource_key_id,
+            AeadId::Aead1,
+        )
+        .unwrap();
+        let nonce = vec![9u8; 12];
+        let mut grant = ResourceGrantV1 {
+            v: 1,
+            grant_id: format!("grant-{grant_seq}"),
+            scope_id: ScopeId("scope-1".to_string()),
+            grant_seq: grant_seq as u64,
+            prev_hash,
+            scope_state_ref: scope_state_ref.to_vec(),
+            scope_epoch: *scope_epoch,
+            resource_id: ResourceId("res-1".to_string()),
+            resource_key_id: ResourceKeyId(resource_key_id.to_string()),
+            policy: None,
+            aead: AeadId::Aead1,
+            nonce: nonce.clone(),
+            wrapped_key: aead_encrypt::<Aes256Gcm>(
+                &scope_key(*scope_epoch),
+                &aad,
+                &[4u8; 32],
+                &nonce,
+            )
+            .unwrap(),
+            signer_device_id: DeviceId("device-1".to_string()),
+            sig_suite: SigCiphersuiteId::HybridSig1,
+            signature: Vec::new(),
+        };
+        grant.signature = hybrid_sign(
+            SIG_RESOURCE_GRANT_V1,
+            &grant.to_be_signed_bytes().unwrap(),
+            signer,
+        )
+        .unwrap();
+        prev_hash = grant.grant_ref_bytes().unwrap();
+        out.push(encode_resource_grant_v1(&grant).unwrap());
+    }
+    out
+}
+
+fn open_resource(
+    core: &mut Core,
+    session_id: &SessionId,
+    scope_epoch: u64,
+    grant: &[u8],
+    ops: CapabilityOps,
+) -> Result<KeyHandle, KeyServiceError> {
+    let scope_handle = core
+        .open_scope(
+            session_id,
+            ScopeId("scope-1".to_string()),
+            ScopeEpoch(scope_epoch),
+        )
+        .expect("open scope");
+    core.open_resource_with_ops(session_id, &scope_handle.scope_key_handle, grant, ops)
+        .map(|response| response.resource_key_handle)
+}
+
+fn epoch_bump(
+    signer: &HybridSignatureKeypair,
+    from_epoch: u64,
+    retirement: EpochRetirement,
+) -> ScopeStatePayload {
+    ScopeStatePayload::EpochBump {
+        signer: signer_keys(signer),
+        from_epoch,
+        to_epoch: from_epoch + 1,
+        retirement,
+    }
+}
+
+#[test]
+fn epoch_bump_makes_older_epochs_decrypt_only() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(17),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let owner = generate_device_signing_keypair().expect("signer");
+    let genesis = ScopeStatePayload::Genesis {
+        signer: signer_keys(&owner),
+    };
+    let scope_state_ref = ingest_scope_state(&mut core, &session_id, &owner, 1, 1, genesis);
+    for epoch in 1..=3 {
+        core.persist_scope_key(
+            &session_id,
+            &ScopeId("scope-1".to_string()),
+            ScopeEpoch(epoch),
+            &scope_key(epoch),
+        )
+        .expect("persist scope key");
+    }
+    let grants = grants(
+        &scope_state_ref,
+        &owner,
+        &[(1, "rk-1"), (2, "rk-2"), (1, "rk-1")],
+    );
+    let old = open_resource(&mut core, &session_id, 1, &grants[0], CapabilityOps::ALL)
+        .expect("open epoch 1");
+    let ciphertext = core
+        .encrypt(&session_id, &old, b"aad", b"before")
+        .expect("encrypt before the bump")
+        .ciphertext;
+
+    ingest_scope_state(
+        &mut core,
+        &session_id,
+        &owner,
+        2,
+        2,
+        epoch_bump(&owner, 1, EpochRetirement::DecryptOnly),
+    );
+
+    // Handles opened before the bump keep decrypting but refuse to encrypt.
+    let err = core
+        .encrypt(&session_id, &old, b"aad", b"after")
+        .expect_err("encrypt under a retired epoch");
+    assert!(matches!(
+        err,
+        KeyServiceError::EpochRetired {
+            scope_epoch: ScopeEpoch(1),
+            ..
+        }
+    ));
+    assert_eq!(err.code(), "EpochRetired");
+    assert!(matches!(
+        core.encrypt_init(&session_id, &old, b"aad"),
+        Err(KeyServiceError::EpochRetired { .. })
+    ));
+    let plaintext = core
+        .decrypt(&session_id, &old, b"aad", &ciphertext)
+        .expect("decrypt under a retired epoch")
+        .plaintext;
+    assert_eq!(plaintext, b"before");
+    assert!(matches!(
+        core.mint_capability(
+            &session_id,
+            &old,
+            &CapabilityRequest {
+                aad_prefix: Vec::new(),
+                ops: CapabilityOps::ALL,
+                ttl_ms: 1_000,
+            },
+        ),
+        Err(KeyServiceError::EpochRetired { .. })
+    ));
+
+    let current = open_resource(&mut core, &session_id, 2, &grants[1], CapabilityOps::ALL)
+        .expect("open epoch 2");
+    core.encrypt(&session_id, &current, b"aad", b"current")
+        .expect("encrypt under the current epoch");
+    let encrypt_only = CapabilityOps {
+        encrypt: true,
+        decrypt: false,
+    };
+    assert!(matches!(
+        open_resource(&mut core, &session_id, 1, &grants[2], encrypt_only),
+        Err(KeyServiceError::EpochRetired { .. })
+    ));
+
+    // A bump that keeps its predecessor leaves epoch 2 writable.
+    ingest_scope_state(
+        &mut core,
+        &session_id,
+        &owner,
+        3,
+        3,
+        epoch_bump(&owner, 2, EpochRetirement::Keep),
+    );
+    core.encrypt(&session_id, &current, b"aad", b"still current")
+        .expect("encrypt after a keep bump");
+}
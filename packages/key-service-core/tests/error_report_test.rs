@@ -0,0 +1,78 @@
+use mo_key_service_core::cbor::{as_map, decode_canonical_value, req_text, CborLimits};
+use mo_key_service_core::error::{CoreError, ErrorDetail};
+use mo_key_service_core::key_service::KeyServiceError;
+use mo_key_service_core::types::{ScopeEpoch, ScopeId};
+use std::collections::HashSet;
+
+#[test]
+fn codes_are_unique_and_cover_reported_errors() {
+    let codes: HashSet<_> = KeyServiceError::CODES.iter().collect();
+    assert_eq!(codes.len(), KeyServiceError::CODES.len());
+    for error in [
+        KeyServiceError::StorageError("disk".to_string()),
+        KeyServiceError::WrongPassphrase,
+        KeyServiceError::CosignServerInvalid,
+        KeyServiceError::EpochRetired {
+            scope_id: ScopeId("scope-1".to_string()),
+            scope_epoch: ScopeEpoch(3),
+        },
+    ] {
+        assert!(codes.contains(&error.report().code));
+    }
+}
+
+#[test]
+fn core_error_codes_match_their_key_service_error() {
+    for error in [
+        || CoreError::Cbor("x".to_string()),
+        || CoreError::Format("x".to_string()),
+        || CoreError::Crypto("x".to_string()),
+        || CoreError::Entropy("x".to_string()),
+        || CoreError::Cancelled,
+    ] {
+        assert_eq!(error().code(), KeyServiceError::from(error()).code());
+    }
+}
+
+#[test]
+fn report_carries_variant_fields_as_context() {
+    let report = KeyServiceError::EpochRetired {
+        scope_id: ScopeId("scope-\"1\"".to_string()),
+        scope_epoch: ScopeEpoch(3),
+    }
+    .report();
+    assert_eq!(report.code, "EpochRetired");
+    assert!(!report.retryable);
+    assert_eq!(
+        report.context,
+        vec![
+            ("scopeId", ErrorDetail::Text("scope-\"1\"".to_string())),
+            ("scopeEpoch", ErrorDetail::Uint(3)),
+        ]
+    );
+    assert_eq!(
+        report.to_json(),
+        r#"{"code":"EpochRetired","message":"scope scope-\"1\" epoch 3 is retired for encryption","retryable":false,"context":{"scopeId":"scope-\"1\"","scopeEpoch":3}}"#
+    );
+
+    let storage = KeyServiceError::StorageError("disk\nfull".to_string()).report();
+    assert!(storage.retryable);
+    assert_eq!(
+        storage.to_json(),
+        r#"{"code":"StorageError","message":"storage error: disk\nfull","retryable":true}"#
+    );
+}
+
+#[test]
+fn report_cbor_is_canonical() {
+    let report = KeyServiceError::WeakPassphrase {
+        score: 1,
+        min_score: 3,
+    }
+    .report();
+    let bytes = report.to_cbor().expect("encode");
+    let value = decode_canonical_value(&bytes, &CborLimits::default()).expect("canonical");
+    let map = as_map(&value).expect("map");
+    assert_eq!(req_text(map, 0).expect("code"), "WeakPassphrase");
+    assert_eq!(req_text(map, 1).expect("message"), report.message);
+}
@@ -0,0 +1,185 @@
+use mo_key_service_core::cbor::{decode_canonical_value, CborLimits};
+use mo_key_service_core::ciphersuite::{generate_device_signing_keypair, hybrid_sign};
+use mo_key_service_core::domains::SIG_KEYVAULT_MANIFEST_V1;
+use mo_key_service_core::formats::{
+    decode_keyvault_snapshot, encode_keyvault_header_v1, encode_keyvault_snapshot_v1,
+    KeyVaultSnapshotV1,
+};
+use mo_key_service_core::hash::sha256;
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, KeyServicePolicy,
+};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{DeviceId, SessionId, SnapshotCompression, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+/// A stepped-up vault with a few blobs, and a device identity unless `device` is `None`.
+fn stepped_up(seed: u64, device: Option<&str>, require_manifest: bool) -> (Core, SessionId) {
+    let policy: KeyServicePolicy = test_policy()
+        .require_export_manifest(require_manifest)
+        .build()
+        .expect("policy");
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        config,
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    if let Some(device) = device {
+        core.init_identity(&session_id, &DeviceId(device.to_string()))
+            .expect("init identity");
+    }
+    for i in 0..4 {
+        core.put_vault_blob(&session_id, &format!("note-{i}"), &[b'a' + i as u8; 64])
+            .expect("put blob");
+    }
+    core.step_up(&session_id, b"pass").expect("step up");
+    (core, session_id)
+}
+
+fn decode(blob: &[u8]) -> KeyVaultSnapshotV1 {
+    let limits = CborLimits::default();
+    decode_keyvault_snapshot(decode_canonical_value(blob, &limits).unwrap(), &limits).unwrap()
+}
+
+fn manifest_error(result: Result<(), KeyServiceError>) -> &'static str {
+    match result {
+        Err(KeyServiceError::ExportManifestInvalid { reason }) => reason,
+        other => panic!("expected ExportManifestInvalid, got {other:?}"),
+    }
+}
+
+#[test]
+fn exports_carry_a_manifest_that_import_checks() {
+    let (mut core, session_id) = stepped_up(91, Some("device-1"), true);
+    let blob = core.export_keyvault(&session_id).expect("export");
+    let snapshot = decode(&blob);
+    let manifest = snapshot.manifest.as_ref().expect("manifest");
+    assert_eq!(manifest.record_count, snapshot.records.len() as u64);
+    assert_eq!(manifest.vault_id, snapshot.header.vault_id);
+    assert_eq!(manifest.signer_device_id, DeviceId("device-1".to_string()));
+    assert_eq!(manifest.created_at_ms, 1_000);
+
+    core.import_keyvault(&session_id, &blob).expect("import");
+    let compressed = core
+        .export_keyvault_compressed(&session_id, SnapshotCompression::Deflate)
+        .expect("compressed export");
+    core.import_keyvault(&session_id, &compressed)
+        .expect("import compressed");
+
+    // The incremental decoder reads the manifest after the records.
+    let stream_id = core.import_keyvault_init(&session_id).unwrap().stream_id;
+    for piece in blob.chunks(97) {
+        core.import_keyvault_push(&session_id, &stream_id, piece)
+            .expect("import push");
+    }
+    core.import_keyvault_finish(&session_id, &stream_id)
+        .expect("import finish");
+}
+
+#[test]
+fn tampered_or_truncated_snapshots_are_refused() {
+    let (mut core, session_id) = stepped_up(92, Some("device-1"), false);
+    let snapshot = decode(&core.export_keyvault(&session_id).expect("export"));
+    let mut import = |snapshot: &KeyVaultSnapshotV1| {
+        core.import_keyvault(&session_id, &encode_keyvault_snapshot_v1(snapshot).unwrap())
+    };
+
+    let mut truncated = snapshot.clone();
+    truncated.records.pop();
+    assert_eq!(
+        manifest_error(import(&truncated)),
+        "record count does not match the manifest"
+    );
+
+    let mut tampered = snapshot.clone();
+    tampered.records[1].ct[0] ^= 1;
+    assert_eq!(manifest_error(import(&tampered)), "record chain broken");
+
+    let mut tampered = snapshot.clone();
+    tampered.records.last_mut().unwrap().ct[0] ^= 1;
+    assert_eq!(
+        manifest_error(import(&tampered)),
+        "chain head does not match the manifest"
+    );
+
+    let mut tampered = snapshot.clone();
+    tampered.header.kdf.iterations += 1;
+    assert_eq!(
+        manifest_error(import(&tampered)),
+        "header does not match the manifest"
+    );
+
+    let mut forged = snapshot.clone();
+    forged.manifest.as_mut().unwrap().record_count += 1;
+    forged.records.push(forged.records[0].clone());
+    assert_eq!(
+        manifest_error(import(&forged)),
+        "manifest signature invalid"
+    );
+}
+
+#[test]
+fn required_manifests_must_come_from_this_vault() {
+    let (mut core, session_id) = stepped_up(93, Some("device-1"), true);
+
+    let (mut bare, bare_session) = stepped_up(94, None, false);
+    let unsigned = bare.export_keyvault(&bare_session).expect("export");
+    assert!(decode(&unsigned).manifest.is_none());
+    assert_eq!(
+        manifest_error(core.import_keyvault(&session_id, &unsigned)),
+        "snapshot has no manifest"
+    );
+
+    let (mut other, other_session) = stepped_up(95, Some("device-9"), false);
+    let foreign = other.export_keyvault(&other_session).expect("export");
+    assert!(matches!(
+        core.import_keyvault(&session_id, &foreign),
+        Err(KeyServiceError::UntrustedSigner)
+    ));
+
+    // Without the policy a snapshot may lack a manifest, but one that has it is still pinned.
+    other
+        .import_keyvault(&other_session, &unsigned)
+        .expect("import unsigned");
+    assert!(matches!(
+        bare.import_keyvault(&bare_session, &foreign),
+        Err(KeyServiceError::UntrustedSigner)
+    ));
+}
+
+#[test]
+fn manifests_re_signed_by_another_key_are_refused() {
+    let (mut core, session_id) = stepped_up(96, Some("device-1"), false);
+    let mut snapshot = decode(&core.export_keyvault(&session_id).expect("export"));
+    snapshot.header.kdf.iterations += 1;
+    let attacker = generate_device_signing_keypair().expect("attacker");
+    let manifest = snapshot.manifest.as_mut().unwrap();
+    manifest.header_hash = sha256(&encode_keyvault_header_v1(&snapshot.header).unwrap()).to_vec();
+    manifest.signer.ed25519_pub = attacker.ed25519_pub.clone();
+    manifest.signer.mldsa_pub = attacker.mldsa_pub.clone();
+    manifest.signature = hybrid_sign(
+        SIG_KEYVAULT_MANIFEST_V1,
+        &manifest.to_be_signed_bytes().unwrap(),
+        &attacker,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        core.import_keyvault(
+            &session_id,
+            &encode_keyvault_snapshot_v1(&snapshot).unwrap()
+        ),
+        Err(KeyServiceError::UntrustedSigner)
+    ));
+}
@@ -0,0 +1,59 @@
+use mo_key_service_core::crypto::{KdfParams, INSECURE_TEST_KDF_ID};
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, KeyServicePolicy,
+};
+use mo_key_service_core::testkit::{MemoryStorage, SeededEntropy, VirtualClock};
+use mo_key_service_core::types::UserId;
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn core(seed: u64, allow: bool) -> Core {
+    let policy = KeyServicePolicy::builder()
+        .allow_insecure_test_kdf(allow)
+        .build()
+        .expect("policy");
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        config,
+    )
+}
+
+#[test]
+fn insecure_profile_is_rejected_unless_the_policy_allows_it() {
+    let params = KdfParams::insecure_fast_for_tests();
+    assert_eq!(params.id, INSECURE_TEST_KDF_ID);
+
+    let mut strict = core(1, false);
+    assert!(matches!(
+        strict.create_new_vault(UserId("user-1".to_string()), b"pass", params),
+        Err(KeyServiceError::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn insecure_profile_survives_unlock_and_passphrase_change() {
+    let mut core = core(2, true);
+    core.create_new_vault(
+        UserId("user-1".to_string()),
+        b"pass",
+        KdfParams::insecure_fast_for_tests(),
+    )
+    .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.change_passphrase(&session_id, b"new pass")
+        .expect("change passphrase");
+    core.lock(&session_id).expect("lock");
+
+    assert!(matches!(
+        core.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    core.unlock_passphrase(b"new pass").expect("unlock");
+}
@@ -0,0 +1,413 @@
+use ciborium::value::{Integer, Value};
+use hmac::{Hmac, Mac};
+use mo_key_service_core::adapters::HidAuthenticatorAdapter;
+use mo_key_service_core::fido2::{Fido2Authenticator, Fido2Error};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{SessionAssurance, UserId};
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::{EncodedPoint, FieldBytes, PublicKey, SecretKey};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+const RP_ID: &str = "mo.example";
+const CID: [u8; 4] = [0x0a, 0x0b, 0x0c, 0x0d];
+
+fn core(seed: u64) -> Core {
+    KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    )
+}
+
+/// A CTAP2 authenticator in software: CTAPHID framing, getInfo, clientPIN getKeyAgreement,
+/// and makeCredential/getAssertion with `hmac-secret`.
+#[derive(Clone)]
+struct SoftAuthenticator(Arc<Mutex<SoftState>>);
+
+struct SoftState {
+    protocols: Vec<i64>,
+    hmac_secret: bool,
+    key_agreement: SecretKey,
+    credentials: BTreeMap<Vec<u8>, [u8; 32]>,
+    incoming: Option<(u8, usize, Vec<u8>)>,
+    outgoing: VecDeque<[u8; 64]>,
+}
+
+impl SoftAuthenticator {
+    fn new(protocols: Vec<i64>, hmac_secret: bool) -> Self {
+        Self(Arc::new(Mutex::new(SoftState {
+            protocols,
+            hmac_secret,
+            key_agreement: SecretKey::from_slice(&[0x42; 32]).expect("key"),
+            credentials: BTreeMap::new(),
+            incoming: None,
+            outgoing: VecDeque::new(),
+        })))
+    }
+
+    fn cred_random(&self, credential_id: &[u8]) -> [u8; 32] {
+        self.0.lock().unwrap().credentials[credential_id]
+    }
+}
+
+impl HidAuthenticatorAdapter for SoftAuthenticator {
+    type Error = ();
+
+    fn write_report(&self, report: &[u8; 64]) -> Result<(), ()> {
+        let mut state = self.0.lock().unwrap();
+        if report[4] & 0x80 != 0 {
+            let len = u16::from_be_bytes([report[5], report[6]]) as usize;
+            let take = len.min(57);
+            state.incoming = Some((report[4], len, report[7..7 + take].to_vec()));
+        } else if let Some((_, len, data)) = state.incoming.as_mut() {
+            let take = (*len - data.len()).min(59);
+            data.extend_from_slice(&report[5..5 + take]);
+        }
+        let complete = matches!(&state.incoming, Some((_, len, data)) if data.len() == *len);
+        if complete {
+            let (cmd, _, payload) = state.incoming.take().unwrap();
+            let (cid, response) = match cmd {
+                0x86 => (
+                    [0xff; 4],
+                    [&payload[..8], &CID, &[2, 1, 0, 0, 0x04]].concat(),
+                ),
+                0x90 => {
+                    let mut keepalive = [0u8; 64];
+                    keepalive[..4].copy_from_slice(&CID);
+                    keepalive[4] = 0xbb;
+                    state.outgoing.push_back(keepalive);
+                    (CID, state.handle_cbor(payload[0], &payload[1..]))
+                }
+                _ => panic!("unexpected ctaphid command {cmd:#x}"),
+            };
+            state.send(cid, cmd, &response);
+        }
+        Ok(())
+    }
+
+    fn read_report(&self, _timeout_ms: u32) -> Result<Option<[u8; 64]>, ()> {
+        Ok(self.0.lock().unwrap().outgoing.pop_front())
+    }
+}
+
+impl SoftState {
+    fn send(&mut self, cid: [u8; 4], cmd: u8, payload: &[u8]) {
+        let mut report = [0u8; 64];
+        report[..4].copy_from_slice(&cid);
+        report[4] = cmd;
+        report[5..7].copy_from_slice(&(payload.len() as u16).to_be_bytes());
+        let first = payload.len().min(57);
+        report[7..7 + first].copy_from_slice(&payload[..first]);
+        self.outgoing.push_back(report);
+        for (seq, chunk) in payload[first..].chunks(59).enumerate() {
+            let mut report = [0u8; 64];
+            report[..4].copy_from_slice(&cid);
+            report[4] = seq as u8;
+            report[5..5 + chunk.len()].copy_from_slice(chunk);
+            self.outgoing.push_back(report);
+        }
+    }
+
+    fn handle_cbor(&mut self, command: u8, params: &[u8]) -> Vec<u8> {
+        let request: Value = if params.is_empty() {
+            Value::Null
+        } else {
+            ciborium::de::from_reader(params).expect("request cbor")
+        };
+        let response = match command {
+            0x04 => {
+                let mut extensions = Vec::new();
+                if self.hmac_secret {
+                    extensions.push(Value::Text("hmac-secret".to_string()));
+                }
+                int_map(vec![
+                    (1, Value::Array(vec![Value::Text("FIDO_2_1".to_string())])),
+                    (2, Value::Array(extensions)),
+                    (3, Value::Bytes(vec![0u8; 16])),
+                    (
+                        6,
+                        Value::Array(self.protocols.iter().map(|p| int(*p)).collect()),
+                    ),
+                ])
+            }
+            0x06 => int_map(vec![(1, cose_key(&self.key_agreement.public_key()))]),
+            0x01 => {
+                let rp_id = text(get(get(&request, 2), "id"));
+                let credential_id = vec![self.credentials.len() as u8 + 1; 16];
+                let mut cred_random = [0u8; 32];
+                cred_random[0] = credential_id[0];
+                self.credentials.insert(credential_id.clone(), cred_random);
+                let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+                auth_data.push(0x01 | 0x40 | 0x80);
+                auth_data.extend_from_slice(&[0, 0, 0, 1]);
+                auth_data.extend_from_slice(&[0u8; 16]);
+                auth_data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+                auth_data.extend_from_slice(&credential_id);
+                auth_data.extend_from_slice(&encode(&cose_key(
+                    &SecretKey::from_slice(&[0x24; 32]).unwrap().public_key(),
+                )));
+                auth_data.extend_from_slice(&encode(&text_map(vec![(
+                    "hmac-secret",
+                    Value::Bool(true),
+                )])));
+                int_map(vec![
+                    (1, Value::Text("none".to_string())),
+                    (2, Value::Bytes(auth_data)),
+                    (3, Value::Map(Vec::new())),
+                ])
+            }
+            0x02 => {
+                let rp_id = text(get(&request, 1));
+                let Value::Array(allow) = get(&request, 3) else {
+                    panic!("allow list")
+                };
+                let credential_id = bytes(get(&allow[0], "id"));
+                let Some(cred_random) = self.credentials.get(&credential_id).copied() else {
+                    return vec![0x2e];
+                };
+                let input = get(get(&request, 4), "hmac-secret");
+                let protocol = match map_get(input, &int(4)) {
+                    Some(value) => as_i64(value),
+                    None => 1,
+                };
+                let (hmac_key, aes_key) = self.shared_keys(get(input, 1), protocol);
+                let salt_enc = bytes(get(input, 2));
+                let mut mac = Hmac::<Sha256>::new_from_slice(&hmac_key).unwrap();
+                mac.update(&salt_enc);
+                let tag = mac.finalize().into_bytes();
+                let salt_auth = bytes(get(input, 3));
+                assert_eq!(&tag[..salt_auth.len()], salt_auth.as_slice());
+                let salt = cbc_decrypt(&aes_key, protocol, &salt_enc);
+                let mut mac = Hmac::<Sha256>::new_from_slice(&cred_random).unwrap();
+                mac.update(&salt);
+                let output = mac.finalize().into_bytes();
+                let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+                auth_data.push(0x01 | 0x80);
+                auth_data.extend_from_slice(&[0, 0, 0, 2]);
+                auth_data.extend_from_slice(&encode(&text_map(vec![(
+                    "hmac-secret",
+                    Value::Bytes(cbc_encrypt(&aes_key, protocol, &output)),
+                )])));
+                int_map(vec![
+                    (
+                        1,
+                        text_map(vec![
+                            ("id", Value::Bytes(credential_id)),
+                            ("type", Value::Text("public-key".to_string())),
+                        ]),
+                    ),
+                    (2, Value::Bytes(auth_data)),
+                    (3, Value::Bytes(vec![0u8; 64])),
+                ])
+            }
+            _ => return vec![0x01],
+        };
+        [vec![0x00], encode(&response)].concat()
+    }
+
+    fn shared_keys(&self, platform_key: &Value, protocol: i64) -> (Vec<u8>, Vec<u8>) {
+        let point = EncodedPoint::from_affine_coordinates(
+            FieldBytes::from_slice(&bytes(get(platform_key, -2))),
+            FieldBytes::from_slice(&bytes(get(platform_key, -3))),
+            false,
+        );
+        let peer = PublicKey::from_encoded_point(&point).unwrap();
+        let z =
+            p256::ecdh::diffie_hellman(self.key_agreement.to_nonzero_scalar(), peer.as_affine());
+        let z = z.raw_secret_bytes();
+        if protocol == 1 {
+            let key = Sha256::digest(z).to_vec();
+            return (key.clone(), key);
+        }
+        let hkdf = hkdf::Hkdf::<Sha256>::new(Some(&[0u8; 32]), z);
+        let mut hmac_key = vec![0u8; 32];
+        let mut aes_key = vec![0u8; 32];
+        hkdf.expand(b"CTAP2 HMAC key", &mut hmac_key).unwrap();
+        hkdf.expand(b"CTAP2 AES key", &mut aes_key).unwrap();
+        (hmac_key, aes_key)
+    }
+}
+
+fn cbc_encrypt(key: &[u8], protocol: i64, plaintext: &[u8]) -> Vec<u8> {
+    use cbc::cipher::{block_padding::NoPadding, BlockEncryptMut, KeyIvInit};
+    let iv = if protocol == 1 { [0u8; 16] } else { [9u8; 16] };
+    let ct = cbc::Encryptor::<aes::Aes256>::new_from_slices(key, &iv)
+        .unwrap()
+        .encrypt_padded_vec_mut::<NoPadding>(plaintext);
+    if protocol == 1 {
+        ct
+    } else {
+        [iv.to_vec(), ct].concat()
+    }
+}
+
+fn cbc_decrypt(key: &[u8], protocol: i64, data: &[u8]) -> Vec<u8> {
+    use cbc::cipher::{block_padding::NoPadding, BlockDecryptMut, KeyIvInit};
+    let (iv, ct) = if protocol == 1 {
+        (&[0u8; 16][..], data)
+    } else {
+        data.split_at(16)
+    };
+    cbc::Decryptor::<aes::Aes256>::new_from_slices(key, iv)
+        .unwrap()
+        .decrypt_padded_vec_mut::<NoPadding>(ct)
+        .unwrap()
+}
+
+fn cose_key(public: &PublicKey) -> Value {
+    let point = public.to_encoded_point(false);
+    Value::Map(vec![
+        (int(1), int(2)),
+        (int(3), int(-25)),
+        (int(-1), int(1)),
+        (int(-2), Value::Bytes(point.x().unwrap().to_vec())),
+        (int(-3), Value::Bytes(point.y().unwrap().to_vec())),
+    ])
+}
+
+fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(value, &mut out).unwrap();
+    out
+}
+
+fn int(value: i64) -> Value {
+    Value::Integer(Integer::from(value))
+}
+
+fn as_i64(value: &Value) -> i64 {
+    match value {
+        Value::Integer(i) => i64::try_from(*i).unwrap(),
+        _ => panic!("expected integer"),
+    }
+}
+
+fn int_map(entries: Vec<(i64, Value)>) -> Value {
+    Value::Map(entries.into_iter().map(|(k, v)| (int(k), v)).collect())
+}
+
+fn text_map(entries: Vec<(&str, Value)>) -> Value {
+    Value::Map(
+        entries
+            .into_iter()
+            .map(|(k, v)| (Value::Text(k.to_string()), v))
+            .collect(),
+    )
+}
+
+trait Key {
+    fn value(&self) -> Value;
+}
+
+impl Key for i64 {
+    fn value(&self) -> Value {
+        int(*self)
+    }
+}
+
+impl Key for &str {
+    fn value(&self) -> Value {
+        Value::Text(self.to_string())
+    }
+}
+
+fn map_get<'a>(map: &'a Value, key: &Value) -> Option<&'a Value> {
+    match map {
+        Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+fn get(map: &Value, key: impl Key) -> &Value {
+    map_get(map, &key.value()).expect("map key")
+}
+
+fn bytes(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Bytes(bytes) => bytes.clone(),
+        _ => panic!("expected bytes"),
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Text(text) => text.clone(),
+        _ => panic!("expected text"),
+    }
+}
+
+#[test]
+fn security_key_enrolls_and_unlocks_the_vault() {
+    let mut core = core(1);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let authenticator = Fido2Authenticator::open(
+        SoftAuthenticator::new(vec![2, 1], true),
+        &SeededEntropy::new(9),
+    )
+    .expect("open");
+
+    assert!(matches!(
+        core.enable_user_presence_unlock_fido2(&session_id, &authenticator, RP_ID),
+        Err(KeyServiceError::StepUpRequired)
+    ));
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.enable_user_presence_unlock_fido2(&session_id, &authenticator, RP_ID)
+        .expect("enroll");
+    core.lock(&session_id).expect("lock");
+
+    let unlock = core
+        .unlock_user_presence_fido2(&authenticator, RP_ID)
+        .expect("unlock with security key");
+    assert_eq!(unlock.assurance, SessionAssurance::HardwareBacked);
+}
+
+#[test]
+fn prf_output_matches_webauthn_prf_for_both_pin_protocols() {
+    for protocol in [1, 2] {
+        let soft = SoftAuthenticator::new(vec![protocol], true);
+        let entropy = SeededEntropy::new(protocol as u64);
+        let authenticator = Fido2Authenticator::open(soft.clone(), &entropy).expect("open");
+        let credential_id = authenticator
+            .make_credential(RP_ID, b"user-1", &entropy)
+            .expect("make credential");
+        let output = authenticator
+            .prf(RP_ID, &credential_id, b"vault salt", &entropy)
+            .expect("prf");
+
+        let salt = Sha256::digest(b"WebAuthn PRF\x00vault salt");
+        let mut mac = Hmac::<Sha256>::new_from_slice(&soft.cred_random(&credential_id)).unwrap();
+        mac.update(&salt);
+        assert_eq!(output.as_slice(), mac.finalize().into_bytes().as_slice());
+    }
+}
+
+#[test]
+fn authenticator_without_hmac_secret_is_rejected() {
+    let entropy = SeededEntropy::new(3);
+    let authenticator =
+        Fido2Authenticator::open(SoftAuthenticator::new(vec![2], false), &entropy).expect("open");
+    assert!(matches!(
+        authenticator.make_credential(RP_ID, b"user-1", &entropy),
+        Err(Fido2Error::Unsupported(_))
+    ));
+
+    let mut core = core(4);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    assert!(matches!(
+        core.enable_user_presence_unlock_fido2(&session_id, &authenticator, RP_ID),
+        Err(KeyServiceError::AuthenticatorError(_))
+    ));
+}
@@ -0,0 +1,143 @@
+use mo_key_service_core::cbor::{decode_canonical_value, CborLimits};
+use mo_key_service_core::formats::{decode_keyvault_snapshot, encode_keyvault_snapshot_v1};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::keyvault::{APP_BLOB_RECORD_KIND, CHECKPOINT_RECORD_KIND};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    DeviceId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, SessionId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+const SCOPE_KEY_KIND: u64 = 3;
+
+fn stepped_up(seed: u64) -> (Core, SessionId) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    (core, session_id)
+}
+
+fn kinds(core: &mut Core, session_id: &SessionId) -> Vec<u64> {
+    core.list_records_metadata(session_id)
+        .expect("metadata")
+        .iter()
+        .map(|record| record.kind)
+        .collect()
+}
+
+#[test]
+fn filtered_export_keeps_only_the_selected_kinds() {
+    let (mut source, source_session) = stepped_up(47);
+    source
+        .init_identity(&source_session, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    for epoch in 1..=2 {
+        source
+            .persist_scope_key(
+                &source_session,
+                &ScopeId("scope-1".to_string()),
+                ScopeEpoch(epoch),
+                &[epoch as u8; 32],
+            )
+            .expect("persist scope key");
+        source
+            .put_vault_blob(&source_session, "settings", &[epoch as u8])
+            .expect("put blob");
+    }
+    source
+        .persist_resource_key(
+            &source_session,
+            &ResourceId("res-1".to_string()),
+            &ResourceKeyId("rk-1".to_string()),
+            &[4u8; 32],
+        )
+        .expect("persist resource key");
+    source
+        .write_checkpoint(&source_session)
+        .expect("write checkpoint");
+
+    for rejected in [&[][..], &[SCOPE_KEY_KIND, CHECKPOINT_RECORD_KIND][..]] {
+        assert!(matches!(
+            source.export_keyvault_filtered(&source_session, rejected),
+            Err(KeyServiceError::InvalidFormat(_))
+        ));
+    }
+    let blob = source
+        .export_keyvault_filtered(&source_session, &[SCOPE_KEY_KIND, APP_BLOB_RECORD_KIND])
+        .expect("filtered export");
+
+    // The export is signed by device-1, which the unrelated target vault cannot pin, so the
+    // target takes the records without the manifest.
+    let limits = CborLimits::default();
+    let mut snapshot =
+        decode_keyvault_snapshot(decode_canonical_value(&blob, &limits).unwrap(), &limits).unwrap();
+    assert!(snapshot.manifest.take().is_some());
+    let blob = encode_keyvault_snapshot_v1(&snapshot).unwrap();
+
+    // The kept records form a chain of their own, interleaved in their original order.
+    let (mut target, target_session) = stepped_up(48);
+    target
+        .import_keyvault(&target_session, &blob)
+        .expect("import filtered export");
+    target.lock(&target_session).expect("lock");
+    let session_id = target
+        .unlock_passphrase(b"pass")
+        .expect("unlock")
+        .session_id;
+    assert!(target.verify_keyvault(&session_id).expect("verify").ok);
+    let metadata = target.list_records_metadata(&session_id).expect("metadata");
+    assert_eq!(
+        metadata.iter().map(|r| r.seq).collect::<Vec<_>>(),
+        vec![1, 2, 3, 4]
+    );
+    assert_eq!(
+        kinds(&mut target, &session_id),
+        vec![
+            SCOPE_KEY_KIND,
+            APP_BLOB_RECORD_KIND,
+            SCOPE_KEY_KIND,
+            APP_BLOB_RECORD_KIND
+        ]
+    );
+    assert_eq!(
+        target
+            .get_vault_blob(&session_id, "settings")
+            .unwrap()
+            .unwrap()
+            .as_slice(),
+        &[2u8]
+    );
+    target
+        .open_scope(&session_id, ScopeId("scope-1".to_string()), ScopeEpoch(2))
+        .expect("open exported scope key");
+
+    // Filtering never touches the source vault.
+    assert_eq!(kinds(&mut source, &source_session).len(), 8);
+}
+
+#[test]
+fn filtered_export_requires_step_up() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(49),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert!(matches!(
+        core.export_keyvault_filtered(&session_id, &[SCOPE_KEY_KIND]),
+        Err(KeyServiceError::StepUpRequired)
+    ));
+}
@@ -0,0 +1,161 @@
+use mo_key_service_core::error::ErrorDetail;
+use mo_key_service_core::formats::{encode_key_envelope_v1, KeyEnvelopeV1};
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    AeadId, DeviceId, IdCharset, IdPolicy, KemCiphersuiteId, ScopeEpoch, ScopeId, SessionId,
+    SigCiphersuiteId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn service(config: KeyServiceConfig) -> Core {
+    KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(1),
+        config,
+    )
+}
+
+fn unlocked() -> (Core, SessionId) {
+    let mut core = service(test_config());
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    (core, session_id)
+}
+
+fn envelope(scope_id: &str) -> Vec<u8> {
+    encode_key_envelope_v1(&KeyEnvelopeV1 {
+        v: 1,
+        envelope_id: "env-1".to_string(),
+        scope_id: ScopeId(scope_id.to_string()),
+        scope_epoch: ScopeEpoch(1),
+        recipient_user_id: UserId("user-1".to_string()),
+        scope_state_ref: vec![1u8; 32],
+        kem: KemCiphersuiteId::HybridKem1,
+        aead: AeadId::Aead1,
+        enc: vec![2u8; 32],
+        nonce: vec![3u8; 12],
+        wrapped_scope_key: vec![4u8; 48],
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: vec![5u8; 64],
+        recipient_uk_pub_fingerprint: None,
+        prekey_id: None,
+    })
+    .expect("encode")
+}
+
+#[test]
+fn policy_rejects_empty_long_and_control_ids() {
+    let policy = IdPolicy::default();
+    assert_eq!(policy.violation("scope-1"), None);
+    assert_eq!(policy.violation("Ümlaut scope"), None);
+    assert_eq!(policy.violation(""), Some("empty"));
+    assert_eq!(policy.violation(&"a".repeat(257)), Some("too long"));
+    assert_eq!(
+        policy.violation("scope\u{0}1"),
+        Some("disallowed character")
+    );
+
+    let url_safe = IdPolicy {
+        charset: IdCharset::UrlSafe,
+        ..IdPolicy::default()
+    };
+    assert_eq!(url_safe.violation("scope_1.v2:a-b"), None);
+    assert_eq!(url_safe.violation("scope 1"), Some("disallowed character"));
+    assert_eq!(url_safe.violation("Ümlaut"), Some("disallowed character"));
+}
+
+#[test]
+fn vault_creation_checks_the_user_id() {
+    let mut core = service(test_config());
+    let err = core
+        .create_new_vault(UserId("user\n1".to_string()), b"pass", fast_kdf())
+        .expect_err("control character");
+    assert!(matches!(
+        err,
+        KeyServiceError::InvalidId {
+            kind: "userId",
+            reason: "disallowed character",
+        }
+    ));
+    assert_eq!(
+        err.report().context,
+        vec![
+            ("kind", ErrorDetail::Text("userId".to_string())),
+            (
+                "reason",
+                ErrorDetail::Text("disallowed character".to_string())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn max_length_is_configurable() {
+    let long_device = DeviceId("d".repeat(300));
+    let (mut core, session_id) = unlocked();
+    let err = core
+        .init_identity(&session_id, &long_device)
+        .expect_err("too long");
+    assert!(matches!(
+        err,
+        KeyServiceError::InvalidId {
+            kind: "deviceId",
+            reason: "too long",
+        }
+    ));
+
+    let policy = test_policy()
+        .id_policy(IdPolicy {
+            max_bytes: 512,
+            ..IdPolicy::default()
+        })
+        .build()
+        .expect("policy");
+    let mut core = service(
+        KeyServiceConfig::builder()
+            .policy(policy)
+            .build()
+            .expect("config"),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &long_device)
+        .expect("init identity");
+
+    assert!(test_policy()
+        .id_policy(IdPolicy {
+            max_bytes: 0,
+            ..IdPolicy::default()
+        })
+        .build()
+        .is_err());
+}
+
+#[test]
+fn ingested_ids_are_checked_before_anything_else() {
+    let (mut core, session_id) = unlocked();
+    let err = core
+        .ingest_key_envelope(&session_id, &envelope("scope\u{7}"))
+        .expect_err("bad scope id");
+    assert!(matches!(
+        err,
+        KeyServiceError::InvalidId {
+            kind: "scopeId",
+            ..
+        }
+    ));
+
+    // A well-formed id gets past the check and fails on the unknown scope instead.
+    let err = core
+        .ingest_key_envelope(&session_id, &envelope("scope-1"))
+        .expect_err("unknown scope");
+    assert!(!matches!(err, KeyServiceError::InvalidId { .. }));
+}
@@ -0,0 +1,238 @@
+use aes_gcm::Aes256Gcm;
+use mo_key_service_core::aad::aad_key_envelope_wrap_v1;
+use mo_key_service_core::ciphersuite::{decode_user_public_bytes, hybrid_kem_encapsulate};
+use mo_key_service_core::crypto::aead_encrypt;
+use mo_key_service_core::formats::{
+    encode_key_envelope_v1, encode_scope_state_v1, KeyEnvelopeV1, ScopeStatePayload, ScopeStateV1,
+};
+use mo_key_service_core::key_service::{DevicePublicKey, IngestWarning, KeyService, SignedFormat};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    AeadId, DeviceId, EpochRetirement, KemCiphersuiteId, ScopeEpoch, ScopeId, SessionId,
+    SigCiphersuiteId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn unlocked(seed: u64, device_id: &str) -> (Core, SessionId, DevicePublicKey) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId(device_id.to_string()))
+        .expect("init identity");
+    let device = core
+        .get_device_public_keys(&session_id)
+        .expect("device keys")
+        .devices
+        .remove(0);
+    (core, session_id, device)
+}
+
+fn scope_state(
+    core: &mut Core,
+    session_id: &SessionId,
+    device: &DevicePublicKey,
+    payload: ScopeStatePayload,
+    epoch: u64,
+) -> ScopeStateV1 {
+    let mut scope_state = ScopeStateV1 {
+        v: 1,
+        scope_id: ScopeId("scope-1".to_string()),
+        scope_state_seq: epoch,
+        prev_hash: vec![if epoch == 1 { 0u8 } else { 7u8 }; 32],
+        scope_epoch: epoch,
+        kind: payload.kind(),
+        payload: payload.to_cbor(),
+        signer_device_id: device.device_id.clone(),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    scope_state.signature = core
+        .sign_format(
+            session_id,
+            SignedFormat::ScopeState,
+            &scope_state.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign scope state")
+        .signature;
+    scope_state
+}
+
+fn ingest(
+    core: &mut Core,
+    session_id: &SessionId,
+    scope_state: &ScopeStateV1,
+    fingerprint: &str,
+) -> Vec<IngestWarning> {
+    core.ingest_scope_state(
+        session_id,
+        &encode_scope_state_v1(scope_state).unwrap(),
+        Some(fingerprint.to_string()),
+    )
+    .expect("ingest scope state")
+    .warnings
+}
+
+/// A scope key for epoch 1, wrapped to the vault's own user key under `genesis`.
+fn epoch_one_envelope(core: &mut Core, session_id: &SessionId, genesis: &ScopeStateV1) -> Vec<u8> {
+    let public = core.get_user_public_key(session_id).expect("user key");
+    let encap = hybrid_kem_encapsulate(
+        &decode_user_public_bytes(&public.public_bytes).unwrap(),
+        KemCiphersuiteId::HybridKem1,
+    )
+    .expect("encapsulate");
+    let scope_state_ref = genesis.scope_state_ref_bytes().unwrap();
+    let aad = aad_key_envelope_wrap_v1(
+        "scope-1",
+        1,
+        "user-1",
+        &scope_state_ref,
+        KemCiphersuiteId::HybridKem1,
+        AeadId::Aead1,
+        None,
+    )
+    .unwrap();
+    let nonce = vec![3u8; 12];
+    let mut envelope = KeyEnvelopeV1 {
+        v: 1,
+        envelope_id: "env-1".to_string(),
+        scope_id: ScopeId("scope-1".to_string()),
+        scope_epoch: ScopeEpoch(1),
+        recipient_user_id: UserId("user-1".to_string()),
+        scope_state_ref,
+        kem: KemCiphersuiteId::HybridKem1,
+        aead: AeadId::Aead1,
+        enc: encap.enc,
+        nonce: nonce.clone(),
+        wrapped_scope_key: aead_encrypt::<Aes256Gcm>(&encap.wrap_key, &aad, &[6u8; 32], &nonce)
+            .unwrap(),
+        signer_device_id: genesis.signer_device_id.clone(),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+        recipient_uk_pub_fingerprint: None,
+        prekey_id: None,
+    };
+    envelope.signature = core
+        .sign_format(
+            session_id,
+            SignedFormat::KeyEnvelope,
+            &envelope.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign envelope")
+        .signature;
+    encode_key_envelope_v1(&envelope).unwrap()
+}
+
+#[test]
+fn pins_and_older_epochs_are_reported_without_failing() {
+    let (mut core, session_id, device) = unlocked(51, "device-1");
+    let genesis = scope_state(
+        &mut core,
+        &session_id,
+        &device,
+        ScopeStatePayload::Genesis {
+            signer: device.signer.clone(),
+        },
+        1,
+    );
+    let bump = scope_state(
+        &mut core,
+        &session_id,
+        &device,
+        ScopeStatePayload::EpochBump {
+            signer: device.signer.clone(),
+            from_epoch: 1,
+            to_epoch: 2,
+            retirement: EpochRetirement::Keep,
+        },
+        2,
+    );
+
+    let warnings = ingest(&mut core, &session_id, &genesis, &device.fingerprint);
+    assert_eq!(
+        warnings,
+        vec![IngestWarning::FingerprintPinned {
+            device_id: device.device_id.clone(),
+        }]
+    );
+    assert_eq!(warnings[0].code(), "FingerprintPinned");
+    assert!(ingest(&mut core, &session_id, &bump, &device.fingerprint).is_empty());
+
+    let older = IngestWarning::OlderEpoch {
+        scope_epoch: ScopeEpoch(1),
+        latest_epoch: ScopeEpoch(2),
+    };
+    assert_eq!(
+        ingest(&mut core, &session_id, &genesis, &device.fingerprint),
+        vec![older.clone()]
+    );
+    assert_eq!(older.to_string(), "epoch 1 older than known epoch 2");
+
+    let envelope = epoch_one_envelope(&mut core, &session_id, &genesis);
+    let response = core
+        .ingest_key_envelope(&session_id, &envelope)
+        .expect("ingest envelope");
+    assert!(response.is_new);
+    assert_eq!(response.warnings, vec![older]);
+    assert!(core
+        .ingest_key_envelope(&session_id, &envelope)
+        .expect("replay")
+        .warnings
+        .is_empty());
+}
+
+#[test]
+fn a_self_introduced_signer_in_a_known_scope_is_flagged() {
+    let (mut owner, owner_session, owner_device) = unlocked(52, "device-1");
+    let (mut stranger, stranger_session, stranger_device) = unlocked(53, "device-2");
+    let (mut reader, reader_session, _) = unlocked(54, "device-3");
+
+    let genesis = scope_state(
+        &mut owner,
+        &owner_session,
+        &owner_device,
+        ScopeStatePayload::Genesis {
+            signer: owner_device.signer.clone(),
+        },
+        1,
+    );
+    ingest(
+        &mut reader,
+        &reader_session,
+        &genesis,
+        &owner_device.fingerprint,
+    );
+
+    let intro = scope_state(
+        &mut stranger,
+        &stranger_session,
+        &stranger_device,
+        ScopeStatePayload::Genesis {
+            signer: stranger_device.signer.clone(),
+        },
+        1,
+    );
+    let device_id = stranger_device.device_id.clone();
+    assert_eq!(
+        ingest(
+            &mut reader,
+            &reader_session,
+            &intro,
+            &stranger_device.fingerprint
+        ),
+        vec![
+            IngestWarning::FingerprintPinned {
+                device_id: device_id.clone(),
+            },
+            IngestWarning::SignerPreviouslyUnseen { device_id },
+        ]
+    );
+}
@@ -0,0 +1,133 @@
+use mo_key_service_core::adapters::StorageAdapter;
+use mo_key_service_core::crypto::{KdfMinimums, KdfParams};
+use mo_key_service_core::error::{CoreError, ErrorDetail};
+use mo_key_service_core::formats::{decode_keyvault_header_v1, encode_keyvault_header_v1};
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, KeyServicePolicy,
+};
+use mo_key_service_core::testkit::{MemoryStorage, SeededEntropy, VirtualClock};
+use mo_key_service_core::types::UserId;
+
+/// Cheap `kdf-1` parameters, below the default floor.
+fn weak_kdf() -> KdfParams {
+    KdfParams {
+        id: "kdf-1".to_string(),
+        salt: vec![7u8; 16],
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    }
+}
+
+fn config_with(minimums: KdfMinimums) -> KeyServiceConfig {
+    let policy = KeyServicePolicy::builder()
+        .kdf_minimums(minimums)
+        .build()
+        .expect("policy");
+    KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config")
+}
+
+fn service(
+    storage: MemoryStorage,
+    config: KeyServiceConfig,
+) -> KeyService<MemoryStorage, VirtualClock, SeededEntropy> {
+    KeyService::new(
+        storage,
+        VirtualClock::new(1_000),
+        SeededEntropy::new(1),
+        config,
+    )
+}
+
+#[test]
+fn default_policy_refuses_to_create_a_weak_vault() {
+    let mut core = service(MemoryStorage::new(), KeyServiceConfig::default());
+    let err = core
+        .create_new_vault(UserId("user-1".to_string()), b"pass", weak_kdf())
+        .expect_err("weak kdf");
+    assert!(matches!(
+        err,
+        KeyServiceError::WeakKdfParams {
+            param: "memoryKib",
+            value: 64,
+            minimum: 19_456,
+        }
+    ));
+    let report = err.report();
+    assert_eq!(report.code, "WeakKdfParams");
+    assert_eq!(
+        report.context[0],
+        ("param", ErrorDetail::Text("memoryKib".into()))
+    );
+}
+
+#[test]
+fn stored_header_with_weak_params_does_not_unlock() {
+    let storage = MemoryStorage::new();
+    let mut weak = service(storage.clone(), config_with(KdfMinimums::NONE));
+    weak.create_new_vault(UserId("user-1".to_string()), b"pass", weak_kdf())
+        .expect("create vault");
+
+    let mut strict = service(storage, KeyServiceConfig::default());
+    let err = strict.unlock_passphrase(b"pass").expect_err("weak header");
+    assert!(matches!(err, KeyServiceError::WeakKdfParams { .. }));
+}
+
+#[test]
+fn custom_minimums_apply_per_parameter() {
+    let minimums = KdfMinimums {
+        memory_kib: 64,
+        iterations: 2,
+        parallelism: 1,
+        salt_bytes: 16,
+    };
+    let mut core = service(MemoryStorage::new(), config_with(minimums));
+    let err = core
+        .create_new_vault(UserId("user-1".to_string()), b"pass", weak_kdf())
+        .expect_err("one pass");
+    assert!(matches!(
+        err,
+        KeyServiceError::WeakKdfParams {
+            param: "iterations",
+            value: 1,
+            minimum: 2,
+        }
+    ));
+
+    let short_salt = KdfParams {
+        salt: vec![7u8; 8],
+        iterations: 2,
+        ..weak_kdf()
+    };
+    assert!(matches!(
+        minimums.check(&short_salt),
+        Err(CoreError::WeakKdf {
+            param: "saltBytes",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn header_decode_checks_the_minimums() {
+    let storage = MemoryStorage::new();
+    let mut core = service(storage.clone(), config_with(KdfMinimums::NONE));
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", weak_kdf())
+        .expect("create vault");
+    let bytes = storage
+        .get("keyvault", "header")
+        .expect("storage")
+        .expect("header");
+
+    let header = decode_keyvault_header_v1(&bytes, &KdfMinimums::NONE).expect("decode");
+    assert_eq!(
+        encode_keyvault_header_v1(&header).expect("encode"),
+        bytes.as_slice()
+    );
+    let err = decode_keyvault_header_v1(&bytes, &KdfMinimums::default()).expect_err("weak");
+    assert_eq!(err.code(), "WeakKdfParams");
+    assert_eq!(err.to_string(), "kdf memoryKib 64 below minimum 19456");
+}
@@ -0,0 +1,88 @@
+use mo_key_service_core::crypto::{derive_kek, derive_kek_with_progress, KdfParams};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::UserId;
+
+fn kdf(memory_kib: u32, iterations: u32, parallelism: u32) -> KdfParams {
+    KdfParams {
+        id: "kdf-1".to_string(),
+        salt: vec![7u8; 16],
+        memory_kib,
+        iterations,
+        parallelism,
+    }
+}
+
+#[test]
+fn progress_kdf_derives_the_same_kek() {
+    for (memory_kib, iterations, parallelism) in [
+        (8, 1, 1),
+        (64, 1, 1),
+        (64, 3, 1),
+        (256, 2, 2),
+        (300, 2, 3),
+        (1024, 1, 4),
+    ] {
+        let params = kdf(memory_kib, iterations, parallelism);
+        let expected = derive_kek(b"passphrase", &params).expect("kdf");
+        let actual = derive_kek_with_progress(b"passphrase", &params, &mut |_, _| {}).expect("kdf");
+        assert_eq!(
+            actual.as_slice(),
+            expected.as_slice(),
+            "m={memory_kib} t={iterations} p={parallelism}"
+        );
+    }
+}
+
+#[test]
+fn progress_is_reported_after_each_pass() {
+    let mut seen = Vec::new();
+    derive_kek_with_progress(b"pw", &kdf(64, 3, 1), &mut |done, total| {
+        seen.push((done, total))
+    })
+    .expect("kdf");
+    assert_eq!(seen, vec![(1, 3), (2, 3), (3, 3)]);
+}
+
+#[test]
+fn progress_kdf_rejects_what_derive_kek_rejects() {
+    let mut short_salt = kdf(64, 1, 1);
+    short_salt.salt = vec![0u8; 4];
+    assert!(derive_kek_with_progress(b"pw", &short_salt, &mut |_, _| {}).is_err());
+    assert!(derive_kek_with_progress(b"pw", &kdf(64, 0, 1), &mut |_, _| {}).is_err());
+    let mut other = kdf(64, 1, 1);
+    other.id = "kdf-2".to_string();
+    assert!(derive_kek_with_progress(b"pw", &other, &mut |_, _| {}).is_err());
+}
+
+#[test]
+fn unlock_reports_kdf_passes() {
+    let params = KdfParams {
+        iterations: 2,
+        ..fast_kdf()
+    };
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(76),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", params)
+        .expect("create vault");
+
+    let mut seen = Vec::new();
+    core.unlock_passphrase_with_progress(b"pass", &mut |done, total| seen.push((done, total)))
+        .expect("unlock");
+    assert_eq!(seen, vec![(1, 2), (2, 2)]);
+
+    seen.clear();
+    assert!(matches!(
+        core.unlock_passphrase_with_progress(b"wrong", &mut |done, total| {
+            seen.push((done, total))
+        }),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    assert_eq!(seen.len(), 2);
+}
@@ -0,0 +1,61 @@
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, UserId};
+
+#[test]
+fn re_persisting_the_current_key_appends_nothing() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(31),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let scope_id = ScopeId("scope-1".to_string());
+    let resource_id = ResourceId("res-1".to_string());
+    let resource_key_id = ResourceKeyId("rk-1".to_string());
+    let record_count = |core: &mut KeyService<_, _, _>, session_id| {
+        core.list_records_metadata(session_id)
+            .expect("metadata")
+            .len()
+    };
+
+    for _ in 0..2 {
+        core.persist_scope_key(&session_id, &scope_id, ScopeEpoch(1), &[3u8; 32])
+            .expect("persist scope key");
+        core.persist_resource_key(&session_id, &resource_id, &resource_key_id, &[4u8; 32])
+            .expect("persist resource key");
+    }
+    assert_eq!(record_count(&mut core, &session_id), 2);
+
+    // A different key supersedes the record; the old key is then no longer current.
+    core.persist_scope_key(&session_id, &scope_id, ScopeEpoch(1), &[5u8; 32])
+        .expect("replace scope key");
+    core.persist_scope_key(&session_id, &scope_id, ScopeEpoch(2), &[5u8; 32])
+        .expect("persist next epoch");
+    assert_eq!(record_count(&mut core, &session_id), 4);
+
+    // Reloading folds the records latest-wins, so the replacement is still current.
+    core.lock(&session_id).expect("lock");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.persist_scope_key(&session_id, &scope_id, ScopeEpoch(1), &[5u8; 32])
+        .expect("persist current key");
+    assert_eq!(record_count(&mut core, &session_id), 4);
+    core.persist_scope_key(&session_id, &scope_id, ScopeEpoch(1), &[3u8; 32])
+        .expect("restore superseded key");
+    assert_eq!(record_count(&mut core, &session_id), 5);
+
+    core.lock(&session_id).expect("lock");
+    let session_id = core
+        .unlock_passphrase_read_only(b"pass")
+        .expect("unlock read-only")
+        .session_id;
+    assert!(matches!(
+        core.persist_resource_key(&session_id, &resource_id, &resource_key_id, &[4u8; 32]),
+        Err(KeyServiceError::ReadOnlySession)
+    ));
+}
@@ -2,19 +2,28 @@ use aes_gcm::Aes256Gcm;
 use mo_key_service_core::aad::aad_resource_grant_wrap_v1;
 use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
 use mo_key_service_core::cbor::{cbor_bytes, cbor_map};
-use mo_key_service_core::ciphersuite::{generate_device_signing_keypair, hybrid_sign, SignerKeys};
-use mo_key_service_core::crypto::{aead_encrypt, KdfParams};
+use mo_key_service_core::ciphersuite::{
+    generate_device_signing_keypair, hybrid_sign, hybrid_verify, SignerKeys,
+};
+use mo_key_service_core::crypto::{
+    aead_decrypt_in_place, aead_encrypt, aead_encrypt_in_place, committing_wrap, ct_eq, unwrap_key,
+    KdfParams,
+};
+use mo_key_service_core::domains::{SIG_APP_PAYLOAD_V1, SIG_RESOURCE_GRANT_V1, SIG_SCOPE_STATE_V1};
+use mo_key_service_core::envelope::CIPHERTEXT_ENVELOPE_V1;
 use mo_key_service_core::formats::{
     encode_resource_grant_v1, encode_scope_state_v1, ResourceGrantV1, ScopeStateV1,
 };
 use mo_key_service_core::hash::sha256;
 use mo_key_service_core::key_service::{KeyService, KeyServiceConfig};
+use mo_key_service_core::secret::SecretBytes;
 use mo_key_service_core::types::{
     AeadId, DeviceId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, SessionKind,
     SigCiphersuiteId, UserId,
 };
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use zeroize::Zeroize;
 
 fn signer_fingerprint(signer: &SignerKeys) -> String {
     let mut data = Vec::new();
@@ -122,7 +131,7 @@ fn scope_grant_encrypt_round_trip() {
         signature: Vec::new(),
     };
     let to_sign = scope_state.to_be_signed_bytes().unwrap();
-    let sig = hybrid_sign(&to_sign, &signer).unwrap();
+    let sig = hybrid_sign(SIG_SCOPE_STATE_V1, &to_sign, &signer).unwrap();
     scope_state.signature = sig;
 
     let scope_state_bytes = encode_scope_state_v1(&scope_state).unwrap();
@@ -141,6 +150,15 @@ fn scope_grant_encrypt_round_trip() {
 
     ks.persist_scope_key(&unlock.session_id, &scope_id, ScopeEpoch(1), &scope_key)
         .expect("persist scope key");
+    let scopes = ks
+        .list_scopes(&unlock.session_id)
+        .expect("list scopes")
+        .scopes;
+    assert_eq!(scopes.len(), 1);
+    assert_eq!(scopes[0].scope_id, scope_id);
+    assert_eq!(scopes[0].epochs, vec![ScopeEpoch(1)]);
+    assert!(scopes[0].has_scope_key);
+    assert_eq!(scopes[0].trusted_signer_count, 1);
 
     let scope_handle = ks
         .open_scope(&unlock.session_id, scope_id.clone(), ScopeEpoch(1))
@@ -180,7 +198,7 @@ fn scope_grant_encrypt_round_trip() {
     };
 
     let grant_to_sign = grant.to_be_signed_bytes().unwrap();
-    grant.signature = hybrid_sign(&grant_to_sign, &signer).unwrap();
+    grant.signature = hybrid_sign(SIG_RESOURCE_GRANT_V1, &grant_to_sign, &signer).unwrap();
     let grant_cbor = encode_resource_grant_v1(&grant).unwrap();
 
     let resource_handle = ks
@@ -210,6 +228,52 @@ fn scope_grant_encrypt_round_trip() {
         )
         .expect("decrypt");
     assert_eq!(decrypted.plaintext, payload);
+
+    let mut ct_buf = Vec::with_capacity(64);
+    let mut pt_buf = Vec::with_capacity(64);
+    ks.encrypt_into(
+        &unlock.session_id,
+        &resource_handle.resource_key_handle,
+        aad_payload,
+        payload,
+        &mut ct_buf,
+    )
+    .expect("encrypt into");
+    ks.decrypt_into(
+        &unlock.session_id,
+        &resource_handle.resource_key_handle,
+        aad_payload,
+        &ct_buf,
+        &mut pt_buf,
+    )
+    .expect("decrypt into");
+    assert_eq!(pt_buf, payload);
+    let decrypted = ks
+        .decrypt(
+            &unlock.session_id,
+            &resource_handle.resource_key_handle,
+            aad_payload,
+            &ct_buf,
+        )
+        .expect("decrypt framed by encrypt_into");
+    assert_eq!(decrypted.plaintext, payload);
+    assert_eq!(&encrypted.ciphertext[..2], &[CIPHERTEXT_ENVELOPE_V1, 1]);
+
+    // Legacy `nonce || ct`; this nonce also parses as a v1 header, exercising the fallback.
+    let legacy_nonce = [CIPHERTEXT_ENVELOPE_V1; 12];
+    let mut legacy = legacy_nonce.to_vec();
+    legacy.extend(
+        aead_encrypt::<Aes256Gcm>(&resource_key, aad_payload, payload, &legacy_nonce).unwrap(),
+    );
+    let decrypted = ks
+        .decrypt(
+            &unlock.session_id,
+            &resource_handle.resource_key_handle,
+            aad_payload,
+            &legacy,
+        )
+        .expect("decrypt legacy");
+    assert_eq!(decrypted.plaintext, payload);
 }
 
 #[test]
@@ -233,7 +297,78 @@ fn app_master_key_round_trip() {
         .get_app_master_key(&unlock.session_id)
         .expect("load master key");
 
-    assert_eq!(loaded, master_key);
+    assert_eq!(*loaded, master_key);
+}
+
+#[test]
+fn identity_public_keys_are_exported() {
+    let storage = MemStorage::default();
+    let clock = FixedClock { now: 1_000_000 };
+    let entropy = FixedEntropy {
+        counter: Cell::new(11),
+    };
+    let mut ks = KeyService::new(storage, clock, entropy, KeyServiceConfig::default());
+    let kdf = KdfParams::new_random().expect("kdf params");
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", kdf.clone())
+        .expect("create vault");
+    let info = ks.get_vault_info().expect("vault info before unlock");
+    assert_eq!(info.user_id, UserId("user-1".to_string()));
+    assert_eq!(info.kdf.salt, kdf.salt);
+    assert_eq!(info.record_count, 0);
+    assert!(!info.user_presence_enabled);
+    let unlocked = ks.unlock_passphrase(b"pass").expect("unlock");
+    assert!(!unlocked.has_user_key);
+    assert!(unlocked.device_ids.is_empty());
+    let session_id = unlocked.session_id;
+    ks.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    assert_eq!(ks.get_vault_info().expect("vault info").record_count, 2);
+    ks.lock(&session_id).expect("lock");
+    let unlocked = ks.unlock_passphrase(b"pass").expect("unlock again");
+    assert!(unlocked.has_user_key);
+    assert_eq!(unlocked.device_ids, vec![DeviceId("device-1".to_string())]);
+    let session_id = unlocked.session_id;
+
+    let user = ks.get_user_public_key(&session_id).expect("user key");
+    assert_eq!(user.fingerprint, sha256(&user.public_bytes).to_vec());
+    assert_eq!(user.fingerprint_hex, hex::encode(&user.fingerprint));
+
+    let devices = ks.get_device_public_keys(&session_id).expect("devices");
+    assert_eq!(devices.devices.len(), 1);
+    let device = &devices.devices[0];
+    assert_eq!(device.device_id, DeviceId("device-1".to_string()));
+    assert_eq!(device.fingerprint, signer_fingerprint(&device.signer));
+    let signed = ks.sign(&session_id, b"hello").expect("sign");
+    assert!(hybrid_verify(
+        SIG_APP_PAYLOAD_V1,
+        b"hello",
+        &signed.signature,
+        &device.signer
+    ));
+}
+
+#[test]
+fn constant_time_eq_matches_slice_equality() {
+    assert!(ct_eq(&[1, 2, 3], &[1, 2, 3]));
+    assert!(!ct_eq(&[1, 2, 3], &[1, 2, 4]));
+    assert!(!ct_eq(&[1, 2, 3], &[1, 2]));
+    assert!(ct_eq(&[], &[]));
+}
+
+#[test]
+fn committing_wrap_rejects_other_keys_and_keeps_legacy_path() {
+    let key = vec![1u8; 32];
+    let nonce = vec![2u8; 12];
+    let (ct, commitment) = committing_wrap(&key, b"aad", b"vault-key", &nonce).expect("wrap");
+    let unwrapped = unwrap_key(&key, b"aad", &nonce, &ct, Some(&commitment)).expect("unwrap");
+    assert_eq!(unwrapped.as_slice(), b"vault-key");
+    assert!(unwrap_key(&[3u8; 32], b"aad", &nonce, &ct, Some(&commitment)).is_err());
+    // Stripping the commitment must not fall back to decrypting the committed ciphertext.
+    assert!(unwrap_key(&key, b"aad", &nonce, &ct, None).is_err());
+
+    let legacy = aead_encrypt::<Aes256Gcm>(&key, b"aad", b"vault-key", &nonce).expect("legacy");
+    let unwrapped = unwrap_key(&key, b"aad", &nonce, &legacy, None).expect("legacy unwrap");
+    assert_eq!(unwrapped.as_slice(), b"vault-key");
 }
 
 #[test]
@@ -245,3 +380,37 @@ fn rejects_invalid_nonce_length() {
     let result = aead_encrypt::<Aes256Gcm>(&key, aad, plaintext, &bad_nonce);
     assert!(result.is_err());
 }
+
+#[test]
+fn in_place_aead_round_trip_and_clears_on_failure() {
+    let key = vec![1u8; 32];
+    let nonce = vec![2u8; 12];
+    let mut buffer = b"prefixpayload".to_vec();
+    aead_encrypt_in_place::<Aes256Gcm>(&key, b"aad", &nonce, &mut buffer, 6).expect("encrypt");
+    assert_eq!(&buffer[..6], b"prefix");
+    assert_eq!(
+        buffer[6..].to_vec(),
+        aead_encrypt::<Aes256Gcm>(&key, b"aad", b"payload", &nonce).expect("reference")
+    );
+
+    let mut ct = buffer[6..].to_vec();
+    aead_decrypt_in_place::<Aes256Gcm>(&key, b"aad", &nonce, &mut ct).expect("decrypt");
+    assert_eq!(ct, b"payload");
+
+    let mut tampered = buffer[6..].to_vec();
+    tampered[0] ^= 1;
+    assert!(aead_decrypt_in_place::<Aes256Gcm>(&key, b"aad", &nonce, &mut tampered).is_err());
+    assert!(tampered.is_empty());
+}
+
+#[test]
+fn secret_bytes_round_trip_and_wipe() {
+    let mut secret = SecretBytes::new(&[7u8; 32]).expect("alloc");
+    assert_eq!(&*secret, &[7u8; 32]);
+    assert_eq!(secret.is_locked(), cfg!(feature = "memlock"));
+    let copy = secret.clone();
+    assert!(!format!("{secret:?}").contains('7'));
+    secret.zeroize();
+    assert_eq!(&*secret, &[0u8; 32]);
+    assert_eq!(&*copy, &[7u8; 32]);
+}
@@ -0,0 +1,99 @@
+use mo_key_service_core::adapters::StorageAdapter;
+use mo_key_service_core::crypto::{kwp_unwrap, kwp_wrap, KdfMinimums};
+use mo_key_service_core::formats::{decode_keyvault_header_v1, encode_keyvault_header_v1};
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{KeyWrapAlg, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn service(storage: &MemoryStorage, alg: KeyWrapAlg) -> Core {
+    let policy = test_policy().key_wrap_alg(alg).build().expect("policy");
+    KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(5),
+        KeyServiceConfig { policy },
+    )
+}
+
+fn header_alg(storage: &MemoryStorage) -> KeyWrapAlg {
+    let bytes = storage.get("keyvault", "header").unwrap().unwrap();
+    decode_keyvault_header_v1(&bytes, &KdfMinimums::NONE)
+        .expect("header")
+        .vault_key_wrap
+        .alg
+}
+
+#[test]
+fn kwp_vaults_unlock_and_reject_wrong_passphrases() {
+    let storage = MemoryStorage::new();
+    let mut core = service(&storage, KeyWrapAlg::AesKwp);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+
+    let bytes = storage.get("keyvault", "header").unwrap().unwrap();
+    let header = decode_keyvault_header_v1(&bytes, &KdfMinimums::NONE).expect("header");
+    assert_eq!(header.vault_key_wrap.alg, KeyWrapAlg::AesKwp);
+    assert!(header.vault_key_wrap.nonce.is_empty());
+    assert!(header.vault_key_wrap.commitment.is_none());
+    assert_eq!(header.vault_key_wrap.ct.len(), 40);
+    assert_eq!(encode_keyvault_header_v1(&header).unwrap(), bytes);
+
+    assert!(matches!(
+        core.unlock_passphrase(b"nope"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.enable_user_presence_unlock(&session_id, vec![1, 2], vec![7u8; 32])
+        .expect("enable user presence");
+    core.lock(&session_id).expect("lock");
+    core.unlock_user_presence(&[7u8; 32])
+        .expect("user presence unlock");
+    assert!(matches!(
+        core.unlock_user_presence(&[8u8; 32]),
+        Err(KeyServiceError::VaultKeyUnwrapFailed)
+    ));
+}
+
+#[test]
+fn existing_gcm_wraps_keep_working_and_change_passphrase_rewraps() {
+    let storage = MemoryStorage::new();
+    let mut gcm = service(&storage, KeyWrapAlg::AesGcm);
+    gcm.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    assert_eq!(header_alg(&storage), KeyWrapAlg::AesGcm);
+
+    let mut kwp = service(&storage, KeyWrapAlg::AesKwp);
+    let session_id = kwp.unlock_passphrase(b"pass").expect("unlock").session_id;
+    kwp.step_up(&session_id, b"pass").expect("step up");
+    kwp.change_passphrase(&session_id, b"new pass")
+        .expect("change passphrase");
+    assert_eq!(header_alg(&storage), KeyWrapAlg::AesKwp);
+    kwp.lock(&session_id).expect("lock");
+    kwp.unlock_passphrase(b"new pass").expect("unlock new");
+}
+
+#[test]
+fn kwp_binds_the_aad_and_headers_validate_its_shape() {
+    let wrapped = kwp_wrap(&[3u8; 32], b"aad-a", &[9u8; 32]).unwrap();
+    assert_eq!(
+        *kwp_unwrap(&[3u8; 32], b"aad-a", &wrapped).unwrap(),
+        [9u8; 32]
+    );
+    assert!(kwp_unwrap(&[3u8; 32], b"aad-b", &wrapped).is_err());
+    assert!(kwp_unwrap(&[4u8; 32], b"aad-a", &wrapped).is_err());
+
+    let storage = MemoryStorage::new();
+    let mut core = service(&storage, KeyWrapAlg::AesKwp);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let bytes = storage.get("keyvault", "header").unwrap().unwrap();
+    let mut header = decode_keyvault_header_v1(&bytes, &KdfMinimums::NONE).unwrap();
+    header.vault_key_wrap.nonce = vec![0u8; 12];
+    let tampered = encode_keyvault_header_v1(&header).unwrap();
+    assert!(decode_keyvault_header_v1(&tampered, &KdfMinimums::NONE).is_err());
+}
@@ -0,0 +1,130 @@
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{ResourceId, ResourceKeyId, SessionId, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn lazy_core(storage: &MemoryStorage, seed: u64) -> Core {
+    let policy = test_policy()
+        .lazy_resource_keys(true)
+        .build()
+        .expect("policy");
+    KeyService::new(
+        storage.clone(),
+        VirtualClock::new(2_000),
+        SeededEntropy::new(seed),
+        KeyServiceConfig::builder()
+            .policy(policy)
+            .build()
+            .expect("config"),
+    )
+}
+
+fn resource_key_provenance(core: &mut Core, session_id: &SessionId) -> Vec<bool> {
+    core.list_records_metadata(session_id)
+        .expect("metadata")
+        .iter()
+        .filter(|record| record.kind == 4)
+        .map(|record| record.provenance.is_some())
+        .collect()
+}
+
+#[test]
+fn lazy_unlock_defers_indexed_resource_keys() {
+    let storage = MemoryStorage::new();
+    let mut writer = KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(59),
+        test_config(),
+    );
+    writer
+        .create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = writer
+        .unlock_passphrase(b"pass")
+        .expect("unlock")
+        .session_id;
+    for i in 0..3 {
+        writer
+            .persist_resource_key(
+                &session_id,
+                &ResourceId(format!("res-{i}")),
+                &ResourceKeyId("rk-1".to_string()),
+                &[i as u8; 32],
+            )
+            .expect("persist resource key");
+    }
+    writer
+        .store_app_master_key(&session_id, &[9u8; 32])
+        .expect("store master key");
+    writer
+        .store_app_master_key(&session_id, &[8u8; 32])
+        .expect("replace master key");
+    let stats = writer.vault_stats(&session_id).expect("stats");
+    writer.lock(&session_id).expect("lock");
+
+    // Current resource-key records are chain-checked but not decrypted, so they have no
+    // provenance. The superseded master key is not indexed and is applied as usual.
+    let mut reader = lazy_core(&storage, 60);
+    let session_id = reader
+        .unlock_passphrase(b"pass")
+        .expect("unlock")
+        .session_id;
+    assert_eq!(
+        resource_key_provenance(&mut reader, &session_id),
+        vec![false, false, false, true, false]
+    );
+    assert_eq!(reader.vault_stats(&session_id).expect("stats"), stats);
+    assert_eq!(
+        reader
+            .get_app_master_key(&session_id)
+            .expect("load deferred master key")
+            .as_slice(),
+        &[8u8; 32]
+    );
+
+    // A deferred key is loaded before the redundant-record check.
+    reader
+        .persist_resource_key(
+            &session_id,
+            &ResourceId("res-1".to_string()),
+            &ResourceKeyId("rk-1".to_string()),
+            &[1u8; 32],
+        )
+        .expect("persist current key");
+    assert_eq!(
+        reader
+            .list_records_metadata(&session_id)
+            .expect("metadata")
+            .len(),
+        5
+    );
+}
+
+#[test]
+fn unreadable_index_falls_back_to_a_full_unlock() {
+    let storage = MemoryStorage::new();
+    let mut core = lazy_core(&storage, 61);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.store_app_master_key(&session_id, &[7u8; 32])
+        .expect("store master key");
+    core.lock(&session_id).expect("lock");
+
+    storage.set_raw("keyvault", "index", Some(vec![0xa0]));
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert_eq!(resource_key_provenance(&mut core, &session_id), vec![true]);
+    assert_eq!(
+        core.get_app_master_key(&session_id).unwrap().as_slice(),
+        &[7u8; 32]
+    );
+
+    // The full unlock rewrote the index, so the next unlock defers again.
+    core.lock(&session_id).expect("lock");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert_eq!(resource_key_provenance(&mut core, &session_id), vec![false]);
+}
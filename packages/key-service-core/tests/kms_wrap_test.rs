@@ -0,0 +1,178 @@
+use mo_key_service_core::adapters::KmsAdapter;
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{SessionId, UserId};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+/// Stand-in KMS: the ciphertext is the AAD followed by the plaintext under a key-id pad.
+/// Counts every call and fails all of them while `down` is set.
+#[derive(Clone, Default)]
+struct TestKms {
+    calls: Arc<AtomicUsize>,
+    down: Arc<AtomicBool>,
+}
+
+impl TestKms {
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    fn pad(key_id: &str, data: &[u8]) -> Vec<u8> {
+        let pad = key_id.as_bytes();
+        data.iter()
+            .zip(pad.iter().cycle())
+            .map(|(b, p)| b ^ p)
+            .collect()
+    }
+
+    fn call(&self) -> Result<(), String> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if self.down.load(Ordering::SeqCst) {
+            return Err("kms unavailable".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl KmsAdapter for TestKms {
+    type Error = String;
+
+    fn wrap(&self, key_id: &str, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        self.call()?;
+        Ok([aad, &Self::pad(key_id, plaintext)].concat())
+    }
+
+    fn unwrap(&self, key_id: &str, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        self.call()?;
+        let body = ciphertext
+            .strip_prefix(aad)
+            .ok_or_else(|| "aad mismatch".to_string())?;
+        Ok(Self::pad(key_id, body))
+    }
+}
+
+fn service(storage: &MemoryStorage, kms: Option<&TestKms>) -> Core {
+    let mut core = KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(7),
+        KeyServiceConfig::builder()
+            .policy(test_policy().build().expect("policy"))
+            .build()
+            .expect("config"),
+    );
+    if let Some(kms) = kms {
+        core.set_kms_adapter(kms.clone());
+    }
+    core
+}
+
+/// A vault moved to KMS mode under `kms-key-1`, with the stepped-up session that did it.
+fn kms_vault(storage: &MemoryStorage, kms: &TestKms) -> (Core, SessionId) {
+    let mut core = service(storage, Some(kms));
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.enable_kms_wrap(&session_id, b"pass", "kms-key-1")
+        .expect("enable kms wrap");
+    (core, session_id)
+}
+
+fn rejected_reason(result: Result<(), KeyServiceError>) -> &'static str {
+    match result {
+        Err(KeyServiceError::KmsWrapRejected { reason }) => reason,
+        other => panic!("expected KmsWrapRejected, got {other:?}"),
+    }
+}
+
+#[test]
+fn kms_mode_unlock_needs_the_passphrase_and_the_kms() {
+    let storage = MemoryStorage::new();
+    let kms = TestKms::default();
+    let (mut core, _) = kms_vault(&storage, &kms);
+    let info = core.get_vault_info().expect("vault info");
+    assert_eq!(info.kms_key_id.as_deref(), Some("kms-key-1"));
+
+    let mut without_kms = service(&storage, None);
+    assert!(matches!(
+        without_kms.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::KmsError(_))
+    ));
+
+    let mut fresh = service(&storage, Some(&kms));
+    let before = kms.calls();
+    fresh.unlock_passphrase(b"pass").expect("unlock");
+    assert_eq!(kms.calls(), before + 1);
+
+    // A wrong passphrase fails on the passphrase wrap and never reaches the KMS.
+    assert!(matches!(
+        fresh.unlock_passphrase(b"wrong"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    assert_eq!(kms.calls(), before + 1);
+
+    kms.down.store(true, Ordering::SeqCst);
+    let err = fresh.unlock_passphrase(b"pass").unwrap_err();
+    assert!(matches!(err, KeyServiceError::KmsError(_)));
+    assert!(err.is_retryable());
+}
+
+#[test]
+fn step_up_and_passphrase_change_keep_the_kms_layer() {
+    let storage = MemoryStorage::new();
+    let kms = TestKms::default();
+    let (mut core, session_id) = kms_vault(&storage, &kms);
+
+    let before = kms.calls();
+    core.step_up(&session_id, b"pass").expect("step up");
+    assert_eq!(kms.calls(), before + 1);
+
+    core.change_passphrase(&session_id, b"new pass")
+        .expect("change passphrase");
+    let mut fresh = service(&storage, Some(&kms));
+    fresh.unlock_passphrase(b"new pass").expect("unlock");
+    let mut without_kms = service(&storage, None);
+    assert!(matches!(
+        without_kms.unlock_passphrase(b"new pass"),
+        Err(KeyServiceError::KmsError(_))
+    ));
+
+    assert_eq!(
+        rejected_reason(core.enable_kms_wrap(&session_id, b"pass", "kms-key-2")),
+        "vault already in KMS mode"
+    );
+}
+
+#[test]
+fn user_presence_unlock_and_kms_mode_exclude_each_other() {
+    let storage = MemoryStorage::new();
+    let kms = TestKms::default();
+    let (mut core, session_id) = kms_vault(&storage, &kms);
+    assert_eq!(
+        rejected_reason(core.enable_user_presence_unlock(
+            &session_id,
+            b"cred".to_vec(),
+            vec![9u8; 32]
+        )),
+        "user presence unlock bypasses the KMS"
+    );
+
+    let other = MemoryStorage::new();
+    let mut core = service(&other, Some(&kms));
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.enable_user_presence_unlock(&session_id, b"cred".to_vec(), vec![9u8; 32])
+        .expect("enable user presence");
+    assert_eq!(
+        rejected_reason(core.enable_kms_wrap(&session_id, b"pass", "kms-key-1")),
+        "user presence unlock bypasses the KMS"
+    );
+}
@@ -0,0 +1,134 @@
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{SensitiveOperation, SessionAssurance, SessionId, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn service(require_multi_factor_export: bool) -> Core {
+    let mut policy = test_policy();
+    if require_multi_factor_export {
+        policy = policy.min_assurance(
+            SensitiveOperation::ExportKeyVault,
+            SessionAssurance::MultiFactor,
+        );
+    }
+    let policy = policy.build().expect("policy");
+    KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(101),
+        KeyServiceConfig { policy },
+    )
+}
+
+fn assurance(core: &mut Core, session_id: &SessionId) -> SessionAssurance {
+    core.list_sessions()
+        .into_iter()
+        .find(|s| &s.session_id == session_id)
+        .unwrap()
+        .assurance
+}
+
+/// Creates the vault and enrolls user-presence unlock with secret `[7; 32]`, then locks.
+fn enroll(core: &mut Core) {
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.enable_user_presence_unlock(&session_id, vec![1, 2], vec![7u8; 32])
+        .expect("enable user presence");
+    core.lock(&session_id).expect("lock");
+}
+
+#[test]
+fn both_factors_are_needed_for_a_multi_factor_session() {
+    let mut core = service(false);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    assert!(matches!(
+        core.unlock_multi_factor(b"pass", &[7u8; 32]),
+        Err(KeyServiceError::UserPresenceNotEnabled)
+    ));
+
+    let mut core = service(false);
+    enroll(&mut core);
+    assert!(matches!(
+        core.unlock_multi_factor(b"nope", &[7u8; 32]),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    assert!(matches!(
+        core.unlock_multi_factor(b"pass", &[8u8; 32]),
+        Err(KeyServiceError::VaultKeyUnwrapFailed)
+    ));
+    let unlock = core
+        .unlock_multi_factor(b"pass", &[7u8; 32])
+        .expect("multi-factor unlock");
+    assert_eq!(unlock.assurance, SessionAssurance::MultiFactor);
+}
+
+#[test]
+fn policy_can_reserve_export_for_multi_factor_sessions() {
+    let mut core = service(true);
+    enroll(&mut core);
+
+    let session_id = core.unlock_passphrase(b"pass").unwrap().session_id;
+    core.step_up(&session_id, b"pass").unwrap();
+    let err = core.export_keyvault(&session_id).unwrap_err();
+    assert!(matches!(
+        err,
+        KeyServiceError::AssuranceRequired {
+            required: SessionAssurance::MultiFactor
+        }
+    ));
+    assert_eq!(err.report().context[0].0, "required");
+    core.lock(&session_id).unwrap();
+
+    // A passphrase-only step-up proves one factor, whatever the unlock proved.
+    let session_id = core
+        .unlock_multi_factor(b"pass", &[7u8; 32])
+        .unwrap()
+        .session_id;
+    core.step_up(&session_id, b"pass").unwrap();
+    assert_eq!(
+        assurance(&mut core, &session_id),
+        SessionAssurance::Passphrase
+    );
+    assert!(matches!(
+        core.export_keyvault(&session_id),
+        Err(KeyServiceError::AssuranceRequired { .. })
+    ));
+
+    assert!(matches!(
+        core.step_up_multi_factor(&session_id, b"pass", &[8u8; 32]),
+        Err(KeyServiceError::VaultKeyUnwrapFailed)
+    ));
+    core.step_up_multi_factor(&session_id, b"pass", &[7u8; 32])
+        .unwrap();
+    assert_eq!(
+        assurance(&mut core, &session_id),
+        SessionAssurance::MultiFactor
+    );
+    core.export_keyvault(&session_id)
+        .expect("multi-factor export");
+}
+
+#[test]
+fn renewing_a_multi_factor_session_lowers_its_assurance() {
+    let mut core = service(false);
+    enroll(&mut core);
+    let session_id = core
+        .unlock_multi_factor(b"pass", &[7u8; 32])
+        .unwrap()
+        .session_id;
+    assert_eq!(
+        assurance(&mut core, &session_id),
+        SessionAssurance::MultiFactor
+    );
+    core.renew_session(&session_id).unwrap();
+    assert_eq!(
+        assurance(&mut core, &session_id),
+        SessionAssurance::Passphrase
+    );
+}
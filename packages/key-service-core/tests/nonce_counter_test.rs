@@ -0,0 +1,263 @@
+use aes_gcm::Aes256Gcm;
+use mo_key_service_core::aad::aad_resource_grant_wrap_v1;
+use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
+use mo_key_service_core::cbor::{cbor_bytes, cbor_map};
+use mo_key_service_core::ciphersuite::{
+    generate_device_signing_keypair, hybrid_sign, HybridSignatureKeypair, SignerKeys,
+};
+use mo_key_service_core::crypto::aead_encrypt;
+use mo_key_service_core::domains::{SIG_RESOURCE_GRANT_V1, SIG_SCOPE_STATE_V1};
+use mo_key_service_core::envelope::CIPHERTEXT_ENVELOPE_HEADER_LEN;
+use mo_key_service_core::formats::{
+    encode_resource_grant_v1, encode_scope_state_v1, ResourceGrantV1, ScopeStateV1,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServicePolicy, NonceMode};
+use mo_key_service_core::testkit::{fast_kdf, signer_fingerprint, test_policy};
+use mo_key_service_core::types::{
+    AeadId, DeviceId, KeyHandle, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, SessionId,
+    SigCiphersuiteId, UserId,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct MemStorage {
+    data: RefCell<HashMap<(String, String), Vec<u8>>>,
+}
+
+impl StorageAdapter for MemStorage {
+    type Error = String;
+
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self
+            .data
+            .borrow()
+            .get(&(namespace.to_string(), key.to_string()))
+            .cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+        self.data
+            .borrow_mut()
+            .insert((namespace.to_string(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+    fn list_since(
+        &self,
+        namespace: &str,
+        cursor: &str,
+        _limit: usize,
+    ) -> Result<(Vec<(String, Vec<u8>)>, String), Self::Error> {
+        let mut out = Vec::new();
+        for ((ns, key), value) in self.data.borrow().iter() {
+            if ns == namespace && key.as_str() >= cursor {
+                out.push((key.clone(), value.clone()));
+            }
+        }
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok((out, "".to_string()))
+    }
+}
+
+struct FixedClock {
+    now: u64,
+}
+
+impl ClockAdapter for FixedClock {
+    fn now_ms(&self) -> u64 {
+        self.now
+    }
+}
+
+struct FixedEntropy {
+    counter: Cell<u8>,
+}
+
+impl EntropyAdapter for FixedEntropy {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let value = self.counter.get();
+        self.counter.set(value.wrapping_add(1));
+        vec![value; len]
+    }
+}
+
+type Service = KeyService<MemStorage, FixedClock, FixedEntropy>;
+
+/// Ingests a signed scope state and grant, returning a resource-key handle.
+fn open_resource_handle(
+    ks: &mut Service,
+    session_id: &SessionId,
+    signer: &HybridSignatureKeypair,
+) -> KeyHandle {
+    let device_id = DeviceId("device-1".to_string());
+    let scope_id = ScopeId("scope-1".to_string());
+    let scope_key = vec![3u8; 32];
+    let mut scope_state = ScopeStateV1 {
+        v: 1,
+        scope_id: scope_id.clone(),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: 0,
+        payload: cbor_map(vec![
+            (1, cbor_bytes(&signer.ed25519_pub)),
+            (2, cbor_bytes(&signer.mldsa_pub)),
+        ]),
+        signer_device_id: device_id.clone(),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    scope_state.signature = hybrid_sign(
+        SIG_SCOPE_STATE_V1,
+        &scope_state.to_be_signed_bytes().unwrap(),
+        signer,
+    )
+    .unwrap();
+    let fingerprint = signer_fingerprint(&SignerKeys {
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        ed25519_pub: signer.ed25519_pub.clone(),
+        mldsa_pub: signer.mldsa_pub.clone(),
+    });
+    ks.ingest_scope_state(
+        session_id,
+        &encode_scope_state_v1(&scope_state).unwrap(),
+        Some(fingerprint),
+    )
+    .expect("ingest scope state");
+    ks.persist_scope_key(session_id, &scope_id, ScopeEpoch(1), &scope_key)
+        .expect("persist scope key");
+    let scope_handle = ks
+        .open_scope(session_id, scope_id.clone(), ScopeEpoch(1))
+        .expect("open scope");
+
+    let resource_id = ResourceId("res-1".to_string());
+    let resource_key_id = ResourceKeyId("rk-1".to_string());
+    let aad = aad_resource_grant_wrap_v1(
+        &scope_id.0,
+        &resource_id.0,
+        1,
+        &resource_key_id.0,
+        AeadId::Aead1,
+    )
+    .unwrap();
+    let nonce = vec![9u8; 12];
+    let mut grant = ResourceGrantV1 {
+        v: 1,
+        grant_id: "grant-1".to_string(),
+        scope_id,
+        grant_seq: 0,
+        prev_hash: vec![0u8; 32],
+        scope_state_ref: scope_state.scope_state_ref_bytes().unwrap(),
+        scope_epoch: 1,
+        resource_id,
+        resource_key_id,
+        policy: None,
+        aead: AeadId::Aead1,
+        nonce: nonce.clone(),
+        wrapped_key: aead_encrypt::<Aes256Gcm>(&scope_key, &aad, &[4u8; 32], &nonce).unwrap(),
+        signer_device_id: device_id,
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    grant.signature = hybrid_sign(
+        SIG_RESOURCE_GRANT_V1,
+        &grant.to_be_signed_bytes().unwrap(),
+        signer,
+    )
+    .unwrap();
+    ks.open_resource(
+        session_id,
+        &scope_handle.scope_key_handle,
+        &encode_resource_grant_v1(&grant).unwrap(),
+    )
+    .expect("open resource")
+    .resource_key_handle
+}
+
+fn nonce_of(ciphertext: &[u8]) -> &[u8] {
+    &ciphertext[2..CIPHERTEXT_ENVELOPE_HEADER_LEN]
+}
+
+#[test]
+fn counter_nonces_increment_and_survive_reopen() {
+    let config = KeyServiceConfig {
+        policy: KeyServicePolicy {
+            nonce_mode: NonceMode::Counter,
+            ..test_policy().build().expect("policy")
+        },
+    };
+    let entropy = FixedEntropy {
+        counter: Cell::new(7),
+    };
+    let mut ks = KeyService::new(
+        MemStorage::default(),
+        FixedClock { now: 1_000 },
+        entropy,
+        config,
+    );
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let signer = generate_device_signing_keypair().expect("signer");
+
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let handle = open_resource_handle(&mut ks, &session_id, &signer);
+    let first = ks.encrypt(&session_id, &handle, b"aad", b"one").unwrap();
+    let second = ks.encrypt(&session_id, &handle, b"aad", b"two").unwrap();
+    let (prefix, counter) = nonce_of(&first.ciphertext).split_at(4);
+    assert_eq!(counter, &0u64.to_be_bytes());
+    assert_eq!(&nonce_of(&second.ciphertext)[..4], prefix);
+    assert_eq!(&nonce_of(&second.ciphertext)[4..], &1u64.to_be_bytes());
+    let decrypted = ks
+        .decrypt(&session_id, &handle, b"aad", &second.ciphertext)
+        .expect("decrypt");
+    assert_eq!(decrypted.plaintext, b"two");
+    ks.lock(&session_id).expect("lock");
+
+    // A reopened handle resumes after the persisted lease with a fresh prefix.
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let handle = open_resource_handle(&mut ks, &session_id, &signer);
+    let third = ks.encrypt(&session_id, &handle, b"aad", b"three").unwrap();
+    let (reopened_prefix, counter) = nonce_of(&third.ciphertext).split_at(4);
+    assert_ne!(reopened_prefix, prefix);
+    assert_eq!(counter, &1024u64.to_be_bytes());
+}
+
+#[test]
+fn handles_on_one_key_never_share_a_counter_block() {
+    let config = KeyServiceConfig {
+        policy: KeyServicePolicy {
+            nonce_mode: NonceMode::Counter,
+            ..test_policy().build().expect("policy")
+        },
+    };
+    let entropy = FixedEntropy {
+        counter: Cell::new(7),
+    };
+    let mut ks = KeyService::new(
+        MemStorage::default(),
+        FixedClock { now: 1_000 },
+        entropy,
+        config,
+    );
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let signer = generate_device_signing_keypair().expect("signer");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let a = open_resource_handle(&mut ks, &session_id, &signer);
+    let b = open_resource_handle(&mut ks, &session_id, &signer);
+
+    let counter_of =
+        |ciphertext: &[u8]| u64::from_be_bytes(nonce_of(ciphertext)[4..].try_into().unwrap());
+    let first_a = ks.encrypt(&session_id, &a, b"aad", b"a").unwrap();
+    let first_b = ks.encrypt(&session_id, &b, b"aad", b"b").unwrap();
+    assert_eq!(counter_of(&first_a.ciphertext), 0);
+    assert_eq!(counter_of(&first_b.ciphertext), 1024);
+
+    // Exhausting A's lease must skip past B's block, not re-lease right after A's own.
+    for _ in 1..1024 {
+        ks.encrypt(&session_id, &a, b"aad", b"a").unwrap();
+    }
+    let next_a = ks.encrypt(&session_id, &a, b"aad", b"a").unwrap();
+    assert_eq!(counter_of(&next_a.ciphertext), 2048);
+}
@@ -0,0 +1,304 @@
+use hkdf::Hkdf;
+use mo_key_service_core::adapters::EntropyAdapter;
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, KeyServicePolicy,
+};
+use mo_key_service_core::opaque::{
+    oprf_blind_with, oprf_derive_key_pair, oprf_evaluate, oprf_finalize, OpaqueConfig, OpaqueKsf,
+    OpaqueLogin, OpaqueRegistration, OPAQUE_KE2_LEN,
+};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, OpaqueTestServer, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::UserId;
+use sha2::Sha512;
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+const CREDENTIAL: &[u8] = b"user-1";
+
+fn opaque_config() -> OpaqueConfig {
+    OpaqueConfig {
+        ksf: OpaqueKsf::Argon2id {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        },
+        context: b"mo-local-test".to_vec(),
+        server_identity: Some(b"keys.example".to_vec()),
+        client_identity: None,
+    }
+}
+
+fn core(seed: u64) -> Core {
+    let policy = test_policy()
+        .opaque(opaque_config())
+        .build()
+        .expect("policy");
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        config,
+    )
+}
+
+fn registered(seed: u64) -> (Core, OpaqueTestServer) {
+    let mut core = core(seed);
+    let server = OpaqueTestServer::new(seed + 100, opaque_config());
+    let (registration, request) = core
+        .start_opaque_registration(b"correct horse")
+        .expect("start registration");
+    let response = server
+        .registration_response(CREDENTIAL, &request)
+        .expect("registration response");
+    let record = core
+        .create_new_vault_opaque(
+            UserId("user-1".to_string()),
+            registration,
+            &response,
+            fast_kdf(),
+        )
+        .expect("create vault");
+    server.store(CREDENTIAL, &record).expect("store record");
+    (core, server)
+}
+
+#[test]
+fn register_then_unlock_through_the_server() {
+    let (mut core, server) = registered(1);
+
+    for _ in 0..2 {
+        let (login, ke1) = core
+            .start_opaque_login(b"correct horse")
+            .expect("start login");
+        let ke2 = server.login_response(CREDENTIAL, &ke1).expect("ke2");
+        assert_eq!(ke2.len(), OPAQUE_KE2_LEN);
+        let response = core.unlock_opaque(login, &ke2).expect("unlock");
+        assert!(server.login_finish(CREDENTIAL, &response.ke3).is_some());
+        core.lock(&response.unlock.session_id).expect("lock");
+    }
+
+    // The export key, not the password, is the vault passphrase.
+    assert!(matches!(
+        core.unlock_passphrase(b"correct horse"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+}
+
+#[test]
+fn wrong_password_fails_before_unlock() {
+    let (mut core, server) = registered(2);
+
+    let (login, ke1) = core
+        .start_opaque_login(b"battery staple")
+        .expect("start login");
+    let ke2 = server.login_response(CREDENTIAL, &ke1).expect("ke2");
+    assert!(matches!(
+        core.unlock_opaque(login, &ke2),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    assert!(core.list_sessions().is_empty());
+    assert!(server.login_finish(CREDENTIAL, &[0u8; 64]).is_none());
+}
+
+#[test]
+fn tampered_server_mac_is_rejected() {
+    let (mut core, server) = registered(3);
+
+    let (login, ke1) = core
+        .start_opaque_login(b"correct horse")
+        .expect("start login");
+    let mut ke2 = server.login_response(CREDENTIAL, &ke1).expect("ke2");
+    *ke2.last_mut().expect("mac") ^= 1;
+    assert!(matches!(
+        core.unlock_opaque(login, &ke2),
+        Err(KeyServiceError::OpaqueServerAuthFailed)
+    ));
+}
+
+#[test]
+fn oprf_matches_rfc9497_ristretto255_sha512_vector() {
+    let (secret, _) = oprf_derive_key_pair(&[0xa3; 32], b"test key").expect("derive");
+    assert_eq!(
+        hex::encode(secret),
+        "5ebcea5ee37023ccb9fc2d2019f9d7737be85591ae8652ffa9ef0f4d37063b0e"
+    );
+
+    let blind: [u8; 32] =
+        hex::decode("64d37aed22a27f5191de1c1d69fadb899d8862b58eb4220029e036ec4c1f6706")
+            .expect("hex")
+            .try_into()
+            .expect("len");
+    let blinded = oprf_blind_with(&[0x00], &blind).expect("blind");
+    assert_eq!(
+        hex::encode(blinded),
+        "609a0ae68c15a3cf6903766461307e5c8bb2f95e7e6550e1ffa2dc99e412803c"
+    );
+    let evaluated = oprf_evaluate(&secret, &blinded).expect("evaluate");
+    assert_eq!(
+        hex::encode(evaluated),
+        "7ec6578ae5120958eb2db1745758ff379e77cb64fe77b0b2d8cc917ea0869c7e"
+    );
+    assert_eq!(
+        hex::encode(oprf_finalize(&[0x00], &blind, &evaluated).expect("finalize")),
+        "527759c3d9366f277d8c6020418d96bb393ba2afb20ff90df23fb7708264e2f3\
+         ab9135e3bd69955851de4b1f9fe8a0973396719b7912ba9ee8aa7d0b5e24bcf6"
+    );
+}
+
+#[test]
+fn the_identity_ksf_is_for_tests_only() {
+    assert!(matches!(
+        OpaqueConfig::default().ksf,
+        OpaqueKsf::Argon2id { .. }
+    ));
+    let identity = OpaqueConfig {
+        ksf: OpaqueKsf::Identity,
+        ..OpaqueConfig::default()
+    };
+    assert!(matches!(
+        KeyServicePolicy::builder().opaque(identity.clone()).build(),
+        Err(KeyServiceError::InvalidConfig(_))
+    ));
+    test_policy()
+        .opaque(identity)
+        .build()
+        .expect("allowed with the insecure test KDF");
+}
+
+/// Hands out exactly the bytes it was given, in order.
+struct ScriptedEntropy(std::sync::Mutex<Vec<u8>>);
+
+impl ScriptedEntropy {
+    fn new(chunks: &[&[u8]]) -> Self {
+        Self(std::sync::Mutex::new(chunks.concat()))
+    }
+}
+
+impl EntropyAdapter for ScriptedEntropy {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let mut bytes = self.0.lock().unwrap();
+        assert!(bytes.len() >= len, "script ran out");
+        bytes.drain(..len).collect()
+    }
+}
+
+fn unhex(s: &str) -> Vec<u8> {
+    hex::decode(s).expect("hex")
+}
+
+/// A 32-byte scalar as the 64 bytes `random_scalar` reduces, so it comes out unchanged.
+fn wide(scalar: &[u8]) -> Vec<u8> {
+    [scalar, &[0u8; 32]].concat()
+}
+
+/// RFC 9807 Appendix C.1.1: OPAQUE-3DH real test vector 1, ristretto255-SHA512, identity KSF.
+#[test]
+fn client_matches_rfc9807_real_test_vector_1() {
+    let config = OpaqueConfig {
+        ksf: OpaqueKsf::Identity,
+        context: unhex("4f50415155452d504f43"),
+        server_identity: None,
+        client_identity: None,
+    };
+    let password = unhex("436f7272656374486f72736542617474657279537461706c65");
+    let oprf_seed = unhex(
+        "f433d0227b0b9dd54f7c4422b600e764e47fb503f1f9a0f0a47c6606b054a7fd\
+         c65347f1a08f277e22358bbabe26f823fca82c7848e9a75661f4ec5d5c1989ef",
+    );
+    let credential_identifier = unhex("31323334");
+
+    let entropy = ScriptedEntropy::new(&[
+        &wide(&unhex(
+            "76cfbfe758db884bebb33582331ba9f159720ca8784a2a070a265d9c2d6abe01",
+        )),
+        &unhex("ac13171b2f17bc2c74997f0fce1e1f35bec6b91fe2e12dbd323d23ba7a38dfec"),
+        &wide(&unhex(
+            "6ecc102d2e7a7cf49617aad7bbe188556792d4acd60a1a8a8d2b65d4b0790308",
+        )),
+        &unhex("da7e07376d6d6f034cfa9bb537d11b8c6b4238c334333d1f0aebb380cae6a6cc"),
+        &unhex("82850a697b42a505f5b68fcdafce8c31f0af2b581f063cf1091933541936304b"),
+    ]);
+
+    let (registration, request) = OpaqueRegistration::start(&password, &entropy).unwrap();
+    assert_eq!(
+        hex::encode(&request),
+        "5059ff249eb1551b7ce4991f3336205bde44a105a032e747d21bf382e75f7a71"
+    );
+    // The server's OPRF key comes from its seed and the credential identifier.
+    let oprf_key_seed = Hkdf::<Sha512>::from_prk(&oprf_seed)
+        .map(|hkdf| {
+            let mut out = [0u8; 32];
+            hkdf.expand(
+                &[credential_identifier.as_slice(), b"OprfKey"].concat(),
+                &mut out,
+            )
+            .unwrap();
+            out
+        })
+        .unwrap();
+    let (oprf_key, _) = oprf_derive_key_pair(&oprf_key_seed, b"OPAQUE-DeriveKeyPair").unwrap();
+    let response = unhex(
+        "7408a268083e03abc7097fc05b587834539065e86fb0c7b6342fcf5e01e5b019\
+         b2fe7af9f48cc502d016729d2fe25cdd433f2c4bc904660b2a382c9b79df1a78",
+    );
+    assert_eq!(
+        oprf_evaluate(&oprf_key, &request).unwrap().as_slice(),
+        &response[..32]
+    );
+    let registered = registration.finish(&response, &config, &entropy).unwrap();
+    assert_eq!(
+        hex::encode(&registered.record),
+        "76a845464c68a5d2f7e442436bb1424953b17d3e2e289ccbaccafb57ac5c3675\
+         1ac5844383c7708077dea41cbefe2fa15724f449e535dd7dd562e66f5ecfb958\
+         64eadddec9db5874959905117dad40a4524111849799281fefe3c51fa82785c5\
+         ac13171b2f17bc2c74997f0fce1e1f35bec6b91fe2e12dbd323d23ba7a38dfec\
+         634b0f5b96109c198a8027da51854c35bee90d1e1c781806d07d49b76de6a28b\
+         8d9e9b6c93b9f8b64d16dddd9c5bfb5fea48ee8fd2f75012a8b308605cdd8ba5"
+    );
+
+    let (login, ke1) = OpaqueLogin::start(&password, &entropy).unwrap();
+    assert_eq!(
+        hex::encode(&ke1),
+        "c4dedb0ba6ed5d965d6f250fbe554cd45cba5dfcce3ce836e4aee778aa3cd44d\
+         da7e07376d6d6f034cfa9bb537d11b8c6b4238c334333d1f0aebb380cae6a6cc\
+         6e29bee50701498605b2c085d7b241ca15ba5c32027dd21ba420b94ce60da326"
+    );
+    let ke2 = unhex(
+        "7e308140890bcde30cbcea28b01ea1ecfbd077cff62c4def8efa075aabcbb471\
+         38fe59af0df2c79f57b8780278f5ae47355fe1f817119041951c80f612fdfc6d\
+         d6ec60bcdb26dc455ddf3e718f1020490c192d70dfc7e403981179d8073d1146\
+         a4f9aa1ced4e4cd984c657eb3b54ced3848326f70331953d91b02535af44d9fe\
+         dc80188ca46743c52786e0382f95ad85c08f6afcd1ccfbff95e2bdeb015b166c\
+         6b20b92f832cc6df01e0b86a7efd92c1c804ff865781fa93f2f20b446c8371b6\
+         71cd9960ecef2fe0d0f7494986fa3d8b2bb01963537e60efb13981e138e3d4a1\
+         c4f62198a9d6fa9170c42c3c71f1971b29eb1d5d0bd733e40816c91f7912cc4a\
+         660c48dae03e57aaa38f3d0cffcfc21852ebc8b405d15bd6744945ba1a93438a\
+         162b6111699d98a16bb55b7bdddfe0fc5608b23da246e7bd73b47369169c5c90",
+    );
+    let finished = login.finish(&ke2, &config).unwrap();
+    assert_eq!(
+        hex::encode(&finished.ke3),
+        "4455df4f810ac31a6748835888564b536e6da5d9944dfea9e34defb9575fe5e2\
+         661ef61d2ae3929bcf57e53d464113d364365eb7d1a57b629707ca48da18e442"
+    );
+    assert_eq!(
+        hex::encode(finished.session_key.as_slice()),
+        "42afde6f5aca0cfa5c163763fbad55e73a41db6b41bc87b8e7b62214a8eedc67\
+         31fa3cb857d657ab9b3764b89a84e91ebcb4785166fbb02cedfcbdfda215b96f"
+    );
+    assert_eq!(
+        hex::encode(finished.export_key.as_slice()),
+        "1ef15b4fa99e8a852412450ab78713aad30d21fa6966c9b8c9fb3262a970dc62\
+         950d4dd4ed62598229b1b72794fc0335199d9f7fcc6eaedde92cc04870e63f16"
+    );
+    assert_eq!(
+        registered.export_key.as_slice(),
+        finished.export_key.as_slice()
+    );
+}
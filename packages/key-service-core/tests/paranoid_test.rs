@@ -0,0 +1,96 @@
+#![cfg(feature = "paranoid")]
+
+use mo_key_service_core::crypto::KdfParams;
+use mo_key_service_core::formats::{KeyVaultHeaderV1, VaultKeyWrapV1};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::keyvault::{make_store_scope_key_record, KeyVaultState};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{AeadId, DeviceId, KeyWrapAlg, UserId};
+
+fn make_header() -> KeyVaultHeaderV1 {
+    KeyVaultHeaderV1 {
+        v: 1,
+        vault_id: "vault-1".to_string(),
+        user_id: "user-1".to_string(),
+        kdf: KdfParams::new_random().expect("kdf params"),
+        aead: AeadId::Aead1,
+        records: Vec::new(),
+        vault_key_wrap: VaultKeyWrapV1 {
+            aead: AeadId::Aead1,
+            nonce: vec![1u8; 12],
+            ct: vec![2u8; 16],
+            commitment: None,
+            alg: KeyWrapAlg::AesGcm,
+        },
+        kms_key_id: None,
+        device_shares: None,
+        passphrase_check: None,
+    }
+}
+
+#[test]
+fn keyvault_invariants_catch_a_drifted_head() {
+    let header = make_header();
+    let vault_key = vec![3u8; 32];
+    let mut builder = KeyVaultState::default();
+    let containers: Vec<_> = (1..=2)
+        .map(|seq| {
+            let record =
+                make_store_scope_key_record(&format!("rec-{seq}"), "scope-1", seq, &[9u8; 32]);
+            builder
+                .append_record(&header, &vault_key, &record, seq)
+                .expect("append record")
+        })
+        .collect();
+
+    let (mut state, materialized) =
+        KeyVaultState::apply_containers(&header, &vault_key, &containers).expect("apply");
+    assert_eq!(state.check_invariants(&materialized), Ok(()));
+
+    let head_hash = state.head_hash.clone();
+    state.head_hash = vec![4u8; 32];
+    assert_eq!(
+        state.check_invariants(&materialized),
+        Err("keyvault head hash differs from the last record")
+    );
+    state.head_hash = head_hash;
+
+    // Appending to the chain without folding the record in leaves the materialization behind.
+    let record = make_store_scope_key_record("rec-3", "scope-1", 3, &[9u8; 32]);
+    state
+        .append_record(&header, &vault_key, &record, 3)
+        .expect("append record");
+    assert_eq!(
+        state.check_invariants(&materialized),
+        Err("record metadata out of step with the chain")
+    );
+}
+
+#[test]
+fn service_operations_hold_their_invariants() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(61),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    assert_eq!(
+        core.list_records_metadata(&session_id)
+            .expect("records")
+            .len(),
+        2
+    );
+
+    let report = KeyServiceError::InvariantViolated {
+        invariant: "handle count over max_handles",
+    }
+    .report();
+    assert_eq!(report.code, "InvariantViolated");
+}
@@ -0,0 +1,160 @@
+use aes_gcm::Aes256Gcm;
+use mo_key_service_core::aad::aad_keyvault_passphrase_check_v1;
+use mo_key_service_core::adapters::StorageAdapter;
+use mo_key_service_core::crypto::{aead_decrypt, derive_kek, KdfMinimums};
+use mo_key_service_core::domains::{
+    KEYVAULT_PASSPHRASE_CHECK_KEY_V1, KEYVAULT_PASSPHRASE_CHECK_V1,
+};
+use mo_key_service_core::formats::{decode_keyvault_header_v1, encode_keyvault_header_v1};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::UserId;
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn core_on(storage: &MemoryStorage) -> Core {
+    KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(81),
+        test_config(),
+    )
+}
+
+/// Flips a byte of the stored vault key wrap, leaving the rest of the header intact.
+fn corrupt_vault_key_wrap(storage: &MemoryStorage, clear_check: bool) {
+    let bytes = storage.get("keyvault", "header").unwrap().unwrap();
+    let mut header = decode_keyvault_header_v1(&bytes, &KdfMinimums::NONE).expect("header");
+    header.vault_key_wrap.ct[0] ^= 1;
+    if clear_check {
+        header.passphrase_check = None;
+    }
+    storage.set_raw(
+        "keyvault",
+        "header",
+        Some(encode_keyvault_header_v1(&header).unwrap()),
+    );
+}
+
+#[test]
+fn damaged_wrap_is_reported_as_corruption_not_a_wrong_passphrase() {
+    let storage = MemoryStorage::new();
+    let mut core = core_on(&storage);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    corrupt_vault_key_wrap(&storage, false);
+
+    assert!(matches!(
+        core.unlock_passphrase(b"nope"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    assert!(matches!(
+        core.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::VaultCorrupted { .. })
+    ));
+}
+
+#[test]
+fn check_is_sealed_under_its_own_key_not_the_kek() {
+    let storage = MemoryStorage::new();
+    let mut core = core_on(&storage);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let header = decode_keyvault_header_v1(
+        &storage.get("keyvault", "header").unwrap().unwrap(),
+        &KdfMinimums::NONE,
+    )
+    .unwrap();
+    let check = header.passphrase_check.as_ref().expect("check");
+    let aad =
+        aad_keyvault_passphrase_check_v1(&header.vault_id, &header.user_id, &header.kdf).unwrap();
+
+    let kek = derive_kek(b"pass", &header.kdf).unwrap();
+    assert!(aead_decrypt::<Aes256Gcm>(&kek, &aad, &check.nonce, &check.ct).is_err());
+    let check_key = KEYVAULT_PASSPHRASE_CHECK_KEY_V1.derive(&kek, 32).unwrap();
+    let opened = aead_decrypt::<Aes256Gcm>(&check_key, &aad, &check.nonce, &check.ct)
+        .expect("opens under the check key");
+    assert_eq!(
+        opened.as_slice(),
+        KEYVAULT_PASSPHRASE_CHECK_V1
+            .derive(&kek, 32)
+            .unwrap()
+            .as_slice()
+    );
+}
+
+#[test]
+fn headers_without_a_check_keep_reporting_wrong_passphrase() {
+    let storage = MemoryStorage::new();
+    let mut core = core_on(&storage);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    corrupt_vault_key_wrap(&storage, true);
+
+    assert!(matches!(
+        core.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+}
+
+#[test]
+fn change_passphrase_reseals_the_check() {
+    let storage = MemoryStorage::new();
+    let mut core = core_on(&storage);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let before = decode_keyvault_header_v1(
+        &storage.get("keyvault", "header").unwrap().unwrap(),
+        &KdfMinimums::NONE,
+    )
+    .unwrap()
+    .passphrase_check
+    .expect("check on new vaults");
+
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    core.change_passphrase(&session_id, b"new pass")
+        .expect("change passphrase");
+    core.lock(&session_id).expect("lock");
+    let after = decode_keyvault_header_v1(
+        &storage.get("keyvault", "header").unwrap().unwrap(),
+        &KdfMinimums::NONE,
+    )
+    .unwrap()
+    .passphrase_check
+    .expect("check after change");
+    assert_ne!(after, before);
+
+    corrupt_vault_key_wrap(&storage, false);
+    assert!(matches!(
+        core.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    assert!(matches!(
+        core.unlock_passphrase(b"new pass"),
+        Err(KeyServiceError::VaultCorrupted { .. })
+    ));
+}
+
+#[test]
+fn verify_passphrase_checks_without_a_session() {
+    let storage = MemoryStorage::new();
+    let mut core = core_on(&storage);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+
+    assert!(core.verify_passphrase(b"pass").unwrap());
+    assert!(!core.verify_passphrase(b"nope").unwrap());
+
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert!(!core.verify_passphrase(b"nope").unwrap());
+    core.renew_session(&session_id).expect("session untouched");
+
+    corrupt_vault_key_wrap(&storage, false);
+    assert!(matches!(
+        core.verify_passphrase(b"pass"),
+        Err(KeyServiceError::VaultCorrupted { .. })
+    ));
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "passphrase-strength")]
+
+use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, KeyServicePolicy,
+};
+use mo_key_service_core::strength::estimate_passphrase_strength;
+use mo_key_service_core::testkit::{fast_kdf, test_policy};
+use mo_key_service_core::types::UserId;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type Data = Rc<RefCell<HashMap<(String, String), Vec<u8>>>>;
+
+#[derive(Clone, Default)]
+struct MemStorage {
+    data: Data,
+}
+
+impl StorageAdapter for MemStorage {
+    type Error = String;
+
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self
+            .data
+            .borrow()
+            .get(&(namespace.to_string(), key.to_string()))
+            .cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+        self.data
+            .borrow_mut()
+            .insert((namespace.to_string(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+    fn list_since(
+        &self,
+        namespace: &str,
+        cursor: &str,
+        _limit: usize,
+    ) -> Result<(Vec<(String, Vec<u8>)>, String), Self::Error> {
+        let mut out = Vec::new();
+        for ((ns, key), value) in self.data.borrow().iter() {
+            if ns == namespace && key.as_str() >= cursor {
+                out.push((key.clone(), value.clone()));
+            }
+        }
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok((out, "".to_string()))
+    }
+}
+
+struct FixedClock {
+    now: u64,
+}
+
+impl ClockAdapter for FixedClock {
+    fn now_ms(&self) -> u64 {
+        self.now
+    }
+}
+
+struct FixedEntropy {
+    counter: Cell<u8>,
+}
+
+impl EntropyAdapter for FixedEntropy {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let value = self.counter.get();
+        self.counter.set(value.wrapping_add(1));
+        vec![value; len]
+    }
+}
+
+fn make_service(storage: &MemStorage) -> KeyService<MemStorage, FixedClock, FixedEntropy> {
+    KeyService::new(
+        storage.clone(),
+        FixedClock { now: 1_000 },
+        FixedEntropy {
+            counter: Cell::new(3),
+        },
+        KeyServiceConfig {
+            policy: KeyServicePolicy {
+                min_passphrase_score: Some(3),
+                ..test_policy().build().expect("policy")
+            },
+        },
+    )
+}
+
+const STRONG: &[u8] = b"correct horse battery staple orbit lantern";
+
+#[test]
+fn estimate_ranks_weak_below_strong() {
+    let weak = estimate_passphrase_strength(b"password1").expect("weak");
+    let strong = estimate_passphrase_strength(STRONG).expect("strong");
+    assert!(weak.score < 3);
+    assert_eq!(strong.score, 4);
+    assert!(strong.guesses_log10 > weak.guesses_log10);
+    assert!(estimate_passphrase_strength(&[0xff, 0xfe]).is_err());
+}
+
+#[test]
+fn policy_rejects_weak_passphrases() {
+    let storage = MemStorage::default();
+    let mut ks = make_service(&storage);
+    let err = ks
+        .create_new_vault(UserId("user-1".to_string()), b"password1", fast_kdf())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        KeyServiceError::WeakPassphrase { min_score: 3, .. }
+    ));
+
+    ks.create_new_vault(UserId("user-1".to_string()), STRONG, fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(STRONG).expect("unlock").session_id;
+    ks.step_up(&session_id, STRONG).expect("step up");
+    let err = ks.change_passphrase(&session_id, b"qwerty").unwrap_err();
+    assert!(matches!(err, KeyServiceError::WeakPassphrase { .. }));
+}
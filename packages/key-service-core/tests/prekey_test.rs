@@ -0,0 +1,188 @@
+use mo_key_service_core::formats::{
+    decode_prekey_bundle_v1, encode_prekey_bundle_v1, encode_scope_state_v1, ScopeStatePayload,
+    ScopeStateV1,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError, SignedFormat};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    DeviceId, ScopeEpoch, ScopeId, SessionId, SigCiphersuiteId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn scope() -> ScopeId {
+    ScopeId("scope-1".to_string())
+}
+
+fn vault(user: &str, device: &str, seed: u64) -> (Core, SessionId, String) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(UserId(user.to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId(device.to_string()))
+        .expect("init identity");
+    let fingerprint = core
+        .get_device_public_keys(&session_id)
+        .expect("device keys")
+        .devices
+        .remove(0)
+        .fingerprint;
+    (core, session_id, fingerprint)
+}
+
+/// An owner holding a scope key for `scope-1` epoch 1, and a recipient that trusts the
+/// owner's device for the scope but has no key for it.
+fn owner_and_recipient() -> (Core, SessionId, Core, SessionId, String) {
+    let (mut owner, owner_session, owner_fp) = vault("user-1", "device-1", 51);
+    let (mut recipient, recipient_session, recipient_fp) = vault("user-2", "device-2", 52);
+    let device = owner
+        .get_device_public_keys(&owner_session)
+        .unwrap()
+        .devices
+        .remove(0);
+    let payload = ScopeStatePayload::Genesis {
+        signer: device.signer,
+    };
+    let mut state = ScopeStateV1 {
+        v: 1,
+        scope_id: scope(),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: payload.kind(),
+        payload: payload.to_cbor(),
+        signer_device_id: device.device_id,
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    state.signature = owner
+        .sign_format(
+            &owner_session,
+            SignedFormat::ScopeState,
+            &state.to_be_signed_bytes().unwrap(),
+        )
+        .unwrap()
+        .signature;
+    let genesis = encode_scope_state_v1(&state).unwrap();
+    owner
+        .ingest_scope_state(&owner_session, &genesis, Some(owner_fp.clone()))
+        .unwrap();
+    recipient
+        .ingest_scope_state(&recipient_session, &genesis, Some(owner_fp))
+        .unwrap();
+    owner
+        .persist_scope_key(&owner_session, &scope(), ScopeEpoch(1), &[9u8; 32])
+        .unwrap();
+    (
+        owner,
+        owner_session,
+        recipient,
+        recipient_session,
+        recipient_fp,
+    )
+}
+
+#[test]
+fn offline_recipient_opens_an_envelope_sealed_to_a_prekey() {
+    let (mut owner, owner_session, mut recipient, recipient_session, recipient_fp) =
+        owner_and_recipient();
+    let published = recipient.publish_prekeys(&recipient_session, 3).unwrap();
+    assert_eq!(published.prekey_ids, [0, 1, 2]);
+
+    let sealed = owner
+        .seal_key_envelope_to_prekey(
+            &owner_session,
+            &scope(),
+            ScopeEpoch(1),
+            &published.bundle,
+            1,
+            &recipient_fp,
+        )
+        .unwrap();
+    let ingested = recipient
+        .ingest_key_envelope(&recipient_session, &sealed.envelope)
+        .unwrap();
+    assert!(ingested.is_new);
+    recipient
+        .open_scope(&recipient_session, scope(), ScopeEpoch(1))
+        .expect("scope key from the prekey envelope");
+    assert!(
+        !recipient
+            .ingest_key_envelope(&recipient_session, &sealed.envelope)
+            .unwrap()
+            .is_new
+    );
+
+    // A second envelope to the same prekey is refused.
+    let reused = owner
+        .seal_key_envelope_to_prekey(
+            &owner_session,
+            &scope(),
+            ScopeEpoch(1),
+            &published.bundle,
+            1,
+            &recipient_fp,
+        )
+        .unwrap();
+    assert!(matches!(
+        recipient.ingest_key_envelope(&recipient_session, &reused.envelope),
+        Err(KeyServiceError::PrekeyRejected {
+            reason: "prekey already consumed"
+        })
+    ));
+
+    // Published ids survive a fresh unlock, so new prekeys never repeat old ones.
+    recipient.lock(&recipient_session).unwrap();
+    let recipient_session = recipient.unlock_passphrase(b"pass").unwrap().session_id;
+    let again = recipient.publish_prekeys(&recipient_session, 2).unwrap();
+    assert_eq!(again.prekey_ids, [3, 4]);
+}
+
+#[test]
+fn sealing_checks_the_bundle_signer() {
+    let (mut owner, owner_session, mut recipient, recipient_session, recipient_fp) =
+        owner_and_recipient();
+    let published = recipient.publish_prekeys(&recipient_session, 2).unwrap();
+    let mut seal = |bundle: &[u8], prekey_id: u64, fingerprint: &str| {
+        owner.seal_key_envelope_to_prekey(
+            &owner_session,
+            &scope(),
+            ScopeEpoch(1),
+            bundle,
+            prekey_id,
+            fingerprint,
+        )
+    };
+
+    assert!(matches!(
+        seal(&published.bundle, 0, &"00".repeat(32)),
+        Err(KeyServiceError::FingerprintMismatch)
+    ));
+    assert!(matches!(
+        seal(&published.bundle, 7, &recipient_fp),
+        Err(KeyServiceError::PrekeyRejected {
+            reason: "prekey not in the bundle"
+        })
+    ));
+    let mut bundle = decode_prekey_bundle_v1(&published.bundle).unwrap();
+    bundle.prekeys[0].public_bytes = bundle.prekeys[1].public_bytes.clone();
+    assert!(matches!(
+        seal(&encode_prekey_bundle_v1(&bundle).unwrap(), 0, &recipient_fp),
+        Err(KeyServiceError::SignatureInvalid {
+            format: SignedFormat::PrekeyBundle
+        })
+    ));
+    assert!(matches!(
+        recipient.publish_prekeys(&recipient_session, 0),
+        Err(KeyServiceError::PrekeyRejected {
+            reason: "prekey count out of range"
+        })
+    ));
+}
@@ -0,0 +1,80 @@
+use mo_key_service_core::cbor::{cbor_map, cbor_text, cbor_uint, encode_canonical_value};
+use mo_key_service_core::formats::{decode_keyvault_record_plain_v1, KeyVaultRecordProvenance};
+use mo_key_service_core::key_service::KeyService;
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{DeviceId, ScopeEpoch, ScopeId, UserId};
+
+#[test]
+fn records_carry_creation_time_and_origin_device() {
+    let clock = VirtualClock::new(1_000);
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        clock.clone(),
+        SeededEntropy::new(29),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    clock.set(2_000);
+    core.persist_scope_key(
+        &session_id,
+        &ScopeId("scope-1".to_string()),
+        ScopeEpoch(1),
+        &[3u8; 32],
+    )
+    .expect("persist scope key");
+
+    let records = core.list_records_metadata(&session_id).expect("metadata");
+    let summary: Vec<(u64, u64, Option<KeyVaultRecordProvenance>)> = records
+        .iter()
+        .map(|record| (record.seq, record.kind, record.provenance.clone()))
+        .collect();
+    // The identity records are written before the vault holds a device key.
+    let unattributed = |created_at_ms| {
+        Some(KeyVaultRecordProvenance {
+            created_at_ms,
+            origin_device_id: None,
+        })
+    };
+    assert_eq!(
+        summary,
+        vec![
+            (1, 1, unattributed(1_000)),
+            (2, 2, unattributed(1_000)),
+            (
+                3,
+                3,
+                Some(KeyVaultRecordProvenance {
+                    created_at_ms: 2_000,
+                    origin_device_id: Some(DeviceId("device-1".to_string())),
+                })
+            ),
+        ]
+    );
+
+    // Provenance is stored in the encrypted records, so a fresh unlock reads it back.
+    core.lock(&session_id).expect("lock");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert_eq!(
+        core.list_records_metadata(&session_id).expect("metadata"),
+        records
+    );
+}
+
+#[test]
+fn unknown_record_versions_are_rejected() {
+    let bytes = encode_canonical_value(&cbor_map(vec![
+        (0, cbor_text("record-1")),
+        (1, cbor_uint(3)),
+        (2, cbor_map(Vec::new())),
+        (3, cbor_uint(3)),
+        (4, cbor_uint(1_000)),
+    ]))
+    .unwrap();
+    assert!(decode_keyvault_record_plain_v1(&bytes).is_err());
+}
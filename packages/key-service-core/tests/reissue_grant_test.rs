@@ -0,0 +1,252 @@
+use aes_gcm::Aes256Gcm;
+use mo_key_service_core::aad::aad_resource_grant_wrap_v1;
+use mo_key_service_core::ciphersuite::SignerKeys;
+use mo_key_service_core::crypto::{aead_decrypt, aead_encrypt};
+use mo_key_service_core::formats::{
+    decode_resource_grant_v1, encode_resource_grant_v1, encode_scope_state_v1, ResourceGrantV1,
+    ScopeStatePayload, ScopeStateV1,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError, SignedFormat};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    AeadId, DeviceId, EpochRetirement, KeyHandle, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId,
+    SessionId, SigCiphersuiteId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn scope_key(epoch: u64) -> Vec<u8> {
+    vec![epoch as u8; 32]
+}
+
+/// An unlocked vault with scope keys for epochs 1 and 2 of `scope-1`.
+fn unlocked(seed: u64) -> (Core, SessionId) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    for epoch in 1..=2 {
+        core.persist_scope_key(
+            &session_id,
+            &ScopeId("scope-1".to_string()),
+            ScopeEpoch(epoch),
+            &scope_key(epoch),
+        )
+        .expect("persist scope key");
+    }
+    (core, session_id)
+}
+
+/// The writer's genesis and `DecryptOnly` epoch bump, signed with its device key.
+fn scope_states(
+    writer: &mut Core,
+    session_id: &SessionId,
+    signer: &SignerKeys,
+) -> Vec<ScopeStateV1> {
+    let payloads = [
+        ScopeStatePayload::Genesis {
+            signer: signer.clone(),
+        },
+        ScopeStatePayload::EpochBump {
+            signer: signer.clone(),
+            from_epoch: 1,
+            to_epoch: 2,
+            retirement: EpochRetirement::DecryptOnly,
+        },
+    ];
+    payloads
+        .into_iter()
+        .enumerate()
+        .map(|(i, payload)| {
+            let seq = i as u64 + 1;
+            let mut scope_state = ScopeStateV1 {
+                v: 1,
+                scope_id: ScopeId("scope-1".to_string()),
+                scope_state_seq: seq,
+                prev_hash: vec![if seq == 1 { 0u8 } else { 7u8 }; 32],
+                scope_epoch: seq,
+                kind: payload.kind(),
+                payload: payload.to_cbor(),
+                signer_device_id: DeviceId("device-1".to_string()),
+                sig_suite: SigCiphersuiteId::HybridSig1,
+                signature: Vec::new(),
+            };
+            scope_state.signature = writer
+                .sign_format(
+                    session_id,
+                    SignedFormat::ScopeState,
+                    &scope_state.to_be_signed_bytes().unwrap(),
+                )
+                .expect("sign scope state")
+                .signature;
+            scope_state
+        })
+        .collect()
+}
+
+fn ingest(core: &mut Core, session_id: &SessionId, scope_state: &ScopeStateV1, fingerprint: &str) {
+    core.ingest_scope_state(
+        session_id,
+        &encode_scope_state_v1(scope_state).unwrap(),
+        Some(fingerprint.to_string()),
+    )
+    .expect("ingest scope state");
+}
+
+/// The first grant of `scope-1`, under epoch 1 and signed by the writer's device key.
+fn epoch_one_grant(writer: &mut Core, session_id: &SessionId, scope_state_ref: &[u8]) -> Vec<u8> {
+    let aad = aad_resource_grant_wrap_v1("scope-1", "res-1", 1, "rk-1", AeadId::Aead1).unwrap();
+    let nonce = vec![9u8; 12];
+    let mut grant = ResourceGrantV1 {
+        v: 1,
+        grant_id: "grant-1".to_string(),
+        scope_id: ScopeId("scope-1".to_string()),
+        grant_seq: 0,
+        prev_hash: vec![0u8; 32],
+        scope_state_ref: scope_state_ref.to_vec(),
+        scope_epoch: 1,
+        resource_id: ResourceId("res-1".to_string()),
+        resource_key_id: ResourceKeyId("rk-1".to_string()),
+        policy: None,
+        aead: AeadId::Aead1,
+        nonce: nonce.clone(),
+        wrapped_key: aead_encrypt::<Aes256Gcm>(&scope_key(1), &aad, &[4u8; 32], &nonce).unwrap(),
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    grant.signature = writer
+        .sign_format(
+            session_id,
+            SignedFormat::ResourceGrant,
+            &grant.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign grant")
+        .signature;
+    encode_resource_grant_v1(&grant).unwrap()
+}
+
+fn open_scope(core: &mut Core, session_id: &SessionId, epoch: u64) -> KeyHandle {
+    core.open_scope(
+        session_id,
+        ScopeId("scope-1".to_string()),
+        ScopeEpoch(epoch),
+    )
+    .expect("open scope")
+    .scope_key_handle
+}
+
+#[test]
+fn reissued_grant_moves_the_resource_key_to_the_new_epoch() {
+    let (mut writer, writer_session) = unlocked(21);
+    writer
+        .init_identity(&writer_session, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    let device = writer
+        .get_device_public_keys(&writer_session)
+        .expect("device keys")
+        .devices
+        .remove(0);
+    let states = scope_states(&mut writer, &writer_session, &device.signer);
+    let (mut reader, reader_session) = unlocked(22);
+
+    ingest(
+        &mut writer,
+        &writer_session,
+        &states[0],
+        &device.fingerprint,
+    );
+    ingest(
+        &mut reader,
+        &reader_session,
+        &states[0],
+        &device.fingerprint,
+    );
+    let genesis_ref = states[0].scope_state_ref_bytes().unwrap();
+    let old_grant = epoch_one_grant(&mut writer, &writer_session, &genesis_ref);
+    for (core, session_id) in [
+        (&mut writer, &writer_session),
+        (&mut reader, &reader_session),
+    ] {
+        let scope_handle = open_scope(core, session_id, 1);
+        core.open_resource(session_id, &scope_handle, &old_grant)
+            .expect("open epoch 1 grant");
+    }
+    ingest(
+        &mut writer,
+        &writer_session,
+        &states[1],
+        &device.fingerprint,
+    );
+    ingest(
+        &mut reader,
+        &reader_session,
+        &states[1],
+        &device.fingerprint,
+    );
+
+    // The reissue must target a newer epoch of the same scope.
+    let old_scope = open_scope(&mut writer, &writer_session, 1);
+    assert!(matches!(
+        writer.reissue_grant(&writer_session, &old_grant, &old_scope),
+        Err(KeyServiceError::InvalidFormat(_))
+    ));
+    let mut forged = decode_resource_grant_v1(&old_grant).unwrap();
+    forged.resource_key_id = ResourceKeyId("rk-2".to_string());
+    let new_scope = open_scope(&mut writer, &writer_session, 2);
+    assert!(matches!(
+        writer.reissue_grant(
+            &writer_session,
+            &encode_resource_grant_v1(&forged).unwrap(),
+            &new_scope
+        ),
+        Err(KeyServiceError::SignatureInvalid {
+            format: SignedFormat::ResourceGrant
+        })
+    ));
+
+    let response = writer
+        .reissue_grant(&writer_session, &old_grant, &new_scope)
+        .expect("reissue grant");
+    assert_eq!(response.grant_seq, 1);
+    let reissued = decode_resource_grant_v1(&response.grant).unwrap();
+    assert_eq!(reissued.scope_epoch, 2);
+    assert_eq!(reissued.resource_key_id, ResourceKeyId("rk-1".to_string()));
+    assert_eq!(reissued.signer_device_id, DeviceId("device-1".to_string()));
+    assert_eq!(
+        reissued.scope_state_ref,
+        states[1].scope_state_ref_bytes().unwrap()
+    );
+
+    // A peer opens the reissued grant under epoch 2 and gets the same key, now writable.
+    let reader_scope = open_scope(&mut reader, &reader_session, 2);
+    let handle = reader
+        .open_resource(&reader_session, &reader_scope, &response.grant)
+        .expect("open reissued grant")
+        .resource_key_handle;
+    reader
+        .encrypt(&reader_session, &handle, b"aad", b"migrated")
+        .expect("encrypt under epoch 2");
+    let aad = aad_resource_grant_wrap_v1("scope-1", "res-1", 2, "rk-1", AeadId::Aead1).unwrap();
+    let key =
+        aead_decrypt::<Aes256Gcm>(&scope_key(2), &aad, &reissued.nonce, &reissued.wrapped_key)
+            .unwrap();
+    assert_eq!(key, vec![4u8; 32]);
+
+    // Further reissues extend the writer's chain.
+    let again = writer
+        .reissue_grant(&writer_session, &old_grant, &new_scope)
+        .expect("reissue again");
+    assert_eq!(again.grant_seq, 2);
+    assert_eq!(
+        decode_resource_grant_v1(&again.grant).unwrap().prev_hash,
+        reissued.grant_ref_bytes().unwrap()
+    );
+}
@@ -0,0 +1,314 @@
+use aes_gcm::Aes256Gcm;
+use mo_key_service_core::aad::{aad_key_envelope_wrap_v1, aad_resource_grant_wrap_v1};
+use mo_key_service_core::ciphersuite::{decode_user_public_bytes, hybrid_kem_encapsulate};
+use mo_key_service_core::crypto::aead_encrypt;
+use mo_key_service_core::formats::{
+    encode_key_envelope_v1, encode_resource_grant_v1, encode_scope_state_v1, KeyEnvelopeV1,
+    ResourceGrantV1, ScopeStatePayload, ScopeStateV1,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError, SignedFormat};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    AeadId, DeviceId, KemCiphersuiteId, KeyHandle, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId,
+    SessionId, SigCiphersuiteId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+const SCOPE_KEY: [u8; 32] = [6u8; 32];
+
+/// An unlocked vault whose own device is the genesis signer of `scope-1`; returns the
+/// encoded genesis so it can be ingested again after a re-unlock.
+fn unlocked() -> (Core, SessionId, Vec<u8>) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(41),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    let device = core
+        .get_device_public_keys(&session_id)
+        .expect("device keys")
+        .devices
+        .remove(0);
+    let payload = ScopeStatePayload::Genesis {
+        signer: device.signer,
+    };
+    let mut genesis = ScopeStateV1 {
+        v: 1,
+        scope_id: ScopeId("scope-1".to_string()),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: payload.kind(),
+        payload: payload.to_cbor(),
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    genesis.signature = core
+        .sign_format(
+            &session_id,
+            SignedFormat::ScopeState,
+            &genesis.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign scope state")
+        .signature;
+    let genesis = encode_scope_state_v1(&genesis).unwrap();
+    core.ingest_scope_state(&session_id, &genesis, Some(device.fingerprint))
+        .expect("ingest genesis");
+    (core, session_id, genesis)
+}
+
+fn scope_state_ref(genesis: &[u8]) -> Vec<u8> {
+    mo_key_service_core::formats::decode_scope_state_v1(genesis)
+        .unwrap()
+        .scope_state_ref_bytes()
+        .unwrap()
+}
+
+/// `SCOPE_KEY` for epoch 1, wrapped to the vault's own user key.
+fn envelope(core: &mut Core, session_id: &SessionId, genesis: &[u8], envelope_id: &str) -> Vec<u8> {
+    let public = core.get_user_public_key(session_id).expect("user key");
+    let encap = hybrid_kem_encapsulate(
+        &decode_user_public_bytes(&public.public_bytes).unwrap(),
+        KemCiphersuiteId::HybridKem1,
+    )
+    .expect("encapsulate");
+    let scope_state_ref = scope_state_ref(genesis);
+    let aad = aad_key_envelope_wrap_v1(
+        "scope-1",
+        1,
+        "user-1",
+        &scope_state_ref,
+        KemCiphersuiteId::HybridKem1,
+        AeadId::Aead1,
+        None,
+    )
+    .unwrap();
+    let nonce = vec![3u8; 12];
+    let mut envelope = KeyEnvelopeV1 {
+        v: 1,
+        envelope_id: envelope_id.to_string(),
+        scope_id: ScopeId("scope-1".to_string()),
+        scope_epoch: ScopeEpoch(1),
+        recipient_user_id: UserId("user-1".to_string()),
+        scope_state_ref,
+        kem: KemCiphersuiteId::HybridKem1,
+        aead: AeadId::Aead1,
+        enc: encap.enc,
+        nonce: nonce.clone(),
+        wrapped_scope_key: aead_encrypt::<Aes256Gcm>(&encap.wrap_key, &aad, &SCOPE_KEY, &nonce)
+            .unwrap(),
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+        recipient_uk_pub_fingerprint: None,
+        prekey_id: None,
+    };
+    envelope.signature = core
+        .sign_format(
+            session_id,
+            SignedFormat::KeyEnvelope,
+            &envelope.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign envelope")
+        .signature;
+    encode_key_envelope_v1(&envelope).unwrap()
+}
+
+/// Grant number `seq` of `scope-1`, chained onto `prev_hash`.
+fn grant(
+    core: &mut Core,
+    session_id: &SessionId,
+    genesis: &[u8],
+    seq: u64,
+    prev_hash: Vec<u8>,
+) -> ResourceGrantV1 {
+    let resource_key_id = format!("rk-{seq}");
+    let aad =
+        aad_resource_grant_wrap_v1("scope-1", "res-1", 1, &resource_key_id, AeadId::Aead1).unwrap();
+    let nonce = vec![9u8; 12];
+    let mut grant = ResourceGrantV1 {
+        v: 1,
+        grant_id: format!("grant-{seq}"),
+        scope_id: ScopeId("scope-1".to_string()),
+        grant_seq: seq,
+        prev_hash,
+        scope_state_ref: scope_state_ref(genesis),
+        scope_epoch: 1,
+        resource_id: ResourceId("res-1".to_string()),
+        resource_key_id: ResourceKeyId(resource_key_id),
+        policy: None,
+        aead: AeadId::Aead1,
+        nonce: nonce.clone(),
+        wrapped_key: aead_encrypt::<Aes256Gcm>(&SCOPE_KEY, &aad, &[4u8; 32], &nonce).unwrap(),
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    grant.signature = core
+        .sign_format(
+            session_id,
+            SignedFormat::ResourceGrant,
+            &grant.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign grant")
+        .signature;
+    grant
+}
+
+fn record_count(core: &mut Core, session_id: &SessionId) -> usize {
+    core.list_records_metadata(session_id)
+        .expect("metadata")
+        .len()
+}
+
+fn open_scope(core: &mut Core, session_id: &SessionId) -> KeyHandle {
+    core.open_scope(session_id, ScopeId("scope-1".to_string()), ScopeEpoch(1))
+        .expect("open scope")
+        .scope_key_handle
+}
+
+#[test]
+fn replayed_envelope_is_a_no_op() {
+    let (mut core, session_id, genesis) = unlocked();
+    let first = envelope(&mut core, &session_id, &genesis, "env-1");
+    assert!(
+        core.ingest_key_envelope(&session_id, &first)
+            .expect("ingest")
+            .is_new
+    );
+    let records = record_count(&mut core, &session_id);
+    let audit_entries = core.read_audit_log(&session_id).expect("audit").len();
+
+    let replay = core
+        .ingest_key_envelope(&session_id, &first)
+        .expect("replay");
+    assert!(!replay.is_new);
+    assert_eq!(replay.scope_epoch, ScopeEpoch(1));
+    assert_eq!(record_count(&mut core, &session_id), records);
+    assert_eq!(
+        core.read_audit_log(&session_id).expect("audit").len(),
+        audit_entries
+    );
+
+    // A fresh envelope id for the same key is new, though the key itself is not re-stored.
+    let second = envelope(&mut core, &session_id, &genesis, "env-2");
+    assert!(
+        core.ingest_key_envelope(&session_id, &second)
+            .expect("ingest")
+            .is_new
+    );
+    assert_eq!(record_count(&mut core, &session_id), records + 1);
+}
+
+#[test]
+fn seen_ids_survive_a_re_unlock() {
+    let (mut core, session_id, genesis) = unlocked();
+    let envelope = envelope(&mut core, &session_id, &genesis, "env-1");
+    core.ingest_key_envelope(&session_id, &envelope)
+        .expect("ingest");
+    let scope_handle = open_scope(&mut core, &session_id);
+    let first_grant = grant(&mut core, &session_id, &genesis, 0, vec![0u8; 32]);
+    let first_grant_bytes = encode_resource_grant_v1(&first_grant).unwrap();
+    assert!(
+        core.open_resource(&session_id, &scope_handle, &first_grant_bytes)
+            .expect("open")
+            .is_new
+    );
+    let records = record_count(&mut core, &session_id);
+
+    // A replay in the same session still yields a usable handle but writes nothing.
+    let replay = core
+        .open_resource(&session_id, &scope_handle, &first_grant_bytes)
+        .expect("replay");
+    assert!(!replay.is_new);
+    core.encrypt(&session_id, &replay.resource_key_handle, b"aad", b"hi")
+        .expect("encrypt");
+    assert_eq!(record_count(&mut core, &session_id), records);
+
+    core.lock(&session_id).expect("lock");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let fingerprint = core
+        .get_device_public_keys(&session_id)
+        .expect("device keys")
+        .devices
+        .remove(0)
+        .fingerprint;
+    core.ingest_scope_state(&session_id, &genesis, Some(fingerprint))
+        .expect("re-ingest genesis");
+    assert!(
+        !core
+            .ingest_key_envelope(&session_id, &envelope)
+            .expect("replay envelope")
+            .is_new
+    );
+
+    // Replaying the first grant re-links the in-memory grant chain, so the next one opens.
+    let scope_handle = open_scope(&mut core, &session_id);
+    assert!(
+        !core
+            .open_resource(&session_id, &scope_handle, &first_grant_bytes)
+            .expect("replay grant")
+            .is_new
+    );
+    let prev_hash = first_grant.grant_ref_bytes().unwrap();
+    let next_grant = grant(&mut core, &session_id, &genesis, 1, prev_hash);
+    assert!(
+        core.open_resource(
+            &session_id,
+            &scope_handle,
+            &encode_resource_grant_v1(&next_grant).unwrap()
+        )
+        .expect("open next grant")
+        .is_new
+    );
+    assert_eq!(record_count(&mut core, &session_id), records + 2);
+}
+
+#[test]
+fn a_different_grant_reusing_a_grant_id_is_not_a_replay() {
+    let (mut core, session_id, genesis) = unlocked();
+    let envelope = envelope(&mut core, &session_id, &genesis, "env-1");
+    core.ingest_key_envelope(&session_id, &envelope)
+        .expect("ingest");
+    let scope_handle = open_scope(&mut core, &session_id);
+    let first_grant = grant(&mut core, &session_id, &genesis, 0, vec![0u8; 32]);
+    core.open_resource(
+        &session_id,
+        &scope_handle,
+        &encode_resource_grant_v1(&first_grant).unwrap(),
+    )
+    .expect("open");
+    let records = record_count(&mut core, &session_id);
+
+    // Same grant id and seq, different content: it cannot pass as a replay of the first.
+    let mut forked = grant(&mut core, &session_id, &genesis, 0, vec![0u8; 32]);
+    forked.resource_key_id = ResourceKeyId("rk-forked".to_string());
+    forked.signature = core
+        .sign_format(
+            &session_id,
+            SignedFormat::ResourceGrant,
+            &forked.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign grant")
+        .signature;
+    assert_eq!(forked.grant_id, first_grant.grant_id);
+    assert!(matches!(
+        core.open_resource(
+            &session_id,
+            &scope_handle,
+            &encode_resource_grant_v1(&forked).unwrap()
+        ),
+        Err(KeyServiceError::GrantChainBroken { .. })
+    ));
+    assert_eq!(record_count(&mut core, &session_id), records);
+}
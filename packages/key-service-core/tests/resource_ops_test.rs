@@ -0,0 +1,111 @@
+use mo_key_service_core::cbor::{cbor_map, cbor_uint};
+use mo_key_service_core::ciphersuite::generate_device_signing_keypair;
+use mo_key_service_core::key_service::{CapabilityRequest, KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, ingest_scope_and_grants, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    CapabilityOps, KeyHandle, ScopeEpoch, ScopeId, SessionId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn open_resource(
+    core: &mut Core,
+    session_id: &SessionId,
+    grant: &[u8],
+    ops: CapabilityOps,
+) -> Result<KeyHandle, KeyServiceError> {
+    let scope_handle = core
+        .open_scope(session_id, ScopeId("scope-1".to_string()), ScopeEpoch(1))
+        .expect("open scope");
+    core.open_resource_with_ops(session_id, &scope_handle.scope_key_handle, grant, ops)
+        .map(|response| response.resource_key_handle)
+}
+
+const ENCRYPT_ONLY: CapabilityOps = CapabilityOps {
+    encrypt: true,
+    decrypt: false,
+};
+const DECRYPT_ONLY: CapabilityOps = CapabilityOps {
+    encrypt: false,
+    decrypt: true,
+};
+
+#[test]
+fn resource_handles_are_limited_to_their_ops() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(3),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let writer = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let reader = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let signer = generate_device_signing_keypair().expect("signer");
+    let grants = ingest_scope_and_grants(
+        &mut core,
+        &writer,
+        &signer,
+        &[
+            Some(cbor_map(vec![(0, cbor_uint(ENCRYPT_ONLY.bits()))])),
+            None,
+            Some(cbor_map(vec![(0, cbor_uint(4))])),
+        ],
+    );
+
+    // The grant policy limits the handle even when the caller asks for everything.
+    let sink = open_resource(&mut core, &writer, &grants[0], CapabilityOps::ALL)
+        .expect("open encrypt-only");
+    let ciphertext = core
+        .encrypt(&writer, &sink, b"aad", b"event")
+        .expect("encrypt")
+        .ciphertext;
+    assert!(matches!(
+        core.decrypt(&writer, &sink, b"aad", &ciphertext),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    let stream = core
+        .encrypt_init(&writer, &sink, b"aad")
+        .expect("encrypt init");
+    assert!(matches!(
+        core.decrypt_init(&writer, &sink, b"aad", &stream.header),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    assert!(matches!(
+        core.mint_capability(
+            &writer,
+            &sink,
+            &CapabilityRequest {
+                aad_prefix: Vec::new(),
+                ops: DECRYPT_ONLY,
+                ttl_ms: 1_000,
+            },
+        ),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+
+    // A grant the caller's ops don't overlap is rejected without consuming the chain.
+    assert!(matches!(
+        open_resource(&mut core, &reader, &grants[1], CapabilityOps::default()),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    let reader_handle =
+        open_resource(&mut core, &reader, &grants[1], DECRYPT_ONLY).expect("open decrypt-only");
+    let plaintext = core
+        .decrypt(&reader, &reader_handle, b"aad", &ciphertext)
+        .expect("decrypt")
+        .plaintext;
+    assert_eq!(plaintext, b"event");
+    assert!(matches!(
+        core.encrypt(&reader, &reader_handle, b"aad", b"x"),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+
+    assert!(matches!(
+        open_resource(&mut core, &reader, &grants[2], CapabilityOps::ALL),
+        Err(KeyServiceError::InvalidFormat(_))
+    ));
+}
@@ -0,0 +1,189 @@
+use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter};
+use mo_key_service_core::ciphersuite::generate_device_signing_keypair;
+use mo_key_service_core::envelope::{parse_ratchet_envelope, CIPHERTEXT_ENVELOPE_RATCHET_V2};
+use mo_key_service_core::key_service::{CapabilityRequest, KeyService, KeyServiceError};
+use mo_key_service_core::ratchet::MAX_RATCHET_MESSAGES;
+use mo_key_service_core::shared_key_service::SharedKeyService;
+use mo_key_service_core::testkit::{
+    fast_kdf, ingest_scope_and_grant, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    CapabilityOps, KeyHandle, ScopeEpoch, ScopeId, SessionId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn open_resource<C: ClockAdapter, E: EntropyAdapter>(
+    core: &mut KeyService<MemoryStorage, C, E>,
+    session_id: &SessionId,
+    grant: &[u8],
+) -> KeyHandle {
+    let scope_handle = core
+        .open_scope(session_id, ScopeId("scope-1".to_string()), ScopeEpoch(1))
+        .expect("open scope");
+    core.open_resource(session_id, &scope_handle.scope_key_handle, grant)
+        .expect("open resource")
+        .resource_key_handle
+}
+
+fn unlocked(seed: u64) -> (Core, SessionId, Vec<u8>) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let signer = generate_device_signing_keypair().expect("signer");
+    let grant = ingest_scope_and_grant(&mut core, &session_id, &signer);
+    (core, session_id, grant)
+}
+
+#[test]
+fn ratchet_messages_open_with_the_resource_key() {
+    let (mut core, session_id, grant) = unlocked(61);
+    let writer = open_resource(&mut core, &session_id, &grant);
+    let reader = open_resource(&mut core, &session_id, &grant);
+    core.enable_resource_ratchet(&session_id, &writer)
+        .expect("enable ratchet");
+
+    let messages: Vec<Vec<u8>> = (0..3u8)
+        .map(|i| {
+            core.encrypt(&session_id, &writer, b"doc:1", &[i; 40])
+                .expect("encrypt")
+                .ciphertext
+        })
+        .collect();
+    let first = parse_ratchet_envelope(&messages[0]).expect("v2 framing");
+    assert_eq!(messages[0][0], CIPHERTEXT_ENVELOPE_RATCHET_V2);
+    assert_eq!(first.message, 0);
+    let last = parse_ratchet_envelope(&messages[2]).expect("v2 framing");
+    assert_eq!(last.message, 2);
+    assert_eq!(first.chain_id, last.chain_id);
+    for (i, ciphertext) in messages.iter().enumerate() {
+        let plaintext = core
+            .decrypt(&session_id, &reader, b"doc:1", ciphertext)
+            .expect("decrypt")
+            .plaintext;
+        assert_eq!(plaintext, vec![i as u8; 40]);
+    }
+
+    // A message number that does not match the key used fails authentication.
+    let mut moved = messages[1].clone();
+    moved[2 + 16 + 7] ^= 1;
+    assert!(matches!(
+        core.decrypt(&session_id, &reader, b"doc:1", &moved),
+        Err(KeyServiceError::DecryptFailed)
+    ));
+    assert!(matches!(
+        core.decrypt(&session_id, &reader, b"doc:2", &messages[0]),
+        Err(KeyServiceError::DecryptFailed)
+    ));
+}
+
+#[test]
+fn crafted_ratchet_frames_are_rejected_before_the_chain_walk() {
+    let (mut core, session_id, grant) = unlocked(64);
+    let writer = open_resource(&mut core, &session_id, &grant);
+    let reader = open_resource(&mut core, &session_id, &grant);
+    core.enable_resource_ratchet(&session_id, &writer)
+        .expect("enable ratchet");
+    let ciphertext = core
+        .encrypt(&session_id, &writer, b"doc:1", b"body")
+        .unwrap()
+        .ciphertext;
+
+    let mut past_cap = ciphertext.clone();
+    past_cap[2 + 16..2 + 16 + 8].copy_from_slice(&MAX_RATCHET_MESSAGES.to_be_bytes());
+    let mut unknown_aead = ciphertext.clone();
+    unknown_aead[1] = 0xff;
+    let tagless = &ciphertext[..ciphertext.len() - b"body".len() - 1];
+    for crafted in [&past_cap[..], &unknown_aead[..], tagless] {
+        assert!(matches!(
+            core.decrypt(&session_id, &reader, b"doc:1", crafted),
+            Err(KeyServiceError::DecryptFailed)
+        ));
+    }
+}
+
+#[test]
+fn ratchet_handles_only_encrypt() {
+    let (mut core, session_id, grant) = unlocked(62);
+    let writer = open_resource(&mut core, &session_id, &grant);
+    core.enable_resource_ratchet(&session_id, &writer)
+        .expect("enable ratchet");
+    let ciphertext = core
+        .encrypt(&session_id, &writer, b"doc:1", b"body")
+        .unwrap()
+        .ciphertext;
+
+    assert!(matches!(
+        core.decrypt(&session_id, &writer, b"doc:1", &ciphertext),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    assert!(matches!(
+        core.encrypt_init(&session_id, &writer, b"doc:1"),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    assert!(matches!(
+        core.derive_subkey(&session_id, &writer, &[b"search"], 32),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    let request = CapabilityRequest {
+        aad_prefix: b"doc:".to_vec(),
+        ops: CapabilityOps {
+            encrypt: true,
+            decrypt: false,
+        },
+        ttl_ms: 1_000,
+    };
+    assert!(matches!(
+        core.mint_capability(&session_id, &writer, &request),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+    assert!(matches!(
+        core.enable_resource_ratchet(&session_id, &writer),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+}
+
+#[test]
+fn shared_service_stops_caching_a_ratcheted_handle() {
+    let shared = SharedKeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(63),
+        test_config(),
+    );
+    let (session_id, writer, reader) = shared.with_core(|core| {
+        core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+            .expect("create vault");
+        let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+        let signer = generate_device_signing_keypair().expect("signer");
+        let grant = ingest_scope_and_grant(core, &session_id, &signer);
+        let writer = open_resource(core, &session_id, &grant);
+        let reader = open_resource(core, &session_id, &grant);
+        (session_id, writer, reader)
+    });
+    // Caches the plain resource key for the writer handle.
+    shared
+        .encrypt(&session_id, &writer, b"doc:1", b"before")
+        .unwrap();
+    shared
+        .with_core(|core| core.enable_resource_ratchet(&session_id, &writer))
+        .expect("enable ratchet");
+    let ciphertext = shared
+        .encrypt(&session_id, &writer, b"doc:1", b"after")
+        .unwrap()
+        .ciphertext;
+    assert_eq!(ciphertext[0], CIPHERTEXT_ENVELOPE_RATCHET_V2);
+    assert_eq!(
+        shared
+            .decrypt(&session_id, &reader, b"doc:1", &ciphertext)
+            .unwrap()
+            .plaintext,
+        b"after"
+    );
+}
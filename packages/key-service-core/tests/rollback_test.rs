@@ -0,0 +1,367 @@
+use mo_key_service_core::adapters::{
+    ClockAdapter, DeviceAnchorAdapter, EntropyAdapter, StorageAdapter, VaultEvent,
+    VaultEventsAdapter,
+};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{fast_kdf, test_config, MemoryCounter};
+use mo_key_service_core::types::{ScopeEpoch, ScopeId, UserId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+type Data = Rc<RefCell<HashMap<(String, String), Vec<u8>>>>;
+
+#[derive(Clone, Default)]
+struct MemStorage {
+    data: Data,
+}
+
+impl StorageAdapter for MemStorage {
+    type Error = String;
+
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self
+            .data
+            .borrow()
+            .get(&(namespace.to_string(), key.to_string()))
+            .cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+        self.data
+            .borrow_mut()
+            .insert((namespace.to_string(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+    fn list_since(
+        &self,
+        namespace: &str,
+        cursor: &str,
+        _limit: usize,
+    ) -> Result<(Vec<(String, Vec<u8>)>, String), Self::Error> {
+        let mut out = Vec::new();
+        for ((ns, key), value) in self.data.borrow().iter() {
+            if ns == namespace && key.as_str() >= cursor {
+                out.push((key.clone(), value.clone()));
+            }
+        }
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok((out, "".to_string()))
+    }
+}
+
+struct FixedClock {
+    now: u64,
+}
+
+impl ClockAdapter for FixedClock {
+    fn now_ms(&self) -> u64 {
+        self.now
+    }
+}
+
+struct FixedEntropy {
+    counter: Cell<u8>,
+}
+
+impl EntropyAdapter for FixedEntropy {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let value = self.counter.get();
+        self.counter.set(value.wrapping_add(1));
+        vec![value; len]
+    }
+}
+
+/// Toy anchor: prefixes a tag so tests can see sealed blobs.
+struct PrefixAnchor;
+
+impl DeviceAnchorAdapter for PrefixAnchor {
+    type Error = String;
+
+    fn seal(&self, label: &str, _aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        let mut out = label.as_bytes().to_vec();
+        out.extend_from_slice(plaintext);
+        Ok(out)
+    }
+
+    fn unseal(&self, label: &str, _aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        ciphertext
+            .strip_prefix(label.as_bytes())
+            .map(|rest| rest.to_vec())
+            .ok_or_else(|| "anchor mismatch".to_string())
+    }
+}
+
+struct RecordingEvents {
+    events: Arc<Mutex<Vec<VaultEvent>>>,
+}
+
+impl VaultEventsAdapter for RecordingEvents {
+    fn on_vault_event(&self, event: &VaultEvent) {
+        self.events.lock().expect("events lock").push(event.clone());
+    }
+}
+
+fn make_service(storage: &MemStorage) -> KeyService<MemStorage, FixedClock, FixedEntropy> {
+    KeyService::new(
+        storage.clone(),
+        FixedClock { now: 1_000 },
+        FixedEntropy {
+            counter: Cell::new(3),
+        },
+        test_config(),
+    )
+}
+
+fn get(storage: &MemStorage, key: &str) -> Vec<u8> {
+    storage
+        .get("keyvault", key)
+        .expect("get")
+        .unwrap_or_default()
+}
+
+#[test]
+fn unlock_rejects_rolled_back_chain() {
+    let storage = MemStorage::default();
+    let mut ks = make_service(&storage);
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    ks.store_app_master_key(&session_id, &[1u8; 32])
+        .expect("store 1");
+    let old_index = get(&storage, "record_index");
+    ks.store_app_master_key(&session_id, &[2u8; 32])
+        .expect("store 2");
+    ks.lock(&session_id).expect("lock");
+
+    // Restore the older index while the device's head marker stays current.
+    storage.put("keyvault", "record_index", &old_index).unwrap();
+    let err = ks.unlock_passphrase(b"pass").unwrap_err();
+    assert!(matches!(err, KeyServiceError::RollbackDetected));
+}
+
+#[test]
+fn monotonic_counter_catches_a_restore_that_includes_the_marker() {
+    let storage = MemStorage::default();
+    let counter = MemoryCounter::new();
+    let mut ks = make_service(&storage);
+    ks.set_monotonic_counter(counter.clone());
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    ks.store_app_master_key(&session_id, &[1u8; 32])
+        .expect("store 1");
+    let old = storage.data.borrow().clone();
+    ks.store_app_master_key(&session_id, &[2u8; 32])
+        .expect("store 2");
+    ks.lock(&session_id).expect("lock");
+    let current = storage.data.borrow().clone();
+
+    // Without the counter the old marker matches the old chain, so the restore goes unnoticed.
+    *storage.data.borrow_mut() = old.clone();
+    let mut unbound = make_service(&storage);
+    unbound
+        .unlock_passphrase(b"pass")
+        .expect("unlock without counter");
+    *storage.data.borrow_mut() = old;
+
+    let mut ks = make_service(&storage);
+    ks.set_monotonic_counter(counter.clone());
+    assert!(matches!(
+        ks.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::RollbackDetected)
+    ));
+    storage.put("keyvault", "head_marker", &[]).unwrap();
+    assert!(matches!(
+        ks.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::RollbackDetected)
+    ));
+
+    *storage.data.borrow_mut() = current;
+    ks.unlock_passphrase(b"pass")
+        .expect("unlock current storage");
+}
+
+#[test]
+fn head_marker_is_sealed_by_device_anchor() {
+    let storage = MemStorage::default();
+    let mut ks = make_service(&storage);
+    ks.set_device_anchor(PrefixAnchor);
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    ks.store_app_master_key(&session_id, &[1u8; 32])
+        .expect("store");
+    ks.lock(&session_id).expect("lock");
+
+    assert!(get(&storage, "head_marker").starts_with(b"mo-keyvault-head-marker"));
+    ks.unlock_passphrase(b"pass").expect("unlock with anchor");
+
+    let mut unanchored = make_service(&storage);
+    assert!(unanchored.unlock_passphrase(b"pass").is_err());
+}
+
+#[test]
+fn verify_keyvault_reports_chain_and_marker_problems() {
+    let storage = MemStorage::default();
+    let mut ks = make_service(&storage);
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    ks.store_app_master_key(&session_id, &[1u8; 32])
+        .expect("store 1");
+    let old_index = get(&storage, "record_index");
+    ks.store_app_master_key(&session_id, &[2u8; 32])
+        .expect("store 2");
+
+    let report = ks.verify_keyvault(&session_id).expect("verify");
+    assert!(report.ok);
+    assert_eq!(report.record_count, 2);
+    assert_eq!(report.head_seq, 2);
+    assert!(report.head_marker_match);
+
+    storage.put("keyvault", "record_index", &old_index).unwrap();
+    let report = ks.verify_keyvault(&session_id).expect("verify rolled back");
+    assert!(!report.ok);
+    assert!(!report.head_marker_match);
+    assert_eq!(report.first_invalid_seq, None);
+
+    let mut record_keys: Vec<String> = storage
+        .data
+        .borrow()
+        .keys()
+        .filter(|(ns, key)| ns == "keyvault" && key.starts_with("record:"))
+        .map(|(_, key)| key.clone())
+        .collect();
+    record_keys.sort();
+    let mut bytes = get(&storage, &record_keys[0]);
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0x01;
+    storage.put("keyvault", &record_keys[0], &bytes).unwrap();
+    let report = ks.verify_keyvault(&session_id).expect("verify tampered");
+    assert!(!report.ok);
+    assert!(report.first_invalid_seq.is_some());
+}
+
+#[test]
+fn vault_events_follow_persisted_mutations() {
+    let storage = MemStorage::default();
+    let mut ks = make_service(&storage);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    ks.set_vault_events_adapter(RecordingEvents {
+        events: events.clone(),
+    });
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    ks.persist_scope_key(
+        &session_id,
+        &ScopeId("scope-1".to_string()),
+        ScopeEpoch(1),
+        &[7u8; 32],
+    )
+    .expect("persist scope key");
+
+    let events = events.lock().expect("events lock");
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0], VaultEvent::HeaderUpdated);
+    assert!(matches!(
+        &events[1],
+        VaultEvent::RecordAppended { seq: 1, .. }
+    ));
+    assert_eq!(
+        events[2],
+        VaultEvent::ScopeKeyAdded {
+            scope_id: ScopeId("scope-1".to_string()),
+            scope_epoch: ScopeEpoch(1),
+        }
+    );
+}
+
+#[test]
+fn export_diagnostics_hashes_identifiers_and_keeps_error_codes() {
+    let storage = MemStorage::default();
+    let mut ks = make_service(&storage);
+    assert!(ks.export_diagnostics().vault.is_none());
+
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    ks.persist_scope_key(
+        &session_id,
+        &ScopeId("scope-1".to_string()),
+        ScopeEpoch(1),
+        &[7u8; 32],
+    )
+    .expect("persist scope key");
+    assert!(ks.unlock_passphrase(b"wrong").is_err());
+
+    let report = ks.export_diagnostics();
+    assert!(report.unlocked);
+    assert!(report.problems.is_empty(), "{:?}", report.problems);
+    let vault = report.vault.as_ref().expect("vault");
+    assert_eq!(vault.record_count, 1);
+    assert_eq!(vault.head_seq, 1);
+    assert!(vault.chain_linked);
+    assert!(vault.head_marker_present);
+    assert_eq!(vault.kdf_memory_kib, 8);
+    assert_ne!(vault.user_id_tag, "user-1");
+    assert_eq!(report.recent_errors.len(), 1);
+    assert_eq!(report.recent_errors[0].op.as_str(), "unlock");
+    assert_eq!(report.recent_errors[0].code, "WrongPassphrase");
+
+    let cbor = report.to_cbor().expect("cbor");
+    assert!(!cbor.windows(6).any(|w| w == b"user-1"));
+    assert!(!cbor.windows(7).any(|w| w == b"scope-1"));
+}
+
+#[test]
+fn invalidate_reloads_writes_from_another_instance() {
+    let storage = MemStorage::default();
+    let mut tab_a = make_service(&storage);
+    tab_a
+        .create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_a = tab_a
+        .unlock_passphrase(b"pass")
+        .expect("unlock a")
+        .session_id;
+    let reload = tab_a.reload_from_storage(&session_a).expect("reload");
+    assert!(!reload.changed);
+    assert_eq!(reload.head_seq, 0);
+
+    let mut tab_b = make_service(&storage);
+    let session_b = tab_b
+        .unlock_passphrase(b"pass")
+        .expect("unlock b")
+        .session_id;
+    tab_b
+        .store_app_master_key(&session_b, &[1u8; 32])
+        .expect("store from b");
+
+    tab_a.invalidate();
+    tab_a
+        .store_app_master_key(&session_a, &[2u8; 32])
+        .expect("store from a");
+    let report = tab_a.verify_keyvault(&session_a).expect("verify");
+    assert!(report.ok);
+    assert_eq!(report.record_count, 2);
+    assert_eq!(report.head_seq, 2);
+
+    let reload = tab_b.reload_from_storage(&session_b).expect("reload b");
+    assert!(reload.changed);
+    assert_eq!(reload.head_seq, 2);
+
+    let old_index = get(&storage, "record_index");
+    tab_b
+        .store_app_master_key(&session_b, &[3u8; 32])
+        .expect("store 3");
+    tab_a.reload_from_storage(&session_a).expect("reload a");
+    storage.put("keyvault", "record_index", &old_index).unwrap();
+    assert!(matches!(
+        tab_a.reload_from_storage(&session_a),
+        Err(KeyServiceError::RollbackDetected)
+    ));
+}
@@ -0,0 +1,299 @@
+use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter};
+use mo_key_service_core::ciphersuite::generate_device_signing_keypair;
+use mo_key_service_core::key_service::{KeyService, KeyServiceConfig, KeyServiceError};
+use mo_key_service_core::shared_key_service::SharedKeyService;
+use mo_key_service_core::testkit::{
+    fast_kdf, ingest_scope_and_grant, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    KeyHandle, RotationReason, ScopeEpoch, ScopeId, ScopeRotationPolicy, SessionId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+fn scope_id() -> ScopeId {
+    ScopeId("scope-1".to_string())
+}
+
+fn config() -> KeyServiceConfig {
+    let policy = test_policy()
+        .normal_ttl(30 * DAY_MS)
+        .step_up_ttl(30 * DAY_MS)
+        .build()
+        .expect("policy");
+    KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config")
+}
+
+fn service(storage: &MemoryStorage, clock: &VirtualClock) -> Core {
+    KeyService::new(
+        storage.clone(),
+        clock.clone(),
+        SeededEntropy::new(41),
+        config(),
+    )
+}
+
+fn open_resource<C: ClockAdapter, E: EntropyAdapter>(
+    core: &mut KeyService<MemoryStorage, C, E>,
+    session_id: &SessionId,
+    grant: &[u8],
+) -> KeyHandle {
+    let scope_handle = core
+        .open_scope(session_id, scope_id(), ScopeEpoch(1))
+        .expect("open scope");
+    core.open_resource(session_id, &scope_handle.scope_key_handle, grant)
+        .expect("open resource")
+        .resource_key_handle
+}
+
+/// A vault with `scope-1` at epoch 1 and `policy` set on it, with a stepped-up session and an
+/// open resource handle.
+fn vault_with_policy(
+    storage: &MemoryStorage,
+    clock: &VirtualClock,
+    policy: ScopeRotationPolicy,
+) -> (Core, SessionId, Vec<u8>, KeyHandle) {
+    let mut core = service(storage, clock);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    let signer = generate_device_signing_keypair().expect("signer");
+    let grant = ingest_scope_and_grant(&mut core, &session_id, &signer);
+    core.set_rotation_policy(&session_id, &scope_id(), Some(policy))
+        .expect("set rotation policy");
+    let handle = open_resource(&mut core, &session_id, &grant);
+    (core, session_id, grant, handle)
+}
+
+fn is_overdue(result: Result<impl std::fmt::Debug, KeyServiceError>) -> bool {
+    matches!(
+        result,
+        Err(KeyServiceError::RotationOverdue { scope_id, scope_epoch })
+            if scope_id.0 == "scope-1" && scope_epoch == ScopeEpoch(1)
+    )
+}
+
+#[test]
+fn old_keys_come_due_and_refuse_encryption_after_the_grace_period() {
+    let storage = MemoryStorage::new();
+    let clock = VirtualClock::new(1_000);
+    let policy = ScopeRotationPolicy {
+        max_key_age_ms: Some(10 * DAY_MS),
+        max_operations: None,
+        grace_ms: DAY_MS,
+    };
+    let (mut core, session_id, _, handle) = vault_with_policy(&storage, &clock, policy);
+    let created_ms = clock.now_ms();
+
+    clock.advance(10 * DAY_MS - 1);
+    assert!(core
+        .due_rotations(&session_id)
+        .unwrap()
+        .rotations
+        .is_empty());
+
+    clock.advance(1);
+    let due = core.due_rotations(&session_id).unwrap().rotations;
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].scope_epoch, ScopeEpoch(1));
+    assert_eq!(due[0].reason, RotationReason::KeyAge);
+    assert_eq!(due[0].due_at_ms, created_ms + 10 * DAY_MS);
+    assert_eq!(due[0].hard_deadline_ms, created_ms + 11 * DAY_MS);
+    core.encrypt(&session_id, &handle, b"doc:1", b"still fine")
+        .expect("encrypt within grace");
+
+    clock.advance(DAY_MS + 1);
+    assert!(is_overdue(core.encrypt(
+        &session_id,
+        &handle,
+        b"doc:1",
+        b"too late"
+    )));
+    assert!(is_overdue(core.encrypt_init(
+        &session_id,
+        &handle,
+        b"doc:1"
+    )));
+
+    // Rotating to a new epoch clears the due entry; the old epoch stays refused.
+    core.persist_scope_key(&session_id, &scope_id(), ScopeEpoch(2), &[5u8; 32])
+        .expect("persist epoch 2");
+    assert!(core
+        .due_rotations(&session_id)
+        .unwrap()
+        .rotations
+        .is_empty());
+    assert!(is_overdue(core.encrypt(
+        &session_id,
+        &handle,
+        b"doc:1",
+        b"too late"
+    )));
+}
+
+#[test]
+fn operation_counts_survive_a_restart() {
+    let storage = MemoryStorage::new();
+    let clock = VirtualClock::new(1_000);
+    let policy = ScopeRotationPolicy {
+        max_key_age_ms: None,
+        max_operations: Some(3),
+        grace_ms: 5_000,
+    };
+    let (mut core, session_id, _, handle) = vault_with_policy(&storage, &clock, policy);
+    for _ in 0..2 {
+        core.encrypt(&session_id, &handle, b"doc:1", b"payload")
+            .expect("encrypt");
+    }
+    assert!(core
+        .due_rotations(&session_id)
+        .unwrap()
+        .rotations
+        .is_empty());
+    core.encrypt(&session_id, &handle, b"doc:1", b"payload")
+        .expect("encrypt");
+    let due = core.due_rotations(&session_id).unwrap().rotations;
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].reason, RotationReason::Operations);
+    assert_eq!(due[0].operations, 3);
+    assert_eq!(due[0].due_at_ms, clock.now_ms());
+
+    clock.advance(1_000);
+    let mut restarted = service(&storage, &clock);
+    let restarted_session = restarted
+        .unlock_passphrase(b"pass")
+        .expect("unlock")
+        .session_id;
+    let due = restarted
+        .due_rotations(&restarted_session)
+        .unwrap()
+        .rotations;
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].reason, RotationReason::Operations);
+    assert!(due[0].operations >= 3);
+    assert_eq!(due[0].hard_deadline_ms, clock.now_ms() - 1_000 + 5_000);
+
+    core.encrypt(&session_id, &handle, b"doc:1", b"payload")
+        .expect("encrypt within grace");
+    clock.advance(4_001);
+    assert!(is_overdue(core.encrypt(
+        &session_id,
+        &handle,
+        b"doc:1",
+        b"payload"
+    )));
+}
+
+#[test]
+fn shared_encrypt_counts_operations_and_refuses_past_the_limit() {
+    let clock = VirtualClock::new(1_000);
+    let shared = SharedKeyService::new(
+        MemoryStorage::new(),
+        clock.clone(),
+        SeededEntropy::new(42),
+        config(),
+    );
+    let policy = ScopeRotationPolicy {
+        max_key_age_ms: None,
+        max_operations: Some(2),
+        grace_ms: 0,
+    };
+    let (session_id, handle) = shared.with_core(|core| {
+        core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+            .expect("create vault");
+        let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+        core.step_up(&session_id, b"pass").expect("step up");
+        let signer = generate_device_signing_keypair().expect("signer");
+        let grant = ingest_scope_and_grant(core, &session_id, &signer);
+        core.set_rotation_policy(&session_id, &scope_id(), Some(policy))
+            .expect("set rotation policy");
+        let handle = open_resource(core, &session_id, &grant);
+        (session_id, handle)
+    });
+
+    for _ in 0..2 {
+        shared
+            .encrypt(&session_id, &handle, b"doc:1", b"payload")
+            .expect("encrypt");
+    }
+    let due = shared.with_core(|core| core.due_rotations(&session_id).unwrap().rotations);
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].operations, 2);
+
+    clock.advance(1);
+    assert!(is_overdue(shared.encrypt(
+        &session_id,
+        &handle,
+        b"doc:1",
+        b"payload"
+    )));
+}
+
+#[test]
+fn policies_need_step_up_and_can_be_cleared() {
+    let storage = MemoryStorage::new();
+    let clock = VirtualClock::new(1_000);
+    let policy = ScopeRotationPolicy {
+        max_key_age_ms: Some(1_000),
+        max_operations: None,
+        grace_ms: 0,
+    };
+    let (mut core, session_id, _, handle) = vault_with_policy(&storage, &clock, policy);
+    assert!(matches!(
+        core.set_rotation_policy(
+            &session_id,
+            &scope_id(),
+            Some(ScopeRotationPolicy {
+                max_key_age_ms: None,
+                max_operations: None,
+                grace_ms: 0,
+            })
+        ),
+        Err(KeyServiceError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        core.set_rotation_policy(
+            &session_id,
+            &scope_id(),
+            Some(ScopeRotationPolicy {
+                max_operations: Some(0),
+                ..policy
+            })
+        ),
+        Err(KeyServiceError::InvalidConfig(_))
+    ));
+
+    clock.advance(1_001);
+    assert!(is_overdue(core.encrypt(
+        &session_id,
+        &handle,
+        b"doc:1",
+        b"payload"
+    )));
+
+    let mut restarted = service(&storage, &clock);
+    let normal = restarted
+        .unlock_passphrase(b"pass")
+        .expect("unlock")
+        .session_id;
+    assert_eq!(restarted.due_rotations(&normal).unwrap().rotations.len(), 1);
+    assert!(matches!(
+        restarted.set_rotation_policy(&normal, &scope_id(), None),
+        Err(KeyServiceError::StepUpRequired)
+    ));
+    restarted.step_up(&normal, b"pass").expect("step up");
+    restarted
+        .set_rotation_policy(&normal, &scope_id(), None)
+        .expect("clear policy");
+    assert!(restarted
+        .due_rotations(&normal)
+        .unwrap()
+        .rotations
+        .is_empty());
+}
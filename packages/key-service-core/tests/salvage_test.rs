@@ -0,0 +1,193 @@
+use mo_key_service_core::adapters::StorageAdapter;
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{SessionId, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn core_on(storage: &MemoryStorage, seed: u64) -> Core {
+    KeyService::new(
+        storage.clone(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        test_config(),
+    )
+}
+
+/// Creates a vault holding blobs `a`, `b` and `c`, returning the record id and seq of `b`.
+fn vault_with_blobs(storage: &MemoryStorage) -> (String, u64) {
+    let mut core = core_on(storage, 71);
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    for label in ["a", "b", "c"] {
+        core.put_vault_blob(&session_id, label, label.as_bytes())
+            .expect("put blob");
+    }
+    let records = core.list_records_metadata(&session_id).expect("metadata");
+    let blob_b = &records[records.len() - 2];
+    (blob_b.record_id.clone(), blob_b.seq)
+}
+
+fn blob(core: &mut Core, session_id: &SessionId, label: &str) -> Option<Vec<u8>> {
+    core.get_vault_blob(session_id, label)
+        .expect("get blob")
+        .map(|bytes| bytes.to_vec())
+}
+
+#[test]
+fn salvage_reports_damage_and_repair_rechains_the_log() {
+    let storage = MemoryStorage::new();
+    let (damaged_id, damaged_seq) = vault_with_blobs(&storage);
+    storage.set_raw(
+        "keyvault",
+        &format!("record:{damaged_id}"),
+        Some(b"not a record".to_vec()),
+    );
+
+    let mut core = core_on(&storage, 72);
+    assert!(core.unlock_passphrase(b"pass").is_err());
+
+    let salvage = core
+        .unlock_with_salvage_rechaining(b"pass")
+        .expect("salvage unlock");
+    let report = salvage.report;
+    assert_eq!(report.break_seq, Some(damaged_seq));
+    assert_eq!(report.rechained_record_ids.len(), 1);
+    assert!(report.truncated_record_ids.is_empty());
+    assert_eq!(report.sealed_head_seq, Some(damaged_seq + 1));
+    assert_eq!(report.damaged.len(), 1);
+    assert_eq!(report.damaged[0].record_id, damaged_id);
+    assert_eq!(report.damaged[0].seq, None);
+    assert!(salvage.unlock.read_only);
+
+    let session_id = salvage.unlock.session_id;
+    assert_eq!(blob(&mut core, &session_id, "a"), Some(b"a".to_vec()));
+    assert_eq!(blob(&mut core, &session_id, "b"), None);
+    assert_eq!(blob(&mut core, &session_id, "c"), Some(b"c".to_vec()));
+    assert!(matches!(
+        core.put_vault_blob(&session_id, "d", b"d"),
+        Err(KeyServiceError::ReadOnlySession)
+    ));
+    assert!(matches!(
+        core.repair_keyvault(&session_id),
+        Err(KeyServiceError::StepUpRequired)
+    ));
+
+    core.step_up(&session_id, b"pass").expect("step up");
+    let repaired = core.repair_keyvault(&session_id).expect("repair");
+    assert_eq!(repaired, report);
+    core.put_vault_blob(&session_id, "d", b"d")
+        .expect("writable after repair");
+
+    let mut reopened = core_on(&storage, 73);
+    let session_id = reopened
+        .unlock_passphrase(b"pass")
+        .expect("unlock after repair")
+        .session_id;
+    assert!(reopened.verify_keyvault(&session_id).expect("verify").ok);
+    assert_eq!(blob(&mut reopened, &session_id, "b"), None);
+    assert_eq!(blob(&mut reopened, &session_id, "c"), Some(b"c".to_vec()));
+    assert_eq!(blob(&mut reopened, &session_id, "d"), Some(b"d".to_vec()));
+}
+
+#[test]
+fn salvage_truncates_at_the_break_by_default() {
+    let storage = MemoryStorage::new();
+    let (damaged_id, damaged_seq) = vault_with_blobs(&storage);
+    storage.set_raw(
+        "keyvault",
+        &format!("record:{damaged_id}"),
+        Some(b"not a record".to_vec()),
+    );
+
+    let mut core = core_on(&storage, 76);
+    let salvage = core.unlock_with_salvage(b"pass").expect("salvage unlock");
+    let report = salvage.report;
+    assert_eq!(report.break_seq, Some(damaged_seq));
+    assert!(report.rechained_record_ids.is_empty());
+    assert_eq!(report.truncated_record_ids.len(), 1);
+    assert_eq!(report.sealed_head_seq, Some(damaged_seq + 1));
+
+    let session_id = salvage.unlock.session_id;
+    assert_eq!(blob(&mut core, &session_id, "a"), Some(b"a".to_vec()));
+    assert_eq!(blob(&mut core, &session_id, "c"), None);
+    core.step_up(&session_id, b"pass").expect("step up");
+    assert_eq!(core.repair_keyvault(&session_id).expect("repair"), report);
+
+    let mut reopened = core_on(&storage, 77);
+    let session_id = reopened
+        .unlock_passphrase(b"pass")
+        .expect("unlock after repair")
+        .session_id;
+    assert!(reopened.verify_keyvault(&session_id).expect("verify").ok);
+    assert_eq!(blob(&mut reopened, &session_id, "c"), None);
+}
+
+#[test]
+fn salvage_still_checks_the_head_marker() {
+    let storage = MemoryStorage::new();
+    let (damaged_id, _) = vault_with_blobs(&storage);
+    let records = storage.snapshot();
+    let mut core = core_on(&storage, 78);
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.put_vault_blob(&session_id, "d", b"d")
+        .expect("put blob");
+    let marker = storage.get("keyvault", "head_marker").unwrap();
+
+    // Older storage with a damaged record, under the newer head marker.
+    storage.restore(records);
+    storage.set_raw("keyvault", "head_marker", marker);
+    storage.set_raw(
+        "keyvault",
+        &format!("record:{damaged_id}"),
+        Some(b"not a record".to_vec()),
+    );
+    let mut core = core_on(&storage, 79);
+    assert!(matches!(
+        core.unlock_with_salvage(b"pass"),
+        Err(KeyServiceError::RollbackDetected)
+    ));
+    assert!(matches!(
+        core.unlock_with_salvage_rechaining(b"pass"),
+        Err(KeyServiceError::RollbackDetected)
+    ));
+}
+
+#[test]
+fn tampered_ciphertext_is_reported_with_its_seq() {
+    let storage = MemoryStorage::new();
+    let (damaged_id, damaged_seq) = vault_with_blobs(&storage);
+    let key = format!("record:{damaged_id}");
+    let mut bytes = storage.get("keyvault", &key).expect("get").expect("record");
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0x01;
+    storage.set_raw("keyvault", &key, Some(bytes));
+
+    let mut core = core_on(&storage, 74);
+    let report = core.unlock_with_salvage(b"pass").expect("salvage").report;
+    assert_eq!(report.break_seq, Some(damaged_seq));
+    assert_eq!(report.damaged.len(), 1);
+    assert_eq!(report.damaged[0].seq, Some(damaged_seq));
+}
+
+#[test]
+fn intact_log_unlocks_writable_with_an_empty_report() {
+    let storage = MemoryStorage::new();
+    vault_with_blobs(&storage);
+
+    let mut core = core_on(&storage, 75);
+    let salvage = core.unlock_with_salvage(b"pass").expect("salvage unlock");
+    assert_eq!(salvage.report.break_seq, None);
+    assert!(salvage.report.damaged.is_empty());
+    assert!(!salvage.unlock.read_only);
+
+    let session_id = salvage.unlock.session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    assert!(matches!(
+        core.repair_keyvault(&session_id),
+        Err(KeyServiceError::InvalidFormat(_))
+    ));
+}
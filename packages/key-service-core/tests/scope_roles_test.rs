@@ -0,0 +1,235 @@
+use mo_key_service_core::ciphersuite::{
+    generate_device_signing_keypair, hybrid_sign, HybridSignatureKeypair, SignerKeys,
+};
+use mo_key_service_core::domains::SIG_SCOPE_STATE_V1;
+use mo_key_service_core::formats::{encode_scope_state_v1, ScopeStatePayload, ScopeStateV1};
+use mo_key_service_core::hash::sha256;
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    DeviceId, EpochRetirement, ScopeId, ScopeRole, SessionId, SigCiphersuiteId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+struct Device {
+    id: DeviceId,
+    keypair: HybridSignatureKeypair,
+}
+
+impl Device {
+    fn new(id: &str) -> Self {
+        Self {
+            id: DeviceId(id.to_string()),
+            keypair: generate_device_signing_keypair().expect("signer"),
+        }
+    }
+
+    fn signer_keys(&self) -> SignerKeys {
+        SignerKeys {
+            sig_suite: SigCiphersuiteId::HybridSig1,
+            ed25519_pub: self.keypair.ed25519_pub.clone(),
+            mldsa_pub: self.keypair.mldsa_pub.clone(),
+        }
+    }
+
+    fn fingerprint(&self) -> String {
+        let mut data = Vec::new();
+        data.extend_from_slice(&self.keypair.ed25519_pub);
+        data.extend_from_slice(&self.keypair.mldsa_pub);
+        hex::encode(sha256(&data))
+    }
+
+    fn genesis(&self) -> ScopeStatePayload {
+        ScopeStatePayload::Genesis {
+            signer: self.signer_keys(),
+        }
+    }
+
+    fn epoch_bump(&self, from_epoch: u64) -> ScopeStatePayload {
+        ScopeStatePayload::EpochBump {
+            signer: self.signer_keys(),
+            from_epoch,
+            to_epoch: from_epoch + 1,
+            retirement: EpochRetirement::DecryptOnly,
+        }
+    }
+
+    fn assign(&self, roles: &[(&Device, ScopeRole)]) -> ScopeStatePayload {
+        ScopeStatePayload::MembershipChange {
+            signer: self.signer_keys(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            roles: roles
+                .iter()
+                .map(|(device, role)| (device.id.clone(), *role))
+                .collect(),
+            member_keys: Vec::new(),
+        }
+    }
+
+    /// Signs `payload` as scope state `seq` and ingests it, vouching for this device's keys.
+    fn ingest(
+        &self,
+        core: &mut Core,
+        session_id: &SessionId,
+        seq: u64,
+        scope_epoch: u64,
+        payload: ScopeStatePayload,
+    ) -> Result<(), KeyServiceError> {
+        let mut scope_state = ScopeStateV1 {
+            v: 1,
+            scope_id: ScopeId("scope-1".to_string()),
+            scope_state_seq: seq,
+            prev_hash: vec![if seq == 1 { 0u8 } else { 7u8 }; 32],
+            scope_epoch,
+            kind: payload.kind(),
+            payload: payload.to_cbor(),
+            signer_device_id: self.id.clone(),
+            sig_suite: SigCiphersuiteId::HybridSig1,
+            signature: Vec::new(),
+        };
+        scope_state.signature = hybrid_sign(
+            SIG_SCOPE_STATE_V1,
+            &scope_state.to_be_signed_bytes().unwrap(),
+            &self.keypair,
+        )
+        .unwrap();
+        core.ingest_scope_state(
+            session_id,
+            &encode_scope_state_v1(&scope_state).unwrap(),
+            Some(self.fingerprint()),
+        )
+        .map(|_| ())
+    }
+}
+
+fn is_trusted(core: &mut Core, device: &Device) -> bool {
+    !matches!(
+        core.verify(
+            ScopeId("scope-1".to_string()),
+            device.id.clone(),
+            b"data",
+            b"sig",
+            SigCiphersuiteId::HybridSig1,
+        ),
+        Err(KeyServiceError::UntrustedSigner)
+    )
+}
+
+#[test]
+fn only_owners_and_admins_sign_epoch_bumps_and_membership_changes() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(11),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let owner = Device::new("device-owner");
+    let admin = Device::new("device-admin");
+    let member = Device::new("device-member");
+
+    owner
+        .ingest(&mut core, &session_id, 1, 1, owner.genesis())
+        .expect("genesis");
+    owner
+        .ingest(
+            &mut core,
+            &session_id,
+            2,
+            1,
+            owner.assign(&[(&admin, ScopeRole::Admin), (&member, ScopeRole::Member)]),
+        )
+        .expect("owner assigns roles");
+
+    // A member's first scope state is rejected, and the member stays untrusted.
+    let err = member
+        .ingest(&mut core, &session_id, 3, 2, member.epoch_bump(1))
+        .expect_err("member epoch bump");
+    assert!(matches!(err, KeyServiceError::ScopeRoleDenied { .. }));
+    assert_eq!(err.code(), "ScopeRoleDenied");
+    assert!(!is_trusted(&mut core, &member));
+    assert!(matches!(
+        member.ingest(
+            &mut core,
+            &session_id,
+            3,
+            1,
+            member.assign(&[(&member, ScopeRole::Admin)])
+        ),
+        Err(KeyServiceError::ScopeRoleDenied { .. })
+    ));
+
+    admin
+        .ingest(&mut core, &session_id, 3, 2, admin.epoch_bump(1))
+        .expect("admin epoch bump");
+    assert!(is_trusted(&mut core, &admin));
+
+    // Admins cannot grant ownership or change an owner's role.
+    for roles in [[(&member, ScopeRole::Owner)], [(&owner, ScopeRole::Member)]] {
+        assert!(matches!(
+            admin.ingest(&mut core, &session_id, 4, 2, admin.assign(&roles)),
+            Err(KeyServiceError::ScopeRoleDenied { .. })
+        ));
+    }
+    admin
+        .ingest(
+            &mut core,
+            &session_id,
+            4,
+            2,
+            admin.assign(&[(&member, ScopeRole::Admin)]),
+        )
+        .expect("admin promotes member");
+    member
+        .ingest(&mut core, &session_id, 5, 3, member.epoch_bump(2))
+        .expect("promoted member epoch bump");
+
+    // Demoting the admin revokes its right to sign further changes.
+    owner
+        .ingest(
+            &mut core,
+            &session_id,
+            6,
+            3,
+            owner.assign(&[(&admin, ScopeRole::Member)]),
+        )
+        .expect("owner demotes admin");
+    assert!(matches!(
+        admin.ingest(&mut core, &session_id, 7, 4, admin.epoch_bump(3)),
+        Err(KeyServiceError::ScopeRoleDenied { .. })
+    ));
+}
+
+#[test]
+fn membership_change_rejects_duplicate_role_assignments() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(12),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let owner = Device::new("device-owner");
+    let other = Device::new("device-other");
+    owner
+        .ingest(&mut core, &session_id, 1, 1, owner.genesis())
+        .expect("genesis");
+    assert!(matches!(
+        owner.ingest(
+            &mut core,
+            &session_id,
+            2,
+            1,
+            owner.assign(&[(&other, ScopeRole::Admin), (&other, ScopeRole::Member)]),
+        ),
+        Err(KeyServiceError::InvalidFormat(_))
+    ));
+}
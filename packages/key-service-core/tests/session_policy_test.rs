@@ -1,12 +1,17 @@
-use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
+use mo_key_service_core::adapters::{
+    ClockAdapter, EntropyAdapter, LogAdapter, MetricOp, MetricsAdapter, SessionEvent,
+    SessionEventsAdapter, StorageAdapter, TimerAdapter,
+};
 use mo_key_service_core::crypto::KdfParams;
 use mo_key_service_core::key_service::{
     KeyService, KeyServiceConfig, KeyServiceError, KeyServicePolicy,
 };
-use mo_key_service_core::types::{SessionId, UserId};
+use mo_key_service_core::logging::{redact_session_id, LogEvent};
+use mo_key_service_core::types::{KeyHandle, ScopeEpoch, ScopeId, SessionId, UserId};
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 #[derive(Default)]
 struct MemStorage {
@@ -71,6 +76,70 @@ impl EntropyAdapter for FixedEntropy {
     }
 }
 
+type MetricLog = Arc<Mutex<Vec<(MetricOp, bool)>>>;
+
+struct RecordingMetrics {
+    counts: MetricLog,
+}
+
+impl MetricsAdapter for RecordingMetrics {
+    fn increment(&self, op: MetricOp, ok: bool) {
+        self.counts.lock().expect("metrics lock").push((op, ok));
+    }
+
+    fn observe(&self, _op: MetricOp, _duration_ms: u64) {}
+}
+
+struct ZeroEntropy;
+
+impl EntropyAdapter for ZeroEntropy {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        vec![0; len]
+    }
+}
+
+struct RecordingLog {
+    events: Arc<Mutex<Vec<LogEvent>>>,
+}
+
+impl LogAdapter for RecordingLog {
+    fn log(&self, event: &LogEvent) {
+        self.events.lock().expect("events lock").push(event.clone());
+    }
+}
+
+/// Records `Some(delay_ms)` per schedule and `None` per cancel.
+#[derive(Clone, Default)]
+struct RecordingTimer {
+    calls: Arc<Mutex<Vec<Option<u64>>>>,
+}
+
+impl RecordingTimer {
+    fn take(&self) -> Vec<Option<u64>> {
+        std::mem::take(&mut *self.calls.lock().expect("timer lock"))
+    }
+}
+
+impl TimerAdapter for RecordingTimer {
+    fn schedule(&self, delay_ms: u64) {
+        self.calls.lock().expect("timer lock").push(Some(delay_ms));
+    }
+
+    fn cancel(&self) {
+        self.calls.lock().expect("timer lock").push(None);
+    }
+}
+
+struct RecordingSessionEvents {
+    events: Arc<Mutex<Vec<SessionEvent>>>,
+}
+
+impl SessionEventsAdapter for RecordingSessionEvents {
+    fn on_session_event(&self, event: &SessionEvent) {
+        self.events.lock().expect("events lock").push(event.clone());
+    }
+}
+
 fn make_service(
     now_ms: u64,
 ) -> (
@@ -87,6 +156,7 @@ fn make_service(
         policy: KeyServicePolicy {
             normal_session_ttl_ms: 10,
             step_up_session_ttl_ms: 5,
+            max_plaintext_bytes: 8,
             ..KeyServicePolicy::default()
         },
     };
@@ -108,6 +178,22 @@ fn export_requires_step_up() {
     assert!(matches!(err, KeyServiceError::StepUpRequired));
 }
 
+#[test]
+fn wrong_passphrase_has_distinct_error_code() {
+    let (mut ks, _) = make_service(1_000);
+    let session_id = create_and_unlock(&mut ks);
+
+    let err = ks.unlock_passphrase(b"nope").unwrap_err();
+    assert!(matches!(err, KeyServiceError::WrongPassphrase));
+    assert_eq!(err.code(), "WrongPassphrase");
+    assert!(!err.is_retryable());
+
+    let err = ks.step_up(&session_id, b"nope").unwrap_err();
+    assert!(matches!(err, KeyServiceError::WrongPassphrase));
+    let err = ks.unlock_user_presence(&[0u8; 32]).unwrap_err();
+    assert_eq!(err.code(), "UserPresenceNotEnabled");
+}
+
 #[test]
 fn session_expires() {
     let (mut ks, now) = make_service(1_000);
@@ -121,6 +207,26 @@ fn session_expires() {
     assert!(matches!(err, KeyServiceError::SessionInvalid));
 }
 
+#[test]
+fn sweep_removes_expired_sessions_without_use() {
+    let (mut ks, now) = make_service(1_000);
+    let session_id = create_and_unlock(&mut ks);
+
+    now.set(1_005);
+    assert_eq!(ks.sweep_expired_sessions(), 0);
+    now.set(1_011);
+    assert_eq!(ks.sweep_expired_sessions(), 1);
+    assert_eq!(ks.sweep_expired_sessions(), 0);
+
+    let report = ks.export_diagnostics();
+    assert_eq!(report.session_count, 0);
+    assert!(!report.unlocked);
+    assert!(matches!(
+        ks.lock(&session_id),
+        Err(KeyServiceError::SessionInvalid)
+    ));
+}
+
 #[test]
 fn lock_clears_session() {
     let (mut ks, _) = make_service(1_000);
@@ -130,3 +236,326 @@ fn lock_clears_session() {
     let err = ks.renew_session(&session_id).unwrap_err();
     assert!(matches!(err, KeyServiceError::SessionInvalid));
 }
+
+#[test]
+fn read_only_sessions_reject_vault_writes() {
+    let (mut ks, _) = make_service(1_000);
+    let writer = create_and_unlock(&mut ks);
+    let scope_id = ScopeId("scope-1".to_string());
+    ks.persist_scope_key(&writer, &scope_id, ScopeEpoch(1), &[1u8; 32])
+        .expect("writable session");
+
+    let unlock = ks.unlock_passphrase_read_only(b"pass").expect("unlock");
+    assert!(unlock.read_only);
+    let reader = unlock.session_id;
+    let err = ks
+        .persist_scope_key(&reader, &scope_id, ScopeEpoch(2), &[2u8; 32])
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::ReadOnlySession));
+    assert_eq!(err.code(), "ReadOnlySession");
+
+    // Step-up unlocks export but keeps the session read-only.
+    ks.step_up(&reader, b"pass").expect("step up");
+    let blob = ks.export_keyvault(&reader).expect("export");
+    assert!(matches!(
+        ks.import_keyvault(&reader, &blob),
+        Err(KeyServiceError::ReadOnlySession)
+    ));
+    assert!(matches!(
+        ks.change_passphrase(&reader, b"new pass"),
+        Err(KeyServiceError::ReadOnlySession)
+    ));
+    assert!(ks.verify_keyvault(&reader).expect("verify").ok);
+
+    let config = KeyServiceConfig {
+        policy: KeyServicePolicy::builder()
+            .read_only_sessions(true)
+            .build()
+            .expect("policy"),
+    };
+    let mut view_only = KeyService::new(
+        MemStorage::default(),
+        MutableClock {
+            now: Rc::new(Cell::new(1_000)),
+        },
+        FixedEntropy {
+            counter: Cell::new(7),
+        },
+        config,
+    );
+    let session_id = create_and_unlock(&mut view_only);
+    let err = view_only
+        .persist_scope_key(&session_id, &scope_id, ScopeEpoch(1), &[1u8; 32])
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::ReadOnlySession));
+    assert!(view_only.export_diagnostics().policy.read_only_sessions);
+}
+
+#[test]
+fn oversized_payloads_are_rejected_before_key_lookup() {
+    let (mut ks, _) = make_service(1_000);
+    let session_id = create_and_unlock(&mut ks);
+    let handle = KeyHandle("missing".to_string());
+
+    let err = ks
+        .encrypt(&session_id, &handle, b"", &[0u8; 9])
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::PayloadTooLarge));
+    let err = ks
+        .decrypt(&session_id, &handle, b"", &[0u8; 64])
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::PayloadTooLarge));
+
+    // At the limit the request passes the size gate and fails on the unknown handle instead.
+    let err = ks
+        .encrypt(&session_id, &handle, b"", &[0u8; 8])
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::UnknownHandle));
+}
+
+#[test]
+fn policy_builder_validates() {
+    let policy = KeyServicePolicy::builder()
+        .normal_ttl(60_000)
+        .step_up_ttl(10_000)
+        .max_handles(8)
+        .build()
+        .expect("valid policy");
+    assert_eq!(policy.max_handles_per_session, 8);
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    assert_eq!(config.policy.normal_session_ttl_ms, 60_000);
+
+    let err = KeyServicePolicy::builder()
+        .normal_ttl(1_000)
+        .step_up_ttl(2_000)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::InvalidConfig(_)));
+    assert!(KeyServicePolicy::builder()
+        .min_passphrase_score(5)
+        .build()
+        .is_err());
+
+    // Struct literals bypass the builder, so the config builder re-validates.
+    let literal = KeyServicePolicy {
+        max_handles_per_session: 0,
+        ..KeyServicePolicy::default()
+    };
+    assert!(KeyServiceConfig::builder().policy(literal).build().is_err());
+}
+
+#[test]
+fn metrics_adapter_sees_unlock_kdf_and_record_apply() {
+    let (mut ks, _) = make_service(1_000);
+    let counts = MetricLog::default();
+    ks.set_metrics_adapter(RecordingMetrics {
+        counts: counts.clone(),
+    });
+    create_and_unlock(&mut ks);
+    assert!(ks.unlock_passphrase(b"nope").is_err());
+
+    let counts = counts.lock().expect("metrics lock");
+    assert_eq!(
+        counts.as_slice(),
+        &[
+            (MetricOp::Kdf, true),
+            (MetricOp::Kdf, true),
+            (MetricOp::RecordApply, true),
+            (MetricOp::Unlock, true),
+            (MetricOp::Kdf, true),
+            (MetricOp::Unlock, false),
+        ]
+    );
+}
+
+#[test]
+fn log_adapter_redacts_session_ids() {
+    let (mut ks, _) = make_service(1_000);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    ks.set_log_adapter(RecordingLog {
+        events: events.clone(),
+    });
+    let session_id = create_and_unlock(&mut ks);
+    assert!(ks.unlock_passphrase(b"nope").is_err());
+    ks.lock(&session_id).expect("lock");
+
+    let events = events.lock().expect("events lock");
+    let names: Vec<&str> = events.iter().map(|event| event.name).collect();
+    assert_eq!(
+        names,
+        [
+            "vault.created",
+            "audit.appended",
+            "session.unlocked",
+            "session.unlock_failed",
+            "session.locked"
+        ]
+    );
+    let tag = redact_session_id(&session_id);
+    assert!(events[2].fields.contains(&("session", tag)));
+    assert!(events[3]
+        .fields
+        .contains(&("code", "WrongPassphrase".to_string())));
+    assert!(events
+        .iter()
+        .flat_map(|event| event.fields.iter())
+        .all(|(_, value)| !value.contains(&session_id.0)));
+}
+
+#[test]
+fn health_check_flags_broken_adapters() {
+    let (ks, _) = make_service(1_000);
+    let report = ks.health_check();
+    assert!(report.ok, "{:?}", report.problems);
+
+    let (ks, _) = make_service(0);
+    let report = ks.health_check();
+    assert!(!report.ok);
+    assert!(!report.clock_ok);
+    assert!(report.storage_ok && report.entropy_ok);
+
+    let ks = KeyService::new(
+        MemStorage::default(),
+        MutableClock {
+            now: Rc::new(Cell::new(1_000)),
+        },
+        ZeroEntropy,
+        KeyServiceConfig::default(),
+    );
+    let report = ks.health_check();
+    assert!(!report.entropy_ok);
+    assert_eq!(report.problems.len(), 1);
+}
+
+#[test]
+fn timer_adapter_drives_expiry_warning_and_auto_lock() {
+    let now = Rc::new(Cell::new(1_000));
+    let config = KeyServiceConfig {
+        policy: KeyServicePolicy {
+            normal_session_ttl_ms: 10,
+            step_up_session_ttl_ms: 5,
+            session_expiry_warning_ms: 4,
+            ..KeyServicePolicy::default()
+        },
+    };
+    let mut ks = KeyService::new(
+        MemStorage::default(),
+        MutableClock { now: now.clone() },
+        FixedEntropy {
+            counter: Cell::new(7),
+        },
+        config,
+    );
+    let timer = RecordingTimer::default();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    ks.set_timer_adapter(timer.clone());
+    ks.set_session_events_adapter(RecordingSessionEvents {
+        events: events.clone(),
+    });
+    assert!(timer.take().is_empty());
+
+    // Expires at 1_010; the warning is due at 1_006.
+    let session_id = create_and_unlock(&mut ks);
+    assert_eq!(timer.take(), vec![Some(6)]);
+    now.set(1_006);
+    ks.on_timer();
+    assert_eq!(
+        events.lock().expect("events lock").as_slice(),
+        [SessionEvent::ExpiringSoon {
+            session_id: session_id.clone(),
+            expires_at_ms: 1_010,
+        }]
+    );
+    assert_eq!(timer.take(), vec![Some(5)]);
+
+    // Renewing moves the expiry and re-arms the warning.
+    ks.renew_session(&session_id).expect("renew");
+    assert_eq!(timer.take(), vec![Some(6)]);
+
+    now.set(1_017);
+    ks.on_timer();
+    assert_eq!(
+        events.lock().expect("events lock").last(),
+        Some(&SessionEvent::Expired {
+            session_id: session_id.clone(),
+        })
+    );
+    // The fired wake-up was the last one; nothing is rescheduled.
+    assert!(timer.take().is_empty());
+    assert!(matches!(
+        ks.renew_session(&session_id),
+        Err(KeyServiceError::SessionInvalid)
+    ));
+}
+
+#[test]
+fn locking_a_session_sends_a_locked_event() {
+    let mut ks = KeyService::new(
+        MemStorage::default(),
+        MutableClock {
+            now: Rc::new(Cell::new(1_000)),
+        },
+        FixedEntropy {
+            counter: Cell::new(9),
+        },
+        KeyServiceConfig::default(),
+    );
+    let events = Arc::new(Mutex::new(Vec::new()));
+    ks.set_session_events_adapter(RecordingSessionEvents {
+        events: events.clone(),
+    });
+    let session_id = create_and_unlock(&mut ks);
+    ks.lock(&session_id).expect("lock");
+    assert_eq!(
+        events.lock().expect("events lock").as_slice(),
+        [SessionEvent::Locked { session_id }]
+    );
+}
+
+#[test]
+fn lock_all_wipes_every_listed_session() {
+    let (mut ks, now) = make_service(1_000);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    ks.set_session_events_adapter(RecordingSessionEvents {
+        events: events.clone(),
+    });
+    let first = create_and_unlock(&mut ks);
+    now.set(1_002);
+    let second = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+
+    let listed = ks.list_sessions();
+    assert_eq!(
+        listed
+            .iter()
+            .map(|s| (s.session_id.clone(), s.issued_at_ms))
+            .collect::<Vec<_>>(),
+        [(first.clone(), 1_000), (second.clone(), 1_002)]
+    );
+    assert!(listed.iter().all(|s| s.handle_count == 0 && !s.read_only));
+
+    assert_eq!(ks.lock_all(), 2);
+    assert!(ks.list_sessions().is_empty());
+    assert_eq!(ks.lock_all(), 0);
+    for session_id in [&first, &second] {
+        assert!(matches!(
+            ks.renew_session(session_id),
+            Err(KeyServiceError::SessionInvalid)
+        ));
+    }
+    let mut locked: Vec<SessionId> = events
+        .lock()
+        .expect("events lock")
+        .iter()
+        .filter_map(|event| match event {
+            SessionEvent::Locked { session_id } => Some(session_id.clone()),
+            _ => None,
+        })
+        .collect();
+    locked.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut expected = vec![first, second];
+    expected.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(locked, expected);
+}
@@ -0,0 +1,105 @@
+use mo_key_service_core::ciphersuite::generate_device_signing_keypair;
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::shared_key_service::SharedKeyService;
+use mo_key_service_core::testkit::{
+    fast_kdf, ingest_scope_and_grants, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{KeyHandle, ScopeEpoch, ScopeId, SessionId, UserId};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+type Shared = SharedKeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+type Core = KeyService<MemoryStorage, Arc<VirtualClock>, Arc<SeededEntropy>>;
+
+fn open_resource(core: &mut Core, session_id: &SessionId, grant: &[u8]) -> KeyHandle {
+    let scope_handle = core
+        .open_scope(session_id, ScopeId("scope-1".to_string()), ScopeEpoch(1))
+        .expect("open scope");
+    core.open_resource(session_id, &scope_handle.scope_key_handle, grant)
+        .expect("open resource")
+        .resource_key_handle
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn shared_key_service_is_send_and_sync() {
+    assert_send_sync::<Shared>();
+}
+
+#[test]
+fn cached_sessions_encrypt_while_the_core_is_busy() {
+    let shared = Arc::new(SharedKeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(5),
+        test_config(),
+    ));
+    let signer = generate_device_signing_keypair().expect("signer");
+    let (a, b, handle_a, handle_b) = shared.with_core(|core| {
+        core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+            .expect("create vault");
+        let a = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+        let b = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+        let grants = ingest_scope_and_grants(core, &a, &signer, &[None, None]);
+        let (grant_a, grant_b) = (&grants[0], &grants[1]);
+        let handle_a = open_resource(core, &a, grant_a);
+        let handle_b = open_resource(core, &b, grant_b);
+        (a, b, handle_a, handle_b)
+    });
+
+    // The first call per handle goes through the core and caches the key.
+    let sealed_a = shared
+        .encrypt(&a, &handle_a, b"aad", b"from a")
+        .expect("encrypt a");
+    let sealed_b = shared
+        .encrypt(&b, &handle_b, b"aad", b"from b")
+        .expect("encrypt b");
+
+    // While another thread holds the core lock, both sessions keep working.
+    shared.with_core(|_| {
+        let (done_tx, done) = mpsc::channel();
+        for (session_id, handle, sealed, expected) in [
+            (
+                a.clone(),
+                handle_a.clone(),
+                sealed_a.ciphertext.clone(),
+                b"from a",
+            ),
+            (
+                b.clone(),
+                handle_b.clone(),
+                sealed_b.ciphertext.clone(),
+                b"from b",
+            ),
+        ] {
+            let shared = shared.clone();
+            let done_tx = done_tx.clone();
+            std::thread::spawn(move || {
+                for _ in 0..16 {
+                    let opened = shared
+                        .decrypt(&session_id, &handle, b"aad", &sealed)
+                        .expect("decrypt");
+                    assert_eq!(opened.plaintext, expected);
+                    let resealed = shared
+                        .encrypt(&session_id, &handle, b"aad", expected)
+                        .expect("encrypt");
+                    assert_ne!(resealed.ciphertext, sealed);
+                }
+                done_tx.send(()).expect("send");
+            });
+        }
+        for _ in 0..2 {
+            done.recv_timeout(Duration::from_secs(10))
+                .expect("cached calls must not wait for the core lock");
+        }
+    });
+
+    // Locking through the core drops the session's cached keys.
+    shared.with_core(|core| core.lock(&a)).expect("lock");
+    assert!(matches!(
+        shared.encrypt(&a, &handle_a, b"aad", b"late"),
+        Err(KeyServiceError::SessionInvalid)
+    ));
+}
@@ -0,0 +1,213 @@
+use ed25519_dalek::SigningKey as Ed25519SigningKey;
+use ml_dsa::signature::Signer as MlSigner;
+use ml_dsa::{EncodedSigningKey, MlDsa65, SigningKey as MlDsaSigningKey};
+use mo_key_service_core::cbor::{cbor_bytes, cbor_map};
+use mo_key_service_core::cgka::{CgkaCommitContext, CgkaGroup};
+use mo_key_service_core::ciphersuite::{
+    generate_device_signing_keypair, generate_user_keypair, hybrid_sign, hybrid_verify,
+    hybrid_verify_or_unbound, pack_hybrid_signature, HybridSignatureKeypair, SignerKeys,
+};
+use mo_key_service_core::domains::{
+    SIG_APP_PAYLOAD_V1, SIG_CGKA_COMMIT_V1, SIG_CONTEXTS, SIG_RESOURCE_GRANT_V1, SIG_SCOPE_STATE_V1,
+};
+use mo_key_service_core::formats::{
+    encode_cgka_commit_v1, encode_scope_state_v1, CgkaMemberV1, ScopeStateV1,
+};
+use mo_key_service_core::hash::sha256;
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, SignedFormat,
+};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{DeviceId, ScopeId, SessionId, SigCiphersuiteId, UserId};
+use signature::Signer as EdSigner;
+use std::collections::HashSet;
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn signer_keys(keypair: &HybridSignatureKeypair) -> SignerKeys {
+    SignerKeys {
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        ed25519_pub: keypair.ed25519_pub.clone(),
+        mldsa_pub: keypair.mldsa_pub.clone(),
+    }
+}
+
+/// A signature over the bare bytes, as made before signing contexts existed.
+fn unbound_sign(data: &[u8], keypair: &HybridSignatureKeypair) -> Vec<u8> {
+    let ed_seed: [u8; 32] = keypair.ed25519_priv.as_slice().try_into().unwrap();
+    let ed_sig = Ed25519SigningKey::from_bytes(&ed_seed).sign(data);
+    let ml_enc: EncodedSigningKey<MlDsa65> = keypair.mldsa_priv.as_slice().try_into().unwrap();
+    let ml_sig = MlDsaSigningKey::<MlDsa65>::decode(&ml_enc).sign(data);
+    pack_hybrid_signature(&ed_sig.to_bytes(), &ml_sig.encode()).unwrap()
+}
+
+/// A vault whose `scope-1` roster trusts `owner` as `device-1`.
+fn core_trusting(owner: &HybridSignatureKeypair, accept_unbound: bool) -> Core {
+    vault_trusting(owner, accept_unbound).0
+}
+
+/// [`core_trusting`], with its session and the ref of the ingested genesis state.
+fn vault_trusting(
+    owner: &HybridSignatureKeypair,
+    accept_unbound: bool,
+) -> (Core, SessionId, Vec<u8>) {
+    let policy = test_policy()
+        .accept_unbound_signatures(accept_unbound)
+        .build()
+        .expect("policy");
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(71),
+        config,
+    );
+    let kdf = fast_kdf();
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", kdf)
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+
+    let mut scope_state = ScopeStateV1 {
+        v: 1,
+        scope_id: ScopeId("scope-1".to_string()),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: 0,
+        payload: cbor_map(vec![
+            (1, cbor_bytes(&owner.ed25519_pub)),
+            (2, cbor_bytes(&owner.mldsa_pub)),
+        ]),
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    scope_state.signature = hybrid_sign(
+        SIG_SCOPE_STATE_V1,
+        &scope_state.to_be_signed_bytes().unwrap(),
+        owner,
+    )
+    .unwrap();
+    let signer = signer_keys(owner);
+    let fingerprint = hex::encode(sha256(&[signer.ed25519_pub, signer.mldsa_pub].concat()));
+    let ingested = core
+        .ingest_scope_state(
+            &session_id,
+            &encode_scope_state_v1(&scope_state).unwrap(),
+            Some(fingerprint),
+        )
+        .expect("ingest scope state");
+    let scope_state_ref = hex::decode(ingested.scope_state_ref).unwrap();
+    (core, session_id, scope_state_ref)
+}
+
+fn verify(core: &mut Core, data: &[u8], signature: &[u8]) -> bool {
+    core.verify(
+        ScopeId("scope-1".to_string()),
+        DeviceId("device-1".to_string()),
+        data,
+        signature,
+        SigCiphersuiteId::HybridSig1,
+    )
+    .expect("verify")
+    .ok
+}
+
+#[test]
+fn contexts_are_pinned_and_distinct() {
+    assert_eq!(SIG_SCOPE_STATE_V1.tag, "mo-sig|scope-state|v1");
+    assert_eq!(
+        SIG_APP_PAYLOAD_V1.frame(b"hi"),
+        [&[21u8][..], b"mo-sig|app-payload|v1", b"hi"].concat()
+    );
+    let tags: HashSet<&str> = SIG_CONTEXTS.iter().map(|context| context.tag).collect();
+    assert_eq!(tags.len(), SIG_CONTEXTS.len());
+    assert!(SIG_CONTEXTS
+        .iter()
+        .all(|context| context.tag.starts_with("mo-sig|") && context.tag.len() < 256));
+}
+
+#[test]
+fn signatures_do_not_cross_message_types() {
+    let keypair = generate_device_signing_keypair().expect("keypair");
+    let signer = signer_keys(&keypair);
+    let signature = hybrid_sign(SIG_RESOURCE_GRANT_V1, b"bytes", &keypair).unwrap();
+    assert!(hybrid_verify(
+        SIG_RESOURCE_GRANT_V1,
+        b"bytes",
+        &signature,
+        &signer
+    ));
+    for context in SIG_CONTEXTS.iter().filter(|c| **c != SIG_RESOURCE_GRANT_V1) {
+        assert!(!hybrid_verify_or_unbound(
+            *context, b"bytes", &signature, &signer, true
+        ));
+    }
+
+    let owner = generate_device_signing_keypair().expect("owner");
+    let mut core = core_trusting(&owner, true);
+    let grant_sig = hybrid_sign(SIG_RESOURCE_GRANT_V1, b"payload", &owner).unwrap();
+    assert!(!verify(&mut core, b"payload", &grant_sig));
+    let app_sig = hybrid_sign(SIG_APP_PAYLOAD_V1, b"payload", &owner).unwrap();
+    assert!(verify(&mut core, b"payload", &app_sig));
+}
+
+#[test]
+fn unbound_signatures_verify_only_while_the_policy_accepts_them() {
+    let owner = generate_device_signing_keypair().expect("owner");
+    let legacy = unbound_sign(b"payload", &owner);
+    assert!(!hybrid_verify(
+        SIG_APP_PAYLOAD_V1,
+        b"payload",
+        &legacy,
+        &signer_keys(&owner)
+    ));
+
+    let mut migrating = core_trusting(&owner, true);
+    assert!(verify(&mut migrating, b"payload", &legacy));
+    let mut strict = core_trusting(&owner, false);
+    assert!(!verify(&mut strict, b"payload", &legacy));
+    let bound = hybrid_sign(SIG_APP_PAYLOAD_V1, b"payload", &owner).unwrap();
+    assert!(verify(&mut strict, b"payload", &bound));
+}
+
+#[test]
+fn cgka_commits_need_bound_signatures_even_while_migrating() {
+    let owner = generate_device_signing_keypair().expect("owner");
+    let (mut core, session_id, scope_state_ref) = vault_trusting(&owner, true);
+    let (keys, _) = generate_user_keypair().unwrap();
+    let member = CgkaMemberV1 {
+        user_id: UserId("user-1".to_string()),
+        public_bytes: keys.public_bytes,
+    };
+    let (group, _) =
+        CgkaGroup::create(ScopeId("scope-1".to_string()), 1, &member, &[7u8; 32]).unwrap();
+    let context = CgkaCommitContext {
+        scope_state_ref,
+        signer_device_id: DeviceId("device-1".to_string()),
+        sig_suite: SigCiphersuiteId::HybridSig1,
+    };
+    let mut commit = group.commit(&[], &[], &[8u8; 32], context).unwrap().commit;
+    let to_be_signed = commit.to_be_signed_bytes().unwrap();
+
+    commit.signature = unbound_sign(&to_be_signed, &owner);
+    assert!(matches!(
+        core.ingest_cgka_commit(&session_id, &encode_cgka_commit_v1(&commit).unwrap()),
+        Err(KeyServiceError::SignatureInvalid {
+            format: SignedFormat::CgkaCommit
+        })
+    ));
+    // A bound signature gets past the signer checks; this vault just has no group.
+    commit.signature = hybrid_sign(SIG_CGKA_COMMIT_V1, &to_be_signed, &owner).unwrap();
+    assert!(matches!(
+        core.ingest_cgka_commit(&session_id, &encode_cgka_commit_v1(&commit).unwrap()),
+        Err(KeyServiceError::CgkaFailed {
+            reason: "no group for the scope"
+        })
+    ));
+}
@@ -0,0 +1,156 @@
+use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::stream::{
+    StreamDecryptor, StreamEncryptor, STREAM_HEADER_LEN, STREAM_HEADER_RANDOM_LEN,
+};
+use mo_key_service_core::testkit::{fast_kdf, test_config};
+use mo_key_service_core::types::{KeyHandle, StreamId, UserId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct MemStorage {
+    data: RefCell<HashMap<(String, String), Vec<u8>>>,
+}
+
+impl StorageAdapter for MemStorage {
+    type Error = String;
+
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self
+            .data
+            .borrow()
+            .get(&(namespace.to_string(), key.to_string()))
+            .cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+        self.data
+            .borrow_mut()
+            .insert((namespace.to_string(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+    fn list_since(
+        &self,
+        namespace: &str,
+        cursor: &str,
+        _limit: usize,
+    ) -> Result<(Vec<(String, Vec<u8>)>, String), Self::Error> {
+        let mut out = Vec::new();
+        for ((ns, key), value) in self.data.borrow().iter() {
+            if ns == namespace && key.as_str() >= cursor {
+                out.push((key.clone(), value.clone()));
+            }
+        }
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok((out, "".to_string()))
+    }
+}
+
+struct FixedClock {
+    now: u64,
+}
+
+impl ClockAdapter for FixedClock {
+    fn now_ms(&self) -> u64 {
+        self.now
+    }
+}
+
+struct FixedEntropy {
+    counter: Cell<u8>,
+}
+
+impl EntropyAdapter for FixedEntropy {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let value = self.counter.get();
+        self.counter.set(value.wrapping_add(1));
+        vec![value; len]
+    }
+}
+
+fn encrypt_chunks(key: &[u8], aad: &[u8], chunks: &[&[u8]]) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let (mut encryptor, header) =
+        StreamEncryptor::new(key, aad, &[5u8; STREAM_HEADER_RANDOM_LEN]).expect("init");
+    let mut out = Vec::new();
+    let (last, body) = chunks.split_last().expect("chunks");
+    for chunk in body {
+        out.push(encryptor.push(chunk).expect("push"));
+    }
+    out.push(encryptor.finish(last).expect("finish"));
+    (header, out)
+}
+
+#[test]
+fn stream_round_trip() {
+    let key = vec![4u8; 32];
+    let chunks: [&[u8]; 3] = [b"first chunk", b"second chunk", b"tail"];
+    let (header, ciphertexts) = encrypt_chunks(&key, b"aad", &chunks);
+    assert_eq!(header.len(), STREAM_HEADER_LEN);
+
+    let mut decryptor = StreamDecryptor::new(&key, b"aad", &header).expect("init");
+    let mut plaintext = Vec::new();
+    for ct in &ciphertexts[..2] {
+        plaintext.extend(decryptor.push(ct).expect("push"));
+    }
+    plaintext.extend(decryptor.finish(&ciphertexts[2]).expect("finish"));
+    assert_eq!(plaintext, b"first chunksecond chunktail");
+}
+
+#[test]
+fn stream_rejects_truncation_and_reordering() {
+    let key = vec![4u8; 32];
+    let chunks: [&[u8]; 3] = [b"a", b"b", b"c"];
+    let (header, ciphertexts) = encrypt_chunks(&key, b"aad", &chunks);
+
+    let mut truncated = StreamDecryptor::new(&key, b"aad", &header).expect("init");
+    truncated.push(&ciphertexts[0]).expect("push");
+    assert!(truncated.finish(&ciphertexts[1]).is_err());
+
+    let mut reordered = StreamDecryptor::new(&key, b"aad", &header).expect("init");
+    assert!(reordered.push(&ciphertexts[1]).is_err());
+
+    let mut wrong_aad = StreamDecryptor::new(&key, b"other", &header).expect("init");
+    assert!(wrong_aad.push(&ciphertexts[0]).is_err());
+}
+
+#[test]
+fn stream_rejects_bad_header() {
+    let key = vec![4u8; 32];
+    assert!(StreamDecryptor::new(&key, b"aad", &[1u8; 4]).is_err());
+    let mut header = vec![9u8];
+    header.extend_from_slice(&[0u8; STREAM_HEADER_RANDOM_LEN]);
+    assert!(StreamDecryptor::new(&key, b"aad", &header).is_err());
+}
+
+#[test]
+fn service_rejects_unknown_stream_and_handle() {
+    let entropy = FixedEntropy {
+        counter: Cell::new(3),
+    };
+    let mut ks = KeyService::new(
+        MemStorage::default(),
+        FixedClock { now: 1_000 },
+        entropy,
+        test_config(),
+    );
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+
+    let err = ks
+        .encrypt_init(&session_id, &KeyHandle("missing".to_string()), b"aad")
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::UnknownHandle));
+
+    let err = ks
+        .encrypt_push(&session_id, &StreamId("missing".to_string()), b"chunk")
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::UnknownStream));
+
+    let err = ks
+        .decrypt_finish(&session_id, &StreamId("missing".to_string()), b"chunk")
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::UnknownStream));
+}
@@ -0,0 +1,215 @@
+use mo_key_service_core::cbor::CborLimits;
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, KeyServicePolicy,
+};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{SessionId, SnapshotCompression, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn stepped_up(seed: u64, policy: KeyServicePolicy) -> (Core, SessionId) {
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        config,
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    (core, session_id)
+}
+
+fn with_notes(seed: u64) -> (Core, SessionId) {
+    let (mut core, session_id) = stepped_up(seed, test_policy().build().expect("policy"));
+    for i in 0..12 {
+        core.put_vault_blob(&session_id, &format!("note-{i}"), &[b'a' + i as u8; 1024])
+            .expect("put blob");
+    }
+    (core, session_id)
+}
+
+fn export_chunks(core: &mut Core, session_id: &SessionId, chunk_len: usize) -> Vec<Vec<u8>> {
+    let mut chunks = Vec::new();
+    core.export_keyvault_chunked(session_id, chunk_len, &mut |chunk| {
+        chunks.push(chunk.to_vec());
+        Ok(())
+    })
+    .expect("chunked export");
+    chunks
+}
+
+fn import_pieces(core: &mut Core, session_id: &SessionId, blob: &[u8], piece_len: usize) {
+    let stream_id = core
+        .import_keyvault_init(session_id)
+        .expect("import init")
+        .stream_id;
+    for piece in blob.chunks(piece_len) {
+        core.import_keyvault_push(session_id, &stream_id, piece)
+            .expect("import push");
+    }
+    core.import_keyvault_finish(session_id, &stream_id)
+        .expect("import finish");
+}
+
+#[test]
+fn chunked_export_matches_the_single_blob_export() {
+    let (mut core, session_id) = with_notes(81);
+    let blob = core.export_keyvault(&session_id).expect("export");
+    for chunk_len in [1, 7, 1000, blob.len() + 1] {
+        let chunks = export_chunks(&mut core, &session_id, chunk_len);
+        assert!(chunks[..chunks.len() - 1]
+            .iter()
+            .all(|chunk| chunk.len() == chunk_len));
+        assert_eq!(chunks.concat(), blob);
+    }
+    assert!(matches!(
+        core.export_keyvault_chunked(&session_id, 0, &mut |_| Ok(())),
+        Err(KeyServiceError::InvalidFormat(_))
+    ));
+}
+
+#[test]
+fn sink_errors_stop_the_export() {
+    let (mut core, session_id) = with_notes(82);
+    let mut calls = 0;
+    let err = core
+        .export_keyvault_chunked(&session_id, 512, &mut |_| {
+            calls += 1;
+            if calls == 3 {
+                Err(KeyServiceError::Cancelled)
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap_err();
+    assert!(matches!(err, KeyServiceError::Cancelled));
+    assert_eq!(calls, 3);
+}
+
+#[test]
+fn incremental_import_restores_the_vault() {
+    let (mut source, source_session) = with_notes(83);
+    let blob = source.export_keyvault(&source_session).expect("export");
+
+    for (piece_len, seed) in [(1, 84), (333, 85), (blob.len(), 86)] {
+        let (mut target, target_session) = stepped_up(seed, test_policy().build().expect("policy"));
+        import_pieces(&mut target, &target_session, &blob, piece_len);
+        target.lock(&target_session).expect("lock");
+        let session_id = target
+            .unlock_passphrase(b"pass")
+            .expect("unlock")
+            .session_id;
+        assert_eq!(
+            target
+                .get_vault_blob(&session_id, "note-11")
+                .unwrap()
+                .unwrap()
+                .as_slice(),
+            &[b'a' + 11; 1024][..]
+        );
+    }
+}
+
+#[test]
+fn incremental_import_applies_the_cbor_limit_per_item() {
+    let (mut source, source_session) = with_notes(87);
+    let blob = source.export_keyvault(&source_session).expect("export");
+    let policy = test_policy()
+        .cbor_limits(CborLimits {
+            max_bytes: 4096,
+            max_text_bytes: 1024,
+            ..CborLimits::default()
+        })
+        .build()
+        .expect("policy");
+    assert!(blob.len() > 4096);
+
+    let (mut target, target_session) = stepped_up(88, policy);
+    assert!(matches!(
+        target.import_keyvault(&target_session, &blob),
+        Err(KeyServiceError::InvalidCbor(_))
+    ));
+    import_pieces(&mut target, &target_session, &blob, 256);
+}
+
+#[test]
+fn failed_pushes_discard_the_import() {
+    let (mut source, source_session) = with_notes(89);
+    let blob = source.export_keyvault(&source_session).expect("export");
+    let policy = test_policy().max_total_records(4).build().expect("policy");
+    let (mut target, target_session) = stepped_up(90, policy);
+
+    let stream_id = target
+        .import_keyvault_init(&target_session)
+        .expect("import init")
+        .stream_id;
+    let err = target
+        .import_keyvault_push(&target_session, &stream_id, &blob)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        KeyServiceError::VaultLimitExceeded {
+            limit: "max_total_records"
+        }
+    ));
+    assert!(matches!(
+        target.import_keyvault_push(&target_session, &stream_id, &blob),
+        Err(KeyServiceError::UnknownStream)
+    ));
+}
+
+#[test]
+fn truncated_and_compressed_snapshots_are_rejected() {
+    let (mut core, session_id) = with_notes(91);
+    let blob = core.export_keyvault(&session_id).expect("export");
+
+    let stream_id = core
+        .import_keyvault_init(&session_id)
+        .expect("import init")
+        .stream_id;
+    core.import_keyvault_push(&session_id, &stream_id, &blob[..blob.len() - 1])
+        .expect("push");
+    assert!(matches!(
+        core.import_keyvault_finish(&session_id, &stream_id),
+        Err(KeyServiceError::InvalidFormat(_))
+    ));
+
+    let stream_id = core
+        .import_keyvault_init(&session_id)
+        .expect("import init")
+        .stream_id;
+    assert!(matches!(
+        core.import_keyvault_push(&session_id, &stream_id, &[blob.clone(), vec![0]].concat()),
+        Err(KeyServiceError::InvalidCbor(_))
+    ));
+
+    let compressed = core
+        .export_keyvault_compressed(&session_id, SnapshotCompression::Deflate)
+        .expect("export v2");
+    let stream_id = core
+        .import_keyvault_init(&session_id)
+        .expect("import init")
+        .stream_id;
+    assert!(matches!(
+        core.import_keyvault_push(&session_id, &stream_id, &compressed),
+        Err(KeyServiceError::InvalidFormat(_))
+    ));
+}
+
+#[test]
+fn incremental_import_requires_step_up() {
+    let (mut core, _) = with_notes(92);
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert!(matches!(
+        core.import_keyvault_init(&session_id),
+        Err(KeyServiceError::StepUpRequired)
+    ));
+}
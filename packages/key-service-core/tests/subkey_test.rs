@@ -0,0 +1,83 @@
+use mo_key_service_core::aad::hkdf_info_resource_subkey_v1;
+use mo_key_service_core::ciphersuite::generate_device_signing_keypair;
+use mo_key_service_core::crypto::hkdf_sha256;
+use mo_key_service_core::key_service::{CapabilityRequest, KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, ingest_scope_and_grant, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    CapabilityOps, KeyHandle, ScopeEpoch, ScopeId, SessionId, UserId,
+};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn open_resource(core: &mut Core, session_id: &SessionId, grant: &[u8]) -> KeyHandle {
+    let scope_handle = core
+        .open_scope(session_id, ScopeId("scope-1".to_string()), ScopeEpoch(1))
+        .expect("open scope");
+    core.open_resource(session_id, &scope_handle.scope_key_handle, grant)
+        .expect("open resource")
+        .resource_key_handle
+}
+
+#[test]
+fn subkeys_are_bound_to_path_and_length() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(19),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let signer = generate_device_signing_keypair().expect("signer");
+    let grant = ingest_scope_and_grant(&mut core, &session_id, &signer);
+    let handle = open_resource(&mut core, &session_id, &grant);
+
+    let path: [&[u8]; 2] = [b"chunk", b"17"];
+    let subkey = core
+        .derive_subkey(&session_id, &handle, &path, 32)
+        .expect("derive");
+    let info = hkdf_info_resource_subkey_v1("res-1", "rk-1", &path, 32).unwrap();
+    assert_eq!(*subkey, *hkdf_sha256(&[4u8; 32], &info, 32).unwrap());
+    assert_eq!(
+        *core.derive_subkey(&session_id, &handle, &path, 32).unwrap(),
+        *subkey
+    );
+
+    // Segment boundaries and the length are part of the derivation.
+    let joined = core
+        .derive_subkey(&session_id, &handle, &[b"chunk17"], 32)
+        .unwrap();
+    assert_ne!(*joined, *subkey);
+    let short = core.derive_subkey(&session_id, &handle, &path, 16).unwrap();
+    assert_ne!(short[..], subkey[..16]);
+
+    for (path, len) in [(&path[..], 8), (&path[..], 65), (&[][..], 32)] {
+        assert!(matches!(
+            core.derive_subkey(&session_id, &handle, path, len),
+            Err(KeyServiceError::InvalidFormat(_))
+        ));
+    }
+
+    let minted = core
+        .mint_capability(
+            &session_id,
+            &handle,
+            &CapabilityRequest {
+                aad_prefix: Vec::new(),
+                ops: CapabilityOps::ALL,
+                ttl_ms: 1_000,
+            },
+        )
+        .expect("mint");
+    let limited = core
+        .redeem_capability(&session_id, &minted.token)
+        .expect("redeem")
+        .resource_key_handle;
+    assert!(matches!(
+        core.derive_subkey(&session_id, &limited, &path, 32),
+        Err(KeyServiceError::CapabilityDenied)
+    ));
+}
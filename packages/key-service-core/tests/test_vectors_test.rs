@@ -3,7 +3,7 @@ use mo_key_service_core::aad::aad_keyvault_record_v1;
 use mo_key_service_core::cbor::{
     cbor_map, cbor_text, cbor_uint, decode_canonical_value, CborLimits,
 };
-use mo_key_service_core::crypto::{aead_encrypt, KdfParams};
+use mo_key_service_core::crypto::{aead_encrypt, KdfMinimums, KdfParams};
 use mo_key_service_core::formats::{
     decode_key_envelope_v1, decode_keyvault_header_v1, decode_keyvault_record_container_v1,
     encode_key_envelope_v1, encode_keyvault_header_v1, encode_keyvault_record_container_v1,
@@ -12,7 +12,7 @@ use mo_key_service_core::formats::{
     KeyVaultRecordPlainV1, KeyVaultSnapshotV1, ResourceGrantV1, ScopeStateV1, VaultKeyWrapV1,
 };
 use mo_key_service_core::types::{
-    AeadId, DeviceId, KemCiphersuiteId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId,
+    AeadId, DeviceId, KemCiphersuiteId, KeyWrapAlg, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId,
     SigCiphersuiteId, UserId,
 };
 
@@ -91,6 +91,7 @@ fn key_envelope_vector() {
         sig_suite: SigCiphersuiteId::HybridSig1,
         signature: vec![0x99; 64],
         recipient_uk_pub_fingerprint: Some(vec![0xAB; 32]),
+        prekey_id: None,
     };
     assert_hex(
         encode_key_envelope_v1(&envelope).expect("encode key envelope"),
@@ -122,14 +123,22 @@ fn keyvault_header_vector() {
             aead: AeadId::Aead1,
             nonce: vec![0x10; 12],
             ct: vec![0x20; 32],
+            commitment: None,
+            alg: KeyWrapAlg::AesGcm,
         },
+        kms_key_id: None,
+        device_shares: None,
+        passphrase_check: None,
     };
     assert_hex(
         encode_keyvault_header_v1(&header).expect("encode header"),
         KEYVAULT_HEADER_HEX,
     );
-    let decoded =
-        decode_keyvault_header_v1(&hex::decode(KEYVAULT_HEADER_HEX).expect("hex")).expect("decode");
+    let decoded = decode_keyvault_header_v1(
+        &hex::decode(KEYVAULT_HEADER_HEX).expect("hex"),
+        &KdfMinimums::NONE,
+    )
+    .expect("decode");
     assert_eq!(decoded.vault_id, "vault-1");
     assert_eq!(decoded.user_id, "user-1");
     assert_eq!(decoded.kdf.salt, vec![0x01, 0x02, 0x03, 0x04]);
@@ -145,6 +154,7 @@ fn keyvault_record_and_snapshot_vector() {
             (1, cbor_uint(1)),
             (2, mo_key_service_core::cbor::cbor_bytes(&[0x42; 32])),
         ]),
+        provenance: None,
     };
     let record_plain_bytes = encode_keyvault_record_plain_v1(&record_plain).expect("record");
     let aad = aad_keyvault_record_v1("vault-1", "user-1", AeadId::Aead1, "record-1").expect("aad");
@@ -186,9 +196,15 @@ fn keyvault_record_and_snapshot_vector() {
                 aead: AeadId::Aead1,
                 nonce: vec![0x10; 12],
                 ct: vec![0x20; 32],
+                commitment: None,
+                alg: KeyWrapAlg::AesGcm,
             },
+            kms_key_id: None,
+            device_shares: None,
+            passphrase_check: None,
         },
         records: vec![record_container],
+        manifest: None,
     };
     assert_hex(
         encode_keyvault_snapshot_v1(&snapshot).expect("encode snapshot"),
@@ -202,3 +218,25 @@ fn keyvault_record_and_snapshot_vector() {
     let parsed = KeyVaultSnapshotV1::from_cbor(snapshot_value).expect("snapshot");
     assert_eq!(parsed.records.len(), 1);
 }
+
+#[test]
+fn fuzz_seeds_match_vectors() {
+    let seeds = [
+        ("scope_state", "vector", SCOPE_STATE_HEX),
+        ("resource_grant", "vector", RESOURCE_GRANT_HEX),
+        ("key_envelope", "vector", KEY_ENVELOPE_HEX),
+        ("keyvault_header", "vector", KEYVAULT_HEADER_HEX),
+        ("keyvault_record", "container", KEYVAULT_RECORD_HEX),
+        ("keyvault_snapshot", "vector", KEYVAULT_SNAPSHOT_HEX),
+        ("cbor_canonical", "scope-state", SCOPE_STATE_HEX),
+        ("cbor_canonical", "keyvault-header", KEYVAULT_HEADER_HEX),
+    ];
+    for (target, name, expected) in seeds {
+        let path = format!(
+            "{}/fuzz/corpus/{target}/seed-{name}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let seed = std::fs::read(&path).expect("read seed");
+        assert_eq!(hex::encode(seed), expected, "{path}");
+    }
+}
@@ -0,0 +1,124 @@
+use mo_key_service_core::adapters::{EntropyAdapter, StorageAdapter};
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, MemoryStorageError, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{ScopeEpoch, ScopeId, UserId};
+
+fn make_service(
+    storage: &MemoryStorage,
+    clock: &VirtualClock,
+) -> KeyService<MemoryStorage, VirtualClock, SeededEntropy> {
+    KeyService::new(
+        storage.clone(),
+        clock.clone(),
+        SeededEntropy::new(7),
+        test_config(),
+    )
+}
+
+#[test]
+fn seeded_entropy_is_reproducible() {
+    let a = SeededEntropy::new(1);
+    let b = SeededEntropy::new(1);
+    assert_eq!(a.random_bytes(45), b.random_bytes(45));
+    assert_ne!(a.random_bytes(32), a.random_bytes(32));
+    assert_ne!(SeededEntropy::new(2).random_bytes(32), b.random_bytes(32));
+}
+
+#[test]
+fn virtual_clock_expires_sessions() {
+    let storage = MemoryStorage::new();
+    let clock = VirtualClock::new(1_000);
+    let mut ks = make_service(&storage, &clock);
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let unlock = ks.unlock_passphrase(b"pass").expect("unlock");
+
+    clock.set(unlock.expires_at_ms + 1);
+    let err = ks
+        .persist_scope_key(
+            &unlock.session_id,
+            &ScopeId("scope-1".to_string()),
+            ScopeEpoch(1),
+            &[7u8; 32],
+        )
+        .expect_err("expired");
+    assert!(matches!(err, KeyServiceError::SessionInvalid));
+}
+
+#[test]
+fn memory_storage_injects_write_failures_and_restores_snapshots() {
+    let storage = MemoryStorage::new();
+    let clock = VirtualClock::new(1_000);
+    let mut ks = make_service(&storage, &clock);
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    let before = storage.snapshot();
+
+    storage.fail_nth_write(1);
+    let err = ks
+        .persist_scope_key(
+            &session_id,
+            &ScopeId("scope-1".to_string()),
+            ScopeEpoch(1),
+            &[7u8; 32],
+        )
+        .expect_err("injected failure");
+    assert!(matches!(err, KeyServiceError::StorageError(_)));
+    assert_eq!(storage.snapshot(), before);
+
+    storage.put("ns", "b", b"2").expect("put");
+    storage.put("ns", "a", b"1").expect("put");
+    storage.put("ns", "c", b"3").expect("put");
+    let (first, cursor) = storage.list_since("ns", "", 2).expect("list");
+    assert_eq!(
+        first.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+        ["a", "b"]
+    );
+    let (rest, _) = storage.list_since("ns", &cursor, 2).expect("list");
+    assert_eq!(rest, vec![("c".to_string(), b"3".to_vec())]);
+
+    storage.restore(before.clone());
+    assert_eq!(storage.snapshot(), before);
+    assert_eq!(
+        storage.get("ns", "a").expect("get"),
+        None,
+        "restore drops later writes"
+    );
+    storage.fail_nth_write(2);
+    storage.put("ns", "x", b"1").expect("first write succeeds");
+    assert!(matches!(
+        storage.put("ns", "y", b"1"),
+        Err(MemoryStorageError::InjectedWriteFailure { .. })
+    ));
+}
+
+#[test]
+fn failed_unlocks_leave_no_session_behind() {
+    let storage = MemoryStorage::new();
+    let clock = VirtualClock::new(1_000);
+    let mut ks = make_service(&storage, &clock);
+    ks.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+
+    let writes = storage.write_count();
+    assert!(matches!(
+        ks.unlock_passphrase(b"wrong"),
+        Err(KeyServiceError::WrongPassphrase)
+    ));
+    assert_eq!(storage.write_count(), writes);
+    assert_eq!(ks.export_diagnostics().session_count, 0);
+
+    let session_id = ks.unlock_passphrase(b"pass").expect("unlock").session_id;
+    storage.fail_nth_write(1);
+    assert!(matches!(
+        ks.unlock_passphrase(b"pass"),
+        Err(KeyServiceError::StorageError(_))
+    ));
+    storage.clear_faults();
+    assert_eq!(ks.export_diagnostics().session_count, 1);
+    ks.renew_session(&session_id)
+        .expect("earlier session still usable");
+}
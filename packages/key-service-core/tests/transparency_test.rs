@@ -0,0 +1,231 @@
+use mo_key_service_core::formats::{encode_scope_state_v1, ScopeStatePayload, ScopeStateV1};
+use mo_key_service_core::hash::sha256;
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, SignedFormat,
+};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, test_policy, MemoryStorage, SeededEntropy, TransparencyTestLog,
+    VirtualClock,
+};
+use mo_key_service_core::transparency::{
+    consistency_path, inclusion_path, merkle_root, verify_consistency, verify_inclusion,
+    InclusionProof, TransparencyLeaf,
+};
+use mo_key_service_core::types::{DeviceId, ScopeId, SessionId, SigCiphersuiteId, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn unlocked(config: KeyServiceConfig) -> (Core, SessionId) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(71),
+        config,
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    (core, session_id)
+}
+
+/// A genesis for `scope_id` signed by the vault's own device; returns it with the device's
+/// fingerprint.
+fn genesis(core: &mut Core, session_id: &SessionId, scope_id: &str) -> (ScopeStateV1, String) {
+    let device = core
+        .get_device_public_keys(session_id)
+        .expect("device keys")
+        .devices
+        .remove(0);
+    let payload = ScopeStatePayload::Genesis {
+        signer: device.signer,
+    };
+    let mut scope_state = ScopeStateV1 {
+        v: 1,
+        scope_id: ScopeId(scope_id.to_string()),
+        scope_state_seq: 1,
+        prev_hash: vec![0u8; 32],
+        scope_epoch: 1,
+        kind: payload.kind(),
+        payload: payload.to_cbor(),
+        signer_device_id: device.device_id,
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        signature: Vec::new(),
+    };
+    scope_state.signature = core
+        .sign_format(
+            session_id,
+            SignedFormat::ScopeState,
+            &scope_state.to_be_signed_bytes().unwrap(),
+        )
+        .expect("sign scope state")
+        .signature;
+    (scope_state, device.fingerprint)
+}
+
+fn leaf_of(scope_state: &ScopeStateV1) -> TransparencyLeaf {
+    TransparencyLeaf::ScopeState {
+        scope_id: scope_state.scope_id.clone(),
+        scope_state_ref: scope_state.scope_state_ref_bytes().unwrap(),
+    }
+}
+
+fn ingest(
+    core: &mut Core,
+    session_id: &SessionId,
+    scope_state: &ScopeStateV1,
+    fingerprint: &str,
+) -> Result<(), KeyServiceError> {
+    core.ingest_scope_state(
+        session_id,
+        &encode_scope_state_v1(scope_state).unwrap(),
+        Some(fingerprint.to_string()),
+    )
+    .map(|_| ())
+}
+
+fn assert_reason(result: Result<(), KeyServiceError>, expected: &str) {
+    match result {
+        Err(KeyServiceError::TransparencyCheckFailed { reason }) => assert_eq!(reason, expected),
+        other => panic!("expected TransparencyCheckFailed({expected}), got {other:?}"),
+    }
+}
+
+#[test]
+fn proofs_verify_for_every_leaf_and_prefix() {
+    let leaves: Vec<[u8; 32]> = (0u8..13).map(|i| sha256(&[i])).collect();
+    for size in 1..=leaves.len() {
+        let tree = &leaves[..size];
+        let root = merkle_root(tree);
+        for index in 0..size {
+            let proof = InclusionProof {
+                leaf_index: index as u64,
+                tree_size: size as u64,
+                audit_path: inclusion_path(tree, index),
+            };
+            assert!(verify_inclusion(&tree[index], &proof, &root));
+            assert!(!verify_inclusion(&sha256(b"other"), &proof, &root));
+        }
+        for first in 1..=size {
+            let first_root = merkle_root(&leaves[..first]);
+            let proof = consistency_path(tree, first);
+            assert!(verify_consistency(
+                first as u64,
+                &first_root,
+                size as u64,
+                &root,
+                &proof
+            ));
+            if first < size {
+                assert!(!verify_consistency(
+                    first as u64,
+                    &sha256(b"forked"),
+                    size as u64,
+                    &root,
+                    &proof
+                ));
+            }
+        }
+    }
+}
+
+#[test]
+fn scope_states_and_user_keys_must_be_in_the_log() {
+    let (mut core, session_id) = unlocked(test_config());
+    let log = TransparencyTestLog::new("log-1").expect("log");
+    core.set_transparency_adapter(log.log_key(), log.clone());
+
+    let (logged, fingerprint) = genesis(&mut core, &session_id, "scope-1");
+    let (unlogged, _) = genesis(&mut core, &session_id, "scope-2");
+    log.append(&leaf_of(&logged)).unwrap();
+    log.publish(1_000).unwrap();
+    ingest(&mut core, &session_id, &logged, &fingerprint).expect("logged scope state");
+    assert_reason(
+        ingest(&mut core, &session_id, &unlogged, &fingerprint),
+        "entry not in the transparency log",
+    );
+
+    let public_bytes = core
+        .get_user_public_key(&session_id)
+        .expect("user key")
+        .public_bytes;
+    let user_id = UserId("user-1".to_string());
+    assert_reason(
+        core.verify_user_public_key(&session_id, &user_id, &public_bytes),
+        "entry not in the transparency log",
+    );
+    log.append(&TransparencyLeaf::UserPublicKey {
+        user_id: user_id.clone(),
+        public_bytes: public_bytes.clone(),
+    })
+    .unwrap();
+    log.publish(1_000).unwrap();
+    core.verify_user_public_key(&session_id, &user_id, &public_bytes)
+        .expect("logged user key");
+    assert_reason(
+        core.verify_user_public_key(&session_id, &UserId("user-2".to_string()), &public_bytes),
+        "entry not in the transparency log",
+    );
+}
+
+#[test]
+fn rewritten_history_and_stale_heads_are_rejected() {
+    let (mut core, session_id) = unlocked(test_config());
+    let log = TransparencyTestLog::new("log-1").expect("log");
+    core.set_transparency_adapter(log.log_key(), log.clone());
+    let (first, fingerprint) = genesis(&mut core, &session_id, "scope-1");
+    let (second, _) = genesis(&mut core, &session_id, "scope-2");
+    let (third, _) = genesis(&mut core, &session_id, "scope-3");
+    log.append(&leaf_of(&first)).unwrap();
+    log.append(&leaf_of(&second)).unwrap();
+    log.publish(1_000).unwrap();
+    ingest(&mut core, &session_id, &first, &fingerprint).expect("first");
+
+    // The log swaps an entry the client already saw a head over, then appends.
+    log.rewrite(1, &leaf_of(&third)).unwrap();
+    log.append(&leaf_of(&second)).unwrap();
+    log.publish(2_000).unwrap();
+    assert_reason(
+        ingest(&mut core, &session_id, &second, &fingerprint),
+        "tree head inconsistent with the trusted one",
+    );
+
+    // A head signed by another key is refused outright.
+    let impostor = TransparencyTestLog::new("log-1").expect("impostor");
+    impostor.append(&leaf_of(&first)).unwrap();
+    impostor.publish(1_000).unwrap();
+    core.set_transparency_adapter(log.log_key(), impostor);
+    assert_reason(
+        ingest(&mut core, &session_id, &first, &fingerprint),
+        "tree head signature invalid",
+    );
+
+    let policy = test_policy()
+        .require_transparency(true)
+        .max_tree_head_age(500)
+        .build()
+        .expect("policy");
+    let config = KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .expect("config");
+    let (mut strict, strict_session) = unlocked(config);
+    let (scope_state, fingerprint) = genesis(&mut strict, &strict_session, "scope-1");
+    assert_reason(
+        ingest(&mut strict, &strict_session, &scope_state, &fingerprint),
+        "no transparency log configured",
+    );
+    let log = TransparencyTestLog::new("log-1").expect("log");
+    log.append(&leaf_of(&scope_state)).unwrap();
+    log.publish(100).unwrap();
+    strict.set_transparency_adapter(log.log_key(), log.clone());
+    assert_reason(
+        ingest(&mut strict, &strict_session, &scope_state, &fingerprint),
+        "tree head too old",
+    );
+    log.publish(1_000).unwrap();
+    ingest(&mut strict, &strict_session, &scope_state, &fingerprint).expect("fresh head");
+
+    assert!(test_policy().max_tree_head_age(0).build().is_err());
+}
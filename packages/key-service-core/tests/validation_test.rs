@@ -1,12 +1,21 @@
+use ciborium::value::Value;
+use mo_key_service_core::cbor::{
+    as_map, cbor_array, cbor_bytes, cbor_map, cbor_text, cbor_uint, req_bytes, req_text,
+    zeroize_value,
+};
+use mo_key_service_core::ciphersuite::SignerKeys;
 use mo_key_service_core::crypto::KdfParams;
 use mo_key_service_core::formats::{
     decode_resource_grant_v1, decode_scope_state_v1, encode_resource_grant_v1,
     encode_scope_state_v1, KeyVaultHeaderV1, KeyVaultRecordContainerV1, KeyVaultRecordPlainV1,
-    ResourceGrantV1, ScopeStateV1, VaultKeyWrapV1,
+    ResourceGrantV1, ScopeStatePayload, ScopeStateV1, VaultKeyWrapV1,
+};
+use mo_key_service_core::keyvault::{
+    make_checkpoint_record, make_store_scope_key_record, KeyVaultCheckpoint, KeyVaultState,
 };
-use mo_key_service_core::keyvault::{make_store_scope_key_record, KeyVaultState};
 use mo_key_service_core::types::{
-    AeadId, DeviceId, ResourceId, ResourceKeyId, ScopeId, SigCiphersuiteId,
+    AeadId, DeviceId, EpochRetirement, KeyWrapAlg, ResourceId, ResourceKeyId, ScopeId, ScopeRole,
+    SigCiphersuiteId, UserId,
 };
 
 fn make_header() -> KeyVaultHeaderV1 {
@@ -22,7 +31,12 @@ fn make_header() -> KeyVaultHeaderV1 {
             aead: AeadId::Aead1,
             nonce: vec![1u8; 12],
             ct: vec![2u8; 16],
+            commitment: None,
+            alg: KeyWrapAlg::AesGcm,
         },
+        kms_key_id: None,
+        device_shares: None,
+        passphrase_check: None,
     }
 }
 
@@ -67,6 +81,67 @@ fn keyvault_rejects_duplicate_record_id() {
     assert!(result.is_err());
 }
 
+#[test]
+fn keyvault_checks_checkpoints_against_the_chain() {
+    let (header, vault_key, containers) = make_containers();
+    let (state, _) =
+        KeyVaultState::apply_containers(&header, &vault_key, &containers).expect("apply");
+    let at_head = KeyVaultCheckpoint {
+        seq: state.head_seq,
+        hash: state.head_hash.clone(),
+    };
+    for (checkpoint, ok) in [
+        (at_head.clone(), true),
+        (
+            KeyVaultCheckpoint {
+                seq: 1,
+                hash: state.head_hash.clone(),
+            },
+            false,
+        ),
+    ] {
+        let mut appended = state.clone();
+        let container = appended
+            .append_record(
+                &header,
+                &vault_key,
+                &make_checkpoint_record("rec-3", &checkpoint),
+                3,
+            )
+            .expect("append checkpoint");
+        let mut all = containers.clone();
+        all.push(container);
+        let result = KeyVaultState::apply_containers(&header, &vault_key, &all);
+        assert_eq!(result.is_ok(), ok);
+        if let Ok((applied, _)) = result {
+            assert_eq!(applied.last_checkpoint, Some(at_head.clone()));
+        }
+        let report = KeyVaultState::verify_containers(&header, &vault_key, &all).unwrap();
+        assert_eq!(report.first_invalid_seq, (!ok).then_some(3));
+    }
+
+    // A trusted checkpoint anchors verification of the records after it.
+    let genesis = KeyVaultCheckpoint {
+        seq: 0,
+        hash: vec![0u8; 32],
+    };
+    assert_eq!(
+        KeyVaultState::verify_chain_from(&genesis, &containers).unwrap(),
+        at_head
+    );
+    let first = KeyVaultCheckpoint {
+        seq: 1,
+        hash: containers[1].prev_hash.clone(),
+    };
+    assert_eq!(
+        KeyVaultState::verify_chain_from(&first, &containers).unwrap(),
+        at_head
+    );
+    let mut tampered = containers.clone();
+    tampered[1].prev_hash = vec![4u8; 32];
+    assert!(KeyVaultState::verify_chain_from(&first, &tampered).is_err());
+}
+
 #[test]
 fn decode_rejects_invalid_scope_state_prev_hash() {
     let scope_state = ScopeStateV1 {
@@ -110,3 +185,107 @@ fn decode_rejects_invalid_grant_nonce() {
     let decoded = decode_resource_grant_v1(&bytes);
     assert!(decoded.is_err());
 }
+
+#[test]
+fn keyvault_record_redacts_and_wipes_key_material() {
+    let record = make_store_scope_key_record("rec-1", "scope-1", 1, &[9u8; 32]);
+    let debug = format!("{record:?}");
+    assert!(debug.contains("<redacted>"));
+    assert!(!debug.contains("9, 9"));
+
+    let mut payload = record.payload.clone();
+    zeroize_value(&mut payload);
+    let map = as_map(&payload).expect("map");
+    assert_eq!(req_bytes(map, 2).expect("key"), Vec::<u8>::new());
+    assert_eq!(req_text(map, 0).expect("scope"), "scope-1");
+}
+
+#[test]
+fn scope_state_payload_round_trips_by_kind() {
+    let signer = SignerKeys {
+        sig_suite: SigCiphersuiteId::HybridSig1,
+        ed25519_pub: vec![1u8; 32],
+        mldsa_pub: vec![2u8; 1952],
+    };
+    let payloads = [
+        ScopeStatePayload::Genesis {
+            signer: signer.clone(),
+        },
+        ScopeStatePayload::EpochBump {
+            signer: signer.clone(),
+            from_epoch: 4,
+            to_epoch: 5,
+            retirement: EpochRetirement::Keep,
+        },
+        ScopeStatePayload::MembershipChange {
+            signer: signer.clone(),
+            added: vec![UserId("user-2".to_string())],
+            removed: Vec::new(),
+            roles: vec![(DeviceId("device-2".to_string()), ScopeRole::Admin)],
+            member_keys: vec![(UserId("user-2".to_string()), vec![7u8; 8])],
+        },
+    ];
+    for payload in payloads {
+        let decoded = ScopeStatePayload::from_cbor(payload.kind(), &payload.to_cbor()).unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    let genesis_only = cbor_map(vec![
+        (1, cbor_bytes(&[1u8; 32])),
+        (2, cbor_bytes(&[2u8; 1952])),
+    ]);
+    assert!(ScopeStatePayload::from_cbor(1, &genesis_only).is_err());
+    assert!(ScopeStatePayload::from_cbor(99, &genesis_only).is_err());
+}
+
+#[test]
+fn scope_state_payload_errors_name_the_field() {
+    let keys = || vec![(1, cbor_bytes(&[1u8; 32])), (2, cbor_bytes(&[2u8; 1952]))];
+    let error = |kind: u64, entries: Vec<(u64, Value)>| {
+        ScopeStatePayload::from_cbor(kind, &cbor_map(entries))
+            .expect_err("invalid payload")
+            .to_string()
+    };
+
+    let short_key = vec![(1, cbor_bytes(&[1u8; 31])), (2, cbor_bytes(&[2u8; 1952]))];
+    assert!(
+        error(0, short_key).contains("scope_state.payload.ed25519_pub: expected 32 bytes, got 31")
+    );
+    assert!(error(0, vec![(1, cbor_bytes(&[1u8; 32]))])
+        .contains("scope_state.payload.mldsa_pub: missing key 2"));
+    assert!(error(7, keys()).contains("scope_state.kind: unknown kind 7"));
+
+    let mut extra = keys();
+    extra.push((3, cbor_uint(1)));
+    assert!(error(0, extra).contains("unknown key"));
+
+    let mut bump = keys();
+    bump.extend([(3, cbor_uint(1)), (4, cbor_text("2")), (5, cbor_uint(0))]);
+    assert!(error(1, bump).contains("scope_state.payload.to_epoch: expected u64"));
+    let mut bump = keys();
+    bump.extend([(3, cbor_uint(1)), (4, cbor_uint(2)), (5, cbor_uint(9))]);
+    assert!(error(1, bump).contains("scope_state.payload.retirement"));
+
+    let mut membership = keys();
+    membership.extend([
+        (
+            3,
+            cbor_array(vec![cbor_text("user-2"), cbor_text("user-2")]),
+        ),
+        (4, cbor_array(Vec::new())),
+    ]);
+    assert!(error(2, membership).contains("scope_state.payload.added[1]: duplicate user id"));
+    let mut membership = keys();
+    membership.extend([(3, cbor_array(Vec::new())), (4, cbor_array(Vec::new()))]);
+    assert!(error(2, membership).contains("membership change changes nothing"));
+    let mut membership = keys();
+    membership.extend([
+        (3, cbor_array(Vec::new())),
+        (4, cbor_array(Vec::new())),
+        (
+            5,
+            cbor_array(vec![cbor_array(vec![cbor_text("device-2"), cbor_uint(7)])]),
+        ),
+    ]);
+    assert!(error(2, membership).contains("scope_state.payload.roles[0]: unknown scope role"));
+}
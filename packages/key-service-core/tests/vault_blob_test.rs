@@ -0,0 +1,68 @@
+use mo_key_service_core::key_service::{KeyService, KeyServiceError};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::UserId;
+
+#[test]
+fn vault_blobs_round_trip_and_survive_unlock() {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(23),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+
+    assert!(core
+        .get_vault_blob(&session_id, "settings")
+        .expect("get missing blob")
+        .is_none());
+    core.put_vault_blob(&session_id, "settings", b"{\"theme\":\"dark\"}")
+        .expect("put blob");
+    core.put_vault_blob(&session_id, "api-token", b"secret")
+        .expect("put second blob");
+    core.put_vault_blob(&session_id, "settings", b"{\"theme\":\"light\"}")
+        .expect("replace blob");
+    assert_eq!(
+        core.get_vault_blob(&session_id, "settings")
+            .unwrap()
+            .unwrap()
+            .as_slice(),
+        b"{\"theme\":\"light\"}"
+    );
+
+    for (label, len) in [("", 1), (&"l".repeat(129)[..], 1), ("big", 64 * 1024 + 1)] {
+        assert!(matches!(
+            core.put_vault_blob(&session_id, label, &vec![0u8; len]),
+            Err(KeyServiceError::InvalidFormat(_))
+        ));
+    }
+
+    // Blobs are keyvault records, so a fresh unlock rebuilds them from storage.
+    core.lock(&session_id).expect("lock");
+    let session_id = core
+        .unlock_passphrase_read_only(b"pass")
+        .expect("unlock read-only")
+        .session_id;
+    assert_eq!(
+        core.get_vault_blob(&session_id, "settings")
+            .unwrap()
+            .unwrap()
+            .as_slice(),
+        b"{\"theme\":\"light\"}"
+    );
+    assert_eq!(
+        core.get_vault_blob(&session_id, "api-token")
+            .unwrap()
+            .unwrap()
+            .as_slice(),
+        b"secret"
+    );
+    assert!(matches!(
+        core.put_vault_blob(&session_id, "settings", b"{}"),
+        Err(KeyServiceError::ReadOnlySession)
+    ));
+}
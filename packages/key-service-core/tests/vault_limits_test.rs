@@ -0,0 +1,117 @@
+use mo_key_service_core::key_service::{
+    KeyService, KeyServiceConfig, KeyServiceError, KeyServicePolicy,
+};
+use mo_key_service_core::testkit::{
+    fast_kdf, test_policy, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{ScopeEpoch, ScopeId, SessionId, UserId};
+
+type Core = KeyService<MemoryStorage, VirtualClock, SeededEntropy>;
+
+fn stepped_up(seed: u64, policy: KeyServicePolicy) -> (Core, SessionId) {
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        VirtualClock::new(1_000),
+        SeededEntropy::new(seed),
+        KeyServiceConfig::builder()
+            .policy(policy)
+            .build()
+            .expect("config"),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    core.step_up(&session_id, b"pass").expect("step up");
+    (core, session_id)
+}
+
+fn persist_scope_key(
+    core: &mut Core,
+    session_id: &SessionId,
+    epoch: u64,
+) -> Result<(), KeyServiceError> {
+    core.persist_scope_key(
+        session_id,
+        &ScopeId("scope-1".to_string()),
+        ScopeEpoch(epoch),
+        &[epoch as u8; 32],
+    )
+}
+
+#[test]
+fn appends_stop_at_the_record_limits() {
+    let policy = test_policy()
+        .max_total_records(2)
+        .max_record_bytes(1024)
+        .build()
+        .expect("policy");
+    let (mut core, session_id) = stepped_up(67, policy);
+
+    let err = core
+        .put_vault_blob(&session_id, "big", &[0u8; 2048])
+        .expect_err("record too large");
+    assert!(matches!(
+        err,
+        KeyServiceError::VaultLimitExceeded {
+            limit: "max_record_bytes"
+        }
+    ));
+    assert_eq!(err.code(), "VaultLimitExceeded");
+
+    for epoch in 1..=2 {
+        persist_scope_key(&mut core, &session_id, epoch).expect("persist scope key");
+    }
+    assert!(matches!(
+        persist_scope_key(&mut core, &session_id, 3),
+        Err(KeyServiceError::VaultLimitExceeded {
+            limit: "max_total_records"
+        })
+    ));
+    assert_eq!(
+        core.list_records_metadata(&session_id)
+            .expect("metadata")
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn imports_are_held_to_the_target_limits() {
+    let (mut source, source_session) = stepped_up(68, test_policy().build().expect("policy"));
+    for epoch in 1..=3 {
+        persist_scope_key(&mut source, &source_session, epoch).expect("persist scope key");
+    }
+    source
+        .put_vault_blob(&source_session, "big", &[0u8; 2048])
+        .expect("put blob");
+    let blob = source.export_keyvault(&source_session).expect("export");
+
+    for (policy, limit) in [
+        (test_policy().max_total_records(3), "max_total_records"),
+        (test_policy().max_record_bytes(1024), "max_record_bytes"),
+    ] {
+        let (mut target, target_session) = stepped_up(69, policy.build().expect("policy"));
+        let err = target
+            .import_keyvault(&target_session, &blob)
+            .expect_err("import over limit");
+        assert!(matches!(err, KeyServiceError::VaultLimitExceeded { limit: l } if l == limit));
+    }
+
+    let (mut target, target_session) = stepped_up(70, test_policy().build().expect("policy"));
+    target
+        .import_keyvault(&target_session, &blob)
+        .expect("import within limits");
+}
+
+#[test]
+fn record_limits_must_be_non_zero() {
+    for builder in [
+        test_policy().max_record_bytes(0),
+        test_policy().max_total_records(0),
+    ] {
+        assert!(matches!(
+            builder.build(),
+            Err(KeyServiceError::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,67 @@
+use mo_key_service_core::key_service::KeyService;
+use mo_key_service_core::testkit::{
+    fast_kdf, test_config, MemoryStorage, SeededEntropy, VirtualClock,
+};
+use mo_key_service_core::types::{
+    DeviceId, ResourceId, ResourceKeyId, ScopeEpoch, ScopeId, UserId,
+};
+use std::collections::BTreeMap;
+
+#[test]
+fn vault_stats_track_growth() {
+    let clock = VirtualClock::new(1_000);
+    let mut core = KeyService::new(
+        MemoryStorage::new(),
+        clock.clone(),
+        SeededEntropy::new(53),
+        test_config(),
+    );
+    core.create_new_vault(UserId("user-1".to_string()), b"pass", fast_kdf())
+        .expect("create vault");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+
+    let empty = core.vault_stats(&session_id).expect("stats");
+    assert!(empty.records_by_kind.is_empty());
+    assert_eq!(empty.chain_length, 0);
+    assert_eq!(empty.encrypted_bytes, 0);
+    assert_eq!(empty.last_append_ms, None);
+
+    core.init_identity(&session_id, &DeviceId("device-1".to_string()))
+        .expect("init identity");
+    clock.set(5_000);
+    for (scope, epoch) in [("scope-1", 1), ("scope-1", 2), ("scope-2", 1)] {
+        core.persist_scope_key(
+            &session_id,
+            &ScopeId(scope.to_string()),
+            ScopeEpoch(epoch),
+            &[epoch as u8; 32],
+        )
+        .expect("persist scope key");
+    }
+    for key_id in ["rk-1", "rk-2"] {
+        core.persist_resource_key(
+            &session_id,
+            &ResourceId("res-1".to_string()),
+            &ResourceKeyId(key_id.to_string()),
+            &[4u8; 32],
+        )
+        .expect("persist resource key");
+    }
+
+    let stats = core.vault_stats(&session_id).expect("stats");
+    assert_eq!(
+        stats.records_by_kind,
+        BTreeMap::from([(1, 1), (2, 1), (3, 3), (4, 2)])
+    );
+    assert_eq!(stats.chain_length, 7);
+    assert_eq!(stats.scope_count, 2);
+    assert_eq!(stats.scope_epoch_count, 3);
+    assert_eq!(stats.resource_count, 1);
+    assert_eq!(stats.last_append_ms, Some(5_000));
+    assert!(stats.encrypted_bytes > empty.encrypted_bytes);
+
+    // Stats are rebuilt from the stored records on unlock.
+    core.lock(&session_id).expect("lock");
+    let session_id = core.unlock_passphrase(b"pass").expect("unlock").session_id;
+    assert_eq!(core.vault_stats(&session_id).expect("stats"), stats);
+}
@@ -18,7 +18,7 @@ export type KemCiphersuiteId = 'hybrid-kem-1';
 export type SigCiphersuiteId = 'hybrid-sig-1';
 
 export type SessionKind = 'normal' | 'stepUp';
-export type SessionAssurance = 'passphrase' | 'userPresence';
+export type SessionAssurance = 'passphrase' | 'userPresence' | 'deviceShares' | 'multiFactor' | 'hardwareBacked';
 export type EmptyObject = Readonly<Record<string, never>>;
 
 export type UnlockRequest =
@@ -111,8 +111,16 @@ export type IngestScopeStateRequest = Readonly<{
 export type IngestScopeStateResponse = Readonly<{
   scopeId: ScopeId;
   scopeStateRef: string;
+  warnings: ReadonlyArray<IngestWarning>;
 }>;
 
+/** Soft issue reported by an ingest that still succeeded. */
+export type IngestWarning = Readonly<
+  | { code: 'FingerprintPinned'; message: string; deviceId: DeviceId }
+  | { code: 'SignerPreviouslyUnseen'; message: string; deviceId: DeviceId }
+  | { code: 'OlderEpoch'; message: string; scopeEpoch: ScopeEpoch; latestEpoch: ScopeEpoch }
+>;
+
 export type IngestKeyEnvelopeRequest = Readonly<{
   sessionId: SessionId;
   keyEnvelopeCbor: Uint8Array;
@@ -121,6 +129,9 @@ export type IngestKeyEnvelopeRequest = Readonly<{
 export type IngestKeyEnvelopeResponse = Readonly<{
   scopeId: ScopeId;
   scopeEpoch: ScopeEpoch;
+  /** False when the envelope id was already ingested for the scope. */
+  isNew: boolean;
+  warnings: ReadonlyArray<IngestWarning>;
 }>;
 
 export type OpenResourceRequest = Readonly<{
@@ -137,6 +148,13 @@ export type CloseHandleRequest = Readonly<{
 export type ChangePassphraseRequest = Readonly<{
   sessionId: SessionId;
   newPassphraseUtf8: Uint8Array;
+  /** PRF output that opens the enrolled user-presence wrap, to re-seal it instead of removing it. */
+  userPresenceSecret?: Uint8Array;
+}>;
+
+export type ChangePassphraseResponse = Readonly<{
+  /** True when an enrolled user-presence unlock was removed by the change. */
+  userPresenceDisabled: boolean;
 }>;
 
 export type StoreAppMasterKeyRequest = Readonly<{
@@ -232,7 +250,7 @@ export type KeyServiceResponse =
   | Readonly<{ type: 'lock'; payload: EmptyObject }>
   | Readonly<{ type: 'exportKeyVault'; payload: Readonly<{ blob: Uint8Array }> }>
   | Readonly<{ type: 'importKeyVault'; payload: EmptyObject }>
-  | Readonly<{ type: 'changePassphrase'; payload: EmptyObject }>
+  | Readonly<{ type: 'changePassphrase'; payload: ChangePassphraseResponse }>
   | Readonly<{ type: 'storeAppMasterKey'; payload: EmptyObject }>
   | Readonly<{ type: 'getAppMasterKey'; payload: GetAppMasterKeyResponse }>
   | Readonly<{ type: 'enableUserPresenceUnlock'; payload: EmptyObject }>
@@ -1,22 +1,43 @@
 #![forbid(unsafe_code)]
 
 use js_sys::{Array, BigInt, Object, Reflect, Uint8Array};
-use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter, StorageAdapter};
+use mo_key_service_core::aad;
+use mo_key_service_core::adapters::{ClockAdapter, EntropyAdapter, SessionEvent, StorageAdapter};
+#[cfg(target_arch = "wasm32")]
+use mo_key_service_core::adapters::{SessionEventsAdapter, TimerAdapter};
+use mo_key_service_core::audit::{AuditEntryV1, AuditVerifyReport};
+#[cfg(target_arch = "wasm32")]
+use mo_key_service_core::cancel::CancellationToken;
 use mo_key_service_core::crypto::KdfParams;
+use mo_key_service_core::diagnostics::DiagnosticsReport;
+use mo_key_service_core::error::ErrorDetail;
 use mo_key_service_core::key_service::{
-    DecryptResponse, EncryptResponse, GetUserPresenceUnlockInfoResponse, IngestKeyEnvelopeResponse,
-    IngestScopeStateResponse, KeyService, KeyServiceConfig, KeyServiceError, RenewSessionResponse,
-    SignResponse, StepUpResponse, UnlockResponse,
+    CapabilityRequest, ChangePassphraseResponse, Contact, DecryptResponse, DevicePublicKey,
+    DueRotation, EncryptInitResponse, EncryptResponse, GetUserPresenceUnlockInfoResponse,
+    HealthCheckResponse, IngestDelegationResponse, IngestKeyEnvelopeResponse,
+    IngestScopeStateResponse, IngestWarning, KeyService, KeyServiceConfig, KeyServiceError,
+    KeyServicePolicy, NonceMode, RenewSessionResponse, SalvageUnlockResponse, ScopeSummary,
+    SessionSummary, SignResponse, SignedFormat, StepUpResponse, UnlockResponse,
+    UserPublicKeyResponse, VaultInfoResponse, VerifyKeyVaultResponse,
 };
+use mo_key_service_core::keyvault::{
+    KeyVaultCheckpoint, KeyVaultRecordMetadata, KeyVaultSalvageReport, KeyVaultStats,
+};
+use mo_key_service_core::opaque::{OpaqueKsf, OpaqueLogin, OpaqueRegistration};
+use mo_key_service_core::strength::{estimate_passphrase_strength, PassphraseStrength};
 use mo_key_service_core::types::{
-    DeviceId, KeyHandle, ScopeEpoch, ScopeId, SessionAssurance, SessionId, SessionKind,
-    SigCiphersuiteId, UserId,
+    AeadId, CapabilityOps, DeviceId, IdCharset, KemCiphersuiteId, KeyHandle, KeyWrapAlg,
+    ScopeEpoch, ScopeId, ScopeRotationPolicy, SessionAssurance, SessionId, SessionKind,
+    SigCiphersuiteId, SnapshotCompression, StreamId, UserId,
 };
-use std::cell::RefCell;
+use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "tabs")]
+mod tabs;
+
 #[derive(Clone, Debug)]
 struct StorageEntry {
     namespace: String,
@@ -130,6 +151,69 @@ impl ClockAdapter for WasmClock {
     }
 }
 
+/// `setTimeout`-backed [`TimerAdapter`]; `callback` is expected to call `onTimer`. Only built for
+/// wasm32, where JS values need not be `Send`.
+#[cfg(target_arch = "wasm32")]
+struct WasmTimer {
+    callback: js_sys::Function,
+    timeout_id: RefCell<Option<JsValue>>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WasmTimer {
+    fn global_fn(name: &str) -> Option<(JsValue, js_sys::Function)> {
+        let global: JsValue = js_sys::global().into();
+        let function = Reflect::get(&global, &JsValue::from_str(name))
+            .ok()?
+            .dyn_into::<js_sys::Function>()
+            .ok()?;
+        Some((global, function))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl TimerAdapter for WasmTimer {
+    fn schedule(&self, delay_ms: u64) {
+        self.cancel();
+        // Longer delays overflow `setTimeout`; an early wake-up just reschedules.
+        let delay = delay_ms.min(i32::MAX as u64) as f64;
+        if let Some((global, set_timeout)) = Self::global_fn("setTimeout") {
+            if let Ok(id) = set_timeout.call2(&global, &self.callback, &JsValue::from_f64(delay)) {
+                *self.timeout_id.borrow_mut() = Some(id);
+            }
+        }
+    }
+
+    fn cancel(&self) {
+        if let Some(id) = self.timeout_id.borrow_mut().take() {
+            if let Some((global, clear_timeout)) = Self::global_fn("clearTimeout") {
+                let _ = clear_timeout.call1(&global, &id);
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+struct WasmSessionEvents {
+    callback: js_sys::Function,
+    vault: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl SessionEventsAdapter for WasmSessionEvents {
+    fn on_session_event(&self, event: &SessionEvent) {
+        if let Some(value) = build_session_event(event) {
+            Reflect::set(
+                &value,
+                &JsValue::from_str("vault"),
+                &JsValue::from_str(&self.vault),
+            )
+            .expect("vault");
+            let _ = self.callback.call1(&JsValue::NULL, &value);
+        }
+    }
+}
+
 struct WasmEntropy;
 
 impl EntropyAdapter for WasmEntropy {
@@ -140,59 +224,272 @@ impl EntropyAdapter for WasmEntropy {
     }
 }
 
+type WasmKeyService = KeyService<WasmStorage, WasmClock, WasmEntropy>;
+
+const DEFAULT_VAULT: &str = "default";
+
+struct VaultInstance {
+    storage: WasmStorage,
+    service: WasmKeyService,
+}
+
+impl VaultInstance {
+    fn new(config: KeyServiceConfig) -> Self {
+        let storage = WasmStorage::new();
+        let service = KeyService::new(storage.clone(), WasmClock, WasmEntropy, config);
+        Self { storage, service }
+    }
+}
+
+/// Client state between `opaqueRegistrationStart` and `createVaultOpaque`.
+#[wasm_bindgen(js_name = "OpaqueRegistration")]
+pub struct OpaqueRegistrationWasm {
+    state: RefCell<Option<OpaqueRegistration>>,
+    request: Vec<u8>,
+}
+
+#[wasm_bindgen(js_class = "OpaqueRegistration")]
+impl OpaqueRegistrationWasm {
+    #[wasm_bindgen(getter)]
+    pub fn request(&self) -> Vec<u8> {
+        self.request.clone()
+    }
+}
+
+/// Client state between `opaqueLoginStart` and `unlockOpaque`.
+#[wasm_bindgen(js_name = "OpaqueLogin")]
+pub struct OpaqueLoginWasm {
+    state: RefCell<Option<OpaqueLogin>>,
+    ke1: Vec<u8>,
+}
+
+#[wasm_bindgen(js_class = "OpaqueLogin")]
+impl OpaqueLoginWasm {
+    #[wasm_bindgen(getter)]
+    pub fn ke1(&self) -> Vec<u8> {
+        self.ke1.clone()
+    }
+}
+
+/// One or more independent vaults, each with its own storage and sessions, keyed by a vault
+/// namespace. Calls go to the active vault (`switchVault`).
 #[wasm_bindgen]
 pub struct KeyServiceWasm {
-    storage: WasmStorage,
-    service: RefCell<KeyService<WasmStorage, WasmClock, WasmEntropy>>,
+    vaults: Rc<RefCell<HashMap<String, VaultInstance>>>,
+    active: RefCell<String>,
+    /// Applied to every vault, including ones created later by `switchVault`.
+    config: KeyServiceConfig,
+    #[cfg(target_arch = "wasm32")]
+    auto_lock_callback: RefCell<Option<js_sys::Function>>,
+    #[cfg(target_arch = "wasm32")]
+    session_events_callback: RefCell<Option<js_sys::Function>>,
+    /// Keeps the `onSessionEvent` timer callback alive while JS may still call it.
+    #[cfg(target_arch = "wasm32")]
+    timer_closure: RefCell<Option<Closure<dyn FnMut()>>>,
+}
+
+impl KeyServiceWasm {
+    fn with_vault(namespace: &str, config: KeyServiceConfig) -> Self {
+        let mut vaults = HashMap::new();
+        vaults.insert(namespace.to_string(), VaultInstance::new(config.clone()));
+        Self {
+            vaults: Rc::new(RefCell::new(vaults)),
+            active: RefCell::new(namespace.to_string()),
+            config,
+            #[cfg(target_arch = "wasm32")]
+            auto_lock_callback: RefCell::new(None),
+            #[cfg(target_arch = "wasm32")]
+            session_events_callback: RefCell::new(None),
+            #[cfg(target_arch = "wasm32")]
+            timer_closure: RefCell::new(None),
+        }
+    }
+
+    fn service(&self) -> RefMut<'_, WasmKeyService> {
+        let active = self.active.borrow();
+        RefMut::map(self.vaults.borrow_mut(), |vaults| {
+            &mut vaults
+                .get_mut(active.as_str())
+                .expect("active vault exists")
+                .service
+        })
+    }
+
+    fn storage(&self) -> WasmStorage {
+        self.vaults.borrow()[self.active.borrow().as_str()]
+            .storage
+            .clone()
+    }
+
+    /// Installs the host callbacks registered so far on a newly created vault.
+    #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+    fn attach_callbacks(&self, namespace: &str, instance: &mut VaultInstance) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            if let Some(callback) = self.auto_lock_callback.borrow().clone() {
+                instance.service.set_timer_adapter(WasmTimer {
+                    callback,
+                    timeout_id: RefCell::new(None),
+                });
+            }
+            if let Some(callback) = self.session_events_callback.borrow().clone() {
+                instance
+                    .service
+                    .set_session_events_adapter(WasmSessionEvents {
+                        callback,
+                        vault: namespace.to_string(),
+                    });
+            }
+        }
+    }
+}
+
+fn check_vault_namespace(namespace: &str) -> Result<(), JsValue> {
+    if namespace.is_empty() {
+        return Err(JsValue::from_str("vault namespace must not be empty"));
+    }
+    Ok(())
 }
 
 #[wasm_bindgen]
 impl KeyServiceWasm {
+    /// An instance whose only vault is `"default"`. `options` may set `maxCborBytes`,
+    /// `maxCborDepth`, `maxCborItems`, `keyWrapAlg` (`"aes-gcm"` or `"aes-kwp"`),
+    /// `acceptUnboundSignatures`, `maxIdBytes`, `idCharset` (`"noControl"` or `"urlSafe"`),
+    /// `maxClockRewindMs`, and `maxClockJumpMs`, and the OPAQUE deployment parameters
+    /// `opaqueContext`, `opaqueServerIdentity`, `opaqueClientIdentity` (byte arrays), and
+    /// `opaqueKsf` (`{ memoryKib, iterations, parallelism }` for Argon2id); omitted fields keep
+    /// the core defaults.
     #[wasm_bindgen(constructor)]
-    pub fn new() -> Self {
-        let storage = WasmStorage::new();
-        let service = KeyService::new(
-            storage.clone(),
-            WasmClock,
-            WasmEntropy,
-            KeyServiceConfig::default(),
-        );
-        Self {
-            storage,
-            service: RefCell::new(service),
+    pub fn new(options: JsValue) -> Result<KeyServiceWasm, JsValue> {
+        Ok(Self::with_vault(DEFAULT_VAULT, parse_options(options)?))
+    }
+
+    /// An instance whose first (active) vault is `namespace`, with the constructor's `options`.
+    #[wasm_bindgen(js_name = "forVault")]
+    pub fn for_vault(namespace: String, options: JsValue) -> Result<KeyServiceWasm, JsValue> {
+        check_vault_namespace(&namespace)?;
+        Ok(Self::with_vault(&namespace, parse_options(options)?))
+    }
+
+    /// Makes `namespace` the active vault, creating an empty one with fresh storage on first
+    /// use. Other vaults keep their storage and sessions.
+    #[wasm_bindgen(js_name = "switchVault")]
+    pub fn switch_vault(&self, namespace: String) -> Result<(), JsValue> {
+        check_vault_namespace(&namespace)?;
+        if !self.vaults.borrow().contains_key(&namespace) {
+            let mut instance = VaultInstance::new(self.config.clone());
+            self.attach_callbacks(&namespace, &mut instance);
+            self.vaults.borrow_mut().insert(namespace.clone(), instance);
+        }
+        *self.active.borrow_mut() = namespace;
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = "activeVault")]
+    pub fn active_vault(&self) -> String {
+        self.active.borrow().clone()
+    }
+
+    /// Vault namespaces, sorted.
+    #[wasm_bindgen(js_name = "listVaults")]
+    pub fn list_vaults(&self) -> Vec<String> {
+        let mut namespaces = self.vaults.borrow().keys().cloned().collect::<Vec<_>>();
+        namespaces.sort();
+        namespaces
+    }
+
+    /// Drops a vault other than the active one, with its storage and sessions.
+    #[wasm_bindgen(js_name = "removeVault")]
+    pub fn remove_vault(&self, namespace: String) -> Result<bool, JsValue> {
+        if *self.active.borrow() == namespace {
+            return Err(JsValue::from_str("cannot remove the active vault"));
         }
+        Ok(self.vaults.borrow_mut().remove(&namespace).is_some())
+    }
+
+    #[wasm_bindgen(js_name = "estimatePassphraseStrength")]
+    pub fn estimate_passphrase_strength(passphrase: Vec<u8>) -> Result<JsValue, JsValue> {
+        let strength = estimate_passphrase_strength(&passphrase)
+            .map_err(|err| to_js_error(KeyServiceError::from(err)))?;
+        Ok(build_passphrase_strength(&strength))
+    }
+
+    /// Every error `code` this build can throw.
+    #[wasm_bindgen(js_name = "errorCodes")]
+    pub fn error_codes() -> Array {
+        KeyServiceError::CODES
+            .iter()
+            .map(|code| JsValue::from_str(code))
+            .collect()
     }
 
     #[wasm_bindgen(js_name = "loadStorage")]
     pub fn load_storage(&self, entries: JsValue) -> Result<(), JsValue> {
         let parsed = parse_storage_entries(entries)?;
-        self.storage.load_entries(parsed);
+        self.storage().load_entries(parsed);
+        Ok(())
+    }
+
+    /// Applies writes another tab or a sync process made to the shared storage and marks the
+    /// loaded vault state stale, so the next session call reloads it.
+    #[wasm_bindgen(js_name = "applyExternalWrites")]
+    pub fn apply_external_writes(&self, entries: JsValue) -> Result<(), JsValue> {
+        let parsed = parse_storage_entries(entries)?;
+        self.storage().load_entries(parsed);
+        self.service().invalidate();
         Ok(())
     }
 
+    /// Drops expired sessions in every vault now; hosts can call this from a timer.
+    #[wasm_bindgen(js_name = "sweepExpiredSessions")]
+    pub fn sweep_expired_sessions(&self) -> u32 {
+        self.vaults
+            .borrow_mut()
+            .values_mut()
+            .map(|vault| vault.service.sweep_expired_sessions() as u32)
+            .sum()
+    }
+
+    /// Locks expired sessions and sends expiry warnings in every vault; the `setAutoLockTimer`
+    /// callback calls it.
+    #[wasm_bindgen(js_name = "onTimer")]
+    pub fn on_timer(&self) {
+        for vault in self.vaults.borrow_mut().values_mut() {
+            vault.service.on_timer();
+        }
+    }
+
+    #[wasm_bindgen(js_name = "invalidate")]
+    pub fn invalidate(&self) {
+        self.service().invalidate();
+    }
+
+    #[wasm_bindgen(js_name = "reloadFromStorage")]
+    pub fn reload_from_storage(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .reload_from_storage(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        let obj = Object::new();
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("changed"),
+            &JsValue::from_bool(response.changed),
+        )
+        .expect("changed");
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("headSeq"),
+            &JsValue::from_f64(response.head_seq as f64),
+        )
+        .expect("headSeq");
+        Ok(obj.into())
+    }
+
     #[wasm_bindgen(js_name = "drainStorageWrites")]
     pub fn drain_storage_writes(&self) -> JsValue {
-        let entries = self.storage.drain_pending();
-        let array = Array::new();
-        for entry in entries {
-            let obj = Object::new();
-            let value = Uint8Array::from(entry.value.as_slice());
-            Reflect::set(
-                &obj,
-                &JsValue::from_str("namespace"),
-                &JsValue::from_str(&entry.namespace),
-            )
-            .expect("set namespace");
-            Reflect::set(
-                &obj,
-                &JsValue::from_str("key"),
-                &JsValue::from_str(&entry.key),
-            )
-            .expect("set key");
-            Reflect::set(&obj, &JsValue::from_str("value"), &value.into()).expect("set value");
-            array.push(&obj);
-        }
-        array.into()
+        build_storage_writes(self.storage().drain_pending()).into()
     }
 
     #[wasm_bindgen(js_name = "createVault")]
@@ -203,33 +500,206 @@ impl KeyServiceWasm {
         kdf_params: JsValue,
     ) -> Result<(), JsValue> {
         let params = parse_kdf_params(kdf_params)?;
-        self.service
-            .borrow_mut()
+        self.service()
             .create_new_vault(UserId(user_id), &passphrase_utf8, params)
             .map_err(to_js_error)?;
         Ok(())
     }
 
+    /// `onProgress`, if given, is called as `onProgress(donePasses, totalPasses)` after each
+    /// KDF pass. It runs synchronously inside the call, so it must not call back into the
+    /// service; a worker host would post the numbers to the UI thread.
     #[wasm_bindgen(js_name = "unlockPassphrase")]
-    pub fn unlock_passphrase(&self, passphrase_utf8: Vec<u8>) -> Result<JsValue, JsValue> {
-        let response = self
-            .service
+    pub fn unlock_passphrase(
+        &self,
+        passphrase_utf8: Vec<u8>,
+        on_progress: Option<js_sys::Function>,
+    ) -> Result<JsValue, JsValue> {
+        let mut service = self.service();
+        let response = match on_progress {
+            Some(on_progress) => {
+                service.unlock_passphrase_with_progress(&passphrase_utf8, &mut |done, total| {
+                    let _ = on_progress.call2(
+                        &JsValue::NULL,
+                        &JsValue::from_f64(done as f64),
+                        &JsValue::from_f64(total as f64),
+                    );
+                })
+            }
+            None => service.unlock_passphrase(&passphrase_utf8),
+        }
+        .map_err(to_js_error)?;
+        Ok(build_unlock_response(&response))
+    }
+
+    /// Starts OPAQUE registration; send `registration.request` to the server and pass its
+    /// response to `createVaultOpaque`.
+    #[wasm_bindgen(js_name = "opaqueRegistrationStart")]
+    pub fn opaque_registration_start(
+        &self,
+        password_utf8: Vec<u8>,
+    ) -> Result<OpaqueRegistrationWasm, JsValue> {
+        let (state, request) = self
+            .service()
+            .start_opaque_registration(&password_utf8)
+            .map_err(to_js_error)?;
+        Ok(OpaqueRegistrationWasm {
+            state: RefCell::new(Some(state)),
+            request,
+        })
+    }
+
+    /// Creates a vault keyed by the OPAQUE export key; returns the record to upload to the
+    /// server. `registration` can only be used once.
+    #[wasm_bindgen(js_name = "createVaultOpaque")]
+    pub fn create_vault_opaque(
+        &self,
+        user_id: String,
+        registration: &OpaqueRegistrationWasm,
+        response: Vec<u8>,
+        kdf_params: JsValue,
+    ) -> Result<Vec<u8>, JsValue> {
+        let params = parse_kdf_params(kdf_params)?;
+        let state = registration
+            .state
             .borrow_mut()
-            .unlock_passphrase(&passphrase_utf8)
+            .take()
+            .ok_or_else(|| JsValue::from_str("opaque registration already used"))?;
+        self.service()
+            .create_new_vault_opaque(UserId(user_id), state, &response, params)
+            .map_err(to_js_error)
+    }
+
+    /// Starts an OPAQUE login; send `login.ke1` to the server and pass its KE2 to
+    /// `unlockOpaque`.
+    #[wasm_bindgen(js_name = "opaqueLoginStart")]
+    pub fn opaque_login_start(&self, password_utf8: Vec<u8>) -> Result<OpaqueLoginWasm, JsValue> {
+        let (state, ke1) = self
+            .service()
+            .start_opaque_login(&password_utf8)
             .map_err(to_js_error)?;
-        Ok(build_unlock_response(&response))
+        Ok(OpaqueLoginWasm {
+            state: RefCell::new(Some(state)),
+            ke1,
+        })
+    }
+
+    /// Unlocks with the OPAQUE export key. The result is an unlock response plus `ke3`, which
+    /// the host sends to the server to finish the login.
+    #[wasm_bindgen(js_name = "unlockOpaque")]
+    pub fn unlock_opaque(&self, login: &OpaqueLoginWasm, ke2: Vec<u8>) -> Result<JsValue, JsValue> {
+        let state = login
+            .state
+            .borrow_mut()
+            .take()
+            .ok_or_else(|| JsValue::from_str("opaque login already used"))?;
+        let response = self
+            .service()
+            .unlock_opaque(state, &ke2)
+            .map_err(to_js_error)?;
+        let obj = build_unlock_response(&response.unlock);
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("ke3"),
+            &Uint8Array::from(response.ke3.as_slice()),
+        )
+        .expect("ke3");
+        Ok(obj)
     }
 
     #[wasm_bindgen(js_name = "unlockUserPresence")]
     pub fn unlock_user_presence(&self, user_presence_secret: Vec<u8>) -> Result<JsValue, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .unlock_user_presence(&user_presence_secret)
             .map_err(to_js_error)?;
         Ok(build_unlock_response(&response))
     }
 
+    /// Unlocks with the passphrase and the user-presence secret together; the session's
+    /// assurance is `multiFactor`.
+    #[wasm_bindgen(js_name = "unlockMultiFactor")]
+    pub fn unlock_multi_factor(
+        &self,
+        passphrase_utf8: Vec<u8>,
+        user_presence_secret: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .unlock_multi_factor(&passphrase_utf8, &user_presence_secret)
+            .map_err(to_js_error)?;
+        Ok(build_unlock_response(&response))
+    }
+
+    /// Like `unlockPassphrase`, but the session cannot append vault records or change the
+    /// header (`ReadOnlySession`).
+    #[wasm_bindgen(js_name = "unlockPassphraseReadOnly")]
+    pub fn unlock_passphrase_read_only(
+        &self,
+        passphrase_utf8: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .unlock_passphrase_read_only(&passphrase_utf8)
+            .map_err(to_js_error)?;
+        Ok(build_unlock_response(&response))
+    }
+
+    /// Checks a passphrase against the vault without issuing a session.
+    #[wasm_bindgen(js_name = "verifyPassphrase")]
+    pub fn verify_passphrase(&self, passphrase_utf8: Vec<u8>) -> Result<bool, JsValue> {
+        self.service()
+            .verify_passphrase(&passphrase_utf8)
+            .map_err(to_js_error)
+    }
+
+    #[wasm_bindgen(js_name = "unlockUserPresenceReadOnly")]
+    pub fn unlock_user_presence_read_only(
+        &self,
+        user_presence_secret: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .unlock_user_presence_read_only(&user_presence_secret)
+            .map_err(to_js_error)?;
+        Ok(build_unlock_response(&response))
+    }
+
+    /// Returns the unlock fields plus `report: { breakSeq, rechainedRecordIds,
+    /// truncatedRecordIds, damaged, sealedHeadSeq }`. The log is truncated at the break; the
+    /// session is read-only until `repairKeyVault` when `breakSeq` is set.
+    #[wasm_bindgen(js_name = "unlockWithSalvage")]
+    pub fn unlock_with_salvage(&self, passphrase_utf8: Vec<u8>) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .unlock_with_salvage(&passphrase_utf8)
+            .map_err(to_js_error)?;
+        Ok(build_salvage_unlock_response(&response))
+    }
+
+    /// Like `unlockWithSalvage`, re-chaining readable records after the break.
+    #[wasm_bindgen(js_name = "unlockWithSalvageRechaining")]
+    pub fn unlock_with_salvage_rechaining(
+        &self,
+        passphrase_utf8: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .unlock_with_salvage_rechaining(&passphrase_utf8)
+            .map_err(to_js_error)?;
+        Ok(build_salvage_unlock_response(&response))
+    }
+
+    /// Requires a step-up session; returns the report that was persisted.
+    #[wasm_bindgen(js_name = "repairKeyVault")]
+    pub fn repair_keyvault(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let report = self
+            .service()
+            .repair_keyvault(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        Ok(build_salvage_report(&report))
+    }
+
     #[wasm_bindgen(js_name = "stepUp")]
     pub fn step_up(
         &self,
@@ -237,18 +707,36 @@ impl KeyServiceWasm {
         passphrase_utf8: Vec<u8>,
     ) -> Result<JsValue, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .step_up(&SessionId(session_id), &passphrase_utf8)
             .map_err(to_js_error)?;
         Ok(build_step_up_response(&response))
     }
 
+    /// Like `stepUp`, re-proving the user-presence secret too; the step-up session's
+    /// assurance is `multiFactor`. A plain `stepUp` always gives `passphrase`.
+    #[wasm_bindgen(js_name = "stepUpMultiFactor")]
+    pub fn step_up_multi_factor(
+        &self,
+        session_id: String,
+        passphrase_utf8: Vec<u8>,
+        user_presence_secret: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .step_up_multi_factor(
+                &SessionId(session_id),
+                &passphrase_utf8,
+                &user_presence_secret,
+            )
+            .map_err(to_js_error)?;
+        Ok(build_step_up_response(&response))
+    }
+
     #[wasm_bindgen(js_name = "renewSession")]
     pub fn renew_session(&self, session_id: String) -> Result<JsValue, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .renew_session(&SessionId(session_id))
             .map_err(to_js_error)?;
         Ok(build_renew_response(&response))
@@ -256,43 +744,180 @@ impl KeyServiceWasm {
 
     #[wasm_bindgen(js_name = "lock")]
     pub fn lock(&self, session_id: String) -> Result<(), JsValue> {
-        self.service
-            .borrow_mut()
+        self.service()
             .lock(&SessionId(session_id))
             .map_err(to_js_error)?;
         Ok(())
     }
 
+    /// Locks every session in every vault, wiping all handles; returns how many were locked.
+    #[wasm_bindgen(js_name = "lockAll")]
+    pub fn lock_all(&self) -> u32 {
+        self.vaults
+            .borrow_mut()
+            .values_mut()
+            .map(|vault| vault.service.lock_all() as u32)
+            .sum()
+    }
+
+    /// Open sessions across all vaults, each tagged with its `vault`.
+    #[wasm_bindgen(js_name = "listSessions")]
+    pub fn list_sessions(&self) -> JsValue {
+        let mut vaults = self.vaults.borrow_mut();
+        let mut namespaces = vaults.keys().cloned().collect::<Vec<_>>();
+        namespaces.sort();
+        let array = Array::new();
+        for namespace in namespaces {
+            let vault = vaults.get_mut(&namespace).expect("vault");
+            for summary in vault.service.list_sessions() {
+                array.push(&build_session_summary(&namespace, &summary));
+            }
+        }
+        array.into()
+    }
+
     #[wasm_bindgen(js_name = "exportKeyVault")]
     pub fn export_keyvault(&self, session_id: String) -> Result<Vec<u8>, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .export_keyvault(&SessionId(session_id))
             .map_err(to_js_error)?;
         Ok(response)
     }
 
+    /// `compression` is `"deflate"` or `"zstd"`; zstd needs the core `zstd` feature.
+    #[wasm_bindgen(js_name = "exportKeyVaultCompressed")]
+    pub fn export_keyvault_compressed(
+        &self,
+        session_id: String,
+        compression: String,
+    ) -> Result<Vec<u8>, JsValue> {
+        let compression = SnapshotCompression::try_from(compression.as_str())
+            .map_err(|err| JsValue::from_str(&err))?;
+        let response = self
+            .service()
+            .export_keyvault_compressed(&SessionId(session_id), compression)
+            .map_err(to_js_error)?;
+        Ok(response)
+    }
+
+    /// `kinds` is a `BigUint64Array` of record kinds to keep.
+    #[wasm_bindgen(js_name = "exportKeyVaultFiltered")]
+    pub fn export_keyvault_filtered(
+        &self,
+        session_id: String,
+        kinds: Vec<u64>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let response = self
+            .service()
+            .export_keyvault_filtered(&SessionId(session_id), &kinds)
+            .map_err(to_js_error)?;
+        Ok(response)
+    }
+
+    /// Streams the `exportKeyVault` bytes as `onChunk(Uint8Array)` calls of `chunkLen` bytes
+    /// (the last may be shorter). `onChunk` runs synchronously inside the call and must not
+    /// call back into the service; if it throws, the export stops with that error.
+    #[wasm_bindgen(js_name = "exportKeyVaultChunked")]
+    pub fn export_keyvault_chunked(
+        &self,
+        session_id: String,
+        chunk_len: usize,
+        on_chunk: js_sys::Function,
+    ) -> Result<(), JsValue> {
+        let mut thrown = None;
+        let result = self.service().export_keyvault_chunked(
+            &SessionId(session_id),
+            chunk_len,
+            &mut |chunk| {
+                on_chunk
+                    .call1(&JsValue::NULL, &Uint8Array::from(chunk).into())
+                    .map(|_| ())
+                    .map_err(|err| {
+                        thrown = Some(err);
+                        KeyServiceError::Cancelled
+                    })
+            },
+        );
+        match (result, thrown) {
+            (Err(_), Some(err)) => Err(err),
+            (result, _) => result.map_err(to_js_error),
+        }
+    }
+
     #[wasm_bindgen(js_name = "importKeyVault")]
     pub fn import_keyvault(&self, session_id: String, blob: Vec<u8>) -> Result<(), JsValue> {
-        self.service
-            .borrow_mut()
+        self.service()
             .import_keyvault(&SessionId(session_id), &blob)
             .map_err(to_js_error)?;
         Ok(())
     }
 
+    /// Starts an incremental import; returns the stream id for `importKeyVaultPush` and
+    /// `importKeyVaultFinish`. Only uncompressed exports can be imported this way.
+    #[wasm_bindgen(js_name = "importKeyVaultInit")]
+    pub fn import_keyvault_init(&self, session_id: String) -> Result<String, JsValue> {
+        let response = self
+            .service()
+            .import_keyvault_init(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        Ok(response.stream_id.0)
+    }
+
+    /// Pieces may split the export anywhere; an error discards the import.
+    #[wasm_bindgen(js_name = "importKeyVaultPush")]
+    pub fn import_keyvault_push(
+        &self,
+        session_id: String,
+        stream_id: String,
+        chunk: Vec<u8>,
+    ) -> Result<(), JsValue> {
+        self.service()
+            .import_keyvault_push(&SessionId(session_id), &StreamId(stream_id), &chunk)
+            .map_err(to_js_error)
+    }
+
+    #[wasm_bindgen(js_name = "importKeyVaultFinish")]
+    pub fn import_keyvault_finish(
+        &self,
+        session_id: String,
+        stream_id: String,
+    ) -> Result<(), JsValue> {
+        self.service()
+            .import_keyvault_finish(&SessionId(session_id), &StreamId(stream_id))
+            .map_err(to_js_error)
+    }
+
     #[wasm_bindgen(js_name = "changePassphrase")]
     pub fn change_passphrase(
         &self,
         session_id: String,
         new_passphrase_utf8: Vec<u8>,
-    ) -> Result<(), JsValue> {
-        self.service
-            .borrow_mut()
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
             .change_passphrase(&SessionId(session_id), &new_passphrase_utf8)
             .map_err(to_js_error)?;
-        Ok(())
+        Ok(build_change_passphrase_response(&response))
+    }
+
+    /// Like `changePassphrase`, re-sealing the user-presence wrap with its PRF secret.
+    #[wasm_bindgen(js_name = "changePassphraseKeepingUserPresence")]
+    pub fn change_passphrase_keeping_user_presence(
+        &self,
+        session_id: String,
+        new_passphrase_utf8: Vec<u8>,
+        user_presence_secret: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .change_passphrase_keeping_user_presence(
+                &SessionId(session_id),
+                &new_passphrase_utf8,
+                &user_presence_secret,
+            )
+            .map_err(to_js_error)?;
+        Ok(build_change_passphrase_response(&response))
     }
 
     #[wasm_bindgen(js_name = "storeAppMasterKey")]
@@ -301,8 +926,7 @@ impl KeyServiceWasm {
         session_id: String,
         master_key: Vec<u8>,
     ) -> Result<(), JsValue> {
-        self.service
-            .borrow_mut()
+        self.service()
             .store_app_master_key(&SessionId(session_id), &master_key)
             .map_err(to_js_error)?;
         Ok(())
@@ -311,24 +935,117 @@ impl KeyServiceWasm {
     #[wasm_bindgen(js_name = "getAppMasterKey")]
     pub fn get_app_master_key(&self, session_id: String) -> Result<JsValue, JsValue> {
         let key = self
-            .service
-            .borrow_mut()
+            .service()
             .get_app_master_key(&SessionId(session_id))
             .map_err(to_js_error)?;
         let bytes = Uint8Array::from(key.as_slice());
         Ok(bytes.into())
     }
 
+    /// Returns `[{ seq, recordId, kind, createdAtMs, originDeviceId }]` in seq order; the last
+    /// two are `null` on records without provenance.
+    #[wasm_bindgen(js_name = "vaultStats")]
+    pub fn vault_stats(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let stats = self
+            .service()
+            .vault_stats(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        Ok(build_vault_stats(&stats))
+    }
+
+    #[wasm_bindgen(js_name = "listRecordsMetadata")]
+    pub fn list_records_metadata(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let records = self
+            .service()
+            .list_records_metadata(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        let array = Array::new();
+        for record in &records {
+            array.push(&build_record_metadata(record));
+        }
+        Ok(array.into())
+    }
+
+    /// Returns `{ seq, hash }` of the checkpoint now at the head of the vault.
+    #[wasm_bindgen(js_name = "writeCheckpoint")]
+    pub fn write_checkpoint(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let checkpoint = self
+            .service()
+            .write_checkpoint(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        Ok(build_checkpoint(&checkpoint))
+    }
+
+    /// Returns `{ seq, hash }`, or `null` when the vault has no checkpoint record.
+    #[wasm_bindgen(js_name = "latestCheckpoint")]
+    pub fn latest_checkpoint(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let checkpoint = self
+            .service()
+            .latest_checkpoint(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        Ok(checkpoint.as_ref().map_or(JsValue::NULL, build_checkpoint))
+    }
+
+    #[wasm_bindgen(js_name = "putVaultBlob")]
+    pub fn put_vault_blob(
+        &self,
+        session_id: String,
+        label: String,
+        bytes: Vec<u8>,
+    ) -> Result<(), JsValue> {
+        self.service()
+            .put_vault_blob(&SessionId(session_id), &label, &bytes)
+            .map_err(to_js_error)?;
+        Ok(())
+    }
+
+    /// Returns the blob's bytes, or `null` when nothing is stored under `label`.
+    #[wasm_bindgen(js_name = "getVaultBlob")]
+    pub fn get_vault_blob(&self, session_id: String, label: String) -> Result<JsValue, JsValue> {
+        let blob = self
+            .service()
+            .get_vault_blob(&SessionId(session_id), &label)
+            .map_err(to_js_error)?;
+        Ok(blob.map_or(JsValue::NULL, |bytes| {
+            Uint8Array::from(bytes.as_slice()).into()
+        }))
+    }
+
     #[wasm_bindgen(js_name = "getUserPresenceUnlockInfo")]
     pub fn get_user_presence_unlock_info(&self) -> Result<JsValue, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .get_user_presence_unlock_info()
             .map_err(to_js_error)?;
         Ok(build_user_presence_info(&response))
     }
 
+    #[wasm_bindgen(js_name = "getVaultInfo")]
+    pub fn get_vault_info(&self) -> Result<JsValue, JsValue> {
+        let response = self.service().get_vault_info().map_err(to_js_error)?;
+        Ok(build_vault_info(&response))
+    }
+
+    #[wasm_bindgen(js_name = "healthCheck")]
+    pub fn health_check(&self) -> JsValue {
+        build_health_check(&self.service().health_check())
+    }
+
+    /// Redacted bug-report bundle as a plain, JSON-serializable object.
+    #[wasm_bindgen(js_name = "exportDiagnostics")]
+    pub fn export_diagnostics(&self) -> JsValue {
+        build_diagnostics(&self.service().export_diagnostics())
+    }
+
+    /// The same bundle as canonical CBOR.
+    #[wasm_bindgen(js_name = "exportDiagnosticsCbor")]
+    pub fn export_diagnostics_cbor(&self) -> Result<Vec<u8>, JsValue> {
+        self.service()
+            .export_diagnostics()
+            .to_cbor()
+            .map_err(|e| to_js_error(KeyServiceError::InvalidCbor(e.to_string())))
+    }
+
     #[wasm_bindgen(js_name = "enableUserPresenceUnlock")]
     pub fn enable_user_presence_unlock(
         &self,
@@ -336,8 +1053,7 @@ impl KeyServiceWasm {
         credential_id: Vec<u8>,
         user_presence_secret: Vec<u8>,
     ) -> Result<(), JsValue> {
-        self.service
-            .borrow_mut()
+        self.service()
             .enable_user_presence_unlock(
                 &SessionId(session_id),
                 credential_id,
@@ -349,8 +1065,7 @@ impl KeyServiceWasm {
 
     #[wasm_bindgen(js_name = "disableUserPresenceUnlock")]
     pub fn disable_user_presence_unlock(&self, session_id: String) -> Result<(), JsValue> {
-        self.service
-            .borrow_mut()
+        self.service()
             .disable_user_presence_unlock(&SessionId(session_id))
             .map_err(to_js_error)?;
         Ok(())
@@ -377,13 +1092,26 @@ impl KeyServiceWasm {
             )
         };
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .ingest_scope_state(&SessionId(session_id), &scope_state_cbor, fingerprint)
             .map_err(to_js_error)?;
         Ok(build_ingest_scope_state_response(&response))
     }
 
+    /// Returns `{ scopeId, delegateDeviceId, expiresAtMs }`.
+    #[wasm_bindgen(js_name = "ingestDelegation")]
+    pub fn ingest_delegation(
+        &self,
+        session_id: String,
+        delegation_cbor: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .ingest_delegation(&SessionId(session_id), &delegation_cbor)
+            .map_err(to_js_error)?;
+        Ok(build_ingest_delegation_response(&response))
+    }
+
     #[wasm_bindgen(js_name = "ingestKeyEnvelope")]
     pub fn ingest_key_envelope(
         &self,
@@ -391,13 +1119,61 @@ impl KeyServiceWasm {
         key_envelope_cbor: Vec<u8>,
     ) -> Result<JsValue, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .ingest_key_envelope(&SessionId(session_id), &key_envelope_cbor)
             .map_err(to_js_error)?;
         Ok(build_ingest_key_envelope_response(&response))
     }
 
+    #[wasm_bindgen(js_name = "listScopes")]
+    pub fn list_scopes(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .list_scopes(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        let array = Array::new();
+        for scope in &response.scopes {
+            array.push(&build_scope_summary(scope));
+        }
+        Ok(array.into())
+    }
+
+    /// Sets the scope's rotation policy; leave both limits undefined to clear it.
+    #[wasm_bindgen(js_name = "setRotationPolicy")]
+    pub fn set_rotation_policy(
+        &self,
+        session_id: String,
+        scope_id: String,
+        max_key_age_ms: Option<u64>,
+        max_operations: Option<u64>,
+        grace_ms: u64,
+    ) -> Result<(), JsValue> {
+        let policy =
+            (max_key_age_ms.is_some() || max_operations.is_some()).then_some(ScopeRotationPolicy {
+                max_key_age_ms,
+                max_operations,
+                grace_ms,
+            });
+        self.service()
+            .set_rotation_policy(&SessionId(session_id), &ScopeId(scope_id), policy)
+            .map_err(to_js_error)
+    }
+
+    /// Returns `[{ scopeId, scopeEpoch, reason, dueAtMs, hardDeadlineMs, operations }]`,
+    /// soonest hard deadline first; `reason` is `"key-age"` or `"operations"`.
+    #[wasm_bindgen(js_name = "dueRotations")]
+    pub fn due_rotations(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .due_rotations(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        let array = Array::new();
+        for due in &response.rotations {
+            array.push(&build_due_rotation(due));
+        }
+        Ok(array.into())
+    }
+
     #[wasm_bindgen(js_name = "openScope")]
     pub fn open_scope(
         &self,
@@ -406,8 +1182,7 @@ impl KeyServiceWasm {
         scope_epoch: u64,
     ) -> Result<String, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .open_scope(
                 &SessionId(session_id),
                 ScopeId(scope_id),
@@ -425,8 +1200,7 @@ impl KeyServiceWasm {
         grant_cbor: Vec<u8>,
     ) -> Result<String, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .open_resource(
                 &SessionId(session_id),
                 &KeyHandle(scope_key_handle),
@@ -436,15 +1210,185 @@ impl KeyServiceWasm {
         Ok(response.resource_key_handle.0)
     }
 
+    /// Like `openResource`; `ops` lists `"encrypt"` and/or `"decrypt"` the handle may be used for.
+    #[wasm_bindgen(js_name = "openResourceWithOps")]
+    pub fn open_resource_with_ops(
+        &self,
+        session_id: String,
+        scope_key_handle: String,
+        grant_cbor: Vec<u8>,
+        ops: Vec<String>,
+    ) -> Result<String, JsValue> {
+        let ops = parse_capability_ops(&ops)?;
+        let response = self
+            .service()
+            .open_resource_with_ops(
+                &SessionId(session_id),
+                &KeyHandle(scope_key_handle),
+                &grant_cbor,
+                ops,
+            )
+            .map_err(to_js_error)?;
+        Ok(response.resource_key_handle.0)
+    }
+
+    /// `ops` lists `"encrypt"` and/or `"decrypt"`; returns `{ token, expiresAtMs }`.
+    #[wasm_bindgen(js_name = "mintCapability")]
+    pub fn mint_capability(
+        &self,
+        session_id: String,
+        resource_key_handle: String,
+        aad_prefix: Vec<u8>,
+        ops: Vec<String>,
+        ttl_ms: u64,
+    ) -> Result<JsValue, JsValue> {
+        let request = CapabilityRequest {
+            aad_prefix,
+            ops: parse_capability_ops(&ops)?,
+            ttl_ms,
+        };
+        let response = self
+            .service()
+            .mint_capability(
+                &SessionId(session_id),
+                &KeyHandle(resource_key_handle),
+                &request,
+            )
+            .map_err(to_js_error)?;
+        let obj = Object::new();
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("token"),
+            &Uint8Array::from(response.token.as_slice()).into(),
+        )
+        .expect("token");
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("expiresAtMs"),
+            &JsValue::from_f64(response.expires_at_ms as f64),
+        )
+        .expect("expiresAtMs");
+        Ok(obj.into())
+    }
+
+    /// Returns `{ resourceKeyHandle, ops, expiresAtMs }`.
+    #[wasm_bindgen(js_name = "redeemCapability")]
+    pub fn redeem_capability(
+        &self,
+        session_id: String,
+        token: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .redeem_capability(&SessionId(session_id), &token)
+            .map_err(to_js_error)?;
+        let obj = Object::new();
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("resourceKeyHandle"),
+            &JsValue::from_str(&response.resource_key_handle.0),
+        )
+        .expect("resourceKeyHandle");
+        let ops = Array::new();
+        if response.ops.encrypt {
+            ops.push(&JsValue::from_str("encrypt"));
+        }
+        if response.ops.decrypt {
+            ops.push(&JsValue::from_str("decrypt"));
+        }
+        Reflect::set(&obj, &JsValue::from_str("ops"), &ops.into()).expect("ops");
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("expiresAtMs"),
+            &JsValue::from_f64(response.expires_at_ms as f64),
+        )
+        .expect("expiresAtMs");
+        Ok(obj.into())
+    }
+
+    /// `path` is an array of `Uint8Array` segments.
+    #[wasm_bindgen(js_name = "deriveSubkey")]
+    pub fn derive_subkey(
+        &self,
+        session_id: String,
+        resource_key_handle: String,
+        path: Array,
+        len: usize,
+    ) -> Result<Uint8Array, JsValue> {
+        let segments: Vec<Vec<u8>> = path
+            .iter()
+            .map(|segment| {
+                segment
+                    .dyn_into::<Uint8Array>()
+                    .map(|bytes| bytes.to_vec())
+                    .map_err(|_| JsValue::from_str("path segments must be Uint8Array"))
+            })
+            .collect::<Result<_, _>>()?;
+        let path: Vec<&[u8]> = segments.iter().map(Vec::as_slice).collect();
+        let subkey = self
+            .service()
+            .derive_subkey(
+                &SessionId(session_id),
+                &KeyHandle(resource_key_handle),
+                &path,
+                len,
+            )
+            .map_err(to_js_error)?;
+        Ok(Uint8Array::from(subkey.as_slice()))
+    }
+
+    /// Re-wraps a grant under a newer scope epoch; returns `{ grant, grantSeq }`.
+    #[wasm_bindgen(js_name = "reissueGrant")]
+    pub fn reissue_grant(
+        &self,
+        session_id: String,
+        grant_cbor: Vec<u8>,
+        scope_key_handle: String,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .reissue_grant(
+                &SessionId(session_id),
+                &grant_cbor,
+                &KeyHandle(scope_key_handle),
+            )
+            .map_err(to_js_error)?;
+        let obj = Object::new();
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("grant"),
+            &Uint8Array::from(response.grant.as_slice()).into(),
+        )
+        .expect("grant");
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("grantSeq"),
+            &JsValue::from_f64(response.grant_seq as f64),
+        )
+        .expect("grantSeq");
+        Ok(obj.into())
+    }
+
     #[wasm_bindgen(js_name = "closeHandle")]
     pub fn close_handle(&self, session_id: String, key_handle: String) -> Result<(), JsValue> {
-        self.service
-            .borrow_mut()
+        self.service()
             .close_handle(&SessionId(session_id), &KeyHandle(key_handle))
             .map_err(to_js_error)?;
         Ok(())
     }
 
+    #[wasm_bindgen(js_name = "enableResourceRatchet")]
+    pub fn enable_resource_ratchet(
+        &self,
+        session_id: String,
+        resource_key_handle: String,
+    ) -> Result<(), JsValue> {
+        self.service()
+            .enable_resource_ratchet(&SessionId(session_id), &KeyHandle(resource_key_handle))
+            .map_err(to_js_error)?;
+        Ok(())
+    }
+
     #[wasm_bindgen(js_name = "encrypt")]
     pub fn encrypt(
         &self,
@@ -454,8 +1398,7 @@ impl KeyServiceWasm {
         plaintext: Vec<u8>,
     ) -> Result<Vec<u8>, JsValue> {
         let EncryptResponse { ciphertext } = self
-            .service
-            .borrow_mut()
+            .service()
             .encrypt(
                 &SessionId(session_id),
                 &KeyHandle(resource_key_handle),
@@ -475,8 +1418,7 @@ impl KeyServiceWasm {
         ciphertext: Vec<u8>,
     ) -> Result<Vec<u8>, JsValue> {
         let DecryptResponse { plaintext } = self
-            .service
-            .borrow_mut()
+            .service()
             .decrypt(
                 &SessionId(session_id),
                 &KeyHandle(resource_key_handle),
@@ -487,16 +1429,136 @@ impl KeyServiceWasm {
         Ok(plaintext)
     }
 
+    #[wasm_bindgen(js_name = "encryptInit")]
+    pub fn encrypt_init(
+        &self,
+        session_id: String,
+        resource_key_handle: String,
+        aad: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .encrypt_init(
+                &SessionId(session_id),
+                &KeyHandle(resource_key_handle),
+                &aad,
+            )
+            .map_err(to_js_error)?;
+        Ok(build_encrypt_init_response(&response))
+    }
+
+    #[wasm_bindgen(js_name = "encryptPush")]
+    pub fn encrypt_push(
+        &self,
+        session_id: String,
+        stream_id: String,
+        chunk: Vec<u8>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let EncryptResponse { ciphertext } = self
+            .service()
+            .encrypt_push(&SessionId(session_id), &StreamId(stream_id), &chunk)
+            .map_err(to_js_error)?;
+        Ok(ciphertext)
+    }
+
+    #[wasm_bindgen(js_name = "encryptFinish")]
+    pub fn encrypt_finish(
+        &self,
+        session_id: String,
+        stream_id: String,
+        chunk: Vec<u8>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let EncryptResponse { ciphertext } = self
+            .service()
+            .encrypt_finish(&SessionId(session_id), &StreamId(stream_id), &chunk)
+            .map_err(to_js_error)?;
+        Ok(ciphertext)
+    }
+
+    #[wasm_bindgen(js_name = "decryptInit")]
+    pub fn decrypt_init(
+        &self,
+        session_id: String,
+        resource_key_handle: String,
+        aad: Vec<u8>,
+        header: Vec<u8>,
+    ) -> Result<String, JsValue> {
+        let response = self
+            .service()
+            .decrypt_init(
+                &SessionId(session_id),
+                &KeyHandle(resource_key_handle),
+                &aad,
+                &header,
+            )
+            .map_err(to_js_error)?;
+        Ok(response.stream_id.0)
+    }
+
+    #[wasm_bindgen(js_name = "decryptPush")]
+    pub fn decrypt_push(
+        &self,
+        session_id: String,
+        stream_id: String,
+        chunk: Vec<u8>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let DecryptResponse { plaintext } = self
+            .service()
+            .decrypt_push(&SessionId(session_id), &StreamId(stream_id), &chunk)
+            .map_err(to_js_error)?;
+        Ok(plaintext)
+    }
+
+    #[wasm_bindgen(js_name = "decryptFinish")]
+    pub fn decrypt_finish(
+        &self,
+        session_id: String,
+        stream_id: String,
+        chunk: Vec<u8>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let DecryptResponse { plaintext } = self
+            .service()
+            .decrypt_finish(&SessionId(session_id), &StreamId(stream_id), &chunk)
+            .map_err(to_js_error)?;
+        Ok(plaintext)
+    }
+
     #[wasm_bindgen(js_name = "sign")]
     pub fn sign(&self, session_id: String, data: Vec<u8>) -> Result<JsValue, JsValue> {
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .sign(&SessionId(session_id), &data)
             .map_err(to_js_error)?;
         Ok(build_sign_response(&response))
     }
 
+    /// Signs the to-be-signed bytes of a `"scopeState"`, `"resourceGrant"`, `"keyEnvelope"`, or
+    /// `"signingDelegation"`; `sign` only produces application-payload signatures.
+    #[wasm_bindgen(js_name = "signFormat")]
+    pub fn sign_format(
+        &self,
+        session_id: String,
+        format: String,
+        to_be_signed: Vec<u8>,
+    ) -> Result<JsValue, JsValue> {
+        let format = match format.as_str() {
+            "scopeState" => SignedFormat::ScopeState,
+            "resourceGrant" => SignedFormat::ResourceGrant,
+            "keyEnvelope" => SignedFormat::KeyEnvelope,
+            "signingDelegation" => SignedFormat::SigningDelegation,
+            _ => {
+                return Err(JsValue::from_str(&format!(
+                    "unknown signed format: {format}"
+                )))
+            }
+        };
+        let response = self
+            .service()
+            .sign_format(&SessionId(session_id), format, &to_be_signed)
+            .map_err(to_js_error)?;
+        Ok(build_sign_response(&response))
+    }
+
     #[wasm_bindgen(js_name = "verify")]
     pub fn verify(
         &self,
@@ -509,8 +1571,7 @@ impl KeyServiceWasm {
         let suite = SigCiphersuiteId::try_from(ciphersuite.as_str())
             .map_err(|err| JsValue::from_str(&err))?;
         let response = self
-            .service
-            .borrow_mut()
+            .service()
             .verify(
                 ScopeId(scope_id),
                 DeviceId(signer_device_id),
@@ -521,52 +1582,491 @@ impl KeyServiceWasm {
             .map_err(to_js_error)?;
         Ok(response.ok)
     }
-}
 
-impl Default for KeyServiceWasm {
-    fn default() -> Self {
-        Self::new()
+    /// Returns `{ kem, publicBytes, fingerprint }`; `fingerprint` is the hex SHA-256 of
+    /// `publicBytes` (the envelope recipient fingerprint).
+    #[wasm_bindgen(js_name = "getUserPublicKey")]
+    pub fn get_user_public_key(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .get_user_public_key(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        Ok(build_user_public_key(&response))
     }
-}
 
-fn parse_storage_entries(entries: JsValue) -> Result<Vec<StorageEntry>, JsValue> {
-    if entries.is_null() || entries.is_undefined() {
-        return Ok(Vec::new());
+    /// Returns `[{ deviceId, sigSuite, ed25519Pub, mldsaPub, fingerprint }]` sorted by
+    /// device id; `fingerprint` is the hex signer fingerprint peers pin.
+    #[wasm_bindgen(js_name = "getDevicePublicKeys")]
+    pub fn get_device_public_keys(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .get_device_public_keys(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        let array = Array::new();
+        for device in &response.devices {
+            array.push(&build_device_public_key(device));
+        }
+        Ok(array.into())
     }
-    let array = Array::from(&entries);
-    let mut parsed = Vec::new();
-    for entry in array.iter() {
-        let namespace = get_string(&entry, "namespace")?;
-        let key = get_string(&entry, "key")?;
-        let value = get_u8_array(&entry, "value")?;
-        parsed.push(StorageEntry {
-            namespace,
-            key,
-            value,
-        });
+
+    /// Adds `userId`'s key to the recipient key directory; returns the entry as in
+    /// `listContacts`.
+    #[wasm_bindgen(js_name = "addContact")]
+    pub fn add_contact(
+        &self,
+        session_id: String,
+        user_id: String,
+        public_bytes: Vec<u8>,
+        expected_fingerprint: String,
+    ) -> Result<JsValue, JsValue> {
+        let contact = self
+            .service()
+            .add_contact(
+                &SessionId(session_id),
+                &UserId(user_id),
+                &public_bytes,
+                &expected_fingerprint,
+            )
+            .map_err(to_js_error)?;
+        Ok(build_contact(&contact))
     }
-    Ok(parsed)
-}
 
-fn parse_kdf_params(value: JsValue) -> Result<KdfParams, JsValue> {
-    let id = get_string(&value, "id")?;
-    let salt = get_u8_array(&value, "salt")?;
-    let memory_kib = get_u32(&value, "memoryKib")?;
-    let iterations = get_u32(&value, "iterations")?;
-    let parallelism = get_u32(&value, "parallelism")?;
-    Ok(KdfParams {
-        id,
-        salt,
-        memory_kib,
-        iterations,
-        parallelism,
-    })
-}
+    #[wasm_bindgen(js_name = "removeContact")]
+    pub fn remove_contact(&self, session_id: String, user_id: String) -> Result<bool, JsValue> {
+        self.service()
+            .remove_contact(&SessionId(session_id), &UserId(user_id))
+            .map_err(to_js_error)
+    }
 
-fn get_string(value: &JsValue, key: &str) -> Result<String, JsValue> {
-    let prop = Reflect::get(value, &JsValue::from_str(key))
-        .map_err(|_| JsValue::from_str("failed to read property"))?;
-    prop.as_string()
+    /// Returns `[{ userId, publicBytes, fingerprint, scopeId?, addedAtMs? }]` sorted by user
+    /// id; `scopeId` names the membership change the key came from.
+    #[wasm_bindgen(js_name = "listContacts")]
+    pub fn list_contacts(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .list_contacts(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        let array = Array::new();
+        for contact in &response.contacts {
+            array.push(&build_contact(contact));
+        }
+        Ok(array.into())
+    }
+
+    /// Seals the scope key to `recipientUserId`'s directory key; returns
+    /// `{ envelopeId, envelope }`.
+    #[wasm_bindgen(js_name = "sealKeyEnvelope")]
+    pub fn seal_key_envelope(
+        &self,
+        session_id: String,
+        scope_id: String,
+        scope_epoch: u64,
+        recipient_user_id: String,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .seal_key_envelope(
+                &SessionId(session_id),
+                &ScopeId(scope_id),
+                ScopeEpoch(scope_epoch),
+                &UserId(recipient_user_id),
+            )
+            .map_err(to_js_error)?;
+        let obj = Object::new();
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("envelopeId"),
+            &JsValue::from_str(&response.envelope_id),
+        )
+        .expect("envelopeId");
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("envelope"),
+            &Uint8Array::from(response.envelope.as_slice()).into(),
+        )
+        .expect("envelope");
+        Ok(obj.into())
+    }
+
+    #[wasm_bindgen(js_name = "readAuditLog")]
+    pub fn read_audit_log(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let entries = self
+            .service()
+            .read_audit_log(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        let array = Array::new();
+        for entry in &entries {
+            array.push(&build_audit_entry(entry));
+        }
+        Ok(array.into())
+    }
+
+    #[wasm_bindgen(js_name = "verifyAuditLog")]
+    pub fn verify_audit_log(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let report = self
+            .service()
+            .verify_audit_log(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        Ok(build_audit_verify_report(&report))
+    }
+
+    #[wasm_bindgen(js_name = "verifyKeyvault")]
+    pub fn verify_keyvault(&self, session_id: String) -> Result<JsValue, JsValue> {
+        let report = self
+            .service()
+            .verify_keyvault(&SessionId(session_id))
+            .map_err(to_js_error)?;
+        Ok(build_verify_keyvault_response(&report))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl KeyServiceWasm {
+    /// Auto-locks sessions at expiry: `callback` runs via `setTimeout` at each session deadline
+    /// and should call `onTimer`.
+    #[wasm_bindgen(js_name = "setAutoLockTimer")]
+    pub fn set_auto_lock_timer(&self, callback: js_sys::Function) {
+        for vault in self.vaults.borrow_mut().values_mut() {
+            vault.service.set_timer_adapter(WasmTimer {
+                callback: callback.clone(),
+                timeout_id: RefCell::new(None),
+            });
+        }
+        *self.auto_lock_callback.borrow_mut() = Some(callback);
+    }
+
+    /// `callback` receives `{ type: "expiringSoon", sessionId, expiresAtMs, vault }`,
+    /// `{ type: "expired", sessionId, vault }`, or `{ type: "locked", sessionId, vault }`. It
+    /// runs inside a service call, so it must not call back into the service synchronously.
+    #[wasm_bindgen(js_name = "setSessionEventsCallback")]
+    pub fn set_session_events_callback(&self, callback: js_sys::Function) {
+        for (namespace, vault) in self.vaults.borrow_mut().iter_mut() {
+            vault.service.set_session_events_adapter(WasmSessionEvents {
+                callback: callback.clone(),
+                vault: namespace.clone(),
+            });
+        }
+        *self.session_events_callback.borrow_mut() = Some(callback);
+    }
+
+    /// Registers `callback` like `setSessionEventsCallback` and, unless `setAutoLockTimer`
+    /// already installed a host timer, arms a `setTimeout` wake-up that runs `onTimer` itself,
+    /// so expiry warnings and auto-lock need no polling. `type` is `"expiringSoon"`,
+    /// `"expired"`, or `"locked"` (explicit `lock`).
+    #[wasm_bindgen(js_name = "onSessionEvent")]
+    pub fn on_session_event(&self, callback: js_sys::Function) {
+        self.set_session_events_callback(callback);
+        if self.auto_lock_callback.borrow().is_some() {
+            return;
+        }
+        let vaults = Rc::clone(&self.vaults);
+        let closure = Closure::<dyn FnMut()>::new(move || {
+            for vault in vaults.borrow_mut().values_mut() {
+                vault.service.on_timer();
+            }
+        });
+        let timer = closure.as_ref().unchecked_ref::<js_sys::Function>().clone();
+        *self.timer_closure.borrow_mut() = Some(closure);
+        self.set_auto_lock_timer(timer);
+    }
+
+    /// Like `unlockPassphrase`, failing with `Cancelled` once `isCancelled()` returns true.
+    /// It is polled synchronously mid-call, so a worker host would read a `SharedArrayBuffer`
+    /// flag (`Atomics.load`) that the UI thread sets.
+    #[wasm_bindgen(js_name = "unlockPassphraseCancellable")]
+    pub fn unlock_passphrase_cancellable(
+        &self,
+        passphrase_utf8: Vec<u8>,
+        is_cancelled: js_sys::Function,
+    ) -> Result<JsValue, JsValue> {
+        let response = self
+            .service()
+            .unlock_passphrase_cancellable(&passphrase_utf8, &js_cancellation(is_cancelled))
+            .map_err(to_js_error)?;
+        Ok(build_unlock_response(&response))
+    }
+
+    /// Like `importKeyVault`, failing with `Cancelled` (before anything is written) once
+    /// `isCancelled()` returns true.
+    #[wasm_bindgen(js_name = "importKeyVaultCancellable")]
+    pub fn import_keyvault_cancellable(
+        &self,
+        session_id: String,
+        blob: Vec<u8>,
+        is_cancelled: js_sys::Function,
+    ) -> Result<(), JsValue> {
+        self.service()
+            .import_keyvault_cancellable(
+                &SessionId(session_id),
+                &blob,
+                &js_cancellation(is_cancelled),
+            )
+            .map_err(to_js_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn js_cancellation(is_cancelled: js_sys::Function) -> CancellationToken {
+    CancellationToken::from_probe(move || {
+        is_cancelled
+            .call0(&JsValue::NULL)
+            .map(|value| value.is_truthy())
+            .unwrap_or(false)
+    })
+}
+
+impl Default for KeyServiceWasm {
+    fn default() -> Self {
+        Self::with_vault(DEFAULT_VAULT, KeyServiceConfig::default())
+    }
+}
+
+// AAD builders, so hosts can produce bytes identical to the core's.
+
+#[wasm_bindgen(js_name = "aadKeyvaultKeywrapV1")]
+pub fn aad_keyvault_keywrap_v1(
+    vault_id: String,
+    user_id: String,
+    kdf_params: JsValue,
+    aead: String,
+) -> Result<Uint8Array, JsValue> {
+    let kdf = parse_kdf_params(kdf_params)?;
+    let aad = aad::aad_keyvault_keywrap_v1(&vault_id, &user_id, &kdf, parse_aead(&aead)?)
+        .map_err(|err| to_js_error(KeyServiceError::from(err)))?;
+    Ok(Uint8Array::from(aad.as_slice()))
+}
+
+#[wasm_bindgen(js_name = "aadKeyvaultRecordV1")]
+pub fn aad_keyvault_record_v1(
+    vault_id: String,
+    user_id: String,
+    aead: String,
+    record_id: String,
+) -> Result<Uint8Array, JsValue> {
+    let aad = aad::aad_keyvault_record_v1(&vault_id, &user_id, parse_aead(&aead)?, &record_id)
+        .map_err(|err| to_js_error(KeyServiceError::from(err)))?;
+    Ok(Uint8Array::from(aad.as_slice()))
+}
+
+#[wasm_bindgen(js_name = "aadKeyvaultHeadMarkerV1")]
+pub fn aad_keyvault_head_marker_v1(
+    vault_id: String,
+    user_id: String,
+    aead: String,
+) -> Result<Uint8Array, JsValue> {
+    let aad = aad::aad_keyvault_head_marker_v1(&vault_id, &user_id, parse_aead(&aead)?)
+        .map_err(|err| to_js_error(KeyServiceError::from(err)))?;
+    Ok(Uint8Array::from(aad.as_slice()))
+}
+
+#[wasm_bindgen(js_name = "aadKeyEnvelopeWrapV1")]
+pub fn aad_key_envelope_wrap_v1(
+    scope_id: String,
+    scope_epoch: u64,
+    recipient_user_id: String,
+    scope_state_ref: Vec<u8>,
+    kem: String,
+    aead: String,
+    recipient_uk_pub_fingerprint: Option<Vec<u8>>,
+) -> Result<Uint8Array, JsValue> {
+    let kem = KemCiphersuiteId::try_from(kem.as_str()).map_err(|err| JsValue::from_str(&err))?;
+    let aad = aad::aad_key_envelope_wrap_v1(
+        &scope_id,
+        scope_epoch,
+        &recipient_user_id,
+        &scope_state_ref,
+        kem,
+        parse_aead(&aead)?,
+        recipient_uk_pub_fingerprint.as_ref(),
+    )
+    .map_err(|err| to_js_error(KeyServiceError::from(err)))?;
+    Ok(Uint8Array::from(aad.as_slice()))
+}
+
+#[wasm_bindgen(js_name = "aadKeyEnvelopePrekeyWrapV1")]
+#[allow(clippy::too_many_arguments)]
+pub fn aad_key_envelope_prekey_wrap_v1(
+    scope_id: String,
+    scope_epoch: u64,
+    recipient_user_id: String,
+    scope_state_ref: Vec<u8>,
+    kem: String,
+    aead: String,
+    recipient_uk_pub_fingerprint: Vec<u8>,
+    prekey_id: u64,
+) -> Result<Uint8Array, JsValue> {
+    let kem = KemCiphersuiteId::try_from(kem.as_str()).map_err(|err| JsValue::from_str(&err))?;
+    let aad = aad::aad_key_envelope_prekey_wrap_v1(
+        &scope_id,
+        scope_epoch,
+        &recipient_user_id,
+        &scope_state_ref,
+        kem,
+        parse_aead(&aead)?,
+        &recipient_uk_pub_fingerprint,
+        prekey_id,
+    )
+    .map_err(|err| to_js_error(KeyServiceError::from(err)))?;
+    Ok(Uint8Array::from(aad.as_slice()))
+}
+
+#[wasm_bindgen(js_name = "aadResourceGrantWrapV1")]
+pub fn aad_resource_grant_wrap_v1(
+    scope_id: String,
+    resource_id: String,
+    scope_epoch: u64,
+    resource_key_id: String,
+    aead: String,
+) -> Result<Uint8Array, JsValue> {
+    let aad = aad::aad_resource_grant_wrap_v1(
+        &scope_id,
+        &resource_id,
+        scope_epoch,
+        &resource_key_id,
+        parse_aead(&aead)?,
+    )
+    .map_err(|err| to_js_error(KeyServiceError::from(err)))?;
+    Ok(Uint8Array::from(aad.as_slice()))
+}
+
+#[wasm_bindgen(js_name = "aadUserPresenceWrapV1")]
+pub fn aad_user_presence_wrap_v1(
+    vault_id: String,
+    user_id: String,
+    kdf_params: JsValue,
+    aead: String,
+) -> Result<Uint8Array, JsValue> {
+    let kdf = parse_kdf_params(kdf_params)?;
+    let aad = aad::aad_user_presence_wrap_v1(&vault_id, &user_id, &kdf, parse_aead(&aead)?)
+        .map_err(|err| to_js_error(KeyServiceError::from(err)))?;
+    Ok(Uint8Array::from(aad.as_slice()))
+}
+
+fn parse_capability_ops(ops: &[String]) -> Result<CapabilityOps, JsValue> {
+    let mut parsed = CapabilityOps::default();
+    for op in ops {
+        match op.as_str() {
+            "encrypt" => parsed.encrypt = true,
+            "decrypt" => parsed.decrypt = true,
+            other => {
+                return Err(JsValue::from_str(&format!(
+                    "unknown capability op: {other}"
+                )))
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_aead(value: &str) -> Result<AeadId, JsValue> {
+    AeadId::try_from(value).map_err(|err| JsValue::from_str(&err))
+}
+
+fn parse_storage_entries(entries: JsValue) -> Result<Vec<StorageEntry>, JsValue> {
+    if entries.is_null() || entries.is_undefined() {
+        return Ok(Vec::new());
+    }
+    let array = Array::from(&entries);
+    let mut parsed = Vec::new();
+    for entry in array.iter() {
+        let namespace = get_string(&entry, "namespace")?;
+        let key = get_string(&entry, "key")?;
+        let value = get_u8_array(&entry, "value")?;
+        parsed.push(StorageEntry {
+            namespace,
+            key,
+            value,
+        });
+    }
+    Ok(parsed)
+}
+
+fn parse_options(options: JsValue) -> Result<KeyServiceConfig, JsValue> {
+    let mut policy = KeyServicePolicy::default();
+    if options.is_null() || options.is_undefined() {
+        return Ok(KeyServiceConfig { policy });
+    }
+    if let Some(max) = get_opt_usize(&options, "maxCborBytes")? {
+        policy.max_cbor_bytes = max;
+        // A lowered byte limit also caps text values, which must fit within it.
+        policy.max_cbor_text_bytes = policy.max_cbor_text_bytes.min(max);
+    }
+    if let Some(max) = get_opt_usize(&options, "maxCborDepth")? {
+        policy.max_cbor_depth = max;
+    }
+    if let Some(max) = get_opt_usize(&options, "maxCborItems")? {
+        policy.max_cbor_items = max;
+    }
+    let key_wrap_alg = Reflect::get(&options, &JsValue::from_str("keyWrapAlg"))
+        .map_err(|_| JsValue::from_str("failed to read property"))?;
+    if let Some(alg) = key_wrap_alg.as_string() {
+        policy.key_wrap_alg =
+            KeyWrapAlg::try_from(alg.as_str()).map_err(|err| JsValue::from_str(&err))?;
+    }
+    if let Some(max) = get_opt_usize(&options, "maxIdBytes")? {
+        policy.ids.max_bytes = max;
+    }
+    let id_charset = Reflect::get(&options, &JsValue::from_str("idCharset"))
+        .map_err(|_| JsValue::from_str("failed to read property"))?;
+    if let Some(charset) = id_charset.as_string() {
+        policy.ids.charset = match charset.as_str() {
+            "noControl" => IdCharset::NoControl,
+            "urlSafe" => IdCharset::UrlSafe,
+            _ => return Err(JsValue::from_str("unknown idCharset")),
+        };
+    }
+    if let Some(ms) = get_opt_usize(&options, "maxClockRewindMs")? {
+        policy.max_clock_rewind_ms = ms as u64;
+    }
+    if let Some(ms) = get_opt_usize(&options, "maxClockJumpMs")? {
+        policy.max_clock_jump_ms = Some(ms as u64);
+    }
+    let accept_unbound = Reflect::get(&options, &JsValue::from_str("acceptUnboundSignatures"))
+        .map_err(|_| JsValue::from_str("failed to read property"))?;
+    if let Some(accept) = accept_unbound.as_bool() {
+        policy.accept_unbound_signatures = accept;
+    }
+    policy.opaque.context = get_u8_array(&options, "opaqueContext")?;
+    let server_identity = get_u8_array(&options, "opaqueServerIdentity")?;
+    policy.opaque.server_identity = (!server_identity.is_empty()).then_some(server_identity);
+    let client_identity = get_u8_array(&options, "opaqueClientIdentity")?;
+    policy.opaque.client_identity = (!client_identity.is_empty()).then_some(client_identity);
+    let ksf = Reflect::get(&options, &JsValue::from_str("opaqueKsf"))
+        .map_err(|_| JsValue::from_str("failed to read property"))?;
+    if !ksf.is_null() && !ksf.is_undefined() {
+        policy.opaque.ksf = OpaqueKsf::Argon2id {
+            memory_kib: get_u32(&ksf, "memoryKib")?,
+            iterations: get_u32(&ksf, "iterations")?,
+            parallelism: get_u32(&ksf, "parallelism")?,
+        };
+    }
+    KeyServiceConfig::builder()
+        .policy(policy)
+        .build()
+        .map_err(to_js_error)
+}
+
+fn parse_kdf_params(value: JsValue) -> Result<KdfParams, JsValue> {
+    let id = get_string(&value, "id")?;
+    let salt = get_u8_array(&value, "salt")?;
+    let memory_kib = get_u32(&value, "memoryKib")?;
+    let iterations = get_u32(&value, "iterations")?;
+    let parallelism = get_u32(&value, "parallelism")?;
+    Ok(KdfParams {
+        id,
+        salt,
+        memory_kib,
+        iterations,
+        parallelism,
+    })
+}
+
+fn get_string(value: &JsValue, key: &str) -> Result<String, JsValue> {
+    let prop = Reflect::get(value, &JsValue::from_str(key))
+        .map_err(|_| JsValue::from_str("failed to read property"))?;
+    prop.as_string()
         .ok_or_else(|| JsValue::from_str("expected string"))
 }
 
@@ -578,6 +2078,22 @@ fn get_u32(value: &JsValue, key: &str) -> Result<u32, JsValue> {
         .map(|num| num as u32)
 }
 
+fn get_opt_usize(value: &JsValue, key: &str) -> Result<Option<usize>, JsValue> {
+    let prop = Reflect::get(value, &JsValue::from_str(key))
+        .map_err(|_| JsValue::from_str("failed to read property"))?;
+    if prop.is_null() || prop.is_undefined() {
+        return Ok(None);
+    }
+    match prop.as_f64() {
+        Some(num) if num >= 0.0 && num.fract() == 0.0 && num <= usize::MAX as f64 => {
+            Ok(Some(num as usize))
+        }
+        _ => Err(JsValue::from_str(&format!(
+            "{key} must be a non-negative integer"
+        ))),
+    }
+}
+
 fn get_u8_array(value: &JsValue, key: &str) -> Result<Vec<u8>, JsValue> {
     let prop = Reflect::get(value, &JsValue::from_str(key))
         .map_err(|_| JsValue::from_str("failed to read property"))?;
@@ -588,6 +2104,79 @@ fn get_u8_array(value: &JsValue, key: &str) -> Result<Vec<u8>, JsValue> {
     Ok(array.to_vec())
 }
 
+fn build_salvage_unlock_response(response: &SalvageUnlockResponse) -> JsValue {
+    let obj = build_unlock_response(&response.unlock);
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("report"),
+        &build_salvage_report(&response.report),
+    )
+    .expect("report");
+    obj
+}
+
+fn build_salvage_report(report: &KeyVaultSalvageReport) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("breakSeq"),
+        &report
+            .break_seq
+            .map(|seq| JsValue::from_f64(seq as f64))
+            .unwrap_or(JsValue::NULL),
+    )
+    .expect("breakSeq");
+    let rechained = Array::new();
+    for record_id in &report.rechained_record_ids {
+        rechained.push(&JsValue::from_str(record_id));
+    }
+    Reflect::set(&obj, &JsValue::from_str("rechainedRecordIds"), &rechained)
+        .expect("rechainedRecordIds");
+    let truncated = Array::new();
+    for record_id in &report.truncated_record_ids {
+        truncated.push(&JsValue::from_str(record_id));
+    }
+    Reflect::set(&obj, &JsValue::from_str("truncatedRecordIds"), &truncated)
+        .expect("truncatedRecordIds");
+    let damaged = Array::new();
+    for record in &report.damaged {
+        let entry = Object::new();
+        Reflect::set(
+            &entry,
+            &JsValue::from_str("recordId"),
+            &JsValue::from_str(&record.record_id),
+        )
+        .expect("recordId");
+        Reflect::set(
+            &entry,
+            &JsValue::from_str("seq"),
+            &record
+                .seq
+                .map(|seq| JsValue::from_f64(seq as f64))
+                .unwrap_or(JsValue::NULL),
+        )
+        .expect("seq");
+        Reflect::set(
+            &entry,
+            &JsValue::from_str("reason"),
+            &JsValue::from_str(&record.reason),
+        )
+        .expect("reason");
+        damaged.push(&entry);
+    }
+    Reflect::set(&obj, &JsValue::from_str("damaged"), &damaged).expect("damaged");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("sealedHeadSeq"),
+        &report
+            .sealed_head_seq
+            .map(|seq| JsValue::from_f64(seq as f64))
+            .unwrap_or(JsValue::NULL),
+    )
+    .expect("sealedHeadSeq");
+    obj.into()
+}
+
 fn build_unlock_response(response: &UnlockResponse) -> JsValue {
     let obj = Object::new();
     let kind = session_kind_to_str(response.kind);
@@ -617,6 +2206,34 @@ fn build_unlock_response(response: &UnlockResponse) -> JsValue {
         &JsValue::from_str(assurance),
     )
     .expect("assurance");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("hasUserKey"),
+        &JsValue::from_bool(response.has_user_key),
+    )
+    .expect("hasUserKey");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("readOnly"),
+        &JsValue::from_bool(response.read_only),
+    )
+    .expect("readOnly");
+    let device_ids = Array::new();
+    for device_id in &response.device_ids {
+        device_ids.push(&JsValue::from_str(&device_id.0));
+    }
+    Reflect::set(&obj, &JsValue::from_str("deviceIds"), &device_ids.into()).expect("deviceIds");
+    obj.into()
+}
+
+fn build_change_passphrase_response(response: &ChangePassphraseResponse) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("userPresenceDisabled"),
+        &JsValue::from_bool(response.user_presence_disabled),
+    )
+    .expect("userPresenceDisabled");
     obj.into()
 }
 
@@ -691,6 +2308,315 @@ fn build_user_presence_info(response: &GetUserPresenceUnlockInfoResponse) -> JsV
     obj.into()
 }
 
+fn build_health_check(report: &HealthCheckResponse) -> JsValue {
+    let obj = Object::new();
+    for (key, value) in [
+        ("ok", report.ok),
+        ("storageOk", report.storage_ok),
+        ("entropyOk", report.entropy_ok),
+        ("clockOk", report.clock_ok),
+    ] {
+        Reflect::set(&obj, &JsValue::from_str(key), &JsValue::from_bool(value)).expect(key);
+    }
+    let problems = Array::new();
+    for problem in &report.problems {
+        problems.push(&JsValue::from_str(problem));
+    }
+    Reflect::set(&obj, &JsValue::from_str("problems"), &problems.into()).expect("problems");
+    obj.into()
+}
+
+fn build_diagnostics(report: &DiagnosticsReport) -> JsValue {
+    let obj = Object::new();
+    let set = |target: &Object, key: &str, value: JsValue| {
+        Reflect::set(target, &JsValue::from_str(key), &value).expect(key);
+    };
+    set(
+        &obj,
+        "generatedAtMs",
+        JsValue::from_f64(report.generated_at_ms as f64),
+    );
+    set(&obj, "unlocked", JsValue::from_bool(report.unlocked));
+    set(
+        &obj,
+        "sessionCount",
+        JsValue::from_f64(report.session_count as f64),
+    );
+    if let Some(vault) = &report.vault {
+        let v = Object::new();
+        set(&v, "vaultIdTag", JsValue::from_str(&vault.vault_id_tag));
+        set(&v, "userIdTag", JsValue::from_str(&vault.user_id_tag));
+        set(&v, "aead", JsValue::from_str(vault.aead.as_str()));
+        set(&v, "headHashTag", JsValue::from_str(&vault.head_hash_tag));
+        for (key, value) in [
+            ("kdfMemoryKib", u64::from(vault.kdf_memory_kib)),
+            ("kdfIterations", u64::from(vault.kdf_iterations)),
+            ("kdfParallelism", u64::from(vault.kdf_parallelism)),
+            ("headerBytes", vault.header_bytes),
+            ("headerRecordCount", vault.header_record_count),
+            ("recordCount", vault.record_count),
+            ("recordBytesTotal", vault.record_bytes_total),
+            ("recordBytesMax", vault.record_bytes_max),
+            ("headSeq", vault.head_seq),
+            ("auditHeadSeq", vault.audit_head_seq),
+        ] {
+            set(&v, key, JsValue::from_f64(value as f64));
+        }
+        for (key, value) in [
+            ("chainLinked", vault.chain_linked),
+            ("headMarkerPresent", vault.head_marker_present),
+            ("userPresenceEnabled", vault.user_presence_enabled),
+        ] {
+            set(&v, key, JsValue::from_bool(value));
+        }
+        set(&obj, "vault", v.into());
+    } else {
+        set(&obj, "vault", JsValue::NULL);
+    }
+    let policy = Object::new();
+    let p = &report.policy;
+    for (key, value) in [
+        ("normalSessionTtlMs", p.normal_session_ttl_ms),
+        ("stepUpSessionTtlMs", p.step_up_session_ttl_ms),
+        ("maxHandlesPerSession", p.max_handles_per_session as u64),
+        ("maxCborBytes", p.max_cbor_bytes as u64),
+        ("maxCborDepth", p.max_cbor_depth as u64),
+        ("maxCborItems", p.max_cbor_items as u64),
+        ("maxCborTextBytes", p.max_cbor_text_bytes as u64),
+        (
+            "maxScopeStateRefsPerScope",
+            p.max_scope_state_refs_per_scope as u64,
+        ),
+        ("maxPlaintextBytes", p.max_plaintext_bytes as u64),
+        ("sessionExpiryWarningMs", p.session_expiry_warning_ms),
+        ("maxRecordBytes", p.max_record_bytes as u64),
+        ("maxTotalRecords", p.max_total_records),
+    ] {
+        set(&policy, key, JsValue::from_f64(value as f64));
+    }
+    set(
+        &policy,
+        "readOnlySessions",
+        JsValue::from_bool(p.read_only_sessions),
+    );
+    set(
+        &policy,
+        "lazyResourceKeys",
+        JsValue::from_bool(p.lazy_resource_keys),
+    );
+    set(
+        &policy,
+        "minPassphraseScore",
+        p.min_passphrase_score
+            .map_or(JsValue::NULL, |score| JsValue::from_f64(score as f64)),
+    );
+    set(
+        &policy,
+        "nonceMode",
+        JsValue::from_str(match p.nonce_mode {
+            NonceMode::Random => "random",
+            NonceMode::Counter => "counter",
+        }),
+    );
+    set(
+        &policy,
+        "keyWrapAlg",
+        JsValue::from_str(p.key_wrap_alg.as_str()),
+    );
+    set(
+        &policy,
+        "acceptUnboundSignatures",
+        JsValue::from_bool(p.accept_unbound_signatures),
+    );
+    set(
+        &policy,
+        "opaqueKsf",
+        JsValue::from_str(match p.opaque.ksf {
+            OpaqueKsf::Identity => "identity",
+            OpaqueKsf::Argon2id { .. } => "argon2id",
+        }),
+    );
+    set(&obj, "policy", policy.into());
+    let errors = Array::new();
+    for error in &report.recent_errors {
+        let e = Object::new();
+        set(&e, "atMs", JsValue::from_f64(error.at_ms as f64));
+        set(&e, "op", JsValue::from_str(error.op.as_str()));
+        set(&e, "code", JsValue::from_str(error.code));
+        errors.push(&e);
+    }
+    set(&obj, "recentErrors", errors.into());
+    let problems = Array::new();
+    for problem in &report.problems {
+        problems.push(&JsValue::from_str(problem));
+    }
+    set(&obj, "problems", problems.into());
+    obj.into()
+}
+
+/// `None` for event kinds added after this binding.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+fn build_session_event(event: &SessionEvent) -> Option<JsValue> {
+    let obj = Object::new();
+    let set = |key: &str, value: JsValue| {
+        Reflect::set(&obj, &JsValue::from_str(key), &value).expect(key);
+    };
+    match event {
+        SessionEvent::ExpiringSoon {
+            session_id,
+            expires_at_ms,
+        } => {
+            set("type", JsValue::from_str("expiringSoon"));
+            set("sessionId", JsValue::from_str(&session_id.0));
+            set("expiresAtMs", JsValue::from_f64(*expires_at_ms as f64));
+        }
+        SessionEvent::Expired { session_id } => {
+            set("type", JsValue::from_str("expired"));
+            set("sessionId", JsValue::from_str(&session_id.0));
+        }
+        SessionEvent::Locked { session_id } => {
+            set("type", JsValue::from_str("locked"));
+            set("sessionId", JsValue::from_str(&session_id.0));
+        }
+        _ => return None,
+    }
+    Some(obj.into())
+}
+
+fn build_storage_writes(entries: Vec<StorageEntry>) -> Array {
+    let array = Array::new();
+    for entry in entries {
+        let obj = Object::new();
+        let value = Uint8Array::from(entry.value.as_slice());
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("namespace"),
+            &JsValue::from_str(&entry.namespace),
+        )
+        .expect("set namespace");
+        Reflect::set(
+            &obj,
+            &JsValue::from_str("key"),
+            &JsValue::from_str(&entry.key),
+        )
+        .expect("set key");
+        Reflect::set(&obj, &JsValue::from_str("value"), &value.into()).expect("set value");
+        array.push(&obj);
+    }
+    array
+}
+
+fn build_session_summary(vault: &str, summary: &SessionSummary) -> JsValue {
+    let obj = Object::new();
+    let set = |key: &str, value: JsValue| {
+        Reflect::set(&obj, &JsValue::from_str(key), &value).expect(key);
+    };
+    set("sessionId", JsValue::from_str(&summary.session_id.0));
+    set("vault", JsValue::from_str(vault));
+    set("kind", JsValue::from_str(session_kind_to_str(summary.kind)));
+    set(
+        "assurance",
+        JsValue::from_str(session_assurance_to_str(summary.assurance)),
+    );
+    set("issuedAtMs", JsValue::from_f64(summary.issued_at_ms as f64));
+    set(
+        "expiresAtMs",
+        JsValue::from_f64(summary.expires_at_ms as f64),
+    );
+    set("readOnly", JsValue::from_bool(summary.read_only));
+    set(
+        "handleCount",
+        JsValue::from_f64(summary.handle_count as f64),
+    );
+    obj.into()
+}
+
+fn build_vault_info(response: &VaultInfoResponse) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("vaultId"),
+        &JsValue::from_str(&response.vault_id),
+    )
+    .expect("vaultId");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("userId"),
+        &JsValue::from_str(&response.user_id.0),
+    )
+    .expect("userId");
+    let kdf = Object::new();
+    Reflect::set(
+        &kdf,
+        &JsValue::from_str("id"),
+        &JsValue::from_str(&response.kdf.id),
+    )
+    .expect("id");
+    Reflect::set(
+        &kdf,
+        &JsValue::from_str("salt"),
+        &Uint8Array::from(response.kdf.salt.as_slice()).into(),
+    )
+    .expect("salt");
+    Reflect::set(
+        &kdf,
+        &JsValue::from_str("memoryKib"),
+        &JsValue::from_f64(response.kdf.memory_kib as f64),
+    )
+    .expect("memoryKib");
+    Reflect::set(
+        &kdf,
+        &JsValue::from_str("iterations"),
+        &JsValue::from_f64(response.kdf.iterations as f64),
+    )
+    .expect("iterations");
+    Reflect::set(
+        &kdf,
+        &JsValue::from_str("parallelism"),
+        &JsValue::from_f64(response.kdf.parallelism as f64),
+    )
+    .expect("parallelism");
+    Reflect::set(&obj, &JsValue::from_str("kdf"), &kdf.into()).expect("kdf");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("aead"),
+        &JsValue::from_str(response.aead.as_str()),
+    )
+    .expect("aead");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("recordCount"),
+        &JsValue::from_f64(response.record_count as f64),
+    )
+    .expect("recordCount");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("userPresenceEnabled"),
+        &JsValue::from_bool(response.user_presence_enabled),
+    )
+    .expect("userPresenceEnabled");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("kmsKeyId"),
+        &response
+            .kms_key_id
+            .as_deref()
+            .map(JsValue::from_str)
+            .unwrap_or(JsValue::NULL),
+    )
+    .expect("kmsKeyId");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("deviceShareThreshold"),
+        &response
+            .device_share_threshold
+            .map(|threshold| JsValue::from_f64(threshold as f64))
+            .unwrap_or(JsValue::NULL),
+    )
+    .expect("deviceShareThreshold");
+    obj.into()
+}
+
 fn build_ingest_scope_state_response(response: &IngestScopeStateResponse) -> JsValue {
     let obj = Object::new();
     Reflect::set(
@@ -705,6 +2631,67 @@ fn build_ingest_scope_state_response(response: &IngestScopeStateResponse) -> JsV
         &JsValue::from_str(&response.scope_state_ref),
     )
     .expect("scopeStateRef");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("warnings"),
+        &build_ingest_warnings(&response.warnings),
+    )
+    .expect("warnings");
+    obj.into()
+}
+
+/// `[{ code, message, ... }]`, plus `deviceId`, `scopeEpoch`/`latestEpoch`, or `userId` per
+/// warning.
+fn build_ingest_warnings(warnings: &[IngestWarning]) -> JsValue {
+    let array = Array::new();
+    for warning in warnings {
+        let obj = Object::new();
+        let set = |key: &str, value: JsValue| {
+            Reflect::set(&obj, &JsValue::from_str(key), &value).expect("warning field");
+        };
+        set("code", JsValue::from_str(warning.code()));
+        set("message", JsValue::from_str(&warning.to_string()));
+        match warning {
+            IngestWarning::FingerprintPinned { device_id }
+            | IngestWarning::SignerPreviouslyUnseen { device_id } => {
+                set("deviceId", JsValue::from_str(&device_id.0));
+            }
+            IngestWarning::OlderEpoch {
+                scope_epoch,
+                latest_epoch,
+            } => {
+                set("scopeEpoch", BigInt::from(scope_epoch.0).into());
+                set("latestEpoch", BigInt::from(latest_epoch.0).into());
+            }
+            IngestWarning::ContactKeyConflict { user_id } => {
+                set("userId", JsValue::from_str(&user_id.0));
+            }
+        }
+        array.push(&obj);
+    }
+    array.into()
+}
+
+fn build_ingest_delegation_response(response: &IngestDelegationResponse) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("scopeId"),
+        &JsValue::from_str(&response.scope_id.0),
+    )
+    .expect("scopeId");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("delegateDeviceId"),
+        &JsValue::from_str(&response.delegate_device_id.0),
+    )
+    .expect("delegateDeviceId");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("expiresAtMs"),
+        &JsValue::from_f64(response.expires_at_ms as f64),
+    )
+    .expect("expiresAtMs");
     obj.into()
 }
 
@@ -718,6 +2705,31 @@ fn build_ingest_key_envelope_response(response: &IngestKeyEnvelopeResponse) -> J
     .expect("scopeId");
     let epoch = BigInt::from(response.scope_epoch.0);
     Reflect::set(&obj, &JsValue::from_str("scopeEpoch"), &epoch.into()).expect("scopeEpoch");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("isNew"),
+        &JsValue::from_bool(response.is_new),
+    )
+    .expect("isNew");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("warnings"),
+        &build_ingest_warnings(&response.warnings),
+    )
+    .expect("warnings");
+    obj.into()
+}
+
+fn build_encrypt_init_response(response: &EncryptInitResponse) -> JsValue {
+    let obj = Object::new();
+    let header = Uint8Array::from(response.header.as_slice());
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("streamId"),
+        &JsValue::from_str(&response.stream_id.0),
+    )
+    .expect("streamId");
+    Reflect::set(&obj, &JsValue::from_str("header"), &header.into()).expect("header");
     obj.into()
 }
 
@@ -734,6 +2746,357 @@ fn build_sign_response(response: &SignResponse) -> JsValue {
     obj.into()
 }
 
+fn build_user_public_key(response: &UserPublicKeyResponse) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("kem"),
+        &JsValue::from_str(response.kem.as_str()),
+    )
+    .expect("kem");
+    let public_bytes = Uint8Array::from(response.public_bytes.as_slice());
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("publicBytes"),
+        &public_bytes.into(),
+    )
+    .expect("publicBytes");
+    let xwing_public_bytes = Uint8Array::from(response.xwing_public_bytes.as_slice());
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("xwingPublicBytes"),
+        &xwing_public_bytes.into(),
+    )
+    .expect("xwingPublicBytes");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("fingerprint"),
+        &JsValue::from_str(&response.fingerprint_hex),
+    )
+    .expect("fingerprint");
+    obj.into()
+}
+
+fn build_contact(contact: &Contact) -> JsValue {
+    let obj = Object::new();
+    let set = |key: &str, value: JsValue| {
+        Reflect::set(&obj, &JsValue::from_str(key), &value).expect(key);
+    };
+    set("userId", JsValue::from_str(&contact.user_id.0));
+    set(
+        "publicBytes",
+        Uint8Array::from(contact.public_bytes.as_slice()).into(),
+    );
+    set("fingerprint", JsValue::from_str(&contact.fingerprint_hex));
+    if let Some(scope_id) = &contact.scope_id {
+        set("scopeId", JsValue::from_str(&scope_id.0));
+    }
+    if let Some(added_at_ms) = contact.added_at_ms {
+        set("addedAtMs", JsValue::from_f64(added_at_ms as f64));
+    }
+    obj.into()
+}
+
+fn build_device_public_key(device: &DevicePublicKey) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("deviceId"),
+        &JsValue::from_str(&device.device_id.0),
+    )
+    .expect("deviceId");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("sigSuite"),
+        &JsValue::from_str(device.signer.sig_suite.as_str()),
+    )
+    .expect("sigSuite");
+    let ed25519 = Uint8Array::from(device.signer.ed25519_pub.as_slice());
+    Reflect::set(&obj, &JsValue::from_str("ed25519Pub"), &ed25519.into()).expect("ed25519Pub");
+    let mldsa = Uint8Array::from(device.signer.mldsa_pub.as_slice());
+    Reflect::set(&obj, &JsValue::from_str("mldsaPub"), &mldsa.into()).expect("mldsaPub");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("fingerprint"),
+        &JsValue::from_str(&device.fingerprint),
+    )
+    .expect("fingerprint");
+    obj.into()
+}
+
+fn build_due_rotation(due: &DueRotation) -> JsValue {
+    let obj = Object::new();
+    let set = |key: &str, value: JsValue| {
+        Reflect::set(&obj, &JsValue::from_str(key), &value).expect(key);
+    };
+    set("scopeId", JsValue::from_str(&due.scope_id.0));
+    set("scopeEpoch", BigInt::from(due.scope_epoch.0).into());
+    set("reason", JsValue::from_str(due.reason.as_str()));
+    set("dueAtMs", JsValue::from_f64(due.due_at_ms as f64));
+    set(
+        "hardDeadlineMs",
+        JsValue::from_f64(due.hard_deadline_ms as f64),
+    );
+    set("operations", JsValue::from_f64(due.operations as f64));
+    obj.into()
+}
+
+fn build_scope_summary(summary: &ScopeSummary) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("scopeId"),
+        &JsValue::from_str(&summary.scope_id.0),
+    )
+    .expect("scopeId");
+    let epochs = Array::new();
+    for epoch in &summary.epochs {
+        epochs.push(&BigInt::from(epoch.0).into());
+    }
+    Reflect::set(&obj, &JsValue::from_str("epochs"), &epochs.into()).expect("epochs");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("hasScopeKey"),
+        &JsValue::from_bool(summary.has_scope_key),
+    )
+    .expect("hasScopeKey");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("trustedSignerCount"),
+        &JsValue::from_f64(summary.trusted_signer_count as f64),
+    )
+    .expect("trustedSignerCount");
+    obj.into()
+}
+
+fn build_checkpoint(checkpoint: &KeyVaultCheckpoint) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("seq"),
+        &JsValue::from_f64(checkpoint.seq as f64),
+    )
+    .expect("seq");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("hash"),
+        &Uint8Array::from(checkpoint.hash.as_slice()).into(),
+    )
+    .expect("hash");
+    obj.into()
+}
+
+fn build_vault_stats(stats: &KeyVaultStats) -> JsValue {
+    let obj = Object::new();
+    let by_kind = Array::new();
+    for (kind, count) in &stats.records_by_kind {
+        let entry = Object::new();
+        Reflect::set(
+            &entry,
+            &JsValue::from_str("kind"),
+            &JsValue::from_f64(*kind as f64),
+        )
+        .expect("kind");
+        Reflect::set(
+            &entry,
+            &JsValue::from_str("count"),
+            &JsValue::from_f64(*count as f64),
+        )
+        .expect("count");
+        by_kind.push(&entry);
+    }
+    Reflect::set(&obj, &JsValue::from_str("recordsByKind"), &by_kind).expect("recordsByKind");
+    for (key, value) in [
+        ("encryptedBytes", stats.encrypted_bytes),
+        ("chainLength", stats.chain_length),
+        ("scopeCount", stats.scope_count),
+        ("scopeEpochCount", stats.scope_epoch_count),
+        ("resourceCount", stats.resource_count),
+    ] {
+        Reflect::set(
+            &obj,
+            &JsValue::from_str(key),
+            &JsValue::from_f64(value as f64),
+        )
+        .expect(key);
+    }
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("lastAppendMs"),
+        &stats
+            .last_append_ms
+            .map_or(JsValue::NULL, |ms| JsValue::from_f64(ms as f64)),
+    )
+    .expect("lastAppendMs");
+    obj.into()
+}
+
+fn build_record_metadata(record: &KeyVaultRecordMetadata) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("seq"),
+        &JsValue::from_f64(record.seq as f64),
+    )
+    .expect("seq");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("recordId"),
+        &JsValue::from_str(&record.record_id),
+    )
+    .expect("recordId");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("kind"),
+        &JsValue::from_f64(record.kind as f64),
+    )
+    .expect("kind");
+    let provenance = record.provenance.as_ref();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("createdAtMs"),
+        &provenance.map_or(JsValue::NULL, |p| JsValue::from_f64(p.created_at_ms as f64)),
+    )
+    .expect("createdAtMs");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("originDeviceId"),
+        &provenance
+            .and_then(|p| p.origin_device_id.as_ref())
+            .map_or(JsValue::NULL, |device_id| JsValue::from_str(&device_id.0)),
+    )
+    .expect("originDeviceId");
+    obj.into()
+}
+
+fn build_passphrase_strength(strength: &PassphraseStrength) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("score"),
+        &JsValue::from_f64(strength.score as f64),
+    )
+    .expect("score");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("guessesLog10"),
+        &JsValue::from_f64(strength.guesses_log10),
+    )
+    .expect("guessesLog10");
+    obj.into()
+}
+
+fn build_audit_entry(entry: &AuditEntryV1) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("seq"),
+        &JsValue::from_f64(entry.seq as f64),
+    )
+    .expect("seq");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("atMs"),
+        &JsValue::from_f64(entry.at_ms as f64),
+    )
+    .expect("atMs");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("kind"),
+        &JsValue::from_str(entry.kind.as_str()),
+    )
+    .expect("kind");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("detail"),
+        &JsValue::from_str(&entry.detail),
+    )
+    .expect("detail");
+    let signer = entry
+        .signer_device_id
+        .as_ref()
+        .map(|device_id| JsValue::from_str(&device_id.0))
+        .unwrap_or(JsValue::NULL);
+    Reflect::set(&obj, &JsValue::from_str("signerDeviceId"), &signer).expect("signerDeviceId");
+    obj.into()
+}
+
+fn build_audit_verify_report(report: &AuditVerifyReport) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("ok"),
+        &JsValue::from_bool(report.ok),
+    )
+    .expect("ok");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("entryCount"),
+        &JsValue::from_f64(report.entry_count as f64),
+    )
+    .expect("entryCount");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("unsignedCount"),
+        &JsValue::from_f64(report.unsigned_count as f64),
+    )
+    .expect("unsignedCount");
+    let first_invalid = report
+        .first_invalid_seq
+        .map(|seq| JsValue::from_f64(seq as f64))
+        .unwrap_or(JsValue::NULL);
+    Reflect::set(&obj, &JsValue::from_str("firstInvalidSeq"), &first_invalid)
+        .expect("firstInvalidSeq");
+    obj.into()
+}
+
+fn build_verify_keyvault_response(report: &VerifyKeyVaultResponse) -> JsValue {
+    let obj = Object::new();
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("ok"),
+        &JsValue::from_bool(report.ok),
+    )
+    .expect("ok");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("recordCount"),
+        &JsValue::from_f64(report.record_count as f64),
+    )
+    .expect("recordCount");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("headSeq"),
+        &JsValue::from_f64(report.head_seq as f64),
+    )
+    .expect("headSeq");
+    let first_invalid = report
+        .first_invalid_seq
+        .map(|seq| JsValue::from_f64(seq as f64))
+        .unwrap_or(JsValue::NULL);
+    Reflect::set(&obj, &JsValue::from_str("firstInvalidSeq"), &first_invalid)
+        .expect("firstInvalidSeq");
+    let problem = report
+        .problem
+        .as_deref()
+        .map(JsValue::from_str)
+        .unwrap_or(JsValue::NULL);
+    Reflect::set(&obj, &JsValue::from_str("problem"), &problem).expect("problem");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("headerRecordsMatch"),
+        &JsValue::from_bool(report.header_records_match),
+    )
+    .expect("headerRecordsMatch");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("headMarkerMatch"),
+        &JsValue::from_bool(report.head_marker_match),
+    )
+    .expect("headMarkerMatch");
+    obj.into()
+}
+
 fn session_kind_to_str(kind: SessionKind) -> &'static str {
     match kind {
         SessionKind::Normal => "normal",
@@ -745,36 +3108,44 @@ fn session_assurance_to_str(assurance: SessionAssurance) -> &'static str {
     match assurance {
         SessionAssurance::Passphrase => "passphrase",
         SessionAssurance::UserPresence => "userPresence",
+        SessionAssurance::DeviceShares => "deviceShares",
+        SessionAssurance::HardwareBacked => "hardwareBacked",
+        SessionAssurance::MultiFactor => "multiFactor",
     }
 }
 
+/// Forwards the core's [`ErrorReport`](mo_key_service_core::error::ErrorReport) field for field.
 fn to_js_error(error: KeyServiceError) -> JsValue {
+    let report = error.report();
     let obj = Object::new();
-    let code = error_code(&error);
-    Reflect::set(&obj, &JsValue::from_str("code"), &JsValue::from_str(code)).expect("error code");
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("code"),
+        &JsValue::from_str(report.code),
+    )
+    .expect("error code");
     Reflect::set(
         &obj,
         &JsValue::from_str("message"),
-        &JsValue::from_str(&error.to_string()),
+        &JsValue::from_str(&report.message),
     )
     .expect("error message");
-    obj.into()
-}
-
-fn error_code(error: &KeyServiceError) -> &'static str {
-    match error {
-        KeyServiceError::StorageError(_) => "StorageError",
-        KeyServiceError::InvalidCbor(_) => "InvalidCbor",
-        KeyServiceError::InvalidFormat(_) => "InvalidFormat",
-        KeyServiceError::CryptoError(_) => "CryptoError",
-        KeyServiceError::SessionInvalid => "SessionInvalid",
-        KeyServiceError::StepUpRequired => "StepUpRequired",
-        KeyServiceError::UntrustedSigner => "UntrustedSigner",
-        KeyServiceError::UnknownScope => "UnknownScope",
-        KeyServiceError::UnknownHandle => "UnknownHandle",
-        KeyServiceError::ResourceKeyMissing => "ResourceKeyMissing",
-        KeyServiceError::ScopeKeyMissing => "ScopeKeyMissing",
-        KeyServiceError::FingerprintMismatch => "FingerprintMismatch",
-        KeyServiceError::SignerFingerprintRequired => "SignerFingerprintRequired",
+    Reflect::set(
+        &obj,
+        &JsValue::from_str("retryable"),
+        &JsValue::from_bool(report.retryable),
+    )
+    .expect("error retryable");
+    if !report.context.is_empty() {
+        let context = Object::new();
+        for (name, detail) in &report.context {
+            let value = match detail {
+                ErrorDetail::Text(text) => JsValue::from_str(text),
+                ErrorDetail::Uint(value) => JsValue::from_f64(*value as f64),
+            };
+            Reflect::set(&context, &JsValue::from_str(name), &value).expect("error context");
+        }
+        Reflect::set(&obj, &JsValue::from_str("context"), &context).expect("error context");
     }
+    obj.into()
 }
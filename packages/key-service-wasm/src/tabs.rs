@@ -0,0 +1,254 @@
+//! Cross-tab coordination over a `BroadcastChannel` (`tabs` feature).
+//!
+//! Tabs of one origin join a named channel and exchange small JS messages tagged with a `type`
+//! and the sender's `tabId`:
+//! - `lockAll`: the sender locked everything; every receiver locks all its sessions too.
+//! - `writes`: the sender persisted writes to `vault`; receivers invalidate that vault and
+//!   call the host's `onRemoteWrites(vault)` so it reloads the entries.
+//! - `forwardWrites`: a follower hands its drained storage writes to the leader.
+//! - `heartbeat` / `leave`: liveness for leader election. The leader is the live tab with the
+//!   smallest id; a peer is live until `leaseMs` passes without a message from it.
+
+use js_sys::{Array, Object, Reflect};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{BroadcastChannel, MessageEvent};
+
+use crate::{build_storage_writes, KeyServiceWasm, VaultInstance};
+
+const DEFAULT_LEASE_MS: f64 = 5_000.0;
+
+type Vaults = Rc<RefCell<HashMap<String, VaultInstance>>>;
+
+struct TabState {
+    tab_id: String,
+    lease_ms: f64,
+    /// Last time (ms) each peer was heard from.
+    peers: HashMap<String, f64>,
+    /// `(vault, writes)` forwarded by followers while this tab was the leader.
+    forwarded: Vec<(String, JsValue)>,
+    on_remote_writes: Option<js_sys::Function>,
+}
+
+impl TabState {
+    fn is_leader(&mut self, now: f64) -> bool {
+        let lease_ms = self.lease_ms;
+        self.peers.retain(|_, seen| now - *seen <= lease_ms);
+        self.peers.keys().all(|peer| *peer > self.tab_id)
+    }
+}
+
+/// One tab's membership in a coordination channel; see the module docs for the protocol.
+#[wasm_bindgen]
+pub struct TabCoordinator {
+    channel: BroadcastChannel,
+    state: Rc<RefCell<TabState>>,
+    vaults: Vaults,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+#[wasm_bindgen]
+impl KeyServiceWasm {
+    /// Joins the `channelName` BroadcastChannel as `tabId`, which must be unique per tab (e.g.
+    /// `crypto.randomUUID()`). `onRemoteWrites(vault)` runs after another tab announces
+    /// persisted writes to `vault`; the host reloads that vault's entries with `loadStorage`.
+    #[wasm_bindgen(js_name = "coordinateTabs")]
+    pub fn coordinate_tabs(
+        &self,
+        channel_name: String,
+        tab_id: String,
+        on_remote_writes: Option<js_sys::Function>,
+    ) -> Result<TabCoordinator, JsValue> {
+        if tab_id.is_empty() {
+            return Err(JsValue::from_str("tab id must not be empty"));
+        }
+        let channel = BroadcastChannel::new(&channel_name)?;
+        let state = Rc::new(RefCell::new(TabState {
+            tab_id,
+            lease_ms: DEFAULT_LEASE_MS,
+            peers: HashMap::new(),
+            forwarded: Vec::new(),
+            on_remote_writes,
+        }));
+        let on_message = {
+            let state = state.clone();
+            let vaults = self.vaults.clone();
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                handle_message(&state, &vaults, &event.data());
+            })
+        };
+        channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        let coordinator = TabCoordinator {
+            channel,
+            state,
+            vaults: self.vaults.clone(),
+            _on_message: on_message,
+        };
+        coordinator.post("heartbeat", &[])?;
+        Ok(coordinator)
+    }
+}
+
+#[wasm_bindgen]
+impl TabCoordinator {
+    #[wasm_bindgen(js_name = "tabId")]
+    pub fn tab_id(&self) -> String {
+        self.state.borrow().tab_id.clone()
+    }
+
+    /// How long a silent peer still counts as live (default 5000 ms).
+    #[wasm_bindgen(js_name = "setLeaseMs")]
+    pub fn set_lease_ms(&self, lease_ms: f64) -> Result<(), JsValue> {
+        if !lease_ms.is_finite() || lease_ms <= 0.0 {
+            return Err(JsValue::from_str("lease must be a positive number of ms"));
+        }
+        self.state.borrow_mut().lease_ms = lease_ms;
+        Ok(())
+    }
+
+    /// Sends a heartbeat and returns whether this tab leads. Call it more often than the lease.
+    pub fn tick(&self) -> Result<bool, JsValue> {
+        self.post("heartbeat", &[])?;
+        Ok(self.is_leader())
+    }
+
+    #[wasm_bindgen(js_name = "isLeader")]
+    pub fn is_leader(&self) -> bool {
+        self.state.borrow_mut().is_leader(js_sys::Date::now())
+    }
+
+    /// Locks every session in this tab's vaults and asks every other tab to do the same.
+    #[wasm_bindgen(js_name = "lockAll")]
+    pub fn lock_all(&self) -> Result<u32, JsValue> {
+        let locked = lock_all_vaults(&self.vaults);
+        self.post("lockAll", &[])?;
+        Ok(locked)
+    }
+
+    /// Tells other tabs that writes to `vault` were persisted, so they reload it.
+    #[wasm_bindgen(js_name = "announceWrites")]
+    pub fn announce_writes(&self, vault: String) -> Result<(), JsValue> {
+        self.post("writes", &[("vault", JsValue::from_str(&vault))])
+    }
+
+    /// Drains pending storage writes from every vault of this tab. The leader returns them,
+    /// together with writes forwarded by followers, as `[{ vault, writes }]` for the host to
+    /// persist and then `announceWrites`. A follower forwards its writes to the leader and gets
+    /// an empty array; writes forwarded to a leader that closes before draining are lost.
+    #[wasm_bindgen(js_name = "drainWrites")]
+    pub fn drain_writes(&self) -> Result<Array, JsValue> {
+        let mut drained = Vec::new();
+        {
+            let vaults = self.vaults.borrow();
+            let mut namespaces = vaults.keys().cloned().collect::<Vec<_>>();
+            namespaces.sort();
+            for namespace in namespaces {
+                let entries = vaults[&namespace].storage.drain_pending();
+                if !entries.is_empty() {
+                    drained.push((namespace, JsValue::from(build_storage_writes(entries))));
+                }
+            }
+        }
+        let out = Array::new();
+        if !self.is_leader() {
+            for (vault, writes) in drained {
+                self.post(
+                    "forwardWrites",
+                    &[("vault", JsValue::from_str(&vault)), ("writes", writes)],
+                )?;
+            }
+            return Ok(out);
+        }
+        let forwarded = std::mem::take(&mut self.state.borrow_mut().forwarded);
+        for (vault, writes) in drained.into_iter().chain(forwarded) {
+            let obj = Object::new();
+            Reflect::set(
+                &obj,
+                &JsValue::from_str("vault"),
+                &JsValue::from_str(&vault),
+            )
+            .expect("vault");
+            Reflect::set(&obj, &JsValue::from_str("writes"), &writes).expect("writes");
+            out.push(&obj);
+        }
+        Ok(out)
+    }
+
+    /// Tells other tabs this one is gone and leaves the channel.
+    pub fn close(&self) -> Result<(), JsValue> {
+        self.post("leave", &[])?;
+        self.channel.set_onmessage(None);
+        self.channel.close();
+        Ok(())
+    }
+}
+
+impl TabCoordinator {
+    fn post(&self, kind: &str, fields: &[(&str, JsValue)]) -> Result<(), JsValue> {
+        let obj = Object::new();
+        let set = |key: &str, value: &JsValue| {
+            Reflect::set(&obj, &JsValue::from_str(key), value).expect(key);
+        };
+        set("type", &JsValue::from_str(kind));
+        set("tabId", &JsValue::from_str(&self.state.borrow().tab_id));
+        for (key, value) in fields {
+            set(key, value);
+        }
+        self.channel.post_message(&obj)
+    }
+}
+
+fn lock_all_vaults(vaults: &Vaults) -> u32 {
+    vaults
+        .borrow_mut()
+        .values_mut()
+        .map(|vault| vault.service.lock_all() as u32)
+        .sum()
+}
+
+fn handle_message(state: &Rc<RefCell<TabState>>, vaults: &Vaults, data: &JsValue) {
+    let field =
+        |key: &str| Reflect::get(data, &JsValue::from_str(key)).unwrap_or(JsValue::UNDEFINED);
+    let (Some(kind), Some(from)) = (field("type").as_string(), field("tabId").as_string()) else {
+        return;
+    };
+    let now = js_sys::Date::now();
+    let mut tab = state.borrow_mut();
+    if from == tab.tab_id {
+        return;
+    }
+    if kind == "leave" {
+        tab.peers.remove(&from);
+        return;
+    }
+    tab.peers.insert(from, now);
+    match kind.as_str() {
+        "lockAll" => {
+            drop(tab);
+            lock_all_vaults(vaults);
+        }
+        "writes" => {
+            let Some(vault) = field("vault").as_string() else {
+                return;
+            };
+            let callback = tab.on_remote_writes.clone();
+            drop(tab);
+            if let Some(instance) = vaults.borrow_mut().get_mut(&vault) {
+                instance.service.invalidate();
+            }
+            if let Some(callback) = callback {
+                let _ = callback.call1(&JsValue::NULL, &JsValue::from_str(&vault));
+            }
+        }
+        "forwardWrites" => {
+            if let Some(vault) = field("vault").as_string() {
+                if tab.is_leader(now) {
+                    tab.forwarded.push((vault, field("writes")));
+                }
+            }
+        }
+        _ => {}
+    }
+}
@@ -6,6 +6,7 @@ export type {
   SessionId,
   KeyHandle,
   ChangePassphraseRequest,
+  ChangePassphraseResponse,
   CloseHandleRequest,
   CreateVaultRequest,
   DecryptRequest,
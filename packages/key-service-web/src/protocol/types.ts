@@ -2,6 +2,7 @@ export type {
   AeadId,
   Brand,
   ChangePassphraseRequest,
+  ChangePassphraseResponse,
   CloseHandleRequest,
   CreateVaultRequest,
   DecryptRequest,
@@ -16,6 +17,7 @@ export type {
   IngestKeyEnvelopeResponse,
   IngestScopeStateRequest,
   IngestScopeStateResponse,
+  IngestWarning,
   KdfParams,
   KemCiphersuiteId,
   KeyHandle,
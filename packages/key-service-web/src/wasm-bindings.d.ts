@@ -3,22 +3,158 @@ declare module '@mo/key-service-wasm' {
 
   export default function init(moduleOrPath?: WasmInitInput): Promise<void>;
 
+  export interface KeyServiceWasmOptions {
+    maxCborBytes?: number;
+    maxCborDepth?: number;
+    maxCborItems?: number;
+    keyWrapAlg?: 'aes-gcm' | 'aes-kwp';
+    acceptUnboundSignatures?: boolean;
+    maxIdBytes?: number;
+    idCharset?: 'noControl' | 'urlSafe';
+    maxClockRewindMs?: number;
+    maxClockJumpMs?: number;
+    opaqueContext?: Uint8Array;
+    opaqueServerIdentity?: Uint8Array;
+    opaqueClientIdentity?: Uint8Array;
+    opaqueKsf?: { memoryKib: number; iterations: number; parallelism: number };
+  }
+
+  export class OpaqueRegistration {
+    readonly request: Uint8Array;
+  }
+
+  export class OpaqueLogin {
+    readonly ke1: Uint8Array;
+  }
+
+  export class TabCoordinator {
+    tabId(): string;
+    setLeaseMs(leaseMs: number): void;
+    tick(): boolean;
+    isLeader(): boolean;
+    lockAll(): number;
+    announceWrites(vault: string): void;
+    drainWrites(): Array<{
+      vault: string;
+      writes: Array<{ namespace: string; key: string; value: Uint8Array }>;
+    }>;
+    close(): void;
+  }
+
   export class KeyServiceWasm {
-    constructor();
+    constructor(options?: KeyServiceWasmOptions);
+    static forVault(namespace: string, options?: KeyServiceWasmOptions): KeyServiceWasm;
+    switchVault(namespace: string): void;
+    activeVault(): string;
+    listVaults(): string[];
+    removeVault(namespace: string): boolean;
+    static estimatePassphraseStrength(passphrase: Uint8Array): unknown;
+    static errorCodes(): string[];
+    /** Only in builds with the `tabs` feature. */
+    coordinateTabs(
+      channelName: string,
+      tabId: string,
+      onRemoteWrites?: (vault: string) => void
+    ): TabCoordinator;
+    onSessionEvent(
+      callback: (event: {
+        type: 'expiringSoon' | 'expired' | 'locked';
+        sessionId: string;
+        expiresAtMs?: number;
+        vault: string;
+      }) => void
+    ): void;
+    setSessionEventsCallback(
+      callback: (event: {
+        type: 'expiringSoon' | 'expired' | 'locked';
+        sessionId: string;
+        expiresAtMs?: number;
+        vault: string;
+      }) => void
+    ): void;
+    setAutoLockTimer(callback: () => void): void;
+    onTimer(): void;
     loadStorage(entries: unknown): void;
     drainStorageWrites(): unknown;
+    applyExternalWrites(entries: unknown): void;
+    invalidate(): void;
+    reloadFromStorage(sessionId: string): unknown;
     createVault(userId: string, passphraseUtf8: Uint8Array, kdfParams: unknown): void;
-    unlockPassphrase(passphraseUtf8: Uint8Array): unknown;
+    unlockPassphrase(
+      passphraseUtf8: Uint8Array,
+      onProgress?: (donePasses: number, totalPasses: number) => void
+    ): unknown;
+    unlockPassphraseCancellable(passphraseUtf8: Uint8Array, isCancelled: () => boolean): unknown;
+    unlockPassphraseReadOnly(passphraseUtf8: Uint8Array): unknown;
+    opaqueRegistrationStart(passwordUtf8: Uint8Array): OpaqueRegistration;
+    createVaultOpaque(
+      userId: string,
+      registration: OpaqueRegistration,
+      response: Uint8Array,
+      kdfParams: unknown
+    ): Uint8Array;
+    opaqueLoginStart(passwordUtf8: Uint8Array): OpaqueLogin;
+    unlockOpaque(login: OpaqueLogin, ke2: Uint8Array): unknown;
     unlockUserPresence(userPresenceSecret: Uint8Array): unknown;
+    unlockUserPresenceReadOnly(userPresenceSecret: Uint8Array): unknown;
+    unlockMultiFactor(passphraseUtf8: Uint8Array, userPresenceSecret: Uint8Array): unknown;
+    unlockWithSalvage(passphraseUtf8: Uint8Array): unknown;
+    unlockWithSalvageRechaining(passphraseUtf8: Uint8Array): unknown;
+    repairKeyVault(sessionId: string): unknown;
     stepUp(sessionId: string, passphraseUtf8: Uint8Array): unknown;
+    stepUpMultiFactor(
+      sessionId: string,
+      passphraseUtf8: Uint8Array,
+      userPresenceSecret: Uint8Array
+    ): unknown;
+    verifyPassphrase(passphraseUtf8: Uint8Array): boolean;
     getUserPresenceUnlockInfo(): unknown;
+    getVaultInfo(): unknown;
+    healthCheck(): unknown;
+    exportDiagnostics(): unknown;
+    exportDiagnosticsCbor(): Uint8Array;
     renewSession(sessionId: string): unknown;
+    sweepExpiredSessions(): number;
     lock(sessionId: string): void;
+    lockAll(): number;
+    listSessions(): Array<{
+      sessionId: string;
+      vault: string;
+      kind: 'normal' | 'stepUp';
+      assurance: 'passphrase' | 'userPresence' | 'deviceShares' | 'multiFactor' | 'hardwareBacked';
+      issuedAtMs: number;
+      expiresAtMs: number;
+      readOnly: boolean;
+      handleCount: number;
+    }>;
     exportKeyVault(sessionId: string): unknown;
+    exportKeyVaultCompressed(sessionId: string, compression: 'deflate' | 'zstd'): Uint8Array;
+    exportKeyVaultFiltered(sessionId: string, kinds: BigUint64Array): Uint8Array;
+    exportKeyVaultChunked(
+      sessionId: string,
+      chunkLen: number,
+      onChunk: (chunk: Uint8Array) => void
+    ): void;
     importKeyVault(sessionId: string, blob: Uint8Array): void;
-    changePassphrase(sessionId: string, newPassphraseUtf8: Uint8Array): void;
+    importKeyVaultCancellable(sessionId: string, blob: Uint8Array, isCancelled: () => boolean): void;
+    importKeyVaultInit(sessionId: string): string;
+    importKeyVaultPush(sessionId: string, streamId: string, chunk: Uint8Array): void;
+    importKeyVaultFinish(sessionId: string, streamId: string): void;
+    verifyKeyvault(sessionId: string): unknown;
+    listRecordsMetadata(sessionId: string): unknown;
+    writeCheckpoint(sessionId: string): unknown;
+    latestCheckpoint(sessionId: string): unknown;
+    vaultStats(sessionId: string): unknown;
+    changePassphrase(sessionId: string, newPassphraseUtf8: Uint8Array): { userPresenceDisabled: boolean };
+    changePassphraseKeepingUserPresence(
+      sessionId: string,
+      newPassphraseUtf8: Uint8Array,
+      userPresenceSecret: Uint8Array
+    ): { userPresenceDisabled: boolean };
     storeAppMasterKey(sessionId: string, masterKey: Uint8Array): void;
     getAppMasterKey(sessionId: string): unknown;
+    putVaultBlob(sessionId: string, label: string, bytes: Uint8Array): void;
+    getVaultBlob(sessionId: string, label: string): Uint8Array | null;
     enableUserPresenceUnlock(sessionId: string, credentialId: Uint8Array, userPresenceSecret: Uint8Array): void;
     disableUserPresenceUnlock(sessionId: string): void;
     ingestScopeState(
@@ -27,12 +163,50 @@ declare module '@mo/key-service-wasm' {
       expectedOwnerSignerFingerprint: string | null
     ): unknown;
     ingestKeyEnvelope(sessionId: string, keyEnvelopeCbor: Uint8Array): unknown;
+    ingestDelegation(sessionId: string, delegationCbor: Uint8Array): unknown;
     openScope(sessionId: string, scopeId: string, scopeEpoch: bigint): unknown;
+    listScopes(sessionId: string): unknown;
+    setRotationPolicy(
+      sessionId: string,
+      scopeId: string,
+      maxKeyAgeMs: bigint | undefined,
+      maxOperations: bigint | undefined,
+      graceMs: bigint
+    ): void;
+    dueRotations(sessionId: string): unknown;
     openResource(sessionId: string, scopeKeyHandle: string, grantCbor: Uint8Array): unknown;
+    openResourceWithOps(
+      sessionId: string,
+      scopeKeyHandle: string,
+      grantCbor: Uint8Array,
+      ops: Array<'encrypt' | 'decrypt'>
+    ): string;
+    reissueGrant(sessionId: string, grantCbor: Uint8Array, scopeKeyHandle: string): unknown;
     closeHandle(sessionId: string, keyHandle: string): void;
     encrypt(sessionId: string, resourceKeyHandle: string, aad: Uint8Array, plaintext: Uint8Array): unknown;
     decrypt(sessionId: string, resourceKeyHandle: string, aad: Uint8Array, ciphertext: Uint8Array): unknown;
+    encryptInit(sessionId: string, resourceKeyHandle: string, aad: Uint8Array): unknown;
+    encryptPush(sessionId: string, streamId: string, chunk: Uint8Array): Uint8Array;
+    encryptFinish(sessionId: string, streamId: string, chunk: Uint8Array): Uint8Array;
+    decryptInit(sessionId: string, resourceKeyHandle: string, aad: Uint8Array, header: Uint8Array): string;
+    decryptPush(sessionId: string, streamId: string, chunk: Uint8Array): Uint8Array;
+    decryptFinish(sessionId: string, streamId: string, chunk: Uint8Array): Uint8Array;
+    mintCapability(
+      sessionId: string,
+      resourceKeyHandle: string,
+      aadPrefix: Uint8Array,
+      ops: Array<'encrypt' | 'decrypt'>,
+      ttlMs: bigint
+    ): unknown;
+    redeemCapability(sessionId: string, token: Uint8Array): unknown;
+    deriveSubkey(sessionId: string, resourceKeyHandle: string, path: Uint8Array[], len: number): Uint8Array;
+    enableResourceRatchet(sessionId: string, resourceKeyHandle: string): void;
     sign(sessionId: string, data: Uint8Array): unknown;
+    signFormat(
+      sessionId: string,
+      format: 'scopeState' | 'resourceGrant' | 'keyEnvelope' | 'signingDelegation',
+      toBeSigned: Uint8Array
+    ): unknown;
     verify(
       scopeId: string,
       signerDeviceId: string,
@@ -40,5 +214,55 @@ declare module '@mo/key-service-wasm' {
       signature: Uint8Array,
       ciphersuite: string
     ): unknown;
+    getUserPublicKey(sessionId: string): {
+      kem: string;
+      publicBytes: Uint8Array;
+      fingerprint: string;
+      xwingPublicBytes: Uint8Array;
+    };
+    getDevicePublicKeys(sessionId: string): Array<{
+      deviceId: string;
+      sigSuite: string;
+      ed25519Pub: Uint8Array;
+      mldsaPub: Uint8Array;
+      fingerprint: string;
+    }>;
+    readAuditLog(sessionId: string): unknown;
+    verifyAuditLog(sessionId: string): unknown;
+    addContact(sessionId: string, userId: string, publicBytes: Uint8Array, expectedFingerprint: string): unknown;
+    removeContact(sessionId: string, userId: string): boolean;
+    listContacts(sessionId: string): unknown;
+    sealKeyEnvelope(sessionId: string, scopeId: string, scopeEpoch: bigint, recipientUserId: string): unknown;
   }
+
+  export function aadKeyvaultKeywrapV1(vaultId: string, userId: string, kdfParams: unknown, aead: string): Uint8Array;
+  export function aadKeyvaultRecordV1(vaultId: string, userId: string, aead: string, recordId: string): Uint8Array;
+  export function aadKeyvaultHeadMarkerV1(vaultId: string, userId: string, aead: string): Uint8Array;
+  export function aadKeyEnvelopeWrapV1(
+    scopeId: string,
+    scopeEpoch: bigint,
+    recipientUserId: string,
+    scopeStateRef: Uint8Array,
+    kem: string,
+    aead: string,
+    recipientUkPubFingerprint?: Uint8Array
+  ): Uint8Array;
+  export function aadKeyEnvelopePrekeyWrapV1(
+    scopeId: string,
+    scopeEpoch: bigint,
+    recipientUserId: string,
+    scopeStateRef: Uint8Array,
+    kem: string,
+    aead: string,
+    recipientUkPubFingerprint: Uint8Array,
+    prekeyId: bigint
+  ): Uint8Array;
+  export function aadResourceGrantWrapV1(
+    scopeId: string,
+    resourceId: string,
+    scopeEpoch: bigint,
+    resourceKeyId: string,
+    aead: string
+  ): Uint8Array;
+  export function aadUserPresenceWrapV1(vaultId: string, userId: string, kdfParams: unknown, aead: string): Uint8Array;
 }
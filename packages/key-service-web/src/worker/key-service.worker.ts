@@ -12,13 +12,17 @@ import {
   type KeyHandle,
   type ScopeEpoch,
   type ScopeId,
+  type SessionAssurance,
   type SessionId,
   type UnlockResponse,
   type StepUpResponse,
+  type ChangePassphraseResponse,
   type RenewSessionResponse,
   type GetUserPresenceUnlockInfoResponse,
   type IngestScopeStateResponse,
   type IngestKeyEnvelopeResponse,
+  type IngestWarning,
+  type DeviceId,
   type SignResponse,
   type WorkerEnvelope,
   type WorkerHello,
@@ -289,13 +293,20 @@ async function handleRequest(
     }
     case 'changePassphrase': {
       const passphraseUtf8 = request.payload.newPassphraseUtf8;
+      const userPresenceSecret = request.payload.userPresenceSecret;
+      let response: ChangePassphraseResponse;
       try {
-        service.changePassphrase(request.payload.sessionId, passphraseUtf8);
+        response = parseChangePassphraseResponse(
+          userPresenceSecret
+            ? service.changePassphraseKeepingUserPresence(request.payload.sessionId, passphraseUtf8, userPresenceSecret)
+            : service.changePassphrase(request.payload.sessionId, passphraseUtf8)
+        );
       } finally {
         passphraseUtf8.fill(0);
+        userPresenceSecret?.fill(0);
       }
       await persistWrites(runtime);
-      return { type: 'changePassphrase', payload: {} };
+      return { type: 'changePassphrase', payload: response };
     }
     case 'storeAppMasterKey': {
       const masterKey = request.payload.masterKey;
@@ -634,6 +645,13 @@ function parseRenewResponse(value: unknown): RenewSessionResponse {
   };
 }
 
+function parseChangePassphraseResponse(value: unknown): ChangePassphraseResponse {
+  if (!isRecord(value)) throw new Error('Invalid changePassphrase response');
+  return {
+    userPresenceDisabled: requireBoolean(value.userPresenceDisabled, 'userPresenceDisabled'),
+  };
+}
+
 function parseUserPresenceInfo(value: unknown): GetUserPresenceUnlockInfoResponse {
   if (!isRecord(value)) throw new Error('Invalid user presence response');
   const credentialId = value.credentialId;
@@ -651,14 +669,43 @@ function parseIngestScopeStateResponse(value: unknown): IngestScopeStateResponse
   return {
     scopeId: asScopeId(requireString(value.scopeId, 'scopeId')),
     scopeStateRef: requireString(value.scopeStateRef, 'scopeStateRef'),
+    warnings: parseIngestWarnings(value.warnings),
   };
 }
 
+function parseIngestWarnings(value: unknown): IngestWarning[] {
+  if (!Array.isArray(value)) throw new Error('Invalid warnings');
+  return value.map((item: unknown): IngestWarning => {
+    if (!isRecord(item)) throw new Error('Invalid warning');
+    const message = requireString(item.message, 'message');
+    switch (item.code) {
+      case 'FingerprintPinned':
+      case 'SignerPreviouslyUnseen':
+        return {
+          code: item.code,
+          message,
+          deviceId: asDeviceId(requireString(item.deviceId, 'deviceId')),
+        };
+      case 'OlderEpoch':
+        return {
+          code: item.code,
+          message,
+          scopeEpoch: asScopeEpoch(requireBigint(item.scopeEpoch, 'scopeEpoch')),
+          latestEpoch: asScopeEpoch(requireBigint(item.latestEpoch, 'latestEpoch')),
+        };
+      default:
+        throw new Error('Invalid warning code');
+    }
+  });
+}
+
 function parseIngestKeyEnvelopeResponse(value: unknown): IngestKeyEnvelopeResponse {
   if (!isRecord(value)) throw new Error('Invalid ingestKeyEnvelope response');
   return {
     scopeId: asScopeId(requireString(value.scopeId, 'scopeId')),
     scopeEpoch: asScopeEpoch(requireBigint(value.scopeEpoch, 'scopeEpoch')),
+    isNew: requireBoolean(value.isNew, 'isNew'),
+    warnings: parseIngestWarnings(value.warnings),
   };
 }
 
@@ -695,9 +742,17 @@ function requireSessionKind(value: unknown, field: string): 'normal' | 'stepUp'
   throw new Error(`Invalid ${field}`);
 }
 
-function requireSessionAssurance(value: unknown, field: string): 'passphrase' | 'userPresence' {
-  if (value === 'passphrase') return value;
-  if (value === 'webauthnPrf' || value === 'userPresence') return 'userPresence';
+function requireSessionAssurance(value: unknown, field: string): SessionAssurance {
+  if (value === 'webauthnPrf') return 'userPresence';
+  if (
+    value === 'passphrase' ||
+    value === 'userPresence' ||
+    value === 'deviceShares' ||
+    value === 'multiFactor' ||
+    value === 'hardwareBacked'
+  ) {
+    return value;
+  }
   throw new Error(`Invalid ${field}`);
 }
 
@@ -721,6 +776,11 @@ function asScopeId(value: string): ScopeId {
   return value as ScopeId;
 }
 
+function asDeviceId(value: string): DeviceId {
+  if (value.length === 0) throw new Error('Invalid deviceId');
+  return value as DeviceId;
+}
+
 function asScopeEpoch(value: bigint): ScopeEpoch {
   if (value < 0n) throw new Error('Invalid scopeEpoch');
   return value as ScopeEpoch;